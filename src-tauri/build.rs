@@ -1,3 +1,24 @@
 fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
     tauri_build::build()
 }
+
+// Types and the service trait for proto/labelgood.proto, compiled with protox
+// so building doesn't need protoc installed
+#[cfg(feature = "grpc")]
+fn grpc() {
+    use prost::Message;
+
+    println!("cargo:rerun-if-changed=proto/labelgood.proto");
+    let descriptors = protox::compile(["labelgood.proto"], ["proto"]).expect("Failed to compile proto/labelgood.proto");
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").expect("OUT_DIR not set"));
+    let descriptor_path = out_dir.join("labelgood_descriptor.bin");
+    std::fs::write(&descriptor_path, descriptors.encode_to_vec()).expect("Failed to write the file descriptor set");
+    tonic_build::configure()
+        .build_client(false)
+        .file_descriptor_set_path(&descriptor_path)
+        .skip_protoc_run()
+        .compile_protos(&["labelgood.proto"], &["proto"])
+        .expect("Failed to generate the gRPC service");
+}
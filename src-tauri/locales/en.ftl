@@ -0,0 +1,136 @@
+# Messages the backend generates. Arguments are filled in by the backend;
+# sizes are in millimetres.
+
+## Print results
+
+printed-to = Printed to { $printer } with { $command }
+virtual-print-saved = Saved to { $path }
+print-failed = Failed to print with { $command }: { $error }
+sent-to = Sent to { $printer }
+sent-to-job = Sent to { $printer } as job { $job }
+job-held = { $printer } is unreachable; the job is held and prints when it's back
+
+## Jobs
+
+job-cancelled = Job { $job } was cancelled
+job-not-running = Job { $job } isn't running
+operation-not-running = Operation { $operation } isn't running
+command-timed-out = { $program } did not finish within { $seconds } seconds
+duplicate-print = This label was already printed at { $printed_at }; confirm to print it again
+quota-exceeded = The daily quota of { $limit } labels is used up: { $remaining } left today, this job needs { $labels }
+audit-unavailable = The audit log can't record this job, so it isn't printed: { $error }
+audit-write-failed = The labels were printed, but the audit log couldn't record the job: { $error }
+
+## Printer authentication
+
+auth-required = { $printer } requires a username and password
+auth-rejected = { $printer } rejected the stored username and password
+
+## Label sizes
+
+invalid-label-size = Invalid label size { $width }x{ $height }mm: both sides must be positive
+label-too-large = Label size { $width }x{ $height }mm is larger than the { $max }mm maximum
+
+## Label images sent to print
+
+image-not-base64 = Image data URL is not base64 encoded
+image-not-png-type = Expected a PNG image, got { $type }
+image-missing = No image data was sent
+image-bad-base64 = Failed to decode base64 image: { $error }
+image-not-png = Image data is not a PNG
+upload-unknown = Unknown upload: { $id }
+upload-not-png = Upload { $id } is not a PNG ({ $bytes } bytes received)
+upload-too-large = Upload { $id } is larger than { $max } bytes
+upload-not-binary = Upload chunks must be sent as binary data
+
+## Kiosk mode and operators
+
+kiosk-command-refused = { $command } isn't available in kiosk mode
+kiosk-wrong-pin = Wrong PIN
+kiosk-locked-out = Too many wrong PINs; try again in { $seconds } seconds
+operator-unknown = { $name } isn't a configured operator
+operator-list-empty = No operators are configured; sign in by badge or ask an administrator
+operator-badge-unknown = No operator has badge "{ $code }"
+
+## Web shop orders
+
+orders-no-shop = No web shop is configured
+order-not-open = Order { $order } is no longer open
+orders-nothing-to-print = The selected orders have nothing to print
+
+## Printer connections
+
+ipp-credentials-cleartext = { $printer } asks for a password, which would be sent unencrypted over { $uri }; use its ipps:// address instead
+unsupported-file-type = Unsupported file type: .{ $extension }
+
+## Missing programs
+
+imagemagick-missing =
+    ImageMagick not found: { $error }. Please install ImageMagick:
+    - Fedora: sudo dnf install ImageMagick
+    - Ubuntu/Debian: sudo apt install imagemagick
+    - Arch: sudo pacman -S imagemagick
+
+imagemagick-pdf-blocked =
+    ImageMagick's security policy forbids reading and writing PDF files. In { $policy }, change
+    <policy domain="coder" rights="none" pattern="PDF" /> to rights="read|write", or install GraphicsMagick.
+
+rembg-missing =
+    rembg not found: { $error }. Removing backgrounds with a model needs rembg:
+    - pip install "rembg[cli]"
+
+hunspell-missing =
+    Hunspell not found: { $error }. Spell checking needs Hunspell and a dictionary for the language:
+    - Fedora: sudo dnf install hunspell hunspell-en
+    - Ubuntu/Debian: sudo apt install hunspell hunspell-en-us
+    - Arch: sudo pacman -S hunspell hunspell-en_us
+
+tesseract-missing =
+    Tesseract not found: { $error }. Reading text from label photos needs Tesseract:
+    - Fedora: sudo dnf install tesseract
+    - Ubuntu/Debian: sudo apt install tesseract-ocr
+    - Arch: sudo pacman -S tesseract tesseract-data-eng
+
+scanimage-missing =
+    scanimage not found: { $error }. Scanning needs SANE:
+    - Fedora: sudo dnf install sane-backends
+    - Ubuntu/Debian: sudo apt install sane-utils
+    - Arch: sudo pacman -S sane
+
+## Printer media, checked while designing
+
+media-too-wide = The { $size } label is wider than the widest media { $printer } offers ({ $max }mm)
+media-outside-printable-area = { $printer } can only print { $printable }mm across; the edges of the { $size } label will be cut off
+media-too-short = The { $size } label is shorter than the { $min }mm { $printer } can feed
+media-too-long = The { $size } label is longer than the { $max }mm { $printer } can print
+media-no-match = { $printer } has no { $size } media and doesn't take custom sizes
+
+## Media left on the roll
+
+media-low = { $printer } has about { $remaining } left on its roll, not enough for this job's { $needed }
+media-mm = { $amount }mm
+media-labels = { $amount ->
+    [one] { $amount } label
+   *[other] { $amount } labels
+}
+
+## Tape colours
+
+tape-low-contrast = { $ink } ink barely stands out on { $tape } tape
+tape-element-too-light = Element { $id } is drawn in { $color }, too light to print; it won't show on the { $tape } tape
+tape-light-areas = Parts of the label are in colours too light to print; they won't show on the { $tape } tape
+
+## Nutrition declaration
+
+nutrition-title = Nutrition declaration
+nutrition-per = per { $amount }
+nutrition-reference-intake = RI*
+nutrition-reference-intake-note = *Reference intake of an average adult (8400 kJ/2000 kcal)
+nutrition-energy = Energy
+nutrition-fat = Fat
+nutrition-saturates = of which saturates
+nutrition-carbohydrate = Carbohydrate
+nutrition-sugars = of which sugars
+nutrition-fibre = Fibre
+nutrition-protein = Protein
+nutrition-salt = Salt
@@ -0,0 +1,136 @@
+# Meldingen die de backend genereert. Argumenten vult de backend in;
+# formaten zijn in millimeters.
+
+## Afdrukresultaten
+
+printed-to = Afgedrukt op { $printer } met { $command }
+virtual-print-saved = Opgeslagen in { $path }
+print-failed = Afdrukken met { $command } mislukt: { $error }
+sent-to = Verzonden naar { $printer }
+sent-to-job = Verzonden naar { $printer } als taak { $job }
+job-held = { $printer } is onbereikbaar; de taak wordt vastgehouden en afgedrukt zodra de printer terug is
+
+## Taken
+
+job-cancelled = Taak { $job } is geannuleerd
+job-not-running = Taak { $job } is niet actief
+operation-not-running = Bewerking { $operation } is niet actief
+command-timed-out = { $program } was niet binnen { $seconds } seconden klaar
+duplicate-print = Dit etiket is al afgedrukt op { $printed_at }; bevestig om het opnieuw af te drukken
+quota-exceeded = Het dagquotum van { $limit } etiketten is op: vandaag nog { $remaining } over, deze opdracht heeft er { $labels } nodig
+audit-unavailable = Het auditlogboek kan deze opdracht niet vastleggen, dus wordt ze niet afgedrukt: { $error }
+audit-write-failed = De etiketten zijn afgedrukt, maar het auditlogboek kon de opdracht niet vastleggen: { $error }
+
+## Printerauthenticatie
+
+auth-required = { $printer } vereist een gebruikersnaam en wachtwoord
+auth-rejected = { $printer } heeft de opgeslagen gebruikersnaam en wachtwoord geweigerd
+
+## Labelformaten
+
+invalid-label-size = Ongeldig labelformaat { $width }x{ $height }mm: beide zijden moeten positief zijn
+label-too-large = Labelformaat { $width }x{ $height }mm is groter dan het maximum van { $max }mm
+
+## Labelafbeeldingen om af te drukken
+
+image-not-base64 = De data-URL van de afbeelding is niet base64-gecodeerd
+image-not-png-type = Een PNG-afbeelding verwacht, maar { $type } ontvangen
+image-missing = Er zijn geen afbeeldingsgegevens verzonden
+image-bad-base64 = Base64-afbeelding decoderen mislukt: { $error }
+image-not-png = De afbeeldingsgegevens zijn geen PNG
+upload-unknown = Onbekende upload: { $id }
+upload-not-png = Upload { $id } is geen PNG ({ $bytes } bytes ontvangen)
+upload-too-large = Upload { $id } is groter dan { $max } bytes
+upload-not-binary = Uploaddelen moeten als binaire gegevens worden verzonden
+
+## Kioskmodus en operators
+
+kiosk-command-refused = { $command } is niet beschikbaar in kioskmodus
+kiosk-wrong-pin = Onjuiste pincode
+kiosk-locked-out = Te vaak een onjuiste pincode; probeer het over { $seconds } seconden opnieuw
+operator-unknown = { $name } is geen ingestelde operator
+operator-list-empty = Er zijn geen operators ingesteld; meld je aan met je badge of vraag een beheerder
+operator-badge-unknown = Geen operator heeft badge "{ $code }"
+
+## Webwinkelbestellingen
+
+orders-no-shop = Er is geen webwinkel ingesteld
+order-not-open = Bestelling { $order } staat niet meer open
+orders-nothing-to-print = De gekozen bestellingen hebben niets om af te drukken
+
+## Printerverbindingen
+
+ipp-credentials-cleartext = { $printer } vraagt om een wachtwoord, dat onversleuteld over { $uri } zou gaan; gebruik het ipps://-adres
+unsupported-file-type = Niet-ondersteund bestandstype: .{ $extension }
+
+## Ontbrekende programma's
+
+imagemagick-missing =
+    ImageMagick niet gevonden: { $error }. Installeer ImageMagick:
+    - Fedora: sudo dnf install ImageMagick
+    - Ubuntu/Debian: sudo apt install imagemagick
+    - Arch: sudo pacman -S imagemagick
+
+imagemagick-pdf-blocked =
+    Het beveiligingsbeleid van ImageMagick verbiedt het lezen en schrijven van PDF-bestanden. Wijzig in { $policy }
+    <policy domain="coder" rights="none" pattern="PDF" /> in rights="read|write", of installeer GraphicsMagick.
+
+rembg-missing =
+    rembg niet gevonden: { $error }. Achtergronden verwijderen met een model vereist rembg:
+    - pip install "rembg[cli]"
+
+hunspell-missing =
+    Hunspell niet gevonden: { $error }. Spellingcontrole vereist Hunspell en een woordenboek voor de taal:
+    - Fedora: sudo dnf install hunspell hunspell-nl
+    - Ubuntu/Debian: sudo apt install hunspell hunspell-nl
+    - Arch: sudo pacman -S hunspell hunspell-nl
+
+tesseract-missing =
+    Tesseract niet gevonden: { $error }. Tekst lezen uit foto's van labels vereist Tesseract:
+    - Fedora: sudo dnf install tesseract tesseract-langpack-nld
+    - Ubuntu/Debian: sudo apt install tesseract-ocr tesseract-ocr-nld
+    - Arch: sudo pacman -S tesseract tesseract-data-nld
+
+scanimage-missing =
+    scanimage niet gevonden: { $error }. Scannen vereist SANE:
+    - Fedora: sudo dnf install sane-backends
+    - Ubuntu/Debian: sudo apt install sane-utils
+    - Arch: sudo pacman -S sane
+
+## Printermedia, gecontroleerd tijdens het ontwerpen
+
+media-too-wide = Het label van { $size } is breder dan de breedste media van { $printer } ({ $max }mm)
+media-outside-printable-area = { $printer } kan maar { $printable }mm breed afdrukken; de randen van het label van { $size } vallen weg
+media-too-short = Het label van { $size } is korter dan de { $min }mm die { $printer } kan invoeren
+media-too-long = Het label van { $size } is langer dan de { $max }mm die { $printer } kan afdrukken
+media-no-match = { $printer } heeft geen media van { $size } en ondersteunt geen aangepaste formaten
+
+## Media op de rol
+
+media-low = { $printer } heeft nog ongeveer { $remaining } op de rol, niet genoeg voor de { $needed } van deze taak
+media-mm = { $amount }mm
+media-labels = { $amount ->
+    [one] { $amount } label
+   *[other] { $amount } labels
+}
+
+## Tapekleuren
+
+tape-low-contrast = { $ink } inkt valt nauwelijks op op { $tape } tape
+tape-element-too-light = Element { $id } is getekend in { $color }, te licht om af te drukken; het is niet te zien op de { $tape } tape
+tape-light-areas = Delen van het label hebben kleuren die te licht zijn om af te drukken; ze zijn niet te zien op de { $tape } tape
+
+## Voedingswaardevermelding
+
+nutrition-title = Voedingswaarde
+nutrition-per = per { $amount }
+nutrition-reference-intake = RI*
+nutrition-reference-intake-note = *Referentie-inname van een gemiddelde volwassene (8400 kJ/2000 kcal)
+nutrition-energy = Energie
+nutrition-fat = Vetten
+nutrition-saturates = waarvan verzadigde vetzuren
+nutrition-carbohydrate = Koolhydraten
+nutrition-sugars = waarvan suikers
+nutrition-fibre = Voedingsvezel
+nutrition-protein = Eiwitten
+nutrition-salt = Zout
@@ -0,0 +1,110 @@
+// Image adjustments applied at render time. The asset keeps the imported
+// original, so adjustments can be changed or removed later without losing
+// detail.
+use crate::document::{Asset, ImageAdjustments};
+use crate::svg;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::imageops::{self, colorops};
+use image::{DynamicImage, ImageFormat, Rgba};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Mutex, OnceLock};
+
+/// Adjusted images kept for reuse; batches repeat the same image on every label
+const CACHE_ENTRIES: usize = 32;
+
+static CACHE: OnceLock<Mutex<HashMap<[u8; 32], String>>> = OnceLock::new();
+
+/// Blur radius of the unsharp mask
+const SHARPEN_SIGMA: f32 = 1.0;
+
+fn is_identity(adjustments: &ImageAdjustments) -> bool {
+    *adjustments == ImageAdjustments::default()
+}
+
+/// Mime type and base64 data of `asset` with `adjustments` applied, as PNG.
+/// SVG and unadjusted images are returned unchanged.
+pub fn adjusted(asset: &Asset, adjustments: &ImageAdjustments) -> Result<(String, String), String> {
+    if is_identity(adjustments) || asset.mime_type == svg::MIME_TYPE {
+        return Ok((asset.mime_type.clone(), asset.data.clone()));
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(asset.data.as_bytes());
+    hasher.update(serde_json::to_vec(adjustments).unwrap_or_default());
+    let key: [u8; 32] = hasher.finalize().into();
+    let cache = CACHE.get_or_init(Mutex::default);
+    if let Some(data) = cache.lock().unwrap().get(&key) {
+        return Ok(("image/png".to_string(), data.clone()));
+    }
+
+    let png = apply(&asset.bytes()?, adjustments)?;
+    let data = BASE64.encode(png);
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= CACHE_ENTRIES {
+        cache.clear();
+    }
+    cache.insert(key, data.clone());
+    Ok(("image/png".to_string(), data))
+}
+
+/// `bytes`, a PNG, JPEG, GIF or WebP, with `adjustments` applied, as PNG
+pub fn apply(bytes: &[u8], adjustments: &ImageAdjustments) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("Failed to decode image: {}", e))?;
+    let mut image = image.to_rgba8();
+
+    if adjustments.brightness != 0.0 {
+        let amount = (adjustments.brightness.clamp(-100.0, 100.0) * 2.55).round() as i32;
+        colorops::brighten_in_place(&mut image, amount);
+    }
+    if adjustments.contrast != 0.0 {
+        colorops::contrast_in_place(&mut image, adjustments.contrast.clamp(-100.0, 100.0) as f32);
+    }
+    if adjustments.gamma > 0.0 && adjustments.gamma != 1.0 {
+        let lookup: Vec<u8> = (0..=255)
+            .map(|v| ((v as f64 / 255.0).powf(1.0 / adjustments.gamma) * 255.0).round() as u8)
+            .collect();
+        for Rgba([r, g, b, _]) in image.pixels_mut() {
+            for channel in [r, g, b] {
+                *channel = lookup[*channel as usize];
+            }
+        }
+    }
+    if adjustments.sharpen > 0.0 {
+        // Unsharp masking: push each pixel away from its blurred value
+        let blurred = imageops::blur(&image, SHARPEN_SIGMA);
+        let amount = adjustments.sharpen as f32;
+        for (pixel, blur) in image.pixels_mut().zip(blurred.pixels()) {
+            for c in 0..3 {
+                let value = pixel[c] as f32 + (pixel[c] as f32 - blur[c] as f32) * amount;
+                pixel[c] = value.clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    if adjustments.invert {
+        colorops::invert(&mut image);
+    }
+    if let Some(threshold) = adjustments.threshold {
+        for pixel in image.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+            let value = if luma < threshold as f64 { 0 } else { 255 };
+            *pixel = Rgba([value, value, value, a]);
+        }
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(png)
+}
+
+/// PNG data URL of an asset with `adjustments` applied, for the designer's
+/// preview of an image element
+#[tauri::command]
+pub fn preview_image_adjustments(asset: Asset, adjustments: ImageAdjustments) -> Result<String, String> {
+    let (mime_type, data) = adjusted(&asset, &adjustments)?;
+    Ok(format!("data:{};base64,{}", mime_type, data))
+}
@@ -0,0 +1,324 @@
+// Per-printer position and size correction, for printers that print a little
+// off: shifted because the sensor sits slightly off, or stretched along the
+// feed by worn rollers. The wizard prints a pattern of lines at known
+// distances, the user measures where they ended up on the label, and the
+// correction that puts them back is saved. Jobs for a corrected printer are
+// rasterized, shifted and scaled just before they're spooled.
+use crate::error::LabelgoodError;
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::formats::escape;
+use crate::groups;
+use crate::history::JobDetails;
+use crate::printing::{self, RENDER_DPI};
+use crate::render;
+use crate::settings::SettingsStore;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::State;
+use tracing::info;
+
+/// Measured distances further than this fraction from the pattern's are
+/// taken for typos rather than a printer that far off
+const MAX_SCALE_ERROR: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrinterCorrection {
+    /// Moves the label right, in millimetres
+    pub offset_x_mm: f64,
+    /// Moves the label down, in millimetres
+    pub offset_y_mm: f64,
+    /// Stretches the label across, from its left edge
+    pub scale_x: f64,
+    /// Stretches the label along, from its top edge
+    pub scale_y: f64,
+}
+
+impl Default for PrinterCorrection {
+    fn default() -> Self {
+        PrinterCorrection {
+            offset_x_mm: 0.0,
+            offset_y_mm: 0.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+        }
+    }
+}
+
+impl PrinterCorrection {
+    fn is_identity(&self) -> bool {
+        *self == PrinterCorrection::default()
+    }
+}
+
+/// Where the pattern's lines were found on the printed label, in millimetres
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct AlignmentMeasurement {
+    /// From the label's left edge to the left line
+    pub left_mm: f64,
+    /// From the label's top edge to the top line
+    pub top_mm: f64,
+    /// Between the left and right lines
+    pub width_mm: f64,
+    /// Between the top and bottom lines
+    pub height_mm: f64,
+}
+
+static CORRECTIONS: OnceLock<Mutex<BTreeMap<String, PrinterCorrection>>> = OnceLock::new();
+
+fn corrections() -> &'static Mutex<BTreeMap<String, PrinterCorrection>> {
+    CORRECTIONS.get_or_init(Mutex::default)
+}
+
+pub fn configure(alignment: BTreeMap<String, PrinterCorrection>) {
+    *corrections().lock().unwrap() = alignment;
+}
+
+/// The correction for `printer_name`; None when it prints true
+pub fn for_printer(printer_name: &str) -> Option<PrinterCorrection> {
+    corrections()
+        .lock()
+        .unwrap()
+        .get(printer_name)
+        .copied()
+        .filter(|correction| !correction.is_identity())
+}
+
+// One page rendered at `RENDER_DPI`, shifted and scaled onto a white page of
+// the same size
+fn correct_png(png: &[u8], correction: &PrinterCorrection) -> Result<Vec<u8>, LabelgoodError> {
+    let page = image::load_from_memory(png)
+        .map_err(|e| LabelgoodError::RenderFailed {
+            message: format!("Failed to read page image: {}", e),
+        })?
+        .to_rgb8();
+    let (width, height) = page.dimensions();
+    let scaled = if correction.scale_x == 1.0 && correction.scale_y == 1.0 {
+        page
+    } else {
+        imageops::resize(
+            &page,
+            ((width as f64 * correction.scale_x).round() as u32).max(1),
+            ((height as f64 * correction.scale_y).round() as u32).max(1),
+            FilterType::Triangle,
+        )
+    };
+    let px_per_mm = RENDER_DPI / 25.4;
+    let mut corrected = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+    imageops::overlay(
+        &mut corrected,
+        &scaled,
+        (correction.offset_x_mm * px_per_mm).round() as i64,
+        (correction.offset_y_mm * px_per_mm).round() as i64,
+    );
+    let mut out = Vec::new();
+    corrected
+        .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+        .map_err(|e| LabelgoodError::RenderFailed {
+            message: format!("Failed to encode page image: {}", e),
+        })?;
+    Ok(out)
+}
+
+/// A copy of the PDF at `pdf_path` in the spool directory with `correction`
+/// applied to every page
+pub async fn correct_pdf(
+    pdf_path: &Path,
+    correction: &PrinterCorrection,
+    width_mm: f64,
+    height_mm: f64,
+) -> Result<PathBuf, LabelgoodError> {
+    let pages = printing::rasterize_pdf(pdf_path, None).await?;
+    let temp_dir = tempfile::tempdir().map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to create temp directory: {}", e),
+    })?;
+    let mut paths = Vec::with_capacity(pages.len());
+    for (i, page) in pages.iter().enumerate() {
+        let path = temp_dir.path().join(format!("page_{:05}.png", i));
+        fs::write(&path, correct_png(page, correction)?).map_err(|e| LabelgoodError::Io {
+            message: format!("Failed to write PNG file: {}", e),
+        })?;
+        paths.push(path);
+    }
+    let page_refs: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+    printing::images_to_pdf(&page_refs, width_mm, height_mm).await
+}
+
+// Distance of the pattern's lines from the label edges
+fn pattern_margin(width_mm: f64, height_mm: f64) -> f64 {
+    (width_mm.min(height_mm) / 4.0).min(5.0)
+}
+
+// Ruler ticks are longer every 5 and 10 millimetres
+fn tick_length(mm: u32) -> f64 {
+    match mm {
+        mm if mm % 10 == 0 => 2.0,
+        mm if mm % 5 == 0 => 1.5,
+        _ => 0.8,
+    }
+}
+
+// Lines a margin in from each edge, ruled in millimetres from the top left
+// corner they meet at, and a crosshair in the middle
+fn pattern_svg(width_mm: f64, height_mm: f64) -> String {
+    let margin = pattern_margin(width_mm, height_mm);
+    let (right, bottom) = (width_mm - margin, height_mm - margin);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#ffffff\"/>\n\
+         <g stroke=\"#000000\" stroke-width=\"0.2\">\n",
+        w = width_mm,
+        h = height_mm
+    );
+    for x in [margin, right] {
+        svg.push_str(&format!(
+            "<line x1=\"{x}\" y1=\"0\" x2=\"{x}\" y2=\"{}\"/>\n",
+            height_mm
+        ));
+    }
+    for y in [margin, bottom] {
+        svg.push_str(&format!("<line x1=\"0\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\"/>\n", width_mm));
+    }
+    for (i, x) in (1..)
+        .map(|i| (i, margin + f64::from(i)))
+        .take_while(|(_, x)| *x < right)
+    {
+        let tick = tick_length(i);
+        svg.push_str(&format!(
+            "<line x1=\"{x}\" y1=\"{margin}\" x2=\"{x}\" y2=\"{}\"/>\n",
+            margin + tick
+        ));
+    }
+    for (i, y) in (1..)
+        .map(|i| (i, margin + f64::from(i)))
+        .take_while(|(_, y)| *y < bottom)
+    {
+        let tick = tick_length(i);
+        svg.push_str(&format!(
+            "<line x1=\"{margin}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\"/>\n",
+            margin + tick
+        ));
+    }
+    let (cx, cy, arm) = (width_mm / 2.0, height_mm / 2.0, margin);
+    svg.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{cy}\" x2=\"{}\" y2=\"{cy}\"/>\n<line x1=\"{cx}\" y1=\"{}\" x2=\"{cx}\" y2=\"{}\"/>\n\
+         <circle cx=\"{cx}\" cy=\"{cy}\" r=\"{}\" fill=\"none\"/>\n</g>\n",
+        cx - arm,
+        cx + arm,
+        cy - arm,
+        cy + arm,
+        arm / 2.0
+    ));
+    let size = (margin * 0.5).max(1.2);
+    svg.push_str(&format!(
+        "<text x=\"{cx}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{size}\" text-anchor=\"middle\">{}</text>\n</svg>\n",
+        cy + arm + size * 1.2,
+        escape(&format!("{} x {} mm", right - margin, bottom - margin))
+    ));
+    svg
+}
+
+// The correction along one axis that moves a line measured at
+// `measured_start` to `nominal_start` and makes a span measured as
+// `measured_span` `nominal_span` long, on top of `scale` and `offset` the
+// pattern was printed with
+fn correct_axis(
+    (scale, offset): (f64, f64),
+    (nominal_start, nominal_span): (f64, f64),
+    (measured_start, measured_span): (f64, f64),
+) -> Result<(f64, f64), String> {
+    let stretch = measured_span / nominal_span;
+    if !stretch.is_finite() || (stretch - 1.0).abs() > MAX_SCALE_ERROR {
+        return Err(format!(
+            "{} mm between the lines is too far from the {} mm printed; check the measurement",
+            measured_span, nominal_span
+        ));
+    }
+    let error = measured_start - stretch * nominal_start;
+    if !error.is_finite() || error.abs() > nominal_start.max(measured_start) + nominal_span / 2.0 {
+        return Err(format!(
+            "A line {} mm from the edge is too far from the {} mm printed; check the measurement",
+            measured_start, nominal_start
+        ));
+    }
+    Ok((scale / stretch, offset - error * scale / stretch))
+}
+
+/// Print the alignment pattern on a `width_mm` x `height_mm` label on
+/// `printer_name`, with its current correction, for the user to measure.
+/// `op_id` lets `cancel_operation` stop it.
+#[tauri::command]
+pub async fn print_alignment_pattern(
+    fonts: State<'_, FontLibrary>,
+    events: State<'_, EventBus>,
+    printer_name: String,
+    width_mm: f64,
+    height_mm: f64,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    LabelgoodError::check_dimensions(width_mm, height_mm)?;
+    if groups::is_group(&printer_name) {
+        return Err("Align each printer of a group on its own".to_string());
+    }
+    let png = render::rasterize(
+        &pattern_svg(width_mm, height_mm),
+        fonts.database(),
+        width_mm,
+        height_mm,
+        RENDER_DPI,
+    )?;
+    let work = async {
+        let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+        let path = temp_dir.path().join("alignment.png");
+        fs::write(&path, &png).map_err(|e| format!("Failed to write PNG file: {}", e))?;
+        let pdf_path = printing::images_to_pdf(&[path.as_path()], width_mm, height_mm).await?;
+        Ok::<_, String>(printing::submit_pdf(&pdf_path, width_mm, height_mm, Some(&printer_name)).await?)
+    };
+    let job = events.run_job("alignment", 1, Some(&printer_name), JobDetails::default(), work);
+    events.run_operation(op_id, job).await
+}
+
+/// Work out and save the correction for `printer_name` from where the lines
+/// of the pattern printed on a `width_mm` x `height_mm` label were measured
+#[tauri::command]
+pub fn save_alignment(
+    store: State<'_, SettingsStore>,
+    printer_name: String,
+    width_mm: f64,
+    height_mm: f64,
+    measured: AlignmentMeasurement,
+) -> Result<PrinterCorrection, String> {
+    LabelgoodError::check_dimensions(width_mm, height_mm)?;
+    let margin = pattern_margin(width_mm, height_mm);
+    let mut settings = store.get();
+    let current = settings.alignment.get(&printer_name).copied().unwrap_or_default();
+    let (scale_x, offset_x_mm) = correct_axis(
+        (current.scale_x, current.offset_x_mm),
+        (margin, width_mm - 2.0 * margin),
+        (measured.left_mm, measured.width_mm),
+    )?;
+    let (scale_y, offset_y_mm) = correct_axis(
+        (current.scale_y, current.offset_y_mm),
+        (margin, height_mm - 2.0 * margin),
+        (measured.top_mm, measured.height_mm),
+    )?;
+    let correction = PrinterCorrection {
+        offset_x_mm,
+        offset_y_mm,
+        scale_x,
+        scale_y,
+    };
+    settings.alignment.insert(printer_name.clone(), correction);
+    let alignment = settings.alignment.clone();
+    store.set(settings)?;
+    configure(alignment);
+    info!("Saved alignment of {}: {:?}", printer_name, correction);
+    Ok(correction)
+}
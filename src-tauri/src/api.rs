@@ -0,0 +1,444 @@
+// Optional local REST API, so POS systems and ERP scripts on the LAN can
+// print through Labelgood:
+//
+//   GET  /health
+//   GET  /printers
+//   GET  /templates
+//   POST /print      {"template": "price", "records": [{"name": "Tea"}], "printer": "QL-820NWB"}
+//                    or {"definition": "width = 62\nheight = 29\n...", ...} to print a label definition
+//   GET  /jobs       jobs waiting for or being sent to a printer
+//   GET  /events     job and printer events as server-sent events
+//   GET  /ws         the same events over a WebSocket
+//   GET  /status     state of every printer
+//   GET  /history    recent jobs, filtered like the history view (?limit=25&printer=...)
+//   POST /history/{id}/reprint
+//
+// With the dashboard on, GET / serves a page showing the queue, printer status
+// and recent jobs with reprint buttons, so a Labelgood on e.g. a Raspberry Pi
+// can be the shop's shared print server. Open it as /?token=<token> when a
+// token is configured.
+//
+// Off by default. When a token is configured, every request needs an
+// `Authorization: Bearer <token>` header, or a `token` query parameter for
+// browser clients that can't set headers on EventSource and WebSocket.
+// Listening on anything but loopback requires a token.
+use crate::backends::{self, PrinterStatus};
+use crate::counters::CounterStore;
+use crate::events::{EventBus, LabelEvent};
+use crate::fonts::FontLibrary;
+use crate::formats;
+use crate::history::{self, HistoryEntry, HistoryFilter, HistoryStore, JobDetails};
+use crate::merge::{self, CheckDigitPolicy, DataSource, MergeRequest};
+use crate::queue::{self, Priority, QueuedJob};
+use crate::scale::Scale;
+use crate::scripting::ScriptHooks;
+use crate::settings::SettingsStore;
+use crate::templates::{self, TemplateSummary};
+use axum::extract::ws::{Message, WebSocketUpgrade};
+use axum::extract::{Path as UrlPath, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tracing::{error, info};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// Address to listen on; 0.0.0.0 to accept connections from the LAN, which needs a token
+    pub bind: String,
+    pub port: u16,
+    /// Required as a bearer token when set
+    pub token: Option<String>,
+    /// Serve the print-server dashboard at /
+    pub dashboard: bool,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 8723,
+            token: None,
+            dashboard: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrintRequest {
+    /// Gallery template id, or a path to a .lblg or importable label file;
+    /// one in the shared templates directory when the request came over the
+    /// network
+    #[serde(default)]
+    pub template: String,
+    /// Label definition (TOML) to print instead of `template`
+    #[serde(default)]
+    pub definition: Option<String>,
+    /// One label per record; a single label with only print-time fields when empty
+    #[serde(default)]
+    pub records: Vec<BTreeMap<String, String>>,
+    /// Opens the PDF on the host when absent
+    #[serde(default)]
+    pub printer: Option<String>,
+    #[serde(default)]
+    pub check_digits: CheckDigitPolicy,
+    /// Place in the print queue, e.g. "rush" to go ahead of waiting batches
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrintResponse {
+    pub labels: usize,
+    pub message: String,
+}
+
+pub struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app: AppHandle,
+    token: Option<String>,
+    dashboard: bool,
+}
+
+const DASHBOARD: &str = include_str!("../dashboard/index.html");
+
+// How long a restart waits for open connections, e.g. event streams, to
+// finish before the old server is stopped outright
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// The running server, if any. Restarted whenever its settings change.
+#[derive(Default)]
+pub struct ApiServer {
+    running: Mutex<Option<(oneshot::Sender<()>, JoinHandle<()>)>>,
+}
+
+impl ApiServer {
+    pub async fn start(&self, app: &AppHandle, settings: &ApiSettings) -> Result<(), String> {
+        let mut running = self.running.lock().await;
+        // The old server must have let go of the port before binding again
+        if let Some((stop, mut task)) = running.take() {
+            let _ = stop.send(());
+            if tokio::time::timeout(SHUTDOWN_GRACE, &mut task).await.is_err() {
+                task.abort();
+                let _ = task.await;
+            }
+        }
+        if !settings.enabled {
+            return Ok(());
+        }
+        let token = settings.token.clone().filter(|t| !t.is_empty());
+        if token.is_none() && !loopback(&settings.bind) {
+            return Err(format!(
+                "The REST API needs a token to listen on {}; set one or bind to 127.0.0.1",
+                settings.bind
+            ));
+        }
+
+        // Bind here rather than in the task so a taken port is reported
+        let address = format!("{}:{}", settings.bind, settings.port);
+        let listener = std::net::TcpListener::bind(&address)
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .map_err(|e| format!("Failed to start REST API on {}: {}", address, e))?;
+
+        let state = ApiState {
+            app: app.clone(),
+            token,
+            dashboard: settings.dashboard,
+        };
+        let router = Router::new()
+            .route("/health", get(health))
+            .route("/printers", get(printers))
+            .route("/templates", get(list_templates))
+            .route("/print", post(print))
+            .route("/jobs", get(jobs))
+            .route("/events", get(event_stream))
+            .route("/ws", get(event_socket))
+            .route("/status", get(printer_status))
+            .route("/history", get(recent_jobs))
+            .route("/history/{id}/reprint", post(reprint))
+            .route("/", get(dashboard))
+            .layer(middleware::from_fn_with_state(state.clone(), authorize))
+            .with_state(state);
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let task = tauri::async_runtime::spawn(async move {
+            let result = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => {
+                    axum::serve(listener, router)
+                        .with_graceful_shutdown(async move {
+                            let _ = stopped.await;
+                        })
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("REST API stopped: {}", e);
+            }
+        });
+        info!("REST API listening on http://{}", address);
+        *running = Some((stop, task));
+        Ok(())
+    }
+}
+
+/// Whether `bind` only accepts connections from this machine
+pub(crate) fn loopback(bind: &str) -> bool {
+    bind.eq_ignore_ascii_case("localhost") || bind.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let from_header = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let from_query = request
+            .uri()
+            .query()
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .find_map(|pair| pair.strip_prefix("token="));
+        let given = from_header.or(from_query);
+        if !given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes()))) {
+            return ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid API token".to_string()).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn printers() -> Result<Json<Vec<String>>, ApiError> {
+    let printers = crate::list_printers().await.map_err(|e| ApiError::from(String::from(e)))?;
+    Ok(Json(printers))
+}
+
+async fn list_templates(State(state): State<ApiState>) -> Result<Json<Vec<TemplateSummary>>, ApiError> {
+    let shared_dir = shared_dir(&state.app);
+    let templates = templates::all_templates(shared_dir.as_deref())?;
+    Ok(Json(templates.iter().map(TemplateSummary::from).collect()))
+}
+
+async fn print(State(state): State<ApiState>, Json(request): Json<PrintRequest>) -> Result<Json<PrintResponse>, ApiError> {
+    check_template_path(&state.app, &request.template)?;
+    let response = print_records(&state.app, "api", request).await?;
+    Ok(Json(response))
+}
+
+async fn jobs() -> Json<Vec<QueuedJob>> {
+    Json(queue::snapshot())
+}
+
+async fn printer_status() -> Result<Json<Vec<PrinterStatus>>, ApiError> {
+    let printers = backends::enumerate()
+        .await
+        .map_err(|e| ApiError::from(String::from(e)))?;
+    let mut statuses = Vec::with_capacity(printers.len());
+    for printer in &printers {
+        statuses.push(backends::resolve(printer).status(printer).await);
+    }
+    Ok(Json(statuses))
+}
+
+async fn recent_jobs(
+    State(state): State<ApiState>,
+    Query(filter): Query<HistoryFilter>,
+) -> Result<Json<Vec<HistoryEntry>>, ApiError> {
+    Ok(Json(state.app.state::<HistoryStore>().list(&filter)?))
+}
+
+async fn reprint(
+    State(state): State<ApiState>,
+    UrlPath(id): UrlPath<i64>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let app = &state.app;
+    let message = history::reprint(&app.state::<HistoryStore>(), &app.state::<EventBus>(), id, None, None).await?;
+    Ok(Json(serde_json::json!({ "message": message })))
+}
+
+async fn dashboard(State(state): State<ApiState>) -> Response {
+    if !state.dashboard {
+        return ApiError(StatusCode::NOT_FOUND, "The dashboard is turned off".to_string()).into_response();
+    }
+    Html(DASHBOARD).into_response()
+}
+
+async fn event_stream(State(state): State<ApiState>) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = stream::unfold(state.app.state::<EventBus>().subscribe(), |mut receiver| async move {
+        let event = next_event(&mut receiver).await?;
+        Some((Event::default().event(event.kind()).json_data(&event), receiver))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn event_socket(State(state): State<ApiState>, upgrade: WebSocketUpgrade) -> Response {
+    let mut receiver = state.app.state::<EventBus>().subscribe();
+    upgrade.on_upgrade(move |mut socket| async move {
+        while let Some(event) = next_event(&mut receiver).await {
+            let Ok(json) = serde_json::to_string(&event) else {
+                continue;
+            };
+            if socket.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Next event for a subscriber; a subscriber that fell behind skips what it
+/// missed rather than disconnecting
+pub async fn next_event(receiver: &mut broadcast::Receiver<LabelEvent>) -> Option<LabelEvent> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Render and print the labels of a print request. `source` names the
+/// submitter in job events.
+pub async fn print_records(app: &AppHandle, source: &str, request: PrintRequest) -> Result<PrintResponse, String> {
+    let records = if request.records.is_empty() {
+        vec![BTreeMap::new()]
+    } else {
+        request.records
+    };
+    let data = DataSource::Records { records };
+    let (printer, check_digits) = (request.printer, request.check_digits);
+    if let Some(definition) = request.definition {
+        let merge_request = MergeRequest {
+            // Only REST and gRPC requests carry definitions
+            document: formats::definition::compile(&definition, Path::new(""), true)?,
+            source: data,
+            mapping: BTreeMap::new(),
+            range: None,
+            printer_name: printer,
+            check_digits,
+            numbering: None,
+            answers: BTreeMap::new(),
+        };
+        let work = print_document(app, source, "definition", merge_request);
+        return queue::with_priority(request.priority, work).await;
+    }
+    let work = print_template(app, source, &request.template, data, BTreeMap::new(), printer, check_digits);
+    queue::with_priority(request.priority, work).await
+}
+
+/// Requests from the network may name a gallery template or a file in the
+/// shared templates directory, but no other file on this machine
+pub(crate) fn check_template_path(app: &AppHandle, template: &str) -> Result<(), String> {
+    let path = Path::new(template);
+    if !path.is_file() {
+        return Ok(());
+    }
+    let inside = shared_dir(app)
+        .and_then(|dir| dir.canonicalize().ok())
+        .zip(path.canonicalize().ok())
+        .is_some_and(|(dir, file)| file.starts_with(dir));
+    if !inside {
+        return Err(format!("Template \"{}\" isn't in the shared templates directory", template));
+    }
+    Ok(())
+}
+
+/// Render and print one label of `template` per row of `data`, with fields
+/// mapped to columns as in a merge
+pub async fn print_template(
+    app: &AppHandle,
+    source: &str,
+    template: &str,
+    data: DataSource,
+    mapping: BTreeMap<String, String>,
+    printer: Option<String>,
+    check_digits: CheckDigitPolicy,
+) -> Result<PrintResponse, String> {
+    let document = {
+        let (app, template) = (app.clone(), template.to_string());
+        merge::blocking(move || templates::resolve_document(&template, shared_dir(&app).as_deref())).await?
+    };
+    let merge_request = MergeRequest {
+        document,
+        source: data,
+        mapping,
+        range: None,
+        printer_name: printer,
+        check_digits,
+        numbering: None,
+        answers: BTreeMap::new(),
+    };
+    print_document(app, source, template, merge_request).await
+}
+
+// Render and print `merge_request`; `template` names the design in the job history
+async fn print_document(
+    app: &AppHandle,
+    source: &str,
+    template: &str,
+    merge_request: MergeRequest,
+) -> Result<PrintResponse, String> {
+    let printer = merge_request.printer_name.clone();
+    let (documents, details) = {
+        let (app, template) = (app.clone(), template.to_string());
+        merge::blocking(move || {
+            let scripts = app.state::<ScriptHooks>();
+            let table = merge::load_table(&merge_request, &app.state::<Scale>(), &scripts)?;
+            let counters = app.state::<CounterStore>();
+            let mut documents =
+                merge::merge_documents(&merge_request, &table, &mut |name| counters.next_serial(name))?;
+            scripts.before_render(&mut documents)?;
+            let details = JobDetails {
+                template: Some(template),
+                data: merge::selected_records(&merge_request, &table),
+            };
+            Ok((documents, details))
+        })
+        .await?
+    };
+    let printer = printer.as_deref();
+    let work = merge::print_documents(&documents, app.state::<FontLibrary>().database(), printer);
+    let message = app.state::<EventBus>().run_job(source, documents.len(), printer, details, work).await?;
+    Ok(PrintResponse {
+        labels: documents.len(),
+        message,
+    })
+}
+
+/// The shared templates directory from the settings
+pub fn shared_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.state::<SettingsStore>().get().shared_templates_dir.map(PathBuf::from)
+}
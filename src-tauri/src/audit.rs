@@ -0,0 +1,434 @@
+// Compliance audit log for GMP and food-safety sites: who printed how many
+// labels with what data, and when. Unlike the print history it can't be
+// edited or cleared from the app; the database refuses updates and deletes,
+// and each entry carries a hash chained to the previous one so tampering
+// with the file outside the app shows up in `verify_audit_log`.
+use crate::fonts::FontLibrary;
+use crate::formats::escape;
+use crate::history::JobDetails;
+use crate::printing::{self, RENDER_DPI};
+use crate::render;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS audit (
+        id INTEGER PRIMARY KEY,
+        recorded_at TEXT NOT NULL,
+        user TEXT NOT NULL,
+        operator TEXT,
+        source TEXT NOT NULL,
+        template TEXT,
+        printer TEXT,
+        labels INTEGER NOT NULL,
+        ok INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE TRIGGER IF NOT EXISTS audit_no_update BEFORE UPDATE ON audit
+        BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+    CREATE TRIGGER IF NOT EXISTS audit_no_delete BEFORE DELETE ON audit
+        BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+";
+
+const ENTRY_COLUMNS: &str = "id, recorded_at, user, operator, source, template, printer, labels, ok, data, hash";
+
+// A4 portrait report layout, in millimetres
+const PAGE_WIDTH_MM: f64 = 210.0;
+const PAGE_HEIGHT_MM: f64 = 297.0;
+const MARGIN_MM: f64 = 15.0;
+const ENTRY_HEIGHT_MM: f64 = 9.0;
+const DATA_PREVIEW_CHARS: usize = 140;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// UTC, RFC 3339
+    pub recorded_at: String,
+    /// Account the app runs under
+    pub user: String,
+    /// Operator signed in, or the name entered in the settings, for shared
+    /// workstation accounts
+    pub operator: Option<String>,
+    pub source: String,
+    pub template: Option<String>,
+    pub printer: Option<String>,
+    pub labels: usize,
+    pub ok: bool,
+    pub data: Vec<BTreeMap<String, String>>,
+    /// SHA-256 over the previous entry's hash and this entry's fields
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Pdf,
+}
+
+pub struct AuditLog {
+    connection: Mutex<Option<Connection>>,
+}
+
+impl AuditLog {
+    /// Open or create the log at `path`. If it can't be opened, jobs are
+    /// refused rather than printed unaudited, and every audit command says
+    /// why.
+    pub fn open(path: &Path) -> Self {
+        let connection = match open_file(path) {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                error!("{}", e);
+                None
+            }
+        };
+        AuditLog {
+            connection: Mutex::new(connection),
+        }
+    }
+
+    /// Whether entries can be appended, checked before a job is sent
+    pub fn check(&self) -> Result<(), String> {
+        match self.connection.lock().unwrap().as_ref() {
+            Some(_) => Ok(()),
+            None => Err("The audit log couldn't be opened".to_string()),
+        }
+    }
+
+    pub fn append(
+        &self,
+        source: &str,
+        labels: usize,
+        printer: Option<&str>,
+        operator: Option<&str>,
+        details: &JobDetails,
+        ok: bool,
+    ) -> Result<(), String> {
+        let connection = self.connection.lock().unwrap();
+        let connection = connection.as_ref().ok_or("The audit log couldn't be opened")?;
+        let previous: String = connection
+            .query_row("SELECT hash FROM audit ORDER BY id DESC LIMIT 1", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read the audit log: {}", e))?
+            .unwrap_or_default();
+
+        let mut entry = AuditEntry {
+            id: 0,
+            recorded_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            user: os_user(),
+            operator: operator.map(str::to_string),
+            source: source.to_string(),
+            template: details.template.clone(),
+            printer: printer.map(str::to_string),
+            labels,
+            ok,
+            data: details.data.clone(),
+            hash: String::new(),
+        };
+        let data = serde_json::to_string(&entry.data).map_err(|e| format!("Failed to serialize job data: {}", e))?;
+        entry.hash = entry_hash(&previous, &entry, &data);
+
+        connection
+            .execute(
+                "INSERT INTO audit (recorded_at, user, operator, source, template, printer, labels, ok, data, hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    entry.recorded_at,
+                    entry.user,
+                    entry.operator,
+                    entry.source,
+                    entry.template,
+                    entry.printer,
+                    labels as i64,
+                    ok,
+                    data,
+                    entry.hash,
+                ],
+            )
+            .map_err(|e| format!("Failed to write the audit log: {}", e))?;
+        Ok(())
+    }
+
+    /// Labels printed successfully since `since` by `operator`, or by
+    /// anyone when None, of `template`, or of any template when None
+    pub fn labels_printed(&self, operator: Option<&str>, template: Option<&str>, since: &str) -> Result<usize, String> {
+        let connection = self.connection.lock().unwrap();
+        let connection = connection.as_ref().ok_or("The audit log couldn't be opened")?;
+        let labels: i64 = connection
+            .query_row(
+                "SELECT COALESCE(SUM(labels), 0) FROM audit
+                 WHERE ok = 1 AND (?1 IS NULL OR operator = ?1) AND (?2 IS NULL OR template = ?2) AND recorded_at >= ?3",
+                params![operator, template, since],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to read the audit log: {}", e))?;
+        Ok(labels as usize)
+    }
+
+    /// Entries recorded in `[since, until)`, oldest first
+    pub fn entries(&self, since: Option<&str>, until: Option<&str>) -> Result<Vec<AuditEntry>, String> {
+        let connection = self.connection.lock().unwrap();
+        let connection = connection.as_ref().ok_or("The audit log couldn't be opened")?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM audit WHERE recorded_at >= ?1 AND recorded_at < ?2 ORDER BY id",
+                ENTRY_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to read the audit log: {}", e))?;
+        // Every timestamp sorts between these
+        let since = since.unwrap_or("");
+        let until = until.unwrap_or("~");
+        let entries = statement
+            .query_map([since, until], entry)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to read the audit log: {}", e))?;
+        Ok(entries)
+    }
+}
+
+fn open_file(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let connection = Connection::open(path).map_err(|e| format!("Failed to open the audit log: {}", e))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to set up the audit log: {}", e))?;
+    Ok(connection)
+}
+
+fn entry(row: &Row) -> rusqlite::Result<AuditEntry> {
+    let data: String = row.get(9)?;
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        recorded_at: row.get(1)?,
+        user: row.get(2)?,
+        operator: row.get(3)?,
+        source: row.get(4)?,
+        template: row.get(5)?,
+        printer: row.get(6)?,
+        labels: row.get::<_, i64>(7)? as usize,
+        ok: row.get(8)?,
+        // An unreadable record still appears; `verify` reports it
+        data: serde_json::from_str(&data).unwrap_or_default(),
+        hash: row.get(10)?,
+    })
+}
+
+fn os_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn entry_hash(previous: &str, entry: &AuditEntry, data: &str) -> String {
+    let labels = entry.labels.to_string();
+    let fields: [&str; 10] = [
+        previous,
+        &entry.recorded_at,
+        &entry.user,
+        entry.operator.as_deref().unwrap_or(""),
+        &entry.source,
+        entry.template.as_deref().unwrap_or(""),
+        entry.printer.as_deref().unwrap_or(""),
+        &labels,
+        if entry.ok { "1" } else { "0" },
+        data,
+    ];
+    let mut hasher = Sha256::new();
+    for field in fields {
+        hasher.update(field.as_bytes());
+        // Separator, so moving text between fields changes the hash
+        hasher.update([0]);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// One line of text per entry's data, e.g. "name=Tea; price=2.50 | name=Jam"
+fn data_summary(data: &[BTreeMap<String, String>]) -> String {
+    data.iter()
+        .map(|record| {
+            record
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn write_csv(entries: &[AuditEntry], path: &Path) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| format!("Failed to create CSV file: {}", e))?;
+    let csv_error = |e: csv::Error| format!("Failed to write CSV file: {}", e);
+    writer
+        .write_record([
+            "id", "recorded_at", "user", "operator", "source", "template", "printer", "labels", "result", "data", "hash",
+        ])
+        .map_err(csv_error)?;
+    for entry in entries {
+        writer
+            .write_record([
+                entry.id.to_string(),
+                entry.recorded_at.clone(),
+                entry.user.clone(),
+                entry.operator.clone().unwrap_or_default(),
+                entry.source.clone(),
+                entry.template.clone().unwrap_or_default(),
+                entry.printer.clone().unwrap_or_default(),
+                entry.labels.to_string(),
+                if entry.ok { "printed" } else { "failed" }.to_string(),
+                data_summary(&entry.data),
+                entry.hash.clone(),
+            ])
+            .map_err(csv_error)?;
+    }
+    writer.flush().map_err(|e| format!("Failed to write CSV file: {}", e))
+}
+
+// A4 report pages as SVG: a header line per entry, with its data underneath
+fn report_pages(entries: &[AuditEntry]) -> Vec<String> {
+    let per_page = ((PAGE_HEIGHT_MM - 2.0 * MARGIN_MM - 12.0) / ENTRY_HEIGHT_MM).floor().max(1.0) as usize;
+    let chunks: Vec<&[AuditEntry]> = if entries.is_empty() {
+        vec![&[]]
+    } else {
+        entries.chunks(per_page).collect()
+    };
+    let page_count = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(page, entries)| {
+            let mut svg = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+                 <rect width=\"{w}\" height=\"{h}\" fill=\"#ffffff\"/>\n\
+                 <text x=\"{m}\" y=\"{t}\" font-family=\"sans-serif\" font-size=\"5\" font-weight=\"bold\">Label audit log</text>\n\
+                 <text x=\"{r}\" y=\"{t}\" font-family=\"sans-serif\" font-size=\"3\" text-anchor=\"end\">Page {p} of {n}</text>\n",
+                w = PAGE_WIDTH_MM,
+                h = PAGE_HEIGHT_MM,
+                m = MARGIN_MM,
+                r = PAGE_WIDTH_MM - MARGIN_MM,
+                t = MARGIN_MM + 5.0,
+                p = page + 1,
+                n = page_count
+            );
+            for (i, entry) in entries.iter().enumerate() {
+                let y = MARGIN_MM + 12.0 + i as f64 * ENTRY_HEIGHT_MM;
+                let who = match &entry.operator {
+                    Some(operator) => format!("{} ({})", operator, entry.user),
+                    None => entry.user.clone(),
+                };
+                let header = format!(
+                    "#{}  {}  {}  {}  {}  {} label(s) on {}  {}",
+                    entry.id,
+                    entry.recorded_at,
+                    who,
+                    entry.source,
+                    entry.template.as_deref().unwrap_or("-"),
+                    entry.labels,
+                    entry.printer.as_deref().unwrap_or("PDF"),
+                    if entry.ok { "printed" } else { "FAILED" }
+                );
+                let mut data = data_summary(&entry.data);
+                if data.chars().count() > DATA_PREVIEW_CHARS {
+                    data = data.chars().take(DATA_PREVIEW_CHARS).collect::<String>() + "…";
+                }
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"2.8\">{}</text>",
+                    MARGIN_MM,
+                    y,
+                    escape(&header)
+                );
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"2.3\" fill=\"#555555\">{}</text>",
+                    MARGIN_MM + 3.0,
+                    y + 3.8,
+                    escape(&data)
+                );
+            }
+            svg.push_str("</svg>\n");
+            svg
+        })
+        .collect()
+}
+
+async fn write_pdf(entries: &[AuditEntry], fonts: &FontLibrary, path: &Path) -> Result<(), String> {
+    let pages = tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let mut png_paths = Vec::new();
+    for (i, svg) in report_pages(entries).iter().enumerate() {
+        let png = render::rasterize(svg, fonts.database(), PAGE_WIDTH_MM, PAGE_HEIGHT_MM, RENDER_DPI)?;
+        let png_path = pages.path().join(format!("page_{:04}.png", i));
+        fs::write(&png_path, png).map_err(|e| format!("Failed to write PNG file: {}", e))?;
+        png_paths.push(png_path);
+    }
+    let png_refs: Vec<&Path> = png_paths.iter().map(|p| p.as_path()).collect();
+    let pdf_path = printing::images_to_pdf(&png_refs, PAGE_WIDTH_MM, PAGE_HEIGHT_MM).await?;
+    fs::copy(&pdf_path, path).map_err(|e| format!("Failed to save PDF file: {}", e))?;
+    let _ = fs::remove_file(&pdf_path);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_audit_log(
+    audit: State<'_, AuditLog>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<Vec<AuditEntry>, String> {
+    audit.entries(since.as_deref(), until.as_deref())
+}
+
+/// Write the entries recorded in `[since, until)` to `path` as CSV or an A4
+/// PDF report. Returns how many entries were exported.
+#[tauri::command]
+pub async fn export_audit_log(
+    audit: State<'_, AuditLog>,
+    fonts: State<'_, FontLibrary>,
+    format: ExportFormat,
+    path: String,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<usize, String> {
+    let entries = audit.entries(since.as_deref(), until.as_deref())?;
+    match format {
+        ExportFormat::Csv => write_csv(&entries, Path::new(&path))?,
+        ExportFormat::Pdf => write_pdf(&entries, &fonts, Path::new(&path)).await?,
+    }
+    Ok(entries.len())
+}
+
+/// Recompute the hash chain. Returns the number of entries checked, or the
+/// first entry that doesn't match what was recorded.
+#[tauri::command]
+pub async fn verify_audit_log(audit: State<'_, AuditLog>) -> Result<usize, String> {
+    let connection = audit.connection.lock().unwrap();
+    let connection = connection.as_ref().ok_or("The audit log couldn't be opened")?;
+    let mut statement = connection
+        .prepare(&format!("SELECT {} FROM audit ORDER BY id", ENTRY_COLUMNS))
+        .map_err(|e| format!("Failed to read the audit log: {}", e))?;
+    let rows = statement
+        .query_map([], |row| Ok((entry(row)?, row.get::<_, String>(9)?)))
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read the audit log: {}", e))?;
+
+    let mut previous = String::new();
+    for (entry, data) in &rows {
+        if entry_hash(&previous, entry, data) != entry.hash {
+            return Err(format!("Audit log entry {} has been altered", entry.id));
+        }
+        previous = entry.hash.clone();
+    }
+    Ok(rows.len())
+}
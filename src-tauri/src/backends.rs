@@ -0,0 +1,351 @@
+// Printer backends: every way a finished PDF can reach a printer, behind one
+// interface. The printer name picks the backend:
+//
+//   Brother_QL-820NWB                  CUPS queue, through lpr or lp
+//   ipp://printer.local/ipp/print      IPP, to the printer or a remote CUPS
+//   ipps://printer.local/ipp/print     IPP over TLS
+//   socket://printer.local:9100        raw TCP (AppSocket/JetDirect)
+//   usb:/dev/usb/lp0                   USB printer class device
+//   file:///home/me/labels             PDF saved to a directory
+//   Virtual Printer (PDF)              see `virtual_printer`
+//   escpos:Counter printer             plugin driver, see `plugins`
+//   Warehouse Zebra                    network printer added by address,
+//                                      see `registered`
+//   System Print Service               the OS print dialog, on phones and
+//                                      tablets, see `mobile`
+//   System Print Dialog                the XDG print portal, in Flatpak and
+//                                      Snap builds, see `portal`
+//
+// Raw TCP and USB send the PDF unchanged, for printers that take PDF
+// directly; printers that need their own language go through CUPS or a
+// plugin driver.
+pub mod cups;
+pub mod file;
+mod ipp;
+#[cfg(mobile)]
+pub mod mobile;
+mod plugin;
+#[cfg(target_os = "linux")]
+mod portal;
+pub mod registered;
+mod socket;
+mod usb;
+
+use crate::capabilities::PrinterCapabilities;
+use crate::error::LabelgoodError;
+use crate::printerinfo::PrinterInfo;
+use crate::settings::SettingsStore;
+use registered::NetworkPrinter;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tracing::{info, warn};
+
+/// Future returned by backend methods; boxed so backends can be trait objects
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// How long a printer that has started answering may go quiet before its
+/// reply is taken to be complete
+const REPLY_PAUSE: Duration = Duration::from_millis(300);
+
+/// A PDF to print, with pages of `width_mm` x `height_mm`
+#[derive(Debug, Clone, Copy)]
+pub struct PrintJob<'a> {
+    /// Printer name as listed, including any scheme or driver prefix
+    pub printer: &'a str,
+    pub pdf_path: &'a Path,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+/// A job the backend accepted
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// Status message for the user
+    pub message: String,
+    /// Id to pass to `cancel`, when the backend reports one
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterStatus {
+    pub printer: String,
+    /// "idle", "printing", "disabled", "offline" or "unknown"
+    pub state: String,
+    /// Details from the printer or spooler, if any
+    pub message: String,
+}
+
+impl PrinterStatus {
+    pub fn new(printer: &str, state: &str, message: impl Into<String>) -> Self {
+        PrinterStatus {
+            printer: printer.to_string(),
+            state: state.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// One way of delivering jobs. Every method takes the printer name as
+/// listed; the backend strips its own prefix.
+pub trait PrinterBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether `printer_name` is addressed to this backend
+    fn handles(&self, printer_name: &str) -> bool;
+
+    /// Printers to offer in the printer list; backends addressed by URI have
+    /// none to offer
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn capabilities<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterCapabilities> {
+        Box::pin(async move { PrinterCapabilities::unknown(printer_name) })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>>;
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move { PrinterStatus::new(printer_name, "unknown", "") })
+    }
+
+    /// Cancel a job `submit` returned the id of
+    fn cancel<'a>(&'a self, printer_name: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            Err(LabelgoodError::InvalidInput {
+                message: format!("Job {} on {} can't be cancelled once sent", job_id, printer_name),
+            })
+        })
+    }
+
+    /// What the printer reports about itself
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(async move { PrinterInfo::new(printer_name) })
+    }
+
+    /// Send commands in the printer's own language, e.g. ZPL, past any
+    /// driver
+    fn send_raw<'a>(&'a self, printer_name: &'a str, _data: &'a [u8]) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            Err(LabelgoodError::InvalidInput {
+                message: format!("Printer commands can't be sent to {}", printer_name),
+            })
+        })
+    }
+
+    /// Send commands like `send_raw` and collect what the printer answers
+    /// within `wait`
+    fn query<'a>(
+        &'a self,
+        printer_name: &'a str,
+        _data: &'a [u8],
+        _wait: Duration,
+    ) -> BoxFuture<'a, Result<Vec<u8>, LabelgoodError>> {
+        Box::pin(async move {
+            Err(LabelgoodError::InvalidInput {
+                message: format!("Replies can't be read from {}", printer_name),
+            })
+        })
+    }
+}
+
+// In the order they're asked whether they handle a printer; `fallback` takes
+// every name the others don't
+fn backends() -> &'static [Box<dyn PrinterBackend>] {
+    static BACKENDS: OnceLock<Vec<Box<dyn PrinterBackend>>> = OnceLock::new();
+    BACKENDS.get_or_init(|| {
+        vec![
+            // First, so a name the user chose can't be taken for another
+            // backend's
+            Box::new(registered::RegisteredBackend),
+            Box::new(ipp::IppBackend),
+            Box::new(socket::SocketBackend),
+            Box::new(usb::UsbBackend),
+            Box::new(plugin::PluginBackend),
+            Box::new(file::FileBackend),
+        ]
+    })
+}
+
+/// "host:port" of a URI authority, adding `port` when it has none;
+/// IPv6 hosts are bracketed, e.g. "[fe80::1]:631"
+pub fn with_default_port(authority: &str, port: u16) -> String {
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, p)| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()));
+    if has_port {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, port)
+    }
+}
+
+// CUPS queues on desktop, or the print portal inside a sandbox that can't
+// reach them; phones and tablets have no CUPS and use the system print
+// service instead
+#[cfg(not(mobile))]
+fn fallback() -> &'static dyn PrinterBackend {
+    static CUPS: cups::CupsBackend = cups::CupsBackend;
+    #[cfg(target_os = "linux")]
+    if crate::sandbox::detect().is_some() {
+        static PORTAL: portal::PortalBackend = portal::PortalBackend;
+        return &PORTAL;
+    }
+    &CUPS
+}
+
+#[cfg(mobile)]
+fn fallback() -> &'static dyn PrinterBackend {
+    static MOBILE: mobile::MobileBackend = mobile::MobileBackend;
+    &MOBILE
+}
+
+/// The backend `printer_name` is printed through
+pub fn resolve(printer_name: &str) -> &'static dyn PrinterBackend {
+    backends()
+        .iter()
+        .find(|backend| backend.handles(printer_name))
+        .map_or(fallback(), |backend| backend.as_ref())
+}
+
+/// Every printer the backends offer, CUPS queues (or the system print
+/// dialog) first. Fails when CUPS can't be asked; other backends that fail
+/// are left out.
+pub async fn enumerate() -> Result<Vec<String>, LabelgoodError> {
+    let mut printers = fallback().enumerate().await?;
+    for backend in backends() {
+        match backend.enumerate().await {
+            Ok(found) => printers.extend(found),
+            Err(e) => warn!("The {} backend failed to list printers: {}", backend.name(), e),
+        }
+    }
+    Ok(printers)
+}
+
+/// Hand a PDF to its printer's backend
+pub async fn submit(job: PrintJob<'_>) -> Result<Submission, LabelgoodError> {
+    let backend = resolve(job.printer);
+    info!("Printing to {} through the {} backend", job.printer, backend.name());
+    let submission = backend.submit(job).await?;
+    if let Some(job_id) = &submission.job_id {
+        info!("{} accepted the job as {}", job.printer, job_id);
+    }
+    Ok(submission)
+}
+
+/// Send commands in the printer's own language through its backend
+pub async fn send_raw(printer_name: &str, data: &[u8]) -> Result<(), LabelgoodError> {
+    let backend = resolve(printer_name);
+    info!("Sending printer commands to {} through the {} backend", printer_name, backend.name());
+    backend.send_raw(printer_name, data).await
+}
+
+/// Send commands in the printer's own language and read its reply
+pub async fn query(printer_name: &str, data: &[u8], wait: Duration) -> Result<Vec<u8>, LabelgoodError> {
+    let backend = resolve(printer_name);
+    info!("Querying {} through the {} backend", printer_name, backend.name());
+    backend.query(printer_name, data, wait).await
+}
+
+#[tauri::command]
+pub async fn get_printer_status(printer_name: String) -> PrinterStatus {
+    resolve(&printer_name).status(&printer_name).await
+}
+
+/// Cancel a job on the printer or spooler it was sent to, by the id its
+/// backend reported
+#[tauri::command]
+pub async fn cancel_printer_job(printer_name: String, job_id: String) -> Result<(), LabelgoodError> {
+    resolve(&printer_name).cancel(&printer_name, &job_id).await
+}
+
+/// How `add_printer` talks to a network printer
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkProtocol {
+    Ipp,
+    Ipps,
+    /// Raw TCP on port 9100 (AppSocket/JetDirect)
+    Raw,
+}
+
+impl NetworkProtocol {
+    // The URI of the printer at `host`, on the protocol's usual port unless
+    // `host` names one
+    fn uri(self, host: &str) -> String {
+        let host = host.trim().trim_end_matches('/');
+        // A bare IPv6 address needs brackets before a port can follow
+        let host = if host.matches(':').count() > 1 && !host.starts_with('[') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        };
+        match self {
+            NetworkProtocol::Ipp => format!("ipp://{}/ipp/print", with_default_port(&host, 631)),
+            NetworkProtocol::Ipps => format!("ipps://{}/ipp/print", with_default_port(&host, 631)),
+            NetworkProtocol::Raw => format!("socket://{}", with_default_port(&host, 9100)),
+        }
+    }
+}
+
+/// Add the network printer at `host` (an IP address or host name) as
+/// `name`: a CUPS queue made with lpadmin when `cups_queue` is set, or else
+/// a printer only Labelgood knows, which needs no admin rights. Returns the
+/// name it's listed under.
+#[tauri::command]
+pub async fn add_printer(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    host: String,
+    protocol: NetworkProtocol,
+    name: String,
+    cups_queue: Option<bool>,
+) -> Result<String, LabelgoodError> {
+    let name = name.trim().to_string();
+    if host.trim().is_empty() || host.contains("://") {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!("Invalid printer address: {}", host),
+        });
+    }
+    if name.is_empty() || name.contains("://") || name.starts_with(crate::groups::PREFIX) {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!("Invalid printer name: {}", name),
+        });
+    }
+    let uri = protocol.uri(&host);
+
+    if cups_queue.unwrap_or(false) {
+        // CUPS only takes printable names without spaces, slashes or #
+        if name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '/' | '\\' | '#'))
+        {
+            return Err(LabelgoodError::InvalidInput {
+                message: format!("Printer queue names can't contain spaces, slashes or #: {}", name),
+            });
+        }
+        cups::add_queue(&name, &uri).await?;
+    } else {
+        if enumerate().await.unwrap_or_default().contains(&name) {
+            return Err(LabelgoodError::InvalidInput {
+                message: format!("There already is a printer named {}", name),
+            });
+        }
+        let mut settings = store.get();
+        settings.network_printers.push(NetworkPrinter {
+            name: name.clone(),
+            uri: uri.clone(),
+        });
+        let printers = settings.network_printers.clone();
+        store.set(settings)?;
+        registered::configure(printers);
+        info!("Registered printer {} at {}", name, uri);
+    }
+    crate::tray::refresh(&app).await?;
+    Ok(name)
+}
@@ -0,0 +1,422 @@
+// Queues of the local CUPS server, the default for any printer name no other
+// backend claims. Jobs are submitted with lpr, or lp where only that is
+// installed, with the label size as a custom page size. Queues with stored
+// credentials are sent their jobs over IPP instead, since neither command
+// can be given a password.
+use super::ipp::{self, Destination};
+use super::{BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::capabilities::{self, PrinterCapabilities};
+use crate::credentials;
+use crate::error::LabelgoodError;
+use crate::handling;
+use crate::i18n;
+use crate::printerinfo::PrinterInfo;
+use crate::printing::{run_command, LPR_TIMEOUT, LPSTAT_TIMEOUT};
+use crate::tools;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Output;
+use tracing::{debug, error, info, warn};
+
+pub struct CupsBackend;
+
+impl PrinterBackend for CupsBackend {
+    fn name(&self) -> &'static str {
+        "cups"
+    }
+
+    fn handles(&self, _printer_name: &str) -> bool {
+        true
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(list_queues())
+    }
+
+    fn capabilities<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterCapabilities> {
+        Box::pin(capabilities::capabilities(printer_name))
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(submit(job))
+    }
+
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(printer_info(printer_name))
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            printer_states()
+                .await
+                .into_iter()
+                .find(|(printer, _, _)| printer == printer_name)
+                .map_or_else(
+                    || PrinterStatus::new(printer_name, "unknown", ""),
+                    |(printer, state, message)| PrinterStatus::new(&printer, &state, message),
+                )
+        })
+    }
+
+    fn cancel<'a>(&'a self, printer_name: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            let mut command = tools::command("cancel");
+            command.arg(job_id);
+            let missing = |e: io::Error| LabelgoodError::DependencyMissing {
+                program: "cancel".to_string(),
+                message: format!("Failed to execute cancel: {}. Make sure CUPS is installed.", e),
+            };
+            let output = run_command(command, LPSTAT_TIMEOUT, missing).await?;
+            if output.status.success() {
+                info!("Cancelled job {} on {}", job_id, printer_name);
+                return Ok(());
+            }
+            Err(LabelgoodError::Other {
+                message: format!(
+                    "Failed to cancel job {}: {}",
+                    job_id,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            })
+        })
+    }
+
+    fn send_raw<'a>(&'a self, printer_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            let io_error = |e: io::Error| LabelgoodError::Io {
+                message: format!("Failed to write printer commands: {}", e),
+            };
+            let mut file = tempfile::NamedTempFile::new().map_err(io_error)?;
+            file.write_all(data).map_err(io_error)?;
+            // "raw" keeps the queue's filters from treating the commands as
+            // a document to print
+            let options = ["raw".to_string()];
+            let (spooler, output) = submit_to_cups(printer_name, &file.path().to_string_lossy(), &options).await?;
+            if output.status.success() {
+                return Ok(());
+            }
+            Err(LabelgoodError::SpoolFailed {
+                printer: printer_name.to_string(),
+                message: i18n::t(
+                    "print-failed",
+                    &[
+                        ("command", spooler.describe(&options).into()),
+                        ("error", String::from_utf8_lossy(&output.stderr).trim().into()),
+                    ],
+                ),
+            })
+        })
+    }
+}
+
+/// Create the queue `name` for the printer at `uri` with lpadmin, driverless
+/// (IPP Everywhere) for IPP printers and raw for the rest. Needs the user to
+/// be allowed to administer CUPS.
+pub async fn add_queue(name: &str, uri: &str) -> Result<(), LabelgoodError> {
+    let model = if uri.starts_with("ipp://") || uri.starts_with("ipps://") {
+        "everywhere"
+    } else {
+        "raw"
+    };
+    let mut command = tools::command("lpadmin");
+    command.args(["-p", name, "-E", "-v", uri, "-m", model]);
+    let output = run_command(command, LPSTAT_TIMEOUT, |e| LabelgoodError::DependencyMissing {
+        program: "lpadmin".to_string(),
+        message: format!("Failed to execute lpadmin: {}. Make sure CUPS is installed.", e),
+    })
+    .await?;
+    if !output.status.success() {
+        return Err(LabelgoodError::PrinterUnavailable {
+            printer: name.to_string(),
+            message: format!(
+                "Failed to create printer queue {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    info!("Created printer queue {} for {}", name, uri);
+    Ok(())
+}
+
+// Every queue, including wireless and network printers, from `lpstat -e`
+async fn list_queues() -> Result<Vec<String>, LabelgoodError> {
+    let mut command = tools::command("lpstat");
+    command.arg("-e");
+    let output = run_command(command, LPSTAT_TIMEOUT, |e| LabelgoodError::DependencyMissing {
+        program: "lpstat".to_string(),
+        message: format!("Failed to execute lpstat: {}. Make sure CUPS is installed.", e),
+    })
+    .await?;
+
+    if !output.status.success() {
+        return Err(LabelgoodError::Other {
+            message: format!("Failed to get printer list: {}", String::from_utf8_lossy(&output.stderr)),
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+async fn submit(job: PrintJob<'_>) -> Result<Submission, LabelgoodError> {
+    let printer_name = job.printer;
+    let pdf_path_str = job.pdf_path.to_string_lossy().to_string();
+    check_media_width(printer_name, job.width_mm, job.height_mm).await?;
+    if let Some(credentials) = credentials::lookup(printer_name).await {
+        info!("Printing to {} over IPP as {}", printer_name, credentials.username);
+        return ipp::print_job(&Destination::cups_queue(printer_name, credentials), job).await;
+    }
+
+    // Print with lpr (or lp) using the exact page dimensions from the PDF
+    // The PDF already has the correct page size, but we need to tell
+    // CUPS/lpr to use that size and not fit it to A4 or other default sizes
+    let width_mm = job.width_mm as u32;
+    let height_mm = job.height_mm as u32;
+
+    info!("PDF path: {}", pdf_path_str);
+    info!("Label dimensions: {}mm x {}mm", width_mm, height_mm);
+
+    // Use PageSize with dimensions in mm - this is more universally supported
+    let mut options = vec![
+        format!("PageSize=Custom.{}x{}mm", width_mm, height_mm),
+        "fit-to-page=false".to_string(),
+    ];
+    if let Some(mode) = handling::for_printer(printer_name) {
+        match handling::cups_option(mode, &printer_options(printer_name).await.unwrap_or_default()) {
+            Some(option) => options.push(option),
+            None => warn!(
+                "The driver of {} has no option for {:?}, printing with its own setting",
+                printer_name, mode
+            ),
+        }
+    }
+    let (spooler, print_output) = submit_to_cups(printer_name, &pdf_path_str, &options).await?;
+    let command = spooler.describe(&options);
+    let stdout = String::from_utf8_lossy(&print_output.stdout);
+
+    if print_output.status.success() {
+        info!("Sent to printer: {}", printer_name);
+        return Ok(Submission {
+            message: i18n::t("printed-to", &[("printer", printer_name.into()), ("command", command.into())]),
+            job_id: request_id(&stdout),
+        });
+    }
+    let stderr = String::from_utf8_lossy(&print_output.stderr);
+    debug!("{} stdout: {}", spooler.program(), stdout);
+    error!("{} stderr: {}", spooler.program(), stderr);
+    // CUPS wording for a queue that needs a login
+    if ["Unauthorized", "Forbidden", "authentication"].iter().any(|phrase| stderr.contains(phrase)) {
+        return Err(LabelgoodError::auth_required(printer_name, false));
+    }
+    let message = i18n::t("print-failed", &[("command", command.into()), ("error", stderr.trim().into())]);
+    let printer = printer_name.to_string();
+    // CUPS wording for a missing, disabled or rejecting destination
+    let unavailable = ["does not exist", "Unknown destination", "not accepting jobs"]
+        .iter()
+        .any(|phrase| stderr.contains(phrase));
+    Err(if unavailable {
+        LabelgoodError::PrinterUnavailable { printer, message }
+    } else {
+        LabelgoodError::SpoolFailed { printer, message }
+    })
+}
+
+// Job id from lp's "request id is QL-820NWB-42 (1 file(s))"; lpr prints none
+fn request_id(stdout: &str) -> Option<String> {
+    let (_, rest) = stdout.split_once("request id is ")?;
+    rest.split_whitespace().next().map(str::to_string)
+}
+
+/// CUPS commands a job can be submitted with, in the order they're tried:
+/// BSD `lpr`, then System V `lp`, which minimal systems without cups-bsd
+/// have on its own. Both take the same `-o` options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spooler {
+    Lpr,
+    Lp,
+}
+
+impl Spooler {
+    pub fn program(self) -> &'static str {
+        match self {
+            Spooler::Lpr => "lpr",
+            Spooler::Lp => "lp",
+        }
+    }
+
+    fn args(self, printer_name: &str, options: &[String], file: &str) -> Vec<String> {
+        let destination = match self {
+            Spooler::Lpr => "-P",
+            Spooler::Lp => "-d",
+        };
+        let mut args = vec![destination.to_string(), printer_name.to_string()];
+        for option in options {
+            args.push("-o".to_string());
+            args.push(option.clone());
+        }
+        args.push(file.to_string());
+        args
+    }
+
+    /// The tool and options, e.g. "lp -o fit-to-page=false", for job results
+    pub fn describe(self, options: &[String]) -> String {
+        let mut description = self.program().to_string();
+        for option in options {
+            description.push_str(" -o ");
+            description.push_str(option);
+        }
+        description
+    }
+}
+
+// Run the first spooler that's installed on `file`. A spooler that ran but
+// failed or timed out isn't followed by the next: the job may have been
+// queued already.
+async fn submit_to_cups(
+    printer_name: &str,
+    file: &str,
+    options: &[String],
+) -> Result<(Spooler, Output), LabelgoodError> {
+    for spooler in [Spooler::Lpr, Spooler::Lp] {
+        let args = spooler.args(printer_name, options, file);
+        info!("Running: {} {}", spooler.program(), args.join(" "));
+        let mut command = tools::command(spooler.program());
+        command.args(&args);
+        let missing = |e: io::Error| LabelgoodError::DependencyMissing {
+            program: spooler.program().to_string(),
+            message: e.to_string(),
+        };
+        match run_command(command, LPR_TIMEOUT, missing).await {
+            Ok(output) => return Ok((spooler, output)),
+            Err(LabelgoodError::DependencyMissing { message, .. }) => {
+                warn!("Can't run {}: {}", spooler.program(), message);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(LabelgoodError::DependencyMissing {
+        program: "lpr".to_string(),
+        message: "Failed to execute lpr or lp. Make sure CUPS is installed.".to_string(),
+    })
+}
+
+/// Refuse labels wider than every medium the printer offers, which it would
+/// otherwise silently cut off, e.g. a 100mm design on 62mm tape. Labels fit
+/// either way round. Printers whose media can't be read aren't checked.
+async fn check_media_width(printer_name: &str, width_mm: f64, height_mm: f64) -> Result<(), LabelgoodError> {
+    let capabilities = capabilities::capabilities(printer_name).await;
+    match capabilities::check_fit(&capabilities, width_mm, height_mm)
+        .into_iter()
+        .find(|warning| warning.code == "too_wide")
+    {
+        Some(warning) => Err(LabelgoodError::InvalidDimensions {
+            width_mm,
+            height_mm,
+            message: warning.message,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The printer's PPD options as listed by `lpoptions -l`, if CUPS knows it
+pub async fn printer_options(printer_name: &str) -> Option<String> {
+    let mut command = tools::command("lpoptions");
+    command.arg("-p").arg(printer_name).arg("-l");
+    let missing = |e: io::Error| LabelgoodError::Io { message: e.to_string() };
+    let output = run_command(command, LPSTAT_TIMEOUT, missing).await.ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The selected Resolution choice in `lpoptions -l` output, e.g.
+/// "Resolution/Resolution: 203dpi *300dpi" or "Resolution/Quality: *300x600dpi"
+pub fn default_resolution(options: &str) -> Option<f64> {
+    let line = options.lines().find(|line| line.starts_with("Resolution/"))?;
+    let (_, choices) = line.split_once(':')?;
+    let choice = choices.split_whitespace().find_map(|choice| choice.strip_prefix('*'))?;
+    let dpi = choice.to_ascii_lowercase();
+    let dpi = dpi.strip_suffix("dpi")?;
+    let horizontal = dpi.split_once('x').map_or(dpi, |(x, _)| x);
+    horizontal.parse::<f64>().ok().filter(|dpi| *dpi > 0.0)
+}
+
+// What the queue's PPD and device URI say, topped up with what the printer
+// itself answers when the queue reaches it over the network
+async fn printer_info(printer_name: &str) -> PrinterInfo {
+    let mut info = PrinterInfo::new(printer_name);
+    let ppd = fs::read_to_string(Path::new(capabilities::PPD_DIR).join(format!("{}.ppd", printer_name)))
+        .unwrap_or_default();
+    let ppd_value = |keyword: &str| {
+        ppd.lines()
+            .find_map(|line| line.strip_prefix(keyword)?.strip_prefix(':'))
+            .map(|value| value.trim().trim_matches('"').to_string())
+            .filter(|value| !value.is_empty())
+    };
+    info.make = ppd_value("*Manufacturer");
+    // "*ModelName" repeats the make, e.g. "Zebra ZD420"
+    info.model = ppd_value("*ModelName").map(|model| match &info.make {
+        Some(make) => model.strip_prefix(make.as_str()).unwrap_or(&model).trim().to_string(),
+        None => model,
+    });
+    info.resolution_dpi = printer_options(printer_name).await.as_deref().and_then(default_resolution);
+
+    let Some(uri) = device_uri(printer_name).await else {
+        return info;
+    };
+    // CUPS USB URIs carry the serial number, e.g.
+    // "usb://Brother/QL-820NWB?serial=000G1Z123456"
+    info.serial = uri
+        .split_once("?serial=")
+        .and_then(|(_, serial)| serial.split('&').next())
+        .map(str::to_string);
+    if ["ipp://", "ipps://", "socket://"].iter().any(|scheme| uri.starts_with(scheme)) {
+        let mut reported = super::resolve(&uri).info(&uri).await;
+        reported.printer = info.printer.clone();
+        reported.merge(info);
+        return reported;
+    }
+    info
+}
+
+/// The device URI of a queue, from "device for QL-820NWB: usb://..." in
+/// `lpstat -v`
+pub async fn device_uri(printer_name: &str) -> Option<String> {
+    let mut command = tools::command("lpstat");
+    command.arg("-v").arg(printer_name);
+    let missing = |e: io::Error| LabelgoodError::Io { message: e.to_string() };
+    let output = run_command(command, LPSTAT_TIMEOUT, missing).await.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|line| line.starts_with("device for "))?;
+    line.split_once(": ").map(|(_, uri)| uri.trim().to_string())
+}
+
+/// (printer, status, full lpstat line) from `lpstat -p`, e.g.
+/// "printer QL-820NWB disabled since Tue 14 Oct 2026 09:12:01 - Paused"
+pub async fn printer_states() -> Vec<(String, String, String)> {
+    let mut command = tools::command("lpstat");
+    command.arg("-p");
+    let missing = |e: io::Error| LabelgoodError::Io { message: e.to_string() };
+    let Ok(output) = run_command(command, LPSTAT_TIMEOUT, missing).await else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("printer ")?;
+            let (name, state) = rest.split_once(' ')?;
+            let status = if state.starts_with("disabled") {
+                "disabled"
+            } else if state.contains("now printing") {
+                "printing"
+            } else {
+                "idle"
+            };
+            Some((name.to_string(), status.to_string(), line.trim().to_string()))
+        })
+        .collect()
+}
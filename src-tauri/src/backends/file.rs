@@ -0,0 +1,117 @@
+// Printers that save instead of print: the virtual printer, and file:// URIs
+// naming a directory each job's PDF is copied into. Printer names can come
+// from the HTTP API, MQTT and the hot folder, so file:// printers only save
+// inside directories allowed in settings.
+use super::{BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::virtual_printer::{self, VIRTUAL_PRINTER};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tracing::info;
+
+const PREFIX: &str = "file://";
+
+static ALLOWED_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+pub struct FileBackend;
+
+/// Let file:// printers save inside `dirs` and their subdirectories
+pub fn configure(dirs: Vec<String>) {
+    *ALLOWED_DIRS.lock().unwrap() = dirs.into_iter().filter(|d| !d.is_empty()).map(PathBuf::from).collect();
+}
+
+// `dir` with symlinks resolved as far as it exists; None for relative paths
+// or ones going up with ".."
+fn resolve(dir: &Path) -> Option<PathBuf> {
+    if !dir.is_absolute() || dir.components().any(|c| c == Component::ParentDir) {
+        return None;
+    }
+    let mut existing = dir;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        rest.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+    let mut resolved = fs::canonicalize(existing).ok()?;
+    resolved.extend(rest.into_iter().rev());
+    Some(resolved)
+}
+
+// The directory of a file:// printer, if settings allow saving there
+fn allowed_dir(printer_name: &str, dir: &Path) -> Result<PathBuf, LabelgoodError> {
+    let refused = || LabelgoodError::InvalidInput {
+        message: format!(
+            "{} isn't an output directory allowed in settings; add it to let {} save there",
+            dir.display(),
+            printer_name
+        ),
+    };
+    let resolved = resolve(dir).ok_or_else(refused)?;
+    let allowed = ALLOWED_DIRS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|allowed| fs::canonicalize(allowed).ok())
+        .any(|allowed| resolved.starts_with(allowed));
+    if allowed {
+        Ok(resolved)
+    } else {
+        Err(refused())
+    }
+}
+
+impl PrinterBackend for FileBackend {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        printer_name == VIRTUAL_PRINTER || printer_name.starts_with(PREFIX)
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async { Ok(vec![VIRTUAL_PRINTER.to_string()]) })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            let message = match job.printer.strip_prefix(PREFIX) {
+                Some(dir) => save(job.pdf_path, &allowed_dir(job.printer, Path::new(dir))?)?,
+                None => virtual_printer::print(job.pdf_path, job.width_mm, job.height_mm).await?,
+            };
+            Ok(Submission { message, job_id: None })
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            // The directory is created on the first job, but a file in its
+            // place can't be replaced
+            let Some(dir) = printer_name.strip_prefix(PREFIX).map(Path::new) else {
+                return PrinterStatus::new(printer_name, "idle", "");
+            };
+            match allowed_dir(printer_name, dir) {
+                Err(e) => PrinterStatus::new(printer_name, "offline", e.to_string()),
+                Ok(dir) if dir.exists() && !dir.is_dir() => {
+                    PrinterStatus::new(printer_name, "offline", format!("{} is not a directory", dir.display()))
+                }
+                _ => PrinterStatus::new(printer_name, "idle", ""),
+            }
+        })
+    }
+}
+
+// Copy the PDF into `dir` under a timestamped name
+fn save(pdf_path: &Path, dir: &Path) -> Result<String, LabelgoodError> {
+    let io_err = |e: std::io::Error| LabelgoodError::Io {
+        message: format!("Failed to save PDF to {}: {}", dir.display(), e),
+    };
+    fs::create_dir_all(dir).map_err(io_err)?;
+    let stem = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+    let target = dir.join(format!("{}.pdf", stem));
+    fs::copy(pdf_path, &target).map_err(io_err)?;
+    info!("Saved {} to {}", pdf_path.display(), target.display());
+    Ok(i18n::t("virtual-print-saved", &[("path", target.display().to_string().into())]))
+}
@@ -0,0 +1,517 @@
+// Internet Printing Protocol, for printers addressed by ipp:// or ipps:// URI:
+// driverless network printers, which take PDF directly, and queues shared by
+// other CUPS servers. Only the operations Labelgood needs are encoded
+// (RFC 8010), posted over HTTP with ureq. Stored credentials are sent with
+// HTTP Basic authentication, which only ipps:// keeps private.
+use super::{with_default_port, BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::capabilities::{self, PrinterCapabilities};
+use crate::credentials::{self, Credentials};
+use crate::error::LabelgoodError;
+use crate::handling::{self, LabelHandling};
+use crate::i18n;
+use crate::printerinfo::PrinterInfo;
+use crate::printing::{LPR_TIMEOUT, RENDER_DPI};
+use crate::progress;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::fs;
+use std::net::IpAddr;
+use tracing::{debug, info, warn};
+
+const DEFAULT_PORT: u16 = 631;
+
+// Operations
+const PRINT_JOB: u16 = 0x0002;
+const CANCEL_JOB: u16 = 0x0008;
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+
+// Delimiter and value tags
+const OPERATION_ATTRIBUTES: u8 = 0x01;
+const JOB_ATTRIBUTES: u8 = 0x02;
+const END_OF_ATTRIBUTES: u8 = 0x03;
+const INTEGER: u8 = 0x21;
+const ENUM: u8 = 0x23;
+const TEXT: u8 = 0x41;
+const NAME: u8 = 0x42;
+const KEYWORD: u8 = 0x44;
+const URI: u8 = 0x45;
+const CHARSET: u8 = 0x47;
+const NATURAL_LANGUAGE: u8 = 0x48;
+const MIME_MEDIA_TYPE: u8 = 0x49;
+
+// Status codes for a printer that can't take jobs at all, rather than a job
+// it refused: client-error-not-found, server-error-service-unavailable,
+// server-error-not-accepting-jobs
+const UNAVAILABLE: [u16; 3] = [0x0406, 0x0502, 0x0506];
+// client-error-not-authenticated and client-error-not-authorized
+const UNAUTHORIZED: [u16; 2] = [0x0402, 0x0403];
+
+// finishings values: none, and a cut after every page (label)
+const FINISHINGS_NONE: i32 = 3;
+const FINISHINGS_TRIM_AFTER_PAGES: i32 = 60;
+
+/// An IPP printer and who requests to it are sent as
+pub struct Destination {
+    /// Printer name as listed, for messages
+    pub printer: String,
+    pub uri: String,
+    pub credentials: Option<Credentials>,
+    /// Whether to hand the credentials to CUPS as the job's auth-info too,
+    /// for queues whose backend logs in to the printer, e.g. SMB shares
+    pub auth_info: bool,
+}
+
+impl Destination {
+    /// An ipp:// or ipps:// printer, with the credentials stored for it
+    pub async fn printer(printer_name: &str) -> Self {
+        Destination {
+            printer: printer_name.to_string(),
+            uri: printer_name.to_string(),
+            credentials: credentials::lookup(printer_name).await,
+            auth_info: false,
+        }
+    }
+
+    /// A queue of the local CUPS server
+    pub fn cups_queue(queue: &str, credentials: Credentials) -> Self {
+        Destination {
+            printer: queue.to_string(),
+            uri: format!("ipp://localhost/printers/{}", queue),
+            credentials: Some(credentials),
+            auth_info: true,
+        }
+    }
+
+    fn unauthorized(&self) -> LabelgoodError {
+        LabelgoodError::auth_required(&self.printer, self.credentials.is_some())
+    }
+}
+
+pub struct IppBackend;
+
+impl PrinterBackend for IppBackend {
+    fn name(&self) -> &'static str {
+        "ipp"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        printer_name.starts_with("ipp://") || printer_name.starts_with("ipps://")
+    }
+
+    fn capabilities<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterCapabilities> {
+        Box::pin(async move {
+            let destination = Destination::printer(printer_name).await;
+            let mut request = Request::new(GET_PRINTER_ATTRIBUTES, &destination);
+            request
+                .attribute(KEYWORD, "requested-attributes", b"printer-resolution-default")
+                .value(KEYWORD, b"media-supported");
+            let response = match call(&destination, request.finish(&[])).await {
+                Ok(response) if response.succeeded() => response,
+                Ok(response) => {
+                    debug!("{} sent no printer attributes: {}", printer_name, response.message());
+                    return PrinterCapabilities::unknown(printer_name);
+                }
+                Err(e) => {
+                    debug!("Failed to ask {} for its capabilities: {}", printer_name, e);
+                    return PrinterCapabilities::unknown(printer_name);
+                }
+            };
+            let dpi = response.value("printer-resolution-default").and_then(resolution_dpi);
+            let media: Vec<String> = response
+                .values("media-supported")
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .collect();
+            capabilities::from_media_names(printer_name, dpi.unwrap_or(RENDER_DPI), &media)
+        })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move { print_job(&Destination::printer(job.printer).await, job).await })
+    }
+
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(async move {
+            let mut info = PrinterInfo::new(printer_name);
+            let destination = Destination::printer(printer_name).await;
+            let mut request = Request::new(GET_PRINTER_ATTRIBUTES, &destination);
+            request
+                .attribute(KEYWORD, "requested-attributes", b"printer-device-id")
+                .value(KEYWORD, b"printer-make-and-model")
+                .value(KEYWORD, b"printer-firmware-string-version")
+                .value(KEYWORD, b"printer-resolution-default");
+            match call(&destination, request.finish(&[])).await {
+                Ok(response) if response.succeeded() => {
+                    if let Some(device_id) = response.text("printer-device-id") {
+                        info.add_device_id(&device_id);
+                    }
+                    if let Some(make_and_model) = response.text("printer-make-and-model") {
+                        info.add_make_and_model(&make_and_model);
+                    }
+                    info.firmware = response.text("printer-firmware-string-version");
+                    info.resolution_dpi = response.value("printer-resolution-default").and_then(resolution_dpi);
+                }
+                Ok(response) => debug!("{} sent no printer attributes: {}", printer_name, response.message()),
+                Err(e) => debug!("Failed to ask {} about itself: {}", printer_name, e),
+            }
+            info
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            let destination = Destination::printer(printer_name).await;
+            let mut request = Request::new(GET_PRINTER_ATTRIBUTES, &destination);
+            request
+                .attribute(KEYWORD, "requested-attributes", b"printer-state")
+                .value(KEYWORD, b"printer-state-message")
+                .value(KEYWORD, b"printer-state-reasons");
+            let response = match call(&destination, request.finish(&[])).await {
+                Ok(response) if response.succeeded() => response,
+                Ok(response) => return PrinterStatus::new(printer_name, "unknown", response.message()),
+                Err(e @ LabelgoodError::AuthRequired { .. }) => {
+                    return PrinterStatus::new(printer_name, "unknown", e.to_string())
+                }
+                Err(e) => return PrinterStatus::new(printer_name, "offline", e.to_string()),
+            };
+            let state = match response.value("printer-state").and_then(integer) {
+                Some(3) => "idle",
+                Some(4) => "printing",
+                Some(5) => "disabled",
+                _ => "unknown",
+            };
+            let message = response.text("printer-state-message").unwrap_or_else(|| {
+                let reasons: Vec<String> = response
+                    .values("printer-state-reasons")
+                    .map(|reason| String::from_utf8_lossy(reason).to_string())
+                    .filter(|reason| reason != "none")
+                    .collect();
+                reasons.join(", ")
+            });
+            PrinterStatus::new(printer_name, state, message)
+        })
+    }
+
+    fn cancel<'a>(&'a self, printer_name: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move { cancel_job(&Destination::printer(printer_name).await, job_id).await })
+    }
+}
+
+/// Send a PDF with Print-Job, at the label size and unscaled
+pub async fn print_job(destination: &Destination, job: PrintJob<'_>) -> Result<Submission, LabelgoodError> {
+    let document = fs::read(job.pdf_path).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to read PDF: {}", e),
+    })?;
+    let job_name = job.pdf_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    // The label size as a PWG custom media name, e.g.
+    // "custom_62x29mm_62x29mm"
+    let size = format!("{}x{}mm", job.width_mm, job.height_mm);
+    let media = format!("custom_{}_{}", size, size);
+
+    let mut request = Request::new(PRINT_JOB, destination);
+    request
+        .user(destination)
+        .attribute(NAME, "job-name", job_name.as_bytes())
+        .attribute(MIME_MEDIA_TYPE, "document-format", b"application/pdf");
+    if let Some(credentials) = destination.credentials.as_ref().filter(|_| destination.auth_info) {
+        request
+            .attribute(TEXT, "auth-info", credentials.username.as_bytes())
+            .value(TEXT, credentials.password.as_bytes());
+    }
+    request
+        .group(JOB_ATTRIBUTES)
+        .attribute(KEYWORD, "media", media.as_bytes())
+        .attribute(KEYWORD, "print-scaling", b"none");
+    // IPP can say whether to cut, but has no peel or applicator modes
+    match handling::for_printer(job.printer) {
+        Some(LabelHandling::Cutter) => {
+            request.attribute(ENUM, "finishings", &FINISHINGS_TRIM_AFTER_PAGES.to_be_bytes());
+        }
+        Some(LabelHandling::TearOff) => {
+            request.attribute(ENUM, "finishings", &FINISHINGS_NONE.to_be_bytes());
+        }
+        Some(mode) => warn!("IPP has no option for {:?}, {} prints with its own setting", mode, job.printer),
+        None => {}
+    }
+    let response = call(destination, request.finish(&document)).await?;
+
+    if !response.succeeded() {
+        let printer = destination.printer.clone();
+        if UNAUTHORIZED.contains(&response.status) {
+            return Err(destination.unauthorized());
+        }
+        let message = i18n::t(
+            "print-failed",
+            &[("command", "IPP Print-Job".into()), ("error", response.message().into())],
+        );
+        return Err(if UNAVAILABLE.contains(&response.status) {
+            LabelgoodError::PrinterUnavailable { printer, message }
+        } else {
+            LabelgoodError::SpoolFailed { printer, message }
+        });
+    }
+    let job_id = response.value("job-id").and_then(integer).map(|id| id.to_string());
+
+    // CUPS accepts jobs for queues that need credentials it wasn't given and
+    // holds them until someone authenticates. Cancel it so printing again
+    // after logging in doesn't print twice.
+    if response.values("job-state-reasons").any(|reason| reason == b"cups-held-for-authentication") {
+        if let Some(id) = &job_id {
+            if let Err(e) = cancel_job(destination, id).await {
+                warn!("Failed to cancel job {} held for authentication: {}", id, e);
+            }
+        }
+        return Err(destination.unauthorized());
+    }
+
+    info!("Sent to {} as job {:?}", destination.printer, job_id);
+    let printer = destination.printer.as_str();
+    let message = match &job_id {
+        Some(id) => i18n::t("sent-to-job", &[("printer", printer.into()), ("job", id.as_str().into())]),
+        None => i18n::t("sent-to", &[("printer", printer.into())]),
+    };
+    Ok(Submission { message, job_id })
+}
+
+/// Cancel a job `print_job` reported the id of
+pub async fn cancel_job(destination: &Destination, job_id: &str) -> Result<(), LabelgoodError> {
+    let id: i32 = job_id.parse().map_err(|_| LabelgoodError::InvalidInput {
+        message: format!("{} is not an IPP job id", job_id),
+    })?;
+    let mut request = Request::new(CANCEL_JOB, destination);
+    request.attribute(INTEGER, "job-id", &id.to_be_bytes()).user(destination);
+    let response = call(destination, request.finish(&[])).await?;
+    if UNAUTHORIZED.contains(&response.status) {
+        return Err(destination.unauthorized());
+    }
+    if !response.succeeded() {
+        return Err(LabelgoodError::Other {
+            message: format!("Failed to cancel job {}: {}", job_id, response.message()),
+        });
+    }
+    info!("Cancelled job {} on {}", job_id, destination.printer);
+    Ok(())
+}
+
+// An IPP request being encoded; attributes go into the operation group until
+// another group is started
+struct Request {
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn new(operation: u16, destination: &Destination) -> Self {
+        // Version 2.0, the operation and request id 1
+        let mut body = vec![2, 0];
+        body.extend_from_slice(&operation.to_be_bytes());
+        body.extend_from_slice(&1u32.to_be_bytes());
+        body.push(OPERATION_ATTRIBUTES);
+        let mut request = Request { body };
+        request
+            .attribute(CHARSET, "attributes-charset", b"utf-8")
+            .attribute(NATURAL_LANGUAGE, "attributes-natural-language", i18n::language().as_bytes())
+            .attribute(URI, "printer-uri", destination.uri.as_bytes());
+        request
+    }
+
+    fn attribute(&mut self, tag: u8, name: &str, value: &[u8]) -> &mut Self {
+        self.body.push(tag);
+        self.body.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        self.body.extend_from_slice(name.as_bytes());
+        self.body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        self.body.extend_from_slice(value);
+        self
+    }
+
+    // Another value of the attribute before
+    fn value(&mut self, tag: u8, value: &[u8]) -> &mut Self {
+        self.attribute(tag, "", value)
+    }
+
+    // The stored username, or the system account's
+    fn user(&mut self, destination: &Destination) -> &mut Self {
+        let user = match &destination.credentials {
+            Some(credentials) => credentials.username.clone(),
+            None => std::env::var("USER").unwrap_or_else(|_| "labelgood".to_string()),
+        };
+        self.attribute(NAME, "requesting-user-name", user.as_bytes())
+    }
+
+    fn group(&mut self, tag: u8) -> &mut Self {
+        self.body.push(tag);
+        self
+    }
+
+    fn finish(&mut self, document: &[u8]) -> Vec<u8> {
+        let mut body = std::mem::take(&mut self.body);
+        body.push(END_OF_ATTRIBUTES);
+        body.extend_from_slice(document);
+        body
+    }
+}
+
+struct Response {
+    status: u16,
+    /// (name, value tag, value), additional values under their attribute's name
+    attributes: Vec<(String, u8, Vec<u8>)>,
+}
+
+impl Response {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let status = u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]);
+        let mut attributes = Vec::new();
+        let mut name = String::new();
+        let mut pos = 8;
+        while let Some(&tag) = bytes.get(pos) {
+            pos += 1;
+            if tag == END_OF_ATTRIBUTES {
+                break;
+            }
+            // Any other delimiter starts a group
+            if tag < 0x10 {
+                continue;
+            }
+            let attribute_name = take(bytes, &mut pos)?;
+            let value = take(bytes, &mut pos)?;
+            if !attribute_name.is_empty() {
+                name = String::from_utf8_lossy(attribute_name).to_string();
+            }
+            attributes.push((name.clone(), tag, value.to_vec()));
+        }
+        Some(Response { status, attributes })
+    }
+
+    // Successful status codes are 0x0000 to 0x00FF
+    fn succeeded(&self) -> bool {
+        self.status < 0x0100
+    }
+
+    fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.attributes
+            .iter()
+            .filter(move |(n, _, _)| n == name)
+            .map(|(_, _, value)| value.as_slice())
+    }
+
+    fn value(&self, name: &str) -> Option<&[u8]> {
+        self.values(name).next()
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.value(name)
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|text| !text.is_empty())
+    }
+
+    fn message(&self) -> String {
+        self.text("status-message")
+            .unwrap_or_else(|| format!("IPP status 0x{:04x}", self.status))
+    }
+}
+
+// A length-prefixed field at `pos`, moving past it
+fn take<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let length = u16::from_be_bytes([*bytes.get(*pos)?, *bytes.get(*pos + 1)?]) as usize;
+    let field = bytes.get(*pos + 2..*pos + 2 + length)?;
+    *pos += 2 + length;
+    Some(field)
+}
+
+fn integer(value: &[u8]) -> Option<i32> {
+    Some(i32::from_be_bytes(value.try_into().ok()?))
+}
+
+// Horizontal dots per inch of a resolution value: cross-feed, feed, units
+// (3 per inch, 4 per cm)
+fn resolution_dpi(value: &[u8]) -> Option<f64> {
+    let horizontal = integer(value.get(..4)?)? as f64;
+    let dpi = match *value.get(8)? {
+        3 => horizontal,
+        4 => horizontal * 2.54,
+        _ => return None,
+    };
+    (dpi > 0.0).then_some(dpi)
+}
+
+// The HTTP URL a printer URI is posted to, e.g. ipp://printer.local/ipp/print
+// gives http://printer.local:631/ipp/print
+fn http_url(printer_uri: &str) -> Option<String> {
+    let (scheme, rest) = match printer_uri.strip_prefix("ipps://") {
+        Some(rest) => ("https", rest),
+        None => ("http", printer_uri.strip_prefix("ipp://")?),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if authority.is_empty() {
+        return None;
+    }
+    Some(format!("{}://{}{}", scheme, with_default_port(authority, DEFAULT_PORT), path))
+}
+
+// Whether `url` (from `http_url`) is encrypted or stays on this machine, so
+// credentials sent to it can't be read on the network
+fn private_url(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return url.starts_with("https://");
+    };
+    let authority = &rest[..rest.find('/').unwrap_or(rest.len())];
+    let host = match authority.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => authority.rsplit_once(':').map_or(authority, |(host, _)| host),
+    };
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+// Post `body` to the printer, without blocking the async runtime. A printer
+// that can't be reached is unavailable; one that answers HTTP 401 or 403
+// wants (other) credentials. Credentials are only sent over ipps://, or to
+// this machine, since Basic auth over ipp:// is readable by anyone on the
+// network.
+async fn call(destination: &Destination, body: Vec<u8>) -> Result<Response, LabelgoodError> {
+    let url = http_url(&destination.uri).ok_or_else(|| LabelgoodError::InvalidInput {
+        message: format!("{} is not a valid IPP printer URI", destination.uri),
+    })?;
+    if destination.credentials.is_some() && !private_url(&url) {
+        return Err(LabelgoodError::InvalidInput {
+            message: i18n::t(
+                "ipp-credentials-cleartext",
+                &[
+                    ("printer", destination.printer.as_str().into()),
+                    ("uri", destination.uri.as_str().into()),
+                ],
+            ),
+        });
+    }
+    let unavailable = |message: String| LabelgoodError::PrinterUnavailable {
+        printer: destination.printer.clone(),
+        message,
+    };
+    let authorization = destination.credentials.as_ref().map(|credentials| {
+        let pair = format!("{}:{}", credentials.username, credentials.password);
+        format!("Basic {}", BASE64.encode(pair))
+    });
+    let posting = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<u8>, Option<String>> {
+        let agent: ureq::Agent = ureq::Agent::config_builder().timeout_global(Some(LPR_TIMEOUT)).build().into();
+        let mut request = agent.post(&url).header("Content-Type", "application/ipp");
+        if let Some(authorization) = &authorization {
+            request = request.header("Authorization", authorization.as_str());
+        }
+        let mut response = match request.send(&body[..]) {
+            Ok(response) => response,
+            Err(ureq::Error::StatusCode(401 | 403)) => return Err(None),
+            Err(e) => return Err(Some(format!("Failed to reach {}: {}", url, e))),
+        };
+        response
+            .body_mut()
+            .read_to_vec()
+            .map_err(|e| Some(format!("Failed to read the reply from {}: {}", url, e)))
+    });
+    // The request can't be aborted; cancelling stops waiting for it
+    let bytes = tokio::select! {
+        result = posting => match result.map_err(|e| Some(e.to_string())).and_then(|r| r) {
+            Ok(bytes) => bytes,
+            Err(Some(message)) => return Err(unavailable(message)),
+            Err(None) => return Err(destination.unauthorized()),
+        },
+        cancelled = progress::cancelled() => return Err(cancelled),
+    };
+    Response::parse(&bytes).ok_or_else(|| unavailable(format!("{} sent an invalid IPP reply", destination.uri)))
+}
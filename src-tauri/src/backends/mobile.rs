@@ -0,0 +1,83 @@
+// Printing on Android and iOS, which have no CUPS, lpr or ImageMagick. Labels
+// are rendered and written to PDF in Rust as on desktop; network printers
+// are then reached by URI through the IPP and raw TCP backends, and
+// everything else goes to the system print service: the PDF is handed to the
+// OS, which offers it to its print dialog and any installed print plugins.
+use super::{BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::error::LabelgoodError;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use tauri_plugin_opener::OpenerExt;
+use tracing::info;
+
+/// Printer name of the system print service, listed on phones and tablets
+pub const SYSTEM_PRINTER: &str = "System Print Service";
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+pub struct MobileBackend;
+
+/// Keep the app handle the OS is reached through
+pub fn init(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+}
+
+/// Hand a PDF to the OS to print or share
+pub fn open(pdf_path: &Path) -> Result<(), LabelgoodError> {
+    let app = APP.get().ok_or_else(|| LabelgoodError::Other {
+        message: "The system print service isn't available yet".to_string(),
+    })?;
+    app.opener()
+        .open_path(pdf_path.to_string_lossy(), None::<&str>)
+        .map_err(|e| LabelgoodError::Io {
+            message: format!("Failed to open PDF: {}", e),
+        })
+}
+
+impl PrinterBackend for MobileBackend {
+    fn name(&self) -> &'static str {
+        "system print service"
+    }
+
+    // Takes every name no other backend claims, like CUPS on desktop
+    fn handles(&self, _printer_name: &str) -> bool {
+        true
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async { Ok(vec![SYSTEM_PRINTER.to_string()]) })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            if job.printer != SYSTEM_PRINTER {
+                // Not PrinterUnavailable, which would hold the job for a
+                // printer that never comes back
+                return Err(LabelgoodError::InvalidInput {
+                    message: format!(
+                        "{} isn't a printer this device can reach; use {} or a printer address like \
+                         ipp://printer.local/ipp/print or socket://printer.local:9100",
+                        job.printer, SYSTEM_PRINTER
+                    ),
+                });
+            }
+            open(job.pdf_path)?;
+            info!("Handed {} to the system print service", job.pdf_path.display());
+            Ok(Submission {
+                message: "Sent to the system print dialog".to_string(),
+                job_id: None,
+            })
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            if printer_name == SYSTEM_PRINTER {
+                PrinterStatus::new(printer_name, "idle", "")
+            } else {
+                PrinterStatus::new(printer_name, "offline", "Not reachable from this device")
+            }
+        })
+    }
+}
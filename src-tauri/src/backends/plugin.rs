@@ -0,0 +1,45 @@
+// Printers of plugin drivers, listed as "driver:printer"; see `plugins`
+use super::{BoxFuture, PrintJob, PrinterBackend, Submission};
+use crate::error::LabelgoodError;
+use crate::plugins;
+use crate::progress;
+
+pub struct PluginBackend;
+
+impl PrinterBackend for PluginBackend {
+    fn name(&self) -> &'static str {
+        "plugin"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        plugins::registry().resolve_printer(printer_name).is_some()
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async { Ok(plugins::registry().printers()) })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            let failed = |message: String| LabelgoodError::SpoolFailed {
+                printer: job.printer.to_string(),
+                message,
+            };
+            let (driver, printer) = plugins::registry()
+                .resolve_printer(job.printer)
+                .ok_or_else(|| failed(format!("No plugin driver for {}", job.printer)))?;
+            let (printer, path) = (printer.to_string(), job.pdf_path.to_path_buf());
+            let (width_mm, height_mm) = (job.width_mm, job.height_mm);
+            let printing =
+                tauri::async_runtime::spawn_blocking(move || driver.print_pdf(&printer, &path, width_mm, height_mm));
+            // A driver runs in-process and can't be killed; cancelling stops
+            // waiting for it and leaves it to finish on its own
+            let result = tokio::select! {
+                result = printing => result.map_err(|e| e.to_string()).and_then(|r| r),
+                cancelled = progress::cancelled() => return Err(cancelled),
+            };
+            let message = result.map_err(failed)?;
+            Ok(Submission { message, job_id: None })
+        })
+    }
+}
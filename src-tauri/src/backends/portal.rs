@@ -0,0 +1,128 @@
+// The XDG print portal, for Flatpak and Snap builds: the sandbox has no lpr
+// and can't see CUPS queues, so the PDF is passed to
+// org.freedesktop.portal.Print, which shows the desktop's print dialog
+// outside the sandbox and prints to whichever printer is picked there.
+// Names of CUPS queues from before the app was packaged print through the
+// dialog too; socket:// and ipp:// printers keep printing without one.
+use super::{BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::error::LabelgoodError;
+use crate::progress;
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::info;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{Fd, OwnedObjectPath, OwnedValue, Value};
+
+/// Printer name of the print dialog, listed when running sandboxed
+pub const PORTAL_PRINTER: &str = "System Print Dialog";
+
+const PORTAL_SERVICE: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+pub struct PortalBackend;
+
+impl PrinterBackend for PortalBackend {
+    fn name(&self) -> &'static str {
+        "print portal"
+    }
+
+    // Takes every name no other backend claims, like CUPS outside a sandbox
+    fn handles(&self, _printer_name: &str) -> bool {
+        true
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async { Ok(vec![PORTAL_PRINTER.to_string()]) })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            if job.printer != PORTAL_PRINTER {
+                info!(
+                    "{} isn't reachable from the sandbox, asking in the print dialog",
+                    job.printer
+                );
+            }
+            let pdf_path = job.pdf_path.to_path_buf();
+            let printer = job.printer.to_string();
+            let printing = tauri::async_runtime::spawn_blocking(move || print(&pdf_path, &printer));
+            tokio::select! {
+                result = printing => result.map_err(|e| LabelgoodError::Other { message: e.to_string() })??,
+                cancelled = progress::cancelled() => return Err(cancelled),
+            }
+            Ok(Submission {
+                message: "Printed through the system print dialog".to_string(),
+                job_id: None,
+            })
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move { PrinterStatus::new(printer_name, "idle", "") })
+    }
+}
+
+// Show the print dialog for `pdf_path` and wait until it's printed or
+// dismissed
+fn print(pdf_path: &Path, printer: &str) -> Result<(), LabelgoodError> {
+    static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+    let portal_error = |e: zbus::Error| LabelgoodError::SpoolFailed {
+        printer: printer.to_string(),
+        message: format!("The print portal failed: {}", e),
+    };
+
+    let connection = Connection::session().map_err(portal_error)?;
+    let portal =
+        Proxy::new(&connection, PORTAL_SERVICE, PORTAL_PATH, "org.freedesktop.portal.Print").map_err(portal_error)?;
+
+    // The reply comes as a signal on a request object whose path follows
+    // from our bus name and a token; listen before asking so it can't be
+    // missed
+    let token = format!("labelgood{}", NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+    let sender = connection
+        .unique_name()
+        .map(|name| name.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default();
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+    let request = Proxy::new(
+        &connection,
+        PORTAL_SERVICE,
+        request_path,
+        "org.freedesktop.portal.Request",
+    )
+    .map_err(portal_error)?;
+    let mut responses = request.receive_signal("Response").map_err(portal_error)?;
+
+    let file = File::open(pdf_path).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to open {}: {}", pdf_path.display(), e),
+    })?;
+    let title = pdf_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("handle_token", Value::from(token.as_str()));
+    options.insert("modal", Value::from(true));
+    let _: OwnedObjectPath = portal
+        .call("Print", &("", title.as_str(), Fd::from(file.as_fd()), options))
+        .map_err(portal_error)?;
+
+    let response = responses.next().ok_or_else(|| LabelgoodError::SpoolFailed {
+        printer: printer.to_string(),
+        message: "The print portal closed without answering".to_string(),
+    })?;
+    let (code, _): (u32, HashMap<String, OwnedValue>) = response.body().deserialize().map_err(portal_error)?;
+    match code {
+        0 => {
+            info!("Printed {} through the print portal", pdf_path.display());
+            Ok(())
+        }
+        1 => Err(LabelgoodError::Cancelled {
+            message: "Printing was cancelled in the print dialog".to_string(),
+        }),
+        _ => Err(LabelgoodError::SpoolFailed {
+            printer: printer.to_string(),
+            message: "The print dialog failed to print".to_string(),
+        }),
+    }
+}
@@ -0,0 +1,127 @@
+// Network printers added by address, listed under a name of the user's
+// choosing without a CUPS queue. Every call is passed on to the backend of
+// the printer's URI, e.g. IPP or raw TCP.
+use super::{resolve, BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission};
+use crate::capabilities::PrinterCapabilities;
+use crate::error::LabelgoodError;
+use crate::printerinfo::PrinterInfo;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPrinter {
+    /// Name as listed
+    pub name: String,
+    /// Where jobs go, e.g. "ipp://192.168.1.20:631/ipp/print"
+    pub uri: String,
+}
+
+static PRINTERS: Mutex<Vec<NetworkPrinter>> = Mutex::new(Vec::new());
+
+pub fn configure(printers: Vec<NetworkPrinter>) {
+    *PRINTERS.lock().unwrap() = printers;
+}
+
+/// The URI `printer_name` was added with, if it's a registered printer
+pub fn uri(printer_name: &str) -> Option<String> {
+    PRINTERS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|printer| printer.name == printer_name)
+        .map(|printer| printer.uri.clone())
+}
+
+pub struct RegisteredBackend;
+
+impl PrinterBackend for RegisteredBackend {
+    fn name(&self) -> &'static str {
+        "registered"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        uri(printer_name).is_some()
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async {
+            Ok(PRINTERS
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|printer| printer.name.clone())
+                .collect())
+        })
+    }
+
+    fn capabilities<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterCapabilities> {
+        Box::pin(async move {
+            let Some(uri) = uri(printer_name) else {
+                return PrinterCapabilities::unknown(printer_name);
+            };
+            resolve(&uri).capabilities(&uri).await
+        })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            let uri = uri(job.printer).ok_or_else(|| unknown(job.printer))?;
+            resolve(&uri).submit(PrintJob { printer: &uri, ..job }).await
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            let Some(uri) = uri(printer_name) else {
+                return PrinterStatus::new(printer_name, "unknown", "");
+            };
+            // Reported under the name it's listed as
+            let status = resolve(&uri).status(&uri).await;
+            PrinterStatus::new(printer_name, &status.state, status.message)
+        })
+    }
+
+    fn cancel<'a>(&'a self, printer_name: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            let uri = uri(printer_name).ok_or_else(|| unknown(printer_name))?;
+            resolve(&uri).cancel(&uri, job_id).await
+        })
+    }
+
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(async move {
+            let Some(uri) = uri(printer_name) else {
+                return PrinterInfo::new(printer_name);
+            };
+            resolve(&uri).info(&uri).await
+        })
+    }
+
+    fn send_raw<'a>(&'a self, printer_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            let uri = uri(printer_name).ok_or_else(|| unknown(printer_name))?;
+            resolve(&uri).send_raw(&uri, data).await
+        })
+    }
+
+    fn query<'a>(
+        &'a self,
+        printer_name: &'a str,
+        data: &'a [u8],
+        wait: Duration,
+    ) -> BoxFuture<'a, Result<Vec<u8>, LabelgoodError>> {
+        Box::pin(async move {
+            let uri = uri(printer_name).ok_or_else(|| unknown(printer_name))?;
+            resolve(&uri).query(&uri, data, wait).await
+        })
+    }
+}
+
+// The printer was removed between being resolved and used
+fn unknown(printer_name: &str) -> LabelgoodError {
+    LabelgoodError::PrinterUnavailable {
+        printer: printer_name.to_string(),
+        message: format!("{} is no longer a registered printer", printer_name),
+    }
+}
@@ -0,0 +1,194 @@
+// Raw TCP printing, the AppSocket/JetDirect protocol most network printers
+// listen for on port 9100: the PDF is written to the connection as is and the
+// printer starts once it's closed. There's no reply, so no job id or
+// progress, and status only tells whether the printer is reachable. Printer
+// commands can be answered over the same connection.
+use super::{with_default_port, BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission, REPLY_PAUSE};
+use crate::error::LabelgoodError;
+use crate::handling;
+use crate::i18n;
+use crate::printerinfo::{snmp, PrinterInfo};
+use crate::printing::{LPR_TIMEOUT, LPSTAT_TIMEOUT};
+use crate::progress;
+use std::fs;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tracing::debug;
+
+const PREFIX: &str = "socket://";
+const DEFAULT_PORT: u16 = 9100;
+
+/// IEEE 1284 device ID (PWG Printer Port Monitor MIB), hrDeviceDescr,
+/// prtGeneralSerialNumber and sysDescr, which printers fill with their
+/// firmware version
+const SNMP_OIDS: [&str; 4] = [
+    "1.3.6.1.4.1.2699.1.2.1.2.1.1.3.1",
+    "1.3.6.1.2.1.25.3.2.1.3.1",
+    "1.3.6.1.2.1.43.5.1.1.17.1",
+    "1.3.6.1.2.1.1.1.0",
+];
+
+pub struct SocketBackend;
+
+// host:port of a "socket://host[:port]" name
+fn address(printer_name: &str) -> Option<String> {
+    let authority = printer_name.strip_prefix(PREFIX)?.trim_end_matches('/');
+    (!authority.is_empty()).then(|| with_default_port(authority, DEFAULT_PORT))
+}
+
+async fn connect(address: &str) -> Result<TcpStream, String> {
+    match tokio::time::timeout(LPSTAT_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("no answer within {} seconds", LPSTAT_TIMEOUT.as_secs())),
+    }
+}
+
+// A connection to the printer, and its address for messages
+async fn open(printer_name: &str) -> Result<(TcpStream, String), LabelgoodError> {
+    let address = address(printer_name).ok_or_else(|| LabelgoodError::InvalidInput {
+        message: format!("{} has no host", printer_name),
+    })?;
+    let stream = tokio::select! {
+        result = connect(&address) => result.map_err(|e| LabelgoodError::PrinterUnavailable {
+            printer: printer_name.to_string(),
+            message: format!("Failed to connect to {}: {}", address, e),
+        })?,
+        cancelled = progress::cancelled() => return Err(cancelled),
+    };
+    Ok((stream, address))
+}
+
+// Write `bytes` to the printer and close the connection
+async fn send(printer_name: &str, bytes: &[u8]) -> Result<(), LabelgoodError> {
+    let printer = printer_name.to_string();
+    let (mut stream, address) = open(printer_name).await?;
+    let sending = async {
+        stream.write_all(bytes).await?;
+        stream.shutdown().await
+    };
+    tokio::select! {
+        result = tokio::time::timeout(LPR_TIMEOUT, sending) => match result {
+            Ok(result) => result.map_err(|e| LabelgoodError::SpoolFailed {
+                printer: printer.clone(),
+                message: format!("Failed to send to {}: {}", address, e),
+            })?,
+            Err(_) => return Err(LabelgoodError::TimedOut {
+                message: i18n::t(
+                    "command-timed-out",
+                    &[("program", address.as_str().into()), ("seconds", LPR_TIMEOUT.as_secs().into())],
+                ),
+                program: printer,
+            }),
+        },
+        cancelled = progress::cancelled() => return Err(cancelled),
+    }
+    Ok(())
+}
+
+// Write `bytes` to the printer and read what it answers until it goes quiet,
+// closes the connection or `wait` has passed
+async fn query(printer_name: &str, bytes: &[u8], wait: Duration) -> Result<Vec<u8>, LabelgoodError> {
+    let (mut stream, address) = open(printer_name).await?;
+    let failed = |action: &str, e: std::io::Error| LabelgoodError::SpoolFailed {
+        printer: printer_name.to_string(),
+        message: format!("Failed to {} {}: {}", action, address, e),
+    };
+    stream.write_all(bytes).await.map_err(|e| failed("send to", e))?;
+    let deadline = Instant::now() + wait;
+    let mut reply = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let limit = if reply.is_empty() {
+            remaining
+        } else {
+            remaining.min(REPLY_PAUSE)
+        };
+        match tokio::time::timeout(limit, stream.read(&mut buffer)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(read)) => reply.extend_from_slice(&buffer[..read]),
+            Ok(Err(e)) => return Err(failed("read from", e)),
+        }
+    }
+    let _ = stream.shutdown().await;
+    Ok(reply)
+}
+
+impl PrinterBackend for SocketBackend {
+    fn name(&self) -> &'static str {
+        "socket"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        printer_name.starts_with(PREFIX)
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            let bytes = fs::read(job.pdf_path).map_err(|e| LabelgoodError::Io {
+                message: format!("Failed to read PDF: {}", e),
+            })?;
+            handling::warn_unsupported("Raw socket", job.printer);
+            send(job.printer, &bytes).await?;
+            Ok(Submission {
+                message: i18n::t("sent-to", &[("printer", job.printer.into())]),
+                job_id: None,
+            })
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            let Some(address) = address(printer_name) else {
+                return PrinterStatus::new(printer_name, "unknown", "");
+            };
+            match connect(&address).await {
+                Ok(_) => PrinterStatus::new(printer_name, "idle", ""),
+                Err(e) => PrinterStatus::new(printer_name, "offline", format!("{}: {}", address, e)),
+            }
+        })
+    }
+
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(async move {
+            let mut info = PrinterInfo::new(printer_name);
+            let Some(address) = address(printer_name) else {
+                return info;
+            };
+            // The host alone; SNMP has its own port
+            let host = address.rsplit_once(':').map_or(address.as_str(), |(host, _)| host);
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            match snmp::get_strings(host, &SNMP_OIDS).await {
+                Ok(values) => {
+                    let [device_id, description, serial, system] = <[Option<String>; 4]>::try_from(values)
+                        .unwrap_or_default();
+                    if let Some(device_id) = device_id {
+                        info.add_device_id(&device_id);
+                    }
+                    if let Some(description) = description {
+                        info.add_make_and_model(&description);
+                    }
+                    info.serial = info.serial.take().or(serial);
+                    info.firmware = system;
+                }
+                Err(e) => debug!("{}", e),
+            }
+            info
+        })
+    }
+
+    fn send_raw<'a>(&'a self, printer_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(send(printer_name, data))
+    }
+
+    fn query<'a>(
+        &'a self,
+        printer_name: &'a str,
+        data: &'a [u8],
+        wait: Duration,
+    ) -> BoxFuture<'a, Result<Vec<u8>, LabelgoodError>> {
+        Box::pin(query(printer_name, data, wait))
+    }
+}
@@ -0,0 +1,258 @@
+// USB printer class devices written to directly, e.g. usb:/dev/usb/lp0, for
+// printers that take PDF and have no CUPS queue. Writing needs membership of
+// the lp group; see the USB check in `diagnostics`. Printer names can come
+// from the HTTP API, MQTT and the hot folder, so only printer devices are
+// ever opened: /dev/usb/lp*, /dev/lp* and, on Windows, \\.\USB*.
+use super::{BoxFuture, PrintJob, PrinterBackend, PrinterStatus, Submission, REPLY_PAUSE};
+use crate::error::LabelgoodError;
+use crate::handling;
+use crate::i18n;
+use crate::printerinfo::PrinterInfo;
+use crate::progress;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PREFIX: &str = "usb:";
+/// Where the kernel's usblp driver creates printer devices
+const DEVICE_DIR: &str = "/dev/usb";
+/// Where the usblp driver lists its devices' attributes
+const SYSFS_DIR: &str = "/sys/class/usbmisc";
+/// Printer ports on Windows, e.g. \\.\USB001
+const WINDOWS_DEVICE_PREFIX: &str = r"\\.\USB";
+/// How often a device is checked for a reply
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+#[cfg(target_os = "linux")]
+const O_NONBLOCK: i32 = 0o4000;
+
+pub struct UsbBackend;
+
+// The device of a "usb:/dev/..." name. CUPS device URIs like
+// "usb://Brother/QL-820NWB" aren't paths and aren't handled here.
+fn device(printer_name: &str) -> Option<&Path> {
+    printer_name
+        .strip_prefix(PREFIX)
+        .filter(|path| {
+            (path.starts_with('/') && !path.starts_with("//"))
+                || (cfg!(windows) && path.to_ascii_uppercase().starts_with(WINDOWS_DEVICE_PREFIX))
+        })
+        .map(Path::new)
+}
+
+// Whether `path`, resolved, is a printer device
+fn is_printer_device(path: &Path) -> bool {
+    if cfg!(windows) {
+        let path = path.to_string_lossy().to_ascii_uppercase();
+        return path
+            .strip_prefix(WINDOWS_DEVICE_PREFIX)
+            .is_some_and(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+    let numbered = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("lp"))
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    numbered && (path.parent() == Some(Path::new(DEVICE_DIR)) || path.parent() == Some(Path::new("/dev")))
+}
+
+// The device of `printer_name`, symlinks resolved, if it's a printer device;
+// anything else is refused rather than written to
+fn checked_device(printer_name: &str) -> Result<PathBuf, LabelgoodError> {
+    let unavailable = |message: String| LabelgoodError::PrinterUnavailable {
+        printer: printer_name.to_string(),
+        message,
+    };
+    let path = device(printer_name).ok_or_else(|| unavailable(format!("{} is not a USB device", printer_name)))?;
+    let resolved = if cfg!(windows) {
+        path.to_path_buf()
+    } else {
+        fs::canonicalize(path).map_err(|e| unavailable(format!("Failed to open {}: {}", path.display(), e)))?
+    };
+    if !is_printer_device(&resolved) {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!(
+                "{} is not a USB printer device; only {}/lp*, /dev/lp* and {}* can be printed to",
+                path.display(),
+                DEVICE_DIR,
+                WINDOWS_DEVICE_PREFIX
+            ),
+        });
+    }
+    Ok(resolved)
+}
+
+impl PrinterBackend for UsbBackend {
+    fn name(&self) -> &'static str {
+        "usb"
+    }
+
+    fn handles(&self, printer_name: &str) -> bool {
+        device(printer_name).is_some()
+    }
+
+    fn enumerate(&self) -> BoxFuture<'_, Result<Vec<String>, LabelgoodError>> {
+        Box::pin(async {
+            let mut devices: Vec<PathBuf> = fs::read_dir(DEVICE_DIR)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .map(|entry| entry.path())
+                        .filter(|path| path.file_name().is_some_and(|n| n.to_string_lossy().starts_with("lp")))
+                        .collect()
+                })
+                .unwrap_or_default();
+            devices.sort();
+            Ok(devices.iter().map(|d| format!("{}{}", PREFIX, d.display())).collect())
+        })
+    }
+
+    fn submit<'a>(&'a self, job: PrintJob<'a>) -> BoxFuture<'a, Result<Submission, LabelgoodError>> {
+        Box::pin(async move {
+            let printer = job.printer.to_string();
+            let device = checked_device(job.printer)?;
+            let bytes = fs::read(job.pdf_path).map_err(|e| LabelgoodError::Io {
+                message: format!("Failed to read PDF: {}", e),
+            })?;
+            handling::warn_unsupported("USB", job.printer);
+            let writing = tauri::async_runtime::spawn_blocking(move || write_device(&printer, &device, &bytes));
+            // A write to a device can't be interrupted; cancelling stops
+            // waiting for it
+            tokio::select! {
+                result = writing => result.map_err(|e| LabelgoodError::Other { message: e.to_string() })??,
+                cancelled = progress::cancelled() => return Err(cancelled),
+            }
+            Ok(Submission {
+                message: i18n::t("sent-to", &[("printer", job.printer.into())]),
+                job_id: None,
+            })
+        })
+    }
+
+    fn status<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterStatus> {
+        Box::pin(async move {
+            let Some(path) = device(printer_name) else {
+                return PrinterStatus::new(printer_name, "unknown", "");
+            };
+            if !path.exists() {
+                return PrinterStatus::new(printer_name, "offline", format!("{} is not connected", path.display()));
+            }
+            let device = match checked_device(printer_name) {
+                Ok(device) => device,
+                Err(e) => return PrinterStatus::new(printer_name, "unknown", e.to_string()),
+            };
+            match OpenOptions::new().write(true).open(&device) {
+                Ok(_) => PrinterStatus::new(printer_name, "idle", ""),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    PrinterStatus::new(printer_name, "offline", format!("{} is not connected", device.display()))
+                }
+                Err(e) => PrinterStatus::new(printer_name, "unknown", format!("{}: {}", device.display(), e)),
+            }
+        })
+    }
+
+    fn info<'a>(&'a self, printer_name: &'a str) -> BoxFuture<'a, PrinterInfo> {
+        Box::pin(async move {
+            let mut info = PrinterInfo::new(printer_name);
+            // usblp publishes the device ID next to the interface, and the
+            // USB device above it has the serial number
+            let Ok(device) = checked_device(printer_name) else {
+                return info;
+            };
+            let Some(name) = device.file_name() else {
+                return info;
+            };
+            let interface = Path::new(SYSFS_DIR).join(name).join("device");
+            if let Ok(device_id) = fs::read_to_string(interface.join("ieee1284_id")) {
+                info.add_device_id(&device_id);
+            }
+            if let Ok(serial) = fs::read_to_string(interface.join("../serial")) {
+                info.serial = info.serial.take().or(Some(serial.trim().to_string()).filter(|s| !s.is_empty()));
+            }
+            info
+        })
+    }
+
+    fn send_raw<'a>(&'a self, printer_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<(), LabelgoodError>> {
+        Box::pin(async move {
+            let printer = printer_name.to_string();
+            let device = checked_device(printer_name)?;
+            let bytes = data.to_vec();
+            tauri::async_runtime::spawn_blocking(move || write_device(&printer, &device, &bytes))
+                .await
+                .map_err(|e| LabelgoodError::Other { message: e.to_string() })?
+        })
+    }
+
+    fn query<'a>(
+        &'a self,
+        printer_name: &'a str,
+        data: &'a [u8],
+        wait: Duration,
+    ) -> BoxFuture<'a, Result<Vec<u8>, LabelgoodError>> {
+        Box::pin(async move {
+            let printer = printer_name.to_string();
+            let device = checked_device(printer_name)?;
+            let bytes = data.to_vec();
+            tauri::async_runtime::spawn_blocking(move || query_device(&printer, &device, &bytes, wait))
+                .await
+                .map_err(|e| LabelgoodError::Other { message: e.to_string() })?
+        })
+    }
+}
+
+fn open_device(printer: &str, device: &Path, options: &OpenOptions) -> Result<File, LabelgoodError> {
+    options.open(device).map_err(|e| LabelgoodError::PrinterUnavailable {
+        printer: printer.to_string(),
+        message: format!("Failed to open {}: {}", device.display(), e),
+    })
+}
+
+fn write_all(printer: &str, device: &Path, file: &mut File, bytes: &[u8]) -> Result<(), LabelgoodError> {
+    file.write_all(bytes)
+        .and_then(|_| file.flush())
+        .map_err(|e| LabelgoodError::SpoolFailed {
+            printer: printer.to_string(),
+            message: format!("Failed to write to {}: {}", device.display(), e),
+        })
+}
+
+fn write_device(printer: &str, device: &Path, bytes: &[u8]) -> Result<(), LabelgoodError> {
+    let mut file = open_device(printer, device, OpenOptions::new().write(true))?;
+    write_all(printer, device, &mut file, bytes)
+}
+
+// Write `bytes` and read what the printer answers until it goes quiet or
+// `wait` has passed. Reads from usblp block until the printer says
+// something, so the device is opened non-blocking and polled.
+fn query_device(printer: &str, device: &Path, bytes: &[u8], wait: Duration) -> Result<Vec<u8>, LabelgoodError> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+    #[cfg(target_os = "linux")]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, O_NONBLOCK);
+    let mut file = open_device(printer, device, &options)?;
+    write_all(printer, device, &mut file, bytes)?;
+
+    let deadline = Instant::now() + wait;
+    let mut last_read: Option<Instant> = None;
+    let mut reply = Vec::new();
+    let mut buffer = [0; 4096];
+    while Instant::now() < deadline && last_read.is_none_or(|at| at.elapsed() < REPLY_PAUSE) {
+        match file.read(&mut buffer) {
+            Ok(read) if read > 0 => {
+                reply.extend_from_slice(&buffer[..read]);
+                last_read = Some(Instant::now());
+            }
+            Ok(_) => thread::sleep(POLL_INTERVAL),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                return Err(LabelgoodError::SpoolFailed {
+                    printer: printer.to_string(),
+                    message: format!("Failed to read from {}: {}", device.display(), e),
+                })
+            }
+        }
+    }
+    Ok(reply)
+}
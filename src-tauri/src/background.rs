@@ -0,0 +1,194 @@
+// Background removal for product photos, so they sit on a label without a
+// white box around them. Plain backgrounds are removed in-process, either by
+// colour or by following the background in from the image's edges; busy
+// ones can go through rembg, which runs an ONNX segmentation model.
+use crate::document::Asset;
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::ingest;
+use crate::printing::{self, CONVERT_TIMEOUT};
+use crate::project::LoadedAsset;
+use crate::tools;
+use image::{ImageFormat, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum BackgroundRemoval {
+    /// Every pixel within `tolerance` of `color` ("#rrggbb"), or of the
+    /// image's corners when no colour is given
+    Chroma {
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default = "default_tolerance")]
+        tolerance: u8,
+    },
+    /// The background reachable from the image's edges without crossing a
+    /// step in colour larger than `tolerance`; keeps white inside the
+    /// product, and copes with shadows and gradients
+    Edge {
+        #[serde(default = "default_tolerance")]
+        tolerance: u8,
+    },
+    /// rembg with one of its models, "u2net" by default
+    Model {
+        #[serde(default)]
+        model: Option<String>,
+    },
+}
+
+fn default_tolerance() -> u8 {
+    40
+}
+
+// Largest difference between two pixels' channels
+fn distance(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0)
+}
+
+fn parse_color(color: &str) -> Result<Rgba<u8>, String> {
+    let hex = color.trim_start_matches('#');
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok(Rgba([r, g, b, 255])),
+        _ => Err(format!("Invalid colour: {}", color)),
+    }
+}
+
+// Average of the four corner pixels
+fn corner_color(image: &RgbaImage) -> Rgba<u8> {
+    let (w, h) = (image.width() - 1, image.height() - 1);
+    let corners = [(0, 0), (w, 0), (0, h), (w, h)].map(|(x, y)| *image.get_pixel(x, y));
+    let average = |c: usize| (corners.iter().map(|p| p[c] as u32).sum::<u32>() / 4) as u8;
+    Rgba([average(0), average(1), average(2), 255])
+}
+
+fn chroma(image: &mut RgbaImage, color: Rgba<u8>, tolerance: u8) {
+    for pixel in image.pixels_mut() {
+        if distance(pixel, &color) <= tolerance {
+            pixel[3] = 0;
+        }
+    }
+}
+
+fn edge(image: &mut RgbaImage, tolerance: u8) {
+    let (w, h) = (image.width(), image.height());
+    let mut background = vec![false; (w * h) as usize];
+    // Start from the border pixels that look like the corners, so a product
+    // touching the edge isn't taken for background
+    let corners = corner_color(image);
+    let mut queue = VecDeque::new();
+    let border = (0..w)
+        .flat_map(|x| [(x, 0), (x, h - 1)])
+        .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+    for (x, y) in border {
+        let i = (y * w + x) as usize;
+        if !background[i] && distance(image.get_pixel(x, y), &corners) <= tolerance {
+            background[i] = true;
+            queue.push_back((x, y));
+        }
+    }
+    while let Some((x, y)) = queue.pop_front() {
+        let pixel = *image.get_pixel(x, y);
+        let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+        for (nx, ny) in neighbours {
+            if nx >= w || ny >= h || background[(ny * w + nx) as usize] {
+                continue;
+            }
+            if distance(&pixel, image.get_pixel(nx, ny)) <= tolerance {
+                background[(ny * w + nx) as usize] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    for (i, pixel) in image.pixels_mut().enumerate() {
+        if background[i] {
+            pixel[3] = 0;
+        }
+    }
+}
+
+// Run rembg on the image, returning its PNG output
+async fn rembg(bytes: &[u8], model: Option<&str>) -> Result<Vec<u8>, LabelgoodError> {
+    let dir = tempfile::tempdir().map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to create temp directory: {}", e),
+    })?;
+    let (input, output) = (dir.path().join("input.png"), dir.path().join("output.png"));
+    fs::write(&input, bytes).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to write image: {}", e),
+    })?;
+    let mut command = tools::command("rembg");
+    command.arg("i");
+    if let Some(model) = model {
+        command.arg("-m").arg(model);
+    }
+    command.arg(&input).arg(&output);
+    let missing = |e: std::io::Error| LabelgoodError::DependencyMissing {
+        program: "rembg".to_string(),
+        message: i18n::t("rembg-missing", &[("error", e.to_string().into())]),
+    };
+    let result = printing::run_command(command, CONVERT_TIMEOUT, missing).await?;
+    if !result.status.success() {
+        return Err(LabelgoodError::RenderFailed {
+            message: format!("rembg failed: {}", String::from_utf8_lossy(&result.stderr).trim()),
+        });
+    }
+    fs::read(&output).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to read rembg output: {}", e),
+    })
+}
+
+fn decode(bytes: &[u8]) -> Result<RgbaImage, LabelgoodError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| LabelgoodError::InvalidInput {
+            message: format!("Failed to decode image: {}", e),
+        })?
+        .to_rgba8();
+    if image.width() == 0 || image.height() == 0 {
+        return Err(LabelgoodError::InvalidInput {
+            message: "The image is empty".to_string(),
+        });
+    }
+    Ok(image)
+}
+
+fn encode(image: &RgbaImage) -> Result<Vec<u8>, LabelgoodError> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(png)
+}
+
+/// `asset` with its background made transparent, as a new PNG asset to
+/// point the image element at; the original asset is left as it is
+#[tauri::command]
+pub async fn remove_background(asset: Asset, method: BackgroundRemoval) -> Result<LoadedAsset, LabelgoodError> {
+    let bytes = asset.bytes()?;
+    let png = match method {
+        BackgroundRemoval::Chroma { color, tolerance } => {
+            let mut image = decode(&bytes)?;
+            let color = match color {
+                Some(color) => parse_color(&color)?,
+                None => corner_color(&image),
+            };
+            chroma(&mut image, color, tolerance);
+            encode(&image)?
+        }
+        BackgroundRemoval::Edge { tolerance } => {
+            let mut image = decode(&bytes)?;
+            edge(&mut image, tolerance);
+            encode(&image)?
+        }
+        BackgroundRemoval::Model { model } => rembg(&bytes, model.as_deref()).await?,
+    };
+    let stem = asset
+        .file_name
+        .rsplit_once('.')
+        .map_or(asset.file_name.as_str(), |(stem, _)| stem);
+    let image = ingest::from_raster(&format!("{}-cutout", stem), png, None)?;
+    Ok(image.into_asset())
+}
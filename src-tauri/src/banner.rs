@@ -0,0 +1,193 @@
+// Banners longer than a printer prints in one go: the design is split along
+// its length into pieces printed one after another. Neighbouring pieces
+// repeat a strip of the design, with tick marks halfway through it, so the
+// pieces can be lined up and joined after printing.
+use crate::backends;
+use crate::document::{Document, Element, ElementKind, ShapeElement, ShapeKind};
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::groups;
+use crate::history::JobDetails;
+use crate::merge;
+use serde::Deserialize;
+use tauri::State;
+
+const TICK_LENGTH_MM: f64 = 3.0;
+const TICK_WIDTH_MM: f64 = 0.2;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BannerOptions {
+    /// Longest piece; the printer's maximum label length when absent
+    pub max_length_mm: Option<f64>,
+    /// Length of the design printed on both of two neighbouring pieces
+    pub overlap_mm: f64,
+    /// Draw alignment ticks in the overlaps
+    pub marks: bool,
+}
+
+impl Default for BannerOptions {
+    fn default() -> Self {
+        BannerOptions {
+            max_length_mm: None,
+            overlap_mm: 5.0,
+            marks: true,
+        }
+    }
+}
+
+/// Split `document` along its longer side into pieces of equal length, at
+/// most `max_length_mm` long. A document that fits is returned as it is.
+pub fn split(document: &Document, max_length_mm: f64, overlap_mm: f64, marks: bool) -> Result<Vec<Document>, String> {
+    if !max_length_mm.is_finite() || overlap_mm < 0.0 || max_length_mm <= overlap_mm {
+        return Err(format!(
+            "A banner can't be split into {} mm pieces overlapping {} mm",
+            max_length_mm, overlap_mm
+        ));
+    }
+    let along_width = document.width_mm >= document.height_mm;
+    let length = if along_width {
+        document.width_mm
+    } else {
+        document.height_mm
+    };
+    if length <= max_length_mm {
+        return Ok(vec![document.clone()]);
+    }
+    let count = ((length - overlap_mm) / (max_length_mm - overlap_mm)).ceil() as usize;
+    let piece_length = (length + (count - 1) as f64 * overlap_mm) / count as f64;
+    let step = piece_length - overlap_mm;
+
+    let pieces = (0..count)
+        .map(|i| {
+            let start = i as f64 * step;
+            let mut piece = Document {
+                elements: Vec::new(),
+                assets: document.assets.clone(),
+                prompts: document.prompts.clone(),
+                layers: document.layers.clone(),
+                // One tag for the whole banner
+                rfid: document.rfid.clone().filter(|_| i == 0),
+                ..*document
+            };
+            if along_width {
+                piece.width_mm = piece_length;
+            } else {
+                piece.height_mm = piece_length;
+            }
+            // Elements entirely on other pieces are left out
+            for element in &document.elements {
+                let (low, high) = extent(element, along_width);
+                if high <= start || low >= start + piece_length {
+                    continue;
+                }
+                let mut element = element.clone();
+                if along_width {
+                    element.x_mm -= start;
+                } else {
+                    element.y_mm -= start;
+                }
+                piece.elements.push(element);
+            }
+            if marks && overlap_mm > 0.0 {
+                if i > 0 {
+                    add_ticks(&mut piece, overlap_mm / 2.0, along_width);
+                }
+                if i + 1 < count {
+                    add_ticks(&mut piece, piece_length - overlap_mm / 2.0, along_width);
+                }
+            }
+            piece
+        })
+        .collect();
+    Ok(pieces)
+}
+
+// Lowest and highest position of `element` along the banner
+fn extent(element: &Element, along_width: bool) -> (f64, f64) {
+    let (sin, cos) = element.rotation_deg.to_radians().sin_cos();
+    let (w, h) = (element.width_mm, element.height_mm);
+    [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)]
+        .iter()
+        .map(|&(x, y)| {
+            if along_width {
+                element.x_mm + x * cos - y * sin
+            } else {
+                element.y_mm + x * sin + y * cos
+            }
+        })
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), v| {
+            (low.min(v), high.max(v))
+        })
+}
+
+// Ticks `at` mm along the banner, in from both of its edges
+fn add_ticks(document: &mut Document, at: f64, along_width: bool) {
+    let across = if along_width {
+        document.height_mm
+    } else {
+        document.width_mm
+    };
+    let tick = TICK_LENGTH_MM.min(across / 4.0);
+    for from in [0.0, across - tick] {
+        let line = ElementKind::Shape(ShapeElement {
+            shape: ShapeKind::Line,
+            stroke_width_mm: TICK_WIDTH_MM,
+            stroke_color: "#000000".to_string(),
+            fill_color: None,
+        });
+        document.elements.push(if along_width {
+            Element::new(at, from, 0.0, tick, line)
+        } else {
+            Element::new(from, at, tick, 0.0, line)
+        });
+    }
+}
+
+// `options.max_length_mm`, or the longest label the printer takes
+async fn max_length(printer_name: Option<&str>, options: &BannerOptions) -> Result<f64, String> {
+    if let Some(max_length) = options.max_length_mm {
+        return Ok(max_length);
+    }
+    let printer = groups::primary(printer_name.ok_or("Give the longest piece to split the banner into")?);
+    backends::resolve(&printer)
+        .capabilities(&printer)
+        .await
+        .max_length_mm
+        .ok_or_else(|| {
+            format!(
+                "{} doesn't state its longest label; give the longest piece to split the banner into",
+                printer
+            )
+        })
+}
+
+/// The pieces `print_banner` would print, for previewing
+#[tauri::command]
+pub async fn split_banner(
+    document: Document,
+    printer_name: Option<String>,
+    options: BannerOptions,
+) -> Result<Vec<Document>, String> {
+    let max_length = max_length(printer_name.as_deref(), &options).await?;
+    split(&document, max_length, options.overlap_mm, options.marks)
+}
+
+/// Print `document` as a banner of pieces no longer than the printer (or
+/// `options`) allows. `op_id` lets `cancel_operation` stop it.
+#[tauri::command]
+pub async fn print_banner(
+    fonts: State<'_, FontLibrary>,
+    events: State<'_, EventBus>,
+    document: Document,
+    printer_name: Option<String>,
+    options: BannerOptions,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    let printer_name = printer_name.as_deref();
+    let max_length = max_length(printer_name, &options).await?;
+    let pieces = split(&document, max_length, options.overlap_mm, options.marks)?;
+    let work = merge::print_documents(&pieces, fonts.database(), printer_name);
+    let job = events.run_job("banner", pieces.len(), printer_name, JobDetails::default(), work);
+    events.run_operation(op_id, job).await
+}
@@ -0,0 +1,347 @@
+// Barcode generation in the backend, so printed symbols come from the same
+// encoder as the preview instead of a canvas-rendering JS library
+pub mod check_digits;
+pub mod digital_link;
+pub mod gs1;
+pub mod payloads;
+pub mod udi;
+pub mod variable_measure;
+pub mod verify;
+
+use crate::document::{BarcodeElement, BarcodeOptions};
+use crate::fonts::FontLibrary;
+use crate::plugins;
+use crate::render;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use payloads::QrPayload;
+use rxing::datamatrix::encoder::SymbolShapeHint;
+use rxing::{BarcodeFormat, EncodeHints, MultiFormatWriter, Writer};
+use std::fmt::Write as _;
+use tauri::State;
+
+/// An encoded barcode as a grid of modules (bars or cells)
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub width: u32,
+    /// 1 for linear symbologies
+    pub height: u32,
+    modules: Vec<bool>,
+    /// Human readable text, including any computed check digit
+    pub text: String,
+}
+
+impl Symbol {
+    /// Symbol from row-major modules, `true` for dark
+    pub fn from_modules(width: u32, height: u32, modules: Vec<bool>, text: String) -> Self {
+        Symbol {
+            width,
+            height,
+            modules,
+            text,
+        }
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.height == 1
+    }
+
+    pub fn module(&self, x: u32, y: u32) -> bool {
+        self.modules[(y * self.width + x) as usize]
+    }
+
+    /// SVG path data for the dark modules, drawn into a `width` x `height`
+    /// box at the origin. Runs of modules become single rectangles.
+    pub fn path_data(&self, width: f64, height: f64) -> String {
+        let module_w = width / self.width as f64;
+        let module_h = height / self.height as f64;
+        let mut path = String::new();
+        for y in 0..self.height {
+            let mut x = 0;
+            while x < self.width {
+                if !self.module(x, y) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < self.width && self.module(x, y) {
+                    x += 1;
+                }
+                let _ = write!(
+                    path,
+                    "M{} {}h{}v{}h{}z",
+                    start as f64 * module_w,
+                    y as f64 * module_h,
+                    (x - start) as f64 * module_w,
+                    module_h,
+                    -((x - start) as f64 * module_w)
+                );
+            }
+        }
+        path
+    }
+}
+
+/// `module_mm` as a whole number, at least one, of the dots of a printer at
+/// `dpi`, so bars and 2D cells of the same width print with the same number
+/// of dots instead of alternating between two. Fitted modules round down to stay in
+/// their element. Unchanged without a `dpi`.
+pub fn snap_module(module_mm: f64, dpi: Option<f64>, fitted: bool) -> f64 {
+    let Some(dot_mm) = dpi.filter(|dpi| *dpi > 0.0).map(|dpi| 25.4 / dpi) else {
+        return module_mm;
+    };
+    // Tolerate rounding error just below a whole number of dots
+    let dots = module_mm / dot_mm + 1e-6;
+    let dots = if fitted { dots.floor() } else { dots.round() };
+    dots.max(1.0) * dot_mm
+}
+
+fn format_for(symbology: &str) -> Result<BarcodeFormat, String> {
+    Ok(match symbology {
+        "code128" | "gs1128" => BarcodeFormat::CODE_128,
+        "code39" => BarcodeFormat::CODE_39,
+        "ean13" => BarcodeFormat::EAN_13,
+        "ean8" => BarcodeFormat::EAN_8,
+        "upca" => BarcodeFormat::UPC_A,
+        "upce" => BarcodeFormat::UPC_E,
+        "itf" | "itf14" => BarcodeFormat::ITF,
+        "qr" => BarcodeFormat::QR_CODE,
+        "datamatrix" | "gs1datamatrix" => BarcodeFormat::DATA_MATRIX,
+        "pdf417" => BarcodeFormat::PDF_417,
+        "aztec" => BarcodeFormat::AZTEC,
+        _ => return Err(format!("Unsupported barcode symbology: {}", symbology)),
+    })
+}
+
+fn is_2d(format: &BarcodeFormat) -> bool {
+    matches!(
+        format,
+        BarcodeFormat::QR_CODE | BarcodeFormat::DATA_MATRIX | BarcodeFormat::PDF_417 | BarcodeFormat::AZTEC
+    )
+}
+
+// Check the error correction setting against what the symbology accepts and
+// fill in our defaults. Labels get scuffed, so the defaults lean towards
+// recovering damage over the smallest possible symbol.
+fn error_correction(format: &BarcodeFormat, level: Option<&str>) -> Result<Option<String>, String> {
+    let level = level.map(str::trim).filter(|l| !l.is_empty());
+    match format {
+        BarcodeFormat::QR_CODE => {
+            let level = level.unwrap_or("M").to_ascii_uppercase();
+            if !["L", "M", "Q", "H"].contains(&level.as_str()) {
+                return Err(format!("QR error correction must be L, M, Q or H, got \"{}\"", level));
+            }
+            Ok(Some(level))
+        }
+        BarcodeFormat::PDF_417 => {
+            let level = level.unwrap_or("2");
+            match level.parse::<u8>() {
+                Ok(0..=8) => Ok(Some(level.to_string())),
+                _ => Err(format!("PDF417 error correction level must be 0-8, got \"{}\"", level)),
+            }
+        }
+        BarcodeFormat::AZTEC => {
+            let level = level.unwrap_or("23").trim_end_matches('%');
+            match level.parse::<u8>() {
+                Ok(5..=95) => Ok(Some(level.to_string())),
+                _ => Err(format!("Aztec error correction must be 5-95%, got \"{}\"", level)),
+            }
+        }
+        // DataMatrix is always ECC 200
+        BarcodeFormat::DATA_MATRIX if level.is_some_and(|l| l != "200") => {
+            Err("DataMatrix only supports ECC 200".to_string())
+        }
+        _ => Ok(None),
+    }
+}
+
+fn shape_hint(shape: Option<&str>) -> Result<SymbolShapeHint, String> {
+    match shape.map(str::trim) {
+        None | Some("") | Some("auto") => Ok(SymbolShapeHint::FORCE_NONE),
+        Some("square") => Ok(SymbolShapeHint::FORCE_SQUARE),
+        Some("rectangle") => Ok(SymbolShapeHint::FORCE_RECTANGLE),
+        Some(other) => Err(format!("DataMatrix shape must be square or rectangle, got \"{}\"", other)),
+    }
+}
+
+/// Encode `data` with a symbology name as used in `BarcodeElement`
+pub fn encode(symbology: &str, data: &str, options: &BarcodeOptions) -> Result<Symbol, String> {
+    let format = match format_for(symbology) {
+        Ok(format) => format,
+        Err(e) => {
+            return match plugins::registry().symbology(symbology) {
+                Some(plugin) => plugin.encode(data),
+                None => Err(e),
+            }
+        }
+    };
+    // GS1 data is encoded with FNC1 separators but printed with brackets
+    let gs1_datamatrix = symbology == "gs1datamatrix";
+    let (content, text) = if symbology == "gs1128" {
+        let elements = gs1::parse(data)?;
+        (gs1::barcode_content(&elements), gs1::human_readable(&elements))
+    } else if gs1_datamatrix {
+        // The DataMatrix encoder writes the leading FNC1 and turns each GS
+        // into one, but only in its compact mode
+        let elements = gs1::parse(data)?;
+        (gs1::joined(&elements, gs1::GS), gs1::human_readable(&elements))
+    } else {
+        let data = prepare(symbology, data)?;
+        (data.clone(), data)
+    };
+
+    let hints = EncodeHints {
+        // Quiet zones are the layout's job, not the symbol's
+        Margin: Some("0".to_string()),
+        ErrorCorrection: error_correction(&format, options.error_correction.as_deref())?,
+        DataMatrixShape: (format == BarcodeFormat::DATA_MATRIX)
+            .then(|| shape_hint(options.shape.as_deref()))
+            .transpose()?,
+        DataMatrixCompact: gs1_datamatrix.then_some(true),
+        Gs1Format: gs1_datamatrix.then_some(true),
+        ..EncodeHints::default()
+    };
+
+    let matrix = MultiFormatWriter
+        .encode_with_hints(&content, &format, 0, 0, &hints)
+        .map_err(|e| format!("Cannot encode \"{}\" as {}: {}", text, symbology, e))?;
+
+    let width = matrix.getWidth();
+    let height = if is_2d(&format) { matrix.getHeight() } else { 1 };
+    let modules = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| matrix.get(x, y))
+        .collect();
+
+    Ok(Symbol {
+        width,
+        height,
+        modules,
+        text,
+    })
+}
+
+// Validate data and complete check digits so the human readable text
+// matches what is encoded
+fn prepare(symbology: &str, data: &str) -> Result<String, String> {
+    let data = data.trim();
+    let digits_only = !data.is_empty() && data.chars().all(|c| c.is_ascii_digit());
+    let expect_digits = |lengths: &[usize]| -> Result<(), String> {
+        if digits_only && lengths.contains(&data.len()) {
+            Ok(())
+        } else {
+            let lengths: Vec<String> = lengths.iter().map(|l| l.to_string()).collect();
+            Err(format!("{} needs {} digits, got \"{}\"", symbology, lengths.join(" or "), data))
+        }
+    };
+
+    match symbology {
+        "ean13" => expect_digits(&[12, 13])?,
+        "ean8" => expect_digits(&[7, 8])?,
+        "upca" => expect_digits(&[11, 12])?,
+        "upce" => expect_digits(&[7, 8])?,
+        "itf14" => expect_digits(&[13, 14])?,
+        "itf" if !digits_only || !data.len().is_multiple_of(2) => {
+            return Err(format!("itf needs an even number of digits, got \"{}\"", data));
+        }
+        _ if data.is_empty() => return Err("Barcode data is empty".to_string()),
+        _ => {}
+    }
+
+    let Some(full_length) = check_digits::full_length(symbology) else {
+        return Ok(data.to_string());
+    };
+    if data.len() < full_length {
+        return Ok(format!("{}{}", data, check_digits::mod10(data)));
+    }
+    if !check_digits::is_valid(data) {
+        let (body, check) = data.split_at(full_length - 1);
+        return Err(format!(
+            "{} \"{}\" has check digit {}, expected {}",
+            symbology,
+            data,
+            check,
+            check_digits::mod10(body)
+        ));
+    }
+    Ok(data.to_string())
+}
+
+/// A barcode at a physical size, for the editor preview. Returns SVG, or a
+/// PNG data URL when `dpi` is given. Uses the same layout as the print
+/// renderer.
+#[tauri::command]
+pub fn generate_barcode(
+    fonts: State<'_, FontLibrary>,
+    barcode: BarcodeElement,
+    width_mm: f64,
+    height_mm: f64,
+    dpi: Option<f64>,
+) -> Result<String, String> {
+    let symbol = encode(&barcode.symbology, &barcode.data, &barcode.options)?;
+    let body = render::barcode_svg(&symbol, width_mm, height_mm, &barcode, dpi);
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n{body}</svg>\n",
+        w = width_mm,
+        h = height_mm,
+        body = body
+    );
+
+    match dpi {
+        Some(dpi) => {
+            let png = render::rasterize(&svg, fonts.database(), width_mm, height_mm, dpi)?;
+            Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+        }
+        None => Ok(svg),
+    }
+}
+
+/// Payload text for structured QR content (Wi-Fi, contacts, URLs, places)
+#[tauri::command]
+pub fn build_qr_payload(payload: QrPayload) -> Result<String, String> {
+    payload.build()
+}
+
+/// GS1 or HIBC UDI data for a medical device label, validated and checked
+/// to encode in the chosen symbology
+#[tauri::command]
+pub fn compose_udi(request: udi::UdiRequest) -> Result<udi::UdiBarcode, String> {
+    request.compose()
+}
+
+/// GS1 Digital Link URI for a GTIN with its lot, serial and dates, checked
+/// to fit a QR code
+#[tauri::command]
+pub fn compose_digital_link(request: digital_link::DigitalLinkRequest) -> Result<digital_link::DigitalLinkBarcode, String> {
+    request.compose()
+}
+
+/// In-store EAN-13 with a price or weight embedded after the item code
+#[tauri::command]
+pub fn compose_variable_measure(request: variable_measure::VariableMeasureRequest) -> Result<String, String> {
+    request.compose()
+}
+
+/// Check bracketed GS1 data and list its element strings, so the editor can
+/// show what each AI means before anything is printed
+#[tauri::command]
+pub fn parse_gs1(data: String) -> Result<Vec<gs1::ElementString>, String> {
+    gs1::parse(&data)
+}
+
+/// Check digit for a number given without one, e.g. a 12 digit EAN-13 body
+#[tauri::command]
+pub fn compute_check_digit(digits: String) -> Result<u32, String> {
+    let digits = digits.trim();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Check digits are computed over digits only, got \"{}\"", digits));
+    }
+    Ok(check_digits::mod10(digits))
+}
+
+/// Whether a complete number (EAN/UPC, GTIN, SSCC, ...) ends in its correct
+/// check digit
+#[tauri::command]
+pub fn verify_check_digit(number: String) -> bool {
+    check_digits::is_valid(number.trim())
+}
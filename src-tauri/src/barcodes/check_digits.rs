@@ -0,0 +1,60 @@
+// GS1 mod-10 check digits, shared by EAN/UPC, ITF-14, GTIN and SSCC numbers
+// as well as the numeric GS1 AIs that carry one
+
+/// Check digit for the digits before it
+pub fn mod10(digits: &str) -> u32 {
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { d })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+/// Whether the last digit of `number` is its correct check digit
+pub fn is_valid(number: &str) -> bool {
+    match number.len().checked_sub(1) {
+        Some(split) if split > 0 && number.chars().all(|c| c.is_ascii_digit()) => {
+            let (body, check) = number.split_at(split);
+            check == mod10(body).to_string()
+        }
+        _ => false,
+    }
+}
+
+/// Length including the check digit, for symbologies and numbers that end
+/// in a mod-10 check digit. UPC-E check digits are computed over the
+/// expanded UPC-A number, so those are left to the encoder.
+pub fn full_length(symbology: &str) -> Option<usize> {
+    Some(match symbology {
+        "ean13" | "gtin13" => 13,
+        "ean8" | "gtin8" => 8,
+        "upca" | "gtin12" => 12,
+        "itf14" | "gtin14" => 14,
+        "sscc" => 18,
+        _ => return None,
+    })
+}
+
+/// `data` with a missing check digit appended or a wrong one replaced.
+/// `None` when the data is already correct or has no check digit to fix.
+pub fn fix(symbology: &str, data: &str) -> Option<String> {
+    let full_length = full_length(symbology)?;
+    let data = data.trim();
+    if !data.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let body = if data.len() == full_length {
+        if is_valid(data) {
+            return None;
+        }
+        &data[..full_length - 1]
+    } else if data.len() + 1 == full_length {
+        data
+    } else {
+        return None;
+    };
+    Some(format!("{}{}", body, mod10(body)))
+}
@@ -0,0 +1,106 @@
+// GS1 Digital Link: the GTIN and its lot, serial and dates as a web address,
+// e.g. "https://id.gs1.org/01/09501101530003/10/LOT42?17=261231", printed as
+// a QR code. Phones open it as a link while retail scanners read the same
+// GS1 data they'd get from an EAN-13 or GS1 DataMatrix.
+use super::gs1;
+use super::udi::{field, gtin14, parse_date, ProductionIds};
+use crate::document::BarcodeOptions;
+use serde::{Deserialize, Serialize};
+
+/// GS1's own resolver, for brands without a domain of their own
+pub const DEFAULT_DOMAIN: &str = "https://id.gs1.org";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DigitalLinkRequest {
+    /// Scheme and host the link points to, e.g. "https://example.com"
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// GTIN-8, -12, -13 or -14, with its check digit
+    pub gtin: String,
+    #[serde(flatten)]
+    pub production: ProductionIds,
+}
+
+/// Digital Link data ready for a barcode element
+#[derive(Debug, Clone, Serialize)]
+pub struct DigitalLinkBarcode {
+    pub symbology: String,
+    /// The URI
+    pub data: String,
+    /// The same data as bracketed GS1 element strings, to print under the
+    /// symbol or check against another barcode
+    pub human_readable: String,
+}
+
+// Characters that go into a URI path or query as they are (RFC 3986
+// unreserved); the rest of the GS1 character set is percent-encoded
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+impl DigitalLinkRequest {
+    pub fn compose(&self) -> Result<DigitalLinkBarcode, String> {
+        let domain = self
+            .domain
+            .as_deref()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .unwrap_or(DEFAULT_DOMAIN)
+            .trim_end_matches('/');
+        if !domain.starts_with("https://") && !domain.starts_with("http://") {
+            return Err(format!(
+                "A Digital Link domain starts with https://, got \"{}\"",
+                domain
+            ));
+        }
+        let gtin = gtin14(&self.gtin)?;
+
+        // The GTIN and its qualifiers (lot, then serial) form the path; dates
+        // are attributes in the query
+        let mut path = vec![("01", gtin)];
+        if let Some(lot) = field(&self.production.lot) {
+            path.push(("10", lot.to_string()));
+        }
+        if let Some(serial) = field(&self.production.serial) {
+            path.push(("21", serial.to_string()));
+        }
+        let mut query = Vec::new();
+        if let Some(date) = field(&self.production.manufactured) {
+            query.push(("11", parse_date(date, "manufacturing")?.format("%y%m%d").to_string()));
+        }
+        if let Some(date) = field(&self.production.expiry) {
+            query.push(("17", parse_date(date, "expiry")?.format("%y%m%d").to_string()));
+        }
+
+        // Checked as element strings, so lots and serials follow the same
+        // length and character rules as in any other GS1 symbol
+        let bracketed: String = path
+            .iter()
+            .chain(&query)
+            .map(|(ai, value)| format!("({}){}", ai, value))
+            .collect();
+        let elements = gs1::parse(&bracketed)?;
+
+        let mut uri = domain.to_string();
+        for (ai, value) in &path {
+            uri += &format!("/{}/{}", ai, percent_encode(value));
+        }
+        for (i, (ai, value)) in query.iter().enumerate() {
+            uri += &format!("{}{}={}", if i == 0 { '?' } else { '&' }, ai, value);
+        }
+        super::encode("qr", &uri, &BarcodeOptions::default())?;
+        Ok(DigitalLinkBarcode {
+            symbology: "qr".to_string(),
+            data: uri,
+            human_readable: gs1::human_readable(&elements),
+        })
+    }
+}
@@ -0,0 +1,253 @@
+// GS1 Application Identifier (AI) parsing for GS1-128 and other GS1 symbols.
+// Data is written in the bracketed human readable form, e.g.
+// "(01)09501101530003(17)261231(10)LOT42", and checked against the AI's
+// length, character set, date and check digit rules before encoding.
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// FNC1, as understood by the Code 128 encoder. Leads every GS1-128 symbol
+/// and terminates variable length fields that aren't last.
+pub const FNC1: char = '\u{00f1}';
+
+/// Group separator, standing in for FNC1 in GS1 DataMatrix content
+pub const GS: char = '\u{001d}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Numeric,
+    /// GS1 AI encodable character set 82
+    Alphanumeric,
+}
+
+struct AiSpec {
+    ai: &'static str,
+    title: &'static str,
+    charset: Charset,
+    min: usize,
+    max: usize,
+    /// Last digit is a GS1 mod-10 check digit
+    check_digit: bool,
+    /// YYMMDD
+    date: bool,
+}
+
+const fn fixed(ai: &'static str, title: &'static str, len: usize) -> AiSpec {
+    AiSpec { ai, title, charset: Charset::Numeric, min: len, max: len, check_digit: false, date: false }
+}
+
+const fn checked(ai: &'static str, title: &'static str, len: usize) -> AiSpec {
+    AiSpec { ai, title, charset: Charset::Numeric, min: len, max: len, check_digit: true, date: false }
+}
+
+const fn date(ai: &'static str, title: &'static str) -> AiSpec {
+    AiSpec { ai, title, charset: Charset::Numeric, min: 6, max: 6, check_digit: false, date: true }
+}
+
+const fn numeric(ai: &'static str, title: &'static str, max: usize) -> AiSpec {
+    AiSpec { ai, title, charset: Charset::Numeric, min: 1, max, check_digit: false, date: false }
+}
+
+const fn text(ai: &'static str, title: &'static str, max: usize) -> AiSpec {
+    AiSpec { ai, title, charset: Charset::Alphanumeric, min: 1, max, check_digit: false, date: false }
+}
+
+// The AIs seen on logistics, retail and healthcare labels. Measurement AIs
+// (31nn-36nn, 39nn) carry the decimal point position in their last digit and
+// are matched on their first three digits.
+const AIS: &[AiSpec] = &[
+    checked("00", "SSCC", 18),
+    checked("01", "GTIN", 14),
+    checked("02", "CONTENT", 14),
+    text("10", "BATCH/LOT", 20),
+    date("11", "PROD DATE"),
+    date("12", "DUE DATE"),
+    date("13", "PACK DATE"),
+    date("15", "BEST BEFORE"),
+    date("16", "SELL BY"),
+    date("17", "USE BY"),
+    fixed("20", "VARIANT", 2),
+    text("21", "SERIAL", 20),
+    text("22", "CPV", 20),
+    text("240", "ADDITIONAL ID", 30),
+    text("241", "CUST. PART No.", 30),
+    text("250", "SECONDARY SERIAL", 30),
+    text("251", "REF. TO SOURCE", 30),
+    text("254", "GLN EXTENSION", 20),
+    numeric("30", "VAR. COUNT", 8),
+    fixed("310", "NET WEIGHT (kg)", 6),
+    fixed("311", "LENGTH (m)", 6),
+    fixed("312", "WIDTH (m)", 6),
+    fixed("313", "HEIGHT (m)", 6),
+    fixed("314", "AREA (m2)", 6),
+    fixed("315", "NET VOLUME (l)", 6),
+    fixed("316", "NET VOLUME (m3)", 6),
+    fixed("320", "NET WEIGHT (lb)", 6),
+    fixed("330", "GROSS WEIGHT (kg)", 6),
+    fixed("340", "GROSS WEIGHT (lb)", 6),
+    numeric("37", "COUNT", 8),
+    numeric("390", "AMOUNT", 15),
+    numeric("392", "PRICE", 15),
+    text("400", "ORDER NUMBER", 30),
+    text("401", "GINC", 30),
+    checked("402", "GSIN", 17),
+    text("403", "ROUTE", 30),
+    checked("410", "SHIP TO LOC", 13),
+    checked("411", "BILL TO", 13),
+    checked("412", "PURCHASE FROM", 13),
+    checked("413", "SHIP FOR LOC", 13),
+    checked("414", "LOC No.", 13),
+    checked("415", "PAY TO", 13),
+    checked("416", "PROD/SERV LOC", 13),
+    text("420", "SHIP TO POST", 20),
+    fixed("422", "ORIGIN", 3),
+    fixed("424", "COUNTRY - PROCESS", 3),
+    fixed("426", "COUNTRY - FULL PROCESS", 3),
+    fixed("7003", "EXPIRY TIME", 10),
+    text("8004", "GIAI", 30),
+    fixed("8005", "PRICE PER UNIT", 6),
+    text("8012", "VERSION", 20),
+    text("8020", "REF No.", 25),
+    text("90", "INTERNAL", 30),
+    text("91", "INTERNAL", 90),
+    text("92", "INTERNAL", 90),
+    text("93", "INTERNAL", 90),
+    text("94", "INTERNAL", 90),
+    text("95", "INTERNAL", 90),
+    text("96", "INTERNAL", 90),
+    text("97", "INTERNAL", 90),
+    text("98", "INTERNAL", 90),
+    text("99", "INTERNAL", 90),
+];
+
+// Leading digits of AIs whose length is fixed by the GS1 general
+// specification; these never need an FNC1 separator after them
+const PREDEFINED_LENGTH: &[&str] = &[
+    "00", "01", "02", "03", "04", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20", "31", "32", "33",
+    "34", "35", "36", "41",
+];
+
+/// One AI and its value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ElementString {
+    pub ai: String,
+    pub title: String,
+    pub value: String,
+}
+
+fn spec_for(ai: &str) -> Option<&'static AiSpec> {
+    AIS.iter().find(|spec| {
+        spec.ai == ai || (ai.len() == 4 && spec.ai.len() == 3 && is_measure(spec.ai) && ai.starts_with(spec.ai))
+    })
+}
+
+fn is_measure(ai: &str) -> bool {
+    matches!(&ai[..2], "31" | "32" | "33" | "34" | "35" | "36" | "39")
+}
+
+fn is_gs1_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!\"%&'()*+,-./:;<=>?_".contains(c)
+}
+
+/// Split bracketed GS1 data into element strings and validate each one
+pub fn parse(data: &str) -> Result<Vec<ElementString>, String> {
+    let data = data.trim();
+    if !data.starts_with('(') {
+        return Err(format!("GS1 data must start with an AI in brackets, e.g. (01), got \"{}\"", data));
+    }
+
+    let mut elements: Vec<ElementString> = Vec::new();
+    let mut rest = data;
+    while let Some(after) = rest.strip_prefix('(') {
+        let close = after.find(')').ok_or_else(|| format!("Unclosed AI bracket in \"{}\"", data))?;
+        let ai = &after[..close];
+        let value_end = after[close + 1..].find('(').map_or(after.len(), |i| close + 1 + i);
+        let value = &after[close + 1..value_end];
+        rest = &after[value_end..];
+
+        let spec = spec_for(ai).ok_or_else(|| format!("Unknown or unsupported GS1 AI ({})", ai))?;
+        validate(ai, spec, value)?;
+        if elements.iter().any(|e| e.ai == ai) {
+            return Err(format!("AI ({}) appears more than once", ai));
+        }
+        elements.push(ElementString {
+            ai: ai.to_string(),
+            title: spec.title.to_string(),
+            value: value.to_string(),
+        });
+    }
+    Ok(elements)
+}
+
+fn validate(ai: &str, spec: &AiSpec, value: &str) -> Result<(), String> {
+    let len = value.chars().count();
+    if len < spec.min || len > spec.max {
+        let expected = if spec.min == spec.max {
+            format!("{}", spec.max)
+        } else {
+            format!("up to {}", spec.max)
+        };
+        return Err(format!("AI ({}) {} needs {} characters, got {} in \"{}\"", ai, spec.title, expected, len, value));
+    }
+    match spec.charset {
+        Charset::Numeric if !value.chars().all(|c| c.is_ascii_digit()) => {
+            return Err(format!("AI ({}) {} must be digits only, got \"{}\"", ai, spec.title, value));
+        }
+        Charset::Alphanumeric => {
+            if let Some(c) = value.chars().find(|c| !is_gs1_char(*c)) {
+                return Err(format!("AI ({}) {} contains '{}', which GS1 doesn't allow", ai, spec.title, c));
+            }
+        }
+        _ => {}
+    }
+    if spec.check_digit {
+        let (body, check) = value.split_at(value.len() - 1);
+        let expected = super::check_digits::mod10(body);
+        if check != expected.to_string() {
+            return Err(format!("AI ({}) {} has check digit {}, expected {}", ai, spec.title, check, expected));
+        }
+    }
+    if spec.date {
+        validate_date(value).map_err(|e| format!("AI ({}) {}: {}", ai, spec.title, e))?;
+    }
+    Ok(())
+}
+
+// YYMMDD, where a day of 00 means the end of the month
+fn validate_date(value: &str) -> Result<(), String> {
+    let year: i32 = value[0..2].parse().unwrap_or_default();
+    let month: u32 = value[2..4].parse().unwrap_or_default();
+    let day: u32 = value[4..6].parse().unwrap_or_default();
+    if !(1..=12).contains(&month) {
+        return Err(format!("invalid month {:02} in \"{}\"", month, value));
+    }
+    if day != 0 && NaiveDate::from_ymd_opt(2000 + year, month, day).is_none() {
+        return Err(format!("invalid date \"{}\", expected YYMMDD", value));
+    }
+    Ok(())
+}
+
+/// Barcode content with FNC1 at the start and after each variable length
+/// field that isn't last
+pub fn barcode_content(elements: &[ElementString]) -> String {
+    format!("{}{}", FNC1, joined(elements, FNC1))
+}
+
+/// Element strings run together, with `separator` after each variable length
+/// field that isn't last
+pub fn joined(elements: &[ElementString], separator: char) -> String {
+    let mut content = String::new();
+    for (i, element) in elements.iter().enumerate() {
+        content.push_str(&element.ai);
+        content.push_str(&element.value);
+        let predefined = PREDEFINED_LENGTH.contains(&&element.ai[..2]);
+        if !predefined && i + 1 < elements.len() {
+            content.push(separator);
+        }
+    }
+    content
+}
+
+/// Bracketed text printed under the bars
+pub fn human_readable(elements: &[ElementString]) -> String {
+    elements.iter().map(|e| format!("({}){}", e.ai, e.value)).collect()
+}
@@ -0,0 +1,275 @@
+// Builders for structured QR payloads. Each field is escaped for its format
+// here, so the frontend never has to assemble the strings itself.
+use serde::Deserialize;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QrPayload {
+    Wifi {
+        ssid: String,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        security: WifiSecurity,
+        #[serde(default)]
+        hidden: bool,
+    },
+    Vcard(Contact),
+    Mecard(Contact),
+    Url { url: String },
+    Geo {
+        latitude: f64,
+        longitude: f64,
+        /// Metres above sea level
+        #[serde(default)]
+        altitude: Option<f64>,
+        /// Place name shown by map apps
+        #[serde(default)]
+        label: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WifiSecurity {
+    #[default]
+    Wpa,
+    Wep,
+    /// Open network, no password
+    Nopass,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Contact {
+    pub first_name: String,
+    pub last_name: String,
+    pub organization: Option<String>,
+    pub title: Option<String>,
+    pub phones: Vec<String>,
+    pub emails: Vec<String>,
+    pub url: Option<String>,
+    pub street: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Contact {
+    fn full_name(&self) -> String {
+        [self.first_name.trim(), self.last_name.trim()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn has_address(&self) -> bool {
+        [&self.street, &self.city, &self.postal_code, &self.country]
+            .iter()
+            .any(|part| part.as_deref().is_some_and(|p| !p.trim().is_empty()))
+    }
+}
+
+impl QrPayload {
+    pub fn build(&self) -> Result<String, String> {
+        match self {
+            QrPayload::Wifi { ssid, password, security, hidden } => wifi(ssid, password.as_deref(), *security, *hidden),
+            QrPayload::Vcard(contact) => vcard(contact),
+            QrPayload::Mecard(contact) => mecard(contact),
+            QrPayload::Url { url } => url_payload(url),
+            QrPayload::Geo { latitude, longitude, altitude, label } => {
+                geo(*latitude, *longitude, *altitude, label.as_deref())
+            }
+        }
+    }
+}
+
+// Wi-Fi and MeCard share the same backslash escaping of their separators
+fn escape_mecard(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn wifi(ssid: &str, password: Option<&str>, security: WifiSecurity, hidden: bool) -> Result<String, String> {
+    if ssid.is_empty() {
+        return Err("Wi-Fi network name is empty".to_string());
+    }
+    let password = password.unwrap_or_default();
+    let security = match security {
+        WifiSecurity::Wpa => "WPA",
+        WifiSecurity::Wep => "WEP",
+        WifiSecurity::Nopass => "nopass",
+    };
+    if security != "nopass" && password.is_empty() {
+        return Err(format!("A {} network needs a password", security));
+    }
+
+    let mut payload = format!("WIFI:T:{};S:{};", security, escape_mecard(ssid));
+    if security != "nopass" {
+        let _ = write!(payload, "P:{};", escape_mecard(password));
+    }
+    if hidden {
+        payload.push_str("H:true;");
+    }
+    payload.push(';');
+    Ok(payload)
+}
+
+// vCard 3.0 (RFC 2426) text value escaping
+fn escape_vcard(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.trim().chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn vcard(contact: &Contact) -> Result<String, String> {
+    let full_name = contact.full_name();
+    if full_name.is_empty() && contact.organization.is_none() {
+        return Err("A contact needs a name or an organization".to_string());
+    }
+    let field = |value: &Option<String>| escape_vcard(value.as_deref().unwrap_or_default());
+    let display_name = if full_name.is_empty() { field(&contact.organization) } else { escape_vcard(&full_name) };
+
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("N:{};{};;;", escape_vcard(&contact.last_name), escape_vcard(&contact.first_name)),
+        format!("FN:{}", display_name),
+    ];
+    if contact.organization.is_some() {
+        lines.push(format!("ORG:{}", field(&contact.organization)));
+    }
+    if contact.title.is_some() {
+        lines.push(format!("TITLE:{}", field(&contact.title)));
+    }
+    for phone in &contact.phones {
+        lines.push(format!("TEL:{}", escape_vcard(phone)));
+    }
+    for email in &contact.emails {
+        lines.push(format!("EMAIL:{}", escape_vcard(email)));
+    }
+    if contact.url.is_some() {
+        lines.push(format!("URL:{}", field(&contact.url)));
+    }
+    if contact.has_address() {
+        lines.push(format!(
+            "ADR:;;{};{};;{};{}",
+            field(&contact.street),
+            field(&contact.city),
+            field(&contact.postal_code),
+            field(&contact.country)
+        ));
+    }
+    if contact.note.is_some() {
+        lines.push(format!("NOTE:{}", field(&contact.note)));
+    }
+    lines.push("END:VCARD".to_string());
+    Ok(lines.join("\r\n"))
+}
+
+// MeCard is much shorter than a vCard, which keeps small QR codes scannable
+fn mecard(contact: &Contact) -> Result<String, String> {
+    if contact.full_name().is_empty() {
+        return Err("A MeCard contact needs a name".to_string());
+    }
+    let mut payload = format!(
+        "MECARD:N:{},{};",
+        escape_mecard(contact.last_name.trim()),
+        escape_mecard(contact.first_name.trim())
+    );
+    let mut push = |key: &str, value: &str| {
+        if !value.trim().is_empty() {
+            let _ = write!(payload, "{}:{};", key, escape_mecard(value.trim()));
+        }
+    };
+    if let Some(organization) = &contact.organization {
+        push("ORG", organization);
+    }
+    for phone in &contact.phones {
+        push("TEL", phone);
+    }
+    for email in &contact.emails {
+        push("EMAIL", email);
+    }
+    if let Some(url) = &contact.url {
+        push("URL", url);
+    }
+    if contact.has_address() {
+        let address: Vec<&str> = [&contact.street, &contact.postal_code, &contact.city, &contact.country]
+            .iter()
+            .filter_map(|part| part.as_deref().map(str::trim))
+            .filter(|part| !part.is_empty())
+            .collect();
+        push("ADR", &address.join(", "));
+    }
+    if let Some(note) = &contact.note {
+        push("NOTE", note);
+    }
+    payload.push(';');
+    Ok(payload)
+}
+
+fn url_payload(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err("URL is empty".to_string());
+    }
+    if url.chars().any(char::is_whitespace) {
+        return Err(format!("URL contains spaces: \"{}\"", url));
+    }
+    // Scanners only open links that carry a scheme
+    if url.contains("://") || url.starts_with("mailto:") || url.starts_with("tel:") {
+        Ok(url.to_string())
+    } else {
+        Ok(format!("https://{}", url))
+    }
+}
+
+fn geo(latitude: f64, longitude: f64, altitude: Option<f64>, label: Option<&str>) -> Result<String, String> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!("Latitude {} is out of range", latitude));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("Longitude {} is out of range", longitude));
+    }
+    // RFC 5870; six decimals is ~10cm, plenty for a map pin
+    let mut payload = format!("geo:{:.6},{:.6}", latitude, longitude);
+    if let Some(altitude) = altitude {
+        let _ = write!(payload, ",{}", altitude);
+    }
+    if let Some(label) = label.map(str::trim).filter(|l| !l.is_empty()) {
+        let _ = write!(payload, "?q={}", percent_encode(label));
+    }
+    Ok(payload)
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
@@ -0,0 +1,290 @@
+// Unique Device Identification (UDI) for medical device labels: the device
+// identifier (DI) plus production identifiers (PI) such as lot, serial
+// number and dates, composed as GS1 or HIBC data. Everything is checked up
+// front and the result encoded once, so a label that prints is one a
+// verifier will accept.
+use super::check_digits;
+use super::gs1;
+use crate::document::BarcodeOptions;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Code 39 character values used by the HIBC mod 43 check character
+const HIBC_CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+/// Longest HIBC product code, lot and serial number
+const HIBC_MAX_FIELD: usize = 18;
+
+/// GS1-128 symbols carry at most 48 data characters
+const GS1_128_MAX_DATA: usize = 48;
+
+/// Production identifiers, all optional; dates as YYYY-MM-DD
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProductionIds {
+    pub lot: Option<String>,
+    pub serial: Option<String>,
+    pub expiry: Option<String>,
+    pub manufactured: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "issuer", rename_all = "snake_case")]
+pub enum UdiRequest {
+    Gs1 {
+        /// GTIN-8, -12, -13 or -14, with its check digit
+        gtin: String,
+        #[serde(flatten)]
+        production: ProductionIds,
+        /// Software version of software as a medical device, AI (8012)
+        #[serde(default)]
+        software_version: Option<String>,
+        /// "gs1datamatrix" (default) or "gs1128"
+        #[serde(default)]
+        symbology: Option<String>,
+    },
+    Hibc {
+        /// Labeler identification code (LIC): four characters, the first a letter
+        labeler: String,
+        /// Product or catalogue number
+        product: String,
+        /// Packaging level, 0 for the unit of use
+        #[serde(default)]
+        unit_of_measure: u8,
+        #[serde(flatten)]
+        production: ProductionIds,
+        /// "datamatrix" (default), "code128", "code39", "qr" or "aztec"
+        #[serde(default)]
+        symbology: Option<String>,
+    },
+}
+
+/// UDI data ready for a barcode element
+#[derive(Debug, Clone, Serialize)]
+pub struct UdiBarcode {
+    pub symbology: String,
+    /// Barcode element data: bracketed GS1 element strings, or HIBC data
+    /// including its check character
+    pub data: String,
+    /// Text to print with the symbol
+    pub human_readable: String,
+    /// The device identifier alone, for UDI databases (GUDID, EUDAMED): the
+    /// GTIN-14, or the HIBC primary data with its own check character
+    pub device_identifier: String,
+}
+
+impl UdiRequest {
+    pub fn compose(&self) -> Result<UdiBarcode, String> {
+        let udi = match self {
+            UdiRequest::Gs1 {
+                gtin,
+                production,
+                software_version,
+                symbology,
+            } => gs1_udi(gtin, production, software_version.as_deref(), symbology.as_deref())?,
+            UdiRequest::Hibc {
+                labeler,
+                product,
+                unit_of_measure,
+                production,
+                symbology,
+            } => hibc_udi(labeler, product, *unit_of_measure, production, symbology.as_deref())?,
+        };
+        // Catches data that's valid but doesn't fit the symbology
+        super::encode(&udi.symbology, &udi.data, &BarcodeOptions::default())?;
+        Ok(udi)
+    }
+}
+
+pub fn parse_date(value: &str, what: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("The {} date must be YYYY-MM-DD, got \"{}\"", what, value))
+}
+
+/// Trimmed value, None when absent or blank
+pub fn field(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// A GTIN-8, -12, -13 or -14 checked and padded to 14 digits
+pub fn gtin14(gtin: &str) -> Result<String, String> {
+    let gtin = gtin.trim();
+    if !gtin.chars().all(|c| c.is_ascii_digit()) || ![8, 12, 13, 14].contains(&gtin.len()) {
+        return Err(format!("A GTIN has 8, 12, 13 or 14 digits, got \"{}\"", gtin));
+    }
+    if !check_digits::is_valid(gtin) {
+        let (body, check) = gtin.split_at(gtin.len() - 1);
+        return Err(format!(
+            "GTIN \"{}\" has check digit {}, expected {}",
+            gtin,
+            check,
+            check_digits::mod10(body)
+        ));
+    }
+    Ok(format!("{:0>14}", gtin))
+}
+
+fn gs1_udi(
+    gtin: &str,
+    production: &ProductionIds,
+    software_version: Option<&str>,
+    symbology: Option<&str>,
+) -> Result<UdiBarcode, String> {
+    let gtin = gtin14(gtin)?;
+
+    // Dates first, then lot and serial: the order the GS1 UDI guideline
+    // prints them in
+    let mut data = format!("(01){}", gtin);
+    if let Some(date) = field(&production.manufactured) {
+        data += &format!("(11){}", parse_date(date, "manufacturing")?.format("%y%m%d"));
+    }
+    if let Some(date) = field(&production.expiry) {
+        data += &format!("(17){}", parse_date(date, "expiry")?.format("%y%m%d"));
+    }
+    if let Some(lot) = field(&production.lot) {
+        data += &format!("(10){}", lot);
+    }
+    if let Some(serial) = field(&production.serial) {
+        data += &format!("(21){}", serial);
+    }
+    if let Some(version) = software_version.map(str::trim).filter(|v| !v.is_empty()) {
+        data += &format!("(8012){}", version);
+    }
+    let elements = gs1::parse(&data)?;
+
+    let symbology = symbology.unwrap_or("gs1datamatrix");
+    match symbology {
+        "gs1datamatrix" => {}
+        "gs1128" => {
+            let length = gs1::barcode_content(&elements).chars().count() - 1;
+            if length > GS1_128_MAX_DATA {
+                return Err(format!(
+                    "The UDI needs {} characters, more than GS1-128's {}; use GS1 DataMatrix",
+                    length, GS1_128_MAX_DATA
+                ));
+            }
+        }
+        _ => {
+            return Err(format!(
+                "GS1 UDIs are printed as gs1datamatrix or gs1128, not {}",
+                symbology
+            ))
+        }
+    }
+    Ok(UdiBarcode {
+        symbology: symbology.to_string(),
+        human_readable: gs1::human_readable(&elements),
+        data,
+        device_identifier: gtin,
+    })
+}
+
+/// HIBC mod 43 check character over `data`
+pub fn hibc_check_character(data: &str) -> Result<char, String> {
+    let mut sum = 0;
+    for c in data.chars() {
+        sum += HIBC_CHARSET
+            .find(c)
+            .ok_or_else(|| format!("'{}' can't be used in HIBC data", c))?;
+    }
+    Ok(HIBC_CHARSET.as_bytes()[sum % 43] as char)
+}
+
+// Uppercased `value`, checked to be 1 to `HIBC_MAX_FIELD` letters, digits,
+// '-' or '.'
+fn hibc_field(value: &str, what: &str) -> Result<String, String> {
+    let value = value.trim().to_ascii_uppercase();
+    if value.is_empty() || value.len() > HIBC_MAX_FIELD {
+        return Err(format!(
+            "The HIBC {} needs 1 to {} characters, got \"{}\"",
+            what, HIBC_MAX_FIELD, value
+        ));
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '.')
+    {
+        return Err(format!(
+            "The HIBC {} contains '{}'; use letters, digits, '-' or '.'",
+            what, c
+        ));
+    }
+    Ok(value)
+}
+
+fn hibc_udi(
+    labeler: &str,
+    product: &str,
+    unit_of_measure: u8,
+    production: &ProductionIds,
+    symbology: Option<&str>,
+) -> Result<UdiBarcode, String> {
+    let labeler = labeler.trim().to_ascii_uppercase();
+    let valid_labeler = labeler.len() == 4
+        && labeler.starts_with(|c: char| c.is_ascii_uppercase())
+        && labeler.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid_labeler {
+        return Err(format!(
+            "The HIBC labeler code is four letters or digits starting with a letter, got \"{}\"",
+            labeler
+        ));
+    }
+    let product = hibc_field(product, "product number")?;
+    if unit_of_measure > 9 {
+        return Err(format!("The HIBC unit of measure is 0-9, got {}", unit_of_measure));
+    }
+    let primary = format!("+{}{}{}", labeler, product, unit_of_measure);
+
+    // Secondary data: "$$3" with a YYMMDD expiry then the lot, "$$+3" for a
+    // serial number instead, "$" or "$+" without expiry; a serial number
+    // next to a lot, and the manufacturing date, go in "/S" and "/16D"
+    // supplemental fields
+    let lot = field(&production.lot)
+        .map(|lot| hibc_field(lot, "lot number"))
+        .transpose()?;
+    let serial = field(&production.serial)
+        .map(|serial| hibc_field(serial, "serial number"))
+        .transpose()?;
+    let expiry = field(&production.expiry)
+        .map(|date| parse_date(date, "expiry"))
+        .transpose()?;
+    let manufactured = field(&production.manufactured)
+        .map(|date| parse_date(date, "manufacturing"))
+        .transpose()?;
+    let mut secondary = match (&expiry, &lot, &serial) {
+        (Some(expiry), None, Some(serial)) => format!("$$+3{}{}", expiry.format("%y%m%d"), serial),
+        (Some(expiry), lot, _) => format!("$$3{}{}", expiry.format("%y%m%d"), lot.as_deref().unwrap_or_default()),
+        (None, None, Some(serial)) => format!("$+{}", serial),
+        (None, Some(lot), _) => format!("${}", lot),
+        (None, None, None) if manufactured.is_some() => "$$7".to_string(),
+        (None, None, None) => String::new(),
+    };
+    if let (Some(_), Some(serial)) = (&lot, &serial) {
+        secondary += &format!("/S{}", serial);
+    }
+    if let Some(date) = manufactured {
+        secondary += &format!("/16D{}", date.format("%Y%m%d"));
+    }
+
+    // Primary and secondary concatenated, with one check character
+    let body = if secondary.is_empty() {
+        primary.clone()
+    } else {
+        format!("{}/{}", primary, secondary)
+    };
+    let data = format!("{}{}", body, hibc_check_character(&body)?);
+
+    let symbology = symbology.unwrap_or("datamatrix");
+    if !["datamatrix", "code128", "code39", "qr", "aztec"].contains(&symbology) {
+        return Err(format!(
+            "HIBC UDIs are printed as datamatrix, code128, code39, qr or aztec, not {}",
+            symbology
+        ));
+    }
+    Ok(UdiBarcode {
+        symbology: symbology.to_string(),
+        human_readable: format!("*{}*", data),
+        data,
+        device_identifier: format!("{}{}", primary, hibc_check_character(&primary)?),
+    })
+}
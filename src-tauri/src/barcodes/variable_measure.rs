@@ -0,0 +1,136 @@
+// In-store variable measure EAN-13s: a restricted circulation prefix (02 or
+// 20-29), the retailer's item code, optionally a price check digit, and the
+// price or weight of this one item, e.g. 2 12345 01234 x for 12.34 or 1.234 kg.
+// Which prefix means price and which weight is up to the retailer's POS.
+use super::check_digits;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VariableMeasureRequest {
+    /// "02" or "20" to "29"
+    pub prefix: String,
+    /// Item code, padded with leading zeros to the room left by the value
+    pub item: String,
+    /// Price or weight, e.g. "12.34" or "1,234"
+    pub value: String,
+    /// Decimals the value is embedded with: 2 for prices, 3 for kilograms
+    #[serde(default = "default_decimals")]
+    pub decimals: usize,
+    /// Digits the value takes, 4 or 5
+    #[serde(default = "default_value_digits")]
+    pub value_digits: usize,
+    /// Put a price check digit in front of the value
+    #[serde(default)]
+    pub price_check: bool,
+}
+
+fn default_decimals() -> usize {
+    2
+}
+
+fn default_value_digits() -> usize {
+    5
+}
+
+impl VariableMeasureRequest {
+    pub fn compose(&self) -> Result<String, String> {
+        if !(4..=5).contains(&self.value_digits) {
+            return Err(format!("The value takes 4 or 5 digits, not {}", self.value_digits));
+        }
+        let prefix = self.prefix.trim();
+        if prefix.len() != 2 || !(prefix == "02" || prefix.starts_with('2')) {
+            return Err(format!("Variable measure prefixes are 02 or 20-29, got \"{}\"", prefix));
+        }
+        let item_digits = 10 - self.value_digits - usize::from(self.price_check);
+        let item = self.item.trim();
+        if item.is_empty() || item.len() > item_digits || !item.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!(
+                "The item code needs 1 to {} digits, got \"{}\"",
+                item_digits, item
+            ));
+        }
+        let value = self
+            .value
+            .trim()
+            .replace(',', ".")
+            .parse()
+            .map_err(|_| format!("\"{}\" is not a price or weight", self.value))?;
+        let code = format!("{}{:0>width$}", prefix, item, width = item_digits);
+        compose(&code, value, self.decimals, self.price_check)
+    }
+}
+
+/// EAN-13 data for `value` embedded after `code`, the prefix and item code,
+/// with `decimals` implied decimals. The value fills what's left of the 12
+/// digits, after a price check digit if `price_check` is set.
+pub fn compose(code: &str, value: f64, decimals: usize, price_check: bool) -> Result<String, String> {
+    let code = code.trim();
+    if !code.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Variable measure codes are digits only, got \"{}\"", code));
+    }
+    if !(code.starts_with("02") || code.starts_with('2')) {
+        return Err(format!(
+            "Variable measure codes start with prefix 02 or 20-29, got \"{}\"",
+            code
+        ));
+    }
+    let value_digits = 12usize
+        .checked_sub(code.len() + usize::from(price_check))
+        .filter(|digits| (4..=5).contains(digits))
+        .ok_or_else(|| format!("\"{}\" leaves no room for a 4 or 5 digit value in an EAN-13", code))?;
+
+    let scaled = (value * 10f64.powi(decimals as i32)).round();
+    if !(scaled >= 0.0 && scaled < 10f64.powi(value_digits as i32)) {
+        return Err(format!(
+            "{} doesn't fit in {} digits with {} decimals",
+            value, value_digits, decimals
+        ));
+    }
+    let value = format!("{:0width$}", scaled as u64, width = value_digits);
+    let body = if price_check {
+        format!("{}{}{}", code, price_check_digit(&value)?, value)
+    } else {
+        format!("{}{}", code, value)
+    };
+    Ok(format!("{}{}", body, check_digits::mod10(&body)))
+}
+
+// Weighting factors of the price check digit: "2-" doubles a digit and
+// subtracts the tens from the units of the product, "5+" multiplies by five
+// and adds them, "3" keeps the units of three times the digit
+fn two_minus(d: u32) -> u32 {
+    (2 * d % 10 + 10 - 2 * d / 10) % 10
+}
+
+fn three(d: u32) -> u32 {
+    3 * d % 10
+}
+
+fn five_plus(d: u32) -> u32 {
+    (5 * d % 10 + 5 * d / 10) % 10
+}
+
+fn five_minus(d: u32) -> u32 {
+    (5 * d % 10 + 10 - 5 * d / 10) % 10
+}
+
+/// GS1 price check digit (General Specifications 7.9.3) over a 4 or 5
+/// digit price or weight
+pub fn price_check_digit(value: &str) -> Result<u32, String> {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() != value.len() {
+        return Err(format!(
+            "Price check digits are computed over digits only, got \"{}\"",
+            value
+        ));
+    }
+    match digits[..] {
+        [a, b, c, d] => Ok(3 * (two_minus(a) + two_minus(b) + three(c) + five_minus(d)) % 10),
+        [a, b, c, d, e] => {
+            let sum = five_plus(a) + two_minus(b) + five_minus(c) + five_plus(d) + two_minus(e);
+            let target = (10 - sum % 10) % 10;
+            Ok((0..10).find(|&digit| five_minus(digit) == target).unwrap_or_default())
+        }
+        _ => Err(format!("Price check digits cover 4 or 5 digits, got \"{}\"", value)),
+    }
+}
@@ -0,0 +1,126 @@
+// Barcode verification before printing: each code is decoded back out of the
+// label as the printer will print it, scaled to its resolution and dithered,
+// so a code too small or too fine for the printer fails the job instead of
+// wasting a roll of labels nobody can scan.
+use super::{encode, format_for, snap_module, Symbol};
+use crate::document::{BarcodeElement, Document, Element, ElementKind};
+use crate::raster;
+use image::{GrayImage, Luma};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Light margin decoded around each code, in modules
+const LINEAR_QUIET_ZONE: f64 = 10.0;
+const MATRIX_QUIET_ZONE: f64 = 4.0;
+
+pub fn configure(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Decode every barcode of `document`, rendered to `png`, from the raster a
+/// `dpi` printer would print. Fails on the first code that can't be read or
+/// reads as something else. Plugin symbologies aren't checked.
+pub fn check(document: &Document, png: &[u8], dpi: f64) -> Result<(), String> {
+    let barcodes: Vec<(&Element, &BarcodeElement)> = document
+        .stacked()
+        .into_iter()
+        .filter_map(|element| match &element.kind {
+            ElementKind::Barcode(barcode) => Some((element, barcode)),
+            _ => None,
+        })
+        .collect();
+    if barcodes.is_empty() {
+        return Ok(());
+    }
+    let page = raster::print_raster(png, dpi)?;
+    for (element, barcode) in barcodes {
+        let Ok(format) = format_for(&barcode.symbology) else {
+            continue;
+        };
+        let symbol = encode(&barcode.symbology, &barcode.data, &barcode.options)?;
+        let image = crop(&page, element, barcode, &symbol, dpi);
+        let (width, height) = image.dimensions();
+        let result = rxing::helpers::detect_in_luma(image.into_raw(), width, height, Some(format)).map_err(|_| {
+            format!(
+                "The {} barcode \"{}\" can't be scanned when printed at {} dpi; make it larger",
+                barcode.symbology,
+                symbol.text,
+                dpi.round()
+            )
+        })?;
+        if !same_content(result.getText(), &barcode.data, &symbol.text) {
+            return Err(format!(
+                "The {} barcode \"{}\" scans as \"{}\" when printed at {} dpi; make it larger",
+                barcode.symbology,
+                symbol.text,
+                result.getText(),
+                dpi.round()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Decoders report GS1 separators, check digits and spacing differently, so
+// only letters and digits are compared
+fn same_content(decoded: &str, data: &str, text: &str) -> bool {
+    let significant = |s: &str| s.chars().filter(|c| c.is_alphanumeric()).collect::<String>();
+    let decoded = significant(decoded);
+    decoded == significant(data) || decoded == significant(text)
+}
+
+// The symbol of `element` with its quiet zone, in the element's own upright
+// frame at `dpi`, laid out as `render::barcode_svg` draws it for a printer
+// at that resolution. Off the page is white.
+fn crop(page: &GrayImage, element: &Element, barcode: &BarcodeElement, symbol: &Symbol, dpi: f64) -> GrayImage {
+    let px_per_mm = dpi / 25.4;
+    let fixed_module = barcode.options.module_size_mm.filter(|m| *m > 0.0);
+    let (width, height, quiet) = if symbol.is_linear() {
+        let module = match fixed_module {
+            Some(module) => snap_module(module, Some(dpi), false),
+            None => snap_module(element.width_mm / symbol.width as f64, Some(dpi), true),
+        };
+        let width = module * symbol.width as f64;
+        (
+            width,
+            element.height_mm,
+            width / symbol.width as f64 * LINEAR_QUIET_ZONE,
+        )
+    } else {
+        let module = match fixed_module {
+            Some(module) => snap_module(module, Some(dpi), false),
+            None => {
+                let fit = (element.width_mm / symbol.width as f64).min(element.height_mm / symbol.height as f64);
+                snap_module(fit, Some(dpi), true)
+            }
+        };
+        (
+            module * symbol.width as f64,
+            module * symbol.height as f64,
+            module * MATRIX_QUIET_ZONE,
+        )
+    };
+    let left = (element.width_mm - width) / 2.0 - quiet;
+    let top = (element.height_mm - height) / 2.0 - quiet;
+    let mut image = GrayImage::from_pixel(
+        ((width + 2.0 * quiet) * px_per_mm).ceil() as u32,
+        ((height + 2.0 * quiet) * px_per_mm).ceil() as u32,
+        Luma([255]),
+    );
+    let (sin, cos) = element.rotation_deg.to_radians().sin_cos();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let ex = left + (x as f64 + 0.5) / px_per_mm;
+        let ey = top + (y as f64 + 0.5) / px_per_mm;
+        let px = ((element.x_mm + ex * cos - ey * sin) * px_per_mm).floor();
+        let py = ((element.y_mm + ex * sin + ey * cos) * px_per_mm).floor();
+        if px >= 0.0 && py >= 0.0 && (px as u32) < page.width() && (py as u32) < page.height() {
+            *pixel = *page.get_pixel(px as u32, py as u32);
+        }
+    }
+    image
+}
@@ -0,0 +1,336 @@
+// What a printer can print on: the media sizes it offers, the custom size
+// range and, for CUPS queues whose PPD states them, the unprintable hardware
+// margins. Backends other than CUPS fill in what they can. Used to
+// warn about a label that won't fit while it's being designed, and to refuse
+// one that can't at print time.
+use crate::backends::{self, cups};
+use crate::groups;
+use crate::i18n;
+use crate::printing::{POINTS_PER_MM, RENDER_DPI};
+use fluent_bundle::FluentValue;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Directory CUPS keeps installed printers' PPD files in
+pub const PPD_DIR: &str = "/etc/cups/ppd";
+/// Slack for sizes rounded differently in the PPD
+const TOLERANCE_MM: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaOption {
+    /// PageSize choice, e.g. "62x29"
+    pub name: String,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+/// Limits of a printer; a limit is None when the printer doesn't state it
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterCapabilities {
+    pub printer: String,
+    pub dpi: f64,
+    pub media: Vec<MediaOption>,
+    /// Whether sizes other than `media` can be printed
+    pub custom_sizes: bool,
+    /// Widest media the printer takes
+    pub max_width_mm: Option<f64>,
+    /// `max_width_mm` less the hardware margins
+    pub printable_width_mm: Option<f64>,
+    /// Shortest and longest label along the feed direction
+    pub min_length_mm: Option<f64>,
+    pub max_length_mm: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The label prints, but probably not as designed
+    Warning,
+    /// The printer will refuse or cut off the label
+    Error,
+}
+
+/// A problem with printing a label size on a printer
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaWarning {
+    /// "too_wide", "outside_printable_area", "too_short", "too_long" or
+    /// "no_matching_media"
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl MediaWarning {
+    fn new(code: &str, severity: Severity, message: String) -> Self {
+        MediaWarning {
+            code: code.to_string(),
+            severity,
+            message,
+        }
+    }
+}
+
+impl PrinterCapabilities {
+    /// A printer that doesn't state any limits, printing at `RENDER_DPI`
+    pub fn unknown(printer_name: &str) -> Self {
+        from_options(printer_name, RENDER_DPI, "", "")
+    }
+}
+
+/// Capabilities of the CUPS queue `printer_name`; all limits unknown when
+/// CUPS doesn't know it
+pub async fn capabilities(printer_name: &str) -> PrinterCapabilities {
+    let options = cups::printer_options(printer_name).await.unwrap_or_default();
+    let ppd = fs::read_to_string(Path::new(PPD_DIR).join(format!("{}.ppd", printer_name))).unwrap_or_default();
+    let dpi = cups::default_resolution(&options).unwrap_or(RENDER_DPI);
+    from_options(printer_name, dpi, &options, &ppd)
+}
+
+/// Capabilities from the PWG media names an IPP printer lists as
+/// `media-supported`, e.g. "oe_4x6-label_4x6in"; "custom_min_..." and
+/// "custom_max_..." give the range of custom sizes
+pub fn from_media_names(printer_name: &str, dpi: f64, names: &[String]) -> PrinterCapabilities {
+    let mut media = Vec::new();
+    let (mut custom_min, mut custom_max) = (None, None);
+    for name in names {
+        let Some((width_mm, height_mm)) = page_size_mm(name) else {
+            continue;
+        };
+        if name.starts_with("custom_min_") {
+            custom_min = Some((width_mm, height_mm));
+        } else if name.starts_with("custom_max_") {
+            custom_max = Some((width_mm, height_mm));
+        } else {
+            media.push(MediaOption {
+                name: name.clone(),
+                width_mm,
+                height_mm,
+            });
+        }
+    }
+
+    let max_width_mm = media
+        .iter()
+        .map(|m| m.width_mm)
+        .chain(custom_max.map(|(width, _)| width))
+        .reduce(f64::max);
+    let (min_length_mm, max_length_mm) = match custom_max {
+        Some((_, max)) => (custom_min.map(|(_, min)| min), Some(max)),
+        None => (
+            media.iter().map(|m| m.height_mm).reduce(f64::min),
+            media.iter().map(|m| m.height_mm).reduce(f64::max),
+        ),
+    };
+
+    PrinterCapabilities {
+        printer: printer_name.to_string(),
+        dpi,
+        media,
+        custom_sizes: custom_max.is_some(),
+        max_width_mm,
+        // Margins aren't asked for
+        printable_width_mm: None,
+        min_length_mm,
+        max_length_mm,
+    }
+}
+
+fn from_options(printer_name: &str, dpi: f64, options: &str, ppd: &str) -> PrinterCapabilities {
+    let media = media_options(options);
+    let custom_width = custom_range(ppd, "Width");
+    let custom_height = custom_range(ppd, "Height");
+    let custom_sizes = custom_width.is_some() || custom_height.is_some();
+
+    let max_width_mm = media
+        .iter()
+        .map(|m| m.width_mm)
+        .chain(custom_width.map(|(_, max)| max))
+        .reduce(f64::max);
+    let (margin_left, margin_right) = hardware_margins(ppd);
+    let printable_width_mm = max_width_mm.map(|width| width - margin_left - margin_right);
+    let (min_length_mm, max_length_mm) = match custom_height {
+        Some((min, max)) => (Some(min), Some(max)),
+        None if custom_sizes => (None, None),
+        None => (
+            media.iter().map(|m| m.height_mm).reduce(f64::min),
+            media.iter().map(|m| m.height_mm).reduce(f64::max),
+        ),
+    };
+
+    PrinterCapabilities {
+        printer: printer_name.to_string(),
+        dpi,
+        media,
+        custom_sizes,
+        max_width_mm,
+        printable_width_mm,
+        min_length_mm,
+        max_length_mm,
+    }
+}
+
+/// Everything that stops a `width_mm` x `height_mm` label printing as
+/// designed on the printer. Labels are fed either way round, whichever fits.
+pub fn check_fit(capabilities: &PrinterCapabilities, width_mm: f64, height_mm: f64) -> Vec<MediaWarning> {
+    let printer = || ("printer", FluentValue::from(capabilities.printer.as_str()));
+    let size = || ("size", FluentValue::from(format!("{}x{}mm", width_mm, height_mm)));
+    // Limits converted from points have long fractions
+    let mm = |value: f64| FluentValue::from((value * 10.0).round() / 10.0);
+    let mut warnings = Vec::new();
+
+    // The side across the print head; the other one is the feed length
+    let fits_across = |across: f64, limit: Option<f64>| limit.is_none_or(|limit| across <= limit + TOLERANCE_MM);
+    let (across, length) = if fits_across(width_mm, capabilities.max_width_mm) || width_mm <= height_mm {
+        (width_mm, height_mm)
+    } else {
+        (height_mm, width_mm)
+    };
+
+    if let Some(max_width) = capabilities
+        .max_width_mm
+        .filter(|_| !fits_across(across, capabilities.max_width_mm))
+    {
+        warnings.push(MediaWarning::new(
+            "too_wide",
+            Severity::Error,
+            i18n::t("media-too-wide", &[size(), printer(), ("max", mm(max_width))]),
+        ));
+    } else if let Some(printable) = capabilities
+        .printable_width_mm
+        .filter(|_| !fits_across(across, capabilities.printable_width_mm))
+    {
+        warnings.push(MediaWarning::new(
+            "outside_printable_area",
+            Severity::Warning,
+            i18n::t("media-outside-printable-area", &[printer(), ("printable", mm(printable)), size()]),
+        ));
+    }
+
+    if let Some(min_length) = capabilities.min_length_mm.filter(|min| length < min - TOLERANCE_MM) {
+        warnings.push(MediaWarning::new(
+            "too_short",
+            Severity::Error,
+            i18n::t("media-too-short", &[size(), ("min", mm(min_length)), printer()]),
+        ));
+    }
+    if let Some(max_length) = capabilities.max_length_mm.filter(|max| length > max + TOLERANCE_MM) {
+        warnings.push(MediaWarning::new(
+            "too_long",
+            Severity::Error,
+            i18n::t("media-too-long", &[size(), ("max", mm(max_length)), printer()]),
+        ));
+    }
+
+    let matches = |m: &MediaOption| {
+        let same = |a: f64, b: f64| (a - b).abs() <= TOLERANCE_MM;
+        (same(m.width_mm, width_mm) && same(m.height_mm, height_mm))
+            || (same(m.width_mm, height_mm) && same(m.height_mm, width_mm))
+    };
+    if !capabilities.custom_sizes && !capabilities.media.is_empty() && !capabilities.media.iter().any(matches) {
+        warnings.push(MediaWarning::new(
+            "no_matching_media",
+            Severity::Warning,
+            i18n::t("media-no-match", &[printer(), size()]),
+        ));
+    }
+    warnings
+}
+
+// Named sizes of the PageSize choices in `lpoptions -l` output, e.g.
+// "PageSize/Media Size: 29x90 *62x29 62X1 Custom.WIDTHxHEIGHT"
+fn media_options(options: &str) -> Vec<MediaOption> {
+    options
+        .lines()
+        .filter(|line| line.starts_with("PageSize/") || line.starts_with("media/"))
+        .filter_map(|line| line.split_once(':'))
+        .flat_map(|(_, choices)| choices.split_whitespace())
+        .map(|choice| choice.trim_start_matches('*'))
+        .filter_map(|name| {
+            let (width_mm, height_mm) = page_size_mm(name)?;
+            Some(MediaOption {
+                name: name.to_string(),
+                width_mm,
+                height_mm,
+            })
+        })
+        .collect()
+}
+
+// Size of a page size name: Adobe "w175h567" in points, PWG
+// "om_62x29mm_62x29mm" or "oe_4x6-label_4x6in", and driver names like
+// "62x29mm" or "62x29" (Brother) where bare numbers are mm. Names without
+// dimensions, like "A4", and ambiguous ones give None.
+fn page_size_mm(name: &str) -> Option<(f64, f64)> {
+    let name = name.to_ascii_lowercase();
+    if let Some(size) = name.strip_prefix('w') {
+        let (width, height) = size.split_once('h')?;
+        let height = height.trim_end_matches(|c: char| !c.is_ascii_digit());
+        return Some((
+            width.parse::<f64>().ok()? / POINTS_PER_MM,
+            height.parse::<f64>().ok()? / POINTS_PER_MM,
+        ));
+    }
+    let size = name.rsplit('_').next()?;
+    let (width, rest) = size.split_once('x')?;
+    let width: f64 = width.parse().ok()?;
+    let height_end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let height: f64 = rest[..height_end].parse().ok()?;
+    match &rest[height_end..] {
+        "in" => Some((width * 25.4, height * 25.4)),
+        "mm" => Some((width, height)),
+        // A bare "4x6" means inches, which the name alone can't confirm
+        "" if width >= 10.0 => Some((width, height)),
+        _ => None,
+    }
+}
+
+// Custom page size range of `param` ("Width" or "Height") in mm, from e.g.
+// "*ParamCustomPageSize Width: 1 points 72 288"
+fn custom_range(ppd: &str, param: &str) -> Option<(f64, f64)> {
+    let prefix = format!("*ParamCustomPageSize {}:", param);
+    let line = ppd.lines().find_map(|line| line.strip_prefix(prefix.as_str()))?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [_, unit, min, max] = fields[..] else {
+        return None;
+    };
+    let per_mm = match unit {
+        "points" => POINTS_PER_MM,
+        "inches" => 1.0 / 25.4,
+        "cm" => 0.1,
+        "mm" => 1.0,
+        _ => return None,
+    };
+    Some((min.parse::<f64>().ok()? / per_mm, max.parse::<f64>().ok()? / per_mm))
+}
+
+// Left and right unprintable margins in mm, from
+// "*HWMargins: left bottom right top" in points
+fn hardware_margins(ppd: &str) -> (f64, f64) {
+    let margins: Vec<f64> = ppd
+        .lines()
+        .find_map(|line| line.strip_prefix("*HWMargins:"))
+        .map(|line| line.split_whitespace().filter_map(|m| m.parse().ok()).collect())
+        .unwrap_or_default();
+    match margins[..] {
+        [left, _, right, _] => (left / POINTS_PER_MM, right / POINTS_PER_MM),
+        _ => (0.0, 0.0),
+    }
+}
+
+#[tauri::command]
+pub async fn get_printer_capabilities(printer_name: String) -> PrinterCapabilities {
+    let printer_name = groups::primary(&printer_name);
+    backends::resolve(&printer_name).capabilities(&printer_name).await
+}
+
+/// Problems with printing the current document size on `printer_name`, for
+/// the editor to show before printing; empty when it fits
+#[tauri::command]
+pub async fn check_media_fit(printer_name: String, width_mm: f64, height_mm: f64) -> Vec<MediaWarning> {
+    let printer_name = groups::primary(&printer_name);
+    let capabilities = backends::resolve(&printer_name).capabilities(&printer_name).await;
+    check_fit(&capabilities, width_mm, height_mm)
+}
@@ -0,0 +1,194 @@
+// Headless command line mode, for printing from scripts and cron jobs with
+// the same render and print pipeline as the app:
+//
+//   labelgood print --template shelf.lblg --data items.csv --printer QL-820NWB
+//   labelgood printers
+use crate::counters::CounterStore;
+use crate::document::Document;
+use crate::fonts::FontLibrary;
+use crate::merge::{self, DataSource, MergeRequest, RowRange};
+use crate::placeholders::Context;
+use crate::portable;
+use crate::project;
+use crate::prompts;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Matches `identifier` in tauri.conf.json, so the CLI shares the app's
+/// fonts and serial counters
+const APP_IDENTIFIER: &str = "com.labelgood.app";
+
+const USAGE: &str = "Usage:
+  labelgood print --template FILE [options]
+  labelgood printers
+
+Print options:
+  --template FILE     .lblg project, .toml label definition or importable label file
+                      (glabels, Dymo, P-touch, NiceLabel)
+  --data FILE         CSV or spreadsheet to merge, one label per row
+  --sheet NAME        Spreadsheet sheet (first sheet by default)
+  --map FIELD=COLUMN  Read a template field from a differently named column; repeatable
+  --rows FIRST[-LAST] 1-based data rows to print (all by default)
+  --answer FIELD=TEXT Answer a prompt field of the template; repeatable
+  --printer NAME      Printer to send the labels to
+  --output FILE       Write the PDF to FILE instead of printing";
+
+/// Whether the process was started as a CLI command rather than the app
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    matches!(args.get(1).map(String::as_str), Some("print" | "printers" | "help" | "--help" | "-h"))
+}
+
+/// Run a CLI command and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    crate::logging::init_stderr();
+    let result = match args.get(1).map(String::as_str) {
+        Some("print") => tauri::async_runtime::block_on(print(&args[2..])),
+        Some("printers") => tauri::async_runtime::block_on(crate::list_printers())
+            .map(|printers| printers.join("\n"))
+            .map_err(String::from),
+        _ => Ok(USAGE.to_string()),
+    };
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+#[derive(Default)]
+struct PrintArgs {
+    template: Option<PathBuf>,
+    data: Option<PathBuf>,
+    sheet: Option<String>,
+    mapping: BTreeMap<String, String>,
+    range: Option<RowRange>,
+    answers: BTreeMap<String, String>,
+    printer: Option<String>,
+    output: Option<PathBuf>,
+}
+
+fn parse_print_args(args: &[String]) -> Result<PrintArgs, String> {
+    let mut parsed = PrintArgs::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value\n\n{}", flag, USAGE));
+        match flag.as_str() {
+            "--template" => parsed.template = Some(PathBuf::from(value()?)),
+            "--data" => parsed.data = Some(PathBuf::from(value()?)),
+            "--sheet" => parsed.sheet = Some(value()?),
+            "--map" => {
+                let pair = value()?;
+                let (field, column) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--map expects FIELD=COLUMN, got \"{}\"", pair))?;
+                parsed.mapping.insert(field.to_string(), column.to_string());
+            }
+            "--rows" => parsed.range = Some(parse_rows(&value()?)?),
+            "--answer" => {
+                let pair = value()?;
+                let (field, answer) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--answer expects FIELD=TEXT, got \"{}\"", pair))?;
+                parsed.answers.insert(field.to_string(), answer.to_string());
+            }
+            "--printer" => parsed.printer = Some(value()?),
+            "--output" => parsed.output = Some(PathBuf::from(value()?)),
+            other => return Err(format!("Unknown option {}\n\n{}", other, USAGE)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_rows(text: &str) -> Result<RowRange, String> {
+    let invalid = || format!("--rows expects FIRST or FIRST-LAST, got \"{}\"", text);
+    let (first, last) = match text.split_once('-') {
+        Some((first, last)) => (first, Some(last)),
+        None => (text, None),
+    };
+    Ok(RowRange {
+        first: first.trim().parse().map_err(|_| invalid())?,
+        last: last.map(|l| l.trim().parse()).transpose().map_err(|_| invalid())?,
+    })
+}
+
+async fn print(args: &[String]) -> Result<String, String> {
+    let args = parse_print_args(args)?;
+    let template = args.template.as_deref().ok_or_else(|| format!("--template is required\n\n{}", USAGE))?;
+    if args.printer.is_none() && args.output.is_none() {
+        return Err("Give --printer or --output; there is no viewer to open the PDF in".to_string());
+    }
+    let document = project::load_document(template)?;
+
+    let data_dir = app_data_dir()?;
+    let fonts = FontLibrary::load(data_dir.join("fonts"));
+    let counters = CounterStore::load(data_dir.join("counters.json"));
+    let mut take_serial = |name: &str| counters.next_serial(name);
+    let next_serial: &mut dyn FnMut(&str) -> Result<String, String> = &mut take_serial;
+
+    let documents = match &args.data {
+        Some(data) => {
+            let request = MergeRequest {
+                document,
+                source: DataSource::from_file(data, args.sheet.clone()),
+                mapping: args.mapping.clone(),
+                range: args.range,
+                printer_name: args.printer.clone(),
+                check_digits: Default::default(),
+                numbering: None,
+                answers: args.answers.clone(),
+            };
+            let table = request.source.load()?;
+            merge::merge_documents(&request, &table, next_serial)?
+        }
+        None => {
+            prompts::validate(&document.prompts, &args.answers)?;
+            let mut values = BTreeMap::new();
+            prompts::fill(&document.prompts, &args.answers, &mut values);
+            let mut context = Context {
+                values: &values,
+                now: chrono::Local::now(),
+                next_serial: Some(next_serial),
+            };
+            vec![merge::apply(&document, &mut context)?]
+        }
+    };
+
+    match &args.output {
+        Some(output) => {
+            let pdf = merge::documents_to_pdf(&documents, fonts.database()).await?;
+            std::fs::copy(&pdf, output).map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+            Ok(format!("Wrote {} labels to {}", documents.len(), output.display()))
+        }
+        None => {
+            merge::print_documents(&documents, fonts.database(), args.printer.as_deref()).await?;
+            Ok(format!("Printed {} labels to {}", documents.len(), args.printer.unwrap_or_default()))
+        }
+    }
+}
+
+// Same location `portable::app_data_dir` resolves to
+fn app_data_dir() -> Result<PathBuf, String> {
+    #[cfg(feature = "mock-printer")]
+    if let Some(dir) = crate::testing::data_dir() {
+        return Ok(dir.clone());
+    }
+    if let Some(dir) = portable::data_dir() {
+        return Ok(dir.clone());
+    }
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let home = env_dir("HOME");
+    let base = if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        home.map(|h| h.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| home.map(|h| h.join(".local/share")))
+    };
+    base.map(|b| b.join(APP_IDENTIFIER))
+        .ok_or_else(|| "Cannot find the application data directory".to_string())
+}
@@ -0,0 +1,63 @@
+// Images pasted from the system clipboard, e.g. screenshots or product
+// photos copied from a browser
+use crate::document::{asset_id, Asset};
+use crate::project::LoadedAsset;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{ImageFormat, RgbaImage};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Resolution assumed for clipboard bitmaps at a display scale of 1, which
+/// carry no DPI of their own
+pub const SCREEN_DPI: f64 = 96.0;
+
+#[derive(Debug, Serialize)]
+pub struct PastedImage {
+    #[serde(flatten)]
+    pub loaded: LoadedAsset,
+    pub width_px: u32,
+    pub height_px: u32,
+    pub dpi: f64,
+    /// Physical size at `dpi`, so the image lands on the label at the size
+    /// it appeared on screen
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+/// PNG asset of the image on the clipboard
+#[tauri::command]
+pub fn paste_image_from_clipboard(window: tauri::Window) -> Result<PastedImage, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Failed to open the clipboard: {}", e))?;
+    let image = clipboard
+        .get_image()
+        .map_err(|e| format!("The clipboard doesn't hold an image: {}", e))?;
+    let (width_px, height_px) = (image.width as u32, image.height as u32);
+
+    let rgba = RgbaImage::from_raw(width_px, height_px, image.bytes.into_owned())
+        .ok_or("Clipboard image data has an unexpected size")?;
+    let mut png = Vec::new();
+    rgba.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode pasted image: {}", e))?;
+
+    // Screenshots on a HiDPI display have scale-factor times more pixels
+    // than what was seen on screen
+    let dpi = SCREEN_DPI * window.scale_factor().unwrap_or(1.0);
+    let to_mm = |px: u32| px as f64 / dpi * 25.4;
+
+    Ok(PastedImage {
+        loaded: LoadedAsset {
+            id: asset_id(&png),
+            asset: Asset {
+                file_name: "pasted.png".to_string(),
+                mime_type: "image/png".to_string(),
+                data: BASE64.encode(&png),
+            },
+        },
+        width_px,
+        height_px,
+        dpi,
+        width_mm: to_mm(width_px),
+        height_mm: to_mm(height_px),
+    })
+}
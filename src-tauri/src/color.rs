@@ -0,0 +1,271 @@
+// Colour management for inkjet label printers such as Epson ColorWorks and
+// Primera. Images are drawn in sRGB, so ones carrying an ICC profile of their
+// own (e.g. Adobe RGB photos) are converted to sRGB when rendered. Printers
+// set up as colour printers are previewed in colour rather than dithered to
+// black and white, and with an output profile their jobs are converted from
+// sRGB to the printer's RGB space before sending; colour adjustment in the
+// driver should then be off, or colours are corrected twice.
+use crate::document::Asset;
+use crate::error::LabelgoodError;
+use crate::printing::{self, RENDER_DPI};
+use crate::raster::PrintRaster;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageDecoder, ImageFormat, RgbImage, RgbaImage};
+use lcms2::{ColorSpaceSignature, InfoType, Intent, Locale, PixelFormat, Profile, Transform};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::{debug, info};
+
+/// Converted images kept for reuse; batches repeat the same image on every label
+const CACHE_ENTRIES: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorPrinter {
+    /// ICC profile of the printer, ink and media; jobs are sent as sRGB when
+    /// absent
+    pub output_profile: Option<String>,
+    pub intent: RenderingIntent,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderingIntent {
+    /// Squeezes every colour into what the printer can print, keeping how
+    /// they relate; best for photos
+    #[default]
+    Perceptual,
+    /// Prints colours the printer can reach exactly, clipping the rest;
+    /// best for brand colours
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    fn lcms(self) -> Intent {
+        match self {
+            RenderingIntent::Perceptual => Intent::Perceptual,
+            RenderingIntent::RelativeColorimetric => Intent::RelativeColorimetric,
+            RenderingIntent::Saturation => Intent::Saturation,
+            RenderingIntent::AbsoluteColorimetric => Intent::AbsoluteColorimetric,
+        }
+    }
+}
+
+static PRINTERS: OnceLock<Mutex<BTreeMap<String, ColorPrinter>>> = OnceLock::new();
+static CACHE: OnceLock<Mutex<HashMap<[u8; 32], Option<Asset>>>> = OnceLock::new();
+
+fn printers() -> &'static Mutex<BTreeMap<String, ColorPrinter>> {
+    PRINTERS.get_or_init(Mutex::default)
+}
+
+pub fn configure(color_printers: BTreeMap<String, ColorPrinter>) {
+    *printers().lock().unwrap() = color_printers;
+}
+
+/// How `printer_name` prints colour; None for black and white printers
+pub fn for_printer(printer_name: &str) -> Option<ColorPrinter> {
+    printers().lock().unwrap().get(printer_name).cloned()
+}
+
+fn load_profile(path: &str) -> Result<Profile, String> {
+    let profile = Profile::new_file(path).map_err(|e| format!("Failed to read ICC profile {}: {}", path, e))?;
+    if profile.color_space() != ColorSpaceSignature::RgbData {
+        return Err(format!(
+            "{} isn't an RGB profile; colour label printer drivers take RGB",
+            path
+        ));
+    }
+    Ok(profile)
+}
+
+// ICC profile embedded in a PNG, JPEG or WebP image
+fn embedded_profile(bytes: &[u8]) -> Option<Vec<u8>> {
+    let profile = match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => PngDecoder::new(Cursor::new(bytes)).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(Cursor::new(bytes)).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(bytes)).ok()?.icc_profile(),
+        _ => return None,
+    };
+    profile.ok().flatten()
+}
+
+// Whether `profile` is sRGB already, judged by its description
+fn is_srgb(profile: &Profile) -> bool {
+    profile
+        .info(InfoType::Description, Locale::none())
+        .is_some_and(|description| description.to_ascii_lowercase().contains("srgb"))
+}
+
+// RGB pixels of `image` converted from `input` to `output`, alpha kept
+fn transform(image: &mut RgbaImage, input: &Profile, output: &Profile, intent: Intent) -> Result<(), String> {
+    let transform = Transform::new(input, PixelFormat::RGB_8, output, PixelFormat::RGB_8, intent)
+        .map_err(|e| format!("Failed to set up colour conversion: {}", e))?;
+    let source: Vec<[u8; 3]> = image.pixels().map(|p| [p[0], p[1], p[2]]).collect();
+    let mut converted = vec![[0u8; 3]; source.len()];
+    transform.transform_pixels(&source, &mut converted);
+    for (pixel, rgb) in image.pixels_mut().zip(converted) {
+        pixel.0[..3].copy_from_slice(&rgb);
+    }
+    Ok(())
+}
+
+// `bytes` converted to sRGB from the profile they carry, as PNG; None when
+// they carry none or it's sRGB
+fn convert_to_srgb(bytes: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let Some(icc) = embedded_profile(bytes) else {
+        return Ok(None);
+    };
+    let input = match Profile::new_icc(&icc) {
+        Ok(profile) if profile.color_space() == ColorSpaceSignature::RgbData && !is_srgb(&profile) => profile,
+        Ok(_) => return Ok(None),
+        Err(e) => {
+            debug!("Ignoring unreadable embedded ICC profile: {}", e);
+            return Ok(None);
+        }
+    };
+    let mut image = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to decode image: {}", e))?
+        .to_rgba8();
+    transform(&mut image, &input, &Profile::new_srgb(), Intent::Perceptual)?;
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(Some(png))
+}
+
+/// `asset` converted to sRGB when it carries an ICC profile for another
+/// colour space, as PNG; otherwise the asset itself
+pub fn in_srgb(asset: &Asset) -> Result<Cow<'_, Asset>, String> {
+    if !matches!(asset.mime_type.as_str(), "image/png" | "image/jpeg" | "image/webp") {
+        return Ok(Cow::Borrowed(asset));
+    }
+    let key: [u8; 32] = Sha256::digest(asset.data.as_bytes()).into();
+    let cache = CACHE.get_or_init(Mutex::default);
+    if let Some(converted) = cache.lock().unwrap().get(&key) {
+        return Ok(converted.clone().map_or(Cow::Borrowed(asset), Cow::Owned));
+    }
+
+    let converted = convert_to_srgb(&asset.bytes()?)?.map(|png| Asset {
+        file_name: asset.file_name.clone(),
+        mime_type: "image/png".to_string(),
+        data: BASE64.encode(png),
+    });
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= CACHE_ENTRIES {
+        cache.clear();
+    }
+    cache.insert(key, converted.clone());
+    Ok(converted.map_or(Cow::Borrowed(asset), Cow::Owned))
+}
+
+// One page rendered at `RENDER_DPI` converted from sRGB to `output`
+fn convert_page(png: &[u8], output: &Profile, intent: Intent) -> Result<Vec<u8>, String> {
+    let mut page = image::load_from_memory(png)
+        .map_err(|e| format!("Failed to read page image: {}", e))?
+        .to_rgba8();
+    transform(&mut page, &Profile::new_srgb(), output, intent)?;
+    let mut converted = Vec::new();
+    DynamicImage::ImageRgba8(page)
+        .write_to(&mut Cursor::new(&mut converted), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode page image: {}", e))?;
+    Ok(converted)
+}
+
+/// The PDF at `pdf_path` converted to the output profile of `printer`, as a
+/// new PDF; the same path when it has none
+pub async fn convert_pdf(
+    pdf_path: &Path,
+    printer: &ColorPrinter,
+    width_mm: f64,
+    height_mm: f64,
+) -> Result<Option<PathBuf>, LabelgoodError> {
+    let Some(profile_path) = printer.output_profile.clone().filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+    let pages = printing::rasterize_pdf(pdf_path, None).await?;
+    let temp_dir = tempfile::tempdir().map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to create temp directory: {}", e),
+    })?;
+    let dir = temp_dir.path().to_path_buf();
+    let intent = printer.intent.lcms();
+    let converting = tauri::async_runtime::spawn_blocking(move || {
+        let output = load_profile(&profile_path)?;
+        let mut paths = Vec::with_capacity(pages.len());
+        for (i, page) in pages.iter().enumerate() {
+            let path = dir.join(format!("page_{:05}.png", i));
+            fs::write(&path, convert_page(page, &output, intent)?)
+                .map_err(|e| format!("Failed to write PNG file: {}", e))?;
+            paths.push(path);
+        }
+        info!("Converted {} page(s) to {}", paths.len(), profile_path);
+        Ok::<_, String>(paths)
+    });
+    let paths = converting
+        .await
+        .map_err(|e| LabelgoodError::Other { message: e.to_string() })?
+        .map_err(|message| LabelgoodError::RenderFailed { message })?;
+    let page_refs: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+    printing::images_to_pdf(&page_refs, width_mm, height_mm).await.map(Some)
+}
+
+/// A page rendered at `RENDER_DPI` as colour `printer` would print it:
+/// scaled to `dpi` and, with an output profile, through the printer's colours
+/// and back, without dithering
+pub fn preview(png: &[u8], dpi: f64, printer: &ColorPrinter) -> Result<PrintRaster, String> {
+    let page = image::load_from_memory(png)
+        .map_err(|e| format!("Failed to decode rendered page: {}", e))?
+        .to_rgb8();
+    let scale = dpi / RENDER_DPI;
+    let width = ((page.width() as f64 * scale).round() as u32).max(1);
+    let height = ((page.height() as f64 * scale).round() as u32).max(1);
+    let mut page: RgbImage = if (width, height) == page.dimensions() {
+        page
+    } else {
+        imageops::resize(&page, width, height, FilterType::Triangle)
+    };
+    if let Some(path) = printer.output_profile.as_deref().filter(|p| !p.is_empty()) {
+        let output = load_profile(path)?;
+        let mut rgba = DynamicImage::ImageRgb8(page).to_rgba8();
+        let srgb = Profile::new_srgb();
+        transform(&mut rgba, &srgb, &output, printer.intent.lcms())?;
+        // Back for the screen, so colours out of the printer's reach show
+        // as they'll print
+        transform(&mut rgba, &output, &srgb, Intent::RelativeColorimetric)?;
+        page = DynamicImage::ImageRgba8(rgba).to_rgb8();
+    }
+    let mut encoded = Vec::new();
+    page.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode preview: {}", e))?;
+    Ok(PrintRaster {
+        image: format!("data:image/png;base64,{}", BASE64.encode(encoded)),
+        width_px: width,
+        height_px: height,
+        dpi,
+        tape: None,
+        warnings: Vec::new(),
+    })
+}
+
+/// Description of the ICC profile at `path`, checking it can be used as an
+/// output profile
+#[tauri::command]
+pub fn describe_color_profile(path: String) -> Result<String, String> {
+    let profile = load_profile(&path)?;
+    Ok(profile
+        .info(InfoType::Description, Locale::none())
+        .unwrap_or_else(|| Path::new(&path).display().to_string()))
+}
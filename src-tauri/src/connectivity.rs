@@ -0,0 +1,169 @@
+// Printer connectivity test: opens the port a printer listens on (631 for
+// IPP, 9100 for raw TCP) or its USB device, with a short timeout, and
+// optionally asks for its status, so an unreachable printer is reported in
+// seconds with what failed rather than after a print command times out.
+use crate::backends::{self, cups, registered, with_default_port, PrinterStatus};
+use crate::groups;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityReport {
+    pub printer: String,
+    /// What was tested, e.g. "192.168.1.20:9100" or "/dev/usb/lp0"
+    pub endpoint: Option<String>,
+    /// Whether the endpoint could be opened; true when there's no endpoint
+    /// to test and the status doesn't say otherwise
+    pub reachable: bool,
+    /// Time to open the connection or device
+    pub connect_ms: Option<u64>,
+    /// Status as the printer or spooler reports it, when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<PrinterStatus>,
+    pub status_ms: Option<u64>,
+    pub message: String,
+}
+
+// Where a printer is reached
+enum Endpoint {
+    /// host:port
+    Network(String),
+    /// Device path
+    Device(String),
+}
+
+// The endpoint of a device URI; None for ones nothing can be opened for,
+// like CUPS USB URIs, files and plugin drivers
+fn endpoint(uri: &str) -> Option<Endpoint> {
+    if let Some(path) = uri
+        .strip_prefix("usb:")
+        .filter(|path| path.starts_with('/') && !path.starts_with("//"))
+    {
+        return Some(Endpoint::Device(path.to_string()));
+    }
+    let (scheme, rest) = uri.split_once("://")?;
+    let port = match scheme {
+        "ipp" | "ipps" => 631,
+        "socket" => 9100,
+        "lpd" => 515,
+        "http" => 80,
+        "https" => 443,
+        _ => return None,
+    };
+    let authority = rest.split(['/', '?']).next().unwrap_or(rest);
+    // Credentials in the URI aren't part of the address
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    (!authority.is_empty()).then(|| Endpoint::Network(with_default_port(authority, port)))
+}
+
+fn millis(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+async fn connect(address: &str) -> Result<u64, String> {
+    let started = Instant::now();
+    match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Ok(millis(started)),
+        Ok(Err(e)) => Err(format!("Failed to connect to {}: {}", address, e)),
+        Err(_) => Err(format!(
+            "No answer from {} within {} seconds",
+            address,
+            CONNECT_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+async fn open_device(path: &str) -> Result<u64, String> {
+    let started = Instant::now();
+    let device = path.to_string();
+    let opened = tauri::async_runtime::spawn_blocking(move || OpenOptions::new().write(true).open(&device));
+    match tokio::time::timeout(CONNECT_TIMEOUT, opened).await {
+        Ok(Ok(Ok(_))) => Ok(millis(started)),
+        Ok(Ok(Err(e))) => Err(format!("Failed to open {}: {}", path, e)),
+        Ok(Err(e)) => Err(format!("Failed to open {}: {}", path, e)),
+        Err(_) => Err(format!(
+            "{} didn't open within {} seconds",
+            path,
+            CONNECT_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// Test whether `printer_name` can be reached, and with `query_status` ask
+/// for its status too. A group is tested by its first printer.
+pub async fn test(printer_name: &str, query_status: bool) -> ConnectivityReport {
+    let printer = groups::primary(printer_name);
+    // The address a CUPS queue or registered printer stands for
+    let uri = match registered::uri(&printer) {
+        Some(uri) => Some(uri),
+        None if backends::resolve(&printer).name() == "cups" => cups::device_uri(&printer).await,
+        None => Some(printer.clone()),
+    };
+    let mut report = ConnectivityReport {
+        printer: printer.clone(),
+        endpoint: None,
+        reachable: true,
+        connect_ms: None,
+        status: None,
+        status_ms: None,
+        message: String::new(),
+    };
+
+    let opened = match uri.as_deref().and_then(endpoint) {
+        Some(Endpoint::Network(address)) => {
+            report.endpoint = Some(address.clone());
+            Some(connect(&address).await)
+        }
+        Some(Endpoint::Device(path)) => {
+            report.endpoint = Some(path.clone());
+            Some(open_device(&path).await)
+        }
+        None => None,
+    };
+    match opened {
+        Some(Ok(ms)) => {
+            report.connect_ms = Some(ms);
+            report.message = format!("Connected in {} ms", ms);
+        }
+        Some(Err(e)) => {
+            report.reachable = false;
+            report.message = e;
+        }
+        None => report.message = format!("{} has no network address or device to test", printer),
+    }
+
+    if query_status && report.reachable {
+        let started = Instant::now();
+        let status = tokio::time::timeout(STATUS_TIMEOUT, backends::resolve(&printer).status(&printer)).await;
+        report.status_ms = Some(millis(started));
+        match status {
+            Ok(status) => {
+                if matches!(status.state.as_str(), "offline" | "disabled") {
+                    report.reachable = false;
+                    report.message = format!("{} is {}", printer, status.state);
+                }
+                report.status = Some(status);
+            }
+            Err(_) => {
+                report.message = format!(
+                    "{}; no status within {} seconds",
+                    report.message,
+                    STATUS_TIMEOUT.as_secs()
+                )
+            }
+        }
+    }
+    report
+}
+
+/// Test whether `printer_name` can be reached, asking for its status when
+/// `query_status` is set
+#[tauri::command]
+pub async fn test_printer(printer_name: String, query_status: Option<bool>) -> ConnectivityReport {
+    test(&printer_name, query_status.unwrap_or(false)).await
+}
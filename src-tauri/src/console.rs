@@ -0,0 +1,54 @@
+// Raw command console for troubleshooting: commands in the printer's own
+// language (ZPL, EPL, ESC/POS, TSPL, ...) sent past any driver, and what the
+// printer answers, e.g. to a ZPL ~HS host status request. Only printers
+// connected over raw TCP or USB can answer.
+use crate::backends;
+use crate::error::LabelgoodError;
+use serde::Serialize;
+use std::fmt::Write;
+use std::time::Duration;
+
+const MAX_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RawReply {
+    pub bytes: Vec<u8>,
+    /// The reply as text, other control characters than line breaks and
+    /// tabs written as "\x02"
+    pub text: String,
+}
+
+impl RawReply {
+    fn new(bytes: Vec<u8>) -> Self {
+        let mut text = String::new();
+        for c in String::from_utf8_lossy(&bytes).chars() {
+            if c.is_control() && !matches!(c, '\r' | '\n' | '\t') {
+                let _ = write!(text, "\\x{:02X}", c as u32);
+            } else {
+                text.push(c);
+            }
+        }
+        RawReply { bytes, text }
+    }
+}
+
+/// Send `data` to `printer_name` as is. With `wait_ms`, what the printer
+/// answers within that time (at most 30 seconds) is read and returned.
+#[tauri::command]
+pub async fn send_raw(
+    printer_name: String,
+    data: Vec<u8>,
+    wait_ms: Option<u64>,
+) -> Result<Option<RawReply>, LabelgoodError> {
+    match wait_ms {
+        Some(wait_ms) => {
+            let wait = Duration::from_millis(wait_ms).min(MAX_WAIT);
+            let reply = backends::query(&printer_name, &data, wait).await?;
+            Ok(Some(RawReply::new(reply)))
+        }
+        None => {
+            backends::send_raw(&printer_name, &data).await?;
+            Ok(None)
+        }
+    }
+}
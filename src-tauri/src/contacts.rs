@@ -0,0 +1,340 @@
+// Address book: contacts kept in a SQLite database, each printable as an
+// address label in one step, for the classic desk-printer workflow that
+// otherwise needs a spreadsheet
+pub mod import;
+
+use crate::api::{self, PrintResponse};
+use crate::merge::{CheckDigitPolicy, DataSource};
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS contacts (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        company TEXT NOT NULL,
+        street TEXT NOT NULL,
+        postcode TEXT NOT NULL,
+        city TEXT NOT NULL,
+        country TEXT NOT NULL,
+        phone TEXT NOT NULL,
+        email TEXT NOT NULL,
+        notes TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS contacts_name ON contacts (name);
+";
+
+const CONTACT_COLUMNS: &str = "id, name, company, street, postcode, city, country, phone, email, notes, updated_at";
+
+/// Gallery template printed when none is given
+const DEFAULT_TEMPLATE: &str = "address";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contact {
+    /// None for a contact that hasn't been saved yet
+    pub id: Option<i64>,
+    pub name: String,
+    pub company: String,
+    pub street: String,
+    pub postcode: String,
+    pub city: String,
+    pub country: String,
+    pub phone: String,
+    pub email: String,
+    pub notes: String,
+    /// UTC, RFC 3339; set when saved
+    pub updated_at: String,
+}
+
+impl Contact {
+    /// Merge fields for printing: every field by name, plus `postcode_city`,
+    /// the two joined as they're written on an envelope
+    pub fn record(&self) -> BTreeMap<String, String> {
+        let postcode_city = [self.postcode.trim(), self.city.trim()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        BTreeMap::from([
+            ("name".to_string(), self.name.clone()),
+            ("company".to_string(), self.company.clone()),
+            ("street".to_string(), self.street.clone()),
+            ("postcode".to_string(), self.postcode.clone()),
+            ("city".to_string(), self.city.clone()),
+            ("postcode_city".to_string(), postcode_city),
+            ("country".to_string(), self.country.clone()),
+            ("phone".to_string(), self.phone.clone()),
+            ("email".to_string(), self.email.clone()),
+        ])
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ContactImport {
+    pub imported: usize,
+    /// Already in the address book
+    pub skipped: usize,
+}
+
+pub struct ContactStore {
+    connection: Mutex<Connection>,
+}
+
+impl ContactStore {
+    /// Open or create the database at `path`. Contacts are kept in memory
+    /// for this session when the file can't be opened.
+    pub fn open(path: &Path) -> Self {
+        let connection = match open_file(path) {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("{}; contacts won't be saved", e);
+                let connection = Connection::open_in_memory().expect("in-memory SQLite database");
+                connection.execute_batch(SCHEMA).expect("contacts schema");
+                connection
+            }
+        };
+        ContactStore {
+            connection: Mutex::new(connection),
+        }
+    }
+
+    /// Contacts with `search` in any field, by name
+    pub fn list(&self, search: Option<&str>) -> Result<Vec<Contact>, String> {
+        let search = search.map(str::trim).filter(|s| !s.is_empty());
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM contacts
+                 WHERE ?1 IS NULL OR instr(lower(name || ' ' || company || ' ' || street || ' ' || postcode || ' '
+                     || city || ' ' || country || ' ' || phone || ' ' || email || ' ' || notes), lower(?1)) > 0
+                 ORDER BY name COLLATE NOCASE, id",
+                CONTACT_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to query contacts: {}", e))?;
+        let contacts = statement
+            .query_map([search], contact)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query contacts: {}", e))?;
+        Ok(contacts)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Contact, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                &format!("SELECT {} FROM contacts WHERE id = ?1", CONTACT_COLUMNS),
+                [id],
+                contact,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read contact: {}", e))?
+            .ok_or_else(|| format!("Contact {} not found", id))
+    }
+
+    /// Insert a new contact, or update the one with its id. Returns it as saved.
+    pub fn save(&self, mut contact: Contact) -> Result<Contact, String> {
+        if contact.name.trim().is_empty() && contact.company.trim().is_empty() {
+            return Err("A contact needs a name or a company".to_string());
+        }
+        contact.updated_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let connection = self.connection.lock().unwrap();
+        match contact.id {
+            Some(id) => {
+                let updated = connection
+                    .execute(
+                        "UPDATE contacts SET name = ?1, company = ?2, street = ?3, postcode = ?4, city = ?5,
+                         country = ?6, phone = ?7, email = ?8, notes = ?9, updated_at = ?10 WHERE id = ?11",
+                        params![
+                            contact.name,
+                            contact.company,
+                            contact.street,
+                            contact.postcode,
+                            contact.city,
+                            contact.country,
+                            contact.phone,
+                            contact.email,
+                            contact.notes,
+                            contact.updated_at,
+                            id,
+                        ],
+                    )
+                    .map_err(|e| format!("Failed to save contact: {}", e))?;
+                if updated == 0 {
+                    return Err(format!("Contact {} not found", id));
+                }
+            }
+            None => insert(&connection, &mut contact).map_err(|e| format!("Failed to save contact: {}", e))?,
+        }
+        Ok(contact)
+    }
+
+    /// Add imported contacts in one transaction, skipping those already in
+    /// the address book with the same name, company and address
+    pub fn import(&self, contacts: Vec<Contact>) -> Result<ContactImport, String> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection
+            .transaction()
+            .map_err(|e| format!("Failed to import contacts: {}", e))?;
+        let mut result = ContactImport::default();
+        let updated_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        for mut contact in contacts {
+            let exists: bool = transaction
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM contacts
+                     WHERE name = ?1 AND company = ?2 AND street = ?3 AND postcode = ?4 AND city = ?5)",
+                    params![
+                        contact.name,
+                        contact.company,
+                        contact.street,
+                        contact.postcode,
+                        contact.city
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to import contacts: {}", e))?;
+            if exists {
+                result.skipped += 1;
+                continue;
+            }
+            contact.updated_at = updated_at.clone();
+            insert(&transaction, &mut contact).map_err(|e| format!("Failed to import contacts: {}", e))?;
+            result.imported += 1;
+        }
+        transaction
+            .commit()
+            .map_err(|e| format!("Failed to import contacts: {}", e))?;
+        Ok(result)
+    }
+
+    pub fn delete(&self, id: i64) -> Result<(), String> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection
+            .execute("DELETE FROM contacts WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete contact: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("Contact {} not found", id));
+        }
+        Ok(())
+    }
+}
+
+fn open_file(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let connection = Connection::open(path).map_err(|e| format!("Failed to open contacts: {}", e))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to set up contacts: {}", e))?;
+    Ok(connection)
+}
+
+// Insert a new contact, setting its id
+fn insert(connection: &Connection, contact: &mut Contact) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO contacts (name, company, street, postcode, city, country, phone, email, notes, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            contact.name,
+            contact.company,
+            contact.street,
+            contact.postcode,
+            contact.city,
+            contact.country,
+            contact.phone,
+            contact.email,
+            contact.notes,
+            contact.updated_at,
+        ],
+    )?;
+    contact.id = Some(connection.last_insert_rowid());
+    Ok(())
+}
+
+fn contact(row: &Row) -> rusqlite::Result<Contact> {
+    Ok(Contact {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        company: row.get(2)?,
+        street: row.get(3)?,
+        postcode: row.get(4)?,
+        city: row.get(5)?,
+        country: row.get(6)?,
+        phone: row.get(7)?,
+        email: row.get(8)?,
+        notes: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+#[tauri::command]
+pub async fn list_contacts(contacts: State<'_, ContactStore>, search: Option<String>) -> Result<Vec<Contact>, String> {
+    contacts.list(search.as_deref())
+}
+
+#[tauri::command]
+pub async fn get_contact(contacts: State<'_, ContactStore>, id: i64) -> Result<Contact, String> {
+    contacts.get(id)
+}
+
+/// Add a contact, or update it when it has an id
+#[tauri::command]
+pub async fn save_contact(contacts: State<'_, ContactStore>, contact: Contact) -> Result<Contact, String> {
+    contacts.save(contact)
+}
+
+#[tauri::command]
+pub async fn delete_contact(contacts: State<'_, ContactStore>, id: i64) -> Result<(), String> {
+    contacts.delete(id)
+}
+
+/// Add the contacts in a vCard file or a Google or Outlook CSV export
+#[tauri::command]
+pub async fn import_contacts(contacts: State<'_, ContactStore>, path: String) -> Result<ContactImport, String> {
+    let imported = import::read(Path::new(&path))?;
+    if imported.is_empty() {
+        return Err(format!("No contacts found in {}", path));
+    }
+    contacts.import(imported)
+}
+
+/// Print `copies` address labels for contact `id` with `template` (a gallery
+/// template id or label file), the bundled address label by default. The
+/// bundled label's "Postcode and city" line gets `postcode_city`.
+#[tauri::command]
+pub async fn print_contact_label(
+    app: AppHandle,
+    contacts: State<'_, ContactStore>,
+    id: i64,
+    template: Option<String>,
+    printer_name: Option<String>,
+    copies: Option<usize>,
+) -> Result<PrintResponse, String> {
+    let record = contacts.get(id)?.record();
+    let records = vec![record; copies.unwrap_or(1).max(1)];
+    let mapping = match &template {
+        Some(_) => BTreeMap::new(),
+        None => BTreeMap::from([("city".to_string(), "postcode_city".to_string())]),
+    };
+    let template = template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    let data = DataSource::Records { records };
+    api::print_template(
+        &app,
+        "contacts",
+        template,
+        data,
+        mapping,
+        printer_name,
+        CheckDigitPolicy::default(),
+    )
+    .await
+}
@@ -0,0 +1,421 @@
+// Contact import from vCard files and the CSV exports of Google Contacts and
+// Outlook, into the address book or straight into a merge
+use super::Contact;
+use crate::merge::{self, DataTable};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Country codes expanded to names, for exports that only give the code
+const COUNTRIES: &[(&str, &str)] = &[
+    ("AT", "Austria"),
+    ("AU", "Australia"),
+    ("BE", "Belgium"),
+    ("BG", "Bulgaria"),
+    ("CA", "Canada"),
+    ("CH", "Switzerland"),
+    ("CN", "China"),
+    ("CY", "Cyprus"),
+    ("CZ", "Czech Republic"),
+    ("DE", "Germany"),
+    ("DK", "Denmark"),
+    ("EE", "Estonia"),
+    ("ES", "Spain"),
+    ("FI", "Finland"),
+    ("FR", "France"),
+    ("GB", "United Kingdom"),
+    ("GR", "Greece"),
+    ("HR", "Croatia"),
+    ("HU", "Hungary"),
+    ("IE", "Ireland"),
+    ("IN", "India"),
+    ("IS", "Iceland"),
+    ("IT", "Italy"),
+    ("JP", "Japan"),
+    ("LT", "Lithuania"),
+    ("LU", "Luxembourg"),
+    ("LV", "Latvia"),
+    ("MT", "Malta"),
+    ("MX", "Mexico"),
+    ("NL", "Netherlands"),
+    ("NO", "Norway"),
+    ("NZ", "New Zealand"),
+    ("PL", "Poland"),
+    ("PT", "Portugal"),
+    ("RO", "Romania"),
+    ("SE", "Sweden"),
+    ("SI", "Slovenia"),
+    ("SK", "Slovakia"),
+    ("TR", "Turkey"),
+    ("UK", "United Kingdom"),
+    ("US", "United States"),
+    ("ZA", "South Africa"),
+];
+
+/// Column prefixes of an address in the CSV exports: Google numbers them,
+/// Outlook has business, home and other addresses; plain files have none
+const ADDRESS_PREFIXES: &[&str] = &["address 1 - ", "business ", "home ", "other ", ""];
+
+/// Contacts in a .vcf file or a contacts CSV export
+pub fn read(path: &Path) -> Result<Vec<Contact>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read contacts file: {}", e))?;
+    // Outlook writes its CSV in the Windows code page; keep what's readable
+    let text = String::from_utf8_lossy(&bytes);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let is_vcard = matches!(extension.as_str(), "vcf" | "vcard")
+        || text
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .to_ascii_uppercase()
+            .starts_with("BEGIN:VCARD");
+    if is_vcard {
+        Ok(parse_vcards(&text))
+    } else {
+        from_table(&merge::csv::parse(text.as_bytes(), None)?)
+    }
+}
+
+/// A contacts file as a merge table with the address book's fields
+pub fn load(path: &Path) -> Result<DataTable, String> {
+    let records: Vec<_> = read(path)?.iter().map(Contact::record).collect();
+    Ok(DataTable::from_records(&records))
+}
+
+/// Every card in a vCard 2.1, 3.0 or 4.0 file
+pub fn parse_vcards(text: &str) -> Vec<Contact> {
+    let mut contacts = Vec::new();
+    let mut card: Option<Card> = None;
+    for line in unfold(text) {
+        let Some((head, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = head.split(';');
+        let name = params.next().unwrap_or_default();
+        // Apple and Google group properties as "item1.ADR"
+        let name = name.rsplit('.').next().unwrap_or_default().to_ascii_uppercase();
+        let params: Vec<String> = params.map(str::to_ascii_uppercase).collect();
+        match name.as_str() {
+            "BEGIN" if value.trim().eq_ignore_ascii_case("VCARD") => card = Some(Card::default()),
+            "END" if value.trim().eq_ignore_ascii_case("VCARD") => {
+                if let Some(contact) = card.take().and_then(Card::into_contact) {
+                    contacts.push(contact);
+                }
+            }
+            _ => {
+                if let Some(card) = card.as_mut() {
+                    let value = if params.iter().any(|p| p.contains("QUOTED-PRINTABLE")) {
+                        decode_quoted_printable(value)
+                    } else {
+                        value.to_string()
+                    };
+                    let preferred = params.iter().any(|p| p.contains("PREF"));
+                    card.set(&name, preferred, &value);
+                }
+            }
+        }
+    }
+    contacts
+}
+
+// Joins folded lines: continuations start with a space or tab, and vCard
+// 2.1 quoted-printable values continue after a trailing '='
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut soft_break = false;
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim_end_matches('\r');
+        match lines.last_mut() {
+            Some(last) if soft_break => last.push_str(line),
+            Some(last) if line.starts_with([' ', '\t']) => last.push_str(&line[1..]),
+            _ => lines.push(line.to_string()),
+        }
+        let last = lines.last().map(String::as_str).unwrap_or_default();
+        soft_break = last.ends_with('=')
+            && last
+                .split_once(':')
+                .is_some_and(|(head, _)| head.to_ascii_uppercase().contains("QUOTED-PRINTABLE"));
+        if soft_break {
+            lines.last_mut().unwrap().pop();
+        }
+    }
+    lines
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) if bytes[i] == b'=' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+// A structured value's components, split on unescaped ';', with escapes resolved
+fn components(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => parts.last_mut().unwrap().push('\n'),
+                Some(c) => parts.last_mut().unwrap().push(c),
+                None => {}
+            },
+            ';' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts.into_iter().map(|part| part.trim().to_string()).collect()
+}
+
+// The properties of one card; of repeated ones, the preferred or first
+#[derive(Default)]
+struct Card {
+    full_name: String,
+    name: Vec<String>,
+    organization: String,
+    address: Option<(bool, Vec<String>)>,
+    label: String,
+    phone: Option<(bool, String)>,
+    email: Option<(bool, String)>,
+    notes: String,
+}
+
+fn keep<T>(slot: &mut Option<(bool, T)>, preferred: bool, value: T) {
+    match slot {
+        Some((kept, _)) if *kept || !preferred => {}
+        _ => *slot = Some((preferred, value)),
+    }
+}
+
+impl Card {
+    fn set(&mut self, property: &str, preferred: bool, value: &str) {
+        let text = || components(value).join(";");
+        match property {
+            "FN" => self.full_name = text(),
+            "N" => self.name = components(value),
+            "ORG" => self.organization = components(value).into_iter().next().unwrap_or_default(),
+            "ADR" => keep(&mut self.address, preferred, components(value)),
+            "LABEL" if self.label.is_empty() => self.label = text(),
+            "TEL" => keep(&mut self.phone, preferred, text()),
+            "EMAIL" => keep(&mut self.email, preferred, text()),
+            "NOTE" => self.notes = text(),
+            _ => {}
+        }
+    }
+
+    fn into_contact(self) -> Option<Contact> {
+        let name = if self.full_name.is_empty() {
+            // Family; given; additional; prefix; suffix
+            let part = |i: usize| self.name.get(i).map(String::as_str).unwrap_or_default();
+            join(&[part(3), part(1), part(2), part(0), part(4)], " ")
+        } else {
+            self.full_name
+        };
+        // PO box; extended address; street; locality; region; postcode; country
+        let address = self.address.map(|(_, parts)| parts).unwrap_or_default();
+        let part = |i: usize| address.get(i).map(String::as_str).unwrap_or_default();
+        let contact = Contact {
+            name,
+            company: self.organization,
+            street: join(&[part(0), part(1), part(2)], "\n"),
+            postcode: part(5).to_string(),
+            city: join(&[part(3), part(4)], ", "),
+            country: part(6).to_string(),
+            phone: self.phone.map(|(_, phone)| phone).unwrap_or_default(),
+            email: self.email.map(|(_, email)| email).unwrap_or_default(),
+            notes: self.notes,
+            ..Default::default()
+        };
+        finish(contact, &self.label)
+    }
+}
+
+/// Contacts from the rows of a Google Contacts or Outlook CSV export, or any
+/// table with recognizable column names
+pub fn from_table(table: &DataTable) -> Result<Vec<Contact>, String> {
+    let columns: Vec<String> = table.columns.iter().map(|c| c.trim().to_lowercase()).collect();
+    let names = [
+        "name",
+        "first name",
+        "given name",
+        "last name",
+        "family name",
+        "company",
+        "organization 1 - name",
+        "organization name",
+    ];
+    if !columns.iter().any(|column| names.contains(&column.as_str())) {
+        return Err("The file has no name or company column; is it a contacts export?".to_string());
+    }
+
+    let mut contacts = Vec::new();
+    for row in &table.rows {
+        let record: BTreeMap<&str, &str> = columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| (column.as_str(), value.trim()))
+            .collect();
+        // First non-empty value among the columns
+        let get = |names: &[&str]| -> String {
+            names
+                .iter()
+                .filter_map(|name| record.get(name))
+                .find(|value| !value.is_empty())
+                // Google puts several values in one cell, separated by " ::: "
+                .map(|value| value.split(" ::: ").next().unwrap_or_default().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut name = get(&["name", "full name", "display name"]);
+        if name.is_empty() {
+            name = join(
+                &[
+                    &get(&["name prefix", "title"]),
+                    &get(&["first name", "given name"]),
+                    &get(&["middle name", "additional name"]),
+                    &get(&["last name", "family name"]),
+                    &get(&["name suffix", "suffix"]),
+                ],
+                " ",
+            );
+        }
+
+        let address = |prefix: &str, suffixes: &[&str]| {
+            let names: Vec<String> = suffixes.iter().map(|suffix| format!("{}{}", prefix, suffix)).collect();
+            get(&names.iter().map(String::as_str).collect::<Vec<_>>())
+        };
+        let street_of = |prefix: &str| {
+            join(
+                &[
+                    &address(prefix, &["po box", "pobox"]),
+                    &address(prefix, &["extended address"]),
+                    &address(prefix, &["street", "address"]),
+                    &address(prefix, &["street 2"]),
+                    &address(prefix, &["street 3"]),
+                ],
+                "\n",
+            )
+        };
+        let postcode_of = |prefix: &str| address(prefix, &["postal code", "postcode", "zip", "zip code"]);
+        let city_of = |prefix: &str| address(prefix, &["city"]);
+        let formatted_of = |prefix: &str| address(prefix, &["formatted"]);
+        // The first address that has anything in it
+        let prefix = ADDRESS_PREFIXES
+            .iter()
+            .copied()
+            .find(|&prefix| {
+                [
+                    street_of(prefix),
+                    postcode_of(prefix),
+                    city_of(prefix),
+                    formatted_of(prefix),
+                ]
+                .iter()
+                .any(|part| !part.is_empty())
+            })
+            .unwrap_or_default();
+
+        let contact = Contact {
+            name,
+            company: get(&["company", "organization 1 - name", "organization name", "organization"]),
+            street: street_of(prefix),
+            postcode: postcode_of(prefix),
+            city: join(&[&city_of(prefix), &address(prefix, &["region", "state"])], ", "),
+            country: address(prefix, &["country", "country/region"]),
+            phone: get(&[
+                "phone 1 - value",
+                "business phone",
+                "mobile phone",
+                "home phone",
+                "primary phone",
+                "phone",
+            ]),
+            email: get(&["e-mail 1 - value", "e-mail address", "email", "email address"]),
+            notes: get(&["notes", "note"]),
+            ..Default::default()
+        };
+        if let Some(contact) = finish(contact, &formatted_of(prefix)) {
+            contacts.push(contact);
+        }
+    }
+    Ok(contacts)
+}
+
+fn join(parts: &[&str], separator: &str) -> String {
+    parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+// Tidies an imported contact: one street line per address line, the
+// address taken from `formatted` when the export only has it in one block,
+// and country codes written out. None for a contact without a name or company.
+fn finish(mut contact: Contact, formatted: &str) -> Option<Contact> {
+    if contact.name.trim().is_empty() && contact.company.trim().is_empty() {
+        return None;
+    }
+    contact.street = join(&contact.street.lines().collect::<Vec<_>>(), "\n");
+    if contact.street.is_empty() && contact.city.is_empty() {
+        let mut lines: Vec<&str> = formatted.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        let country_line = lines
+            .last()
+            .is_some_and(|last| !contact.country.is_empty() && last.eq_ignore_ascii_case(&contact.country));
+        if country_line {
+            lines.pop();
+        }
+        if lines.len() > 1 {
+            // "1234 AB Amsterdam" or "Amsterdam 1234": the postcode is the
+            // part with digits in it
+            let place = lines.pop().unwrap_or_default();
+            let (postcode, city): (Vec<&str>, Vec<&str>) = place
+                .split_whitespace()
+                .partition(|word| word.chars().any(|c| c.is_ascii_digit()) || is_postcode_letters(word, place));
+            if contact.postcode.is_empty() {
+                contact.postcode = postcode.join(" ");
+            }
+            contact.city = city.join(" ");
+        }
+        contact.street = lines.join("\n");
+    }
+    contact.country = country_name(&contact.country);
+    Some(contact)
+}
+
+// The letters of a Dutch postcode, "1234 AB", stand on their own after the digits
+fn is_postcode_letters(word: &str, place: &str) -> bool {
+    word.len() == 2
+        && word.chars().all(|c| c.is_ascii_uppercase())
+        && place
+            .split_whitespace()
+            .next()
+            .is_some_and(|first| first.len() == 4 && first.chars().all(|c| c.is_ascii_digit()))
+        && place.split_whitespace().nth(1) == Some(word)
+}
+
+/// `country` written out when it's a two-letter country code
+pub fn country_name(country: &str) -> String {
+    let country = country.trim();
+    COUNTRIES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(country))
+        .map_or(country.to_string(), |(_, name)| name.to_string())
+}
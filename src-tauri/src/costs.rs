@@ -0,0 +1,132 @@
+// Label cost accounting. Each printer's media can be given a price per roll
+// or sheet; a job's cost is the share of that its labels take, estimated
+// before a batch is printed and totalled over the print history for reports.
+// Reports price past jobs at today's prices.
+use crate::groups;
+use crate::history::HistoryStore;
+use crate::media::{self, RollKind};
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::State;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaCost {
+    /// Price of one roll or sheet
+    pub price: f64,
+    pub kind: RollKind,
+    /// Millimeters of tape, or labels, one roll or sheet holds
+    pub quantity: f64,
+    /// Tape width, to tell which side of a label runs along the tape
+    #[serde(default)]
+    pub width_mm: Option<f64>,
+}
+
+impl MediaCost {
+    pub fn cost(&self, labels: usize, width_mm: f64, height_mm: f64) -> Option<f64> {
+        if self.quantity <= 0.0 {
+            return None;
+        }
+        let used = media::usage(self.kind, self.width_mm, labels, width_mm, height_mm);
+        Some(used / self.quantity * self.price)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobCost {
+    pub cost: f64,
+    /// E.g. "EUR"; absent when none is configured
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostGrouping {
+    #[default]
+    Printer,
+    Template,
+    /// Calendar month (UTC), e.g. "2026-10"
+    Month,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CostLine {
+    /// Printer, template or month; empty for jobs without a template
+    pub key: String,
+    pub jobs: usize,
+    pub labels: usize,
+    /// Cost of the labels whose printer has a media cost
+    pub cost: f64,
+    /// Labels printed on printers without one, left out of `cost`
+    pub unpriced_labels: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CostReport {
+    pub lines: Vec<CostLine>,
+    pub total: f64,
+    pub currency: Option<String>,
+}
+
+// Media cost of `printer`; groups are priced as their first printer
+pub fn media_cost<'a>(costs: &'a BTreeMap<String, MediaCost>, printer: &str) -> Option<&'a MediaCost> {
+    costs.get(printer).or_else(|| costs.get(&groups::primary(printer)))
+}
+
+/// Cost of printing `labels` labels on `printer_name`, for confirming a
+/// large batch; None when its media has no price
+#[tauri::command]
+pub fn estimate_job_cost(
+    settings: State<'_, SettingsStore>,
+    printer_name: String,
+    labels: usize,
+    width_mm: f64,
+    height_mm: f64,
+) -> Option<JobCost> {
+    let settings = settings.get();
+    let cost = media_cost(&settings.media_costs, &printer_name)?.cost(labels, width_mm, height_mm)?;
+    Some(JobCost {
+        cost,
+        currency: settings.currency,
+    })
+}
+
+/// Cost of the jobs printed at or after `since` and before `until` (RFC 3339
+/// UTC or dates like 2026-10-01), one line per printer, template or month
+#[tauri::command]
+pub async fn get_cost_report(
+    settings: State<'_, SettingsStore>,
+    history: State<'_, HistoryStore>,
+    since: Option<String>,
+    until: Option<String>,
+    group_by: Option<CostGrouping>,
+) -> Result<CostReport, String> {
+    let settings = settings.get();
+    let group_by = group_by.unwrap_or_default();
+    let mut lines: BTreeMap<String, CostLine> = BTreeMap::new();
+    for job in history.printed(since.as_deref(), until.as_deref())? {
+        let key = match group_by {
+            CostGrouping::Printer => job.printer.clone(),
+            CostGrouping::Template => job.template.clone().unwrap_or_default(),
+            CostGrouping::Month => job.printed_at.chars().take(7).collect(),
+        };
+        let line = lines.entry(key.clone()).or_insert_with(|| CostLine {
+            key,
+            ..CostLine::default()
+        });
+        line.jobs += 1;
+        line.labels += job.labels;
+        match media_cost(&settings.media_costs, &job.printer)
+            .and_then(|cost| cost.cost(job.labels, job.width_mm, job.height_mm))
+        {
+            Some(cost) => line.cost += cost,
+            None => line.unpriced_labels += job.labels,
+        }
+    }
+    let lines: Vec<CostLine> = lines.into_values().collect();
+    Ok(CostReport {
+        total: lines.iter().map(|line| line.cost).sum(),
+        lines,
+        currency: settings.currency,
+    })
+}
@@ -0,0 +1,160 @@
+// Named serial-number counters, persisted in the app data directory so asset
+// and batch numbers never repeat across sessions
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+use tracing::error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Counter {
+    /// Value handed out by the next call to `next_serial`
+    pub next: u64,
+    #[serde(default = "default_step")]
+    pub step: u64,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+    /// Minimum number of digits, zero-padded
+    #[serde(default)]
+    pub padding: usize,
+}
+
+impl Counter {
+    pub fn format(&self, value: u64) -> String {
+        format!("{}{:0width$}{}", self.prefix, value, self.suffix, width = self.padding)
+    }
+}
+
+fn default_step() -> u64 {
+    1
+}
+
+pub struct CounterStore {
+    path: PathBuf,
+    counters: Mutex<BTreeMap<String, Counter>>,
+    /// Why the counters file couldn't be loaded. Counters are unusable until
+    /// it is fixed, since starting over would hand out duplicate serials.
+    load_error: Option<String>,
+}
+
+impl CounterStore {
+    pub fn load(path: PathBuf) -> Self {
+        let (counters, load_error) = match fs::read_to_string(&path) {
+            Ok(json) => match serde_json::from_str(&json) {
+                Ok(counters) => (counters, None),
+                Err(e) => (BTreeMap::new(), Some(format!("Counters file {} is invalid: {}", path.display(), e))),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (BTreeMap::new(), None),
+            Err(e) => (BTreeMap::new(), Some(format!("Failed to read counters file: {}", e))),
+        };
+        if let Some(error) = &load_error {
+            error!("{}", error);
+        }
+        CounterStore {
+            path,
+            counters: Mutex::new(counters),
+            load_error,
+        }
+    }
+
+    pub fn list(&self) -> BTreeMap<String, Counter> {
+        self.counters.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, name: &str, counter: Counter) -> Result<(), String> {
+        let mut counters = self.counters.lock().unwrap();
+        let mut updated = counters.clone();
+        updated.insert(name.to_string(), counter);
+        self.write(&updated)?;
+        *counters = updated;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let mut counters = self.counters.lock().unwrap();
+        let mut updated = counters.clone();
+        updated.remove(name);
+        self.write(&updated)?;
+        *counters = updated;
+        Ok(())
+    }
+
+    /// Formatted next value of `name`. The increment is saved before the
+    /// value is returned, so a failed print leaves a gap rather than a repeat.
+    pub fn next_serial(&self, name: &str) -> Result<String, String> {
+        let mut counters = self.counters.lock().unwrap();
+        let mut updated = counters.clone();
+        let counter = updated
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown counter: {}", name))?;
+        let value = counter.next;
+        counter.next = value
+            .checked_add(counter.step)
+            .ok_or_else(|| format!("Counter {} has run out of values", name))?;
+        let serial = counter.format(value);
+        self.write(&updated)?;
+        *counters = updated;
+        Ok(serial)
+    }
+
+    /// Move `name` on to `next` for values from `first` that were handed
+    /// out some other way, e.g. a numbered range. Fails when the counter has
+    /// already handed out `first`, which would print numbers twice.
+    pub fn reserve(&self, name: &str, first: u64, next: u64) -> Result<(), String> {
+        let mut counters = self.counters.lock().unwrap();
+        let mut updated = counters.clone();
+        let counter = updated
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown counter: {}", name))?;
+        if first < counter.next {
+            return Err(format!(
+                "Counter {} is already at {}; numbering from {} would repeat numbers",
+                name, counter.next, first
+            ));
+        }
+        counter.next = next;
+        self.write(&updated)?;
+        *counters = updated;
+        Ok(())
+    }
+
+    // Write to a temp file and rename so a crash can't leave half a file behind
+    fn write(&self, counters: &BTreeMap<String, Counter>) -> Result<(), String> {
+        // Never overwrite a file we failed to read
+        if let Some(error) = &self.load_error {
+            return Err(error.clone());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(counters)
+            .map_err(|e| format!("Failed to serialize counters: {}", e))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, json).map_err(|e| format!("Failed to write counters: {}", e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| format!("Failed to save counters: {}", e))
+    }
+}
+
+#[tauri::command]
+pub fn list_counters(store: State<'_, CounterStore>) -> BTreeMap<String, Counter> {
+    store.list()
+}
+
+#[tauri::command]
+pub fn set_counter(store: State<'_, CounterStore>, name: String, counter: Counter) -> Result<(), String> {
+    store.set(&name, counter)
+}
+
+#[tauri::command]
+pub fn delete_counter(store: State<'_, CounterStore>, name: String) -> Result<(), String> {
+    store.delete(&name)
+}
+
+#[tauri::command]
+pub fn next_serial(store: State<'_, CounterStore>, counter: String) -> Result<String, String> {
+    store.next_serial(&counter)
+}
@@ -0,0 +1,85 @@
+// Credentials for printers and print servers that require authentication,
+// kept in the OS keyring (Secret Service, Keychain, Credential Manager) rather
+// than the settings file. Entries are keyed by the printer name as listed, so
+// a CUPS queue and an ipp:// URI for the same printer are stored separately.
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::warn;
+
+/// Keyring service every entry is stored under
+const SERVICE: &str = "labelgood";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+// Keeps passwords out of logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials").field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+fn entry(printer_name: &str) -> Result<Entry, String> {
+    Entry::new(SERVICE, printer_name).map_err(|e| format!("Failed to open the keyring: {}", e))
+}
+
+/// Credentials stored for `printer_name`; None when there are none or the
+/// keyring can't be read
+pub fn get(printer_name: &str) -> Option<Credentials> {
+    let secret = match entry(printer_name).map(|entry| entry.get_password()) {
+        Ok(Ok(secret)) => secret,
+        Ok(Err(keyring::Error::NoEntry)) => return None,
+        Ok(Err(e)) => {
+            warn!("Failed to read credentials for {}: {}", printer_name, e);
+            return None;
+        }
+        Err(e) => {
+            warn!("{}", e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&secret) {
+        Ok(credentials) => Some(credentials),
+        Err(e) => {
+            warn!("Ignoring unreadable credentials for {}: {}", printer_name, e);
+            None
+        }
+    }
+}
+
+/// `get` without blocking the async runtime; unlocking the keyring may wait
+/// for the user
+pub async fn lookup(printer_name: &str) -> Option<Credentials> {
+    let printer_name = printer_name.to_string();
+    tauri::async_runtime::spawn_blocking(move || get(&printer_name)).await.ok().flatten()
+}
+
+/// Store the username and password to print to `printer_name` with,
+/// replacing any stored before
+#[tauri::command]
+pub fn set_printer_credentials(printer_name: String, username: String, password: String) -> Result<(), String> {
+    let secret = serde_json::to_string(&Credentials { username, password })
+        .map_err(|e| format!("Failed to encode credentials: {}", e))?;
+    entry(&printer_name)?
+        .set_password(&secret)
+        .map_err(|e| format!("Failed to store credentials for {}: {}", printer_name, e))
+}
+
+#[tauri::command]
+pub fn delete_printer_credentials(printer_name: String) -> Result<(), String> {
+    match entry(&printer_name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to delete credentials for {}: {}", printer_name, e)),
+    }
+}
+
+/// Username stored for `printer_name`, for the credentials dialog; the
+/// password never leaves the backend
+#[tauri::command]
+pub fn get_printer_username(printer_name: String) -> Option<String> {
+    get(&printer_name).map(|credentials| credentials.username)
+}
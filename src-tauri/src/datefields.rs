@@ -0,0 +1,57 @@
+// Date/time placeholders evaluated at print time, so "packed on" and
+// "best before" labels always carry the current date:
+//
+//   {date}              2026-03-14        {date:%d.%m.%Y}    14.03.2026
+//   {time}              09:30             {time:%H:%M:%S}
+//   {datetime}          2026-03-14 09:30
+//   {expiry:30}         today + 30 days   {expiry:30:%d/%m/%y}
+//   {week}              ISO week number   {week:%G-W%V}
+use chrono::{DateTime, Duration, Local};
+use std::fmt::Write;
+
+/// Value of a date field at `now`, or `None` if `field` isn't a date field.
+/// Formats use strftime syntax.
+pub fn evaluate(field: &str, now: DateTime<Local>) -> Option<Result<String, String>> {
+    let (name, args) = match field.split_once(':') {
+        Some((name, args)) => (name.trim(), Some(args)),
+        None => (field.trim(), None),
+    };
+
+    let result = match name {
+        "date" => format(now, args.unwrap_or("%Y-%m-%d")),
+        "time" => format(now, args.unwrap_or("%H:%M")),
+        "datetime" => format(now, args.unwrap_or("%Y-%m-%d %H:%M")),
+        "week" => format(now, args.unwrap_or("%V")),
+        "expiry" => {
+            let args = args.unwrap_or_default();
+            let (days, fmt) = args.split_once(':').unwrap_or((args, "%Y-%m-%d"));
+            days.trim()
+                .parse::<i64>()
+                .ok()
+                .and_then(|days| now.checked_add_signed(Duration::days(days)))
+                .ok_or_else(|| format!("Invalid expiry field {{{}}}: expected {{expiry:DAYS}}", field))
+                .and_then(|date| format(date, fmt))
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn format(time: DateTime<Local>, fmt: &str) -> Result<String, String> {
+    let mut out = String::new();
+    write!(out, "{}", time.format(fmt)).map_err(|_| format!("Invalid date format: {}", fmt))?;
+    Ok(out)
+}
+
+/// Replace the date fields in `text` with their current values, for labels
+/// printed outside a merge
+#[tauri::command]
+pub fn resolve_date_fields(text: String) -> Result<String, String> {
+    let values = std::collections::BTreeMap::new();
+    let mut context = crate::placeholders::Context {
+        values: &values,
+        now: Local::now(),
+        next_serial: None,
+    };
+    crate::placeholders::render(&text, &mut context)
+}
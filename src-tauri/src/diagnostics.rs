@@ -0,0 +1,252 @@
+// Setup checklist: probes for the external programs and permissions printing
+// depends on, so a missing ImageMagick or CUPS shows up with install
+// instructions before the first print rather than halfway through one
+use crate::imagemagick;
+use crate::printing::{self, LPSTAT_TIMEOUT};
+use crate::sandbox::{self, Sandbox};
+use crate::tools;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// Printing works, but not everywhere or not as well as it could
+    Warning,
+    /// Printing fails until this is fixed
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// Stable identifier, e.g. "imagemagick"
+    pub id: &'static str,
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Command that fixes a failed check on this distribution
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvironmentReport {
+    /// ID from /etc/os-release, e.g. "fedora"; the OS name elsewhere
+    pub distro: String,
+    /// No check is missing
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+// Package manager family, for install hints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    Fedora,
+    Debian,
+    Arch,
+    Suse,
+    Other,
+}
+
+impl Family {
+    fn install(self, fedora: &str, debian: &str, arch: &str, suse: &str) -> String {
+        match self {
+            Family::Fedora => format!("sudo dnf install {}", fedora),
+            Family::Debian => format!("sudo apt install {}", debian),
+            Family::Arch => format!("sudo pacman -S {}", arch),
+            Family::Suse => format!("sudo zypper install {}", suse),
+            Family::Other => format!("Install {} with your package manager", debian),
+        }
+    }
+}
+
+// (ID, family) from /etc/os-release, falling back to ID_LIKE for derivatives
+// such as Linux Mint or Rocky
+fn distro() -> (String, Family) {
+    let Ok(release) = fs::read_to_string("/etc/os-release") else {
+        return (std::env::consts::OS.to_string(), Family::Other);
+    };
+    let field = |name: &str| {
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .map(|value| value.trim_matches('"').to_string())
+            .unwrap_or_default()
+    };
+    let id = field("ID");
+    let family = format!("{} {}", id, field("ID_LIKE"))
+        .split_whitespace()
+        .find_map(|name| match name {
+            "fedora" | "rhel" | "centos" => Some(Family::Fedora),
+            "debian" | "ubuntu" => Some(Family::Debian),
+            "arch" => Some(Family::Arch),
+            "suse" | "opensuse" => Some(Family::Suse),
+            _ => None,
+        })
+        .unwrap_or(Family::Other);
+    (id, family)
+}
+
+// First line of `program arg`, or None when it can't be run
+async fn first_line(program: &str, arg: &str) -> Option<String> {
+    let mut command = tools::command(program);
+    command.arg(arg);
+    let output = printing::run_command(command, LPSTAT_TIMEOUT, |e| e.to_string().into()).await.ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).to_string();
+    Some(text.lines().next().unwrap_or_default().trim().to_string())
+}
+
+async fn check_imagemagick(family: Family) -> Check {
+    let hint = family.install("ImageMagick", "imagemagick", "imagemagick", "ImageMagick");
+    let (status, detail, hint) = match imagemagick::converter().await {
+        Ok(converter) => match &converter.pdf_blocked_by {
+            // Labels still print through the built-in PDF writer, but PDFs
+            // can't be read, e.g. shipping labels and previews of them
+            Some(policy) => (
+                CheckStatus::Warning,
+                format!("{} may not read or write PDF files ({})", converter.version, policy.display()),
+                Some(format!(
+                    "Allow PDF in {} with rights=\"read|write\" on the PDF coder policy",
+                    policy.display()
+                )),
+            ),
+            None => (CheckStatus::Ok, converter.version.clone(), None),
+        },
+        Err(_) => (
+            CheckStatus::Missing,
+            "Neither ImageMagick nor GraphicsMagick was found".to_string(),
+            Some(hint),
+        ),
+    };
+    Check {
+        id: "imagemagick",
+        name: "ImageMagick",
+        status,
+        detail,
+        hint,
+    }
+}
+
+fn check_spooler(family: Family) -> Check {
+    let lpr = tools::resolve("lpr");
+    let lp = tools::resolve("lp");
+    let (status, detail) = match (&lpr, &lp) {
+        (Some(lpr), _) => (CheckStatus::Ok, format!("lpr found at {}", lpr.display())),
+        (None, Some(lp)) => (
+            CheckStatus::Ok,
+            format!("lp found at {}; used because lpr isn't installed", lp.display()),
+        ),
+        (None, None) => (CheckStatus::Missing, "Neither lpr nor lp was found".to_string()),
+    };
+    Check {
+        id: "lpr",
+        name: "Print commands",
+        status,
+        detail,
+        hint: (status != CheckStatus::Ok).then(|| family.install("cups-client", "cups-bsd", "cups", "cups-client")),
+    }
+}
+
+async fn check_cups(family: Family) -> Check {
+    let (status, detail, hint) = match first_line("lpstat", "-r").await {
+        Some(line) if line.contains("not running") => (
+            CheckStatus::Missing,
+            "The CUPS scheduler is not running".to_string(),
+            Some("sudo systemctl enable --now cups".to_string()),
+        ),
+        Some(line) => (CheckStatus::Ok, line, None),
+        None => (
+            CheckStatus::Missing,
+            "CUPS is not installed".to_string(),
+            Some(family.install("cups", "cups", "cups", "cups")),
+        ),
+    };
+    Check {
+        id: "cups",
+        name: "CUPS",
+        status,
+        detail,
+        hint,
+    }
+}
+
+// USB printers show up as /dev/usb/lp*; writing to them needs the lp group
+fn check_usb() -> Check {
+    let devices: Vec<PathBuf> = fs::read_dir("/dev/usb")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.file_name().is_some_and(|n| n.to_string_lossy().starts_with("lp")))
+                .collect()
+        })
+        .unwrap_or_default();
+    let denied: Vec<String> = devices
+        .iter()
+        .filter(|device| fs::OpenOptions::new().write(true).open(device).is_err())
+        .map(|device| device.display().to_string())
+        .collect();
+    let (status, detail) = if devices.is_empty() {
+        (CheckStatus::Ok, "No USB printers connected directly".to_string())
+    } else if denied.is_empty() {
+        (CheckStatus::Ok, format!("{} USB printer(s) writable", devices.len()))
+    } else {
+        (CheckStatus::Warning, format!("No write access to {}", denied.join(", ")))
+    };
+    Check {
+        id: "usb",
+        name: "USB printer access",
+        status,
+        detail,
+        hint: (status != CheckStatus::Ok)
+            .then(|| "sudo usermod -aG lp $USER, then log out and back in".to_string()),
+    }
+}
+
+// Inside Flatpak or Snap, print commands and USB devices are out of reach
+// by design; the portal and network printers are what's left
+fn check_sandbox(sandbox: Sandbox) -> Check {
+    Check {
+        id: "sandbox",
+        name: "Sandbox",
+        status: CheckStatus::Warning,
+        detail: format!(
+            "Running in {}: system printers print through the print dialog, and USB printers can't be reached",
+            sandbox.name()
+        ),
+        hint: Some(
+            "Add network printers as socket://HOST:9100 or ipp://HOST/ipp/print to print without a dialog"
+                .to_string(),
+        ),
+    }
+}
+
+/// Probe everything printing needs
+#[tauri::command]
+pub async fn check_environment() -> EnvironmentReport {
+    let (distro, family) = distro();
+    let mut checks = vec![check_imagemagick(family).await];
+    match sandbox::detect() {
+        Some(sandbox) => checks.push(check_sandbox(sandbox)),
+        None => checks.extend([check_spooler(family), check_cups(family).await, check_usb()]),
+    }
+    EnvironmentReport {
+        distro,
+        ready: checks.iter().all(|c| c.status != CheckStatus::Missing),
+        checks,
+    }
+}
+
+/// Log failed checks at startup, so they're in the log even if the checklist
+/// is never opened
+pub fn log_problems() {
+    tauri::async_runtime::spawn(async {
+        for check in check_environment().await.checks {
+            if check.status != CheckStatus::Ok {
+                warn!("{}: {}", check.name, check.detail);
+            }
+        }
+    });
+}
@@ -0,0 +1,598 @@
+use crate::rfid::RfidTag;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// A label design as understood by the backend.
+///
+/// All positions and sizes are in millimetres, measured from the top-left
+/// corner of the label in its printed (unrotated) orientation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub width_mm: f64,
+    pub height_mm: f64,
+    #[serde(default)]
+    pub continuous_width: bool,
+    #[serde(default)]
+    pub continuous_height: bool,
+    #[serde(default)]
+    pub elements: Vec<Element>,
+    /// Original bytes of images used by the document, keyed by content hash
+    #[serde(default)]
+    pub assets: BTreeMap<String, Asset>,
+    /// Fields asked for in a form when the label is printed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prompts: Vec<PromptField>,
+    /// Bottom layer first; elements outside any layer are drawn above them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<Layer>,
+    /// Tag encoded into each label by RFID printers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfid: Option<RfidTag>,
+}
+
+impl Document {
+    pub fn new(width_mm: f64, height_mm: f64) -> Self {
+        Document {
+            width_mm,
+            height_mm,
+            continuous_width: false,
+            continuous_height: false,
+            elements: Vec::new(),
+            assets: BTreeMap::new(),
+            prompts: Vec::new(),
+            layers: Vec::new(),
+            rfid: None,
+        }
+    }
+
+    /// Store an asset and return its id; identical files share one entry
+    pub fn add_asset(&mut self, file_name: &str, mime_type: &str, bytes: &[u8]) -> String {
+        let id = asset_id(bytes);
+        self.assets.entry(id.clone()).or_insert_with(|| Asset {
+            file_name: file_name.to_string(),
+            mime_type: mime_type.to_string(),
+            data: BASE64.encode(bytes),
+        });
+        id
+    }
+
+    /// Next free element id, matching the frontend's `max(id) + 1` scheme
+    pub fn next_element_id(&self) -> u32 {
+        self.elements.iter().map(|e| e.id).max().unwrap_or(0) + 1
+    }
+
+    /// Append an element, assigning it a fresh id
+    pub fn push(&mut self, mut element: Element) {
+        element.id = self.next_element_id();
+        self.elements.push(element);
+    }
+
+    fn layer_of(&self, element: &Element) -> Option<&Layer> {
+        let id = element.layer?;
+        self.layers.iter().find(|layer| layer.id == id)
+    }
+
+    /// Elements to draw, bottom first: layer by layer, then those outside
+    /// any layer. Hidden layers are left out, and the elements of a group
+    /// stay together where its lowest element is.
+    pub fn stacked(&self) -> Vec<&Element> {
+        // Elements of a layer that no longer exists are drawn with the ones
+        // outside any layer
+        let position = |element: &Element| {
+            element
+                .layer
+                .and_then(|id| self.layers.iter().position(|layer| layer.id == id))
+                .unwrap_or(self.layers.len())
+        };
+        let group_start = |index: usize, element: &Element| match element.group {
+            Some(group) => self
+                .elements
+                .iter()
+                .position(|e| e.group == Some(group))
+                .unwrap_or(index),
+            None => index,
+        };
+        let mut stacked: Vec<(usize, usize, &Element)> = self
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| !self.layer_of(element).is_some_and(|layer| layer.hidden))
+            .map(|(i, element)| (position(element), group_start(i, element), element))
+            .collect();
+        stacked.sort_by_key(|(layer, group, _)| (*layer, *group));
+        stacked.into_iter().map(|(_, _, element)| element).collect()
+    }
+
+    /// Copy without the elements of background layers, for printing; a
+    /// background is only a guide to design on, e.g. a scan of pre-printed
+    /// stock
+    pub fn printable(&self) -> Document {
+        let mut document = self.clone();
+        document
+            .elements
+            .retain(|element| !self.layer_of(element).is_some_and(|layer| layer.background));
+        document
+    }
+}
+
+/// A named set of elements drawn, hidden and locked together, e.g. a frame
+/// of fixed artwork that merged data is laid over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Layer {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    /// Not drawn at all
+    #[serde(default)]
+    pub hidden: bool,
+    /// Its elements can't be selected or moved in the editor
+    #[serde(default)]
+    pub locked: bool,
+    /// Shown while designing but never printed
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// A placeholder field filled in at print time, e.g. the batch number or
+/// best-before date of a production run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptField {
+    /// Placeholder name, as in `{batch}`
+    pub name: String,
+    /// Shown in the form; the name when empty
+    #[serde(default)]
+    pub label: String,
+    #[serde(rename = "type", default)]
+    pub kind: PromptKind,
+    /// Used when the field is left empty
+    #[serde(default)]
+    pub default: String,
+    /// Regular expression a non-empty answer must match in full
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptKind {
+    #[default]
+    Text,
+    /// Decimal comma or point
+    Number,
+    /// YYYY-MM-DD
+    Date,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Element {
+    pub id: u32,
+    pub x_mm: f64,
+    pub y_mm: f64,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    /// Clockwise rotation about the element's top-left corner
+    #[serde(default)]
+    pub rotation_deg: f64,
+    #[serde(flatten)]
+    pub kind: ElementKind,
+    /// Conditional visibility and formatting, evaluated per merged label
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    /// Id of the layer it's on; None for above all layers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<u32>,
+    /// Id shared by the elements of a group, which are selected and moved
+    /// as one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<u32>,
+    /// Can't be selected or moved in the editor
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl Element {
+    pub fn new(x_mm: f64, y_mm: f64, width_mm: f64, height_mm: f64, kind: ElementKind) -> Self {
+        Element {
+            id: 0,
+            x_mm,
+            y_mm,
+            width_mm,
+            height_mm,
+            rotation_deg: 0.0,
+            kind,
+            rules: Vec::new(),
+            layer: None,
+            group: None,
+            locked: false,
+        }
+    }
+}
+
+/// "When `when` holds, do `then`", e.g. hide the fragile icon unless the
+/// `fragile` column is "yes"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub when: Condition,
+    pub then: RuleAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    /// Merge field the condition reads
+    pub field: String,
+    pub op: ConditionOp,
+    /// Compared against; unused by `empty` and `not_empty`
+    #[serde(default)]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionOp {
+    /// Case-insensitive, ignoring surrounding whitespace
+    Equals,
+    NotEquals,
+    Contains,
+    Empty,
+    NotEmpty,
+    /// Numeric comparisons; false when either side isn't a number
+    GreaterThan,
+    LessThan,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleAction {
+    Hide,
+    /// Hide the element unless at least one of its `show` rules holds
+    Show,
+    /// Text, barcode or shape outline colour
+    SetColor { color: String },
+    /// Shape fill colour
+    SetFill { color: String },
+    /// Replace the text or barcode data; may contain placeholders
+    SetText { text: String },
+    SetBold { bold: bool },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ElementKind {
+    Text(TextElement),
+    Barcode(BarcodeElement),
+    Image(ImageElement),
+    Shape(ShapeElement),
+    Nutrition(NutritionElement),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextElement {
+    pub text: String,
+    pub font_family: String,
+    pub font_size_pt: f64,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default = "default_color")]
+    pub color: String,
+    #[serde(default)]
+    pub align: TextAlign,
+    /// Differently styled spans; when present they replace `text`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<TextRun>,
+    #[serde(default)]
+    pub layout: TextLayout,
+}
+
+impl TextElement {
+    /// The text without styling: the runs joined, or `text` when there are
+    /// none
+    pub fn plain_text(&self) -> String {
+        if self.runs.is_empty() {
+            self.text.clone()
+        } else {
+            self.runs.iter().map(|run| run.text.as_str()).collect()
+        }
+    }
+}
+
+impl Default for TextElement {
+    fn default() -> Self {
+        TextElement {
+            text: String::new(),
+            font_family: "Arial".to_string(),
+            font_size_pt: 12.0,
+            bold: false,
+            italic: false,
+            color: default_color(),
+            align: TextAlign::Left,
+            runs: Vec::new(),
+            layout: TextLayout::Horizontal,
+        }
+    }
+}
+
+/// A span of a text element with its own style; anything unset is taken
+/// from the element. Runs may contain line breaks.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextRun {
+    pub text: String,
+    pub font_family: Option<String>,
+    pub font_size_pt: Option<f64>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TextLayout {
+    #[default]
+    Horizontal,
+    /// Characters stacked top to bottom, each line a column; columns run
+    /// right to left from the element's right edge. Alignment places the
+    /// columns at the top, middle or bottom.
+    Vertical,
+    /// Along a circle centred horizontally in the element, on one line.
+    /// Text reads over the top of the circle, or along the bottom when
+    /// `inside` is set, e.g. for round stickers. Alignment places it at the
+    /// start, middle or end of the half circle.
+    Arc {
+        radius_mm: f64,
+        #[serde(default)]
+        inside: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BarcodeElement {
+    /// Lowercase symbology name, e.g. "code128", "ean13", "qr"
+    pub symbology: String,
+    pub data: String,
+    #[serde(default = "default_true")]
+    pub show_text: bool,
+    #[serde(default = "default_color")]
+    pub color: String,
+    #[serde(flatten)]
+    pub options: BarcodeOptions,
+}
+
+/// Symbology specific settings; anything unset uses the symbology's default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BarcodeOptions {
+    /// Width of one module (narrow bar or 2D cell). The symbol is stretched
+    /// to fit the element when absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_size_mm: Option<f64>,
+    /// QR "L"/"M"/"Q"/"H", PDF417 level "0"-"8", or Aztec percentage, e.g. "23"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_correction: Option<String>,
+    /// DataMatrix "square" or "rectangle"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shape: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageElement {
+    /// Key into `Document::assets`
+    pub asset_id: String,
+    /// Applied when the label is rendered; the asset keeps the original
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adjustments: Option<ImageAdjustments>,
+}
+
+/// Tone adjustments for raster images, mostly to get photos to survive a
+/// 1-bit thermal printer. Applied in field order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageAdjustments {
+    /// -100 to 100
+    pub brightness: f64,
+    /// -100 to 100
+    pub contrast: f64,
+    /// 1 leaves midtones as they are; higher lightens them
+    pub gamma: f64,
+    /// Unsharp mask strength, 0 for none
+    pub sharpen: f64,
+    pub invert: bool,
+    /// Pixels darker than this (0-255) print black and the rest white;
+    /// without it greys are left to the printer's dithering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<u8>,
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        ImageAdjustments {
+            brightness: 0.0,
+            contrast: 0.0,
+            gamma: 1.0,
+            sharpen: 0.0,
+            invert: false,
+            threshold: None,
+        }
+    }
+}
+
+/// An embedded file, kept exactly as it was imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Asset {
+    pub file_name: String,
+    pub mime_type: String,
+    /// Base64 encoded original bytes (no data URL prefix). Empty inside a
+    /// project bundle's manifest, where the bytes live in separate entries.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub data: String,
+}
+
+impl Asset {
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        BASE64
+            .decode(&self.data)
+            .map_err(|e| format!("Asset {} has invalid data: {}", self.file_name, e))
+    }
+}
+
+/// Content hash used as asset id
+pub fn asset_id(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest.iter().take(12).map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeElement {
+    pub shape: ShapeKind,
+    #[serde(default = "default_stroke_width")]
+    pub stroke_width_mm: f64,
+    #[serde(default = "default_color")]
+    pub stroke_color: String,
+    #[serde(default)]
+    pub fill_color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    /// Line from (x, y) to (x + width, y + height)
+    Line,
+}
+
+/// A nutrition declaration, laid out by the renderer to fill the element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NutritionElement {
+    #[serde(default)]
+    pub standard: NutritionStandard,
+    /// EU: what the values are given for, "100 g" when empty. US: the
+    /// serving size, e.g. "1 cup (228g)".
+    #[serde(default)]
+    pub serving_size: String,
+    /// US only, e.g. "8"
+    #[serde(default)]
+    pub servings_per_container: String,
+    /// EU only: add a column with the percentage of the reference intake
+    #[serde(default)]
+    pub reference_intakes: bool,
+    /// EU only: write decimals with a comma
+    #[serde(default)]
+    pub decimal_comma: bool,
+    #[serde(default = "default_font_family")]
+    pub font_family: String,
+    #[serde(default = "default_color")]
+    pub color: String,
+    #[serde(default)]
+    pub values: NutritionValues,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NutritionStandard {
+    /// Regulation (EU) No 1169/2011 table
+    #[default]
+    Eu,
+    /// FDA Nutrition Facts panel
+    Us,
+}
+
+/// Declared amounts as text, so they can be placeholders. Grams, except
+/// energy, cholesterol, sodium, calcium, iron and potassium (mg) and
+/// vitamin D (µg). Empty for nutrients that aren't declared.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NutritionValues {
+    pub energy_kj: String,
+    pub energy_kcal: String,
+    pub fat: String,
+    pub saturated_fat: String,
+    pub trans_fat: String,
+    pub cholesterol: String,
+    pub carbohydrate: String,
+    pub sugars: String,
+    pub added_sugars: String,
+    pub fibre: String,
+    pub protein: String,
+    pub salt: String,
+    pub sodium: String,
+    pub vitamin_d: String,
+    pub calcium: String,
+    pub iron: String,
+    pub potassium: String,
+}
+
+impl NutritionValues {
+    pub fn fields(&self) -> [&String; 17] {
+        [
+            &self.energy_kj,
+            &self.energy_kcal,
+            &self.fat,
+            &self.saturated_fat,
+            &self.trans_fat,
+            &self.cholesterol,
+            &self.carbohydrate,
+            &self.sugars,
+            &self.added_sugars,
+            &self.fibre,
+            &self.protein,
+            &self.salt,
+            &self.sodium,
+            &self.vitamin_d,
+            &self.calcium,
+            &self.iron,
+            &self.potassium,
+        ]
+    }
+
+    pub fn fields_mut(&mut self) -> [&mut String; 17] {
+        [
+            &mut self.energy_kj,
+            &mut self.energy_kcal,
+            &mut self.fat,
+            &mut self.saturated_fat,
+            &mut self.trans_fat,
+            &mut self.cholesterol,
+            &mut self.carbohydrate,
+            &mut self.sugars,
+            &mut self.added_sugars,
+            &mut self.fibre,
+            &mut self.protein,
+            &mut self.salt,
+            &mut self.sodium,
+            &mut self.vitamin_d,
+            &mut self.calcium,
+            &mut self.iron,
+            &mut self.potassium,
+        ]
+    }
+}
+
+fn default_font_family() -> String {
+    "Arial".to_string()
+}
+
+fn default_color() -> String {
+    "#000000".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_stroke_width() -> f64 {
+    0.3
+}
@@ -0,0 +1,96 @@
+// Files dropped onto the window. Images and PDFs become image elements at
+// their physical size; label files open as projects.
+use crate::document::{Document, Element, ElementKind, ImageElement};
+use crate::formats;
+use crate::ingest;
+use crate::printing;
+use crate::project::{self, LoadedAsset};
+use crate::settings::SettingsStore;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{Emitter, Manager, PhysicalPosition, Window};
+use tracing::error;
+
+/// Event emitted once per dropped file
+pub const FILE_DROPPED_EVENT: &str = "file-dropped";
+
+#[derive(Debug, Serialize)]
+pub struct DroppedFile {
+    pub path: String,
+    /// Drop point in physical window pixels
+    pub x: f64,
+    pub y: f64,
+    #[serde(flatten)]
+    pub content: DropContent,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DropContent {
+    /// An image element at the origin, sized to the file, and the asset it
+    /// refers to
+    Image { element: Element, asset: LoadedAsset },
+    Project { document: Document },
+    Failed { error: String },
+}
+
+/// Handle a drop on `window`. Files are processed off the event loop since
+/// PDFs go through ImageMagick.
+pub fn handle_drop(window: &Window, paths: Vec<PathBuf>, position: PhysicalPosition<f64>) {
+    let window = window.clone();
+    let max_dpi = window.state::<SettingsStore>().get().dropped_image_max_dpi;
+    std::thread::spawn(move || {
+        for path in paths {
+            let content = match load(&path, max_dpi) {
+                Ok(content) => content,
+                Err(error) => {
+                    error!("Failed to import dropped file {}: {}", path.display(), error);
+                    DropContent::Failed { error }
+                }
+            };
+            let dropped = DroppedFile {
+                path: path.to_string_lossy().to_string(),
+                x: position.x,
+                y: position.y,
+                content,
+            };
+            if let Err(e) = window.emit(FILE_DROPPED_EVENT, dropped) {
+                error!("Failed to emit {}: {}", FILE_DROPPED_EVENT, e);
+            }
+        }
+    });
+}
+
+fn load(path: &Path, max_dpi: Option<f64>) -> Result<DropContent, String> {
+    if formats::is_importable(path) {
+        return Ok(DropContent::Project {
+            document: project::load_document(path)?,
+        });
+    }
+
+    // Drops are handled on their own thread, off the async runtime
+    let image = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) {
+        let bytes = tauri::async_runtime::block_on(printing::rasterize_pdf(path, Some(0)))?.remove(0);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        ingest::from_raster(&stem, bytes, max_dpi)?
+    } else if ingest::is_image(path) {
+        tauri::async_runtime::block_on(ingest::load(path, max_dpi))?
+    } else {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        return Err(format!("Unsupported file type: {}", file_name));
+    };
+    let (width_mm, height_mm) = (image.width_mm, image.height_mm);
+
+    let loaded = image.into_asset();
+    let element = Element::new(
+        0.0,
+        0.0,
+        width_mm,
+        height_mm,
+        ElementKind::Image(ImageElement {
+            asset_id: loaded.id.clone(),
+            adjustments: None,
+        }),
+    );
+    Ok(DropContent::Image { element, asset: loaded })
+}
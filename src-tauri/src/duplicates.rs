@@ -0,0 +1,130 @@
+// Guard against printing the same label twice, e.g. a second asset tag with
+// a serial number that's already on a machine. A job is held back when one
+// of its labels, the same template filled with the same data, was printed
+// successfully within the configured window. The user confirms by approving
+// the job's fingerprint and printing again. Jobs without data print the same
+// fixed label on purpose and are never held back.
+use crate::history::{HistoryStore, JobDetails};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Sources that repeat earlier jobs on purpose: reprints from the history
+/// and held jobs printed once their printer is back
+const EXEMPT_SOURCES: &[&str] = &["history", "offline"];
+
+/// How long an approval waits for the job to be printed again
+const APPROVAL_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateGuard {
+    pub enabled: bool,
+    /// How far back to look for the same label
+    pub window_minutes: u32,
+}
+
+impl Default for DuplicateGuard {
+    fn default() -> Self {
+        DuplicateGuard {
+            enabled: false,
+            window_minutes: 60,
+        }
+    }
+}
+
+/// An earlier print of one of a job's labels
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    /// Identifies the job, for `approve_duplicate_print`
+    pub fingerprint: String,
+    /// When the label was last printed, UTC, RFC 3339
+    pub printed_at: String,
+}
+
+static APPROVED: Mutex<Vec<(String, Instant)>> = Mutex::new(Vec::new());
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// One label's hash: the template and the data it was filled with
+fn label_hash(template: Option<&str>, record: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(template.unwrap_or_default().as_bytes());
+    for (name, value) in record {
+        hasher.update([0]);
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(value.as_bytes());
+    }
+    hex(&hasher.finalize())
+}
+
+// Hashes of the labels of a job that carry data
+fn label_hashes(template: Option<&str>, data: &[BTreeMap<String, String>]) -> Vec<String> {
+    data.iter()
+        .filter(|record| record.values().any(|value| !value.is_empty()))
+        .map(|record| label_hash(template, record))
+        .collect()
+}
+
+// Use up an approval of `fingerprint`, if there's one
+fn take_approval(fingerprint: &str) -> bool {
+    let mut approved = APPROVED.lock().unwrap();
+    approved.retain(|(_, at)| at.elapsed() < APPROVAL_TTL);
+    match approved.iter().position(|(approved, _)| approved == fingerprint) {
+        Some(i) => {
+            approved.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The latest earlier print of one of the labels of a job from `source`,
+/// when the guard is on and the job wasn't approved
+pub fn find(
+    history: &HistoryStore,
+    guard: &DuplicateGuard,
+    source: &str,
+    details: &JobDetails,
+) -> Result<Option<Duplicate>, String> {
+    if !guard.enabled || EXEMPT_SOURCES.contains(&source) {
+        return Ok(None);
+    }
+    let template = details.template.as_deref();
+    let hashes = label_hashes(template, &details.data);
+    if hashes.is_empty() {
+        return Ok(None);
+    }
+    let since = (Utc::now() - chrono::Duration::minutes(guard.window_minutes.into()))
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+    let labels: HashSet<&String> = hashes.iter().collect();
+    let Some(printed_at) = history
+        .printed_data(template, &since)?
+        .into_iter()
+        .find(|(_, data)| label_hashes(template, data).iter().any(|hash| labels.contains(hash)))
+        .map(|(printed_at, _)| printed_at)
+    else {
+        return Ok(None);
+    };
+    let fingerprint = hex(&Sha256::digest(hashes.concat()));
+    if take_approval(&fingerprint) {
+        return Ok(None);
+    }
+    Ok(Some(Duplicate {
+        fingerprint,
+        printed_at,
+    }))
+}
+
+/// Let the next print of the job with `fingerprint` through, within five
+/// minutes, although its labels were printed before
+#[tauri::command]
+pub fn approve_duplicate_print(fingerprint: String) {
+    APPROVED.lock().unwrap().push((fingerprint, Instant::now()));
+}
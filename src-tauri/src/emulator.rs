@@ -0,0 +1,187 @@
+// Network label printer emulator for development: listens like a printer on
+// port 9100 and renders each job it receives to PNGs in a folder, so driver
+// output can be developed and regression-tested without hardware. Enabled by
+// pointing LABELGOOD_EMULATOR_DIR at a directory; print to
+// "socket://127.0.0.1:9100". Each connection is one job, e.g.
+//
+//   job-1.zpl     the job exactly as received
+//   job-1-1.png   each label or page, as a 203 dpi printer would print it
+//
+// ZPL, EPL, PDF and PWG raster jobs are rendered; anything else is only
+// saved.
+pub mod pwg;
+
+use crate::document::Document;
+use crate::fonts::FontLibrary;
+use crate::formats::{epl, zpl};
+use crate::printing::{self, RENDER_DPI};
+use crate::raster;
+use crate::render;
+use image::GrayImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+pub const EMULATOR_DIR_ENV: &str = "LABELGOOD_EMULATOR_DIR";
+/// Where to listen instead of `DEFAULT_ADDRESS`, e.g. "0.0.0.0:9100"
+pub const EMULATOR_ADDRESS_ENV: &str = "LABELGOOD_EMULATOR_ADDRESS";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:9100";
+/// Resolution of the emulated printer, in dots per mm
+const DPMM: f64 = zpl::DEFAULT_DPMM;
+
+#[derive(Debug, Clone, Copy)]
+enum JobFormat {
+    Zpl,
+    Epl,
+    Pdf,
+    PwgRaster,
+    Unknown,
+}
+
+impl JobFormat {
+    fn detect(data: &[u8]) -> Self {
+        let mut lines = data
+            .split(|b| *b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+        if data.starts_with(b"%PDF") {
+            JobFormat::Pdf
+        } else if data.starts_with(pwg::SYNC) {
+            JobFormat::PwgRaster
+        } else if data.windows(3).any(|w| w.eq_ignore_ascii_case(b"^XA")) {
+            JobFormat::Zpl
+        } else if lines.any(|line| line == b"N") {
+            // EPL labels start by clearing the image buffer
+            JobFormat::Epl
+        } else {
+            JobFormat::Unknown
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            JobFormat::Zpl => "zpl",
+            JobFormat::Epl => "epl",
+            JobFormat::Pdf => "pdf",
+            JobFormat::PwgRaster => "pwg",
+            JobFormat::Unknown => "bin",
+        }
+    }
+}
+
+/// Start the emulator when `EMULATOR_DIR_ENV` is set
+pub fn start(app: &AppHandle) {
+    let Some(dir) = std::env::var_os(EMULATOR_DIR_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+    else {
+        return;
+    };
+    let address = std::env::var(EMULATOR_ADDRESS_ENV)
+        .ok()
+        .filter(|a| !a.is_empty())
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let fonts = app.state::<FontLibrary>().database();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            error!("Failed to create printer emulator directory {}: {}", dir.display(), e);
+            return;
+        }
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start the printer emulator on {}: {}", address, e);
+                return;
+            }
+        };
+        info!(
+            "Printer emulator listening on {}, saving jobs to {}",
+            address,
+            dir.display()
+        );
+        let mut next_job = last_job(&dir) + 1;
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("Printer emulator failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let (job, dir, fonts) = (next_job, dir.clone(), fonts.clone());
+            next_job += 1;
+            tauri::async_runtime::spawn(async move {
+                let mut data = Vec::new();
+                if let Err(e) = stream.read_to_end(&mut data).await {
+                    warn!("Printer emulator failed to receive job {} from {}: {}", job, peer, e);
+                    return;
+                }
+                match receive(&dir, job, &data, fonts).await {
+                    Ok(pages) => info!(
+                        "Printer emulator job {}: {} bytes from {}, {} pages",
+                        job,
+                        data.len(),
+                        peer,
+                        pages
+                    ),
+                    Err(e) => warn!("Printer emulator job {}: {}", job, e),
+                }
+            });
+        }
+    });
+}
+
+// Highest job number saved in `dir`, so a restart doesn't overwrite jobs
+fn last_job(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    name.strip_prefix("job-")?.split(['-', '.']).next()?.parse::<u64>().ok()
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0)
+}
+
+// Save job `job` and render what it prints; the number of pages rendered
+async fn receive(dir: &Path, job: u64, data: &[u8], fonts: Arc<fontdb::Database>) -> Result<usize, String> {
+    let format = JobFormat::detect(data);
+    let saved = dir.join(format!("job-{}.{}", job, format.extension()));
+    fs::write(&saved, data).map_err(|e| format!("Failed to save {}: {}", saved.display(), e))?;
+    let pages = match format {
+        JobFormat::Zpl => render_labels(&zpl::parse_all(&String::from_utf8_lossy(data), DPMM)?, fonts)?,
+        JobFormat::Epl => render_labels(&epl::parse_all(data, DPMM)?, fonts)?,
+        JobFormat::Pdf => printing::rasterize_pdf(&saved, None)
+            .await?
+            .iter()
+            .map(|page| raster::print_raster(page, DPMM * 25.4))
+            .collect::<Result<_, _>>()?,
+        JobFormat::PwgRaster => pwg::decode(data)?,
+        JobFormat::Unknown => {
+            warn!("Printer emulator job {} is in an unknown format; saved as is", job);
+            Vec::new()
+        }
+    };
+    for (i, page) in pages.iter().enumerate() {
+        let path = dir.join(format!("job-{}-{}.png", job, i + 1));
+        fs::write(&path, raster::encode_png(page)?).map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+    }
+    Ok(pages.len())
+}
+
+fn render_labels(labels: &[Document], fonts: Arc<fontdb::Database>) -> Result<Vec<GrayImage>, String> {
+    labels
+        .iter()
+        .map(|label| {
+            let png = render::render_png(&label.printable(), fonts.clone(), RENDER_DPI)?;
+            raster::print_raster(&png, DPMM * 25.4)
+        })
+        .collect()
+}
@@ -0,0 +1,141 @@
+// PWG Raster (PWG 5102.4), what CUPS and IPP Everywhere send to printers
+// that take raster: a sync word, then for each page a 1796 byte header and
+// its lines, run-length compressed. Decoded to greyscale.
+use image::{GrayImage, Luma};
+
+pub const SYNC: &[u8] = b"RaS2";
+const HEADER_LEN: usize = 1796;
+
+// Header fields, by byte offset
+const WIDTH: usize = 372;
+const HEIGHT: usize = 376;
+const BITS_PER_PIXEL: usize = 388;
+const BYTES_PER_LINE: usize = 392;
+const COLOR_SPACE: usize = 400;
+
+// Colour spaces; the others are read as greyscale
+const RGB: u32 = 1;
+const BLACK: u32 = 3;
+const SRGB: u32 = 19;
+const ADOBE_RGB: u32 = 20;
+
+struct PageHeader {
+    width: u32,
+    height: u32,
+    bits_per_pixel: usize,
+    bytes_per_line: usize,
+    color_space: u32,
+}
+
+impl PageHeader {
+    fn read(header: &[u8]) -> Result<Self, String> {
+        let field = |offset: usize| {
+            u32::from_be_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ])
+        };
+        let page = PageHeader {
+            width: field(WIDTH),
+            height: field(HEIGHT),
+            bits_per_pixel: field(BITS_PER_PIXEL) as usize,
+            bytes_per_line: field(BYTES_PER_LINE) as usize,
+            color_space: field(COLOR_SPACE),
+        };
+        if page.width == 0 || page.height == 0 || page.bits_per_pixel == 0 || page.bytes_per_line == 0 {
+            return Err("PWG raster page header has no size".to_string());
+        }
+        Ok(page)
+    }
+
+    // Byte value of a blank pixel
+    fn white(&self) -> u8 {
+        if self.color_space == BLACK {
+            0x00
+        } else {
+            0xff
+        }
+    }
+
+    // Luma of pixel `x` of an uncompressed line
+    fn luma(&self, line: &[u8], x: usize) -> u8 {
+        let byte = |i: usize| line.get(i).copied().unwrap_or(self.white()) as u32;
+        let bits = self.bits_per_pixel;
+        let value = if bits < 8 {
+            let bit = x * bits;
+            let max = (1 << bits) - 1;
+            ((byte(bit / 8) >> (8 - bits - bit % 8)) & max) * 255 / max
+        } else if matches!(self.color_space, RGB | SRGB | ADOBE_RGB) && bits >= 24 {
+            // The high byte of each of red, green and blue
+            let (start, step) = (x * bits / 8, bits / 24);
+            (byte(start) * 299 + byte(start + step) * 587 + byte(start + 2 * step) * 114) / 1000
+        } else {
+            byte(x * bits / 8)
+        };
+        if self.color_space == BLACK {
+            255 - value as u8
+        } else {
+            value as u8
+        }
+    }
+}
+
+/// Pages of a PWG raster stream
+pub fn decode(data: &[u8]) -> Result<Vec<GrayImage>, String> {
+    let mut rest = data.strip_prefix(SYNC).ok_or("Not PWG raster")?;
+    let mut pages = Vec::new();
+    while rest.len() >= HEADER_LEN {
+        let (header, body) = rest.split_at(HEADER_LEN);
+        let (page, used) = decode_page(&PageHeader::read(header)?, body)?;
+        pages.push(page);
+        rest = &body[used..];
+    }
+    Ok(pages)
+}
+
+// The page of `header` at the start of `data`, and the bytes it took. Each
+// line starts with how many more times it repeats, then runs of a repeated
+// pixel (0-127: 1-128 times), literal pixels (129-255: 128-2 of them) or
+// 128 to leave the rest of the line blank.
+fn decode_page(header: &PageHeader, data: &[u8]) -> Result<(GrayImage, usize), String> {
+    let truncated = || "PWG raster page is cut short".to_string();
+    // Lines with less than a byte per pixel are compressed by the byte
+    let pixel_len = header.bits_per_pixel.div_ceil(8);
+    let mut image = GrayImage::new(header.width, header.height);
+    let mut line = Vec::with_capacity(header.bytes_per_line);
+    let mut pos = 0;
+    let mut y = 0;
+    while y < header.height {
+        let repeat = *data.get(pos).ok_or_else(truncated)? as u32 + 1;
+        pos += 1;
+        line.clear();
+        while line.len() < header.bytes_per_line {
+            let control = *data.get(pos).ok_or_else(truncated)? as usize;
+            pos += 1;
+            match control {
+                0..=127 => {
+                    let pixel = data.get(pos..pos + pixel_len).ok_or_else(truncated)?;
+                    pos += pixel_len;
+                    for _ in 0..=control {
+                        line.extend_from_slice(pixel);
+                    }
+                }
+                128 => line.resize(header.bytes_per_line, header.white()),
+                _ => {
+                    let len = (257 - control) * pixel_len;
+                    line.extend_from_slice(data.get(pos..pos + len).ok_or_else(truncated)?);
+                    pos += len;
+                }
+            }
+        }
+        for row in y..(y + repeat).min(header.height) {
+            for x in 0..header.width {
+                image.put_pixel(x, row, Luma([header.luma(&line, x as usize)]));
+            }
+        }
+        y += repeat;
+    }
+    Ok((image, pos))
+}
@@ -0,0 +1,142 @@
+// Errors the frontend can act on. Each serializes with a machine-readable
+// `code` and a readable `message`, e.g.
+//
+//   {"code": "dependency_missing", "program": "convert", "message": "ImageMagick not found: ..."}
+//
+// so the UI can offer install instructions or a printer picker rather than
+// showing raw stderr. Most of the backend still reports plain strings; these
+// convert to `Other` and back.
+use crate::i18n;
+use fluent_bundle::FluentValue;
+use serde::Serialize;
+use std::fmt;
+
+/// Longest side a label may have. Long continuous labels stay well under
+/// this; anything bigger is a unit mix-up that would render a huge image.
+pub const MAX_LABEL_MM: f64 = 2000.0;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum LabelgoodError {
+    /// An external program (ImageMagick, CUPS) isn't installed
+    DependencyMissing { program: String, message: String },
+    /// The printer doesn't exist or isn't accepting jobs
+    PrinterUnavailable { printer: String, message: String },
+    /// The label couldn't be rendered or converted to PDF
+    RenderFailed { message: String },
+    /// ImageMagick's security policy forbids the conversion, e.g. reading PDF
+    PolicyBlocked { program: String, message: String },
+    /// A label size that can't be printed
+    InvalidDimensions { width_mm: f64, height_mm: f64, message: String },
+    /// Input from the frontend couldn't be used, e.g. bad image data
+    InvalidInput { message: String },
+    /// The printer or print server wants a username and password, or
+    /// refused the stored ones
+    AuthRequired { printer: String, message: String },
+    /// The printer exists but the job couldn't be handed to it
+    SpoolFailed { printer: String, message: String },
+    /// An external program didn't finish in time and was killed
+    TimedOut { program: String, message: String },
+    /// The job was cancelled from the frontend before it finished
+    Cancelled { message: String },
+    /// The printer is unreachable, so nothing was printed yet; the job is
+    /// kept as held job `held` and printed once the printer is back
+    Held { printer: String, held: i64, message: String },
+    /// The same label was printed recently; printing it again needs the
+    /// job's fingerprint approved
+    DuplicatePrint {
+        fingerprint: String,
+        printed_at: String,
+        message: String,
+    },
+    /// Kiosk mode's daily label quota for the operator doesn't leave room
+    /// for the job
+    QuotaExceeded {
+        operator: Option<String>,
+        template: Option<String>,
+        limit: u32,
+        used: usize,
+        message: String,
+    },
+    Io { message: String },
+    Other { message: String },
+}
+
+impl LabelgoodError {
+    pub fn message(&self) -> &str {
+        match self {
+            LabelgoodError::DependencyMissing { message, .. }
+            | LabelgoodError::PrinterUnavailable { message, .. }
+            | LabelgoodError::RenderFailed { message }
+            | LabelgoodError::PolicyBlocked { message, .. }
+            | LabelgoodError::InvalidDimensions { message, .. }
+            | LabelgoodError::InvalidInput { message }
+            | LabelgoodError::AuthRequired { message, .. }
+            | LabelgoodError::SpoolFailed { message, .. }
+            | LabelgoodError::TimedOut { message, .. }
+            | LabelgoodError::Cancelled { message }
+            | LabelgoodError::Held { message, .. }
+            | LabelgoodError::DuplicatePrint { message, .. }
+            | LabelgoodError::QuotaExceeded { message, .. }
+            | LabelgoodError::Io { message }
+            | LabelgoodError::Other { message } => message,
+        }
+    }
+
+    /// ImageMagick isn't installed, with how to install it
+    pub fn imagemagick_missing(e: impl fmt::Display) -> Self {
+        LabelgoodError::DependencyMissing {
+            program: "convert".to_string(),
+            message: i18n::t("imagemagick-missing", &[("error", e.to_string().into())]),
+        }
+    }
+
+    /// `printer` refused a job for lack of credentials; `rejected` when the
+    /// stored ones were sent
+    pub fn auth_required(printer: &str, rejected: bool) -> Self {
+        let id = if rejected { "auth-rejected" } else { "auth-required" };
+        LabelgoodError::AuthRequired {
+            printer: printer.to_string(),
+            message: i18n::t(id, &[("printer", printer.into())]),
+        }
+    }
+
+    /// A label size must be positive, finite and no longer than `MAX_LABEL_MM`
+    pub fn check_dimensions(width_mm: f64, height_mm: f64) -> Result<(), Self> {
+        let valid = |v: f64| v.is_finite() && v > 0.0;
+        let size: [(&str, FluentValue); 2] = [("width", width_mm.into()), ("height", height_mm.into())];
+        let message = if !valid(width_mm) || !valid(height_mm) {
+            i18n::t("invalid-label-size", &size)
+        } else if width_mm > MAX_LABEL_MM || height_mm > MAX_LABEL_MM {
+            let [width, height] = size;
+            i18n::t("label-too-large", &[width, height, ("max", MAX_LABEL_MM.into())])
+        } else {
+            return Ok(());
+        };
+        Err(LabelgoodError::InvalidDimensions {
+            width_mm,
+            height_mm,
+            message,
+        })
+    }
+}
+
+impl fmt::Display for LabelgoodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for LabelgoodError {}
+
+impl From<String> for LabelgoodError {
+    fn from(message: String) -> Self {
+        LabelgoodError::Other { message }
+    }
+}
+
+impl From<LabelgoodError> for String {
+    fn from(error: LabelgoodError) -> Self {
+        error.to_string()
+    }
+}
@@ -0,0 +1,409 @@
+// Job and printer status events. One bus feeds both the frontend (as Tauri
+// events) and external dashboards (over the REST API's SSE and WebSocket
+// streams), so everyone sees the same sequence.
+use crate::audit::AuditLog;
+use crate::backends::{self, cups};
+use crate::duplicates;
+use crate::error::LabelgoodError;
+use crate::history::{HistoryStore, JobDetails};
+use crate::i18n;
+use crate::inventory;
+use crate::media::RollKind;
+use crate::offline;
+use crate::operators;
+use crate::pdfpreview::PdfPreview;
+use crate::printing;
+use crate::progress::{self, JobTracker};
+use crate::quotas;
+use crate::settings::SettingsStore;
+use crate::webhooks::{self, JobNotification};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{broadcast, watch};
+use tracing::error;
+
+/// Tauri event name every `LabelEvent` is emitted under
+pub const LABEL_EVENT: &str = "label-event";
+
+// Slow subscribers skip events beyond this backlog rather than hold up jobs
+const CHANNEL_CAPACITY: usize = 256;
+
+const PRINTER_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LabelEvent {
+    JobAccepted {
+        job: u64,
+        /// What submitted the job: "app", "merge", "api", "hot_folder", "scan", "tray", "shortcut", "mqtt", "history", "shipping", "offline", "schedule", "image_folder", "pdf", "contacts", "reprint", "alignment", "grpc", "orders"
+        source: String,
+        labels: usize,
+        printer: Option<String>,
+    },
+    JobPrinting {
+        job: u64,
+    },
+    JobCompleted {
+        job: u64,
+        message: String,
+    },
+    JobFailed {
+        job: u64,
+        error: String,
+    },
+    /// The job's printer was unreachable, so its PDF is kept as held job
+    /// `held` and printed once the printer is back. Ends the job, which
+    /// neither completed nor failed.
+    JobHeld {
+        job: u64,
+        held: i64,
+        printer: String,
+    },
+    /// A printer changed state, e.g. went from idle to disabled
+    PrinterStatus {
+        printer: String,
+        status: String,
+        message: String,
+    },
+    /// A printer refused a job for lack of credentials, or the stored ones;
+    /// the frontend asks for them, stores them with
+    /// `set_printer_credentials` and prints again
+    AuthRequired {
+        job: Option<u64>,
+        printer: String,
+        message: String,
+    },
+    /// A job needs more media than the printer's roll is estimated to have
+    /// left; `remaining` and `needed` are in millimeters or labels by `kind`
+    MediaLow {
+        job: Option<u64>,
+        printer: String,
+        kind: RollKind,
+        remaining: f64,
+        needed: f64,
+        message: String,
+    },
+    /// A job wasn't started because its labels were printed recently; the
+    /// frontend asks whether to print them again, approves with
+    /// `approve_duplicate_print` and prints again
+    DuplicateBlocked {
+        source: String,
+        printer: Option<String>,
+        fingerprint: String,
+        printed_at: String,
+        message: String,
+    },
+    /// A job without a printer made a PDF to proof; the frontend shows it
+    /// in the preview pane
+    PreviewReady {
+        job: Option<u64>,
+        preview: PdfPreview,
+    },
+}
+
+impl LabelEvent {
+    /// Event name for SSE clients, matching the serialized `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LabelEvent::JobAccepted { .. } => "job_accepted",
+            LabelEvent::JobPrinting { .. } => "job_printing",
+            LabelEvent::JobCompleted { .. } => "job_completed",
+            LabelEvent::JobFailed { .. } => "job_failed",
+            LabelEvent::JobHeld { .. } => "job_held",
+            LabelEvent::PrinterStatus { .. } => "printer_status",
+            LabelEvent::AuthRequired { .. } => "auth_required",
+            LabelEvent::MediaLow { .. } => "media_low",
+            LabelEvent::DuplicateBlocked { .. } => "duplicate_blocked",
+            LabelEvent::PreviewReady { .. } => "preview_ready",
+        }
+    }
+}
+
+pub struct EventBus {
+    app: AppHandle,
+    sender: broadcast::Sender<LabelEvent>,
+    next_job: AtomicU64,
+    /// Cancel switches of the jobs currently running
+    running: Mutex<HashMap<u64, CancelSwitch>>,
+    /// Cancel switches of running operations, by the id the frontend gave
+    operations: Mutex<HashMap<String, CancelSwitch>>,
+}
+
+// Set to true to cancel. Shared by an operation and the jobs it runs.
+type CancelSwitch = Arc<watch::Sender<bool>>;
+
+tokio::task_local! {
+    // Switch of the operation the current task is running for
+    static OPERATION: CancelSwitch;
+}
+
+impl EventBus {
+    pub fn new(app: AppHandle) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        EventBus {
+            app,
+            sender,
+            next_job: AtomicU64::new(1),
+            running: Mutex::new(HashMap::new()),
+            operations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn publish(&self, event: LabelEvent) {
+        if let Err(e) = self.app.emit(LABEL_EVENT, &event) {
+            error!("Failed to emit {}: {}", LABEL_EVENT, e);
+        }
+        // No subscribers is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LabelEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Run a command's work as operation `op_id`, so `cancel_operation` can
+    /// stop it whether or not its job has started yet. Work without an id
+    /// can only be cancelled by job.
+    pub async fn run_operation<T>(&self, op_id: Option<String>, work: impl Future<Output = T>) -> T {
+        let Some(op_id) = op_id else {
+            return work.await;
+        };
+        let cancel = Arc::new(watch::channel(false).0);
+        self.operations.lock().unwrap().insert(op_id.clone(), cancel.clone());
+        let output = OPERATION.scope(cancel, work).await;
+        self.operations.lock().unwrap().remove(&op_id);
+        output
+    }
+
+    /// Run a print job, publishing its accepted, printing and completed or
+    /// failed events around `work`. `work` reports its progress under the
+    /// job's id and stops when the job is cancelled. The outcome is recorded
+    /// in the print history and audit log and sent to the configured webhooks.
+    /// Jobs repeating labels printed recently while the duplicate guard is
+    /// on, or going over an operator's quota in kiosk mode, fail without
+    /// starting. Jobs held for an unreachable printer end with `JobHeld`
+    /// and are recorded when they're printed.
+    pub async fn run_job<E: std::fmt::Display + From<LabelgoodError>>(
+        &self,
+        source: &str,
+        labels: usize,
+        printer: Option<&str>,
+        details: JobDetails,
+        work: impl Future<Output = Result<String, E>>,
+    ) -> Result<String, E> {
+        // Whoever started the job, even if someone else signs in meanwhile
+        let operator = operators::current();
+        self.run_job_as(source, labels, printer, operator, details, work).await
+    }
+
+    /// `run_job` on behalf of `operator` rather than whoever is signed in,
+    /// e.g. for a held job they started earlier
+    pub async fn run_job_as<E: std::fmt::Display + From<LabelgoodError>>(
+        &self,
+        source: &str,
+        labels: usize,
+        printer: Option<&str>,
+        operator: Option<String>,
+        details: JobDetails,
+        work: impl Future<Output = Result<String, E>>,
+    ) -> Result<String, E> {
+        if let Some(error) = self.duplicate(source, printer, &details) {
+            return Err(error.into());
+        }
+        // A job the audit log can't record isn't printed
+        if let Some(Err(e)) = self.app.try_state::<AuditLog>().map(|audit| audit.check()) {
+            let message = i18n::t("audit-unavailable", &[("error", e.into())]);
+            return Err(LabelgoodError::Io { message }.into());
+        }
+        // Counted against quotas until the job is in the audit log
+        let admission = match self.app.try_state::<AuditLog>() {
+            Some(audit) => {
+                let settings = self.app.try_state::<SettingsStore>().map(|s| s.get()).unwrap_or_default();
+                let template = details.template.as_deref();
+                Some(quotas::admit(&audit, &settings.quotas, source, operator.as_deref(), template, labels)?)
+            }
+            None => None,
+        };
+        let job = self.next_job.fetch_add(1, Ordering::Relaxed);
+        self.publish(LabelEvent::JobAccepted {
+            job,
+            source: source.to_string(),
+            labels,
+            printer: printer.map(str::to_string),
+        });
+        self.publish(LabelEvent::JobPrinting { job });
+        let cancel = OPERATION
+            .try_with(Arc::clone)
+            .unwrap_or_else(|_| Arc::new(watch::channel(false).0));
+        let tracker = JobTracker::new(self.app.clone(), job, cancel.subscribe());
+        self.running.lock().unwrap().insert(job, cancel);
+        let work = offline::track(operator.clone(), details.clone(), printing::capture_submitted(work));
+        let ((outcome, submitted), held) = progress::track(tracker, work).await;
+        self.running.lock().unwrap().remove(&job);
+        if held.is_some() {
+            // Nothing printed yet; the held job is recorded once it is
+            if let Some(audit) = self.app.try_state::<AuditLog>() {
+                if let Err(e) = audit.append(source, labels, printer, operator.as_deref(), &details, false) {
+                    error!("{}", e);
+                }
+            }
+            drop(admission);
+            return outcome;
+        }
+        let mut outcome = outcome;
+        let mut result = match &outcome {
+            Ok(message) => Ok(message.clone()),
+            Err(error) => Err(error.to_string()),
+        };
+        let printed = result.is_ok();
+        let settings = self.app.try_state::<SettingsStore>().map(|s| s.get()).unwrap_or_default();
+        // Failed when it can't be audited, even though the labels came out
+        if let Some(audit) = self.app.try_state::<AuditLog>() {
+            if let Err(e) = audit.append(source, labels, printer, operator.as_deref(), &details, printed) {
+                error!("{}", e);
+                let message = i18n::t("audit-write-failed", &[("error", e.into())]);
+                result = Err(message.clone());
+                outcome = Err(LabelgoodError::Io { message }.into());
+            }
+        }
+        if let Some(history) = self.app.try_state::<HistoryStore>() {
+            let operator = operator.as_deref();
+            if let Err(e) = history.record(source, labels, printer, operator, &details, submitted.as_ref(), &result) {
+                error!("{}", e);
+            }
+        }
+        drop(admission);
+        if printed {
+            inventory::decrement(settings.inventory, &details);
+        }
+        webhooks::notify(
+            settings.webhooks,
+            JobNotification::new(job, source, labels, printer, &details, &result),
+        );
+        self.publish(match &result {
+            Ok(message) => LabelEvent::JobCompleted { job, message: message.clone() },
+            Err(error) => LabelEvent::JobFailed { job, error: error.clone() },
+        });
+        outcome
+    }
+
+    // The error to fail a job with when the duplicate guard holds it back.
+    // A history that can't be read lets the job through.
+    fn duplicate(&self, source: &str, printer: Option<&str>, details: &JobDetails) -> Option<LabelgoodError> {
+        let history = self.app.try_state::<HistoryStore>()?;
+        let guard = self.app.try_state::<SettingsStore>()?.get().duplicate_guard;
+        let duplicate = match duplicates::find(&history, &guard, source, details) {
+            Ok(duplicate) => duplicate?,
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        };
+        let message = i18n::t("duplicate-print", &[("printed_at", duplicate.printed_at.clone().into())]);
+        self.publish(LabelEvent::DuplicateBlocked {
+            source: source.to_string(),
+            printer: printer.map(str::to_string),
+            fingerprint: duplicate.fingerprint.clone(),
+            printed_at: duplicate.printed_at.clone(),
+            message: message.clone(),
+        });
+        Some(LabelgoodError::DuplicatePrint {
+            fingerprint: duplicate.fingerprint,
+            printed_at: duplicate.printed_at,
+            message,
+        })
+    }
+}
+
+/// Publish `event` from inside a job's work, which has no access to Tauri
+/// state; no effect outside a job
+pub fn publish_in_job(event: LabelEvent) {
+    let Some(app) = progress::app() else {
+        return;
+    };
+    if let Some(bus) = app.try_state::<EventBus>() {
+        bus.publish(event);
+    }
+}
+
+/// Ask for credentials for `printer` from inside the job it refused
+pub fn request_credentials(printer: &str, message: &str) {
+    publish_in_job(LabelEvent::AuthRequired {
+        job: progress::current_job(),
+        printer: printer.to_string(),
+        message: message.to_string(),
+    });
+}
+
+/// Cancel a running job. Whatever step it's on stops, killing ImageMagick
+/// or lpr if they're running; a job already handed to the printer is not
+/// recalled.
+#[tauri::command]
+pub fn cancel_job(events: State<'_, EventBus>, job: u64) -> Result<(), String> {
+    let running = events.running.lock().unwrap();
+    let cancel = running.get(&job).ok_or_else(|| i18n::t("job-not-running", &[("job", job.into())]))?;
+    cancel.send_replace(true);
+    Ok(())
+}
+
+/// Cancel the operation a command was invoked with, e.g. a batch that is
+/// still rendering. Stops like `cancel_job`.
+#[tauri::command]
+pub fn cancel_operation(events: State<'_, EventBus>, op_id: String) -> Result<(), String> {
+    let operations = events.operations.lock().unwrap();
+    let cancel = operations
+        .get(&op_id)
+        .ok_or_else(|| i18n::t("operation-not-running", &[("operation", op_id.as_str().into())]))?;
+    cancel.send_replace(true);
+    Ok(())
+}
+
+/// Poll CUPS for printer states and publish a `PrinterStatus` event whenever
+/// one changes, e.g. a printer is disabled after running out of labels.
+/// Printers with held jobs are watched too, whatever their backend, and
+/// their jobs released on every poll that finds them ready.
+pub fn start_printer_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let bus = app.state::<EventBus>();
+        let mut known: HashMap<String, String> = HashMap::new();
+        loop {
+            let mut states = cups::printer_states().await;
+            let held = offline::held_printers();
+            for printer in &held {
+                if !states.iter().any(|(name, _, _)| name == printer) {
+                    let status = backends::resolve(printer).status(printer).await;
+                    states.push((status.printer, status.state, status.message));
+                }
+            }
+            for (printer, status, message) in states {
+                // Back, found ready at startup, or still up after a release
+                // that failed; releases already running aren't repeated
+                if held.contains(&printer) && matches!(status.as_str(), "idle" | "printing") {
+                    let (app, printer) = (app.clone(), printer.clone());
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = offline::release(app, printer).await {
+                            error!("{}", e);
+                        }
+                    });
+                }
+                if known.get(&printer) != Some(&status) {
+                    // The first poll only records the starting state
+                    if known.contains_key(&printer) || status != "idle" {
+                        bus.publish(LabelEvent::PrinterStatus {
+                            printer: printer.clone(),
+                            status: status.clone(),
+                            message,
+                        });
+                    }
+                    known.insert(printer, status);
+                }
+            }
+            tokio::time::sleep(PRINTER_POLL_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,354 @@
+// Installed and user-supplied fonts, shared by the font picker and the
+// backend renderer so both resolve the same font files
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use fontdb::{Database, Family, Query, Source, Stretch, Style, Weight, ID};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tauri::State;
+use tracing::info;
+
+pub struct FontLibrary {
+    db: RwLock<Arc<Database>>,
+    /// Where fonts added through `add_font_file` are copied so they persist
+    user_font_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FontFamily {
+    pub family: String,
+    pub faces: Vec<FontFace>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FontFace {
+    pub weight: u16,
+    pub style: &'static str,
+    pub postscript_name: String,
+    pub monospaced: bool,
+    /// True for fonts added by the user rather than installed on the system
+    pub user: bool,
+}
+
+impl FontLibrary {
+    pub fn load(user_font_dir: PathBuf) -> Self {
+        let mut db = Database::new();
+        db.load_system_fonts();
+        if user_font_dir.is_dir() {
+            db.load_fonts_dir(&user_font_dir);
+        }
+        set_generic_families(&mut db);
+        info!("Loaded {} font faces", db.len());
+        FontLibrary {
+            db: RwLock::new(Arc::new(db)),
+            user_font_dir,
+        }
+    }
+
+    /// Snapshot of the font database, e.g. for text rendering
+    pub fn database(&self) -> Arc<Database> {
+        self.db.read().unwrap().clone()
+    }
+
+    pub fn families(&self) -> Vec<FontFamily> {
+        let db = self.database();
+        let mut families: BTreeMap<String, Vec<FontFace>> = BTreeMap::new();
+
+        for face in db.faces() {
+            let Some((family, _)) = face.families.first() else {
+                continue;
+            };
+            let user = match &face.source {
+                Source::File(path) | Source::SharedFile(path, _) => path.starts_with(&self.user_font_dir),
+                Source::Binary(_) => false,
+            };
+            families.entry(family.clone()).or_default().push(FontFace {
+                weight: face.weight.0,
+                style: style_name(face.style),
+                postscript_name: face.post_script_name.clone(),
+                monospaced: face.monospaced,
+                user,
+            });
+        }
+
+        families
+            .into_iter()
+            .map(|(family, mut faces)| {
+                faces.sort_by_key(|f| (f.weight, f.style));
+                faces.dedup_by(|a, b| a.weight == b.weight && a.style == b.style);
+                FontFamily { family, faces }
+            })
+            .collect()
+    }
+
+    /// Copy a font file into the user font directory and load it.
+    /// Returns the families it provides.
+    pub fn add_font_file(&self, path: &Path) -> Result<Vec<String>, String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read font file: {}", e))?;
+        let file_name = path.file_name().ok_or("Font path has no file name")?;
+        self.add_font_data(&file_name.to_string_lossy(), data)
+    }
+
+    /// Store font file `data` as `file_name` in the user font directory and
+    /// load it, under another name when a different font has that one.
+    /// Returns the families it provides.
+    pub fn add_font_data(&self, file_name: &str, data: Vec<u8>) -> Result<Vec<String>, String> {
+        // Parse into a scratch database first so invalid files are rejected
+        // before anything is copied
+        let mut probe = Database::new();
+        probe.load_font_data(data.clone());
+        let mut families: Vec<String> = probe
+            .faces()
+            .filter_map(|f| f.families.first().map(|(name, _)| name.clone()))
+            .collect();
+        if families.is_empty() {
+            return Err(format!("{} is not a supported font file", file_name));
+        }
+        families.dedup();
+
+        let file_name = Path::new(file_name).file_name().ok_or("Font has no file name")?;
+        fs::create_dir_all(&self.user_font_dir)
+            .map_err(|e| format!("Failed to create font directory: {}", e))?;
+        let target = font_target(&self.user_font_dir, Path::new(file_name), &data);
+        if !target.exists() {
+            fs::write(&target, &data).map_err(|e| format!("Failed to copy font file: {}", e))?;
+        }
+
+        let mut db = self.db.write().unwrap();
+        let db = Arc::make_mut(&mut db);
+        // Faces loaded from the file before would otherwise be listed twice
+        let stale: Vec<ID> = db
+            .faces()
+            .filter(|face| match &face.source {
+                Source::File(path) | Source::SharedFile(path, _) => *path == target,
+                Source::Binary(_) => false,
+            })
+            .map(|face| face.id)
+            .collect();
+        for id in stale {
+            db.remove_face(id);
+        }
+        db.load_font_file(&target)
+            .map_err(|e| format!("Failed to load font file: {}", e))?;
+        Ok(families)
+    }
+
+    /// Files holding faces of `family`, installed or added by the user
+    pub fn font_files(&self, family: &str) -> Vec<PathBuf> {
+        let db = self.database();
+        let mut files: Vec<PathBuf> = db
+            .faces()
+            .filter(|face| face.families.iter().any(|(name, _)| name.eq_ignore_ascii_case(family)))
+            .filter_map(|face| match &face.source {
+                Source::File(path) | Source::SharedFile(path, _) => Some(path.clone()),
+                Source::Binary(_) => None,
+            })
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Bytes of the face that best matches the requested family and style
+    pub fn font_data(&self, family: &str, weight: u16, italic: bool) -> Option<Vec<u8>> {
+        let db = self.database();
+        let id = db.query(&Query {
+            families: &[Family::Name(family)],
+            weight: Weight(weight),
+            stretch: Stretch::Normal,
+            style: if italic { Style::Italic } else { Style::Normal },
+        })?;
+        db.with_face_data(id, |data, _| data.to_vec())
+    }
+}
+
+// Where to store font `data` named `file_name` in `dir`: a file there with
+// the same bytes, or else the first of "name.ttf", "name-2.ttf", ... that
+// isn't taken, so adding a font never replaces a different one
+fn font_target(dir: &Path, file_name: &Path, data: &[u8]) -> PathBuf {
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file_name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = dir.join(file_name);
+    let mut n = 1;
+    loop {
+        match fs::read(&candidate) {
+            Ok(existing) if existing != data => {
+                n += 1;
+                candidate = dir.join(format!("{}-{}{}", stem, n, extension));
+            }
+            _ => return candidate,
+        }
+    }
+}
+
+// fontdb maps the generic families to Windows font names; point them at
+// whatever is actually installed so fallback text still renders
+const GENERIC_CANDIDATES: &[(&str, &[&str])] = &[
+    ("sans-serif", &["Arial", "Helvetica", "Liberation Sans", "DejaVu Sans", "Noto Sans"]),
+    ("serif", &["Times New Roman", "Liberation Serif", "DejaVu Serif", "Noto Serif"]),
+    ("monospace", &["Courier New", "Liberation Mono", "DejaVu Sans Mono", "Noto Sans Mono"]),
+];
+
+pub fn set_generic_families(db: &mut Database) {
+    for (generic, candidates) in GENERIC_CANDIDATES {
+        let installed = candidates.iter().find(|name| {
+            db.faces()
+                .any(|face| face.families.iter().any(|(family, _)| family == *name))
+        });
+        if let Some(name) = installed {
+            match *generic {
+                "sans-serif" => db.set_sans_serif_family(*name),
+                "serif" => db.set_serif_family(*name),
+                _ => db.set_monospace_family(*name),
+            }
+        }
+    }
+}
+
+// Fonts to try first for characters the chosen font lacks, by script, so
+// Chinese, Japanese, Arabic and Hebrew text falls back to a font made for it
+// rather than whichever installed font happens to cover the character
+const FALLBACK_CANDIDATES: &[(&str, &[&str])] = &[
+    (
+        "cjk",
+        &[
+            "Noto Sans CJK JP",
+            "Noto Sans CJK SC",
+            "Source Han Sans",
+            "Yu Gothic",
+            "Microsoft YaHei",
+            "Hiragino Sans",
+            "PingFang SC",
+            "Malgun Gothic",
+            "Apple SD Gothic Neo",
+            "WenQuanYi Zen Hei",
+        ],
+    ),
+    (
+        "arabic",
+        &["Noto Naskh Arabic", "Noto Sans Arabic", "Segoe UI", "Arial", "Geeza Pro", "DejaVu Sans"],
+    ),
+    (
+        "hebrew",
+        &["Noto Sans Hebrew", "Arial", "Segoe UI", "Arial Hebrew", "DejaVu Sans"],
+    ),
+    (
+        "emoji",
+        &[
+            "Noto Color Emoji",
+            "Apple Color Emoji",
+            "Segoe UI Emoji",
+            "Twemoji",
+            "Twitter Color Emoji",
+            "JoyPixels",
+            "EmojiOne Color",
+        ],
+    ),
+    (
+        "monochrome-emoji",
+        &["Noto Emoji", "OpenMoji Black", "Segoe UI Symbol", "Symbola"],
+    ),
+];
+
+// Thermal printers dither color emoji into gray smudges; outline emoji
+// print crisply
+static MONOCHROME_EMOJI: AtomicBool = AtomicBool::new(false);
+
+/// Draw emoji from a black and white emoji font when one is installed
+pub fn configure_emoji(monochrome: bool) {
+    MONOCHROME_EMOJI.store(monochrome, Ordering::Relaxed);
+}
+
+pub fn monochrome_emoji() -> bool {
+    MONOCHROME_EMOJI.load(Ordering::Relaxed)
+}
+
+fn script(c: char) -> Option<&'static str> {
+    match c as u32 {
+        0x1100..=0x11FF | 0x2E80..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF | 0x20000..=0x3FFFF => {
+            Some("cjk")
+        }
+        0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Some("arabic"),
+        0x0590..=0x05FF | 0xFB1D..=0xFB4F => Some("hebrew"),
+        // Pictographs, flags and skin tones, plus the joiner, emoji
+        // presentation selector and tag characters inside emoji sequences,
+        // so a whole sequence comes from the same font
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x200D | 0xFE0F | 0xE0020..=0xE007F => Some("emoji"),
+        _ => None,
+    }
+}
+
+fn has_char(db: &Database, id: fontdb::ID, c: char) -> bool {
+    db.with_face_data(id, |data, index| {
+        ttf_parser::Face::parse(data, index).is_ok_and(|face| face.glyph_index(c).is_some())
+    })
+    .unwrap_or(false)
+}
+
+/// A face made for the script of `c` that has it, in the weight and style of
+/// the font it's missing from (the first of `exclude`); None for scripts
+/// without candidates or when none is installed
+pub fn script_fallback(db: &Database, c: char, exclude: &[fontdb::ID]) -> Option<fontdb::ID> {
+    let scripts = match script(c)? {
+        // Color emoji still beat tofu boxes when no monochrome font is
+        // installed, and the other way round
+        "emoji" if monochrome_emoji() => vec!["monochrome-emoji", "emoji"],
+        "emoji" => vec!["emoji", "monochrome-emoji"],
+        script => vec![script],
+    };
+    let candidates = scripts.into_iter().flat_map(|script| {
+        FALLBACK_CANDIDATES
+            .iter()
+            .filter(move |(name, _)| *name == script)
+            .flat_map(|(_, candidates)| candidates.iter())
+    });
+    let base = exclude.first().and_then(|id| db.face(*id));
+    candidates.into_iter().find_map(|name| {
+        let id = db.query(&Query {
+            families: &[Family::Name(name)],
+            weight: base.map_or(Weight::NORMAL, |face| face.weight),
+            stretch: base.map_or(Stretch::Normal, |face| face.stretch),
+            style: base.map_or(Style::Normal, |face| face.style),
+        })?;
+        (!exclude.contains(&id) && has_char(db, id, c)).then_some(id)
+    })
+}
+
+fn style_name(style: Style) -> &'static str {
+    match style {
+        Style::Normal => "normal",
+        Style::Italic => "italic",
+        Style::Oblique => "oblique",
+    }
+}
+
+#[tauri::command]
+pub fn list_fonts(fonts: State<'_, FontLibrary>) -> Vec<FontFamily> {
+    fonts.families()
+}
+
+#[tauri::command]
+pub async fn add_font_file(fonts: State<'_, FontLibrary>, path: String) -> Result<Vec<String>, String> {
+    fonts.add_font_file(Path::new(&path))
+}
+
+/// Base64 font file for registering a user font with the preview (CSS FontFace)
+#[tauri::command]
+pub fn get_font_data(
+    fonts: State<'_, FontLibrary>,
+    family: String,
+    weight: u16,
+    italic: bool,
+) -> Result<String, String> {
+    fonts
+        .font_data(&family, weight, italic)
+        .map(|data| BASE64.encode(data))
+        .ok_or_else(|| format!("Font not found: {}", family))
+}
@@ -0,0 +1,182 @@
+// Importers/exporters for label formats used by other label software
+pub mod definition;
+pub mod dymo;
+pub mod epl;
+pub mod glabels;
+pub mod nicelabel;
+pub mod ptouch;
+pub mod zpl;
+pub mod zpl_template;
+
+use crate::document::Document;
+use definition::DEFINITION_EXTENSION;
+use crate::project::{self, PROJECT_EXTENSION};
+use crate::fonts::FontLibrary;
+use crate::render::LINE_HEIGHT;
+use std::path::Path;
+use tauri::State;
+use zpl_template::ZplTemplateOptions;
+
+/// Millimetres per typographic point (1pt = 1/72 inch)
+pub const MM_PER_PT: f64 = 25.4 / 72.0;
+
+/// Extensions `import` understands
+const IMPORT_EXTENSIONS: &[&str] = &[
+    PROJECT_EXTENSION,
+    DEFINITION_EXTENSION,
+    "glabels",
+    "label",
+    "lbx",
+    "nlbl",
+    "zpl",
+    "epl",
+];
+
+pub fn is_importable(path: &Path) -> bool {
+    extension(path).is_some_and(|e| IMPORT_EXTENSIONS.contains(&e.as_str()))
+}
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    match extension(path).as_deref() {
+        Some(PROJECT_EXTENSION) => project::load(path).map(|p| p.document),
+        Some(DEFINITION_EXTENSION) => definition::import(path),
+        Some("glabels") => glabels::import(path),
+        Some("label") => dymo::import(path),
+        Some("lbx") => ptouch::import(path),
+        Some("nlbl") => nicelabel::import(path),
+        // Closed binary format; a Zebra driver's print to file is readable
+        Some("btw") => Err("BarTender documents can't be read; print the label to a file with a Zebra driver \
+                            and import that as a .zpl file"
+            .to_string()),
+        Some("zpl") => zpl::import(path),
+        Some("epl") => epl::import(path),
+        _ => Err(format!("Unsupported label file format: {}", path.display())),
+    }
+}
+
+#[tauri::command]
+pub async fn import_document(path: String) -> Result<Document, String> {
+    import(Path::new(&path))
+}
+
+#[tauri::command]
+pub async fn export_document(fonts: State<'_, FontLibrary>, document: Document, path: String) -> Result<(), String> {
+    let path = Path::new(&path);
+    match extension(path).as_deref() {
+        Some("glabels") => glabels::export(&document, path),
+        Some("zpl") => zpl_template::export(&document, fonts.database(), &ZplTemplateOptions::default(), path),
+        _ => Err(format!("Unsupported export format: {}", path.display())),
+    }
+}
+
+/// Write `document` to `path` as a ZPL template whose placeholders are ^FN
+/// fields of a stored format, or left in the text with `options.placeholders`
+#[tauri::command]
+pub async fn export_zpl_template(
+    fonts: State<'_, FontLibrary>,
+    document: Document,
+    path: String,
+    options: Option<ZplTemplateOptions>,
+) -> Result<(), String> {
+    zpl_template::export(&document, fonts.database(), &options.unwrap_or_default(), Path::new(&path))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+// `(x, y)` rotated clockwise by `degrees` (y points down)
+fn rotate((x, y): (f64, f64), degrees: f64) -> (f64, f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+// Width and height reaching to the right and bottom edges of the elements of
+// `document`, for formats that don't always give a label size
+fn content_size(document: &Document) -> (f64, f64) {
+    let (mut right, mut bottom) = (0.0f64, 0.0f64);
+    for element in &document.elements {
+        for corner in [
+            (element.width_mm, 0.0),
+            (0.0, element.height_mm),
+            (element.width_mm, element.height_mm),
+        ] {
+            let (dx, dy) = rotate(corner, element.rotation_deg);
+            right = right.max(element.x_mm.max(element.x_mm + dx));
+            bottom = bottom.max(element.y_mm.max(element.y_mm + dy));
+        }
+    }
+    (right, bottom)
+}
+
+// Total height in mm of a linear barcode with `bars` mm tall bars and its
+// text underneath, as `render::barcode_svg` sizes the text
+fn height_with_text(bars: f64) -> f64 {
+    let height = bars / (1.0 - 0.2 * LINE_HEIGHT);
+    if height * 0.2 <= 3.0 {
+        height
+    } else {
+        bars + 3.0 * LINE_HEIGHT
+    }
+}
+
+/// Guess an image mime type from a file name, defaulting to PNG
+pub fn mime_type_for(name: &str) -> &'static str {
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".jpg") || lower.ends_with(".jpeg") {
+        "image/jpeg"
+    } else if lower.ends_with(".gif") {
+        "image/gif"
+    } else if lower.ends_with(".bmp") {
+        "image/bmp"
+    } else if lower.ends_with(".svg") {
+        "image/svg+xml"
+    } else {
+        "image/png"
+    }
+}
+
+/// Map a symbology name from another application onto our lowercase names
+pub fn normalize_symbology(name: &str) -> String {
+    let key: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    let normalized = match key.as_str() {
+        "code128" | "code128a" | "code128b" | "code128c" | "code128auto" => "code128",
+        "code39" | "code39ext" | "code3of9" => "code39",
+        "ean" | "ean13" | "jan13" => "ean13",
+        "ean8" | "jan8" => "ean8",
+        "upc" | "upca" => "upca",
+        "upce" => "upce",
+        "itf14" => "itf14",
+        "itf" | "i25" | "itf25" | "interleaved2of5" | "2of5interleaved" | "code2of5" => "itf",
+        "gs1128" | "ean128" | "ucc128" => "gs1128",
+        "qr" | "qrcode" => "qr",
+        "datamatrix" | "ecc200" | "iec16022" => "datamatrix",
+        "pdf417" => "pdf417",
+        "aztec" | "azteccode" => "aztec",
+        _ => return key,
+    };
+    normalized.to_string()
+}
+
+/// Escape text for use in XML content and attribute values
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
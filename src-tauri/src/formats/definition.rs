@@ -0,0 +1,206 @@
+// Label definitions: labels written by hand as TOML, so they can be kept in
+// version control next to the code that prints them and printed through the
+// CLI or REST API without ever opening the editor. Sizes and positions are in
+// mm; elements are drawn in the order they're listed.
+//
+//   width = 62
+//   height = 29
+//
+//   [[element]]
+//   type = "text"
+//   x = 2
+//   y = 2
+//   width = 58
+//   height = 8
+//   text = "{{name}}"
+//   font_size = 14
+//   bold = true
+//
+//   [[element]]
+//   type = "barcode"
+//   x = 2
+//   y = 12
+//   width = 58
+//   height = 15
+//   symbology = "code128"
+//   data = "{{sku}}"
+//
+// Image `file`s are relative to the definition file. Definitions sent over
+// the network may only name images below the working directory, so a
+// client can't have any file on this machine read into a label.
+use super::mime_type_for;
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement, ShapeKind, TextAlign,
+    TextElement,
+};
+use std::path::{Component, Path};
+use std::fs;
+use std::path::Path;
+
+pub const DEFINITION_EXTENSION: &str = "toml";
+
+#[derive(Debug, Deserialize)]
+struct Definition {
+    width: f64,
+    height: f64,
+    /// Roll media cut to the content's length
+    #[serde(default)]
+    continuous_height: bool,
+    #[serde(default, rename = "element")]
+    elements: Vec<ElementDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ElementDefinition {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    /// Clockwise, about the top-left corner
+    #[serde(default)]
+    rotation: f64,
+    #[serde(flatten)]
+    kind: KindDefinition,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum KindDefinition {
+    Text {
+        text: String,
+        #[serde(default)]
+        font: Option<String>,
+        /// Points
+        #[serde(default)]
+        font_size: Option<f64>,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        italic: bool,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        align: TextAlign,
+    },
+    Barcode {
+        symbology: String,
+        data: String,
+        #[serde(default)]
+        show_text: Option<bool>,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(flatten)]
+        options: BarcodeOptions,
+    },
+    Image {
+        file: String,
+    },
+    Rectangle(ShapeDefinition),
+    Ellipse(ShapeDefinition),
+    /// From (x, y) to (x + width, y + height)
+    Line(ShapeDefinition),
+}
+
+#[derive(Debug, Deserialize)]
+struct ShapeDefinition {
+    /// Outline width in mm
+    #[serde(default)]
+    stroke: Option<f64>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    fill: Option<String>,
+}
+
+impl ShapeDefinition {
+    fn into_shape(self, shape: ShapeKind) -> ShapeElement {
+        ShapeElement {
+            shape,
+            stroke_width_mm: self.stroke.unwrap_or(0.3),
+            stroke_color: self.color.unwrap_or_else(|| "#000000".to_string()),
+            fill_color: self.fill,
+        }
+    }
+}
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read label definition: {}", e))?;
+    compile(&text, path.parent().unwrap_or(Path::new("")), false)
+}
+
+/// Build the document a label definition describes; image files are looked
+/// up relative to `base_dir`. `from_network` refuses absolute image paths
+/// and ones leading out of `base_dir`.
+pub fn compile(text: &str, base_dir: &Path, from_network: bool) -> Result<Document, String> {
+    let definition: Definition = toml::from_str(text).map_err(|e| format!("Invalid label definition: {}", e))?;
+    let mut document = Document::new(definition.width, definition.height);
+    document.continuous_height = definition.continuous_height;
+
+    for element in definition.elements {
+        let kind = match element.kind {
+            KindDefinition::Text {
+                text,
+                font,
+                font_size,
+                bold,
+                italic,
+                color,
+                align,
+            } => {
+                let defaults = TextElement::default();
+                ElementKind::Text(TextElement {
+                    text,
+                    font_family: font.unwrap_or(defaults.font_family),
+                    font_size_pt: font_size.unwrap_or(defaults.font_size_pt),
+                    bold,
+                    italic,
+                    color: color.unwrap_or(defaults.color),
+                    align,
+                    ..defaults
+                })
+            }
+            KindDefinition::Barcode {
+                symbology,
+                data,
+                show_text,
+                color,
+                options,
+            } => ElementKind::Barcode(BarcodeElement {
+                symbology: super::normalize_symbology(&symbology),
+                data,
+                show_text: show_text.unwrap_or(true),
+                color: color.unwrap_or_else(|| "#000000".to_string()),
+                options,
+            }),
+            KindDefinition::Image { file } => {
+                let contained = Path::new(&file)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+                if from_network && !contained {
+                    return Err(format!("Image {} isn't allowed in a label definition sent over the network", file));
+                }
+                let path = base_dir.join(&file);
+                let bytes = fs::read(&path).map_err(|e| format!("Failed to read image {}: {}", path.display(), e))?;
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let asset_id = document.add_asset(&file_name, mime_type_for(&file_name), &bytes);
+                ElementKind::Image(ImageElement {
+                    asset_id,
+                    adjustments: None,
+                })
+            }
+            KindDefinition::Rectangle(shape) => ElementKind::Shape(shape.into_shape(ShapeKind::Rectangle)),
+            KindDefinition::Ellipse(shape) => ElementKind::Shape(shape.into_shape(ShapeKind::Ellipse)),
+            KindDefinition::Line(shape) => ElementKind::Shape(shape.into_shape(ShapeKind::Line)),
+        };
+        let mut compiled = Element::new(element.x, element.y, element.width, element.height, kind);
+        compiled.rotation_deg = element.rotation;
+        document.push(compiled);
+    }
+    Ok(document)
+}
+
+/// Compile label definition text from the editor, e.g. to preview it
+#[tauri::command]
+pub async fn compile_label_definition(text: String) -> Result<Document, String> {
+    compile(&text, Path::new(""), false)
+}
@@ -0,0 +1,271 @@
+// DYMO Label Software (DLS 8) .label files: XML with one <ObjectInfo> per
+// object, positioned in twips (1/1440 inch)
+use super::normalize_symbology;
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement,
+    ShapeKind, TextAlign, TextElement,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use roxmltree::Node;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+const MM_PER_TWIP: f64 = 25.4 / 1440.0;
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let xml = fs::read_to_string(path).map_err(|e| format!("Failed to read Dymo label file: {}", e))?;
+    parse(&xml)
+}
+
+pub fn parse(xml: &str) -> Result<Document, String> {
+    // DLS writes a UTF-8 BOM that roxmltree doesn't skip
+    let xml = xml.trim_start_matches('\u{feff}');
+    let xml_doc = roxmltree::Document::parse(xml)
+        .map_err(|e| format!("Failed to parse Dymo label XML: {}", e))?;
+    let root = xml_doc.root_element();
+
+    let continuous = match root.tag_name().name() {
+        "DieCutLabel" => false,
+        "ContinuousLabel" => true,
+        _ => return Err("Not a Dymo label document".to_string()),
+    };
+
+    // The label outline is drawn in portrait; landscape labels lay their
+    // objects out on the rotated outline
+    let outline = child(root, "DrawCommands")
+        .and_then(|d| d.children().find(|n| n.is_element()))
+        .and_then(|shape| Some((twips_attr(shape, "Width")?, twips_attr(shape, "Height")?)));
+    let landscape = child_text(root, "PaperOrientation").is_some_and(|o| o == "Landscape");
+
+    let objects: Vec<(Node, (f64, f64, f64, f64))> = root
+        .children()
+        .filter(|n| n.has_tag_name("ObjectInfo"))
+        .filter_map(|info| {
+            let object = info
+                .children()
+                .find(|n| n.is_element() && !n.has_tag_name("Bounds"))?;
+            let bounds = child(info, "Bounds")?;
+            Some((
+                object,
+                (
+                    twips_attr(bounds, "X")?,
+                    twips_attr(bounds, "Y")?,
+                    twips_attr(bounds, "Width")?,
+                    twips_attr(bounds, "Height")?,
+                ),
+            ))
+        })
+        .collect();
+
+    let (width, height) = match outline {
+        Some((w, h)) if landscape => (h, w),
+        Some((w, h)) => (w, h),
+        // Continuous labels have no outline, size them to fit their content
+        None => objects.iter().fold((0.0_f64, 0.0_f64), |(w, h), (_, (x, y, bw, bh))| {
+            (w.max(x + bw), h.max(y + bh))
+        }),
+    };
+    if width <= 0.0 || height <= 0.0 {
+        return Err("Dymo label has no size".to_string());
+    }
+
+    let mut document = Document::new(width, height);
+    if continuous {
+        if landscape {
+            document.continuous_width = true;
+        } else {
+            document.continuous_height = true;
+        }
+    }
+
+    for (object, (x, y, w, h)) in objects {
+        let kind = match object.tag_name().name() {
+            "TextObject" | "AddressObject" => ElementKind::Text(parse_text(object)),
+            "BarcodeObject" => ElementKind::Barcode(parse_barcode(object)),
+            "ImageObject" => match parse_image(object, &mut document) {
+                Some(image) => ElementKind::Image(image),
+                None => continue,
+            },
+            "ShapeObject" => ElementKind::Shape(parse_shape(object)),
+            other => {
+                warn!("Skipping unsupported Dymo object: {}", other);
+                continue;
+            }
+        };
+
+        let mut element = Element::new(x, y, w, h, kind);
+        element.rotation_deg = match child_text(object, "Rotation").as_deref() {
+            Some("Rotation90") => 90.0,
+            Some("Rotation180") => 180.0,
+            Some("Rotation270") => 270.0,
+            _ => 0.0,
+        };
+
+        // Lines are stored as a bounding box; draw them through its middle
+        if let ElementKind::Shape(shape) = &element.kind {
+            if shape.shape == ShapeKind::Line {
+                match child_text(object, "ShapeType").as_deref() {
+                    Some("VerticalLine") => {
+                        element.x_mm += element.width_mm / 2.0;
+                        element.width_mm = 0.0;
+                    }
+                    _ => {
+                        element.y_mm += element.height_mm / 2.0;
+                        element.height_mm = 0.0;
+                    }
+                }
+            }
+        }
+
+        document.push(element);
+    }
+
+    Ok(document)
+}
+
+fn parse_text(object: Node) -> TextElement {
+    let defaults = TextElement::default();
+    let mut element = TextElement {
+        color: child(object, "ForeColor")
+            .and_then(parse_color)
+            .unwrap_or(defaults.color.clone()),
+        align: match child_text(object, "HorizontalAlignment").as_deref() {
+            Some("Center") => TextAlign::Center,
+            Some("Right") => TextAlign::Right,
+            _ => TextAlign::Left,
+        },
+        ..defaults
+    };
+
+    // DLS 8 uses <StyledText> runs; older files hold a plain <Text> and <Font>
+    match child(object, "StyledText") {
+        Some(styled) => {
+            let mut font_set = false;
+            for run in styled.children().filter(|n| n.has_tag_name("Element")) {
+                element.text.push_str(&child_text(run, "String").unwrap_or_default());
+                // The first run decides the element's font
+                if !font_set {
+                    if let Some(attributes) = child(run, "Attributes") {
+                        if let Some(font) = child(attributes, "Font") {
+                            apply_font(&mut element, font);
+                            font_set = true;
+                        }
+                        if let Some(color) = child(attributes, "ForeColor").and_then(parse_color) {
+                            element.color = color;
+                        }
+                    }
+                }
+            }
+        }
+        None => {
+            element.text = child_text(object, "Text").unwrap_or_default();
+            if let Some(font) = child(object, "Font") {
+                apply_font(&mut element, font);
+            }
+        }
+    }
+
+    element.text = element.text.replace("\r\n", "\n");
+    element
+}
+
+fn apply_font(element: &mut TextElement, font: Node) {
+    if let Some(family) = font.attribute("Family") {
+        element.font_family = family.to_string();
+    }
+    if let Some(size) = font.attribute("Size").and_then(|s| s.parse().ok()) {
+        element.font_size_pt = size;
+    }
+    element.bold = font.attribute("Bold").is_some_and(is_true);
+    element.italic = font.attribute("Italic").is_some_and(is_true);
+}
+
+fn parse_barcode(object: Node) -> BarcodeElement {
+    BarcodeElement {
+        symbology: normalize_symbology(&child_text(object, "Type").unwrap_or_else(|| "Code128Auto".to_string())),
+        data: child_text(object, "Text").unwrap_or_default(),
+        show_text: child_text(object, "TextPosition").is_none_or(|p| p != "None"),
+        color: child(object, "ForeColor")
+            .and_then(parse_color)
+            .unwrap_or_else(|| "#000000".to_string()),
+        options: BarcodeOptions::default(),
+    }
+}
+
+fn parse_image(object: Node, document: &mut Document) -> Option<ImageElement> {
+    // Embedded images are base64 PNG in <Image> (DLS 8) or <ImageData>
+    let data = child_text(object, "Image").or_else(|| child_text(object, "ImageData"))?;
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if data.is_empty() {
+        warn!("Skipping Dymo image object without embedded data");
+        return None;
+    }
+    match BASE64.decode(data) {
+        Ok(bytes) => Some(ImageElement {
+            asset_id: document.add_asset(&format!("{}.png", object_name(object)), "image/png", &bytes),
+            adjustments: None,
+        }),
+        Err(e) => {
+            warn!("Skipping Dymo image with invalid data: {}", e);
+            None
+        }
+    }
+}
+
+fn parse_shape(object: Node) -> ShapeElement {
+    let shape = match child_text(object, "ShapeType").as_deref() {
+        Some("Ellipse") => ShapeKind::Ellipse,
+        Some("HorizontalLine") | Some("VerticalLine") => ShapeKind::Line,
+        _ => ShapeKind::Rectangle,
+    };
+    ShapeElement {
+        shape,
+        stroke_width_mm: child_text(object, "LineWidth")
+            .and_then(|w| w.trim().parse::<f64>().ok())
+            .map(|w| w * MM_PER_TWIP)
+            .unwrap_or(0.3),
+        stroke_color: child(object, "ForeColor")
+            .and_then(parse_color)
+            .unwrap_or_else(|| "#000000".to_string()),
+        fill_color: child(object, "FillColor").and_then(parse_color),
+    }
+}
+
+fn object_name(object: Node) -> String {
+    child_text(object, "Name")
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "image".to_string())
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name).map(|n| n.text().unwrap_or("").to_string())
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true")
+}
+
+fn twips_attr(node: Node, name: &str) -> Option<f64> {
+    let twips: f64 = node.attribute(name)?.trim().parse().ok()?;
+    Some(twips * MM_PER_TWIP)
+}
+
+// <ForeColor Alpha="255" Red="0" Green="0" Blue="0"/>; transparent is None
+fn parse_color(node: Node) -> Option<String> {
+    let channel = |name: &str| -> Option<u8> { node.attribute(name)?.trim().parse().ok() };
+    if channel("Alpha") == Some(0) {
+        return None;
+    }
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        channel("Red")?,
+        channel("Green")?,
+        channel("Blue")?
+    ))
+}
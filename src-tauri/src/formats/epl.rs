@@ -0,0 +1,404 @@
+// Zebra (Eltron) EPL2 programs, interpreted into a document like ZPL is in
+// `zpl`: one command per line, positions in printer dots. Covers text in the
+// resident fonts, linear and 2D barcodes, lines, boxes and graphics. Fields
+// filled from variables or counters are skipped, and the resident fonts are
+// approximated with a monospace one.
+use super::{content_size, height_with_text, zpl, MM_PER_PT};
+use crate::barcodes;
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement, ShapeKind, TextElement,
+};
+use crate::render::LINE_HEIGHT;
+use image::{GrayAlphaImage, ImageFormat, LumaA};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use tracing::warn;
+
+// Commands by their leading letters; anything else is a setting that
+// doesn't change what's printed
+const COMMANDS: &[&str] = &["GW", "LO", "LW", "LE", "LS", "A", "B", "b", "X", "q", "Q", "N", "P"];
+
+const BLACK: &str = "#000000";
+const WHITE: &str = "#ffffff";
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let epl = fs::read(path).map_err(|e| format!("Failed to read EPL file: {}", e))?;
+    parse(&epl, zpl::DEFAULT_DPMM)
+}
+
+/// The first label of `epl`
+pub fn parse(epl: &[u8], dpmm: f64) -> Result<Document, String> {
+    let mut labels = parse_all(epl, dpmm)?.into_iter();
+    let first = labels.next().ok_or("No EPL label found (nothing is printed with P)")?;
+    if labels.next().is_some() {
+        warn!("Only the first label of the EPL is read");
+    }
+    Ok(first)
+}
+
+/// Every label `epl` prints, one per P command
+pub fn parse_all(epl: &[u8], dpmm: f64) -> Result<Vec<Document>, String> {
+    if !dpmm.is_finite() || dpmm <= 0.0 {
+        return Err(format!("Invalid printer resolution: {} dots/mm", dpmm));
+    }
+    let mut labels = Vec::new();
+    let mut label = Label::new(dpmm);
+    let mut rest = epl;
+    while !rest.is_empty() {
+        // Graphic data is binary and may contain line breaks
+        if rest.starts_with(b"GW") {
+            rest = label.graphic(&rest[2..]);
+            continue;
+        }
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        let line = String::from_utf8_lossy(&rest[..end]).trim_end_matches('\r').to_string();
+        rest = rest.get(end + 1..).unwrap_or_default();
+        let Some(command) = COMMANDS.iter().find(|c| line.starts_with(**c)) else {
+            continue;
+        };
+        let params = &line[command.len()..];
+        match *command {
+            // Printing leaves the image buffer as it is for the next label
+            "P" if !label.document.elements.is_empty() => labels.push(label.finish()?),
+            "P" => {}
+            "N" => label.clear(),
+            command => label.command(command, params),
+        }
+    }
+    if !label.unsupported.is_empty() {
+        let names: Vec<&str> = label.unsupported.iter().map(String::as_str).collect();
+        warn!("Skipping unsupported EPL fields: {}", names.join(", "));
+    }
+    Ok(labels)
+}
+
+// Comma separated parameters, with quoted data unquoted and its \" and \\
+// escapes decoded. Unquoted parameters are trimmed.
+fn fields(params: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = params.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => field.extend(chars.next()),
+            ',' if !quoted => out.push(std::mem::take(&mut field)),
+            c if quoted => field.push(c),
+            c if !c.is_whitespace() => field.push(c),
+            _ => {}
+        }
+    }
+    out.push(field);
+    out
+}
+
+fn number(params: &[String], index: usize) -> Option<f64> {
+    params.get(index)?.parse().ok()
+}
+
+fn rotation(params: &[String], index: usize) -> f64 {
+    number(params, index).map_or(0.0, |r| (r as u32 % 4) as f64 * 90.0)
+}
+
+// Cell width and height in dots of the resident fonts 1-5, which are sized
+// for the printer's resolution
+fn font_cell(font: &str, dpmm: f64) -> (f64, f64) {
+    let cells = if dpmm > 10.0 {
+        [(12.0, 20.0), (16.0, 28.0), (20.0, 36.0), (24.0, 44.0), (48.0, 80.0)]
+    } else {
+        [(8.0, 12.0), (10.0, 16.0), (12.0, 20.0), (14.0, 24.0), (32.0, 48.0)]
+    };
+    let index = font.parse::<usize>().ok().filter(|f| (1..=5).contains(f)).unwrap_or(3);
+    cells[index - 1]
+}
+
+// Whether the data, the last of `params`, is missing or given by a
+// variable (V00) or counter (C0) instead of quoted text
+fn is_reference(params: &str) -> bool {
+    !params.trim_end().ends_with('"')
+}
+
+struct Label {
+    dpmm: f64,
+    document: Document,
+    /// q
+    width: Option<f64>,
+    /// Q
+    length: Option<f64>,
+    unsupported: BTreeSet<String>,
+}
+
+impl Label {
+    fn new(dpmm: f64) -> Self {
+        Label {
+            dpmm,
+            document: Document::new(0.0, 0.0),
+            width: None,
+            length: None,
+            unsupported: BTreeSet::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.document.elements.clear();
+        self.document.assets.clear();
+    }
+
+    fn command(&mut self, command: &str, params: &str) {
+        let p = &fields(params);
+        match command {
+            "q" => self.width = number(p, 0),
+            "Q" => self.length = number(p, 0).filter(|l| *l > 0.0),
+            "A" if is_reference(params) || p.len() < 8 => {
+                self.unsupported.insert("variable text".to_string());
+            }
+            "A" => self.text(p),
+            "B" if is_reference(params) || p.len() < 9 => {
+                self.unsupported.insert("variable barcodes".to_string());
+            }
+            "B" => self.linear(p),
+            "b" => self.matrix(p),
+            "LO" | "LE" => self.fill(p, BLACK),
+            "LW" => self.fill(p, WHITE),
+            "LS" => self.diagonal(p),
+            "X" => self.frame(p),
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, x: f64, y: f64, width: f64, height: f64, rotation: f64, kind: ElementKind) {
+        let mut element = Element::new(
+            x / self.dpmm,
+            y / self.dpmm,
+            width / self.dpmm,
+            height / self.dpmm,
+            kind,
+        );
+        element.rotation_deg = rotation;
+        self.document.push(element);
+    }
+
+    fn rectangle(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        let kind = ElementKind::Shape(ShapeElement {
+            shape: ShapeKind::Rectangle,
+            stroke_width_mm: 0.0,
+            stroke_color: color.to_string(),
+            fill_color: Some(color.to_string()),
+        });
+        self.push(x, y, width, height, 0.0, kind);
+    }
+
+    // A x,y,rotation,font,width multiplier,height multiplier,N|R,"data"
+    fn text(&mut self, p: &[String]) {
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        let (cell_width, cell_height) = font_cell(&p[3], self.dpmm);
+        let width = p[7].chars().count() as f64 * cell_width * number(p, 4).unwrap_or(1.0);
+        let height = cell_height * number(p, 5).unwrap_or(1.0);
+        // Reverse prints white on a black background
+        let reverse = p[6].eq_ignore_ascii_case("R");
+        let rotation = rotation(p, 2);
+        if reverse {
+            let kind = ElementKind::Shape(ShapeElement {
+                shape: ShapeKind::Rectangle,
+                stroke_width_mm: 0.0,
+                stroke_color: BLACK.to_string(),
+                fill_color: Some(BLACK.to_string()),
+            });
+            self.push(x, y, width, height, rotation, kind);
+        }
+        let text = TextElement {
+            text: p[7].clone(),
+            font_family: "monospace".to_string(),
+            font_size_pt: height / LINE_HEIGHT / self.dpmm / MM_PER_PT,
+            color: if reverse { WHITE } else { BLACK }.to_string(),
+            ..Default::default()
+        };
+        self.push(x, y, width, height, rotation, ElementKind::Text(text));
+    }
+
+    // B x,y,rotation,type,narrow bar,wide bar,height,B|N,"data"
+    fn linear(&mut self, p: &[String]) {
+        let symbology = match p[3].as_str() {
+            "0" | "1" | "1A" | "1B" | "1C" | "1E" => "code128",
+            "3" | "3C" => "code39",
+            "E30" => "ean13",
+            "E80" => "ean8",
+            "UA0" => "upca",
+            "UE0" => "upce",
+            "2" | "2C" | "2D" | "2G" => "itf",
+            other => {
+                self.unsupported.insert(format!("barcode type {}", other));
+                return;
+            }
+        };
+        let module = number(p, 4).unwrap_or(2.0);
+        let barcode = BarcodeElement {
+            symbology: symbology.to_string(),
+            data: p[8].clone(),
+            show_text: p[7].eq_ignore_ascii_case("B"),
+            color: BLACK.to_string(),
+            options: BarcodeOptions {
+                module_size_mm: Some(module / self.dpmm),
+                ..Default::default()
+            },
+        };
+        let symbol = match barcodes::encode(symbology, &barcode.data, &barcode.options) {
+            Ok(symbol) => symbol,
+            Err(e) => {
+                warn!("Skipping EPL {} barcode: {}", symbology, e);
+                return;
+            }
+        };
+        let bars = number(p, 6).unwrap_or(50.0);
+        let height = if barcode.show_text {
+            height_with_text(bars / self.dpmm) * self.dpmm
+        } else {
+            bars
+        };
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        let width = symbol.width as f64 * module;
+        self.push(x, y, width, height, rotation(p, 2), ElementKind::Barcode(barcode));
+    }
+
+    // b x,y,type,options...,"data", options being a letter and a value,
+    // e.g. s4 for a QR code with 4 dot modules
+    fn matrix(&mut self, p: &[String]) {
+        let Some((data, options)) = p.get(3..).and_then(|rest| rest.split_last()) else {
+            return;
+        };
+        let (symbology, module_option) = match p[2].as_str() {
+            "Q" => ("qr", 's'),
+            "D" => ("datamatrix", 'h'),
+            "P" => ("pdf417", 'x'),
+            other => {
+                self.unsupported.insert(format!("2D barcode type {}", other));
+                return;
+            }
+        };
+        let option = |letter: char| options.iter().find_map(|o| o.strip_prefix(letter));
+        let module = option(module_option).and_then(|m| m.parse().ok()).unwrap_or(3.0);
+        let barcode = BarcodeElement {
+            symbology: symbology.to_string(),
+            data: data.clone(),
+            show_text: false,
+            color: BLACK.to_string(),
+            options: BarcodeOptions {
+                module_size_mm: Some(module / self.dpmm),
+                error_correction: (symbology == "qr").then(|| option('e').map(str::to_string)).flatten(),
+                shape: None,
+            },
+        };
+        let symbol = match barcodes::encode(symbology, &barcode.data, &barcode.options) {
+            Ok(symbol) => symbol,
+            Err(e) => {
+                warn!("Skipping EPL {} barcode: {}", symbology, e);
+                return;
+            }
+        };
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        let (width, height) = (symbol.width as f64 * module, symbol.height as f64 * module);
+        self.push(x, y, width, height, 0.0, ElementKind::Barcode(barcode));
+    }
+
+    // LO/LW/LE x,y,width,height
+    fn fill(&mut self, p: &[String], color: &str) {
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        self.rectangle(x, y, number(p, 2).unwrap_or(1.0), number(p, 3).unwrap_or(1.0), color);
+    }
+
+    // LS x,y,thickness,end x,end y
+    fn diagonal(&mut self, p: &[String]) {
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        let (dx, dy) = (number(p, 3).unwrap_or(x) - x, number(p, 4).unwrap_or(y) - y);
+        let kind = ElementKind::Shape(ShapeElement {
+            shape: ShapeKind::Line,
+            stroke_width_mm: number(p, 2).unwrap_or(1.0) / self.dpmm,
+            stroke_color: BLACK.to_string(),
+            fill_color: None,
+        });
+        self.push(x, y, dx.hypot(dy), 0.0, dy.atan2(dx).to_degrees(), kind);
+    }
+
+    // X x,y,thickness,end x,end y: a box with lines `thickness` dots wide
+    // inside it
+    fn frame(&mut self, p: &[String]) {
+        let (x, y) = (number(p, 0).unwrap_or(0.0), number(p, 1).unwrap_or(0.0));
+        let thickness = number(p, 2).unwrap_or(1.0).max(1.0);
+        let (width, height) = (number(p, 3).unwrap_or(x) - x, number(p, 4).unwrap_or(y) - y);
+        if thickness * 2.0 >= width.min(height) {
+            self.rectangle(x, y, width.max(thickness), height.max(thickness), BLACK);
+            return;
+        }
+        let kind = ElementKind::Shape(ShapeElement {
+            shape: ShapeKind::Rectangle,
+            stroke_width_mm: thickness / self.dpmm,
+            stroke_color: BLACK.to_string(),
+            fill_color: None,
+        });
+        let inset = thickness / 2.0;
+        self.push(x + inset, y + inset, width - thickness, height - thickness, 0.0, kind);
+    }
+
+    // GW x,y,bytes per row,rows,<data>: a bitmap in which clear bits are
+    // black, eight pixels a byte. Returns what follows the data.
+    fn graphic<'a>(&mut self, rest: &'a [u8]) -> &'a [u8] {
+        let header_end = rest
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == b',')
+            .nth(3)
+            .map_or(rest.len(), |(i, _)| i + 1);
+        let p = fields(&String::from_utf8_lossy(&rest[..header_end]));
+        let (x, y) = (number(&p, 0).unwrap_or(0.0), number(&p, 1).unwrap_or(0.0));
+        let row_bytes = number(&p, 2).unwrap_or(0.0) as usize;
+        let rows = number(&p, 3).unwrap_or(0.0) as usize;
+        let data_end = (header_end + row_bytes * rows).min(rest.len());
+        let data = &rest[header_end..data_end];
+        let mut after = &rest[data_end..];
+        while let [b'\r' | b'\n', tail @ ..] = after {
+            after = tail;
+        }
+        if row_bytes == 0 || rows == 0 {
+            return after;
+        }
+
+        let mut bitmap = GrayAlphaImage::new(row_bytes as u32 * 8, rows as u32);
+        for (i, byte) in data.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) == 0 {
+                    bitmap.put_pixel(
+                        ((i % row_bytes) * 8 + bit) as u32,
+                        (i / row_bytes) as u32,
+                        LumaA([0, 255]),
+                    );
+                }
+            }
+        }
+        let mut png = Vec::new();
+        if let Err(e) = bitmap.write_to(&mut Cursor::new(&mut png), ImageFormat::Png) {
+            warn!("Skipping EPL graphic: {}", e);
+            return after;
+        }
+        let kind = ElementKind::Image(ImageElement {
+            asset_id: self.document.add_asset("graphic.png", "image/png", &png),
+            adjustments: None,
+        });
+        self.push(x, y, bitmap.width() as f64, bitmap.height() as f64, 0.0, kind);
+        after
+    }
+
+    // The image buffer as a label sized by q and Q, or to fit what's on it
+    fn finish(&self) -> Result<Document, String> {
+        let mut document = self.document.clone();
+        let (right, bottom) = content_size(&document);
+        document.width_mm = self.width.map_or(right, |w| w / self.dpmm);
+        document.height_mm = self.length.map_or(bottom, |l| l / self.dpmm);
+        if document.width_mm <= 0.0 || document.height_mm <= 0.0 {
+            return Err("The EPL label is empty and has no size".to_string());
+        }
+        Ok(document)
+    }
+}
@@ -0,0 +1,599 @@
+// glabels (https://glabels.org) project files: gzip-compressed XML holding the
+// label template (media geometry) plus the objects placed on the label
+use super::{escape, mime_type_for, normalize_symbology, MM_PER_PT};
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement,
+    ShapeKind, TextAlign, TextElement,
+};
+use crate::placeholders::{self, Segment, VariableKind};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use tracing::warn;
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read glabels file: {}", e))?;
+    let xml = decompress(&bytes)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    parse(&xml, base_dir)
+}
+
+// glabels writes gzip-compressed XML by default, but can also save plain XML
+fn decompress(bytes: &[u8]) -> Result<String, String> {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut xml = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut xml)
+            .map_err(|e| format!("Failed to decompress glabels file: {}", e))?;
+        Ok(xml)
+    } else {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| format!("glabels file is not valid UTF-8: {}", e))
+    }
+}
+
+/// Parse glabels XML; `base_dir` is used to resolve images referenced by file name
+pub fn parse(xml: &str, base_dir: &Path) -> Result<Document, String> {
+    let xml_doc = roxmltree::Document::parse(xml)
+        .map_err(|e| format!("Failed to parse glabels XML: {}", e))?;
+    let root = xml_doc.root_element();
+    if root.tag_name().name() != "Glabels-document" {
+        return Err("Not a glabels document".to_string());
+    }
+
+    let template = child(root, "Template").ok_or("glabels document has no label template")?;
+    let mut document = parse_template(template)?;
+    let embedded = embedded_files(root);
+
+    if let Some(objects) = child(root, "Objects") {
+        // Objects of a rotated layout are positioned in the rotated frame
+        if bool_attr(objects, "rotate") {
+            std::mem::swap(&mut document.width_mm, &mut document.height_mm);
+            std::mem::swap(&mut document.continuous_width, &mut document.continuous_height);
+        }
+
+        for node in objects.children().filter(|n| n.is_element()) {
+            if let Some(element) = parse_object(node, &embedded, base_dir, &mut document) {
+                document.push(element);
+            }
+        }
+    }
+
+    Ok(document)
+}
+
+fn parse_template(template: Node) -> Result<Document, String> {
+    let frame = template
+        .children()
+        .find(|n| n.is_element() && n.tag_name().name().starts_with("Label-"))
+        .ok_or("glabels template has no label frame")?;
+
+    let document = match frame.tag_name().name() {
+        "Label-round" | "Label-cd" => {
+            let radius = length_attr(frame, "r")
+                .or_else(|| length_attr(frame, "r1"))
+                .ok_or("glabels round label has no radius")?;
+            let width = length_attr(frame, "w").unwrap_or(radius * 2.0);
+            let height = length_attr(frame, "h").unwrap_or(radius * 2.0);
+            Document::new(width, height)
+        }
+        "Label-continuous" => {
+            let width = length_attr(frame, "width").ok_or("glabels continuous label has no width")?;
+            let height = length_attr(frame, "default_height")
+                .or_else(|| length_attr(frame, "height"))
+                .or_else(|| length_attr(frame, "min_height"))
+                .unwrap_or(width);
+            let mut document = Document::new(width, height);
+            document.continuous_height = true;
+            document
+        }
+        _ => {
+            let width = length_attr(frame, "width").ok_or("glabels label has no width")?;
+            let height = length_attr(frame, "height").ok_or("glabels label has no height")?;
+            Document::new(width, height)
+        }
+    };
+
+    Ok(document)
+}
+
+fn parse_object(
+    node: Node,
+    embedded: &HashMap<String, (String, Vec<u8>)>,
+    base_dir: &Path,
+    document: &mut Document,
+) -> Option<Element> {
+    let x = length_attr(node, "x").unwrap_or(0.0);
+    let y = length_attr(node, "y").unwrap_or(0.0);
+    let mut w = length_attr(node, "w").unwrap_or(0.0);
+    let mut h = length_attr(node, "h").unwrap_or(0.0);
+
+    let kind = match node.tag_name().name() {
+        "Object-text" => ElementKind::Text(parse_text(node)),
+        "Object-barcode" => ElementKind::Barcode(parse_barcode(node)),
+        "Object-image" => ElementKind::Image(parse_image(node, embedded, base_dir, document)?),
+        "Object-box" => ElementKind::Shape(parse_shape(node, ShapeKind::Rectangle)),
+        "Object-ellipse" => ElementKind::Shape(parse_shape(node, ShapeKind::Ellipse)),
+        "Object-line" => {
+            w = length_attr(node, "dx").unwrap_or(0.0);
+            h = length_attr(node, "dy").unwrap_or(0.0);
+            ElementKind::Shape(parse_shape(node, ShapeKind::Line))
+        }
+        other => {
+            warn!("Skipping unsupported glabels object: {}", other);
+            return None;
+        }
+    };
+
+    let mut element = Element::new(x, y, w, h, kind);
+
+    // Rotation is stored as an affine matrix [a0 a1 a2 a3 a4 a5]
+    if let (Some(a0), Some(a1)) = (number_attr(node, "a0"), number_attr(node, "a1")) {
+        element.rotation_deg = a1.atan2(a0).to_degrees();
+    }
+
+    Some(element)
+}
+
+fn parse_text(node: Node) -> TextElement {
+    // glabels 3 keeps text and font attributes on a <Span>, glabels 4 keeps
+    // font attributes on the object and text in <p> paragraphs
+    let span = node.descendants().find(|n| n.has_tag_name("Span"));
+    let font_attr = |name: &str| span.and_then(|s| s.attribute(name)).or(node.attribute(name));
+
+    let mut text = String::new();
+    match span {
+        Some(span) => collect_text(span, true, &mut text),
+        None => collect_text(node, false, &mut text),
+    }
+
+    let align = match node.attribute("align").or(node.attribute("justify")) {
+        Some(a) if a.eq_ignore_ascii_case("center") => TextAlign::Center,
+        Some(a) if a.eq_ignore_ascii_case("right") => TextAlign::Right,
+        _ => TextAlign::Left,
+    };
+
+    let defaults = TextElement::default();
+    TextElement {
+        text,
+        font_family: font_attr("font_family")
+            .map(str::to_string)
+            .unwrap_or(defaults.font_family),
+        font_size_pt: font_attr("font_size")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.font_size_pt),
+        bold: font_attr("font_weight").is_some_and(|w| w.eq_ignore_ascii_case("bold")),
+        italic: font_attr("font_italic").is_some_and(is_true),
+        color: font_attr("color")
+            .and_then(parse_color)
+            .unwrap_or(defaults.color),
+        align,
+        ..defaults
+    }
+}
+
+// Flatten glabels rich text into plain text, keeping line breaks and
+// turning merge fields into {field} placeholders
+fn collect_text(node: Node, include_text: bool, out: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            if include_text {
+                out.push_str(child.text().unwrap_or(""));
+            }
+            continue;
+        }
+        match child.tag_name().name() {
+            "NL" => out.push('\n'),
+            "p" => {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                collect_text(child, true, out);
+            }
+            "Field" => {
+                out.push_str(&format!("{{{}}}", child.attribute("name").unwrap_or("")));
+            }
+            _ => collect_text(child, include_text, out),
+        }
+    }
+}
+
+fn parse_barcode(node: Node) -> BarcodeElement {
+    let data = match node.attribute("data") {
+        Some(data) => data.to_string(),
+        None => child(node, "Field")
+            .and_then(|f| f.attribute("name"))
+            .map(|name| format!("{{{}}}", name))
+            .unwrap_or_default(),
+    };
+
+    BarcodeElement {
+        symbology: normalize_symbology(node.attribute("style").unwrap_or("code128")),
+        data,
+        show_text: node.attribute("text").map(is_true).unwrap_or(true),
+        color: node
+            .attribute("color")
+            .and_then(parse_color)
+            .unwrap_or_else(|| "#000000".to_string()),
+        options: BarcodeOptions::default(),
+    }
+}
+
+fn parse_image(
+    node: Node,
+    embedded: &HashMap<String, (String, Vec<u8>)>,
+    base_dir: &Path,
+    document: &mut Document,
+) -> Option<ImageElement> {
+    let src = node.attribute("src")?;
+
+    if let Some((mime_type, bytes)) = embedded.get(src) {
+        return Some(ImageElement {
+            asset_id: document.add_asset(src, mime_type, bytes),
+            adjustments: None,
+        });
+    }
+
+    match fs::read(base_dir.join(src)) {
+        Ok(bytes) => {
+            let file_name = Path::new(src)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| src.to_string());
+            Some(ImageElement {
+                asset_id: document.add_asset(&file_name, mime_type_for(src), &bytes),
+                adjustments: None,
+            })
+        }
+        Err(e) => {
+            warn!("Skipping glabels image {}: {}", src, e);
+            None
+        }
+    }
+}
+
+fn parse_shape(node: Node, shape: ShapeKind) -> ShapeElement {
+    ShapeElement {
+        shape,
+        stroke_width_mm: length_attr(node, "line_width").unwrap_or(0.3),
+        stroke_color: node
+            .attribute("line_color")
+            .and_then(parse_color)
+            .unwrap_or_else(|| "#000000".to_string()),
+        fill_color: node.attribute("fill_color").and_then(parse_color),
+    }
+}
+
+// Images embedded in the document's <Data> section, keyed by file name
+fn embedded_files(root: Node) -> HashMap<String, (String, Vec<u8>)> {
+    let mut files = HashMap::new();
+    let Some(data) = child(root, "Data") else {
+        return files;
+    };
+
+    for file in data.children().filter(|n| n.has_tag_name("File")) {
+        let (Some(name), Some(content)) = (file.attribute("name"), file.text()) else {
+            continue;
+        };
+        if file.attribute("encoding").is_some_and(|e| !e.eq_ignore_ascii_case("base64")) {
+            continue;
+        }
+        let mime_type = file
+            .attribute("mimetype")
+            .map(str::to_string)
+            .unwrap_or_else(|| mime_type_for(name).to_string());
+        let data: String = content.chars().filter(|c| !c.is_whitespace()).collect();
+        match BASE64.decode(data) {
+            Ok(bytes) => {
+                files.insert(name.to_string(), (mime_type, bytes));
+            }
+            Err(e) => warn!("Skipping invalid embedded glabels file {}: {}", name, e),
+        }
+    }
+
+    files
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true") || value == "1"
+}
+
+fn bool_attr(node: Node, name: &str) -> bool {
+    node.attribute(name).is_some_and(is_true)
+}
+
+fn number_attr(node: Node, name: &str) -> Option<f64> {
+    node.attribute(name)?.trim().parse().ok()
+}
+
+fn length_attr(node: Node, name: &str) -> Option<f64> {
+    parse_length(node.attribute(name)?)
+}
+
+/// Parse a glabels length ("12pt", "3.5mm", "1in") into millimetres.
+/// Bare numbers are points.
+pub fn parse_length(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.trim().parse().ok()?;
+
+    let mm = match unit.trim() {
+        "" | "pt" => number * MM_PER_PT,
+        "mm" => number,
+        "cm" => number * 10.0,
+        "in" => number * 25.4,
+        "pc" => number * 12.0 * MM_PER_PT,
+        _ => return None,
+    };
+    Some(mm)
+}
+
+/// Convert a glabels "0xRRGGBBAA" color to "#rrggbb"; fully transparent is None
+pub fn parse_color(value: &str) -> Option<String> {
+    let hex = value.trim().trim_start_matches("0x").trim_start_matches("0X");
+    let rgba = u32::from_str_radix(hex, 16).ok()?;
+    let (rgb, alpha) = if hex.len() > 6 {
+        (rgba >> 8, rgba & 0xff)
+    } else {
+        (rgba, 0xff)
+    };
+    if alpha == 0 {
+        return None;
+    }
+    Some(format!("#{:06x}", rgb))
+}
+
+/// Write a document as a gzip-compressed glabels file
+pub fn export(document: &Document, path: &Path) -> Result<(), String> {
+    let xml = to_xml(document);
+    let file = fs::File::create(path).map_err(|e| format!("Failed to create glabels file: {}", e))?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder
+        .write_all(xml.as_bytes())
+        .and_then(|_| encoder.finish().map(|_| ()))
+        .map_err(|e| format!("Failed to write glabels file: {}", e))
+}
+
+/// Serialize a document as glabels 3 compatible XML
+pub fn to_xml(document: &Document) -> String {
+    let width = format_length(document.width_mm);
+    let height = format_length(document.height_mm);
+    let mut files = Vec::new();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\"?>\n");
+    xml.push_str("<Glabels-document xmlns=\"http://glabels.org/xmlns/3.0/\">\n");
+    xml.push_str(&format!(
+        "  <Template brand=\"Labelgood\" part=\"Custom\" size=\"Other\" width=\"{w}\" height=\"{h}\" description=\"Labelgood export\">\n\
+         \x20   <Meta category=\"label\"/>\n\
+         \x20   <Label-rectangle id=\"0\" width=\"{w}\" height=\"{h}\" round=\"0pt\" x_waste=\"0pt\" y_waste=\"0pt\">\n\
+         \x20     <Markup-margin size=\"0pt\"/>\n\
+         \x20     <Layout nx=\"1\" ny=\"1\" x0=\"0pt\" y0=\"0pt\" dx=\"{w}\" dy=\"{h}\"/>\n\
+         \x20   </Label-rectangle>\n\
+         \x20 </Template>\n",
+        w = width,
+        h = height
+    ));
+    xml.push_str("  <Objects id=\"0\" rotate=\"False\">\n");
+
+    for element in &document.elements {
+        let mut attrs = format!(
+            "x=\"{}\" y=\"{}\"",
+            format_length(element.x_mm),
+            format_length(element.y_mm)
+        );
+        let size = format!(
+            " w=\"{}\" h=\"{}\"",
+            format_length(element.width_mm),
+            format_length(element.height_mm)
+        );
+        let affine = affine_attrs(element.rotation_deg);
+
+        match &element.kind {
+            ElementKind::Text(text) => {
+                let justify = match text.align {
+                    TextAlign::Left => "Left",
+                    TextAlign::Center => "Center",
+                    TextAlign::Right => "Right",
+                };
+                attrs.push_str(&size);
+                xml.push_str(&format!(
+                    "    <Object-text {} justify=\"{}\" valign=\"Top\" auto_shrink=\"False\" {}>\n",
+                    attrs, justify, affine
+                ));
+                xml.push_str(&format!(
+                    "      <Span color=\"{}\" font_family=\"{}\" font_size=\"{}\" font_weight=\"{}\" font_italic=\"{}\" line_spacing=\"1\">{}</Span>\n",
+                    format_color(Some(&text.color)),
+                    escape(&text.font_family),
+                    text.font_size_pt,
+                    if text.bold { "bold" } else { "normal" },
+                    if text.italic { "True" } else { "False" },
+                    span_content(&text.plain_text())
+                ));
+                xml.push_str("    </Object-text>\n");
+            }
+            ElementKind::Barcode(barcode) => {
+                let (backend, style) = barcode_style(&barcode.symbology);
+                attrs.push_str(&size);
+                xml.push_str(&format!(
+                    "    <Object-barcode {} backend=\"{}\" style=\"{}\" text=\"{}\" checksum=\"True\" color=\"{}\" data=\"{}\" {}/>\n",
+                    attrs,
+                    backend,
+                    style,
+                    if barcode.show_text { "True" } else { "False" },
+                    format_color(Some(&barcode.color)),
+                    escape(&barcode.data),
+                    affine
+                ));
+            }
+            ElementKind::Image(image) => {
+                let Some(asset) = document.assets.get(&image.asset_id) else {
+                    warn!("Skipping image with missing asset: {}", image.asset_id);
+                    continue;
+                };
+                let name = format!("image-{}.{}", image.asset_id, image_extension(&asset.mime_type));
+                attrs.push_str(&size);
+                xml.push_str(&format!(
+                    "    <Object-image {} src=\"{}\" {}/>\n",
+                    attrs,
+                    escape(&name),
+                    affine
+                ));
+                if !files.iter().any(|(n, _)| *n == name) {
+                    files.push((name, asset));
+                }
+            }
+            ElementKind::Nutrition(_) => {
+                warn!("Skipping nutrition panel, which gLabels has no object for");
+                continue;
+            }
+            ElementKind::Shape(shape) => {
+                let tag = match shape.shape {
+                    ShapeKind::Rectangle => "Object-box",
+                    ShapeKind::Ellipse => "Object-ellipse",
+                    ShapeKind::Line => "Object-line",
+                };
+                if shape.shape == ShapeKind::Line {
+                    attrs.push_str(&format!(
+                        " dx=\"{}\" dy=\"{}\"",
+                        format_length(element.width_mm),
+                        format_length(element.height_mm)
+                    ));
+                } else {
+                    attrs.push_str(&size);
+                    attrs.push_str(&format!(
+                        " fill_color=\"{}\"",
+                        format_color(shape.fill_color.as_deref())
+                    ));
+                }
+                xml.push_str(&format!(
+                    "    <{} {} line_width=\"{}\" line_color=\"{}\" {}/>\n",
+                    tag,
+                    attrs,
+                    format_length(shape.stroke_width_mm),
+                    format_color(Some(&shape.stroke_color)),
+                    affine
+                ));
+            }
+        }
+    }
+
+    xml.push_str("  </Objects>\n");
+
+    if !files.is_empty() {
+        xml.push_str("  <Data>\n");
+        for (name, asset) in files {
+            xml.push_str(&format!(
+                "    <File name=\"{}\" mimetype=\"{}\" encoding=\"base64\">{}</File>\n",
+                escape(&name),
+                escape(&asset.mime_type),
+                asset.data
+            ));
+        }
+        xml.push_str("  </Data>\n");
+    }
+
+    xml.push_str("</Glabels-document>\n");
+    xml
+}
+
+// glabels text with plain {field} placeholders turned back into <Field> tags.
+// glabels has no equivalent for filters, counters or date fields, so those
+// stay as literal text.
+fn span_content(text: &str) -> String {
+    let Ok(segments) = placeholders::parse(text) else {
+        return escape_lines(text);
+    };
+    let mut out = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => out.push_str(&escape_lines(&literal)),
+            Segment::Expression(expression)
+                if expression.filters.is_empty()
+                    && placeholders::variable_kind(&expression.variable) == VariableKind::Data =>
+            {
+                out.push_str(&format!("<Field name=\"{}\"/>", escape(&expression.variable)));
+            }
+            Segment::Expression(expression) => {
+                out.push_str(&escape_lines(&format!("{{{}}}", expression.source)));
+            }
+        }
+    }
+    out
+}
+
+fn escape_lines(text: &str) -> String {
+    text.split('\n').map(escape).collect::<Vec<_>>().join("<NL/>")
+}
+
+// glabels 3 backend and style ids for our symbology names
+fn barcode_style(symbology: &str) -> (&'static str, String) {
+    let style = match symbology {
+        "code128" => "Code128",
+        "code39" => "Code39",
+        "ean13" => "EAN-13",
+        "ean8" => "EAN-8",
+        "upca" => "UPC-A",
+        "upce" => "UPC-E",
+        "itf" | "itf14" => "I25",
+        "qr" => return ("qrencode", "QRcode".to_string()),
+        "datamatrix" => return ("iec16022", "IEC16022".to_string()),
+        "pdf417" => return ("zint", "PDF417".to_string()),
+        "aztec" => return ("zint", "Aztec".to_string()),
+        other => return ("built-in", escape(other)),
+    };
+    ("built-in", style.to_string())
+}
+
+fn affine_attrs(rotation_deg: f64) -> String {
+    let (sin, cos) = rotation_deg.to_radians().sin_cos();
+    // Adding 0.0 normalizes -0 so unrotated objects get a plain "0"
+    format!(
+        "a0=\"{}\" a1=\"{}\" a2=\"{}\" a3=\"{}\" a4=\"0\" a5=\"0\"",
+        cos,
+        sin,
+        -sin + 0.0,
+        cos
+    )
+}
+
+fn format_length(mm: f64) -> String {
+    format!("{:.4}pt", mm / MM_PER_PT)
+}
+
+// "#rrggbb" to glabels "0xRRGGBBAA"; None is fully transparent
+fn format_color(color: Option<&str>) -> String {
+    let rgb = color
+        .map(|c| c.trim_start_matches('#'))
+        .and_then(|c| c.get(..6))
+        .filter(|c| c.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|c| u32::from_str_radix(c, 16).ok());
+    match rgb {
+        Some(rgb) => format!("0x{:06x}ff", rgb),
+        None => "0x00000000".to_string(),
+    }
+}
+
+fn image_extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/svg+xml" => "svg",
+        _ => "png",
+    }
+}
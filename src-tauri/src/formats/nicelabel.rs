@@ -0,0 +1,343 @@
+// NiceLabel .nlbl files: a zip archive holding the label as XML plus the
+// images it uses, or the XML on its own as NiceLabel exports it. The schema
+// isn't published, so this is best effort: objects are found by their tag
+// names (TextObject, BarcodeObject, GraphicObject, ...) and their properties
+// read from attributes or child elements in any case. Lengths are in the
+// label's unit, micrometres unless it says otherwise. Text and barcodes fed
+// from a variable become placeholders for it, so the label merges as before.
+use super::{content_size, mime_type_for, normalize_symbology, MM_PER_PT};
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement, ShapeKind, TextAlign,
+    TextElement,
+};
+use roxmltree::Node;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use tracing::warn;
+use zip::ZipArchive;
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read NiceLabel file: {}", e))?;
+    let folder = path.parent().unwrap_or(Path::new("."));
+    if !bytes.starts_with(b"PK") {
+        let xml = String::from_utf8(bytes).map_err(|_| "NiceLabel file is not valid UTF-8")?;
+        return parse(&xml, |name| fs::read(folder.join(file_name(name))).ok());
+    }
+
+    let mut archive =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("NiceLabel file is not a valid archive: {}", e))?;
+    // The label is the largest XML document that isn't package metadata
+    let mut entries: Vec<(u64, String)> = (0..archive.len())
+        .filter_map(|i| {
+            let entry = archive.by_index(i).ok()?;
+            let name = entry.name().to_string();
+            let lower = name.to_ascii_lowercase();
+            (lower.ends_with(".xml") && !lower.ends_with("[content_types].xml")).then(|| (entry.size(), name))
+        })
+        .collect();
+    entries.sort_by(|a, b| b.0.cmp(&a.0));
+    let xml = entries
+        .first()
+        .and_then(|(_, name)| read_entry(&mut archive, name))
+        .ok_or("NiceLabel file has no label XML")
+        .and_then(|bytes| String::from_utf8(bytes).map_err(|_| "NiceLabel label XML is not valid UTF-8"))?;
+    parse(&xml, |name| {
+        let wanted = file_name(name).to_ascii_lowercase();
+        let entry = archive
+            .file_names()
+            .find(|entry| file_name(entry).to_ascii_lowercase() == wanted)?
+            .to_string();
+        read_entry(&mut archive, &entry).or_else(|| fs::read(folder.join(file_name(name))).ok())
+    })
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+// Last part of a path written with either separator, as Windows tools do
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+// What an object tag stands for
+#[derive(Clone, Copy)]
+enum Object {
+    Text,
+    Barcode,
+    Image,
+    Shape(ShapeKind),
+}
+
+fn object(tag: &str) -> Option<Object> {
+    let tag = tag.to_ascii_lowercase();
+    if !tag.ends_with("object") {
+        return None;
+    }
+    Some(if tag.contains("barcode") {
+        Object::Barcode
+    } else if tag.contains("text") || tag.contains("paragraph") {
+        Object::Text
+    } else if tag.contains("graphic") || tag.contains("picture") || tag.contains("image") {
+        Object::Image
+    } else if tag.contains("rectangle") || tag.contains("box") {
+        Object::Shape(ShapeKind::Rectangle)
+    } else if tag.contains("ellipse") || tag.contains("circle") {
+        Object::Shape(ShapeKind::Ellipse)
+    } else if tag.contains("line") {
+        Object::Shape(ShapeKind::Line)
+    } else {
+        return None;
+    })
+}
+
+/// Parse the label XML; `load_file` fetches images it refers to by name
+pub fn parse(xml: &str, mut load_file: impl FnMut(&str) -> Option<Vec<u8>>) -> Result<Document, String> {
+    let xml = xml.trim_start_matches('\u{feff}');
+    let xml_doc = roxmltree::Document::parse(xml).map_err(|e| format!("Failed to parse NiceLabel XML: {}", e))?;
+    let root = xml_doc.root_element();
+    let objects: Vec<(Node, Object)> = root
+        .descendants()
+        .filter_map(|node| Some((node, object(node.tag_name().name())?)))
+        .filter(|(node, _)| property(*node, &["X", "Left"]).is_some())
+        .collect();
+    if objects.is_empty() && !root.tag_name().name().to_ascii_lowercase().contains("label") {
+        return Err("Not a NiceLabel label document".to_string());
+    }
+
+    let label = root.descendants().find(|node| {
+        let tag = node.tag_name().name().to_ascii_lowercase();
+        (tag.contains("label") || tag.contains("page") || tag.contains("dimension"))
+            && object(&tag).is_none()
+            && property(*node, &["Width"]).is_some()
+            && property(*node, &["Height"]).is_some()
+    });
+    let raw_width = label.and_then(|node| number(property(node, &["Width"])?));
+    let unit = unit(root, raw_width);
+    let length = |node: Node, names: &[&str]| property(node, names).and_then(|value| to_mm(value, unit));
+
+    let variables: BTreeSet<String> = root
+        .descendants()
+        .filter(|node| node.tag_name().name().eq_ignore_ascii_case("variable"))
+        .filter_map(|node| property(node, &["Name"]).map(str::to_string))
+        .collect();
+
+    let mut document = Document::new(0.0, 0.0);
+    for (node, object) in objects {
+        let x = length(node, &["X", "Left"]).unwrap_or(0.0);
+        let y = length(node, &["Y", "Top"]).unwrap_or(0.0);
+        let width = length(node, &["Width"]).unwrap_or(0.0);
+        let height = length(node, &["Height"]).unwrap_or(0.0);
+        let kind = match object {
+            Object::Text => ElementKind::Text(parse_text(node, &variables, unit)),
+            Object::Barcode => ElementKind::Barcode(parse_barcode(node, &variables)),
+            Object::Image => match parse_image(node, &mut load_file, &mut document) {
+                Some(image) => ElementKind::Image(image),
+                None => continue,
+            },
+            Object::Shape(shape) => ElementKind::Shape(parse_shape(node, shape, unit)),
+        };
+        let mut element = Element::new(x, y, width, height, kind);
+        element.rotation_deg = property(node, &["Rotation", "Angle"]).and_then(number).unwrap_or(0.0);
+        document.push(element);
+    }
+
+    let (right, bottom) = content_size(&document);
+    document.width_mm = label
+        .and_then(|node| length(node, &["Width"]))
+        .filter(|w| *w > 0.0)
+        .unwrap_or(right);
+    document.height_mm = label
+        .and_then(|node| length(node, &["Height"]))
+        .filter(|h| *h > 0.0)
+        .unwrap_or(bottom);
+    if document.width_mm <= 0.0 || document.height_mm <= 0.0 {
+        return Err("NiceLabel label has no size and no objects".to_string());
+    }
+    Ok(document)
+}
+
+// Millimetres per unit of bare lengths: as the label names it, otherwise
+// micrometres for label widths too large to be millimetres
+fn unit(root: Node, label_width: Option<f64>) -> f64 {
+    let named = root
+        .descendants()
+        .find_map(|node| property(node, &["Unit", "MeasurementUnit", "Units"]))
+        .map(|unit| unit.to_ascii_lowercase());
+    match named.as_deref() {
+        Some(u) if u.starts_with("inch") || u == "in" => 25.4,
+        Some(u) if u.starts_with("point") || u == "pt" => MM_PER_PT,
+        Some(u) if u.starts_with("cent") || u == "cm" => 10.0,
+        Some(u) if u.starts_with("milli") || u == "mm" => 1.0,
+        _ if label_width.is_some_and(|w| w <= 1000.0) => 1.0,
+        _ => 0.001,
+    }
+}
+
+fn number(value: &str) -> Option<f64> {
+    value.trim().replace(',', ".").parse().ok()
+}
+
+// A length with its unit, or a bare one in `unit` mm
+fn to_mm(value: &str, unit: f64) -> Option<f64> {
+    let value = value.trim().to_ascii_lowercase();
+    for (suffix, mm) in [
+        ("mm", 1.0),
+        ("cm", 10.0),
+        ("in", 25.4),
+        ("pt", MM_PER_PT),
+        ("um", 0.001),
+        ("µm", 0.001),
+    ] {
+        if let Some(length) = value.strip_suffix(suffix) {
+            return number(length).map(|length| length * mm);
+        }
+    }
+    number(&value).map(|length| length * unit)
+}
+
+// An attribute or child element of `node` with one of `names`, in any case;
+// children of children are searched too, e.g. for <Font><Name>, but not
+// nested objects
+fn property<'a>(node: Node<'a, '_>, names: &[&str]) -> Option<&'a str> {
+    let matches = |name: &str| names.iter().any(|wanted| wanted.eq_ignore_ascii_case(name));
+    if let Some(attribute) = node.attributes().find(|a| matches(a.name())) {
+        return Some(attribute.value());
+    }
+    let children: Vec<Node> = node
+        .children()
+        .filter(|child| child.is_element() && object(child.tag_name().name()).is_none())
+        .collect();
+    children
+        .iter()
+        .find(|child| matches(child.tag_name().name()))
+        .map(|child| child.text().unwrap_or("").trim())
+        .or_else(|| {
+            children
+                .iter()
+                .flat_map(|child| child.children())
+                .find(|grandchild| grandchild.is_element() && matches(grandchild.tag_name().name()))
+                .map(|grandchild| grandchild.text().unwrap_or("").trim())
+        })
+}
+
+// What an object prints: the variable it's fed from as a placeholder, or
+// its text with "[Variable]" references turned into placeholders
+fn content(node: Node, variables: &BTreeSet<String>) -> String {
+    if let Some(variable) = property(node, &["Variable", "VariableName", "DataSource"]).filter(|v| !v.is_empty()) {
+        return format!("{{{}}}", variable);
+    }
+    let text = property(node, &["Text", "Value", "Content", "Data", "Caption"]).unwrap_or("");
+    let text = text.replace('{', "{{").replace('}', "}}");
+    variables.iter().fold(text, |text, variable| {
+        text.replace(&format!("[{}]", variable), &format!("{{{}}}", variable))
+    })
+}
+
+fn color(node: Node, names: &[&str]) -> Option<String> {
+    property(node, names)
+        .and_then(|color| color.strip_prefix('#'))
+        .filter(|hex| matches!(hex.len(), 3 | 6 | 8) && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(|hex| match hex.len() {
+            // #AARRGGBB
+            8 => format!("#{}", &hex[2..]).to_ascii_lowercase(),
+            _ => format!("#{}", hex).to_ascii_lowercase(),
+        })
+}
+
+fn parse_text(node: Node, variables: &BTreeSet<String>, unit: f64) -> TextElement {
+    let defaults = TextElement::default();
+    // Font properties are on the object or in a <Font> of their own
+    let font = node
+        .children()
+        .find(|child| child.tag_name().name().eq_ignore_ascii_case("font"));
+    let font_property = |names: &[&str]| property(node, names).or_else(|| property(font?, names));
+    let is_set = |names: &[&str]| font_property(names).is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1");
+    let style = font_property(&["FontStyle", "Style"])
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    TextElement {
+        text: content(node, variables).replace("\r\n", "\n").replace('\r', "\n"),
+        font_family: property(node, &["FontName", "FontFamily"])
+            .or_else(|| property(font?, &["Name", "Family"]))
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .unwrap_or(defaults.font_family),
+        font_size_pt: font_property(&["FontSize", "Size"])
+            .and_then(|size| {
+                // Points unless a unit says otherwise
+                if size.trim().ends_with(|c: char| c.is_ascii_digit()) {
+                    number(size)
+                } else {
+                    to_mm(size, unit).map(|mm| mm / MM_PER_PT)
+                }
+            })
+            .filter(|size| *size > 0.0)
+            .unwrap_or(defaults.font_size_pt),
+        bold: is_set(&["Bold", "IsBold"]) || style.contains("bold"),
+        italic: is_set(&["Italic", "IsItalic"]) || style.contains("italic"),
+        color: color(node, &["Color", "FontColor", "ForeColor"]).unwrap_or(defaults.color),
+        align: match property(node, &["Alignment", "HorizontalAlignment", "TextAlignment"])
+            .unwrap_or("")
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "center" | "centre" | "middle" => TextAlign::Center,
+            "right" => TextAlign::Right,
+            _ => TextAlign::Left,
+        },
+        ..defaults
+    }
+}
+
+fn parse_barcode(node: Node, variables: &BTreeSet<String>) -> BarcodeElement {
+    let readable = property(node, &["HumanReadable", "ShowText", "Interpretation"]).unwrap_or("true");
+    BarcodeElement {
+        symbology: normalize_symbology(property(node, &["BarcodeType", "Symbology", "Type"]).unwrap_or("Code128")),
+        data: content(node, variables),
+        show_text: !matches!(
+            readable.to_ascii_lowercase().as_str(),
+            "false" | "0" | "none" | "no" | "hidden"
+        ),
+        color: color(node, &["Color", "ForeColor"]).unwrap_or_else(|| "#000000".to_string()),
+        options: BarcodeOptions::default(),
+    }
+}
+
+fn parse_image(
+    node: Node,
+    load_file: &mut impl FnMut(&str) -> Option<Vec<u8>>,
+    document: &mut Document,
+) -> Option<ImageElement> {
+    let Some(path) = property(node, &["FileName", "File", "ImagePath", "Path"]).filter(|p| !p.is_empty()) else {
+        warn!("Skipping NiceLabel graphic without a file name");
+        return None;
+    };
+    let name = file_name(path);
+    match load_file(path) {
+        Some(bytes) => Some(ImageElement {
+            asset_id: document.add_asset(name, mime_type_for(name), &bytes),
+            adjustments: None,
+        }),
+        None => {
+            warn!("Skipping NiceLabel graphic that can't be found: {}", path);
+            None
+        }
+    }
+}
+
+fn parse_shape(node: Node, shape: ShapeKind, unit: f64) -> ShapeElement {
+    ShapeElement {
+        shape,
+        stroke_width_mm: property(node, &["Thickness", "LineWidth", "StrokeWidth"])
+            .and_then(|width| to_mm(width, unit))
+            .unwrap_or(0.3),
+        stroke_color: color(node, &["Color", "LineColor", "ForeColor"]).unwrap_or_else(|| "#000000".to_string()),
+        fill_color: color(node, &["FillColor", "BackColor"]),
+    }
+}
@@ -0,0 +1,226 @@
+// Brother P-touch Editor .lbx files: a zip archive holding label.xml plus the
+// image files it references
+use super::{mime_type_for, normalize_symbology, MM_PER_PT};
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement,
+    ShapeKind, TextAlign, TextElement,
+};
+use roxmltree::Node;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use tracing::warn;
+use zip::ZipArchive;
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open P-touch file: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("P-touch file is not a valid archive: {}", e))?;
+
+    let xml = read_entry(&mut archive, "label.xml")
+        .ok_or("P-touch file has no label.xml")
+        .and_then(|bytes| String::from_utf8(bytes).map_err(|_| "P-touch label.xml is not valid UTF-8"))?;
+
+    parse(&xml, |name| read_entry(&mut archive, name))
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Parse label.xml; `load_file` fetches images stored next to it in the archive
+pub fn parse(
+    xml: &str,
+    mut load_file: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<Document, String> {
+    let xml_doc = roxmltree::Document::parse(xml)
+        .map_err(|e| format!("Failed to parse P-touch label XML: {}", e))?;
+    let root = xml_doc.root_element();
+    if root.tag_name().name() != "document" {
+        return Err("Not a P-touch label document".to_string());
+    }
+
+    // Only the current (first) sheet is imported
+    let sheet = root
+        .descendants()
+        .find(|n| n.has_tag_name("sheet"))
+        .ok_or("P-touch document has no sheet")?;
+    let paper = child(sheet, "paper").ok_or("P-touch sheet has no paper definition")?;
+
+    // Paper size is given for the tape in portrait; landscape designs put
+    // the tape length along x
+    let tape_width = length_attr(paper, "width").ok_or("P-touch paper has no width")?;
+    let tape_length = length_attr(paper, "height").ok_or("P-touch paper has no height")?;
+    let landscape = paper.attribute("orientation") == Some("landscape");
+    let auto_length = paper.attribute("autoLength") == Some("true");
+
+    let mut document = if landscape {
+        Document::new(tape_length, tape_width)
+    } else {
+        Document::new(tape_width, tape_length)
+    };
+    if auto_length {
+        if landscape {
+            document.continuous_width = true;
+        } else {
+            document.continuous_height = true;
+        }
+    }
+
+    let Some(objects) = child(sheet, "objects") else {
+        return Ok(document);
+    };
+
+    for object in objects.children().filter(|n| n.is_element()) {
+        let Some(style) = child(object, "objectStyle") else {
+            continue;
+        };
+        let x = length_attr(style, "x").unwrap_or(0.0);
+        let y = length_attr(style, "y").unwrap_or(0.0);
+        let w = length_attr(style, "width").unwrap_or(0.0);
+        let h = length_attr(style, "height").unwrap_or(0.0);
+
+        let kind = match object.tag_name().name() {
+            "text" => ElementKind::Text(parse_text(object)),
+            "barcode" => ElementKind::Barcode(parse_barcode(object)),
+            "image" => match parse_image(object, &mut load_file, &mut document) {
+                Some(image) => ElementKind::Image(image),
+                None => continue,
+            },
+            "rectangle" | "frame" => ElementKind::Shape(parse_shape(style, ShapeKind::Rectangle)),
+            "ellipse" => ElementKind::Shape(parse_shape(style, ShapeKind::Ellipse)),
+            "poly" => ElementKind::Shape(parse_shape(style, ShapeKind::Line)),
+            other => {
+                warn!("Skipping unsupported P-touch object: {}", other);
+                continue;
+            }
+        };
+
+        let mut element = Element::new(x, y, w, h, kind);
+        element.rotation_deg = style
+            .attribute("angle")
+            .and_then(|a| a.parse().ok())
+            .unwrap_or(0.0);
+        document.push(element);
+    }
+
+    // Auto-length tape carries a placeholder maximum length; trim it to the
+    // content plus the trailing margin like P-touch Editor does when printing
+    if auto_length && !document.elements.is_empty() {
+        let margin_attr = if landscape { "marginRight" } else { "marginBottom" };
+        let margin = length_attr(paper, margin_attr).unwrap_or(0.0);
+        let extent = document
+            .elements
+            .iter()
+            .map(|e| if landscape { e.x_mm + e.width_mm } else { e.y_mm + e.height_mm })
+            .fold(0.0, f64::max);
+        if landscape {
+            document.width_mm = extent + margin;
+        } else {
+            document.height_mm = extent + margin;
+        }
+    }
+
+    Ok(document)
+}
+
+fn parse_text(object: Node) -> TextElement {
+    let defaults = TextElement::default();
+    let font_info = child(object, "ptFontInfo");
+    let log_font = font_info.and_then(|f| child(f, "logFont"));
+    let font_ext = font_info.and_then(|f| child(f, "fontExt"));
+
+    TextElement {
+        text: data_text(object).replace("\r\n", "\n").replace('\r', "\n"),
+        font_family: log_font
+            .and_then(|f| f.attribute("name"))
+            .map(str::to_string)
+            .unwrap_or(defaults.font_family),
+        font_size_pt: font_ext
+            .and_then(|f| length_attr(f, "size"))
+            .map(|mm| (mm / MM_PER_PT * 100.0).round() / 100.0)
+            .unwrap_or(defaults.font_size_pt),
+        bold: log_font
+            .and_then(|f| f.attribute("weight"))
+            .and_then(|w| w.parse::<u32>().ok())
+            .is_some_and(|w| w >= 700),
+        italic: log_font.and_then(|f| f.attribute("italic")) == Some("true"),
+        color: font_ext
+            .and_then(|f| f.attribute("textColor"))
+            .map(|c| c.to_ascii_lowercase())
+            .unwrap_or(defaults.color),
+        align: match child(object, "textAlign").and_then(|a| a.attribute("horizontalAlignment")) {
+            Some("CENTER") => TextAlign::Center,
+            Some("RIGHT") => TextAlign::Right,
+            _ => TextAlign::Left,
+        },
+        ..defaults
+    }
+}
+
+fn parse_barcode(object: Node) -> BarcodeElement {
+    let style = child(object, "barcodeStyle");
+    BarcodeElement {
+        symbology: normalize_symbology(style.and_then(|s| s.attribute("protocol")).unwrap_or("CODE128")),
+        data: data_text(object),
+        show_text: style.and_then(|s| s.attribute("humanReadable")) != Some("false"),
+        color: "#000000".to_string(),
+        options: BarcodeOptions::default(),
+    }
+}
+
+fn parse_image(
+    object: Node,
+    load_file: &mut impl FnMut(&str) -> Option<Vec<u8>>,
+    document: &mut Document,
+) -> Option<ImageElement> {
+    let file_name = child(object, "imageStyle")?.attribute("fileName")?;
+    match load_file(file_name) {
+        Some(bytes) => Some(ImageElement {
+            asset_id: document.add_asset(file_name, mime_type_for(file_name), &bytes),
+            adjustments: None,
+        }),
+        None => {
+            warn!("Skipping P-touch image missing from archive: {}", file_name);
+            None
+        }
+    }
+}
+
+fn parse_shape(style: Node, shape: ShapeKind) -> ShapeElement {
+    let pen = child(style, "pen");
+    let brush = child(style, "brush");
+    ShapeElement {
+        shape,
+        stroke_width_mm: pen.and_then(|p| length_attr(p, "widthX")).unwrap_or(0.3),
+        stroke_color: pen
+            .and_then(|p| p.attribute("color"))
+            .map(|c| c.to_ascii_lowercase())
+            .unwrap_or_else(|| "#000000".to_string()),
+        fill_color: brush
+            .filter(|b| b.attribute("style") != Some("NULL"))
+            .and_then(|b| b.attribute("color"))
+            .map(|c| c.to_ascii_lowercase()),
+    }
+}
+
+fn data_text(object: Node) -> String {
+    child(object, "data")
+        .and_then(|d| d.text())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+// P-touch lengths are always written in points ("70.9pt")
+fn length_attr(node: Node, name: &str) -> Option<f64> {
+    let value = node.attribute(name)?.trim();
+    let points: f64 = value.strip_suffix("pt").unwrap_or(value).trim().parse().ok()?;
+    Some(points * MM_PER_PT)
+}
@@ -0,0 +1,730 @@
+// Zebra ZPL II, interpreted into a document so pasted or generated ZPL can be
+// previewed without a printer. Positions are in printer dots; `dpmm` converts
+// them to millimetres. Covers what label generators commonly emit: field
+// origins, fonts and field blocks, the common barcodes, boxes, circles,
+// diagonal lines, graphic fields and the EPC an RFID printer encodes. Only
+// the first label is read, and Zebra's fonts are approximated with installed
+// ones.
+use super::{content_size, height_with_text, rotate, MM_PER_PT};
+use crate::barcodes;
+use crate::document::{
+    BarcodeElement, BarcodeOptions, Document, Element, ElementKind, ImageElement, ShapeElement, ShapeKind, TextAlign,
+    TextElement,
+};
+use crate::render::{ASCENT, LINE_HEIGHT};
+use crate::rfid::{RfidFailure, RfidTag};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use image::{GrayAlphaImage, ImageFormat, LumaA};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use tracing::warn;
+
+/// Dots per millimetre of a 203 dpi printer, the most common resolution
+pub const DEFAULT_DPMM: f64 = 8.0;
+
+// Setup and status commands that don't change what's printed
+const IGNORED: &[&str] = &[
+    "CI", "CC", "CD", "CT", "FX", "JM", "JU", "JZ", "LR", "LS", "MD", "MM", "MN", "MT", "MU", "PM", "PO", "PQ", "PR",
+    "XB",
+];
+
+pub fn import(path: &Path) -> Result<Document, String> {
+    let zpl = fs::read(path).map_err(|e| format!("Failed to read ZPL file: {}", e))?;
+    parse(&String::from_utf8_lossy(&zpl), DEFAULT_DPMM)
+}
+
+/// The first label of `zpl`
+pub fn parse(zpl: &str, dpmm: f64) -> Result<Document, String> {
+    let mut labels = parse_all(zpl, dpmm)?.into_iter();
+    let first = labels.next().ok_or("No ZPL label found (^XA ... ^XZ)")?;
+    if labels.next().is_some() {
+        warn!("Only the first label of the ZPL is read");
+    }
+    Ok(first)
+}
+
+/// Every label of `zpl`, one per ^XA ... ^XZ
+pub fn parse_all(zpl: &str, dpmm: f64) -> Result<Vec<Document>, String> {
+    if !dpmm.is_finite() || dpmm <= 0.0 {
+        return Err(format!("Invalid printer resolution: {} dots/mm", dpmm));
+    }
+    let mut labels = Vec::new();
+    let mut label: Option<Label> = None;
+    for (command, params) in commands(zpl) {
+        match command.as_str() {
+            "XA" => label = Some(Label::new(dpmm)),
+            // Formats that only change settings, e.g. ^XA^MNY^XZ, print nothing
+            "XZ" => match label.take() {
+                Some(label) if label.document.elements.is_empty() => {}
+                Some(label) => labels.push(label.finish()?),
+                None => {}
+            },
+            _ => {
+                if let Some(label) = label.as_mut() {
+                    label.command(&command, &params);
+                }
+            }
+        }
+    }
+    Ok(labels)
+}
+
+// Commands in order as (name, parameters), e.g. ("FO", "50,30"); tilde
+// commands keep their tilde. The font command ^A takes one letter, the font
+// name being the first character of its parameters. Line breaks are not
+// part of ZPL and dropped.
+fn commands(zpl: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut rest = zpl;
+    while let Some(start) = rest.find(['^', '~']) {
+        let tilde = rest[start..].starts_with('~');
+        rest = &rest[start + 1..];
+        let name_len = if !tilde && rest.starts_with(['A', 'a']) { 1 } else { 2 };
+        let Some(name) = rest.get(..name_len) else {
+            break;
+        };
+        let name = name.to_ascii_uppercase();
+        rest = &rest[name_len..];
+        // Field and graphic data may contain tildes; only a caret ends them
+        let end = if !tilde && matches!(name.as_str(), "FD" | "FV" | "FX" | "GF") {
+            rest.find('^')
+        } else {
+            rest.find(['^', '~'])
+        }
+        .unwrap_or(rest.len());
+        let params = rest[..end].chars().filter(|c| !matches!(c, '\r' | '\n')).collect();
+        out.push((if tilde { format!("~{}", name) } else { name }, params));
+        rest = &rest[end..];
+    }
+    out
+}
+
+fn param<'a>(params: &[&'a str], index: usize) -> Option<&'a str> {
+    params.get(index).map(|p| p.trim()).filter(|p| !p.is_empty())
+}
+
+fn number(params: &[&str], index: usize) -> Option<f64> {
+    param(params, index)?.parse().ok()
+}
+
+fn flag(params: &[&str], index: usize, default: bool) -> bool {
+    param(params, index).map_or(default, |p| p.eq_ignore_ascii_case("Y"))
+}
+
+// Rotation in degrees of a field orientation: Normal, Rotated 90 degrees,
+// Inverted or read from Bottom up
+fn orientation(params: &[&str], index: usize, default: f64) -> f64 {
+    match param(params, index).map(|p| p.to_ascii_uppercase()).as_deref() {
+        Some("N") => 0.0,
+        Some("R") => 90.0,
+        Some("I") => 180.0,
+        Some("B") => 270.0,
+        _ => default,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Font {
+    name: char,
+    height: f64,
+    width: f64,
+}
+
+impl Font {
+    // Scalable font 0 and downloaded fonts are proportional, bitmap fonts
+    // A-H have fixed width cells
+    fn scalable(&self) -> bool {
+        self.name == '@' || self.name.is_ascii_digit()
+    }
+
+    // Average advance of a character in dots, for sizing unbounded text
+    fn advance(&self) -> f64 {
+        if self.scalable() {
+            self.width * 0.55
+        } else {
+            self.width
+        }
+    }
+}
+
+struct FieldBlock {
+    width: f64,
+    lines: usize,
+    align: TextAlign,
+}
+
+struct Barcode {
+    symbology: &'static str,
+    rotation: f64,
+    /// Bar height of linear codes
+    height: Option<f64>,
+    show_text: bool,
+    /// Module size of 2D codes
+    module: Option<f64>,
+    error_correction: Option<String>,
+    shape: Option<String>,
+}
+
+// What the commands since the last ^FS said about the next field
+#[derive(Default)]
+struct Field {
+    origin: Option<(f64, f64)>,
+    /// ^FT: the origin is the baseline (bottom left) instead of the top left
+    baseline: bool,
+    font: Option<(Font, f64)>,
+    block: Option<FieldBlock>,
+    barcode: Option<Barcode>,
+    hex_indicator: Option<u8>,
+    reverse: bool,
+    /// ^RF: the data is written to the RFID tag, not printed
+    rfid: bool,
+    data: Option<String>,
+}
+
+struct Label {
+    dpmm: f64,
+    document: Document,
+    home: (f64, f64),
+    /// ^CF
+    font: Font,
+    /// ^FW
+    rotation: f64,
+    /// ^BY module width and bar height
+    module_width: f64,
+    bar_height: f64,
+    width: Option<f64>,
+    length: Option<f64>,
+    field: Field,
+    unsupported: BTreeSet<String>,
+}
+
+impl Label {
+    fn new(dpmm: f64) -> Self {
+        Label {
+            dpmm,
+            document: Document::new(0.0, 0.0),
+            home: (0.0, 0.0),
+            font: Font {
+                name: 'A',
+                height: 9.0,
+                width: 5.0,
+            },
+            rotation: 0.0,
+            module_width: 2.0,
+            bar_height: 10.0,
+            width: None,
+            length: None,
+            field: Field::default(),
+            unsupported: BTreeSet::new(),
+        }
+    }
+
+    fn command(&mut self, command: &str, params: &str) {
+        let p: Vec<&str> = params.split(',').collect();
+        match command {
+            "LH" => self.home = (number(&p, 0).unwrap_or(0.0), number(&p, 1).unwrap_or(0.0)),
+            "PW" => self.width = number(&p, 0),
+            "LL" => self.length = number(&p, 0),
+            "FW" => self.rotation = orientation(&p, 0, self.rotation),
+            "FO" | "FT" => {
+                let (x, y) = (number(&p, 0).unwrap_or(0.0), number(&p, 1).unwrap_or(0.0));
+                self.field.origin = Some((x + self.home.0, y + self.home.1));
+                self.field.baseline = command == "FT";
+            }
+            "A" => {
+                let name = params.chars().next().unwrap_or('0').to_ascii_uppercase();
+                let p: Vec<&str> = params.get(name.len_utf8()..).unwrap_or_default().split(',').collect();
+                let font = self.font_with(name, number(&p, 1), number(&p, 2));
+                self.field.font = Some((font, orientation(&p, 0, self.rotation)));
+            }
+            "CF" => {
+                let name = param(&p, 0).and_then(|f| f.chars().next()).unwrap_or(self.font.name);
+                self.font = self.font_with(name.to_ascii_uppercase(), number(&p, 1), number(&p, 2));
+            }
+            "FB" => {
+                self.field.block = Some(FieldBlock {
+                    width: number(&p, 0).unwrap_or(0.0),
+                    lines: number(&p, 1).unwrap_or(1.0).max(1.0) as usize,
+                    align: match param(&p, 3).map(|j| j.to_ascii_uppercase()).as_deref() {
+                        Some("C") => TextAlign::Center,
+                        Some("R") => TextAlign::Right,
+                        _ => TextAlign::Left,
+                    },
+                })
+            }
+            "FH" => self.field.hex_indicator = Some(param(&p, 0).map_or(b'_', |c| c.as_bytes()[0])),
+            "FR" => self.field.reverse = true,
+            "FD" | "FV" => self.field.data = Some(params.to_string()),
+            "RS" => {
+                let tag = self.document.rfid.get_or_insert_with(RfidTag::default);
+                tag.retries = number(&p, 3).map_or(tag.retries, |n| n.clamp(1.0, 10.0) as u8);
+                tag.on_failure = match param(&p, 4).map(|e| e.to_ascii_uppercase()).as_deref() {
+                    Some("P") => RfidFailure::Pause,
+                    Some("E") => RfidFailure::Error,
+                    _ => RfidFailure::Void,
+                };
+            }
+            // Only hex writes to the EPC bank are read; reads, passwords
+            // and locks don't change the label
+            "RF" => {
+                let write = param(&p, 0).is_some_and(|o| o.eq_ignore_ascii_case("W"));
+                if write && param(&p, 1).is_some_and(|f| f.eq_ignore_ascii_case("H")) {
+                    self.field.rfid = true;
+                } else {
+                    self.unsupported.insert(format!("^RF{}", params));
+                }
+            }
+            "FS" => self.end_field(),
+            "BY" => {
+                self.module_width = number(&p, 0).unwrap_or(self.module_width);
+                self.bar_height = number(&p, 2).unwrap_or(self.bar_height);
+            }
+            "BC" => self.linear("code128", &p, 1, 2),
+            "BE" => self.linear("ean13", &p, 1, 2),
+            "B8" => self.linear("ean8", &p, 1, 2),
+            "BU" => self.linear("upca", &p, 1, 2),
+            "B9" => self.linear("upce", &p, 1, 2),
+            "B2" => self.linear("itf", &p, 1, 2),
+            "B3" => self.linear("code39", &p, 2, 3),
+            "BQ" => {
+                // Magnification defaults to what suits the resolution: 2 at
+                // 203 dpi, 3 at 300 dpi
+                let magnification = number(&p, 2).unwrap_or((self.dpmm / 4.0).round().max(1.0));
+                self.matrix("qr", &p, Some(magnification), None);
+            }
+            "BX" => {
+                let shape = (param(&p, 7) == Some("2")).then(|| "rectangle".to_string());
+                self.matrix("datamatrix", &p, number(&p, 1), shape);
+            }
+            "B7" => self.matrix("pdf417", &p, None, None),
+            "BO" | "B0" => self.matrix("aztec", &p, number(&p, 1), None),
+            "GB" => {
+                let thickness = number(&p, 2).unwrap_or(1.0);
+                let (width, height) = (number(&p, 0).unwrap_or(thickness), number(&p, 1).unwrap_or(thickness));
+                self.graphic_box(ShapeKind::Rectangle, width, height, thickness, param(&p, 3));
+            }
+            "GC" => {
+                let diameter = number(&p, 0).unwrap_or(3.0);
+                self.graphic_box(
+                    ShapeKind::Ellipse,
+                    diameter,
+                    diameter,
+                    number(&p, 1).unwrap_or(1.0),
+                    param(&p, 2),
+                );
+            }
+            "GE" => {
+                let thickness = number(&p, 2).unwrap_or(1.0);
+                let (width, height) = (number(&p, 0).unwrap_or(thickness), number(&p, 1).unwrap_or(thickness));
+                self.graphic_box(ShapeKind::Ellipse, width, height, thickness, param(&p, 3));
+            }
+            "GD" => self.diagonal(&p),
+            "GF" => self.graphic_field(params),
+            command if IGNORED.contains(&command) || command.starts_with('~') => {}
+            command => {
+                self.unsupported.insert(format!("^{}", command));
+            }
+        }
+    }
+
+    // A font of `name`, `height` and `width` dots. Either size alone scales
+    // the other with it.
+    fn font_with(&self, name: char, height: Option<f64>, width: Option<f64>) -> Font {
+        let ratio = if name == self.font.name {
+            self.font.width / self.font.height
+        } else {
+            1.0
+        };
+        let height = height.or(width.map(|w| w / ratio)).unwrap_or(self.font.height);
+        Font {
+            name,
+            height,
+            width: width.unwrap_or(height * ratio),
+        }
+    }
+
+    fn linear(&mut self, symbology: &'static str, p: &[&str], height: usize, text: usize) {
+        self.field.barcode = Some(Barcode {
+            symbology,
+            rotation: orientation(p, 0, self.rotation),
+            height: number(p, height),
+            show_text: flag(p, text, true),
+            module: None,
+            error_correction: None,
+            shape: None,
+        });
+    }
+
+    fn matrix(&mut self, symbology: &'static str, p: &[&str], module: Option<f64>, shape: Option<String>) {
+        self.field.barcode = Some(Barcode {
+            symbology,
+            rotation: orientation(p, 0, self.rotation),
+            height: None,
+            show_text: false,
+            module,
+            // PDF417 security level
+            error_correction: (symbology == "pdf417")
+                .then(|| param(p, 2).map(str::to_string))
+                .flatten(),
+            shape,
+        });
+    }
+
+    // Where the field starts, in dots
+    fn origin(&self) -> (f64, f64) {
+        self.field.origin.unwrap_or(self.home)
+    }
+
+    // An element `width` x `height` dots turned by `rotation` at the field
+    // origin. ^FO places the top left of what's printed there whatever the
+    // rotation; ^FT places the start of the baseline, `baseline` dots below
+    // the top of the unrotated field.
+    fn element(&self, width: f64, height: f64, baseline: f64, rotation: f64, kind: ElementKind) -> Element {
+        let (x, y) = self.origin();
+        let (dx, dy) = if self.field.baseline {
+            rotate((0.0, -baseline), rotation)
+        } else {
+            match rotation as u32 {
+                90 => (height, 0.0),
+                180 => (width, height),
+                270 => (0.0, width),
+                _ => (0.0, 0.0),
+            }
+        };
+        let mut element = Element::new(
+            (x + dx) / self.dpmm,
+            (y + dy) / self.dpmm,
+            width / self.dpmm,
+            height / self.dpmm,
+            kind,
+        );
+        element.rotation_deg = rotation;
+        element
+    }
+
+    fn end_field(&mut self) {
+        if let Some(data) = self.field.data.take() {
+            let data = match self.field.hex_indicator {
+                Some(indicator) => unhex(&data, indicator),
+                None => data,
+            };
+            if self.field.rfid {
+                let tag = self.document.rfid.get_or_insert_with(RfidTag::default);
+                tag.epc = data.trim().to_ascii_uppercase();
+            } else {
+                match self.field.barcode.take() {
+                    Some(barcode) => self.barcode(barcode, data),
+                    None => self.text(data),
+                }
+            }
+        }
+        self.field = Field::default();
+    }
+
+    fn text(&mut self, data: String) {
+        let (font, rotation) = self.field.font.unwrap_or((self.font, self.rotation));
+        let (lines, width, align) = match &self.field.block {
+            Some(block) => {
+                let max_chars = (block.width / font.advance()).floor().max(1.0) as usize;
+                let mut lines: Vec<String> = data
+                    .split("\\&")
+                    .flat_map(|line| crate::render::wrap(line, max_chars))
+                    .collect();
+                lines.truncate(block.lines);
+                (lines, block.width, block.align)
+            }
+            None => {
+                let width = data.chars().count() as f64 * font.advance();
+                (vec![data], width, TextAlign::Left)
+            }
+        };
+        if width <= 0.0 || lines.is_empty() {
+            return;
+        }
+        let text = TextElement {
+            text: lines.join("\n"),
+            font_family: if font.scalable() { "Arial" } else { "monospace" }.to_string(),
+            font_size_pt: font.height / self.dpmm / MM_PER_PT,
+            // Font 0 is a bold condensed face
+            bold: font.name == '0',
+            // Reverse printing is mostly white text on a black box
+            color: color(self.field.reverse),
+            align,
+            ..Default::default()
+        };
+        let height = lines.len() as f64 * font.height * LINE_HEIGHT;
+        let element = self.element(width, height, font.height * ASCENT, rotation, ElementKind::Text(text));
+        self.document.push(element);
+    }
+
+    fn barcode(&mut self, barcode: Barcode, data: String) {
+        let (data, error_correction) = match barcode.symbology {
+            "code128" => (code128_data(&data), None),
+            // "QA,data": error correction level and input mode, then the data
+            "qr" => match data.split_once(',') {
+                Some((mode, text)) if mode.len() <= 2 => (text.to_string(), mode.get(..1).map(str::to_string)),
+                _ => (data, None),
+            },
+            _ => (data, barcode.error_correction),
+        };
+        let module = barcode.module.unwrap_or(self.module_width);
+        let element = BarcodeElement {
+            symbology: barcode.symbology.to_string(),
+            data,
+            show_text: barcode.show_text,
+            color: color(self.field.reverse),
+            options: BarcodeOptions {
+                module_size_mm: Some(module / self.dpmm),
+                error_correction,
+                shape: barcode.shape,
+            },
+        };
+        let symbol = match barcodes::encode(&element.symbology, &element.data, &element.options) {
+            Ok(symbol) => symbol,
+            Err(e) => {
+                warn!("Skipping ZPL {} barcode: {}", element.symbology, e);
+                return;
+            }
+        };
+        let width = symbol.width as f64 * module;
+        let (height, bars) = if symbol.is_linear() {
+            let bars = barcode.height.unwrap_or(self.bar_height);
+            let height = if element.show_text {
+                height_with_text(bars / self.dpmm) * self.dpmm
+            } else {
+                bars
+            };
+            (height, bars)
+        } else {
+            let height = symbol.height as f64 * module;
+            (height, height)
+        };
+        let element = self.element(width, height, bars, barcode.rotation, ElementKind::Barcode(element));
+        self.document.push(element);
+    }
+
+    // A box or ellipse outlined `thickness` dots thick inside `width` x
+    // `height`, solid when the outline fills it
+    fn graphic_box(&mut self, shape: ShapeKind, width: f64, height: f64, thickness: f64, color: Option<&str>) {
+        let color = color(color.is_some_and(|c| c.eq_ignore_ascii_case("W")) != self.field.reverse);
+        let thickness = thickness.max(1.0);
+        let (width, height) = (width.max(thickness), height.max(thickness));
+        let solid = thickness * 2.0 >= width.min(height);
+        let kind = ElementKind::Shape(ShapeElement {
+            shape,
+            stroke_width_mm: if solid { 0.0 } else { thickness / self.dpmm },
+            stroke_color: color.clone(),
+            fill_color: solid.then_some(color),
+        });
+        let mut element = self.element(width, height, height, 0.0, kind);
+        if !solid {
+            // ZPL draws the outline inside the box, SVG centred on its edge
+            let inset = thickness / self.dpmm;
+            element.x_mm += inset / 2.0;
+            element.y_mm += inset / 2.0;
+            element.width_mm -= inset;
+            element.height_mm -= inset;
+        }
+        self.document.push(element);
+    }
+
+    // ^GD: a line across a `width` x `height` box, leaning right (/) or
+    // left (\)
+    fn diagonal(&mut self, p: &[&str]) {
+        let thickness = number(p, 2).unwrap_or(1.0).max(1.0);
+        let (width, height) = (number(p, 0).unwrap_or(thickness), number(p, 1).unwrap_or(thickness));
+        let color = color(param(p, 3).is_some_and(|c| c.eq_ignore_ascii_case("W")) != self.field.reverse);
+        let left = param(p, 4).is_some_and(|o| o.eq_ignore_ascii_case("L"));
+        let kind = ElementKind::Shape(ShapeElement {
+            shape: ShapeKind::Line,
+            stroke_width_mm: thickness / self.dpmm,
+            stroke_color: color,
+            fill_color: None,
+        });
+        // A horizontal line from the left end, turned down (\) or up (/)
+        let mut element = self.element(width, height, height, 0.0, kind);
+        let angle = height.atan2(width).to_degrees();
+        if !left {
+            element.y_mm += element.height_mm;
+        }
+        element.width_mm = width.hypot(height) / self.dpmm;
+        element.height_mm = 0.0;
+        element.rotation_deg = if left { angle } else { -angle };
+        self.document.push(element);
+    }
+
+    // ^GF: a bitmap of set (black) and clear bits, eight pixels a byte
+    fn graphic_field(&mut self, params: &str) {
+        let p: Vec<&str> = params.splitn(5, ',').collect();
+        let (Some(total), Some(row_bytes)) = (number(&p, 2), number(&p, 3)) else {
+            warn!("Skipping ZPL graphic field without its size");
+            return;
+        };
+        let (total, row_bytes) = (total as usize, row_bytes as usize);
+        if row_bytes == 0 {
+            return;
+        }
+        let data = p.get(4).copied().unwrap_or_default();
+        let bytes = match param(&p, 0).map(|c| c.to_ascii_uppercase()).as_deref() {
+            Some("A") | None => graphic_bytes(data, row_bytes),
+            Some(other) => Err(format!("binary graphic fields (type {}) aren't supported", other)),
+        };
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping ZPL graphic field: {}", e);
+                return;
+            }
+        };
+        let rows = total.div_ceil(row_bytes);
+        let mut bitmap = GrayAlphaImage::new(row_bytes as u32 * 8, rows as u32);
+        for (i, byte) in bytes.iter().take(rows * row_bytes).enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    bitmap.put_pixel(
+                        ((i % row_bytes) * 8 + bit) as u32,
+                        (i / row_bytes) as u32,
+                        LumaA([0, 255]),
+                    );
+                }
+            }
+        }
+        let mut png = Vec::new();
+        if let Err(e) = bitmap.write_to(&mut Cursor::new(&mut png), ImageFormat::Png) {
+            warn!("Skipping ZPL graphic field: {}", e);
+            return;
+        }
+        let kind = ElementKind::Image(ImageElement {
+            asset_id: self.document.add_asset("graphic.png", "image/png", &png),
+            adjustments: None,
+        });
+        let (width, height) = (bitmap.width() as f64, bitmap.height() as f64);
+        let element = self.element(width, height, height, 0.0, kind);
+        self.document.push(element);
+    }
+
+    // The document sized by ^PW and ^LL, or to fit what's on it
+    fn finish(mut self) -> Result<Document, String> {
+        if !self.unsupported.is_empty() {
+            let names: Vec<&str> = self.unsupported.iter().map(String::as_str).collect();
+            warn!("Skipping unsupported ZPL commands: {}", names.join(", "));
+        }
+        let (right, bottom) = content_size(&self.document);
+        self.document.width_mm = self.width.map_or(right, |w| w / self.dpmm);
+        self.document.height_mm = self.length.map_or(bottom, |l| l / self.dpmm);
+        if self.document.width_mm <= 0.0 || self.document.height_mm <= 0.0 {
+            return Err("The ZPL label is empty and has no size".to_string());
+        }
+        Ok(self.document)
+    }
+}
+
+fn color(white: bool) -> String {
+    if white { "#ffffff" } else { "#000000" }.to_string()
+}
+
+// Field data with `indicator` hex escapes decoded, e.g. "_C3_A9" for "é"
+fn unhex(data: &str, indicator: u8) -> String {
+    let bytes = data.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == indicator)
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Code 128 data without the ">" invocation codes that pick subsets and FNC
+// characters; the encoder chooses subsets itself
+fn code128_data(data: &str) -> String {
+    let mut out = String::with_capacity(data.len());
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '>' && chars.peek().is_some_and(|n| matches!(n, '1'..='9' | ':' | ';')) {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Bytes of an ASCII graphic field: hex digits compressed with repeat counts
+// (G-Y for 1-19, g-z for 20-400), "," to clear and "!" to set the rest of a
+// row and ":" to repeat the previous one, or base64 as ":B64:" or
+// zlib-compressed as ":Z64:", followed by a CRC
+fn graphic_bytes(data: &str, row_bytes: usize) -> Result<Vec<u8>, String> {
+    let data = data.trim();
+    for (prefix, compressed) in [(":B64:", false), (":Z64:", true)] {
+        if let Some(encoded) = data.strip_prefix(prefix) {
+            let encoded = encoded.split(':').next().unwrap_or_default();
+            let bytes = BASE64.decode(encoded).map_err(|e| format!("invalid base64: {}", e))?;
+            if !compressed {
+                return Ok(bytes);
+            }
+            let mut out = Vec::new();
+            ZlibDecoder::new(&bytes[..])
+                .read_to_end(&mut out)
+                .map_err(|e| format!("invalid compressed data: {}", e))?;
+            return Ok(out);
+        }
+    }
+
+    let row_digits = row_bytes * 2;
+    let mut rows: Vec<Vec<u8>> = Vec::new();
+    let mut row: Vec<u8> = Vec::with_capacity(row_digits);
+    let mut count = 0;
+    let mut end_row = |row: &mut Vec<u8>, fill: u8, rows: &mut Vec<Vec<u8>>| {
+        row.resize(row_digits, fill);
+        rows.push(std::mem::replace(row, Vec::with_capacity(row_digits)));
+    };
+    for c in data.chars() {
+        match c {
+            'G'..='Y' => count += c as usize - 'G' as usize + 1,
+            'g'..='z' => count += (c as usize - 'g' as usize + 1) * 20,
+            ',' => end_row(&mut row, 0, &mut rows),
+            '!' => end_row(&mut row, 0xf, &mut rows),
+            ':' => {
+                let previous = rows.last().cloned().unwrap_or_else(|| vec![0; row_digits]);
+                rows.push(previous);
+            }
+            c => {
+                let Some(digit) = c.to_digit(16) else {
+                    continue;
+                };
+                for _ in 0..count.max(1) {
+                    row.push(digit as u8);
+                    if row.len() == row_digits {
+                        end_row(&mut row, 0, &mut rows);
+                    }
+                }
+                count = 0;
+            }
+        }
+    }
+    if !row.is_empty() {
+        end_row(&mut row, 0, &mut rows);
+    }
+    Ok(rows
+        .concat()
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).unwrap_or(&0))
+        .collect())
+}
@@ -0,0 +1,484 @@
+// Zebra ZPL II templates compiled from a document, for Zebra printers and
+// legacy systems that fill in and send raw ZPL themselves. Text, barcodes and
+// shapes become native fields; merge placeholders become numbered ^FN fields
+// of a format stored on the printer (recalled with ^XF), or stay as `{name}`
+// in the field data for systems that substitute them in the text. Images,
+// nutrition tables and anything ZPL can't draw natively are rasterized into
+// graphic fields, which only works for elements without placeholders. An
+// RFID tag becomes ^RS and ^RF, its EPC a field like any other, so RFID
+// printers encode each label in the same format that prints it.
+use super::{rotate, MM_PER_PT};
+use crate::barcodes;
+use crate::document::{
+    BarcodeElement, Document, Element, ElementKind, ShapeElement, ShapeKind, TextAlign, TextElement, TextLayout,
+};
+use crate::placeholders;
+use crate::printing::RENDER_DPI;
+use crate::raster;
+use crate::render::{self, LINE_HEIGHT};
+use crate::rfid::{self, ZPL_WRITE_EPC};
+use image::GrayImage;
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ZplTemplateOptions {
+    /// Resolution of the printers the template is for
+    pub dpi: u32,
+    /// Leave placeholders as `{name}` in the field data instead of making a
+    /// stored format with ^FN fields
+    pub placeholders: bool,
+    /// Name the format is stored under on the printer's E: drive
+    pub name: String,
+}
+
+impl Default for ZplTemplateOptions {
+    fn default() -> Self {
+        ZplTemplateOptions {
+            dpi: 203,
+            placeholders: false,
+            name: "LABEL".to_string(),
+        }
+    }
+}
+
+// Compiles elements into ZPL fields, numbering those with placeholders
+struct Writer<'a> {
+    dpmm: f64,
+    options: &'a ZplTemplateOptions,
+    fonts: Arc<fontdb::Database>,
+    document: &'a Document,
+    fields: Vec<String>,
+    out: String,
+}
+
+/// Write `document` as a ZPL template
+pub fn export(
+    document: &Document,
+    fonts: Arc<fontdb::Database>,
+    options: &ZplTemplateOptions,
+    path: &Path,
+) -> Result<(), String> {
+    let zpl = to_zpl(document, fonts, options)?;
+    fs::write(path, zpl).map_err(|e| format!("Failed to write ZPL file: {}", e))
+}
+
+/// `document` as a ZPL template, one command per line
+pub fn to_zpl(
+    document: &Document,
+    fonts: Arc<fontdb::Database>,
+    options: &ZplTemplateOptions,
+) -> Result<String, String> {
+    if options.dpi == 0 {
+        return Err("Resolution must be above 0 dpi".to_string());
+    }
+    let name = format_name(&options.name)?;
+    let document = document.printable();
+    let mut writer = Writer {
+        dpmm: options.dpi as f64 / 25.4,
+        options,
+        fonts,
+        document: &document,
+        fields: Vec::new(),
+        out: String::new(),
+    };
+    for element in document.stacked() {
+        if !element.rules.is_empty() {
+            warn!("Rules of element {} aren't exported to ZPL", element.id);
+        }
+        writer.element(element)?;
+    }
+    if let Some(tag) = &document.rfid {
+        if placeholders::variables(&tag.epc).is_empty() {
+            rfid::check_epc(&tag.epc)?;
+        }
+        writer.out.push_str(&tag.zpl_setup());
+        writer.out.push('\n');
+        writer.out.push_str(ZPL_WRITE_EPC);
+        writer.data(&tag.epc);
+    }
+
+    let mut zpl = String::from("^XA\n");
+    let _ = writeln!(
+        zpl,
+        "^FXLabelgood template, {} x {} mm at {} dpi",
+        document.width_mm, document.height_mm, options.dpi
+    );
+    for (i, data) in writer.fields.iter().enumerate() {
+        let _ = writeln!(zpl, "^FXFN{} = {}", i + 1, comment(data));
+    }
+    if !options.placeholders {
+        let _ = writeln!(zpl, "^FXPrint with ^XA^XFE:{}.ZPL^FS^FN1^FDvalue^FS...^XZ", name);
+        let _ = writeln!(zpl, "^DFE:{}.ZPL^FS", name);
+    }
+    zpl.push_str("^CI28\n");
+    let _ = writeln!(zpl, "^PW{}", writer.dots(document.width_mm));
+    let _ = writeln!(zpl, "^LL{}", writer.dots(document.height_mm));
+    zpl.push_str("^LH0,0\n");
+    zpl.push_str(&writer.out);
+    zpl.push_str("^XZ\n");
+    Ok(zpl)
+}
+
+// Stored format names are up to 8 characters of letters, digits and `_`
+fn format_name(name: &str) -> Result<String, String> {
+    let name = name.trim().trim_end_matches(".ZPL").trim_end_matches(".zpl");
+    if name.is_empty() || name.len() > 8 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "Invalid format name \"{}\": use up to 8 letters, digits or underscores",
+            name
+        ));
+    }
+    Ok(name.to_ascii_uppercase())
+}
+
+// Field data with the characters ZPL treats as commands written as ^FH hex
+// escapes, and line breaks as ^FB's
+fn field_data(text: &str) -> String {
+    text.replace('_', "_5F")
+        .replace('^', "_5E")
+        .replace('~', "_7E")
+        .replace("\r\n", "\n")
+        .replace('\n', "\\&")
+}
+
+// `text` safe to put in a ^FX comment or ^FN prompt
+fn comment(text: &str) -> String {
+    text.replace(['^', '~', '"'], "").replace('\n', " / ")
+}
+
+// ZPL's orientation letter for a rotation, if it's a quarter turn
+fn orientation(degrees: f64) -> Option<char> {
+    let quarters = (degrees / 90.0).round();
+    if (degrees - quarters * 90.0).abs() > 0.5 {
+        return None;
+    }
+    Some(['N', 'R', 'I', 'B'][(quarters as i64).rem_euclid(4) as usize])
+}
+
+// Height in mm of the bars of a linear barcode whose element, text included,
+// is `height` mm tall; the inverse of `height_with_text`
+fn bar_height(height: f64) -> f64 {
+    if height * 0.2 <= 3.0 {
+        height * (1.0 - 0.2 * LINE_HEIGHT)
+    } else {
+        height - 3.0 * LINE_HEIGHT
+    }
+}
+
+// Whether a colour is light enough to print as white
+fn is_light(color: &str) -> bool {
+    let hex = color.trim().trim_start_matches('#');
+    let channel = |i: usize, len: usize| {
+        hex.get(i * len..i * len + len)
+            .and_then(|c| u8::from_str_radix(&c.repeat(3 - len), 16).ok())
+    };
+    let len = match hex.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return color.eq_ignore_ascii_case("white"),
+    };
+    match (channel(0, len), channel(1, len), channel(2, len)) {
+        (Some(r), Some(g), Some(b)) => 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64 >= 128.0,
+        _ => false,
+    }
+}
+
+impl Writer<'_> {
+    fn dots(&self, mm: f64) -> i64 {
+        (mm * self.dpmm).round() as i64
+    }
+
+    // Top left corner and size of the element's bounding box, rotated
+    fn bounds(&self, element: &Element) -> (f64, f64, f64, f64) {
+        let (mut left, mut top, mut right, mut bottom) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for corner in [
+            (element.width_mm, 0.0),
+            (0.0, element.height_mm),
+            (element.width_mm, element.height_mm),
+        ] {
+            let (dx, dy) = rotate(corner, element.rotation_deg);
+            left = left.min(dx);
+            top = top.min(dy);
+            right = right.max(dx);
+            bottom = bottom.max(dy);
+        }
+        (element.x_mm + left, element.y_mm + top, right - left, bottom - top)
+    }
+
+    fn origin(&mut self, element: &Element) {
+        let (x, y, _, _) = self.bounds(element);
+        let _ = write!(self.out, "^FO{},{}", self.dots(x), self.dots(y));
+    }
+
+    // End a field with `data`: a numbered field when it has placeholders and
+    // the template is a stored format, otherwise the data itself
+    fn data(&mut self, data: &str) {
+        if !self.options.placeholders && !placeholders::variables(data).is_empty() {
+            self.fields.push(data.to_string());
+            let _ = writeln!(self.out, "^FN{}\"{}\"^FS", self.fields.len(), comment(data));
+        } else {
+            let _ = writeln!(self.out, "^FH_^FD{}^FS", field_data(data));
+        }
+    }
+
+    fn element(&mut self, element: &Element) -> Result<(), String> {
+        let rotation = orientation(element.rotation_deg);
+        match (&element.kind, rotation) {
+            (ElementKind::Text(text), Some(o)) if text.layout == TextLayout::Horizontal => {
+                self.text(element, text, o);
+                Ok(())
+            }
+            (ElementKind::Barcode(barcode), Some(o)) if self.barcode(element, barcode, o) => Ok(()),
+            (ElementKind::Shape(shape), Some(_)) => {
+                self.shape(element, shape);
+                Ok(())
+            }
+            (kind, _) => {
+                let data = match kind {
+                    ElementKind::Text(text) => text.plain_text(),
+                    ElementKind::Barcode(barcode) => barcode.data.clone(),
+                    _ => String::new(),
+                };
+                if !placeholders::variables(&data).is_empty() {
+                    return Err(format!(
+                        "Element {} has placeholders but can't be drawn with ZPL fields; rotate it by a quarter turn or lay its text out horizontally",
+                        element.id
+                    ));
+                }
+                self.graphic(element)
+            }
+        }
+    }
+
+    fn text(&mut self, element: &Element, text: &TextElement, orientation: char) {
+        let data = text.plain_text();
+        let size = self.dots(text.font_size_pt * MM_PER_PT).max(1);
+        let line_height = text.font_size_pt * MM_PER_PT * LINE_HEIGHT;
+        let lines = (element.height_mm / line_height)
+            .floor()
+            .max(data.lines().count() as f64)
+            .max(1.0);
+        let align = match text.align {
+            TextAlign::Left => 'L',
+            TextAlign::Center => 'C',
+            TextAlign::Right => 'R',
+        };
+        self.origin(element);
+        if is_light(&text.color) {
+            self.out.push_str("^FR");
+        }
+        let _ = write!(
+            self.out,
+            "^A0{},{},{}^FB{},{},0,{},0",
+            orientation,
+            size,
+            size,
+            self.dots(element.width_mm).max(1),
+            lines,
+            align
+        );
+        self.data(&data);
+    }
+
+    // Modules across a symbol of `symbology`; from the data when it has no
+    // placeholders, otherwise the fixed width of EAN and UPC codes
+    fn modules(&self, barcode: &BarcodeElement) -> Option<u32> {
+        if placeholders::variables(&barcode.data).is_empty() {
+            if let Ok(symbol) = barcodes::encode(&barcode.symbology, &barcode.data, &barcode.options) {
+                return Some(symbol.width);
+            }
+        }
+        match barcode.symbology.as_str() {
+            "ean13" | "upca" => Some(95),
+            "ean8" => Some(67),
+            "upce" => Some(51),
+            // Version 2, enough for a short link
+            "qr" => Some(25),
+            "datamatrix" | "gs1datamatrix" => Some(18),
+            _ => None,
+        }
+    }
+
+    // Module size in dots: as set, or the width that fills the element
+    fn module_dots(&self, element: &Element, barcode: &BarcodeElement, max: i64) -> i64 {
+        let dots = match barcode.options.module_size_mm {
+            Some(size) => self.dots(size),
+            None => match self.modules(barcode) {
+                Some(modules) => (element.width_mm * self.dpmm / modules as f64).floor() as i64,
+                None => 2,
+            },
+        };
+        dots.clamp(1, max)
+    }
+
+    // A native barcode field; false for symbologies ZPL has no command for
+    fn barcode(&mut self, element: &Element, barcode: &BarcodeElement, o: char) -> bool {
+        let interpretation = if barcode.show_text { 'Y' } else { 'N' };
+        let bars = self
+            .dots(if barcode.show_text {
+                bar_height(element.height_mm)
+            } else {
+                element.height_mm
+            })
+            .max(1);
+        let mut data = barcode.data.clone();
+        let command = match barcode.symbology.as_str() {
+            "code128" | "gs1128" | "code39" | "ean13" | "ean8" | "upca" | "upce" | "itf" | "itf14" => {
+                let module = self.module_dots(element, barcode, 10);
+                let code = match barcode.symbology.as_str() {
+                    "code128" => format!("^BC{},{},{},N,N", o, bars, interpretation),
+                    // Function 1 first makes it GS1-128
+                    "gs1128" => {
+                        data = format!(">;>8{}", data);
+                        format!("^BC{},{},{},N,N", o, bars, interpretation)
+                    }
+                    "code39" => format!("^B3{},N,{},{},N", o, bars, interpretation),
+                    "ean13" => format!("^BE{},{},{},N", o, bars, interpretation),
+                    "ean8" => format!("^B8{},{},{},N", o, bars, interpretation),
+                    "upca" => format!("^BU{},{},{},N,Y", o, bars, interpretation),
+                    "upce" => format!("^B9{},{},{},N,Y", o, bars, interpretation),
+                    _ => format!("^B2{},{},{},N,N", o, bars, interpretation),
+                };
+                format!("^BY{},3,{}{}", module, bars, code)
+            }
+            "qr" => {
+                let magnification = self.module_dots(element, barcode, 10);
+                let level = match barcode.options.error_correction.as_deref() {
+                    Some("L") => 'L',
+                    Some("Q") => 'Q',
+                    Some("H") => 'H',
+                    _ => 'M',
+                };
+                // Error correction and automatic mode come first in the data
+                data = format!("{}A,{}", level, data);
+                format!("^BQ{},2,{}", o, magnification)
+            }
+            "datamatrix" | "gs1datamatrix" => {
+                let module = self.module_dots(element, barcode, 100);
+                let shape = if barcode.options.shape.as_deref() == Some("rectangle") {
+                    ",,,,,2"
+                } else {
+                    ""
+                };
+                format!("^BX{},{},200{}", o, module, shape)
+            }
+            "pdf417" => {
+                let module = self.module_dots(element, barcode, 10);
+                let level = barcode
+                    .options
+                    .error_correction
+                    .as_deref()
+                    .and_then(|level| level.parse::<u8>().ok())
+                    .unwrap_or(2)
+                    .min(8);
+                format!("^BY{}^B7{},{},{}", module, o, module * 3, level)
+            }
+            "aztec" => {
+                let magnification = self.module_dots(element, barcode, 10);
+                format!("^B0{},{},N", o, magnification)
+            }
+            _ => return false,
+        };
+        self.origin(element);
+        self.out.push_str(&command);
+        self.data(&data);
+        true
+    }
+
+    // A native box, circle, ellipse or line, for shapes turned by quarter
+    // turns
+    fn shape(&mut self, element: &Element, shape: &ShapeElement) {
+        let (_, _, width, height) = self.bounds(element);
+        let (width, height) = (self.dots(width).max(1), self.dots(height).max(1));
+        let stroke = self.dots(shape.stroke_width_mm);
+        let filled = shape.fill_color.as_deref().filter(|fill| !is_light(fill));
+        let color = if filled.is_none() && is_light(&shape.stroke_color) {
+            'W'
+        } else {
+            'B'
+        };
+        let thickness = match filled {
+            Some(_) => width.min(height),
+            None if stroke == 0 => return,
+            None => stroke.max(1),
+        };
+        let command = match shape.shape {
+            ShapeKind::Rectangle => format!("^GB{},{},{},{},0", width, height, thickness, color),
+            ShapeKind::Ellipse if width == height => format!("^GC{},{},{}", width, thickness, color),
+            ShapeKind::Ellipse => format!("^GE{},{},{},{}", width, height, thickness, color),
+            ShapeKind::Line => {
+                let (dx, dy) = rotate((element.width_mm, element.height_mm), element.rotation_deg);
+                let (dx, dy) = (self.dots(dx), self.dots(dy));
+                if dx == 0 || dy == 0 {
+                    // Straight lines are boxes as thick as the stroke
+                    let thickness = stroke.max(1);
+                    format!(
+                        "^GB{},{},{},{},0",
+                        dx.abs().max(thickness),
+                        dy.abs().max(thickness),
+                        thickness,
+                        color
+                    )
+                } else {
+                    let lean = if (dx > 0) == (dy > 0) { 'L' } else { 'R' };
+                    format!("^GD{},{},{},{},{}", dx.abs(), dy.abs(), stroke.max(1), color, lean)
+                }
+            }
+        };
+        self.origin(element);
+        let _ = writeln!(self.out, "{}^FS", command);
+    }
+
+    // The element drawn on its own and stored as a graphic field
+    fn graphic(&mut self, element: &Element) -> Result<(), String> {
+        let (x, y, width, height) = self.bounds(element);
+        if width <= 0.0 || height <= 0.0 {
+            return Ok(());
+        }
+        let mut alone = self.document.clone();
+        alone.width_mm = width;
+        alone.height_mm = height;
+        alone.elements = vec![Element {
+            x_mm: element.x_mm - x,
+            y_mm: element.y_mm - y,
+            rules: Vec::new(),
+            ..element.clone()
+        }];
+        let png = render::render_png(&alone, self.fonts.clone(), RENDER_DPI)?;
+        let image = raster::print_raster(&png, self.options.dpi as f64)?;
+        let (row_bytes, hex) = graphic_hex(&image);
+        if hex.bytes().all(|c| c == b'0') {
+            return Ok(());
+        }
+        self.origin(element);
+        let total = row_bytes * image.height() as usize;
+        let _ = writeln!(self.out, "^GFA,{},{},{},{}^FS", total, total, row_bytes, hex);
+        Ok(())
+    }
+}
+
+// Bytes per row and the hex of a black and white image, a set bit per black
+// dot
+fn graphic_hex(image: &GrayImage) -> (usize, String) {
+    let row_bytes = (image.width() as usize).div_ceil(8);
+    let mut hex = String::with_capacity(row_bytes * image.height() as usize * 2);
+    for y in 0..image.height() {
+        for byte in 0..row_bytes {
+            let mut bits = 0u8;
+            for bit in 0..8 {
+                let x = (byte * 8 + bit) as u32;
+                if x < image.width() && image.get_pixel(x, y).0[0] < 128 {
+                    bits |= 0x80 >> bit;
+                }
+            }
+            let _ = write!(hex, "{:02X}", bits);
+        }
+    }
+    (row_bytes, hex)
+}
@@ -0,0 +1,407 @@
+// GHS chemical hazard labels: the bundled pictograms and hazard (H) and
+// precautionary (P) statements, and a layout composer that turns a set of
+// statement codes into a label following the CLP Regulation's pictogram
+// precedence and sizing rules (Article 26 and Annex I, 1.2.1). The
+// pictograms are redrawn from the UNECE artwork: black symbol, red frame.
+use crate::document::{asset_id, Asset, Document, Element, ElementKind, ImageElement, TextElement};
+use crate::formats::MM_PER_PT;
+use crate::project::LoadedAsset;
+use crate::render;
+use crate::svg;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+const PICTOGRAMS: &[(&str, &str)] = &[
+    ("GHS01", include_str!("../ghs/pictograms/GHS01.svg")),
+    ("GHS02", include_str!("../ghs/pictograms/GHS02.svg")),
+    ("GHS03", include_str!("../ghs/pictograms/GHS03.svg")),
+    ("GHS04", include_str!("../ghs/pictograms/GHS04.svg")),
+    ("GHS05", include_str!("../ghs/pictograms/GHS05.svg")),
+    ("GHS06", include_str!("../ghs/pictograms/GHS06.svg")),
+    ("GHS07", include_str!("../ghs/pictograms/GHS07.svg")),
+    ("GHS08", include_str!("../ghs/pictograms/GHS08.svg")),
+    ("GHS09", include_str!("../ghs/pictograms/GHS09.svg")),
+];
+
+static DATABASE: OnceLock<GhsDatabase> = OnceLock::new();
+
+/// Minimum label size and pictogram side by container capacity in litres
+/// (CLP Annex I, table 1.3)
+const SIZES: &[(f64, (f64, f64), f64)] = &[
+    (3.0, (52.0, 74.0), 10.0),
+    (50.0, (74.0, 105.0), 23.0),
+    (500.0, (105.0, 148.0), 32.0),
+    (f64::INFINITY, (148.0, 210.0), 46.0),
+];
+
+/// Every pictogram covers at least this fraction of the label, and 1 cm²
+const PICTOGRAM_AREA_FRACTION: f64 = 1.0 / 15.0;
+const MIN_PICTOGRAM_SIDE_MM: f64 = 10.0;
+
+/// CLP asks for no more than six precautionary statements on a label
+const MAX_PRECAUTIONS: usize = 6;
+
+const MARGIN_MM: f64 = 2.0;
+const GAP_MM: f64 = 1.5;
+/// Statement text starts at the largest size and shrinks to fit
+const MAX_FONT_PT: f64 = 9.0;
+const MIN_FONT_PT: f64 = 6.0;
+/// Average character width as a fraction of the font size, for wrapping
+const CHAR_WIDTH: f64 = 0.52;
+const LINE_HEIGHT: f64 = 1.2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pictogram {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalWord {
+    Warning,
+    Danger,
+}
+
+impl SignalWord {
+    fn text(self) -> &'static str {
+        match self {
+            SignalWord::Warning => "Warning",
+            SignalWord::Danger => "Danger",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HazardStatement {
+    pub code: String,
+    pub text: String,
+    #[serde(default)]
+    pub pictograms: Vec<String>,
+    /// For the most severe category the code is used for
+    #[serde(default)]
+    pub signal_word: Option<SignalWord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrecautionaryStatement {
+    pub code: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GhsDatabase {
+    pub pictograms: Vec<Pictogram>,
+    /// H statements, and the EU's supplemental EUH statements
+    pub hazards: Vec<HazardStatement>,
+    pub precautions: Vec<PrecautionaryStatement>,
+}
+
+fn database() -> &'static GhsDatabase {
+    DATABASE
+        .get_or_init(|| serde_json::from_str(include_str!("../ghs/statements.json")).expect("bundled GHS statements"))
+}
+
+// The statement with `code`: exact first, since "H360Fd" and "H360FD"
+// differ, then ignoring case
+fn find<'a, T>(statements: &'a [T], code: &str, code_of: impl Fn(&T) -> &str) -> Option<&'a T> {
+    statements
+        .iter()
+        .find(|s| code_of(s) == code)
+        .or_else(|| statements.iter().find(|s| code_of(s).eq_ignore_ascii_case(code)))
+}
+
+/// The statements of `code`, which may combine several with '+', as in
+/// "H300+H310+H330" or "P301+P310"
+fn hazards(code: &str) -> Result<Vec<&'static HazardStatement>, String> {
+    code.split('+')
+        .map(|part| {
+            let part: String = part.split_whitespace().collect();
+            find(&database().hazards, &part, |s| s.code.as_str())
+                .ok_or_else(|| format!("Unknown hazard statement: {}", part))
+        })
+        .collect()
+}
+
+fn precautions(code: &str) -> Result<Vec<&'static PrecautionaryStatement>, String> {
+    code.split('+')
+        .map(|part| {
+            let part: String = part.split_whitespace().collect();
+            find(&database().precautions, &part, |s| s.code.as_str())
+                .ok_or_else(|| format!("Unknown precautionary statement: {}", part))
+        })
+        .collect()
+}
+
+// "H300+H310 Fatal if swallowed. Fatal in contact with skin."
+fn statement_line<'a>(codes: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let (codes, texts): (Vec<&str>, Vec<&str>) = codes.unzip();
+    format!("{} {}", codes.join("+"), texts.join(" "))
+}
+
+/// Pictograms required by `hazards`, after CLP Article 26's precedence
+/// rules, in number order
+pub fn required_pictograms(hazards: &[&HazardStatement]) -> Vec<String> {
+    let mut pictograms: Vec<String> = hazards.iter().flat_map(|h| h.pictograms.iter().cloned()).collect();
+    pictograms.sort();
+    pictograms.dedup();
+    let has = |id: &str| pictograms.iter().any(|p| p == id);
+    // Codes that call for the exclamation mark
+    let exclamation: Vec<&str> = hazards
+        .iter()
+        .filter(|h| h.pictograms.iter().any(|p| p == "GHS07"))
+        .map(|h| h.code.as_str())
+        .collect();
+    let only = |codes: &[&str]| exclamation.iter().all(|code| codes.contains(code));
+    let drop_exclamation = has("GHS06")
+        // Skin or eye irritation under corrosion
+        || (has("GHS05") && only(&["H315", "H319"]))
+        // Skin sensitisation or irritation under respiratory sensitisation
+        || (hazards.iter().any(|h| h.code == "H334") && only(&["H315", "H317", "H319"]));
+    if drop_exclamation {
+        pictograms.retain(|p| p != "GHS07");
+    }
+    pictograms
+}
+
+fn pictogram_svg(id: &str) -> Result<&'static str, String> {
+    PICTOGRAMS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(id))
+        .map(|(_, svg)| *svg)
+        .ok_or_else(|| format!("Unknown GHS pictogram: {}", id))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GhsLabelRequest {
+    pub width_mm: f64,
+    pub height_mm: f64,
+    #[serde(default)]
+    pub product_name: String,
+    /// H and EUH codes, e.g. "H225" or "H300+H310"
+    pub hazards: Vec<String>,
+    /// P codes, e.g. "P210" or "P305+P351+P338"
+    #[serde(default)]
+    pub precautions: Vec<String>,
+    /// Supplier name, address and phone number, one per line
+    #[serde(default)]
+    pub supplier: String,
+    /// Container capacity in litres, for the minimum pictogram size
+    #[serde(default)]
+    pub capacity_l: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GhsLabel {
+    pub document: Document,
+    pub pictograms: Vec<String>,
+    pub signal_word: Option<SignalWord>,
+    /// Where the label falls short of the CLP recommendations
+    pub warnings: Vec<String>,
+}
+
+// A block of text: its wrapped lines, size and weight
+struct Block {
+    lines: Vec<String>,
+    font_size_pt: f64,
+    bold: bool,
+}
+
+impl Block {
+    fn new(text: &str, font_size_pt: f64, bold: bool, width_mm: f64) -> Self {
+        let max_chars = (width_mm / (font_size_pt * MM_PER_PT * CHAR_WIDTH)).floor().max(1.0) as usize;
+        Block {
+            lines: render::wrap(text, max_chars),
+            font_size_pt,
+            bold,
+        }
+    }
+
+    fn height_mm(&self) -> f64 {
+        self.lines.len() as f64 * self.font_size_pt * MM_PER_PT * LINE_HEIGHT
+    }
+}
+
+/// Compose a hazard label: product name, pictograms, signal word, hazard
+/// and precautionary statements and the supplier, with the statement text
+/// shrunk to fit between `MAX_FONT_PT` and `MIN_FONT_PT`
+pub fn compose(request: &GhsLabelRequest) -> Result<GhsLabel, String> {
+    let (width_mm, height_mm) = (request.width_mm, request.height_mm);
+    if width_mm <= 0.0 || height_mm <= 0.0 {
+        return Err("Label size must be positive".to_string());
+    }
+    if request.hazards.is_empty() {
+        return Err("A hazard label needs at least one hazard statement".to_string());
+    }
+    let hazard_codes = request
+        .hazards
+        .iter()
+        .map(String::as_str)
+        .map(hazards)
+        .collect::<Result<Vec<_>, _>>()?;
+    let precaution_codes = request
+        .precautions
+        .iter()
+        .map(String::as_str)
+        .map(precautions)
+        .collect::<Result<Vec<_>, _>>()?;
+    let all_hazards: Vec<&HazardStatement> = hazard_codes.iter().flatten().copied().collect();
+    let pictograms = required_pictograms(&all_hazards);
+    let signal_word = all_hazards.iter().filter_map(|h| h.signal_word).max();
+
+    let mut warnings = Vec::new();
+    let (_, min_label, min_side) = SIZES
+        .iter()
+        .find(|(capacity, _, _)| request.capacity_l.unwrap_or(0.0) <= *capacity)
+        .copied()
+        .unwrap_or(SIZES[0]);
+    let (short, long) = (width_mm.min(height_mm), width_mm.max(height_mm));
+    if request.capacity_l.is_some() && (short < min_label.0 || long < min_label.1) {
+        warnings.push(format!(
+            "CLP asks for a label of at least {} x {} mm for this container",
+            min_label.0, min_label.1
+        ));
+    }
+    if precaution_codes.len() > MAX_PRECAUTIONS {
+        warnings.push(format!(
+            "CLP asks for no more than {} precautionary statements unless more are needed",
+            MAX_PRECAUTIONS
+        ));
+    }
+
+    // Pictograms are squares on a point; the sizing rules apply to the
+    // square, the layout to its bounding box
+    let side = (width_mm * height_mm * PICTOGRAM_AREA_FRACTION)
+        .sqrt()
+        .max(min_side)
+        .max(MIN_PICTOGRAM_SIDE_MM);
+    let diamond = side * std::f64::consts::SQRT_2;
+    let content_width = width_mm - 2.0 * MARGIN_MM;
+    let per_row = ((content_width + GAP_MM) / (diamond + GAP_MM)).floor() as usize;
+    if !pictograms.is_empty() && per_row == 0 {
+        return Err(format!(
+            "The label is too narrow for {:.0} mm hazard pictograms",
+            diamond
+        ));
+    }
+    let pictogram_rows = pictograms.len().div_ceil(per_row.max(1));
+    let pictograms_height = pictogram_rows as f64 * (diamond + GAP_MM);
+
+    let hazard_text = hazard_codes
+        .iter()
+        .map(|statements| statement_line(statements.iter().map(|s| (s.code.as_str(), s.text.as_str()))))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let precaution_text = precaution_codes
+        .iter()
+        .map(|statements| statement_line(statements.iter().map(|s| (s.code.as_str(), s.text.as_str()))))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // The largest statement size at which everything fits
+    let available = height_mm - 2.0 * MARGIN_MM - pictograms_height;
+    let mut font_size_pt = MAX_FONT_PT;
+    let blocks = loop {
+        let blocks: Vec<Block> = [
+            (request.product_name.as_str(), font_size_pt + 2.0, true),
+            (signal_word.map_or("", SignalWord::text), font_size_pt + 3.0, true),
+            (hazard_text.as_str(), font_size_pt, false),
+            (precaution_text.as_str(), font_size_pt, false),
+            (request.supplier.as_str(), font_size_pt, false),
+        ]
+        .into_iter()
+        .map(|(text, size, bold)| Block::new(text.trim(), size, bold, content_width))
+        .collect();
+        let height: f64 = blocks
+            .iter()
+            .filter(|block| block.lines.iter().any(|line| !line.is_empty()))
+            .map(|block| block.height_mm() + GAP_MM)
+            .sum();
+        if height <= available {
+            break blocks;
+        }
+        font_size_pt -= 0.5;
+        if font_size_pt < MIN_FONT_PT {
+            return Err(format!(
+                "The statements don't fit on a {} x {} mm label; use a larger label",
+                width_mm, height_mm
+            ));
+        }
+    };
+
+    let mut document = Document::new(width_mm, height_mm);
+    let mut y = MARGIN_MM;
+    let place_text = |document: &mut Document, block: &Block, y: &mut f64| {
+        if block.lines.iter().all(|line| line.is_empty()) {
+            return;
+        }
+        let height = block.height_mm();
+        document.push(Element::new(
+            MARGIN_MM,
+            *y,
+            content_width,
+            height,
+            ElementKind::Text(TextElement {
+                text: block.lines.join("\n"),
+                font_size_pt: block.font_size_pt,
+                bold: block.bold,
+                ..Default::default()
+            }),
+        ));
+        *y += height + GAP_MM;
+    };
+    place_text(&mut document, &blocks[0], &mut y);
+    for (i, id) in pictograms.iter().enumerate() {
+        let (row, column) = (i / per_row, i % per_row);
+        let asset_id = document.add_asset(&format!("{}.svg", id), svg::MIME_TYPE, pictogram_svg(id)?.as_bytes());
+        document.push(Element::new(
+            MARGIN_MM + column as f64 * (diamond + GAP_MM),
+            y + row as f64 * (diamond + GAP_MM),
+            diamond,
+            diamond,
+            ElementKind::Image(ImageElement {
+                asset_id,
+                adjustments: None,
+            }),
+        ));
+    }
+    y += pictograms_height;
+    for block in &blocks[1..] {
+        place_text(&mut document, block, &mut y);
+    }
+
+    Ok(GhsLabel {
+        document,
+        pictograms,
+        signal_word,
+        warnings,
+    })
+}
+
+/// Pictograms and statements, for the hazard picker
+#[tauri::command]
+pub fn list_ghs_statements() -> GhsDatabase {
+    database().clone()
+}
+
+/// Pictogram `id` ("GHS01" to "GHS09") as an SVG asset for a hand-made design
+#[tauri::command]
+pub fn get_ghs_pictogram(id: String) -> Result<LoadedAsset, String> {
+    let bytes = pictogram_svg(&id)?.as_bytes();
+    Ok(LoadedAsset {
+        id: asset_id(bytes),
+        asset: Asset {
+            file_name: format!("{}.svg", id.to_uppercase()),
+            mime_type: svg::MIME_TYPE.to_string(),
+            data: BASE64.encode(bytes),
+        },
+    })
+}
+
+/// Lay out a hazard label for a set of H and P codes
+#[tauri::command]
+pub fn compose_ghs_label(request: GhsLabelRequest) -> Result<GhsLabel, String> {
+    compose(&request)
+}
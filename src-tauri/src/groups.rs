@@ -0,0 +1,140 @@
+// Printer groups, addressed as "group:NAME" wherever a printer name goes.
+// A round-robin group sends each job to the next of its printers, so several
+// identical printers share the work; a failover group sends every job to its
+// first printer. Either way a job a printer fails on, e.g. one that runs out
+// of labels or drops off the network mid-batch, is sent on to the group's
+// next printer, and fails only once every printer has refused it. Each
+// printer keeps its own queue, so a busy one doesn't hold up the others.
+use crate::error::LabelgoodError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+pub const PREFIX: &str = "group:";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrinterGroup {
+    pub name: String,
+    #[serde(default)]
+    pub mode: GroupMode,
+    /// Printer names, in failover order
+    pub printers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMode {
+    RoundRobin,
+    #[default]
+    Failover,
+}
+
+#[derive(Default)]
+struct Groups {
+    groups: Mutex<Vec<PrinterGroup>>,
+    /// Index of the printer each round-robin group sends its next job to
+    next: Mutex<HashMap<String, usize>>,
+}
+
+static GROUPS: OnceLock<Groups> = OnceLock::new();
+
+fn groups() -> &'static Groups {
+    GROUPS.get_or_init(Groups::default)
+}
+
+pub fn configure(printer_groups: Vec<PrinterGroup>) {
+    *groups().groups.lock().unwrap() = printer_groups;
+}
+
+pub fn is_group(printer_name: &str) -> bool {
+    printer_name.starts_with(PREFIX)
+}
+
+/// Every group as a printer name, for the printer list
+pub fn names() -> Vec<String> {
+    groups()
+        .groups
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|group| format!("{}{}", PREFIX, group.name))
+        .collect()
+}
+
+fn find(printer_name: &str) -> Option<PrinterGroup> {
+    let name = printer_name.strip_prefix(PREFIX)?;
+    groups()
+        .groups
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|group| group.name == name)
+        .cloned()
+}
+
+/// The printer a group's capabilities and previews are taken from: its
+/// first, as its printers are meant to be identical. Other names are
+/// returned as they are.
+pub fn primary(printer_name: &str) -> String {
+    find(printer_name)
+        .and_then(|group| group.printers.into_iter().next())
+        .unwrap_or_else(|| printer_name.to_string())
+}
+
+/// The printers to try a job for `printer_name` on, in order. Round-robin
+/// groups start one further along with every job.
+fn members(printer_name: &str) -> Result<Vec<String>, LabelgoodError> {
+    let unavailable = |message: String| LabelgoodError::PrinterUnavailable {
+        printer: printer_name.to_string(),
+        message,
+    };
+    let group =
+        find(printer_name).ok_or_else(|| unavailable(format!("Printer group {} doesn't exist", printer_name)))?;
+    let mut printers = group.printers;
+    if printers.is_empty() {
+        return Err(unavailable(format!("Printer group {} has no printers", group.name)));
+    }
+    if group.mode == GroupMode::RoundRobin {
+        let mut next = groups().next.lock().unwrap();
+        let start = next.entry(group.name).or_insert(0);
+        let first = *start % printers.len();
+        *start = first + 1;
+        printers.rotate_left(first);
+    }
+    Ok(printers)
+}
+
+// Failures of the printer rather than the job, which another printer may
+// not have
+fn fails_over(error: &LabelgoodError) -> bool {
+    matches!(
+        error,
+        LabelgoodError::PrinterUnavailable { .. }
+            | LabelgoodError::SpoolFailed { .. }
+            | LabelgoodError::TimedOut { .. }
+            | LabelgoodError::AuthRequired { .. }
+            | LabelgoodError::DependencyMissing { .. }
+    )
+}
+
+/// Run `attempt` with each of `group`'s printers in turn until one takes the
+/// job, returning the last printer's error when none does
+pub async fn spool<F, Fut>(group: &str, mut attempt: F) -> Result<String, LabelgoodError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String, LabelgoodError>>,
+{
+    let mut last_error = None;
+    for printer in members(group)? {
+        match attempt(printer.clone()).await {
+            Err(e) if fails_over(&e) => {
+                warn!("{} in {} failed, trying the next printer: {}", printer, group, e);
+                last_error = Some(e);
+            }
+            result => return result,
+        }
+    }
+    Err(last_error.expect("groups have at least one printer"))
+}
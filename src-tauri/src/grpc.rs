@@ -0,0 +1,76 @@
+// Optional gRPC service, for integrators who'd rather generate a typed client
+// from proto/labelgood.proto than call the REST API. It goes through the same
+// pipeline as the app and the REST API:
+//
+//   RenderLabel        a template or label definition filled with one record,
+//                      as PNG
+//   PrintJob           one label per record, like POST /print
+//   StreamJobEvents    job and printer events as they happen
+//
+// Only in builds with the "grpc" feature, and off by default. When a token is
+// configured, every call needs an `authorization: Bearer <token>` metadata
+// entry. Listening on anything but loopback requires a token.
+#[cfg(feature = "grpc")]
+mod service;
+
+use crate::api;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcSettings {
+    pub enabled: bool,
+    /// Address to listen on; 0.0.0.0 to accept connections from the LAN, which needs a token
+    pub bind: String,
+    pub port: u16,
+    /// Required as a bearer token when set
+    pub token: Option<String>,
+}
+
+impl Default for GrpcSettings {
+    fn default() -> Self {
+        GrpcSettings {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 8724,
+            token: None,
+        }
+    }
+}
+
+/// The running server, if any. Restarted whenever its settings change.
+#[derive(Default)]
+pub struct GrpcServer {
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl GrpcServer {
+    pub fn start(&self, app: &AppHandle, settings: &GrpcSettings) -> Result<(), String> {
+        let mut shutdown = self.shutdown.lock().unwrap();
+        if let Some(previous) = shutdown.take() {
+            let _ = previous.send(());
+        }
+        if !settings.enabled {
+            return Ok(());
+        }
+        if settings.token.as_deref().is_none_or(str::is_empty) && !api::loopback(&settings.bind) {
+            return Err(format!(
+                "The gRPC service needs a token to listen on {}; set one or bind to 127.0.0.1",
+                settings.bind
+            ));
+        }
+        #[cfg(feature = "grpc")]
+        {
+            *shutdown = Some(service::serve(app, settings)?);
+            Ok(())
+        }
+        #[cfg(not(feature = "grpc"))]
+        {
+            let _ = app;
+            Err("This build has no gRPC service; it's built with the \"grpc\" feature".to_string())
+        }
+    }
+}
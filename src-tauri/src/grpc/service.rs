@@ -0,0 +1,221 @@
+// The service generated from proto/labelgood.proto, on a tonic server
+use super::GrpcSettings;
+use crate::api::{self, PrintRequest};
+use crate::counters::CounterStore;
+use crate::events::{EventBus, LabelEvent};
+use crate::fonts::FontLibrary;
+use crate::formats;
+use crate::merge::{self, CheckDigitPolicy, DataSource, MergeRequest};
+use crate::printing::RENDER_DPI;
+use crate::queue::Priority;
+use crate::render;
+use crate::scale::Scale;
+use crate::scripting::ScriptHooks;
+use crate::templates;
+use futures_util::stream::{self, Stream};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::pin::Pin;
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+pub mod proto {
+    tonic::include_proto!("labelgood.v1");
+}
+
+use proto::label::Source;
+use proto::labelgood_server::{Labelgood, LabelgoodServer};
+use proto::{
+    JobEvent, PrintJobRequest, PrintJobResponse, RenderLabelRequest, RenderLabelResponse, StreamJobEventsRequest,
+};
+
+struct Service {
+    app: AppHandle,
+}
+
+fn fields(record: proto::Record) -> BTreeMap<String, String> {
+    record.fields.into_iter().collect()
+}
+
+fn check_digits(check_digits: proto::CheckDigits) -> CheckDigitPolicy {
+    match check_digits {
+        proto::CheckDigits::Reject => CheckDigitPolicy::Reject,
+        proto::CheckDigits::Fix => CheckDigitPolicy::Fix,
+    }
+}
+
+fn priority(priority: proto::Priority) -> Priority {
+    match priority {
+        proto::Priority::Normal => Priority::Normal,
+        proto::Priority::Low => Priority::Low,
+        proto::Priority::Rush => Priority::Rush,
+    }
+}
+
+fn missing_label() -> Status {
+    Status::invalid_argument("Give a template or a label definition")
+}
+
+// The label of a render request filled with its record, rendered without
+// advancing counters
+fn render_label(app: &AppHandle, request: RenderLabelRequest) -> Result<RenderLabelResponse, String> {
+    let check_digits = check_digits(request.check_digits());
+    let document = match request.label.and_then(|label| label.source) {
+        Some(Source::Template(template)) => {
+            api::check_template_path(app, &template)?;
+            templates::resolve_document(&template, api::shared_dir(app).as_deref())?
+        }
+        Some(Source::Definition(definition)) => formats::definition::compile(&definition, Path::new(""), true)?,
+        None => return Err("Give a template or a label definition".to_string()),
+    };
+    let merge_request = MergeRequest {
+        document,
+        source: DataSource::Records {
+            records: vec![request.record.map(fields).unwrap_or_default()],
+        },
+        mapping: BTreeMap::new(),
+        range: None,
+        printer_name: None,
+        check_digits,
+        numbering: None,
+        answers: BTreeMap::new(),
+    };
+    let scripts = app.state::<ScriptHooks>();
+    let table = merge::load_table(&merge_request, &app.state::<Scale>(), &scripts)?;
+    let counters = app.state::<CounterStore>();
+    let mut documents = merge::merge_documents(&merge_request, &table, &mut merge::peek_counters(&counters))?;
+    scripts.before_render(&mut documents)?;
+    let document = documents.first().ok_or("Nothing to render")?.printable();
+    let dpi = if request.dpi > 0.0 { request.dpi } else { RENDER_DPI };
+    Ok(RenderLabelResponse {
+        png: render::render_png(&document, app.state::<FontLibrary>().database(), dpi)?,
+        width_mm: document.width_mm,
+        height_mm: document.height_mm,
+    })
+}
+
+// An event with the fields most clients look at pulled out of its JSON
+fn job_event(event: &LabelEvent) -> JobEvent {
+    let json = serde_json::to_value(event).unwrap_or_default();
+    let text = |key: &str| json.get(key).and_then(Value::as_str).map(str::to_string);
+    JobEvent {
+        kind: event.kind().to_string(),
+        job: json.get("job").and_then(Value::as_u64),
+        printer: text("printer"),
+        message: text("message").or_else(|| text("error")),
+        json: json.to_string(),
+    }
+}
+
+#[tonic::async_trait]
+impl Labelgood for Service {
+    async fn render_label(
+        &self,
+        request: Request<RenderLabelRequest>,
+    ) -> Result<Response<RenderLabelResponse>, Status> {
+        let request = request.into_inner();
+        if request.label.as_ref().and_then(|label| label.source.as_ref()).is_none() {
+            return Err(missing_label());
+        }
+        let app = self.app.clone();
+        tauri::async_runtime::spawn_blocking(move || render_label(&app, request))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map(Response::new)
+            .map_err(Status::failed_precondition)
+    }
+
+    async fn print_job(&self, request: Request<PrintJobRequest>) -> Result<Response<PrintJobResponse>, Status> {
+        let request = request.into_inner();
+        let (check_digits, priority) = (check_digits(request.check_digits()), priority(request.priority()));
+        let (template, definition) = match request.label.and_then(|label| label.source) {
+            Some(Source::Template(template)) => {
+                api::check_template_path(&self.app, &template).map_err(Status::permission_denied)?;
+                (template, None)
+            }
+            Some(Source::Definition(definition)) => (String::new(), Some(definition)),
+            None => return Err(missing_label()),
+        };
+        let print = PrintRequest {
+            template,
+            definition,
+            records: request.records.into_iter().map(fields).collect(),
+            printer: request.printer.filter(|printer| !printer.is_empty()),
+            check_digits,
+            priority,
+        };
+        let printed = api::print_records(&self.app, "grpc", print)
+            .await
+            .map_err(Status::failed_precondition)?;
+        Ok(Response::new(PrintJobResponse {
+            labels: printed.labels as u32,
+            message: printed.message,
+        }))
+    }
+
+    type StreamJobEventsStream = Pin<Box<dyn Stream<Item = Result<JobEvent, Status>> + Send>>;
+
+    async fn stream_job_events(
+        &self,
+        _request: Request<StreamJobEventsRequest>,
+    ) -> Result<Response<Self::StreamJobEventsStream>, Status> {
+        let events = stream::unfold(self.app.state::<EventBus>().subscribe(), |mut receiver| async move {
+            let event = api::next_event(&mut receiver).await?;
+            Some((Ok(job_event(&event)), receiver))
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+fn authorize(request: Request<()>, token: Option<&str>) -> Result<Request<()>, Status> {
+    let Some(token) = token else {
+        return Ok(request);
+    };
+    let given = request
+        .metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes()))) {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("Missing or invalid API token"))
+    }
+}
+
+/// Listen on the configured address; send on the returned channel to stop
+pub fn serve(app: &AppHandle, settings: &GrpcSettings) -> Result<oneshot::Sender<()>, String> {
+    // Bind here rather than in the task so a taken port is reported
+    let address = format!("{}:{}", settings.bind, settings.port);
+    let listener = std::net::TcpListener::bind(&address)
+        .and_then(|l| l.set_nonblocking(true).map(|_| l))
+        .map_err(|e| format!("Failed to start gRPC service on {}: {}", address, e))?;
+
+    let token = settings.token.clone().filter(|t| !t.is_empty());
+    let service = LabelgoodServer::with_interceptor(Service { app: app.clone() }, move |request| {
+        authorize(request, token.as_deref())
+    });
+    let (stop, stopped) = oneshot::channel::<()>();
+    tauri::async_runtime::spawn(async move {
+        let result = match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+                    let _ = stopped.await;
+                })
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            error!("gRPC service stopped: {}", e);
+        }
+    });
+    info!("gRPC service listening on {}", address);
+    Ok(stop)
+}
@@ -0,0 +1,96 @@
+// What a label printer does with each label once it's printed: feed it to
+// the tear bar, peel it off the liner, cut it, or hand it to an applicator.
+// Set per printer and sent with every job as the option its driver
+// understands, since the PDF itself can't say any of this.
+use crate::backends::cups;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelHandling {
+    /// Feed each label past the tear bar
+    TearOff,
+    /// Peel each label off the liner, waiting for it to be taken when the
+    /// printer has a label-taken sensor
+    Peel,
+    Cutter,
+    /// Present each label to a print-and-apply applicator
+    Applicator,
+}
+
+impl LabelHandling {
+    const ALL: [LabelHandling; 4] = [
+        LabelHandling::TearOff,
+        LabelHandling::Peel,
+        LabelHandling::Cutter,
+        LabelHandling::Applicator,
+    ];
+
+    // PPD options and choices that select this mode, by driver: Zebra
+    // (CUPS rastertolabel and Zebra's own), Brother QL/TD and DYMO
+    fn ppd_choices(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            LabelHandling::TearOff => &[
+                ("zePrintMode", "Tear"),
+                ("BrCutAtEnd", "OFF"),
+                ("DymoCutOptions", "ChainMarks"),
+            ],
+            LabelHandling::Peel => &[("zePrintMode", "Peel")],
+            LabelHandling::Cutter => &[
+                ("zePrintMode", "Cutter"),
+                ("BrCutAtEnd", "ON"),
+                ("DymoCutOptions", "Cut"),
+            ],
+            LabelHandling::Applicator => &[("zePrintMode", "Applicator")],
+        }
+    }
+}
+
+static HANDLING: OnceLock<Mutex<BTreeMap<String, LabelHandling>>> = OnceLock::new();
+
+fn handling() -> &'static Mutex<BTreeMap<String, LabelHandling>> {
+    HANDLING.get_or_init(Mutex::default)
+}
+
+pub fn configure(label_handling: BTreeMap<String, LabelHandling>) {
+    *handling().lock().unwrap() = label_handling;
+}
+
+/// The mode set for `printer_name`; None leaves the printer's own setting
+pub fn for_printer(printer_name: &str) -> Option<LabelHandling> {
+    handling().lock().unwrap().get(printer_name).copied()
+}
+
+/// Say that `printer_name`'s mode, if one is set, isn't applied by
+/// `backend`, which sends jobs with no way to choose one
+pub fn warn_unsupported(backend: &str, printer_name: &str) {
+    if let Some(mode) = for_printer(printer_name) {
+        warn!("{} printing has no option for {:?}, {} prints with its own setting", backend, mode, printer_name);
+    }
+}
+
+/// The `-o` option selecting `mode` among the PPD options in `lpoptions -l`
+/// output, e.g. "zePrintMode=Peel"; None when the driver has no such choice
+pub fn cups_option(mode: LabelHandling, options: &str) -> Option<String> {
+    mode.ppd_choices().iter().find_map(|(keyword, choice)| {
+        let line = options.lines().find(|line| line.split('/').next() == Some(*keyword))?;
+        let (_, choices) = line.split_once(':')?;
+        choices
+            .split_whitespace()
+            .any(|c| c.trim_start_matches('*') == *choice)
+            .then(|| format!("{}={}", keyword, choice))
+    })
+}
+
+/// Modes the driver of CUPS queue `printer_name` can be set to
+#[tauri::command]
+pub async fn supported_label_handling(printer_name: String) -> Vec<LabelHandling> {
+    let options = cups::printer_options(&printer_name).await.unwrap_or_default();
+    LabelHandling::ALL
+        .into_iter()
+        .filter(|mode| cups_option(*mode, &options).is_some())
+        .collect()
+}
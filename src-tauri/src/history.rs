@@ -0,0 +1,552 @@
+// Print history: every job that reaches the printer is recorded in a SQLite
+// database with the data it printed and the PDF that was sent, so any job can
+// be reprinted exactly as it was, without advancing counters. Only the most
+// recent jobs keep their PDF, and jobs can be dropped after a number of days,
+// so the database doesn't grow without limit.
+use crate::events::EventBus;
+use crate::printing::{self, SubmittedJob};
+use crate::queue::{self, Priority};
+use crate::spool;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS jobs (
+        id INTEGER PRIMARY KEY,
+        printed_at TEXT NOT NULL,
+        source TEXT NOT NULL,
+        template TEXT,
+        data TEXT NOT NULL,
+        printer TEXT,
+        width_mm REAL,
+        height_mm REAL,
+        labels INTEGER NOT NULL,
+        ok INTEGER NOT NULL,
+        message TEXT NOT NULL,
+        pdf BLOB,
+        operator TEXT
+    );
+    CREATE INDEX IF NOT EXISTS jobs_printed_at ON jobs (printed_at);
+";
+
+const JOB_COLUMNS: &str =
+    "id, printed_at, source, template, data, printer, width_mm, height_mm, labels, ok, message, operator, pdf IS NOT NULL";
+
+// Columns added since the table was first created, with their types
+const ADDED_COLUMNS: &[(&str, &str)] = &[("operator", "TEXT")];
+
+const DEFAULT_LIMIT: u32 = 100;
+
+const DEFAULT_KEPT_PDFS: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    /// Most recent jobs whose PDF is kept for reprinting; every job's when
+    /// absent
+    pub keep_pdfs: Option<u32>,
+    /// Days jobs are kept; forever when absent
+    pub keep_days: Option<u32>,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings {
+            keep_pdfs: Some(DEFAULT_KEPT_PDFS),
+            keep_days: None,
+        }
+    }
+}
+
+static SETTINGS: Mutex<Option<HistorySettings>> = Mutex::new(None);
+
+pub fn configure(settings: HistorySettings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// What a job printed, beyond what the job runner knows
+#[derive(Debug, Clone, Default)]
+pub struct JobDetails {
+    /// Gallery template id or label file path, when the job came from one
+    pub template: Option<String>,
+    /// One record per label
+    pub data: Vec<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// UTC, RFC 3339
+    pub printed_at: String,
+    pub source: String,
+    pub template: Option<String>,
+    pub data: Vec<BTreeMap<String, String>>,
+    /// None when the PDF was opened rather than printed
+    pub printer: Option<String>,
+    /// Label size; absent when the job failed before anything was sent
+    pub width_mm: Option<f64>,
+    pub height_mm: Option<f64>,
+    pub labels: usize,
+    pub ok: bool,
+    pub message: String,
+    /// Who was signed in at the workstation
+    pub operator: Option<String>,
+    /// Whether the PDF was kept, which reprinting needs
+    pub reprintable: bool,
+}
+
+/// What a successful job used, for reports
+#[derive(Debug, Clone)]
+pub struct PrintedJob {
+    pub printed_at: String,
+    pub printer: String,
+    pub template: Option<String>,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub labels: usize,
+}
+
+/// Any job, failed or not, for statistics
+#[derive(Debug, Clone)]
+pub struct JobSummary {
+    pub printed_at: String,
+    pub printer: Option<String>,
+    pub template: Option<String>,
+    pub width_mm: Option<f64>,
+    pub height_mm: Option<f64>,
+    pub labels: usize,
+    pub ok: bool,
+}
+
+/// Conditions on `list_print_history`; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    pub template: Option<String>,
+    pub printer: Option<String>,
+    pub source: Option<String>,
+    pub operator: Option<String>,
+    pub ok: Option<bool>,
+    /// Jobs at or after this time, RFC 3339 UTC or a date like 2024-05-01
+    pub since: Option<String>,
+    /// Jobs before this time, in the same form as `since`
+    pub until: Option<String>,
+    /// Text anywhere in the printed data
+    pub search: Option<String>,
+    /// 100 when absent
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+pub struct HistoryStore {
+    connection: Mutex<Connection>,
+}
+
+impl HistoryStore {
+    /// Open or create the database at `path`. History is kept in memory for
+    /// this session when the file can't be opened, so printing still works.
+    pub fn open(path: &Path) -> Self {
+        let connection = match open_file(path) {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("{}; print history won't be saved", e);
+                let connection = Connection::open_in_memory().expect("in-memory SQLite database");
+                connection.execute_batch(SCHEMA).expect("print history schema");
+                connection
+            }
+        };
+        HistoryStore {
+            connection: Mutex::new(connection),
+        }
+    }
+
+    /// Record a finished job. `submitted` is what was sent to the printer,
+    /// if it got that far.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        source: &str,
+        labels: usize,
+        printer: Option<&str>,
+        operator: Option<&str>,
+        details: &JobDetails,
+        submitted: Option<&SubmittedJob>,
+        result: &Result<String, String>,
+    ) -> Result<i64, String> {
+        let data = serde_json::to_string(&details.data)
+            .map_err(|e| format!("Failed to serialize job data: {}", e))?;
+        let pdf = submitted.and_then(|job| fs::read(&job.pdf_path).ok());
+        let (ok, message) = match result {
+            Ok(message) => (true, message),
+            Err(error) => (false, error),
+        };
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO jobs (printed_at, source, template, data, printer, width_mm, height_mm, labels, ok, message, pdf, operator)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    source,
+                    details.template,
+                    data,
+                    printer,
+                    submitted.map(|job| job.width_mm),
+                    submitted.map(|job| job.height_mm),
+                    labels as i64,
+                    ok,
+                    message,
+                    pdf,
+                    operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to record print job: {}", e))?;
+        let id = connection.last_insert_rowid();
+        if let Err(e) = prune(&connection) {
+            error!("{}", e);
+        }
+        Ok(id)
+    }
+
+    /// Matching jobs, newest first
+    pub fn list(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>, String> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        let mut condition = |sql: &str, value: Value| {
+            conditions.push(sql.to_string());
+            values.push(value);
+        };
+        if let Some(template) = &filter.template {
+            condition("template = ?", Value::Text(template.clone()));
+        }
+        if let Some(printer) = &filter.printer {
+            condition("printer = ?", Value::Text(printer.clone()));
+        }
+        if let Some(source) = &filter.source {
+            condition("source = ?", Value::Text(source.clone()));
+        }
+        if let Some(operator) = &filter.operator {
+            condition("operator = ?", Value::Text(operator.clone()));
+        }
+        if let Some(ok) = filter.ok {
+            condition("ok = ?", Value::Integer(ok as i64));
+        }
+        if let Some(since) = &filter.since {
+            condition("printed_at >= ?", Value::Text(since.clone()));
+        }
+        if let Some(until) = &filter.until {
+            condition("printed_at < ?", Value::Text(until.clone()));
+        }
+        if let Some(search) = filter.search.as_deref().filter(|s| !s.is_empty()) {
+            condition("instr(lower(data), lower(?)) > 0", Value::Text(search.to_string()));
+        }
+
+        let mut sql = format!("SELECT {} FROM jobs", JOB_COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id DESC LIMIT ? OFFSET ?");
+        values.push(Value::Integer(filter.limit.unwrap_or(DEFAULT_LIMIT) as i64));
+        values.push(Value::Integer(filter.offset.unwrap_or(0) as i64));
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        let entries = statement
+            .query_map(params_from_iter(values), entry)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        Ok(entries)
+    }
+
+    /// Successful jobs sent to a printer, printed at or after `since` and
+    /// before `until` when given, oldest first
+    pub fn printed(&self, since: Option<&str>, until: Option<&str>) -> Result<Vec<PrintedJob>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT printed_at, printer, template, width_mm, height_mm, labels FROM jobs
+                 WHERE ok = 1 AND printer IS NOT NULL AND width_mm IS NOT NULL AND height_mm IS NOT NULL
+                 AND (?1 IS NULL OR printed_at >= ?1) AND (?2 IS NULL OR printed_at < ?2)
+                 ORDER BY id",
+            )
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        let jobs = statement
+            .query_map(params![since, until], |row| {
+                Ok(PrintedJob {
+                    printed_at: row.get(0)?,
+                    printer: row.get(1)?,
+                    template: row.get(2)?,
+                    width_mm: row.get(3)?,
+                    height_mm: row.get(4)?,
+                    labels: row.get::<_, i64>(5)? as usize,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        Ok(jobs)
+    }
+
+    /// When and with what data `template` was printed successfully at or
+    /// after `since`, newest first
+    pub fn printed_data(
+        &self,
+        template: Option<&str>,
+        since: &str,
+    ) -> Result<Vec<(String, Vec<BTreeMap<String, String>>)>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT printed_at, data FROM jobs
+                 WHERE ok = 1 AND printer IS NOT NULL AND template IS ?1 AND printed_at >= ?2
+                 ORDER BY id DESC",
+            )
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        let jobs = statement
+            .query_map(params![template, since], |row| {
+                let data: String = row.get(1)?;
+                Ok((row.get(0)?, serde_json::from_str(&data).unwrap_or_default()))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        Ok(jobs)
+    }
+
+    /// Every job recorded at or after `since` and before `until` when given,
+    /// oldest first
+    pub fn summaries(&self, since: Option<&str>, until: Option<&str>) -> Result<Vec<JobSummary>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT printed_at, printer, template, width_mm, height_mm, labels, ok FROM jobs
+                 WHERE (?1 IS NULL OR printed_at >= ?1) AND (?2 IS NULL OR printed_at < ?2)
+                 ORDER BY id",
+            )
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        let jobs = statement
+            .query_map(params![since, until], |row| {
+                Ok(JobSummary {
+                    printed_at: row.get(0)?,
+                    printer: row.get(1)?,
+                    template: row.get(2)?,
+                    width_mm: row.get(3)?,
+                    height_mm: row.get(4)?,
+                    labels: row.get::<_, i64>(5)? as usize,
+                    ok: row.get(6)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query print history: {}", e))?;
+        Ok(jobs)
+    }
+
+    /// A job and the PDF that was sent for it
+    pub fn get(&self, id: i64) -> Result<(HistoryEntry, Option<Vec<u8>>), String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                &format!("SELECT {}, pdf FROM jobs WHERE id = ?1", JOB_COLUMNS),
+                [id],
+                |row| Ok((entry(row)?, row.get(13)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read print job: {}", e))?
+            .ok_or_else(|| format!("Print job {} not found", id))
+    }
+
+    /// The newest job that was sent to a printer and kept its PDF
+    pub fn last_printed(&self) -> Result<Option<i64>, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT id FROM jobs WHERE ok AND printer IS NOT NULL AND pdf IS NOT NULL ORDER BY id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query print history: {}", e))
+    }
+
+    /// Delete jobs printed before `before`, or every job when absent.
+    /// Returns how many were deleted.
+    pub fn clear(&self, before: Option<&str>) -> Result<usize, String> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = match before {
+            Some(before) => connection.execute("DELETE FROM jobs WHERE printed_at < ?1", [before]),
+            None => connection.execute("DELETE FROM jobs", []),
+        };
+        deleted.map_err(|e| format!("Failed to clear print history: {}", e))
+    }
+}
+
+// Drop the PDFs and jobs the retention settings no longer keep
+fn prune(connection: &Connection) -> Result<(), String> {
+    let settings = SETTINGS.lock().unwrap().clone().unwrap_or_default();
+    if let Some(days) = settings.keep_days {
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(days));
+        connection
+            .execute(
+                "DELETE FROM jobs WHERE printed_at < ?1",
+                [cutoff.to_rfc3339_opts(SecondsFormat::Secs, true)],
+            )
+            .map_err(|e| format!("Failed to remove old print jobs: {}", e))?;
+    }
+    if let Some(kept) = settings.keep_pdfs {
+        connection
+            .execute(
+                "UPDATE jobs SET pdf = NULL WHERE pdf IS NOT NULL AND id NOT IN
+                 (SELECT id FROM jobs WHERE pdf IS NOT NULL ORDER BY id DESC LIMIT ?1)",
+                [kept],
+            )
+            .map_err(|e| format!("Failed to remove old print job PDFs: {}", e))?;
+    }
+    Ok(())
+}
+
+fn open_file(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let connection = Connection::open(path).map_err(|e| format!("Failed to open print history: {}", e))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to set up print history: {}", e))?;
+    for (column, kind) in ADDED_COLUMNS {
+        // Preparing a query fails when the column doesn't exist yet
+        if connection.prepare(&format!("SELECT {} FROM jobs LIMIT 0", column)).is_err() {
+            connection
+                .execute_batch(&format!("ALTER TABLE jobs ADD COLUMN {} {}", column, kind))
+                .map_err(|e| format!("Failed to update print history: {}", e))?;
+        }
+    }
+    Ok(connection)
+}
+
+fn entry(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let data: String = row.get(4)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        printed_at: row.get(1)?,
+        source: row.get(2)?,
+        template: row.get(3)?,
+        // Written by `record`, so only a hand-edited database fails here
+        data: serde_json::from_str(&data).unwrap_or_default(),
+        printer: row.get(5)?,
+        width_mm: row.get(6)?,
+        height_mm: row.get(7)?,
+        labels: row.get::<_, i64>(8)? as usize,
+        ok: row.get(9)?,
+        message: row.get(10)?,
+        operator: row.get(11)?,
+        reprintable: row.get(12)?,
+    })
+}
+
+#[tauri::command]
+pub async fn list_print_history(
+    history: State<'_, HistoryStore>,
+    filter: Option<HistoryFilter>,
+) -> Result<Vec<HistoryEntry>, String> {
+    history.list(&filter.unwrap_or_default())
+}
+
+/// Send a recorded job's PDF again, to its original printer unless
+/// `printer_name` is given. Queued ahead of other jobs unless a `priority`
+/// is given.
+pub async fn reprint(
+    history: &HistoryStore,
+    events: &EventBus,
+    id: i64,
+    printer_name: Option<String>,
+    priority: Option<Priority>,
+) -> Result<String, String> {
+    resend(history, events, "history", id, printer_name, priority.unwrap_or(Priority::Rush)).await
+}
+
+// Send a recorded job's PDF again from `source`, with the details it was
+// first printed with so quotas and the audit log see the same job
+async fn resend(
+    history: &HistoryStore,
+    events: &EventBus,
+    source: &str,
+    id: i64,
+    printer_name: Option<String>,
+    priority: Priority,
+) -> Result<String, String> {
+    let (entry, pdf) = history.get(id)?;
+    let pdf = pdf.ok_or_else(|| format!("Print job {} failed before anything was printed", id))?;
+    let (Some(width_mm), Some(height_mm)) = (entry.width_mm, entry.height_mm) else {
+        return Err(format!("Print job {} has no label size", id));
+    };
+
+    // Kept in the spool directory like freshly generated PDFs, since a PDF
+    // opened for preview must outlive this call
+    let pdf_path = spool::path(&format!("label_reprint_{}.pdf", id));
+    fs::write(&pdf_path, pdf).map_err(|e| format!("Failed to write PDF file: {}", e))?;
+
+    let printer = printer_name.or(entry.printer);
+    let details = JobDetails {
+        template: entry.template,
+        data: entry.data,
+    };
+    let work = async {
+        printing::submit_pdf(&pdf_path, width_mm, height_mm, printer.as_deref()).await.map_err(String::from)
+    };
+    let work = queue::with_priority(priority, work);
+    events.run_job(source, entry.labels, printer.as_deref(), details, work).await
+}
+
+/// `reprint` a recorded job; `op_id` lets `cancel_operation` stop it
+#[tauri::command]
+pub async fn reprint_job(
+    history: State<'_, HistoryStore>,
+    events: State<'_, EventBus>,
+    id: i64,
+    printer_name: Option<String>,
+    op_id: Option<String>,
+    priority: Option<Priority>,
+) -> Result<String, String> {
+    events.run_operation(op_id, reprint(&history, &events, id, printer_name, priority)).await
+}
+
+/// Send the last job that went to a printer again, from `source`: the same
+/// PDF bytes and details, kept in the print history so this works after a
+/// restart too, to its printer unless `printer_name` is given, ahead of other
+/// jobs. Nothing is rendered again.
+pub async fn replay_last(
+    history: &HistoryStore,
+    events: &EventBus,
+    printer_name: Option<&str>,
+    source: &str,
+) -> Result<String, String> {
+    let id = history.last_printed()?.ok_or("Nothing has been printed yet")?;
+    resend(history, events, source, id, printer_name.map(str::to_string), Priority::Rush).await
+}
+
+/// Reprint the last job sent to a printer, e.g. after a jam, to
+/// `printer_name` or the printer it went to
+#[tauri::command]
+pub async fn reprint_last(
+    history: State<'_, HistoryStore>,
+    events: State<'_, EventBus>,
+    printer_name: Option<String>,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    events.run_operation(op_id, replay_last(&history, &events, printer_name.as_deref(), "reprint")).await
+}
+
+/// Delete history before an RFC 3339 time or date, or all of it
+#[tauri::command]
+pub async fn clear_print_history(history: State<'_, HistoryStore>, before: Option<String>) -> Result<usize, String> {
+    history.clear(before.as_deref())
+}
@@ -0,0 +1,265 @@
+// Hot folder: files dropped into a watched directory are printed without
+// anyone touching the app. Data files (CSV, spreadsheets) are merged against
+// the configured template; PNG and PDF files are printed as they are, at the
+// template's label size. Processed files move to `done/` or `error/`; one
+// that can't be moved is left where it is and not printed again unless it's
+// replaced.
+use crate::counters::CounterStore;
+use crate::document::Document;
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::history::JobDetails;
+use crate::i18n;
+use crate::merge::{self, CheckDigitPolicy, DataSource, MergeRequest};
+use crate::printing;
+use crate::project;
+use crate::scale::Scale;
+use crate::scripting::ScriptHooks;
+use notify::{Config, PollWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Event emitted to the frontend for every file the hot folder processes
+pub const HOT_FOLDER_EVENT: &str = "hot-folder-processed";
+
+// Hot folders are often network shares, which don't deliver native change
+// notifications reliably, so the directory is polled
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const DONE_DIR: &str = "done";
+const ERROR_DIR: &str = "error";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotFolderConfig {
+    pub dir: String,
+    /// .lblg project or importable label file that data files are merged into
+    pub template: String,
+    /// Open the PDF instead of printing when absent
+    #[serde(default)]
+    pub printer_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotFolderResult {
+    pub file: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+struct Worker {
+    _watcher: PollWatcher,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Watches the configured hot folder. Replacing the configuration stops the
+/// previous worker.
+#[derive(Default)]
+pub struct HotFolder {
+    worker: Mutex<Option<Worker>>,
+}
+
+impl HotFolder {
+    /// Start processing `config.dir`, replacing any previous hot folder.
+    /// `None` stops watching.
+    pub fn start(&self, app: &AppHandle, config: Option<HotFolderConfig>) -> Result<(), String> {
+        let mut current = self.worker.lock().unwrap();
+        *current = None;
+        let Some(config) = config else {
+            return Ok(());
+        };
+
+        let dir = PathBuf::from(&config.dir);
+        for sub in [DONE_DIR, ERROR_DIR] {
+            fs::create_dir_all(dir.join(sub))
+                .map_err(|e| format!("Failed to create hot folder {}: {}", dir.join(sub).display(), e))?;
+        }
+
+        // The watcher only wakes the worker; the worker decides what's ready
+        let (wake, mut wakeups) = mpsc::unbounded_channel();
+        let watch_config = Config::default().with_poll_interval(POLL_INTERVAL);
+        let mut watcher = PollWatcher::new(
+            move |result: notify::Result<notify::Event>| match result {
+                Ok(_) => {
+                    let _ = wake.send(());
+                }
+                Err(e) => error!("Hot folder watch error: {}", e),
+            },
+            watch_config,
+        )
+        .map_err(|e| format!("Failed to watch hot folder: {}", e))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch hot folder: {}", e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+            let mut stuck: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+            while !worker_stop.load(Ordering::Relaxed) {
+                for path in settled_files(&dir, &mut sizes, &mut stuck) {
+                    let result = process(&app, &config, &path, &mut stuck).await;
+                    if let Err(e) = app.emit(HOT_FOLDER_EVENT, &result) {
+                        error!("Failed to emit {}: {}", HOT_FOLDER_EVENT, e);
+                    }
+                }
+                match tokio::time::timeout(POLL_INTERVAL, wakeups.recv()).await {
+                    Ok(Some(())) | Err(_) => {}
+                    Ok(None) => break,
+                }
+            }
+        });
+
+        *current = Some(Worker {
+            _watcher: watcher,
+            stop,
+        });
+        Ok(())
+    }
+}
+
+// Files whose size hasn't changed since the previous scan, so a file that is
+// still being copied in isn't picked up half written. Files in `stuck`, by
+// path and modification time, were processed but couldn't be moved away;
+// entries for files since removed or replaced are dropped.
+fn settled_files(
+    dir: &Path,
+    sizes: &mut HashMap<PathBuf, u64>,
+    stuck: &mut HashMap<PathBuf, Option<SystemTime>>,
+) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut current: HashMap<PathBuf, u64> = HashMap::new();
+    let mut still_stuck = HashMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_file() && !hidden => {
+                let modified = metadata.modified().ok();
+                if stuck.get(&path) == Some(&modified) {
+                    still_stuck.insert(path, modified);
+                } else {
+                    current.insert(path, metadata.len());
+                }
+            }
+            _ => {}
+        }
+    }
+    *stuck = still_stuck;
+
+    let mut settled: Vec<PathBuf> = current
+        .iter()
+        .filter(|(path, size)| sizes.get(*path) == Some(size))
+        .map(|(path, _)| path.clone())
+        .collect();
+    settled.sort();
+    *sizes = current;
+    for path in &settled {
+        sizes.remove(path);
+    }
+    settled
+}
+
+async fn process(
+    app: &AppHandle,
+    config: &HotFolderConfig,
+    path: &Path,
+    stuck: &mut HashMap<PathBuf, Option<SystemTime>>,
+) -> HotFolderResult {
+    let file = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    // Read before printing, so a file changed meanwhile counts as new
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let outcome = print_file(app, config, path).await;
+    let (sub, ok, message) = match outcome {
+        Ok(message) => (DONE_DIR, true, message),
+        Err(e) => (ERROR_DIR, false, e),
+    };
+    info!("Hot folder: {}: {}", file, message);
+
+    let target = path.parent().unwrap_or(Path::new(".")).join(sub).join(&file);
+    if let Err(e) = fs::rename(path, &target) {
+        error!("Failed to move {} to {}: {}", path.display(), target.display(), e);
+        stuck.insert(path.to_path_buf(), modified);
+    }
+    if !ok {
+        let _ = fs::write(target.with_file_name(format!("{}.error.txt", file)), &message);
+    }
+    HotFolderResult { file, ok, message }
+}
+
+async fn print_file(app: &AppHandle, config: &HotFolderConfig, path: &Path) -> Result<String, String> {
+    let template_path = PathBuf::from(&config.template);
+    let template: Document = merge::blocking(move || project::load_document(&template_path)).await?;
+    let (width_mm, height_mm) = (template.width_mm, template.height_mm);
+    let printer_name = config.printer_name.as_deref();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+
+    let events = app.state::<EventBus>();
+    let details = JobDetails {
+        template: Some(config.template.clone()),
+        data: Vec::new(),
+    };
+    match extension.as_str() {
+        "png" => {
+            let work = async {
+                let pdf_path = printing::images_to_pdf(&[path], width_mm, height_mm).await?;
+                Ok(printing::submit_pdf(&pdf_path, width_mm, height_mm, printer_name).await?)
+            };
+            events.run_job("hot_folder", 1, printer_name, details, work).await
+        }
+        "pdf" => {
+            let work = async {
+                printing::submit_pdf(path, width_mm, height_mm, printer_name)
+                    .await
+                    .map_err(String::from)
+            };
+            events.run_job("hot_folder", 1, printer_name, details, work).await
+        }
+        "csv" | "tsv" | "txt" | "xlsx" | "xlsm" | "xls" | "ods" => {
+            let request = MergeRequest {
+                document: template,
+                source: DataSource::from_file(path, None),
+                mapping: Default::default(),
+                range: None,
+                printer_name: config.printer_name.clone(),
+                check_digits: CheckDigitPolicy::Reject,
+                numbering: None,
+                answers: Default::default(),
+            };
+            let merging = app.clone();
+            let (documents, data) = merge::blocking(move || {
+                let scripts = merging.state::<ScriptHooks>();
+                let table = merge::load_table(&request, &merging.state::<Scale>(), &scripts)?;
+                let counters = merging.state::<CounterStore>();
+                let mut documents =
+                    merge::merge_documents(&request, &table, &mut |name| counters.next_serial(name))?;
+                scripts.before_render(&mut documents)?;
+                Ok((documents, merge::selected_records(&request, &table)))
+            })
+            .await?;
+            let fonts = app.state::<FontLibrary>();
+            let details = JobDetails { data, ..details };
+            let work = merge::print_documents(&documents, fonts.database(), printer_name);
+            events.run_job("hot_folder", documents.len(), printer_name, details, work).await?;
+            Ok(format!("Printed {} labels", documents.len()))
+        }
+        _ => Err(i18n::t("unsupported-file-type", &[("extension", extension.into())])),
+    }
+}
@@ -0,0 +1,142 @@
+// Translations, kept with the backend so the frontend and the messages the
+// backend generates itself come from the same Fluent bundles: print
+// statuses, media warnings, and the errors of printing, uploads, kiosk mode,
+// operators and orders that people at the printer see. Other errors, mostly
+// for whoever sets the app up, stay in English. Each language is one .ftl
+// file in locales/, compiled into the binary; a message missing from a
+// translation falls back to English.
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use tracing::{error, warn};
+use unic_langid::LanguageIdentifier;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Fluent source of each bundled language
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("nl", include_str!("../locales/nl.ftl")),
+];
+
+/// Language chosen in the settings; the system language when None
+static LANGUAGE: Mutex<Option<String>> = Mutex::new(None);
+
+fn bundles() -> &'static Vec<(&'static str, FluentBundle<FluentResource>)> {
+    static BUNDLES: OnceLock<Vec<(&'static str, FluentBundle<FluentResource>)>> = OnceLock::new();
+    BUNDLES.get_or_init(|| {
+        LOCALES
+            .iter()
+            .map(|(lang, source)| {
+                let id: LanguageIdentifier = lang.parse().expect("bundled language ids are valid");
+                let mut bundle = FluentBundle::new_concurrent(vec![id]);
+                // Isolation marks around arguments end up in logs and file
+                // names as stray characters
+                bundle.set_use_isolating(false);
+                let resource = FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, errors)| {
+                    error!("Errors in the {} translations: {:?}", lang, errors);
+                    resource
+                });
+                if let Err(errors) = bundle.add_resource(resource) {
+                    error!("Errors in the {} translations: {:?}", lang, errors);
+                }
+                (*lang, bundle)
+            })
+            .collect()
+    })
+}
+
+/// The bundled language for a language tag or POSIX locale, e.g. "nl-BE" or
+/// "nl_NL.UTF-8" give "nl"; English when there's no translation
+pub fn resolve(lang: &str) -> &'static str {
+    let primary = lang
+        .split(['-', '_', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    LOCALES
+        .iter()
+        .map(|(lang, _)| *lang)
+        .find(|lang| *lang == primary)
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+// The locale the system's messages are in
+fn system_language() -> &'static str {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map_or(DEFAULT_LANGUAGE, |value| resolve(&value))
+}
+
+/// Generate backend messages in `lang`, or the system language when None
+pub fn configure(lang: Option<&str>) {
+    *LANGUAGE.lock().unwrap() = lang.map(str::to_string);
+}
+
+/// Language backend messages are generated in
+pub fn language() -> &'static str {
+    match LANGUAGE.lock().unwrap().as_deref() {
+        Some(lang) => resolve(lang),
+        None => system_language(),
+    }
+}
+
+/// Message `id` in the current language, with `args` filled in
+pub fn t(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    for lang in [language(), DEFAULT_LANGUAGE] {
+        let Some((_, bundle)) = bundles().iter().find(|(l, _)| *l == lang) else {
+            continue;
+        };
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+            continue;
+        };
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        if !errors.is_empty() {
+            warn!("Errors formatting message {}: {:?}", id, errors);
+        }
+        return text.to_string();
+    }
+    warn!("No translation for message {}", id);
+    id.to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LocaleStrings {
+    /// Language the strings are in, which is English when `lang` has no
+    /// translation
+    pub lang: String,
+    /// Fluent source of the translation
+    pub ftl: String,
+    /// English Fluent source, for messages the translation lacks
+    pub fallback_ftl: String,
+    /// Every bundled language
+    pub available: Vec<String>,
+}
+
+/// Translations for the frontend in `lang`, a language tag like "nl" or
+/// "nl-BE"
+#[tauri::command]
+pub fn get_locale_strings(lang: String) -> LocaleStrings {
+    let lang = resolve(&lang);
+    let source = |wanted: &str| {
+        LOCALES
+            .iter()
+            .find(|(lang, _)| *lang == wanted)
+            .map(|(_, source)| source.to_string())
+            .unwrap_or_default()
+    };
+    LocaleStrings {
+        lang: lang.to_string(),
+        ftl: source(lang),
+        fallback_ftl: source(DEFAULT_LANGUAGE),
+        available: LOCALES.iter().map(|(lang, _)| lang.to_string()).collect(),
+    }
+}
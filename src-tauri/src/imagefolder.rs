@@ -0,0 +1,127 @@
+// Batch printing a folder of ready-made artwork or QR PNGs: one label per
+// image, in file name order, all sent as one job.
+use crate::document::{Document, Element, ElementKind, ImageElement};
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::formats;
+use crate::history::JobDetails;
+use crate::ingest;
+use crate::merge;
+use crate::printing::RENDER_DPI;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFit {
+    /// Scaled to fit the label whole, centred
+    #[default]
+    Fit,
+    /// Scaled to cover the label, centred, with the overflow cropped
+    Fill,
+    /// At its own physical size, centred, cropped if larger than the label
+    Center,
+}
+
+// Images directly in `dir`, by file name
+fn list_images(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read folder {}: {}", dir.display(), e))?;
+    let mut images: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ingest::is_image(path))
+        .collect();
+    images.sort_by_key(|path| path.file_name().map(|name| name.to_string_lossy().to_lowercase()));
+    Ok(images)
+}
+
+// A `width_mm` x `height_mm` label showing the image at `path`
+async fn image_file_label(path: &Path, width_mm: f64, height_mm: f64, fit: ImageFit) -> Result<Document, String> {
+    let image = ingest::load(path, Some(RENDER_DPI)).await?;
+    let size = (image.width_mm, image.height_mm);
+    image_label(&image.file_name, &image.bytes, size, width_mm, height_mm, fit)
+}
+
+/// A `width_mm` x `height_mm` label showing an image `size` millimeters
+/// across, placed according to `fit`
+pub fn image_label(
+    file_name: &str,
+    bytes: &[u8],
+    size: (f64, f64),
+    width_mm: f64,
+    height_mm: f64,
+    fit: ImageFit,
+) -> Result<Document, String> {
+    let (image_width, image_height) = size;
+    if image_width <= 0.0 || image_height <= 0.0 {
+        return Err(format!("Image {} is empty", file_name));
+    }
+    let scale = match fit {
+        ImageFit::Fit => (width_mm / image_width).min(height_mm / image_height),
+        ImageFit::Fill => (width_mm / image_width).max(height_mm / image_height),
+        ImageFit::Center => 1.0,
+    };
+    let (w, h) = (image_width * scale, image_height * scale);
+
+    let mut document = Document::new(width_mm, height_mm);
+    let asset_id = document.add_asset(file_name, formats::mime_type_for(file_name), bytes);
+    document.push(Element::new(
+        (width_mm - w) / 2.0,
+        (height_mm - h) / 2.0,
+        w,
+        h,
+        ElementKind::Image(ImageElement {
+            asset_id,
+            adjustments: None,
+        }),
+    ));
+    Ok(document)
+}
+
+/// Print every image directly in `dir` as one batch of
+/// `width_mm` x `height_mm` labels. `op_id` lets `cancel_operation` stop it.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn print_image_folder(
+    fonts: State<'_, FontLibrary>,
+    events: State<'_, EventBus>,
+    dir: String,
+    width_mm: f64,
+    height_mm: f64,
+    fit: Option<ImageFit>,
+    printer_name: Option<String>,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    if width_mm <= 0.0 || height_mm <= 0.0 {
+        return Err("Label size must be positive".to_string());
+    }
+    let images = list_images(Path::new(&dir))?;
+    if images.is_empty() {
+        return Err(format!("No images found in {}", dir));
+    }
+    let fit = fit.unwrap_or_default();
+    let mut documents = Vec::with_capacity(images.len());
+    for path in &images {
+        documents.push(image_file_label(path, width_mm, height_mm, fit).await?);
+    }
+    info!("Printing {} images from {}", documents.len(), dir);
+
+    let printer_name = printer_name.as_deref();
+    let details = JobDetails {
+        template: None,
+        data: images
+            .iter()
+            .map(|path| BTreeMap::from([("file".to_string(), path.to_string_lossy().to_string())]))
+            .collect(),
+    };
+    let work = merge::print_documents(&documents, fonts.database(), printer_name);
+    let job = events.run_job("image_folder", documents.len(), printer_name, details, work);
+    let result = events.run_operation(op_id, job).await?;
+    Ok(match printer_name {
+        Some(printer) => format!("Printed {} labels to {}", documents.len(), printer),
+        None => result,
+    })
+}
@@ -0,0 +1,157 @@
+// Which image converter to run. ImageMagick 7 distributions often ship only
+// `magick`, ImageMagick 6 only `convert`, and some systems only have
+// GraphicsMagick's `gm convert`; they take the same arguments for what
+// Labelgood does. Many distributions also ship an ImageMagick security policy
+// that forbids reading and writing PDF, which fails conversions with a
+// cryptic "not authorized" error.
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::printing::{self, LPSTAT_TIMEOUT};
+use crate::tools;
+use std::path::PathBuf;
+use tokio::process::Command;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// ImageMagick 7
+    Magick,
+    /// ImageMagick 6, or 7 with the legacy commands installed
+    Convert,
+    GraphicsMagick,
+}
+
+impl Tool {
+    /// Program name, as shown to users
+    pub fn program(self) -> &'static str {
+        match self {
+            Tool::Magick => "magick",
+            Tool::Convert => "convert",
+            Tool::GraphicsMagick => "gm",
+        }
+    }
+
+    /// A conversion command, ready for its arguments
+    pub fn command(self) -> Command {
+        let mut command = tools::command(self.program());
+        if self == Tool::GraphicsMagick {
+            command.arg("convert");
+        }
+        command
+    }
+
+    /// Whether the tool understands `-alpha`; GraphicsMagick has no alpha
+    /// options but renders PDFs without transparency anyway
+    pub fn supports_alpha(self) -> bool {
+        self != Tool::GraphicsMagick
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Converter {
+    pub tool: Tool,
+    /// First line of the version output
+    pub version: String,
+    /// Policy file that forbids PDF, when one does
+    pub pdf_blocked_by: Option<PathBuf>,
+}
+
+impl Converter {
+    /// Error for a PDF conversion refused by the security policy
+    pub fn policy_error(&self) -> LabelgoodError {
+        let policy = self
+            .pdf_blocked_by
+            .as_ref()
+            .map_or("policy.xml".to_string(), |path| path.display().to_string());
+        LabelgoodError::PolicyBlocked {
+            program: self.tool.program().to_string(),
+            message: i18n::t("imagemagick-pdf-blocked", &[("policy", policy.into())]),
+        }
+    }
+}
+
+static DETECTED: OnceCell<Converter> = OnceCell::const_new();
+
+/// The first working converter, detected once; not cached while none is
+/// installed, so installing one takes effect without a restart
+pub async fn converter() -> Result<&'static Converter, LabelgoodError> {
+    DETECTED.get_or_try_init(detect).await
+}
+
+async fn detect() -> Result<Converter, LabelgoodError> {
+    let candidates = [
+        (Tool::Magick, "ImageMagick"),
+        (Tool::Convert, "ImageMagick"),
+        (Tool::GraphicsMagick, "GraphicsMagick"),
+    ];
+    for (tool, product) in candidates {
+        let mut command = tools::command(tool.program());
+        command.arg("-version");
+        let missing = |e: std::io::Error| LabelgoodError::Io { message: e.to_string() };
+        let Ok(output) = printing::run_command(command, LPSTAT_TIMEOUT, missing).await else {
+            continue;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // Windows' and other unrelated `convert` commands don't mention
+        // ImageMagick
+        let Some(version) = stdout.lines().find(|line| line.contains(product)) else {
+            continue;
+        };
+        let pdf_blocked_by = if tool == Tool::GraphicsMagick {
+            None
+        } else {
+            pdf_policy(tool).await
+        };
+        let converter = Converter {
+            tool,
+            version: version.trim().to_string(),
+            pdf_blocked_by,
+        };
+        info!("Using {} ({})", tool.program(), converter.version);
+        return Ok(converter);
+    }
+    Err(LabelgoodError::imagemagick_missing(
+        "neither magick, convert nor gm could be run",
+    ))
+}
+
+// The policy file that forbids PDF, from `-list policy` output like
+//
+//   Path: /etc/ImageMagick-6/policy.xml
+//     Policy: Coder
+//       rights: None
+//       pattern: {PS,PS2,PS3,EPS,PDF,XPS}
+async fn pdf_policy(tool: Tool) -> Option<PathBuf> {
+    let mut command = tools::command(tool.program());
+    command.arg("-list").arg("policy");
+    let missing = |e: std::io::Error| LabelgoodError::Io { message: e.to_string() };
+    let output = printing::run_command(command, LPSTAT_TIMEOUT, missing).await.ok()?;
+    blocking_policy(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn blocking_policy(policies: &str) -> Option<PathBuf> {
+    let mut path = None;
+    let mut coder = false;
+    let mut denied = false;
+    for line in policies.lines().map(str::trim) {
+        if let Some(file) = line.strip_prefix("Path:") {
+            path = Some(PathBuf::from(file.trim()));
+        } else if let Some(domain) = line.strip_prefix("Policy:") {
+            coder = domain.trim().eq_ignore_ascii_case("coder");
+            denied = false;
+        } else if let Some(rights) = line.strip_prefix("rights:") {
+            denied = rights.trim().eq_ignore_ascii_case("none");
+        } else if let Some(pattern) = line.strip_prefix("pattern:") {
+            if coder && denied && pattern.to_ascii_uppercase().contains("PDF") {
+                return Some(path.unwrap_or_else(|| PathBuf::from("policy.xml")));
+            }
+        }
+    }
+    None
+}
+
+/// Whether a conversion failed on the security policy rather than the input
+pub fn is_policy_error(stderr: &str) -> bool {
+    stderr.contains("not allowed by the security policy") || stderr.contains("not authorized")
+}
@@ -0,0 +1,265 @@
+// Image ingest: phone photos arrive as HEIC, web assets as WebP or GIF,
+// artwork as PNG, JPEG or SVG. Every imported image is normalized here to
+// PNG, JPEG or SVG, which both the webview and the backend renderer draw,
+// and given a physical size. HEIC is converted by ImageMagick, which needs
+// libheif for it; everything else is decoded in-process. Photos are turned
+// upright by their EXIF orientation, since the renderer ignores it.
+use crate::clipboard::SCREEN_DPI;
+use crate::document::{asset_id, Asset};
+use crate::error::LabelgoodError;
+use crate::formats;
+use crate::imagemagick;
+use crate::printing::{self, CONVERT_TIMEOUT};
+use crate::project::LoadedAsset;
+use crate::svg;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Resolutions below this are camera or screen defaults (cameras write 72)
+/// rather than a size the image was made to print at
+const MIN_PRINT_DPI: f64 = 150.0;
+/// Longest side given to large images without a print resolution
+const DEFAULT_MAX_MM: f64 = 100.0;
+
+/// File extensions `load` accepts
+pub const EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "heic", "heif", "svg"];
+
+/// A normalized image and its physical size
+#[derive(Debug, Clone)]
+pub struct Image {
+    /// Ends in the extension of the normalized format
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+impl Image {
+    pub fn mime_type(&self) -> &'static str {
+        formats::mime_type_for(&self.file_name)
+    }
+
+    pub fn into_asset(self) -> LoadedAsset {
+        LoadedAsset {
+            id: asset_id(&self.bytes),
+            asset: Asset {
+                mime_type: self.mime_type().to_string(),
+                file_name: self.file_name,
+                data: BASE64.encode(&self.bytes),
+            },
+        }
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Whether `path` looks like an image `load` can read
+pub fn is_image(path: &Path) -> bool {
+    EXTENSIONS.contains(&extension(path).as_str())
+}
+
+/// Read and normalize the image at `path`. Rasters with more pixels than
+/// `max_dpi` needs are downscaled.
+pub async fn load(path: &Path, max_dpi: Option<f64>) -> Result<Image, String> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    match extension(path).as_str() {
+        "svg" => {
+            let bytes = fs::read(path).map_err(|e| format!("Failed to read image file: {}", e))?;
+            let (width_mm, height_mm) = svg::intrinsic_size_mm(&bytes)?;
+            Ok(Image {
+                file_name,
+                bytes,
+                width_mm,
+                height_mm,
+            })
+        }
+        "heic" | "heif" => from_raster(&stem, heic_to_png(path).await?, max_dpi),
+        _ => {
+            let bytes = fs::read(path).map_err(|e| format!("Failed to read image file: {}", e))?;
+            from_raster(&stem, bytes, max_dpi)
+        }
+    }
+}
+
+/// Normalize an encoded raster image: PNG and JPEG are kept as they are
+/// unless rotated upright or downscaled, other formats become PNG. Named
+/// `stem` plus the extension of the result.
+pub fn from_raster(stem: &str, bytes: Vec<u8>, max_dpi: Option<f64>) -> Result<Image, String> {
+    let format = image::guess_format(&bytes).map_err(|e| format!("Failed to read image: {}", e))?;
+    let mut decoder = ImageReader::with_format(Cursor::new(&bytes), format)
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let exif = decoder.exif_metadata().ok().flatten();
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| format!("Failed to decode image: {}", e))?;
+    image.apply_orientation(orientation);
+    let upright = orientation == Orientation::NoTransforms;
+
+    let print_dpi = match format {
+        ImageFormat::Png => png_dpi(&bytes),
+        ImageFormat::Jpeg => jpeg_dpi(&bytes),
+        _ => None,
+    }
+    .or_else(|| exif.as_deref().and_then(exif_dpi))
+    .filter(|dpi| *dpi >= MIN_PRINT_DPI);
+    let dpi = print_dpi.unwrap_or_else(|| {
+        let longest = image.width().max(image.height()) as f64;
+        SCREEN_DPI.max(longest / DEFAULT_MAX_MM * 25.4)
+    });
+    let width_mm = image.width() as f64 / dpi * 25.4;
+    let height_mm = image.height() as f64 / dpi * 25.4;
+
+    let (output, extension) = match format {
+        ImageFormat::Jpeg => (ImageFormat::Jpeg, "jpg"),
+        _ => (ImageFormat::Png, "png"),
+    };
+    let file_name = format!("{}.{}", stem, extension);
+    let max_dpi = max_dpi.filter(|max| *max > 0.0 && dpi > *max);
+    if max_dpi.is_none() && output == format && upright {
+        return Ok(Image {
+            file_name,
+            bytes,
+            width_mm,
+            height_mm,
+        });
+    }
+    let image = match max_dpi {
+        Some(max_dpi) => {
+            let scale = max_dpi / dpi;
+            let width = ((image.width() as f64 * scale).round() as u32).max(1);
+            let height = ((image.height() as f64 * scale).round() as u32).max(1);
+            image.resize_exact(width, height, FilterType::Lanczos3)
+        }
+        None => image,
+    };
+    let mut encoded = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut encoded), output)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(Image {
+        file_name,
+        bytes: encoded,
+        width_mm,
+        height_mm,
+    })
+}
+
+// The first image of a HEIC file as PNG, through ImageMagick
+async fn heic_to_png(path: &Path) -> Result<Vec<u8>, String> {
+    let out_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let out_path = out_dir.path().join("image.png");
+    let converter = imagemagick::converter().await?;
+    let mut command = converter.tool.command();
+    command
+        .arg(format!("{}[0]", path.display()))
+        .arg("-auto-orient")
+        .arg(&out_path);
+    let result = printing::run_command(command, CONVERT_TIMEOUT, LabelgoodError::imagemagick_missing).await?;
+    if !result.status.success() {
+        return Err(format!(
+            "Failed to convert HEIC image (ImageMagick needs libheif for it): {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    fs::read(&out_path).map_err(|e| format!("Failed to read converted image: {}", e))
+}
+
+/// Pixels per inch from a PNG's pHYs chunk, when given in metres
+fn png_dpi(bytes: &[u8]) -> Option<f64> {
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let data = bytes.get(pos + 8..pos + 8 + len)?;
+        match &bytes[pos + 4..pos + 8] {
+            b"pHYs" if len == 9 && data[8] == 1 => {
+                let per_metre = u32::from_be_bytes(data[0..4].try_into().ok()?);
+                return (per_metre > 0).then(|| per_metre as f64 * 0.0254);
+            }
+            // pHYs must come before the image data
+            b"IDAT" => return None,
+            _ => pos += 12 + len,
+        }
+    }
+    None
+}
+
+/// Horizontal density from a JPEG's JFIF header
+fn jpeg_dpi(bytes: &[u8]) -> Option<f64> {
+    if bytes.get(0..4)? != [0xFF, 0xD8, 0xFF, 0xE0] {
+        return None;
+    }
+    // Identifier, version, units, then X density
+    let app0 = bytes.get(6..16)?;
+    if &app0[0..5] != b"JFIF\0" {
+        return None;
+    }
+    let density = u16::from_be_bytes([app0[8], app0[9]]) as f64;
+    match app0[7] {
+        1 if density > 0.0 => Some(density),
+        2 if density > 0.0 => Some(density * 2.54),
+        _ => None,
+    }
+}
+
+/// Horizontal resolution in pixels per inch from EXIF data, a TIFF
+/// structure optionally preceded by the JPEG "Exif" marker
+fn exif_dpi(exif: &[u8]) -> Option<f64> {
+    let tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let bytes = tiff.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let bytes = tiff.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    // First image directory: XResolution and ResolutionUnit (inch by default)
+    let ifd = u32_at(4)? as usize;
+    let (mut resolution, mut unit) = (None, 2);
+    for i in 0..u16_at(ifd)? as usize {
+        let entry = ifd + 2 + i * 12;
+        match u16_at(entry)? {
+            0x011A => {
+                let value = u32_at(entry + 8)? as usize;
+                let (numerator, denominator) = (u32_at(value)?, u32_at(value + 4)?);
+                resolution = (denominator > 0).then(|| numerator as f64 / denominator as f64);
+            }
+            0x0128 => unit = u16_at(entry + 8)?,
+            _ => {}
+        }
+    }
+    let resolution = resolution.filter(|r| *r > 0.0)?;
+    match unit {
+        2 => Some(resolution),
+        3 => Some(resolution * 2.54),
+        _ => None,
+    }
+}
@@ -0,0 +1,186 @@
+// Stock decrements for printed product labels: after a job prints, every
+// label of a template with a SKU field takes one off that SKU's stock, by
+// calling a stock system over HTTP or updating a SQLite database directly.
+use crate::history::JobDetails;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Tells apart decrements started in the same instant
+static NEXT_DECREMENT: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InventorySettings {
+    /// Off when absent
+    pub target: Option<InventoryTarget>,
+    /// Template id or label file path -> field holding the SKU. Jobs of
+    /// other templates don't touch stock.
+    pub sku_fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InventoryTarget {
+    /// POST `{"sku": "...", "quantity": 3, "template": "..."}` per SKU,
+    /// with an `Idempotency-Key` header that stays the same when the
+    /// request is retried
+    Http {
+        url: String,
+        /// Extra request headers, e.g. `Authorization`
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// Run `statement` per SKU with `:sku` and `:quantity` bound where it
+    /// uses them, e.g.
+    /// `UPDATE stock SET on_hand = on_hand - :quantity WHERE sku = :sku`
+    Sqlite { path: String, statement: String },
+}
+
+#[derive(Serialize)]
+struct Decrement<'a> {
+    sku: &'a str,
+    quantity: usize,
+    template: &'a str,
+}
+
+/// Labels per SKU in a job of a template with a SKU field
+fn quantities(settings: &InventorySettings, details: &JobDetails) -> BTreeMap<String, usize> {
+    let mut quantities = BTreeMap::new();
+    let Some(field) = details.template.as_ref().and_then(|t| settings.sku_fields.get(t)) else {
+        return quantities;
+    };
+    for record in &details.data {
+        if let Some(sku) = record.get(field).map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            *quantities.entry(sku.to_string()).or_insert(0) += 1;
+        }
+    }
+    quantities
+}
+
+/// Take the labels of a successful job off stock, in the background
+pub fn decrement(settings: InventorySettings, details: &JobDetails) {
+    let Some(target) = settings.target.clone() else {
+        return;
+    };
+    let quantities = quantities(&settings, details);
+    if quantities.is_empty() {
+        return;
+    }
+    let template = details.template.clone().unwrap_or_default();
+
+    std::thread::spawn(move || {
+        let result = match &target {
+            InventoryTarget::Http { url, headers } => post(url, headers, &template, &quantities),
+            InventoryTarget::Sqlite { path, statement } => update(path, statement, &quantities),
+        };
+        match result {
+            Ok(()) => info!("Updated stock of {} SKU(s) printed with {}", quantities.len(), template),
+            Err(e) => error!("Failed to update stock for {}: {}", template, e),
+        }
+    });
+}
+
+// Key the stock system can tell retries of one SKU's decrement apart from
+// new ones by
+fn idempotency_key(sku: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(now.as_nanos().to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(NEXT_DECREMENT.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.update(sku.as_bytes());
+    hasher.finalize()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// One request per SKU, each retried a few times with the same idempotency
+// key, so a stock system being briefly unreachable doesn't lose the
+// decrement and one that applied it but failed to answer doesn't apply it
+// twice
+fn post(
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    template: &str,
+    quantities: &BTreeMap<String, usize>,
+) -> Result<(), String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into();
+    let mut failed = Vec::new();
+    for (sku, quantity) in quantities {
+        let body = Decrement {
+            sku,
+            quantity: *quantity,
+            template,
+        };
+        let key = idempotency_key(sku);
+        for attempt in 1..=ATTEMPTS {
+            let mut request = agent.post(url).header("Idempotency-Key", &key);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            match request.send_json(&body) {
+                Ok(_) => break,
+                Err(e) if attempt < ATTEMPTS => {
+                    warn!("Stock update for {} failed (attempt {}): {}", sku, attempt, e);
+                    std::thread::sleep(RETRY_DELAY * attempt);
+                }
+                Err(e) => failed.push(format!("{}: {}", sku, e)),
+            }
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed.join("; "))
+    }
+}
+
+// All SKUs in one transaction, so a job is taken off stock entirely or not
+// at all
+fn update(path: &str, statement: &str, quantities: &BTreeMap<String, usize>) -> Result<(), String> {
+    let mut connection = Connection::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let transaction = connection
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    {
+        let mut prepared = transaction
+            .prepare(statement)
+            .map_err(|e| format!("Invalid stock statement: {}", e))?;
+        let index = |name: &str| {
+            prepared
+                .parameter_index(name)
+                .map_err(|e| format!("Invalid stock statement: {}", e))
+        };
+        let (sku_index, quantity_index) = (index(":sku")?, index(":quantity")?);
+        let known = sku_index.iter().chain(&quantity_index).count();
+        if prepared.parameter_count() > known {
+            return Err("The stock statement can only use :sku and :quantity".to_string());
+        }
+        for (sku, quantity) in quantities {
+            let bind_error = |e: rusqlite::Error| format!("Failed to update {}: {}", sku, e);
+            if let Some(i) = sku_index {
+                prepared.raw_bind_parameter(i, sku).map_err(bind_error)?;
+            }
+            if let Some(i) = quantity_index {
+                prepared.raw_bind_parameter(i, *quantity as i64).map_err(bind_error)?;
+            }
+            let changed = prepared.raw_execute().map_err(bind_error)?;
+            if changed == 0 {
+                warn!("No stock row for SKU {}", sku);
+            }
+        }
+    }
+    transaction
+        .commit()
+        .map_err(|e| format!("Failed to commit stock update: {}", e))
+}
@@ -0,0 +1,254 @@
+// Kiosk mode for shared workstations: a designer sets up templates and
+// printers, then locks the app so operators can only fill in prompts and
+// print. While locked, commands outside the operator list are refused in the
+// backend, so a modified frontend can't get around it, and settings are
+// handed out with their secrets left out. Unlocking can be protected by a
+// PIN, kept as a salted PBKDF2 hash in the settings; wrong PINs lock
+// unlocking out for longer and longer, also across restarts.
+use crate::i18n;
+use crate::settings::SettingsStore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use tauri::ipc::Invoke;
+use tauri::{Runtime, State};
+use tracing::{info, warn};
+
+/// PBKDF2-HMAC-SHA256 rounds of new PINs, so short PINs take a while to
+/// guess from a copied settings file
+const PBKDF2_ROUNDS: u32 = 600_000;
+/// Marks PIN hashes made with PBKDF2; older ones are "salt$hash" of
+/// `LEGACY_ROUNDS` of SHA-256
+const PBKDF2_PREFIX: &str = "pbkdf2-sha256";
+const LEGACY_ROUNDS: u32 = 100_000;
+
+/// Wrong PINs allowed before unlocking is refused for a while
+const FREE_ATTEMPTS: u32 = 3;
+/// Refusal after the first wrong PIN beyond the free ones, doubling with
+/// every further one up to `MAX_LOCKOUT`
+const LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Commands an operator can use: looking up templates, printers and history,
+/// filling prompts, printing and leaving kiosk mode
+const OPERATOR_COMMANDS: &[&str] = &[
+    "list_printers",
+    "get_printer_status",
+    "test_printer",
+    "get_printer_capabilities",
+    "check_media_fit",
+    "preview_print_output",
+    "get_locale_strings",
+    "get_settings",
+    "list_templates",
+    "instantiate_template",
+    "get_template_thumbnail",
+    "get_project_thumbnail",
+    "open_project",
+    "list_fonts",
+    "get_font_data",
+    "load_data_source",
+    "list_sheets",
+    "get_prompt_fields",
+    "check_prompt_answers",
+    "preview_merge",
+    "get_loaded_tape",
+    "print_merge",
+    "estimate_job_cost",
+    "read_weight",
+    "handle_scan",
+    "list_print_queue",
+    "cancel_job",
+    "cancel_operation",
+    "list_print_history",
+    "reprint_job",
+    "reprint_last",
+    "list_open_orders",
+    "print_orders",
+    "approve_duplicate_print",
+    "get_quota_usage",
+    "get_operator",
+    "set_operator",
+    "scan_operator_badge",
+    "get_document_state",
+    "list_document_windows",
+    "get_kiosk_status",
+    "lock_kiosk",
+    "unlock_kiosk",
+];
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+// One unlock attempt at a time, so wrong PINs are counted before the next
+// is tried
+static UNLOCKING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KioskSettings {
+    /// Locked into operator mode, also after a restart
+    pub enabled: bool,
+    /// "pbkdf2-sha256$rounds$salt$hash" of the unlock PIN; anyone can unlock
+    /// when absent
+    pub pin_hash: Option<String>,
+    /// Wrong PINs in a row
+    pub failed_attempts: u32,
+    /// UTC, RFC 3339; unlocking is refused until then
+    pub locked_out_until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KioskStatus {
+    pub locked: bool,
+    pub pin_required: bool,
+}
+
+pub fn configure(settings: &KioskSettings) {
+    LOCKED.store(settings.enabled, Ordering::Relaxed);
+}
+
+pub fn locked() -> bool {
+    LOCKED.load(Ordering::Relaxed)
+}
+
+/// Wrap the app's command handler so only operator commands run while locked
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        if locked() && !OPERATOR_COMMANDS.contains(&command) {
+            warn!("Refused {} in kiosk mode", command);
+            invoke
+                .resolver
+                .reject(i18n::t("kiosk-command-refused", &[("command", command.into())]));
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_pin(salt: &str, pin: &str, rounds: u32) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(pin.as_bytes(), salt.as_bytes(), rounds, &mut hash);
+    hex(&hash)
+}
+
+fn legacy_hash_pin(salt: &str, pin: &str) -> String {
+    let mut digest = Sha256::digest(format!("{}${}", salt, pin).as_bytes());
+    for _ in 1..LEGACY_ROUNDS {
+        digest = Sha256::digest(digest);
+    }
+    hex(&digest)
+}
+
+// Unique per PIN rather than secret, so the time and process are enough
+fn new_salt() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let digest = Sha256::digest(format!("{}:{}", nanos, std::process::id()).as_bytes());
+    digest.iter().take(16).map(|b| format!("{:02x}", b)).collect()
+}
+
+fn new_pin_hash(pin: &str) -> String {
+    let salt = new_salt();
+    format!("{}${}${}${}", PBKDF2_PREFIX, PBKDF2_ROUNDS, salt, hash_pin(&salt, pin, PBKDF2_ROUNDS))
+}
+
+fn pin_matches(pin_hash: &str, pin: &str) -> bool {
+    let (expected, hash) = match pin_hash.split('$').collect::<Vec<_>>()[..] {
+        [PBKDF2_PREFIX, rounds, salt, hash] => match rounds.parse() {
+            Ok(rounds) => (hash_pin(salt, pin, rounds), hash),
+            Err(_) => return false,
+        },
+        [salt, hash] => (legacy_hash_pin(salt, pin), hash),
+        _ => return false,
+    };
+    expected.as_bytes().ct_eq(hash.as_bytes()).into()
+}
+
+// Hashing takes a while, so it's kept off the async workers
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Failed to check PIN: {}", e))
+}
+
+// How long unlocking is still refused after `kiosk`'s wrong PINs
+fn lockout_remaining(kiosk: &KioskSettings) -> Option<Duration> {
+    let until = DateTime::parse_from_rfc3339(kiosk.locked_out_until.as_deref()?).ok()?;
+    (until.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+// Count a wrong PIN, locking unlocking out once the free attempts are used
+fn record_failure(kiosk: &mut KioskSettings) {
+    kiosk.failed_attempts += 1;
+    if let Some(beyond) = kiosk.failed_attempts.checked_sub(FREE_ATTEMPTS) {
+        let lockout = LOCKOUT.saturating_mul(2u32.saturating_pow(beyond)).min(MAX_LOCKOUT);
+        let until = Utc::now() + chrono::Duration::from_std(lockout).unwrap_or(chrono::Duration::zero());
+        kiosk.locked_out_until = Some(until.to_rfc3339());
+    }
+}
+
+#[tauri::command]
+pub fn get_kiosk_status(store: State<'_, SettingsStore>) -> KioskStatus {
+    KioskStatus {
+        locked: locked(),
+        pin_required: store.get().kiosk.pin_hash.is_some(),
+    }
+}
+
+/// Switch to operator mode, requiring `pin` to leave it when given
+#[tauri::command]
+pub async fn lock_kiosk(store: State<'_, SettingsStore>, pin: Option<String>) -> Result<(), String> {
+    if locked() {
+        return Ok(());
+    }
+    let pin_hash = match pin.filter(|p| !p.is_empty()) {
+        Some(pin) => Some(blocking(move || new_pin_hash(&pin)).await?),
+        None => None,
+    };
+    let mut settings = store.get();
+    settings.kiosk = KioskSettings {
+        enabled: true,
+        pin_hash,
+        ..KioskSettings::default()
+    };
+    let kiosk = settings.kiosk.clone();
+    store.set(settings)?;
+    configure(&kiosk);
+    info!("Locked into kiosk mode");
+    Ok(())
+}
+
+/// Leave operator mode, checking the PIN it was locked with
+#[tauri::command]
+pub async fn unlock_kiosk(store: State<'_, SettingsStore>, pin: Option<String>) -> Result<(), String> {
+    let _unlocking = UNLOCKING.lock().await;
+    let mut settings = store.get();
+    if let Some(pin_hash) = settings.kiosk.pin_hash.clone() {
+        if let Some(remaining) = lockout_remaining(&settings.kiosk) {
+            return Err(i18n::t("kiosk-locked-out", &[("seconds", remaining.as_secs().max(1).into())]));
+        }
+        let pin = pin.unwrap_or_default();
+        if !blocking(move || pin_matches(&pin_hash, &pin)).await? {
+            warn!("Wrong PIN to leave kiosk mode");
+            record_failure(&mut settings.kiosk);
+            store.set(settings)?;
+            return Err(i18n::t("kiosk-wrong-pin", &[]));
+        }
+    }
+    settings.kiosk = KioskSettings::default();
+    store.set(settings)?;
+    configure(&KioskSettings::default());
+    info!("Left kiosk mode");
+    Ok(())
+}
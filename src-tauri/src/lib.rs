@@ -1,14 +1,126 @@
+mod adjustments;
+mod alignment;
+mod api;
+mod audit;
+mod backends;
+mod background;
+mod banner;
+mod barcodes;
+mod calibration;
+mod capabilities;
+pub mod cli;
+mod clipboard;
+mod color;
+mod console;
+mod connectivity;
+mod contacts;
+mod costs;
+mod counters;
+mod credentials;
+mod datefields;
+mod diagnostics;
+mod document;
+mod duplicates;
+mod dragdrop;
+mod emulator;
+mod error;
+mod events;
+mod fonts;
+mod formats;
+mod ghs;
+mod groups;
+mod grpc;
+mod handling;
+mod history;
+mod hotfolder;
+mod i18n;
+mod imagefolder;
+mod imagemagick;
+mod ingest;
+mod inventory;
+mod kiosk;
+mod logging;
+mod markdown;
+mod media;
+mod merge;
+mod numbering;
+mod ocr;
+mod nutrition;
+mod operators;
+mod orders;
+mod mqtt;
+mod offline;
+mod pdf;
+mod pdfexport;
+mod pdfpreview;
+mod pdfprint;
+mod placeholders;
+mod plugins;
+mod portable;
+mod printerinfo;
+mod printing;
+mod progress;
+mod project;
+mod prompts;
+mod queue;
+mod quotas;
+mod raster;
+mod render;
+mod rfid;
+mod rules;
+mod sandbox;
+mod sane;
+mod scale;
+mod scanprint;
+mod scheduler;
+mod scripting;
+mod settings;
+mod share;
+mod shipping;
+mod shortcuts;
+mod shortener;
+mod spelling;
+mod spool;
+mod statistics;
+mod svg;
+mod symbols;
+mod tape;
+mod templates;
+#[cfg(feature = "mock-printer")]
+pub mod testing;
+mod thumbnails;
+mod tools;
+mod translation;
+mod tray;
+mod updates;
+mod uploads;
+mod versions;
+mod virtual_printer;
+mod watermark;
+mod webhooks;
+mod windows;
+mod zpl_preview;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use error::LabelgoodError;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::process::Command;
-use tempfile::Builder;
+use tauri::{Manager, State};
+use tempfile::{Builder, NamedTempFile};
+use tracing::{error, info};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PrintOptions {
+    #[serde(default)]
     image_data: String, // Base64 encoded PNG image
+    #[serde(default)]
+    upload_id: Option<String>, // PNG sent in chunks through append_upload, instead of image_data
     width_mm: f64,
     height_mm: f64,
     printer_name: Option<String>, // If provided, send to printer instead of opening PDF
+    #[serde(default)]
+    op_id: Option<String>, // Lets cancel_operation stop this print
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -17,163 +129,477 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Printers of every backend: CUPS queues, directly attached USB printers,
+/// plugin drivers and the virtual printer, then printer groups
 #[tauri::command]
-fn list_printers() -> Result<Vec<String>, String> {
-    // Use lpstat -e to list all printers (including wireless/network printers)
-    let output = Command::new("lpstat")
-        .arg("-e")
-        .output()
-        .map_err(|e| format!("Failed to execute lpstat: {}", e))?;
-
-    if !output.status.success() {
-        return Err("Failed to get printer list".to_string());
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let printers: Vec<String> = stdout
-        .lines()
-        .map(|line| line.trim().to_string())
-        .filter(|line| !line.is_empty())
-        .collect();
-
+async fn list_printers() -> Result<Vec<String>, LabelgoodError> {
+    let mut printers = backends::enumerate().await?;
+    printers.extend(groups::names());
     Ok(printers)
 }
 
 #[tauri::command]
-async fn generate_pdf(options: PrintOptions) -> Result<String, String> {
-    // Decode base64 image data
-    let image_data = options.image_data
-        .strip_prefix("data:image/png;base64,")
-        .unwrap_or(&options.image_data);
-
-    let image_bytes = base64::decode(image_data)
-        .map_err(|e| format!("Failed to decode base64 image: {}", e))?;
+async fn generate_pdf(
+    events: State<'_, events::EventBus>,
+    uploads: State<'_, uploads::Uploads>,
+    fonts: State<'_, fonts::FontLibrary>,
+    options: PrintOptions,
+) -> Result<String, LabelgoodError> {
+    LabelgoodError::check_dimensions(options.width_mm, options.height_mm)?;
+    let temp_png = label_png(&uploads, &fonts, &options)?;
 
-    // Create temporary PNG file
-    let temp_png = Builder::new()
-        .suffix(".png")
-        .tempfile()
-        .map_err(|e| format!("Failed to create temp PNG file: {}", e))?;
-    let png_path = temp_png.path().to_string_lossy().to_string();
-
-    fs::write(&png_path, &image_bytes)
-        .map_err(|e| format!("Failed to write PNG file: {}", e))?;
-
-    // Create persistent PDF file in temp directory
-    let temp_dir = std::env::temp_dir();
-    let pdf_filename = format!("label_{}.pdf", std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis());
-    let pdf_path = temp_dir.join(pdf_filename);
-    let pdf_path_str = pdf_path.to_string_lossy().to_string();
-
-    println!("Generated PDF path: {}", pdf_path_str);
-
-    // Use ImageMagick to convert PNG to PDF with exact dimensions
     // The PNG comes from frontend at 300 DPI with pixel dimensions calculated as:
     //   targetWidthPx = labelWidthMm * (300 / 25.4)
     //   targetHeightPx = labelHeightMm * (300 / 25.4)
-    // 
-    // Critical: We need to preserve the 300 DPI throughout the conversion
-    let width_points = options.width_mm * 2.83465; // mm to points (1mm = 2.83465pt)
-    let height_points = options.height_mm * 2.83465;
-    
-    println!("Creating PDF: {}x{} mm ({}x{} points)", 
-             options.width_mm, options.height_mm,
-             width_points as u32, height_points as u32);
+    let printer_name = options.printer_name.as_deref();
+    let work = printing::print_png(temp_png.path(), options.width_mm, options.height_mm, printer_name);
+    let job = events.run_job("app", 1, printer_name, history::JobDetails::default(), work);
+    events.run_operation(options.op_id.clone(), job).await
+}
 
-    // Convert PNG to PDF preserving dimensions:
-    // The key is to read at 300 DPI and write at 72 DPI (PDF standard)
-    // This causes ImageMagick to scale appropriately
-    let result = Command::new("convert")
-        .arg(&png_path)
-        .arg("-density").arg("300") // Input PNG is at 300 DPI
-        .arg("-units").arg("PixelsPerInch")
-        .arg("-density").arg("72") // Output PDF at 72 DPI (standard)
-        .arg("-page").arg(format!("{}x{}", width_points as u32, height_points as u32)) // Set PDF page size in points
-        .arg(&pdf_path_str)
-        .output();
+/// Render the label to a PDF and save it to `path`, or to where the user
+/// picks in a save dialog when no path is given. Returns where it was
+/// saved, or None when the dialog was cancelled.
+#[tauri::command]
+async fn export_pdf(
+    app: tauri::AppHandle,
+    uploads: State<'_, uploads::Uploads>,
+    fonts: State<'_, fonts::FontLibrary>,
+    options: PrintOptions,
+    path: Option<String>,
+) -> Result<Option<String>, LabelgoodError> {
+    LabelgoodError::check_dimensions(options.width_mm, options.height_mm)?;
+    let temp_png = label_png(&uploads, &fonts, &options)?;
+    let pdf_path = printing::images_to_pdf(&[temp_png.path()], options.width_mm, options.height_mm).await?;
+    let saved = pdfexport::save_as(&app, &pdf_path, path).await;
+    let _ = fs::remove_file(&pdf_path);
+    saved
+}
 
-    match result {
-        Ok(output) if output.status.success() => {
-            println!("PDF generated successfully at: {}", pdf_path_str);
+/// Send the label as a PDF or PNG proof: to the share sheet on phones and
+/// tablets, or attached to a new email on desktop
+#[tauri::command]
+async fn share_label(
+    uploads: State<'_, uploads::Uploads>,
+    fonts: State<'_, fonts::FontLibrary>,
+    options: PrintOptions,
+    share: share::ShareOptions,
+) -> Result<share::ShareResult, LabelgoodError> {
+    LabelgoodError::check_dimensions(options.width_mm, options.height_mm)?;
+    let temp_png = label_png(&uploads, &fonts, &options)?;
+    let (source, extension) = match share.format {
+        share::ShareFormat::Pdf => (
+            printing::images_to_pdf(&[temp_png.path()], options.width_mm, options.height_mm).await?,
+            "pdf",
+        ),
+        share::ShareFormat::Png => (temp_png.path().to_path_buf(), "png"),
+    };
+    // Kept after this returns, since the mail app reads it later, and named
+    // for the recipient rather than the spool directory
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let proof = spool::path(&format!("label-proof-{}.{}", stamp, extension));
+    fs::copy(&source, &proof)
+        .map_err(|e| LabelgoodError::Io { message: format!("Failed to write {}: {}", proof.display(), e) })?;
+    if share.format == share::ShareFormat::Pdf {
+        let _ = fs::remove_file(&source);
+    }
+    share::share(&proof, &share).await
+}
 
-            // If printer_name is provided, send to printer
-            if let Some(printer_name) = &options.printer_name {
-                // Verify PDF exists
-                if !std::path::Path::new(&pdf_path_str).exists() {
-                    return Err(format!("PDF file does not exist at: {}", pdf_path_str));
-                }
+/// The label as the selected printer would print it: converted to PDF and
+/// rasterized like a print job, then scaled to the printer's resolution and
+/// dithered to black and white, or to the ink on the tape of the cassette
+/// a P-touch reports. Nothing is sent to the printer; an upload given by
+/// `upload_id` is used up.
+#[tauri::command]
+async fn preview_print_output(
+    uploads: State<'_, uploads::Uploads>,
+    fonts: State<'_, fonts::FontLibrary>,
+    options: PrintOptions,
+) -> Result<raster::PrintRaster, LabelgoodError> {
+    LabelgoodError::check_dimensions(options.width_mm, options.height_mm)?;
+    let temp_png = label_png(&uploads, &fonts, &options)?;
+
+    let png = if cfg!(mobile) {
+        // No ImageMagick to rasterize the PDF with, but the page is this
+        // same image
+        fs::read(temp_png.path())
+            .map_err(|e| LabelgoodError::Io { message: format!("Failed to read PNG file: {}", e) })?
+    } else {
+        let pdf_path = printing::images_to_pdf(&[temp_png.path()], options.width_mm, options.height_mm).await?;
+        let pages = printing::rasterize_pdf(&pdf_path, Some(0)).await;
+        let _ = fs::remove_file(&pdf_path);
+        pages?.swap_remove(0)
+    };
+    let Some(printer) = options.printer_name.as_deref().map(groups::primary) else {
+        let page = raster::print_raster(&png, printing::RENDER_DPI)?;
+        return Ok(raster::to_preview(&page, printing::RENDER_DPI)?);
+    };
+    let dpi = backends::resolve(&printer).capabilities(&printer).await.dpi;
+    // Colour printers aren't dithered
+    if let Some(color) = color::for_printer(&printer) {
+        return Ok(color::preview(&png, dpi, &color)?);
+    }
+    let page = raster::print_raster(&png, dpi)?;
+    // On the cassette's tape, in its ink, when a P-touch says what's loaded
+    match tape::loaded_tape(&printer).await {
+        Some(loaded) => {
+            let warnings = tape::image_warnings(&png, &loaded)?;
+            Ok(tape::preview(&page, dpi, loaded, warnings)?)
+        }
+        None => Ok(raster::to_preview(&page, dpi)?),
+    }
+}
 
-                // Print with lpr using the exact page dimensions from the PDF
-                // The PDF already has the correct page size, but we need to tell
-                // CUPS/lpr to use that size and not fit it to A4 or other default sizes
-                
-                // Calculate dimensions in different units for CUPS compatibility
-                let width_mm = options.width_mm as u32;
-                let height_mm = options.height_mm as u32;
-                
-                println!("Printing to: {}", printer_name);
-                println!("PDF path: {}", pdf_path_str);
-                println!("Label dimensions: {}mm x {}mm", width_mm, height_mm);
+// The label image of `options` as a PNG temp file, from an upload or the
+// inline image data, with the watermark and operator footer when they're on
+fn label_png(
+    uploads: &uploads::Uploads,
+    fonts: &fonts::FontLibrary,
+    options: &PrintOptions,
+) -> Result<NamedTempFile, LabelgoodError> {
+    let temp_png = match &options.upload_id {
+        // Already on disk; ImageMagick reads it from there
+        Some(id) => uploads.take_png(id)?,
+        None => {
+            let image_bytes = decode_png(&options.image_data)?;
 
-                // Use PageSize with dimensions in mm - this is more universally supported
-                let page_size = format!("PageSize=Custom.{}x{}mm", width_mm, height_mm);
-                
-                let print_result = Command::new("lpr")
-                    .arg("-P").arg(printer_name)
-                    .arg("-o").arg(&page_size)
-                    .arg("-o").arg("fit-to-page=false")
-                    .arg(&pdf_path_str)
-                    .output();
+            // Create temporary PNG file
+            let temp_png = Builder::new()
+                .suffix(".png")
+                .tempfile()
+                .map_err(|e| LabelgoodError::Io { message: format!("Failed to create temp PNG file: {}", e) })?;
+            fs::write(temp_png.path(), &image_bytes)
+                .map_err(|e| LabelgoodError::Io { message: format!("Failed to write PNG file: {}", e) })?;
+            temp_png
+        }
+    };
+    if let Some(overlays) = render::overlays(options.width_mm, options.height_mm) {
+        let png = fs::read(temp_png.path())
+            .map_err(|e| LabelgoodError::Io { message: format!("Failed to read PNG file: {}", e) })?;
+        let stamped = render::stamp_png(&png, &overlays, fonts.database(), options.width_mm, options.height_mm)
+            .map_err(|message| LabelgoodError::RenderFailed { message })?;
+        fs::write(temp_png.path(), stamped)
+            .map_err(|e| LabelgoodError::Io { message: format!("Failed to write PNG file: {}", e) })?;
+    }
+    Ok(temp_png)
+}
 
-                match print_result {
-                    Ok(print_output) if print_output.status.success() => {
-                        println!("Sent to printer: {}", printer_name);
-                        return Ok(format!("Printed to {}", printer_name));
-                    }
-                    Ok(print_output) => {
-                        let stdout = String::from_utf8_lossy(&print_output.stdout);
-                        let stderr = String::from_utf8_lossy(&print_output.stderr);
-                        eprintln!("lpr stdout: {}", stdout);
-                        eprintln!("lpr stderr: {}", stderr);
-                        return Err(format!("Failed to print: {}", stderr));
-                    }
-                    Err(e) => {
-                        return Err(format!("Failed to execute lpr command: {}", e));
-                    }
-                }
-            } else {
-                // Open the PDF with the system default application (cross-platform)
-                opener::open(&pdf_path_str)
-                    .map_err(|e| format!("Failed to open PDF: {}", e))?;
-                return Ok(pdf_path_str);
+// PNG bytes from a base64 string or PNG data URL, checked before anything
+// is written or converted
+fn decode_png(image_data: &str) -> Result<Vec<u8>, LabelgoodError> {
+    let invalid = |message: String| LabelgoodError::InvalidInput { message };
+    let image_data = match image_data.strip_prefix("data:") {
+        Some(url) => {
+            let (media_type, data) = url
+                .split_once(";base64,")
+                .ok_or_else(|| invalid(i18n::t("image-not-base64", &[])))?;
+            if media_type != "image/png" {
+                return Err(invalid(i18n::t("image-not-png-type", &[("type", media_type.into())])));
             }
+            data
         }
-        Ok(output) => {
-            let error = String::from_utf8_lossy(&output.stderr);
-            eprintln!("ImageMagick convert failed. stderr: {}", error);
-            eprintln!("ImageMagick stdout: {}", String::from_utf8_lossy(&output.stdout));
-            return Err(format!("ImageMagick convert failed: {}. Make sure ImageMagick is installed.", error));
-        }
-        Err(e) => {
-            return Err(format!(
-                "ImageMagick not found: {}. Please install ImageMagick:\n\
-                 - Fedora: sudo dnf install ImageMagick\n\
-                 - Ubuntu/Debian: sudo apt install imagemagick\n\
-                 - Arch: sudo pacman -S imagemagick", e
-            ));
-        }
+        None => image_data,
+    };
+    if image_data.trim().is_empty() {
+        return Err(invalid(i18n::t("image-missing", &[])));
+    }
+
+    let image_bytes = BASE64.decode(image_data.trim())
+        .map_err(|e| invalid(i18n::t("image-bad-base64", &[("error", e.to_string().into())])))?;
+    if !image_bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Err(invalid(i18n::t("image-not-png", &[])));
     }
+    Ok(image_bytes)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![greet, generate_pdf, list_printers])
+        .register_asynchronous_uri_scheme_protocol(pdfpreview::SCHEME, pdfpreview::protocol)
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(shortcuts::handle)
+                .build(),
+        )
+        .setup(|app| {
+            // First, so everything after it is logged
+            app.manage(logging::init(&portable::app_data_dir(app.handle())?.join("logs")));
+            if let Some(dir) = portable::data_dir() {
+                info!("Running portable, keeping data in {}", dir.display());
+            }
+            if let Some(sandbox) = sandbox::detect() {
+                info!("Running in {}, printing to system printers through the print portal", sandbox.name());
+            }
+            diagnostics::log_problems();
+            app.manage(uploads::Uploads::default());
+            let config_dir = portable::app_config_dir(app.handle())?;
+            let settings = settings::SettingsStore::load(config_dir.join("settings.json"));
+            tools::configure(settings.get().tool_paths);
+            let watcher = templates::TemplateWatcher::default();
+            if let Some(dir) = settings.get().shared_templates_dir {
+                // A missing share shouldn't stop the app from starting
+                if let Err(e) = watcher.watch(app.handle(), Some(std::path::Path::new(&dir))) {
+                    error!("{}", e);
+                }
+            }
+            let initial = settings.get();
+            app.manage(events::EventBus::new(app.handle().clone()));
+            events::start_printer_monitor(app.handle().clone());
+            let scripts = scripting::ScriptHooks::new(app.handle().clone());
+            if let Err(e) = scripts.load(initial.hooks_script.as_deref().map(std::path::Path::new)) {
+                error!("{}", e);
+            }
+            app.manage(scripts);
+            let scale = scale::Scale::default();
+            scale.configure(initial.scale.clone());
+            app.manage(scale);
+            queue::configure(initial.queue.clone());
+            groups::configure(initial.printer_groups.clone());
+            backends::registered::configure(initial.network_printers.clone());
+            handling::configure(initial.label_handling.clone());
+            alignment::configure(initial.alignment.clone());
+            spool::configure(initial.spool.clone());
+            spool::start_cleanup();
+            history::configure(initial.history.clone());
+            shortener::configure(initial.url_shortener.clone());
+            pdfpreview::configure(initial.preview.external_viewer);
+            color::configure(initial.color_printers.clone());
+            barcodes::verify::configure(initial.verify_barcodes);
+            fonts::configure_emoji(initial.monochrome_emoji);
+            watermark::configure(initial.watermark.clone());
+            operators::configure(initial.operators.clone(), initial.operator_name.clone());
+            i18n::configure(initial.language.as_deref());
+            kiosk::configure(&initial.kiosk);
+            virtual_printer::configure(app.handle(), initial.virtual_printer_dir.as_deref());
+            backends::file::configure(initial.file_output_dirs.clone());
+            #[cfg(mobile)]
+            backends::mobile::init(app.handle());
+            app.manage(scanprint::ScanStation::default());
+            scripting::start_after_print_hook(app.handle().clone());
+            app.manage(settings);
+            app.manage(watcher);
+            let data_dir = portable::app_data_dir(app.handle())?;
+            plugins::init(&data_dir.join("plugins"));
+            app.manage(fonts::FontLibrary::load(data_dir.join("fonts")));
+            app.manage(counters::CounterStore::load(data_dir.join("counters.json")));
+            app.manage(spelling::UserDictionary::load(data_dir.join("dictionary.txt")));
+            app.manage(scheduler::ScheduleStore::load(data_dir.join("schedules.json")));
+            app.manage(history::HistoryStore::open(&data_dir.join("history.sqlite")));
+            shortener::init(&data_dir.join("history.sqlite"));
+            offline::init(&data_dir.join("offline.sqlite"));
+            media::init(&data_dir.join("media.json"));
+            templates::init(&data_dir.join("templates"));
+            app.manage(audit::AuditLog::open(&data_dir.join("audit.sqlite")));
+            app.manage(contacts::ContactStore::open(&data_dir.join("contacts.sqlite")));
+            app.manage(thumbnails::Thumbnails::new(data_dir.join("thumbnails")));
+            app.manage(windows::WindowManager::new(data_dir.join("autosave")));
+            windows::start_autosave(app.handle().clone());
+            // Started last, since processing files needs the fonts and counters
+            let hot_folder = hotfolder::HotFolder::default();
+            if let Err(e) = hot_folder.start(app.handle(), initial.hot_folder.clone()) {
+                error!("{}", e);
+            }
+            app.manage(hot_folder);
+            let api = api::ApiServer::default();
+            if let Err(e) = tauri::async_runtime::block_on(api.start(app.handle(), &initial.api)) {
+                error!("{}", e);
+            }
+            app.manage(api);
+            let grpc = grpc::GrpcServer::default();
+            if let Err(e) = grpc.start(app.handle(), &initial.grpc) {
+                error!("{}", e);
+            }
+            app.manage(grpc);
+            let mqtt = mqtt::MqttBridge::default();
+            if let Err(e) = mqtt.start(app.handle(), initial.mqtt.clone()) {
+                error!("{}", e);
+            }
+            app.manage(mqtt);
+            scheduler::start_scheduler(app.handle().clone());
+            if initial.check_for_updates {
+                updates::check_at_startup(app.handle().clone());
+            }
+            emulator::start(app.handle());
+            if let Err(e) = tray::create(app.handle()) {
+                error!("{}", e);
+            }
+            if let Err(e) = shortcuts::register(app.handle(), &initial.shortcuts) {
+                error!("{}", e);
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, position }) = event {
+                dragdrop::handle_drop(window, paths.clone(), *position);
+            }
+            windows::handle_event(window, event);
+        })
+        .invoke_handler(kiosk::guard(tauri::generate_handler![
+            greet,
+            generate_pdf,
+            export_pdf,
+            pdfexport::export_last_pdf,
+            share_label,
+            preview_print_output,
+            i18n::get_locale_strings,
+            capabilities::get_printer_capabilities,
+            capabilities::check_media_fit,
+            backends::get_printer_status,
+            backends::cancel_printer_job,
+            credentials::set_printer_credentials,
+            credentials::delete_printer_credentials,
+            credentials::get_printer_username,
+            offline::list_held_jobs,
+            offline::discard_held_job,
+            offline::release_held_jobs,
+            media::list_media_rolls,
+            media::load_media_roll,
+            media::remove_media_roll,
+            handling::supported_label_handling,
+            calibration::calibrate_media,
+            printerinfo::get_printer_info,
+            console::send_raw,
+            costs::estimate_job_cost,
+            costs::get_cost_report,
+            statistics::get_usage_statistics,
+            uploads::begin_upload,
+            uploads::append_upload,
+            uploads::discard_upload,
+            list_printers,
+            events::cancel_job,
+            events::cancel_operation,
+            queue::list_print_queue,
+            queue::set_queue_priority,
+            queue::move_to_front_of_queue,
+            diagnostics::check_environment,
+            formats::import_document,
+            formats::definition::compile_label_definition,
+            ocr::recognize_label,
+            formats::export_document,
+            formats::export_zpl_template,
+            zpl_preview::preview_zpl,
+            templates::list_templates,
+            templates::instantiate_template,
+            templates::pack::export_template_pack,
+            templates::pack::import_template_pack,
+            thumbnails::get_project_thumbnail,
+            thumbnails::get_template_thumbnail,
+            thumbnails::clear_thumbnails,
+            contacts::list_contacts,
+            contacts::get_contact,
+            contacts::save_contact,
+            contacts::delete_contact,
+            contacts::import_contacts,
+            contacts::print_contact_label,
+            ghs::list_ghs_statements,
+            ghs::get_ghs_pictogram,
+            ghs::compose_ghs_label,
+            markdown::render_markdown_label,
+            symbols::list_assets,
+            symbols::get_asset,
+            spelling::check_text,
+            spelling::list_dictionary_words,
+            spelling::add_dictionary_word,
+            spelling::remove_dictionary_word,
+            settings::get_settings,
+            settings::set_settings,
+            project::save_project,
+            project::open_project,
+            project::load_image_asset,
+            svg::import_svg,
+            svg::render_svg_asset,
+            adjustments::preview_image_adjustments,
+            background::remove_background,
+            sane::list_scanners,
+            sane::scan_image,
+            fonts::list_fonts,
+            fonts::add_font_file,
+            fonts::get_font_data,
+            merge::load_data_source,
+            merge::list_sheets,
+            merge::validate_placeholders,
+            prompts::get_prompt_fields,
+            prompts::check_prompt_answers,
+            merge::preview_merge,
+            merge::print_merge,
+            imagefolder::print_image_folder,
+            pdfprint::print_pdf_file,
+            banner::split_banner,
+            banner::print_banner,
+            counters::list_counters,
+            counters::set_counter,
+            counters::delete_counter,
+            counters::next_serial,
+            scheduler::list_schedules,
+            scheduler::set_schedule,
+            scheduler::delete_schedule,
+            scheduler::run_schedule_now,
+            datefields::resolve_date_fields,
+            barcodes::generate_barcode,
+            barcodes::build_qr_payload,
+            barcodes::compose_udi,
+            barcodes::compose_digital_link,
+            barcodes::compose_variable_measure,
+            barcodes::parse_gs1,
+            barcodes::compute_check_digit,
+            barcodes::verify_check_digit,
+            plugins::list_plugins,
+            scale::read_weight,
+            scale::list_serial_ports,
+            scanprint::register_scan_station,
+            scanprint::clear_scan_station,
+            scanprint::handle_scan,
+            clipboard::paste_image_from_clipboard,
+            history::list_print_history,
+            history::reprint_job,
+            history::reprint_last,
+            history::clear_print_history,
+            audit::list_audit_log,
+            audit::export_audit_log,
+            audit::verify_audit_log,
+            shipping::preview_shipping_label,
+            shipping::print_shipping_label,
+            logging::get_recent_logs,
+            windows::open_document_window,
+            windows::get_document_state,
+            windows::set_document_state,
+            windows::list_document_windows,
+            windows::close_window,
+            windows::list_autosaves,
+            windows::discard_autosave,
+            kiosk::get_kiosk_status,
+            kiosk::lock_kiosk,
+            kiosk::unlock_kiosk,
+            tools::locate_tools,
+            updates::check_for_updates,
+            duplicates::approve_duplicate_print,
+            quotas::get_quota_usage,
+            operators::get_operator,
+            operators::set_operator,
+            operators::scan_operator_badge,
+            alignment::print_alignment_pattern,
+            alignment::save_alignment,
+            tape::get_loaded_tape,
+            orders::list_open_orders,
+            orders::print_orders,
+            shortener::list_short_urls,
+            translation::translate_text,
+            backends::add_printer,
+            connectivity::test_printer,
+            pdfpreview::get_pdf_preview,
+            pdfpreview::open_pdf_preview_externally,
+            color::describe_color_profile,
+            versions::save_version,
+            versions::list_versions,
+            versions::get_version_thumbnail,
+            versions::compare_versions,
+            versions::restore_version,
+            versions::delete_version
+        ]))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,105 @@
+// Diagnostics log: everything the backend reports goes to stdout and to a
+// daily rotating file in the app data directory, so a failed print can be
+// explained after the fact
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+const FILE_PREFIX: &str = "labelgood";
+const FILE_SUFFIX: &str = "log";
+/// Days of logs kept
+const MAX_LOG_FILES: usize = 7;
+const DEFAULT_RECENT_LINES: usize = 500;
+
+/// Where logs are written. Holds the background writer, which flushes when
+/// dropped.
+pub struct LogFiles {
+    dir: PathBuf,
+    _guard: Option<WorkerGuard>,
+}
+
+/// Log to stdout and to files in `dir`. `RUST_LOG` overrides the default
+/// `info` level. Falls back to stdout only when the directory can't be used.
+pub fn init(dir: &Path) -> LogFiles {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let appender = fs::create_dir_all(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(FILE_PREFIX)
+                .filename_suffix(FILE_SUFFIX)
+                .max_log_files(MAX_LOG_FILES)
+                .build(dir)
+                .map_err(|e| e.to_string())
+        });
+
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer());
+    match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let file_layer = fmt::layer().with_writer(writer).with_ansi(false);
+            let _ = registry.with(file_layer).try_init();
+            LogFiles {
+                dir: dir.to_path_buf(),
+                _guard: Some(guard),
+            }
+        }
+        Err(e) => {
+            let _ = registry.try_init();
+            tracing::error!("Failed to open log directory {}: {}", dir.display(), e);
+            LogFiles {
+                dir: dir.to_path_buf(),
+                _guard: None,
+            }
+        }
+    }
+}
+
+/// Log to stderr only, for command line use where stdout is the output
+pub fn init_stderr() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init();
+}
+
+/// The last `lines` log lines (500 by default), oldest first, across the
+/// most recent log files
+#[tauri::command]
+pub async fn get_recent_logs(logs: State<'_, LogFiles>, lines: Option<usize>) -> Result<String, String> {
+    let wanted = lines.unwrap_or(DEFAULT_RECENT_LINES);
+    let mut files: Vec<PathBuf> = fs::read_dir(&logs.dir)
+        .map_err(|e| format!("Failed to read log directory: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().starts_with(FILE_PREFIX))
+                .unwrap_or(false)
+        })
+        .collect();
+    // Names end in the date, so newest sorts last
+    files.sort();
+
+    let mut recent: Vec<String> = Vec::new();
+    for path in files.iter().rev() {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read log file: {}", e))?;
+        let mut file_lines: Vec<String> = text.lines().map(str::to_string).collect();
+        let take = (wanted - recent.len()).min(file_lines.len());
+        let mut tail = file_lines.split_off(file_lines.len() - take);
+        tail.append(&mut recent);
+        recent = tail;
+        if recent.len() >= wanted {
+            break;
+        }
+    }
+    Ok(recent.join("\n"))
+}
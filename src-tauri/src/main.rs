@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if labelgood_lib::cli::is_cli_invocation(&args) {
+        std::process::exit(labelgood_lib::cli::run(&args));
+    }
     labelgood_lib::run()
 }
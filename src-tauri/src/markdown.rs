@@ -0,0 +1,297 @@
+// Markdown snippets rendered to a label image, for text-heavy labels like
+// spice jars and storage boxes that don't need a hand-made layout. Covers
+// what fits on a label: headings, paragraphs, bullet and numbered lists,
+// **bold** (or __bold__) and *italic*. The label is as wide as asked and as
+// tall as its text.
+use crate::document::{Document, Element, ElementKind, ImageElement};
+use crate::fonts::FontLibrary;
+use crate::formats::{escape, MM_PER_PT};
+use crate::printing::RENDER_DPI;
+use crate::render::{self, ASCENT, LINE_HEIGHT};
+use std::fmt::Write;
+use tauri::State;
+
+const MARGIN_MM: f64 = 2.0;
+const DEFAULT_FONT_PT: f64 = 10.0;
+/// Average character width as a fraction of the font size, for wrapping
+const CHAR_WIDTH: f64 = 0.52;
+/// Space after a heading, paragraph or list item, in lines
+const BLOCK_GAP: f64 = 0.4;
+/// Size of # to ### headings relative to the text; smaller ones match ###
+const HEADING_SCALE: [f64; 3] = [1.6, 1.35, 1.15];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    /// Marker ("•" or "3.") and text
+    Item(String, String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+}
+
+// Text in one style; a word is one or more runs, as in "**Salt**:"
+type Run = (String, Style);
+
+fn parse_blocks(markdown: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    // Lines right after a list item continue it
+    let mut in_item = false;
+    let flush = |blocks: &mut Vec<Block>, paragraph: &mut Vec<&str>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+    };
+
+    for line in markdown.lines() {
+        let line = line.trim();
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if line.is_empty() {
+            flush(&mut blocks, &mut paragraph);
+            in_item = false;
+        } else if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            flush(&mut blocks, &mut paragraph);
+            blocks.push(Block::Heading(level, line[level..].trim().to_string()));
+            in_item = false;
+        } else if let Some((marker, text)) = list_item(line) {
+            flush(&mut blocks, &mut paragraph);
+            blocks.push(Block::Item(marker, text.to_string()));
+            in_item = true;
+        } else if let (true, Some(Block::Item(_, text))) = (in_item, blocks.last_mut()) {
+            text.push(' ');
+            text.push_str(line);
+        } else {
+            paragraph.push(line);
+        }
+    }
+    flush(&mut blocks, &mut paragraph);
+    blocks
+}
+
+// "- text", "* text", "+ text" or "12. text"
+fn list_item(line: &str) -> Option<(String, &str)> {
+    if let Some(text) = ["- ", "* ", "+ "].iter().find_map(|m| line.strip_prefix(m)) {
+        return Some(("•".to_string(), text.trim()));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let text = line[digits..].strip_prefix(". ")?;
+    (digits > 0).then(|| (format!("{}.", &line[..digits]), text.trim()))
+}
+
+// Words of `text` as styled runs, starting in `base`
+fn parse_inline(text: &str, base: Style) -> Vec<Vec<Run>> {
+    let mut words = Vec::new();
+    let mut word: Vec<Run> = Vec::new();
+    let mut run = String::new();
+    let mut style = base;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let toggle = match c {
+            '*' | '_' if chars.peek() == Some(&c) => {
+                chars.next();
+                Some(Style {
+                    bold: !style.bold,
+                    ..style
+                })
+            }
+            '*' => Some(Style {
+                italic: !style.italic,
+                ..style
+            }),
+            _ => None,
+        };
+        if toggle.is_some() || c.is_whitespace() {
+            if !run.is_empty() {
+                word.push((std::mem::take(&mut run), style));
+            }
+            if c.is_whitespace() && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        } else {
+            run.push(c);
+        }
+        style = toggle.unwrap_or(style);
+    }
+    if !run.is_empty() {
+        word.push((run, style));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn word_chars(word: &[Run]) -> usize {
+    word.iter().map(|(text, _)| text.chars().count()).sum()
+}
+
+// Greedily fill lines of at most `max_chars`, keeping words whole
+fn wrap_words(words: Vec<Vec<Run>>, max_chars: usize) -> Vec<Vec<Run>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<Run> = Vec::new();
+    let mut length = 0;
+    for word in words {
+        let chars = word_chars(&word);
+        if length > 0 && length + 1 + chars > max_chars {
+            lines.push(std::mem::take(&mut line));
+            length = 0;
+        }
+        if length > 0 {
+            let style = line.last().map_or(Style::default(), |(_, style)| *style);
+            line.push((" ".to_string(), style));
+            length += 1;
+        }
+        line.extend(word);
+        length += chars;
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// One line of text as an SVG <text> at (x, baseline)
+fn write_line(svg: &mut String, runs: &[Run], x: f64, baseline: f64, size_mm: f64, font_family: &str) {
+    let _ = write!(
+        svg,
+        "<text xml:space=\"preserve\" x=\"{}\" y=\"{}\" font-family=\"'{}', sans-serif\" font-size=\"{}\" fill=\"#000000\">",
+        x,
+        baseline,
+        escape(font_family),
+        size_mm
+    );
+    // Right-to-left lines as one embedding around all their runs, as
+    // `render::bidi_line` does for single runs
+    let line: String = runs.iter().map(|(text, _)| text.as_str()).collect();
+    let rtl = render::is_rtl(&line);
+    if rtl {
+        svg.push('\u{202B}');
+    }
+    for (text, style) in runs {
+        let _ = write!(
+            svg,
+            "<tspan font-weight=\"{}\" font-style=\"{}\">{}</tspan>",
+            if style.bold { "bold" } else { "normal" },
+            if style.italic { "italic" } else { "normal" },
+            escape(text)
+        );
+    }
+    if rtl {
+        svg.push('\u{202C}');
+    }
+    svg.push_str("</text>\n");
+}
+
+// Lay out `markdown` `width_mm` wide and return the SVG body and its height
+fn layout(markdown: &str, width_mm: f64, font_size_pt: f64, font_family: &str) -> Result<(String, f64), String> {
+    let content_width = width_mm - 2.0 * MARGIN_MM;
+    if content_width <= 0.0 {
+        return Err(format!("A label {} mm wide has no room for text", width_mm));
+    }
+    let blocks = parse_blocks(markdown);
+    if blocks.is_empty() {
+        return Err("There is no text to render".to_string());
+    }
+
+    let mut body = String::new();
+    let mut y = MARGIN_MM;
+    let mut gap = 0.0;
+    for block in &blocks {
+        let (text, scale, base, marker) = match block {
+            Block::Heading(level, text) => {
+                let scale = HEADING_SCALE[(level - 1).min(HEADING_SCALE.len() - 1)];
+                (
+                    text,
+                    scale,
+                    Style {
+                        bold: true,
+                        italic: false,
+                    },
+                    None,
+                )
+            }
+            Block::Paragraph(text) => (text, 1.0, Style::default(), None),
+            Block::Item(marker, text) => (text, 1.0, Style::default(), Some(marker)),
+        };
+        let size = font_size_pt * scale * MM_PER_PT;
+        // List text hangs past its marker
+        let indent = marker.map_or(0.0, |m| (m.chars().count() as f64 + 1.0) * CHAR_WIDTH * size);
+        let max_chars = ((content_width - indent) / (size * CHAR_WIDTH)).floor().max(1.0) as usize;
+        let lines = wrap_words(parse_inline(text, base), max_chars);
+        if let Some(marker) = marker {
+            let runs = [(marker.clone(), Style::default())];
+            write_line(&mut body, &runs, MARGIN_MM, y + size * ASCENT, size, font_family);
+        }
+        for line in &lines {
+            write_line(
+                &mut body,
+                line,
+                MARGIN_MM + indent,
+                y + size * ASCENT,
+                size,
+                font_family,
+            );
+            y += size * LINE_HEIGHT;
+        }
+        if lines.is_empty() {
+            y += size * LINE_HEIGHT;
+        }
+        gap = size * LINE_HEIGHT * BLOCK_GAP;
+        y += gap;
+    }
+    // No gap after the last block, just the margin
+    Ok((body, y - gap + MARGIN_MM))
+}
+
+/// Render `markdown` as a label `width_mm` wide and as tall as its text: a
+/// document holding the rendered image at `dpi` (300 by default). Body text
+/// is `font_size_pt`, 10pt by default; headings are larger.
+#[tauri::command]
+pub async fn render_markdown_label(
+    fonts: State<'_, FontLibrary>,
+    markdown: String,
+    width_mm: f64,
+    font_size_pt: Option<f64>,
+    font_family: Option<String>,
+    dpi: Option<f64>,
+) -> Result<Document, String> {
+    let font_size_pt = font_size_pt.filter(|s| *s > 0.0).unwrap_or(DEFAULT_FONT_PT);
+    let font_family = font_family
+        .filter(|f| !f.is_empty())
+        .unwrap_or_else(|| "Arial".to_string());
+    let (body, height_mm) = layout(&markdown, width_mm, font_size_pt, &font_family)?;
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#ffffff\"/>\n{body}</svg>\n",
+        w = width_mm,
+        h = height_mm,
+        body = body
+    );
+    let png = render::rasterize(
+        &svg,
+        fonts.database(),
+        width_mm,
+        height_mm,
+        dpi.filter(|d| *d > 0.0).unwrap_or(RENDER_DPI),
+    )?;
+
+    let mut document = Document::new(width_mm, height_mm);
+    let asset_id = document.add_asset("markdown.png", "image/png", &png);
+    document.push(Element::new(
+        0.0,
+        0.0,
+        width_mm,
+        height_mm,
+        ElementKind::Image(ImageElement {
+            asset_id,
+            adjustments: None,
+        }),
+    ));
+    Ok(document)
+}
@@ -0,0 +1,217 @@
+// Media left on each printer's roll. When a roll is loaded its length (or
+// label count, for die-cut rolls) is entered, every label printed is taken
+// off it, and a `MediaLow` event warns when a job needs more than the roll
+// is estimated to have left. The estimate ignores feed and cut waste, so
+// it runs a little high.
+use crate::events::{self, LabelEvent};
+use crate::i18n;
+use crate::progress;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::{error, warn};
+
+/// Sides within this of the roll's width are taken to run across it
+const WIDTH_TOLERANCE_MM: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollKind {
+    /// Tape cut to length, measured in millimeters
+    Continuous,
+    /// Pre-cut labels, counted
+    DieCut,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Roll {
+    pub kind: RollKind,
+    /// Millimeters of tape, or labels, on the roll when it was loaded
+    pub capacity: f64,
+    /// Millimeters or labels printed since
+    #[serde(default)]
+    pub used: f64,
+    /// Tape width, to tell which side of a label runs along the tape
+    #[serde(default)]
+    pub width_mm: Option<f64>,
+    /// UTC, RFC 3339
+    pub loaded_at: String,
+}
+
+impl Roll {
+    pub fn remaining(&self) -> f64 {
+        (self.capacity - self.used).max(0.0)
+    }
+
+    fn usage(&self, labels: usize, width_mm: f64, height_mm: f64) -> f64 {
+        usage(self.kind, self.width_mm, labels, width_mm, height_mm)
+    }
+
+    fn amount(&self, value: f64) -> String {
+        match self.kind {
+            RollKind::Continuous => i18n::t("media-mm", &[("amount", value.round().into())]),
+            RollKind::DieCut => i18n::t("media-labels", &[("amount", value.round().into())]),
+        }
+    }
+}
+
+/// Millimeters of `tape_width_mm` tape, or die-cut labels, that `labels`
+/// labels of `width_mm` x `height_mm` take
+pub fn usage(kind: RollKind, tape_width_mm: Option<f64>, labels: usize, width_mm: f64, height_mm: f64) -> f64 {
+    match kind {
+        RollKind::DieCut => labels as f64,
+        RollKind::Continuous => {
+            // Labels are laid out with their height along the tape unless
+            // that's the side matching the tape's width
+            let length = match tape_width_mm {
+                Some(width) if (height_mm - width).abs() <= WIDTH_TOLERANCE_MM => width_mm,
+                _ => height_mm,
+            };
+            labels as f64 * length
+        }
+    }
+}
+
+struct MediaStore {
+    path: PathBuf,
+    rolls: Mutex<BTreeMap<String, Roll>>,
+}
+
+// Usage is recorded deep inside `printing::send_pdf`, which has no access to
+// Tauri state
+static STORE: OnceLock<MediaStore> = OnceLock::new();
+
+/// Load the rolls recorded at `path`; later calls have no effect. Without
+/// it, as in the CLI, media isn't tracked.
+pub fn init(path: &Path) {
+    let rolls = match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            error!("Ignoring invalid media file {}: {}", path.display(), e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    };
+    let _ = STORE.set(MediaStore {
+        path: path.to_path_buf(),
+        rolls: Mutex::new(rolls),
+    });
+}
+
+fn store() -> Result<&'static MediaStore, String> {
+    STORE.get().ok_or_else(|| "Media tracking isn't available".to_string())
+}
+
+impl MediaStore {
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, Roll>)) -> Result<(), String> {
+        let mut rolls = self.rolls.lock().unwrap();
+        let mut updated = rolls.clone();
+        change(&mut updated);
+        self.write(&updated)?;
+        *rolls = updated;
+        Ok(())
+    }
+
+    // Write to a temp file and rename so a crash can't leave half a file behind
+    fn write(&self, rolls: &BTreeMap<String, Roll>) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(rolls).map_err(|e| format!("Failed to serialize media: {}", e))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, json).map_err(|e| format!("Failed to write media: {}", e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| format!("Failed to save media: {}", e))
+    }
+}
+
+/// Warn with a `MediaLow` event when `printer`'s roll is estimated to run
+/// out before `labels` labels of `width_mm` x `height_mm` are printed. Jobs
+/// aren't stopped: the estimate may be off, and the operator can swap rolls
+/// mid-job.
+pub fn check(printer: &str, labels: usize, width_mm: f64, height_mm: f64) {
+    let Ok(store) = store() else {
+        return;
+    };
+    let Some(roll) = store.rolls.lock().unwrap().get(printer).cloned() else {
+        return;
+    };
+    let needed = roll.usage(labels, width_mm, height_mm);
+    let remaining = roll.remaining();
+    if needed <= remaining {
+        return;
+    }
+    let message = i18n::t(
+        "media-low",
+        &[
+            ("printer", printer.into()),
+            ("remaining", roll.amount(remaining).into()),
+            ("needed", roll.amount(needed).into()),
+        ],
+    );
+    warn!("{}", message);
+    events::publish_in_job(LabelEvent::MediaLow {
+        job: progress::current_job(),
+        printer: printer.to_string(),
+        kind: roll.kind,
+        remaining,
+        needed,
+        message,
+    });
+}
+
+/// Take labels `printer` has printed off its roll
+pub fn consume(printer: &str, labels: usize, width_mm: f64, height_mm: f64) {
+    let Ok(store) = store() else {
+        return;
+    };
+    if !store.rolls.lock().unwrap().contains_key(printer) {
+        return;
+    }
+    let result = store.update(|rolls| {
+        if let Some(roll) = rolls.get_mut(printer) {
+            roll.used += roll.usage(labels, width_mm, height_mm);
+        }
+    });
+    if let Err(e) = result {
+        error!("{}", e);
+    }
+}
+
+#[tauri::command]
+pub fn list_media_rolls() -> Result<BTreeMap<String, Roll>, String> {
+    Ok(store()?.rolls.lock().unwrap().clone())
+}
+
+/// Record a full roll loaded into `printer_name`: `capacity` millimeters of
+/// continuous tape, or `capacity` die-cut labels
+#[tauri::command]
+pub fn load_media_roll(
+    printer_name: String,
+    kind: RollKind,
+    capacity: f64,
+    width_mm: Option<f64>,
+) -> Result<(), String> {
+    if !capacity.is_finite() || capacity <= 0.0 {
+        return Err("A roll's length or label count must be positive".to_string());
+    }
+    let roll = Roll {
+        kind,
+        capacity,
+        used: 0.0,
+        width_mm,
+        loaded_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    store()?.update(|rolls| {
+        rolls.insert(printer_name, roll);
+    })
+}
+
+/// Stop tracking `printer_name`'s media
+#[tauri::command]
+pub fn remove_media_roll(printer_name: String) -> Result<(), String> {
+    store()?.update(|rolls| {
+        rolls.remove(&printer_name);
+    })
+}
@@ -0,0 +1,716 @@
+// Mail merge: fill a label's placeholders from rows of a data source and
+// print one label per row
+pub mod csv;
+pub mod http;
+pub mod sqlite;
+pub mod xlsx;
+
+use crate::backends;
+use crate::barcodes::{self, check_digits, verify};
+use crate::contacts;
+use crate::counters::CounterStore;
+use crate::datefields;
+use crate::document::{Document, ElementKind};
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::groups;
+use crate::history::JobDetails;
+use crate::numbering::{self, NumberRange};
+use crate::plugins;
+use crate::placeholders::{self, Context, Segment, VariableKind};
+use crate::printing::{self, RENDER_DPI};
+use crate::prompts;
+use crate::progress::{self, RENDER_PROGRESS_EVENT};
+use crate::raster;
+use crate::render;
+use crate::rfid;
+use crate::rules;
+use crate::scale::{self, Scale};
+use crate::scripting::ScriptHooks;
+use crate::shortener;
+use crate::tape::{self, TapeWarning};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
+use tracing::info;
+
+/// Where merge rows come from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DataSource {
+    Csv {
+        path: String,
+        /// Defaults to ',' (or ';' / tab when the header clearly uses those)
+        #[serde(default)]
+        delimiter: Option<char>,
+    },
+    /// Excel workbook (.xlsx, .xls, .ods)
+    Xlsx {
+        path: String,
+        /// First sheet when absent
+        #[serde(default)]
+        sheet: Option<String>,
+    },
+    /// SELECT query against a SQLite database file
+    Sqlite { path: String, query: String },
+    /// JSON fetched from a URL
+    Http {
+        url: String,
+        /// Extra request headers, e.g. `Authorization: Bearer ...`
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        /// Dotted path to the record array in the response, e.g. "data.items"
+        #[serde(default)]
+        records_path: Option<String>,
+    },
+    /// vCard file or Google/Outlook contacts CSV export, with the address
+    /// book's fields as columns
+    Contacts { path: String },
+    /// Records passed in directly, e.g. by the REST API
+    Records { records: Vec<BTreeMap<String, String>> },
+    /// Data source provided by a plugin, with plugin-specific settings
+    Plugin {
+        source: String,
+        #[serde(default)]
+        config: serde_json::Value,
+    },
+}
+
+impl DataSource {
+    /// CSV or spreadsheet source chosen by file extension
+    pub fn from_file(path: &Path, sheet: Option<String>) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let path = path.to_string_lossy().to_string();
+        match extension.as_str() {
+            "xlsx" | "xlsm" | "xls" | "ods" => DataSource::Xlsx { path, sheet },
+            _ => DataSource::Csv { path, delimiter: None },
+        }
+    }
+
+    pub fn load(&self) -> Result<DataTable, String> {
+        match self {
+            DataSource::Csv { path, delimiter } => csv::load(Path::new(path), *delimiter),
+            DataSource::Xlsx { path, sheet } => xlsx::load(Path::new(path), sheet.as_deref()),
+            DataSource::Sqlite { path, query } => sqlite::load(Path::new(path), query),
+            DataSource::Http { url, headers, records_path } => {
+                http::load(url, headers, records_path.as_deref())
+            }
+            DataSource::Contacts { path } => contacts::import::load(Path::new(path)),
+            DataSource::Records { records } => Ok(DataTable::from_records(records)),
+            DataSource::Plugin { source, config } => plugins::registry()
+                .data_source(source)
+                .ok_or_else(|| format!("No plugin provides the data source \"{}\"", source))?
+                .load(config),
+        }
+    }
+}
+
+/// Rows of string values under named columns
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl DataTable {
+    /// Table with every key used by any record as a column
+    pub fn from_records(records: &[BTreeMap<String, String>]) -> Self {
+        let mut columns: Vec<String> = Vec::new();
+        for key in records.iter().flat_map(|r| r.keys()) {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+        let rows = records
+            .iter()
+            .map(|record| columns.iter().map(|c| record.get(c).cloned().unwrap_or_default()).collect())
+            .collect();
+        DataTable { columns, rows }
+    }
+
+    /// Values of row `index` keyed by column name
+    pub fn record(&self, index: usize) -> BTreeMap<String, String> {
+        let row = &self.rows[index];
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| (column.clone(), row.get(i).cloned().unwrap_or_default()))
+            .collect()
+    }
+}
+
+/// 1-based, inclusive range of data rows (the header doesn't count)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RowRange {
+    pub first: usize,
+    #[serde(default)]
+    pub last: Option<usize>,
+}
+
+impl RowRange {
+    /// Zero-based row indices selected out of `row_count` rows
+    pub fn indices(&self, row_count: usize) -> Result<std::ops::Range<usize>, String> {
+        let last = self.last.unwrap_or(row_count).min(row_count);
+        if self.first == 0 || self.first > last {
+            return Err(format!(
+                "Invalid row range {}-{} for a data source with {} rows",
+                self.first, last, row_count
+            ));
+        }
+        Ok(self.first - 1..last)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRequest {
+    pub document: Document,
+    pub source: DataSource,
+    /// Document field name -> data source column. Fields that aren't mapped
+    /// use the column with the same name, if there is one.
+    #[serde(default)]
+    pub mapping: BTreeMap<String, String>,
+    /// All rows when absent
+    #[serde(default)]
+    pub range: Option<RowRange>,
+    /// Open the merged PDF instead of printing when absent
+    #[serde(default)]
+    pub printer_name: Option<String>,
+    #[serde(default)]
+    pub check_digits: CheckDigitPolicy,
+    /// Print every selected row once per number of the range, with the
+    /// number in the `{number}` field
+    #[serde(default)]
+    pub numbering: Option<NumberRange>,
+    /// Answers to the document's prompt fields
+    #[serde(default)]
+    pub answers: BTreeMap<String, String>,
+}
+
+/// What to do with barcode data whose check digit is missing or wrong
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckDigitPolicy {
+    /// Fail the merge, listing every row with a bad check digit
+    #[default]
+    Reject,
+    /// Replace wrong check digits with the correct one
+    Fix,
+}
+
+// The texts of an element that can hold placeholders
+fn merge_texts(kind: &ElementKind) -> Vec<&String> {
+    match kind {
+        ElementKind::Text(text) => std::iter::once(&text.text)
+            .chain(text.runs.iter().map(|run| &run.text))
+            .collect(),
+        ElementKind::Barcode(barcode) => vec![&barcode.data],
+        ElementKind::Nutrition(nutrition) => nutrition.values.fields().to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every distinct placeholder used by a document's text, barcodes and
+/// nutrition values, and the fields its rules read
+pub fn document_fields(document: &Document) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for text in document.elements.iter().flat_map(|element| merge_texts(&element.kind)) {
+        for name in placeholders::variables(text) {
+            if !fields.contains(&name) {
+                fields.push(name);
+            }
+        }
+    }
+    if let Some(tag) = &document.rfid {
+        for name in placeholders::variables(&tag.epc) {
+            if !fields.contains(&name) {
+                fields.push(name);
+            }
+        }
+    }
+    for name in rules::fields(document) {
+        if !fields.contains(&name) {
+            fields.push(name);
+        }
+    }
+    fields
+}
+
+/// Field values for one data row, resolved through the mapping
+pub fn field_values(
+    fields: &[String],
+    mapping: &BTreeMap<String, String>,
+    record: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let column = mapping.get(field).unwrap_or(field);
+            record.get(column).map(|value| (field.clone(), value.clone()))
+        })
+        .collect()
+}
+
+/// Copy of `document` with its rules applied and placeholders filled in
+pub fn apply(document: &Document, context: &mut Context) -> Result<Document, String> {
+    let mut merged = document.clone();
+    rules::apply(&mut merged, context.values);
+    for element in &mut merged.elements {
+        match &mut element.kind {
+            ElementKind::Text(text) => {
+                text.text = placeholders::render(&text.text, context)?;
+                for run in &mut text.runs {
+                    run.text = placeholders::render(&run.text, context)?;
+                }
+            }
+            ElementKind::Barcode(barcode) => barcode.data = placeholders::render(&barcode.data, context)?,
+            ElementKind::Nutrition(nutrition) => {
+                for value in nutrition.values.fields_mut() {
+                    *value = placeholders::render(value, context)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(tag) = &mut merged.rfid {
+        tag.epc = placeholders::render(&tag.epc, context)?;
+    }
+    Ok(merged)
+}
+
+/// Records of the labels a merge job prints; numbered rows give one per
+/// number, with the number in it so a reprint has the same numbers
+pub fn selected_records(request: &MergeRequest, table: &DataTable) -> Vec<BTreeMap<String, String>> {
+    let range = request.range.unwrap_or(RowRange { first: 1, last: None });
+    let records = range
+        .indices(table.rows.len())
+        .map(|rows| rows.map(|i| table.record(i)).collect())
+        .unwrap_or_default();
+    let Some(numbers) = request.numbering.as_ref().and_then(|numbering| numbering.labels().ok()) else {
+        return records;
+    };
+    records
+        .into_iter()
+        .flat_map(|record| {
+            numbers.iter().map(move |number| {
+                let mut record = record.clone();
+                record.insert(numbering::NUMBER_FIELD.to_string(), number.clone());
+                record
+            })
+        })
+        .collect()
+}
+
+/// Rows of a merge job's data source, with scale readings added and the
+/// `merge_row` script hook applied
+pub fn load_table(request: &MergeRequest, scale: &Scale, scripts: &ScriptHooks) -> Result<DataTable, String> {
+    let mut table = request.source.load()?;
+    scale.fill_fields(&document_fields(&request.document), &mut table)?;
+    scripts.merge_rows(table)
+}
+
+/// One filled-in document per selected row, or per number of each row with
+/// numbering. `{counter:name}` fields take a fresh value from `next_serial`
+/// for every label; date fields are evaluated once for the whole batch. Every barcode is encoded up front, so a bad row
+/// fails the merge before anything is printed.
+pub fn merge_documents(
+    request: &MergeRequest,
+    table: &DataTable,
+    next_serial: &mut dyn FnMut(&str) -> Result<String, String>,
+) -> Result<Vec<Document>, String> {
+    check_mapping(&request.mapping, table)?;
+    prompts::validate(&request.document.prompts, &request.answers)?;
+
+    let range = request.range.unwrap_or(RowRange { first: 1, last: None });
+    let indices = range.indices(table.rows.len())?;
+    let fields = document_fields(&request.document);
+    let now = chrono::Local::now();
+    let numbers = match &request.numbering {
+        Some(numbering) => numbering.labels()?.into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+
+    let mut documents = Vec::with_capacity(indices.len() * numbers.len());
+    let mut failures = Vec::new();
+    for i in indices {
+        let record = table.record(i);
+        for number in &numbers {
+            let mut values = field_values(&fields, &request.mapping, &record);
+            prompts::fill(&request.document.prompts, &request.answers, &mut values);
+            if let Some(number) = number {
+                values.insert(numbering::NUMBER_FIELD.to_string(), number.clone());
+            }
+            let mut context = Context {
+                values: &values,
+                now,
+                next_serial: Some(&mut *next_serial),
+            };
+            let mut document = apply(&request.document, &mut context)?;
+            let rfid_error = document.rfid.as_ref().and_then(|tag| rfid::check_epc(&tag.epc).err());
+            for error in check_barcodes(&mut document, request.check_digits).into_iter().chain(rfid_error) {
+                match number {
+                    Some(number) => failures.push(format!("Row {}, number {}: {}", i + 1, number, error)),
+                    None => failures.push(format!("Row {}: {}", i + 1, error)),
+                }
+            }
+            documents.push(document);
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(format!(
+            "Invalid barcode data in {} label(s):\n{}",
+            failures.len(),
+            failures.join("\n")
+        ));
+    }
+    Ok(documents)
+}
+
+// Encode each barcode of a merged document, fixing check digits first when
+// the policy allows it. Returns the problems found.
+fn check_barcodes(document: &mut Document, policy: CheckDigitPolicy) -> Vec<String> {
+    let mut errors = Vec::new();
+    for element in &mut document.elements {
+        let ElementKind::Barcode(barcode) = &mut element.kind else {
+            continue;
+        };
+        if policy == CheckDigitPolicy::Fix {
+            if let Some(fixed) = check_digits::fix(&barcode.symbology, &barcode.data) {
+                info!("Fixed check digit: {} -> {}", barcode.data, fixed);
+                barcode.data = fixed;
+            }
+        }
+        if let Err(e) = barcodes::encode(&barcode.symbology, &barcode.data, &barcode.options) {
+            errors.push(e);
+        }
+    }
+    errors
+}
+
+fn check_mapping(mapping: &BTreeMap<String, String>, table: &DataTable) -> Result<(), String> {
+    for column in mapping.values() {
+        if !table.columns.contains(column) {
+            return Err(format!("Data source has no column named \"{}\"", column));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaceholderReport {
+    pub valid: bool,
+    pub fields: Vec<FieldCheck>,
+    /// Syntax errors, unknown filters and mappings to missing columns
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldCheck {
+    pub field: String,
+    /// "data", "counter", "date", "scale", "number" or "prompt"
+    pub kind: &'static str,
+    /// Column a data field reads from
+    pub column: Option<String>,
+    pub ok: bool,
+    pub message: Option<String>,
+}
+
+/// Check every placeholder in `document` against a data source's columns and
+/// the defined counters, without printing anything
+pub fn validate(
+    document: &Document,
+    table: &DataTable,
+    mapping: &BTreeMap<String, String>,
+    counters: &CounterStore,
+) -> PlaceholderReport {
+    let mut errors = Vec::new();
+    if let Err(e) = check_mapping(mapping, table) {
+        errors.push(e);
+    }
+    for text in document.elements.iter().flat_map(|element| merge_texts(&element.kind)) {
+        match placeholders::parse(text) {
+            Ok(segments) => {
+                for segment in segments {
+                    let Segment::Expression(expression) = segment else {
+                        continue;
+                    };
+                    for filter in &expression.filters {
+                        if !placeholders::FILTERS.contains(&filter.name.as_str()) {
+                            errors.push(format!("Unknown filter \"{}\" in {{{}}}", filter.name, expression.source));
+                        }
+                    }
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let defined_counters = counters.list();
+    let now = chrono::Local::now();
+    let fields: Vec<FieldCheck> = document_fields(document)
+        .into_iter()
+        .map(|field| {
+            let column = mapping.get(&field).unwrap_or(&field);
+            if table.columns.contains(column) {
+                return FieldCheck { kind: "data", column: Some(column.clone()), ok: true, message: None, field };
+            }
+            if field == scale::WEIGHT_FIELD || field == scale::WEIGHT_UNIT_FIELD {
+                return FieldCheck { kind: "scale", column: None, ok: true, message: None, field };
+            }
+            if field == numbering::NUMBER_FIELD {
+                return FieldCheck { kind: "number", column: None, ok: true, message: None, field };
+            }
+            if document.prompts.iter().any(|prompt| prompt.name == field) {
+                return FieldCheck { kind: "prompt", column: None, ok: true, message: None, field };
+            }
+            match placeholders::variable_kind(&field) {
+                VariableKind::Counter => {
+                    let name = field[placeholders::COUNTER_PREFIX.len()..].trim();
+                    let ok = defined_counters.contains_key(name);
+                    let message = (!ok).then(|| format!("Counter \"{}\" is not defined", name));
+                    FieldCheck { kind: "counter", column: None, ok, message, field }
+                }
+                VariableKind::Date => {
+                    let message = datefields::evaluate(&field, now).and_then(Result::err);
+                    FieldCheck { kind: "date", column: None, ok: message.is_none(), message, field }
+                }
+                VariableKind::Data => FieldCheck {
+                    kind: "data",
+                    column: None,
+                    ok: false,
+                    message: Some(format!("Data source has no column for \"{}\"", field)),
+                    field,
+                },
+            }
+        })
+        .collect();
+
+    PlaceholderReport {
+        valid: errors.is_empty() && fields.iter().all(|f| f.ok),
+        fields,
+        errors,
+    }
+}
+
+/// Columns and rows of a data source, for the column mapping UI
+#[tauri::command]
+pub async fn load_data_source(source: DataSource) -> Result<DataTable, String> {
+    source.load()
+}
+
+/// Sheets of a workbook, so the user can pick one before mapping columns
+#[tauri::command]
+pub async fn list_sheets(path: String) -> Result<Vec<String>, String> {
+    xlsx::sheet_names(Path::new(&path))
+}
+
+/// Check a document's placeholders against a data source before printing
+#[tauri::command]
+pub fn validate_placeholders(
+    counters: State<'_, CounterStore>,
+    document: Document,
+    source: DataSource,
+    mapping: Option<BTreeMap<String, String>>,
+) -> Result<PlaceholderReport, String> {
+    let table = source.load()?;
+    Ok(validate(&document, &table, &mapping.unwrap_or_default(), &counters))
+}
+
+/// Render documents of the same size as pages of one PDF in the temp
+/// directory
+pub async fn documents_to_pdf(documents: &[Document], fonts: Arc<fontdb::Database>) -> Result<PathBuf, String> {
+    render_pdf(documents, fonts, None).await
+}
+
+// Render documents to one PDF, first checking every barcode scans at
+// `verify_dpi` when given
+/// Run `work`, e.g. reading a data source, rendering or running scripts,
+/// off the async runtime
+pub async fn blocking<T: Send + 'static>(work: impl FnOnce() -> Result<T, String> + Send + 'static) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Failed to run job: {}", e))?
+}
+
+async fn render_pdf(
+    documents: &[Document],
+    fonts: Arc<fontdb::Database>,
+    printer_dpi: Option<f64>,
+    verify_dpi: Option<f64>,
+) -> Result<PathBuf, String> {
+    let first = documents.first().ok_or("Nothing to print")?;
+    let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let mut pages: Vec<PathBuf> = Vec::with_capacity(documents.len());
+    for (i, document) in documents.iter().enumerate() {
+        progress::report(RENDER_PROGRESS_EVENT, "rendering", i, documents.len())?;
+        let document = document.printable();
+        let path = temp_dir.path().join(format!("label_{:05}.png", i));
+        let (fonts, page) = (fonts.clone(), path.clone());
+        blocking(move || {
+            let png = render::render_png_for(&document, fonts, RENDER_DPI, printer_dpi)?;
+            if let Some(dpi) = verify_dpi {
+                verify::check(&document, &png, dpi).map_err(|e| format!("Label {}: {}", i + 1, e))?;
+            }
+            fs::write(&page, png).map_err(|e| format!("Failed to write PNG file: {}", e))
+        })
+        .await?;
+        pages.push(path);
+    }
+
+    progress::report(RENDER_PROGRESS_EVENT, "rendering", documents.len(), documents.len())?;
+
+    let page_refs: Vec<&Path> = pages.iter().map(PathBuf::as_path).collect();
+    Ok(printing::images_to_pdf(&page_refs, first.width_mm, first.height_mm).await?)
+}
+
+/// Print documents as one PDF, or open it when no printer is given.
+/// Returns `submit_pdf`'s message.
+pub async fn print_documents(
+    documents: &[Document],
+    fonts: Arc<fontdb::Database>,
+    printer_name: Option<&str>,
+) -> Result<String, String> {
+    // Barcodes are drawn on the dot grid of the printer they go to, and
+    // verified at its resolution
+    let printer_dpi = match printer_name {
+        Some(printer) => {
+            let printer = groups::primary(printer);
+            Some(backends::resolve(&printer).capabilities(&printer).await.dpi)
+        }
+        None => None,
+    };
+    let verify_dpi = printer_dpi.filter(|_| verify::enabled());
+    let documents = shortener::shorten_documents(documents).await;
+    let pdf_path = render_pdf(&documents, fonts, printer_dpi, verify_dpi).await?;
+    let (width_mm, height_mm) = (documents[0].width_mm, documents[0].height_mm);
+    Ok(printing::submit_pdf(&pdf_path, width_mm, height_mm, printer_name).await?)
+}
+
+/// Serial numbers as `CounterStore::next_serial` would hand them out, from a
+/// copy of the counters, so previews don't advance them
+pub fn peek_counters(counters: &CounterStore) -> impl FnMut(&str) -> Result<String, String> {
+    let mut peeked = counters.list();
+    move |name| {
+        let counter = peeked.get_mut(name).ok_or_else(|| format!("Unknown counter: {}", name))?;
+        let value = counter.next;
+        counter.next = value.saturating_add(counter.step);
+        Ok(counter.format(value))
+    }
+}
+
+/// Resolution of merge previews; enough to read small text on screen
+const PREVIEW_DPI: f64 = 150.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewPage {
+    /// 1-based data row the label was filled from
+    pub row: usize,
+    /// PNG data URL
+    pub image: String,
+    /// What won't show on the tape loaded in the printer
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<TapeWarning>,
+}
+
+/// Render the first `count` labels of a merge without printing, so the
+/// mapping can be checked before committing a whole roll. Counters show the
+/// values they would hand out but aren't advanced. With a P-touch printer
+/// that reports its cassette, labels are shown in its ink on its tape.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn preview_merge(
+    fonts: State<'_, FontLibrary>,
+    counters: State<'_, CounterStore>,
+    scripts: State<'_, ScriptHooks>,
+    scale: State<'_, Scale>,
+    request: MergeRequest,
+    count: usize,
+    dpi: Option<f64>,
+    printer_name: Option<String>,
+) -> Result<Vec<PreviewPage>, String> {
+    let table = load_table(&request, &scale, &scripts)?;
+    let range = request.range.unwrap_or(RowRange { first: 1, last: None });
+    let last = range.last.unwrap_or(table.rows.len()).min(range.first + count.max(1) - 1);
+    // Numbered rows give a label per number; no more than `count` are needed
+    let numbering = request.numbering.as_ref().map(|numbering| numbering.limited(count));
+    let labels_per_row = match &numbering {
+        Some(numbering) => numbering.labels()?.len(),
+        None => 1,
+    };
+    let request = MergeRequest {
+        range: Some(RowRange { first: range.first, last: Some(last) }),
+        numbering,
+        ..request
+    };
+
+    let mut documents = merge_documents(&request, &table, &mut peek_counters(&counters))?;
+    scripts.before_render(&mut documents)?;
+
+    let loaded = match printer_name {
+        Some(printer) => tape::loaded_tape(&groups::primary(&printer)).await,
+        None => None,
+    };
+    let database = fonts.database();
+    let dpi = dpi.unwrap_or(PREVIEW_DPI);
+    documents
+        .iter()
+        .take(count.max(1))
+        .enumerate()
+        .map(|(i, document)| {
+            let png = render::render_png(document, database.clone(), dpi)?;
+            let (image, warnings) = match &loaded {
+                Some(loaded) => {
+                    let page = raster::print_raster(&png, dpi)?;
+                    let preview = tape::preview(&page, dpi, loaded.clone(), tape::warnings(document, loaded))?;
+                    (preview.image, preview.warnings)
+                }
+                None => (format!("data:image/png;base64,{}", BASE64.encode(png)), Vec::new()),
+            };
+            Ok(PreviewPage {
+                row: range.first + i / labels_per_row,
+                image,
+                warnings,
+            })
+        })
+        .collect()
+}
+
+/// Render one label per selected row into a single PDF and print it.
+/// `op_id` lets `cancel_operation` stop the batch.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn print_merge(
+    fonts: State<'_, FontLibrary>,
+    counters: State<'_, CounterStore>,
+    events: State<'_, EventBus>,
+    scripts: State<'_, ScriptHooks>,
+    scale: State<'_, Scale>,
+    request: MergeRequest,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    let table = load_table(&request, &scale, &scripts)?;
+    if let Some(numbering) = &request.numbering {
+        numbering.reserve(&counters)?;
+    }
+    let mut documents = merge_documents(&request, &table, &mut |name| counters.next_serial(name))?;
+    scripts.before_render(&mut documents)?;
+    info!("Merging {} labels", documents.len());
+
+    let printer_name = request.printer_name.as_deref();
+    let details = JobDetails {
+        template: None,
+        data: selected_records(&request, &table),
+    };
+    let work = print_documents(&documents, fonts.database(), printer_name);
+    let job = events.run_job("merge", documents.len(), printer_name, details, work);
+    let result = events.run_operation(op_id, job).await?;
+    Ok(match &request.printer_name {
+        Some(printer) => format!("Printed {} labels to {}", documents.len(), printer),
+        None => result,
+    })
+}
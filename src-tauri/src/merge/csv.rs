@@ -0,0 +1,52 @@
+// CSV data source; the first record is the header
+use super::DataTable;
+use std::path::Path;
+
+pub fn load(path: &Path, delimiter: Option<char>) -> Result<DataTable, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read CSV file: {}", e))?;
+    parse(&bytes, delimiter)
+}
+
+pub fn parse(bytes: &[u8], delimiter: Option<char>) -> Result<DataTable, String> {
+    // Spreadsheet exports often start with a UTF-8 BOM
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let delimiter = match delimiter {
+        Some(c) if c.is_ascii() => c as u8,
+        Some(c) => return Err(format!("Unsupported CSV delimiter: {:?}", c)),
+        None => detect_delimiter(bytes),
+    };
+
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(bytes);
+
+    let columns: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .map(|c| c.trim().to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read CSV row: {}", e))?;
+        // Skip blank lines, which are common at the end of exported files
+        if record.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        rows.push(record.iter().map(str::to_string).collect());
+    }
+
+    Ok(DataTable { columns, rows })
+}
+
+// European spreadsheet exports use ';', some tools use tabs
+fn detect_delimiter(bytes: &[u8]) -> u8 {
+    let header = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    let count = |d: u8| header.iter().filter(|&&b| b == d).count();
+    [b',', b';', b'\t']
+        .into_iter()
+        .max_by_key(|&d| (count(d), d == b','))
+        .unwrap_or(b',')
+}
@@ -0,0 +1,99 @@
+// HTTP/JSON data source: fetches an array of records from an inventory or ERP
+// API at print time. Nested objects become dotted columns ("address.city").
+use super::DataTable;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// GET `url` with `headers` (e.g. `Authorization`) and read the records at
+/// `records_path`, a dotted path to the array inside the response
+pub fn load(
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    records_path: Option<&str>,
+) -> Result<DataTable, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into();
+    let mut request = agent.get(url).header("Accept", "application/json");
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let mut response = request
+        .call()
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    let body: Value = response
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("Response from {} is not valid JSON: {}", url, e))?;
+
+    parse(&body, records_path)
+}
+
+pub fn parse(body: &Value, records_path: Option<&str>) -> Result<DataTable, String> {
+    let mut records = body;
+    for key in records_path.into_iter().flat_map(|p| p.split('.')).filter(|k| !k.is_empty()) {
+        records = records
+            .get(key)
+            .ok_or_else(|| format!("Response has no \"{}\" field", key))?;
+    }
+    let records = match records {
+        Value::Array(items) => items.as_slice(),
+        // A single object is a one-row table
+        Value::Object(_) => std::slice::from_ref(records),
+        _ => return Err("Response records are not a JSON array".to_string()),
+    };
+
+    // Columns in first-seen order across all records
+    let mut table = DataTable::default();
+    let flattened: Vec<BTreeMap<String, String>> = records
+        .iter()
+        .map(|record| {
+            let mut fields = Vec::new();
+            flatten("", record, &mut fields);
+            for (name, _) in &fields {
+                if !table.columns.contains(name) {
+                    table.columns.push(name.clone());
+                }
+            }
+            fields.into_iter().collect()
+        })
+        .collect();
+
+    table.rows = flattened
+        .iter()
+        .map(|record| {
+            table
+                .columns
+                .iter()
+                .map(|column| record.get(column).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Ok(table)
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let name = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&name, value, out);
+            }
+        }
+        _ => {
+            let text = match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            // Scalar records (an array of strings) use a "value" column
+            let name = if prefix.is_empty() { "value".to_string() } else { prefix.to_string() };
+            out.push((name, text));
+        }
+    }
+}
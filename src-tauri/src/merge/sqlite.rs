@@ -0,0 +1,47 @@
+// SQLite data source: runs a user-provided query; its result columns become
+// the merge columns
+use super::DataTable;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+pub fn load(path: &Path, query: &str) -> Result<DataTable, String> {
+    // Read-only, so a mistyped query can never modify the user's catalog
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let mut statement = connection
+        .prepare(query)
+        .map_err(|e| format!("Invalid query: {}", e))?;
+    if !statement.readonly() {
+        return Err("Only SELECT queries can be used as a data source".to_string());
+    }
+
+    let columns: Vec<String> = statement.column_names().iter().map(|c| c.to_string()).collect();
+    let mut result = statement
+        .query([])
+        .map_err(|e| format!("Failed to run query: {}", e))?;
+
+    let mut rows = Vec::new();
+    while let Some(row) = result.next().map_err(|e| format!("Failed to run query: {}", e))? {
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(value_text))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read query result: {}", e))?;
+        rows.push(values);
+    }
+
+    Ok(DataTable { columns, rows })
+}
+
+fn value_text(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).to_string(),
+    }
+}
@@ -0,0 +1,55 @@
+// Excel (and other spreadsheet) data source via calamine; the first used row
+// of the sheet is the header
+use super::DataTable;
+use calamine::{open_workbook_auto, Data, Reader};
+use std::path::Path;
+
+/// Sheet names in workbook order, for the sheet picker
+pub fn sheet_names(path: &Path) -> Result<Vec<String>, String> {
+    let workbook = open_workbook_auto(path).map_err(|e| format!("Failed to open spreadsheet: {}", e))?;
+    Ok(workbook.sheet_names())
+}
+
+/// Load `sheet`, or the first sheet when none is given
+pub fn load(path: &Path, sheet: Option<&str>) -> Result<DataTable, String> {
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("Failed to open spreadsheet: {}", e))?;
+    let sheet = match sheet {
+        Some(name) => name.to_string(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or("Spreadsheet has no sheets")?,
+    };
+    let range = workbook
+        .worksheet_range(&sheet)
+        .map_err(|e| format!("Failed to read sheet \"{}\": {}", sheet, e))?;
+
+    let mut rows = range.rows();
+    let columns: Vec<String> = rows
+        .next()
+        .map(|header| header.iter().map(|cell| cell_text(cell).trim().to_string()).collect())
+        .unwrap_or_default();
+    let rows = rows
+        .map(|row| row.iter().map(cell_text).collect::<Vec<_>>())
+        .filter(|row| row.iter().any(|v| !v.trim().is_empty()))
+        .collect();
+
+    Ok(DataTable { columns, rows })
+}
+
+// Cell value as it would read on a label rather than calamine's raw form
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        // Whole numbers are stored as floats; "12" not "12.0"
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", *f as i64),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(value) if value.and_utc().timestamp() % 86_400 == 0 => value.format("%Y-%m-%d").to_string(),
+            Some(value) => value.format("%Y-%m-%d %H:%M").to_string(),
+            None => dt.to_string(),
+        },
+        Data::Error(_) => String::new(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,251 @@
+// Optional MQTT client for automated printing, e.g. from Home Assistant or a
+// PLC gateway. Each message on the configured topic prints labels and the
+// outcome is published to the result topic. A message is either one record
+// for the configured template:
+//
+//   {"name": "Tea", "price": "2.50"}
+//
+// or a full request:
+//
+//   {"id": "42", "template": "price", "printer": "QL-820NWB", "records": [{"name": "Tea"}]}
+use crate::api::{self, PrintRequest};
+use crate::merge::CheckDigitPolicy;
+use crate::queue::Priority;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MqttSettings {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Topic print requests arrive on
+    pub topic: String,
+    /// Where job results are published; `<topic>/result` when absent
+    #[serde(default)]
+    pub result_topic: Option<String>,
+    /// Gallery template id or label file used when a message doesn't name one
+    pub template: String,
+    /// Used when a message doesn't name a printer; opens the PDF when absent
+    #[serde(default)]
+    pub printer_name: Option<String>,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "labelgood".to_string()
+}
+
+/// Published for every message received, successful or not
+#[derive(Debug, Serialize)]
+struct JobResult {
+    /// The request's `id`, so callers can match results to requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    ok: bool,
+    labels: usize,
+    message: String,
+}
+
+struct Session {
+    client: Client,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.client.try_disconnect();
+    }
+}
+
+/// The MQTT connection, if one is configured. Replacing the settings closes
+/// the previous connection.
+#[derive(Default)]
+pub struct MqttBridge {
+    session: Mutex<Option<Session>>,
+}
+
+impl MqttBridge {
+    /// Connect with `settings`, replacing any previous connection. `None`
+    /// disconnects.
+    pub fn start(&self, app: &AppHandle, settings: Option<MqttSettings>) -> Result<(), String> {
+        let mut current = self.session.lock().unwrap();
+        *current = None;
+        let Some(settings) = settings else {
+            return Ok(());
+        };
+        if settings.host.trim().is_empty() || settings.topic.trim().is_empty() {
+            return Err("MQTT needs a broker host and a topic".to_string());
+        }
+
+        let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &settings.username {
+            options.set_credentials(username, settings.password.clone().unwrap_or_default());
+        }
+        let (client, mut connection) = Client::new(options, 10);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Jobs run on their own task, in arrival order, so a slow printer
+        // doesn't stall the connection's keep-alives
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Vec<u8>>();
+        let worker_client = client.clone();
+        let worker_settings = settings.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result_topic = worker_settings
+                .result_topic
+                .clone()
+                .unwrap_or_else(|| format!("{}/result", worker_settings.topic));
+            while let Some(payload) = queue.recv().await {
+                let result = handle_message(&app, &worker_settings, &payload).await;
+                let json = serde_json::to_vec(&result).unwrap_or_default();
+                // Doesn't wait for room in the client's queue, which would
+                // block the runtime
+                if let Err(e) = worker_client.try_publish(&result_topic, QoS::AtLeastOnce, false, json) {
+                    error!("Failed to publish MQTT job result: {}", e);
+                }
+            }
+        });
+
+        let subscribe_client = client.clone();
+        let connection_stop = stop.clone();
+        std::thread::spawn(move || {
+            for notification in connection.iter() {
+                if connection_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                match notification {
+                    // Subscribe on every (re)connect, since the broker may
+                    // have dropped the session
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker {}:{}", settings.host, settings.port);
+                        if let Err(e) = subscribe_client.try_subscribe(&settings.topic, QoS::AtLeastOnce) {
+                            error!("Failed to subscribe to {}: {}", settings.topic, e);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        if jobs.send(publish.payload.to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("MQTT connection error: {}", e);
+                        std::thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            }
+        });
+
+        *current = Some(Session { client, stop });
+        Ok(())
+    }
+}
+
+async fn handle_message(app: &AppHandle, settings: &MqttSettings, payload: &[u8]) -> JobResult {
+    let mut id = None;
+    let result = match parse_request(app, settings, payload) {
+        Ok((request_id, request)) => {
+            id = request_id;
+            api::print_records(app, "mqtt", request).await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(response) => JobResult {
+            id,
+            ok: true,
+            labels: response.labels,
+            message: response.message,
+        },
+        Err(e) => {
+            error!("MQTT print request failed: {}", e);
+            JobResult {
+                id,
+                ok: false,
+                labels: 0,
+                message: e,
+            }
+        }
+    }
+}
+
+fn parse_request(
+    app: &AppHandle,
+    settings: &MqttSettings,
+    payload: &[u8],
+) -> Result<(Option<Value>, PrintRequest), String> {
+    let mut message: Map<String, Value> =
+        serde_json::from_slice(payload).map_err(|e| format!("MQTT message isn't a JSON object: {}", e))?;
+
+    let mut request = PrintRequest {
+        template: settings.template.clone(),
+        definition: None,
+        records: Vec::new(),
+        printer: settings.printer_name.clone(),
+        check_digits: CheckDigitPolicy::Reject,
+        priority: Priority::Normal,
+    };
+    let Some(records) = message.remove("records") else {
+        request.records.push(record(message));
+        return Ok((None, request));
+    };
+
+    let Value::Array(records) = records else {
+        return Err("\"records\" must be an array of objects".to_string());
+    };
+    for value in records {
+        let Value::Object(fields) = value else {
+            return Err("\"records\" must be an array of objects".to_string());
+        };
+        request.records.push(record(fields));
+    }
+    // Only the configured template may be a file outside the shared
+    // templates directory
+    if let Some(Value::String(template)) = message.remove("template") {
+        api::check_template_path(app, &template)?;
+        request.template = template;
+    }
+    if let Some(Value::String(printer)) = message.remove("printer") {
+        request.printer = Some(printer);
+    }
+    Ok((message.remove("id"), request))
+}
+
+// Automation tools send numbers and booleans as JSON values; merge fields
+// are text
+fn record(fields: Map<String, Value>) -> BTreeMap<String, String> {
+    fields
+        .into_iter()
+        .map(|(name, value)| {
+            let text = match value {
+                Value::String(s) => s,
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            (name, text)
+        })
+        .collect()
+}
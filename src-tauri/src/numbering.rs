@@ -0,0 +1,114 @@
+// Numbered batches: "print 0001-0250" fills the `{number}` field with each
+// number of a range in turn, on as many copies of each label as asked, all
+// generated while the batch is merged. A range can be tied to a counter so
+// later jobs carry on after it and no number is printed twice.
+use crate::counters::{Counter, CounterStore};
+use serde::{Deserialize, Serialize};
+
+/// Field the current number is filled into
+pub const NUMBER_FIELD: &str = "number";
+
+/// Most labels one batch prints, so a range like 0-18446744073709551615 is
+/// refused rather than filling memory
+const MAX_LABELS: u64 = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberRange {
+    pub first: u64,
+    /// Inclusive; the last number printed is the highest step from `first`
+    /// that doesn't pass it
+    pub last: u64,
+    #[serde(default = "default_one")]
+    pub step: u64,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+    /// Minimum number of digits, zero-padded
+    #[serde(default)]
+    pub padding: usize,
+    /// Labels printed with each number
+    #[serde(default = "default_one")]
+    pub copies: u64,
+    /// Counter the range is taken from, which is moved past it when printed
+    #[serde(default)]
+    pub counter: Option<String>,
+}
+
+fn default_one() -> u64 {
+    1
+}
+
+impl NumberRange {
+    fn check(&self) -> Result<(), String> {
+        if self.step == 0 {
+            return Err("A number range's step must be at least 1".to_string());
+        }
+        if self.copies == 0 {
+            return Err("A number range needs at least one copy of each number".to_string());
+        }
+        if self.first > self.last {
+            return Err(format!("Invalid number range {}-{}", self.first, self.last));
+        }
+        if self.count().checked_mul(self.copies).is_none_or(|labels| labels > MAX_LABELS) {
+            return Err(format!(
+                "Number range {}-{} is more than {} labels; print it in smaller batches",
+                self.first, self.last, MAX_LABELS
+            ));
+        }
+        Ok(())
+    }
+
+    // Numbers in the range; saturates for ranges no batch could print
+    fn count(&self) -> u64 {
+        ((self.last - self.first) / self.step).saturating_add(1)
+    }
+
+    /// The value of the `{number}` field on each label, in print order:
+    /// every number `copies` times before the next
+    pub fn labels(&self) -> Result<Vec<String>, String> {
+        self.check()?;
+        let format = Counter {
+            next: self.first,
+            step: self.step,
+            prefix: self.prefix.clone(),
+            suffix: self.suffix.clone(),
+            padding: self.padding,
+        };
+        // Within MAX_LABELS, and every number within `first..=last`
+        let mut labels = Vec::with_capacity((self.count() * self.copies) as usize);
+        for i in 0..self.count() {
+            let number = format.format(self.first + i * self.step);
+            for _ in 0..self.copies {
+                labels.push(number.clone());
+            }
+        }
+        Ok(labels)
+    }
+
+    /// The part of the range that fills the first `labels` labels, for
+    /// previews
+    pub fn limited(&self, labels: usize) -> NumberRange {
+        let numbers = (labels as u64).div_ceil(self.copies.max(1)).max(1);
+        let last = self
+            .first
+            .saturating_add((numbers - 1).saturating_mul(self.step.max(1)));
+        NumberRange {
+            last: last.min(self.last),
+            ..self.clone()
+        }
+    }
+
+    /// Move the range's counter, if it has one, past the range before it's
+    /// printed. A failed print leaves a gap rather than a repeat.
+    pub fn reserve(&self, counters: &CounterStore) -> Result<(), String> {
+        self.check()?;
+        let Some(counter) = &self.counter else {
+            return Ok(());
+        };
+        let next = (self.first + (self.count() - 1) * self.step)
+            .checked_add(self.step)
+            .ok_or_else(|| format!("Counter {} has run out of values", counter))?;
+        counters.reserve(counter, self.first, next)
+    }
+}
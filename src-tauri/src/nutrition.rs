@@ -0,0 +1,650 @@
+// Nutrition panels: the EU nutrition declaration table (Regulation (EU) No
+// 1169/2011, Annex XV) and the FDA Nutrition Facts panel (21 CFR 101.9),
+// with each standard's rounding rules applied to the declared values. The
+// panel is laid out to the element's width, with the text sized so it fills
+// the element's height.
+use crate::document::{NutritionElement, NutritionStandard, NutritionValues};
+use crate::formats::escape;
+use crate::i18n;
+use crate::render::{self, ASCENT, LINE_HEIGHT};
+use std::fmt::Write;
+
+/// Average character width as a fraction of the font size, for fitting text
+const CHAR_WIDTH: f64 = 0.55;
+
+/// Energy conversion used by both standards
+const KJ_PER_KCAL: f64 = 4.184;
+
+/// EU reference intakes (Annex XIII, part B)
+const EU_REFERENCE_INTAKES: &[(&str, f64)] = &[
+    ("energy", 8400.0),
+    ("fat", 70.0),
+    ("saturates", 20.0),
+    ("carbohydrate", 260.0),
+    ("sugars", 90.0),
+    ("protein", 50.0),
+    ("salt", 6.0),
+];
+
+/// Salt is sodium times 2.5
+const SALT_PER_SODIUM: f64 = 2.5;
+
+// A run of text in a row
+struct Span {
+    text: String,
+    bold: bool,
+}
+
+fn span(text: impl Into<String>, bold: bool) -> Span {
+    Span {
+        text: text.into(),
+        bold,
+    }
+}
+
+// One line of the panel, in units of the base font size
+enum Row {
+    Text {
+        left: Vec<Span>,
+        right: Vec<Span>,
+        /// Font size relative to the base
+        size: f64,
+        /// Indent in base font sizes
+        indent: f64,
+    },
+    /// Horizontal line, `thickness` base font sizes thick
+    Rule { thickness: f64 },
+    /// Paragraph wrapped to the panel width
+    Note { text: String, size: f64 },
+}
+
+impl Row {
+    fn text(left: Vec<Span>, right: Vec<Span>, size: f64, indent: f64) -> Self {
+        Row::Text {
+            left,
+            right,
+            size,
+            indent,
+        }
+    }
+}
+
+fn parse(value: &str, name: &str) -> Result<Option<f64>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .map(Some)
+        .ok_or_else(|| format!("Nutrition value for {} is not a number: \"{}\"", name, value))
+}
+
+// Values as numbers, with energy and salt or sodium filled in from each other
+struct Amounts {
+    kj: Option<f64>,
+    kcal: Option<f64>,
+    fat: Option<f64>,
+    saturated_fat: Option<f64>,
+    trans_fat: Option<f64>,
+    cholesterol: Option<f64>,
+    carbohydrate: Option<f64>,
+    sugars: Option<f64>,
+    added_sugars: Option<f64>,
+    fibre: Option<f64>,
+    protein: Option<f64>,
+    /// Grams
+    salt: Option<f64>,
+    /// Milligrams
+    sodium: Option<f64>,
+    vitamin_d: Option<f64>,
+    calcium: Option<f64>,
+    iron: Option<f64>,
+    potassium: Option<f64>,
+}
+
+impl Amounts {
+    fn new(values: &NutritionValues) -> Result<Self, String> {
+        let kj = parse(&values.energy_kj, "energy (kJ)")?;
+        let kcal = parse(&values.energy_kcal, "energy (kcal)")?;
+        let salt = parse(&values.salt, "salt")?;
+        let sodium = parse(&values.sodium, "sodium")?;
+        Ok(Amounts {
+            kj: kj.or(kcal.map(|kcal| kcal * KJ_PER_KCAL)),
+            kcal: kcal.or(kj.map(|kj| kj / KJ_PER_KCAL)),
+            fat: parse(&values.fat, "fat")?,
+            saturated_fat: parse(&values.saturated_fat, "saturated fat")?,
+            trans_fat: parse(&values.trans_fat, "trans fat")?,
+            cholesterol: parse(&values.cholesterol, "cholesterol")?,
+            carbohydrate: parse(&values.carbohydrate, "carbohydrate")?,
+            sugars: parse(&values.sugars, "sugars")?,
+            added_sugars: parse(&values.added_sugars, "added sugars")?,
+            fibre: parse(&values.fibre, "fibre")?,
+            protein: parse(&values.protein, "protein")?,
+            salt: salt.or(sodium.map(|mg| mg / 1000.0 * SALT_PER_SODIUM)),
+            sodium: sodium.or(salt.map(|g| g / SALT_PER_SODIUM * 1000.0)),
+            vitamin_d: parse(&values.vitamin_d, "vitamin D")?,
+            calcium: parse(&values.calcium, "calcium")?,
+            iron: parse(&values.iron, "iron")?,
+            potassium: parse(&values.potassium, "potassium")?,
+        })
+    }
+}
+
+fn required(value: Option<f64>, name: &str) -> Result<f64, String> {
+    value.ok_or_else(|| format!("The nutrition panel needs a value for {}", name))
+}
+
+fn round_to(value: f64, step: f64) -> f64 {
+    (value / step).round() * step
+}
+
+// `value` with `decimals` places, with a decimal comma if asked for
+fn number(value: f64, decimals: usize, comma: bool) -> String {
+    let text = format!("{:.*}", decimals, value);
+    if comma {
+        text.replace('.', ",")
+    } else {
+        text
+    }
+}
+
+// EU rounding guidance for nutrition labelling (European Commission, 2012)
+fn eu_grams(value: f64, comma: bool) -> String {
+    if value >= 10.0 {
+        format!("{} g", number(value.round(), 0, comma))
+    } else if value > 0.5 {
+        format!("{} g", number(round_to(value, 0.1), 1, comma))
+    } else if value > 0.0 {
+        format!("<{} g", number(0.5, 1, comma))
+    } else {
+        "0 g".to_string()
+    }
+}
+
+fn eu_saturates(value: f64, comma: bool) -> String {
+    if value >= 10.0 {
+        format!("{} g", number(value.round(), 0, comma))
+    } else if value > 0.1 {
+        format!("{} g", number(round_to(value, 0.1), 1, comma))
+    } else if value > 0.0 {
+        format!("<{} g", number(0.1, 1, comma))
+    } else {
+        "0 g".to_string()
+    }
+}
+
+fn eu_salt(value: f64, comma: bool) -> String {
+    if value >= 1.0 {
+        format!("{} g", number(round_to(value, 0.1), 1, comma))
+    } else if value > 0.0125 {
+        format!("{} g", number(round_to(value, 0.01), 2, comma))
+    } else if value > 0.0 {
+        format!("<{} g", number(0.01, 2, comma))
+    } else {
+        "0 g".to_string()
+    }
+}
+
+fn eu_rows(panel: &NutritionElement, amounts: &Amounts) -> Result<Vec<Row>, String> {
+    let comma = panel.decimal_comma;
+    let kj = required(amounts.kj, "energy")?;
+    let kcal = required(amounts.kcal, "energy")?;
+    let per = match panel.serving_size.trim() {
+        "" => "100 g",
+        size => size,
+    };
+    let intake = |name: &str, value: f64| -> Vec<Span> {
+        if !panel.reference_intakes {
+            return Vec::new();
+        }
+        let reference = EU_REFERENCE_INTAKES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(0.0, |(_, r)| *r);
+        vec![span(format!("  {}%", (value / reference * 100.0).round()), false)]
+    };
+
+    let mut rows = vec![
+        Row::text(
+            vec![span(i18n::t("nutrition-title", &[]), true)],
+            [
+                vec![span(
+                    i18n::t("nutrition-per", &[("amount", per.to_string().into())]),
+                    true,
+                )],
+                if panel.reference_intakes {
+                    vec![span(format!("  {}", i18n::t("nutrition-reference-intake", &[])), true)]
+                } else {
+                    Vec::new()
+                },
+            ]
+            .concat(),
+            1.0,
+            0.0,
+        ),
+        Row::Rule { thickness: 0.12 },
+        Row::text(
+            vec![span(i18n::t("nutrition-energy", &[]), false)],
+            [
+                vec![span(
+                    format!(
+                        "{} kJ / {} kcal",
+                        number(kj.round(), 0, comma),
+                        number(kcal.round(), 0, comma)
+                    ),
+                    false,
+                )],
+                intake("energy", kj),
+            ]
+            .concat(),
+            1.0,
+            0.0,
+        ),
+    ];
+    let mut nutrient = |label: &str, indent: f64, value: f64, text: String, reference: Option<&str>| {
+        rows.push(Row::Rule { thickness: 0.04 });
+        let right = [
+            vec![span(text, false)],
+            reference.map_or(Vec::new(), |r| intake(r, value)),
+        ]
+        .concat();
+        rows.push(Row::text(vec![span(i18n::t(label, &[]), false)], right, 1.0, indent));
+    };
+    let fat = required(amounts.fat, "fat")?;
+    nutrient("nutrition-fat", 0.0, fat, eu_grams(fat, comma), Some("fat"));
+    let saturates = required(amounts.saturated_fat, "saturated fat")?;
+    nutrient(
+        "nutrition-saturates",
+        1.0,
+        saturates,
+        eu_saturates(saturates, comma),
+        Some("saturates"),
+    );
+    let carbohydrate = required(amounts.carbohydrate, "carbohydrate")?;
+    nutrient(
+        "nutrition-carbohydrate",
+        0.0,
+        carbohydrate,
+        eu_grams(carbohydrate, comma),
+        Some("carbohydrate"),
+    );
+    let sugars = required(amounts.sugars, "sugars")?;
+    nutrient("nutrition-sugars", 1.0, sugars, eu_grams(sugars, comma), Some("sugars"));
+    // Fibre is the one voluntary nutrient here
+    if let Some(fibre) = amounts.fibre {
+        nutrient("nutrition-fibre", 0.0, fibre, eu_grams(fibre, comma), None);
+    }
+    let protein = required(amounts.protein, "protein")?;
+    nutrient(
+        "nutrition-protein",
+        0.0,
+        protein,
+        eu_grams(protein, comma),
+        Some("protein"),
+    );
+    let salt = required(amounts.salt, "salt")?;
+    nutrient("nutrition-salt", 0.0, salt, eu_salt(salt, comma), Some("salt"));
+
+    rows.push(Row::Rule { thickness: 0.12 });
+    if panel.reference_intakes {
+        rows.push(Row::Note {
+            text: i18n::t("nutrition-reference-intake-note", &[]),
+            size: 0.75,
+        });
+    }
+    Ok(rows)
+}
+
+// FDA rounding (21 CFR 101.9(c))
+fn us_calories(kcal: f64) -> f64 {
+    match kcal {
+        k if k < 5.0 => 0.0,
+        k if k <= 50.0 => round_to(k, 5.0),
+        k => round_to(k, 10.0),
+    }
+}
+
+fn us_fat(grams: f64) -> String {
+    let rounded = match grams {
+        g if g < 0.5 => 0.0,
+        g if g < 5.0 => round_to(g, 0.5),
+        g => g.round(),
+    };
+    format!("{}g", rounded)
+}
+
+fn us_grams(grams: f64) -> String {
+    match grams {
+        g if g < 0.5 => "0g".to_string(),
+        g if g < 1.0 => "less than 1g".to_string(),
+        g => format!("{}g", g.round()),
+    }
+}
+
+fn us_cholesterol(mg: f64) -> String {
+    match mg {
+        m if m < 2.0 => "0mg".to_string(),
+        m if m <= 5.0 => "less than 5mg".to_string(),
+        m => format!("{}mg", round_to(m, 5.0)),
+    }
+}
+
+// Sodium and potassium
+fn us_milligrams(mg: f64) -> String {
+    let rounded = match mg {
+        m if m < 5.0 => 0.0,
+        m if m <= 140.0 => round_to(m, 5.0),
+        m => round_to(m, 10.0),
+    };
+    format!("{}mg", rounded)
+}
+
+fn us_daily_value(value: f64, daily: f64) -> String {
+    format!("{}%", (value / daily * 100.0).round())
+}
+
+fn us_rows(panel: &NutritionElement, amounts: &Amounts) -> Result<Vec<Row>, String> {
+    let kcal = required(amounts.kcal, "calories")?;
+    let fat = required(amounts.fat, "total fat")?;
+    let saturated = required(amounts.saturated_fat, "saturated fat")?;
+    let trans = required(amounts.trans_fat, "trans fat")?;
+    let cholesterol = required(amounts.cholesterol, "cholesterol")?;
+    let sodium = required(amounts.sodium, "sodium")?;
+    let carbohydrate = required(amounts.carbohydrate, "total carbohydrate")?;
+    let fibre = required(amounts.fibre, "dietary fiber")?;
+    let sugars = required(amounts.sugars, "total sugars")?;
+    let added = required(amounts.added_sugars, "added sugars")?;
+    let protein = required(amounts.protein, "protein")?;
+    let vitamin_d = required(amounts.vitamin_d, "vitamin D")?;
+    let calcium = required(amounts.calcium, "calcium")?;
+    let iron = required(amounts.iron, "iron")?;
+    let potassium = required(amounts.potassium, "potassium")?;
+
+    let mut rows = vec![Row::text(vec![span("Nutrition Facts", true)], Vec::new(), 2.4, 0.0)];
+    rows.push(Row::Rule { thickness: 0.06 });
+    if !panel.servings_per_container.trim().is_empty() {
+        rows.push(Row::text(
+            vec![span(
+                format!("{} servings per container", panel.servings_per_container.trim()),
+                false,
+            )],
+            Vec::new(),
+            1.1,
+            0.0,
+        ));
+    }
+    rows.push(Row::text(
+        vec![span("Serving size", true)],
+        vec![span(panel.serving_size.trim(), true)],
+        1.25,
+        0.0,
+    ));
+    rows.push(Row::Rule { thickness: 0.8 });
+    rows.push(Row::text(vec![span("Amount per serving", true)], Vec::new(), 0.8, 0.0));
+    rows.push(Row::text(
+        vec![span("Calories", true)],
+        vec![span(us_calories(kcal).to_string(), true)],
+        2.0,
+        0.0,
+    ));
+    rows.push(Row::Rule { thickness: 0.4 });
+    rows.push(Row::text(Vec::new(), vec![span("% Daily Value*", true)], 0.8, 0.0));
+
+    fn nutrient(rows: &mut Vec<Row>, name: &str, bold: bool, indent: f64, amount: String, daily: Option<String>) {
+        rows.push(Row::Rule { thickness: 0.04 });
+        rows.push(Row::text(
+            vec![span(name, bold), span(format!(" {}", amount), false)],
+            daily.map(|d| vec![span(d, true)]).unwrap_or_default(),
+            1.0,
+            indent,
+        ));
+    }
+    // Daily values for adults and children 4 years and older (21 CFR 101.9(c)(8)-(9))
+    nutrient(
+        &mut rows,
+        "Total Fat",
+        true,
+        0.0,
+        us_fat(fat),
+        Some(us_daily_value(fat, 78.0)),
+    );
+    nutrient(
+        &mut rows,
+        "Saturated Fat",
+        false,
+        1.0,
+        us_fat(saturated),
+        Some(us_daily_value(saturated, 20.0)),
+    );
+    nutrient(&mut rows, "Trans Fat", false, 1.0, us_fat(trans), None);
+    nutrient(
+        &mut rows,
+        "Cholesterol",
+        true,
+        0.0,
+        us_cholesterol(cholesterol),
+        Some(us_daily_value(cholesterol, 300.0)),
+    );
+    nutrient(
+        &mut rows,
+        "Sodium",
+        true,
+        0.0,
+        us_milligrams(sodium),
+        Some(us_daily_value(sodium, 2300.0)),
+    );
+    nutrient(
+        &mut rows,
+        "Total Carbohydrate",
+        true,
+        0.0,
+        us_grams(carbohydrate),
+        Some(us_daily_value(carbohydrate, 275.0)),
+    );
+    nutrient(
+        &mut rows,
+        "Dietary Fiber",
+        false,
+        1.0,
+        us_grams(fibre),
+        Some(us_daily_value(fibre, 28.0)),
+    );
+    nutrient(&mut rows, "Total Sugars", false, 1.0, us_grams(sugars), None);
+    rows.push(Row::Rule { thickness: 0.04 });
+    rows.push(Row::text(
+        vec![span(format!("Includes {} Added Sugars", us_grams(added)), false)],
+        vec![span(us_daily_value(added, 50.0), true)],
+        1.0,
+        2.0,
+    ));
+    nutrient(&mut rows, "Protein", true, 0.0, us_grams(protein), None);
+    rows.push(Row::Rule { thickness: 0.8 });
+    let mut micronutrient = |name: &str, amount: String, daily: String, first: bool| {
+        if !first {
+            rows.push(Row::Rule { thickness: 0.04 });
+        }
+        rows.push(Row::text(
+            vec![span(format!("{} {}", name, amount), false)],
+            vec![span(daily, false)],
+            1.0,
+            0.0,
+        ));
+    };
+    micronutrient(
+        "Vitamin D",
+        format!("{:.1}mcg", vitamin_d),
+        us_daily_value(vitamin_d, 20.0),
+        true,
+    );
+    micronutrient(
+        "Calcium",
+        format!("{}mg", round_to(calcium, 10.0)),
+        us_daily_value(calcium, 1300.0),
+        false,
+    );
+    micronutrient("Iron", format!("{:.1}mg", iron), us_daily_value(iron, 18.0), false);
+    micronutrient(
+        "Potassium",
+        us_milligrams(potassium),
+        us_daily_value(potassium, 4700.0),
+        false,
+    );
+    rows.push(Row::Rule { thickness: 0.4 });
+    rows.push(Row::Note {
+        text: "* The % Daily Value (DV) tells you how much a nutrient in a serving of food contributes to a daily \
+               diet. 2,000 calories a day is used for general nutrition advice."
+            .to_string(),
+        size: 0.7,
+    });
+    Ok(rows)
+}
+
+fn chars(spans: &[Span]) -> usize {
+    spans.iter().map(|s| s.text.chars().count()).sum()
+}
+
+// Height of `rows` in base font sizes at base font size `font`, with notes
+// wrapped to `width`
+fn rows_height(rows: &[Row], font: f64, width: f64) -> f64 {
+    rows.iter()
+        .map(|row| match row {
+            Row::Text { size, .. } => size * LINE_HEIGHT,
+            Row::Rule { thickness } => thickness + 0.2,
+            Row::Note { text, size } => {
+                let lines = render::wrap(text, max_chars(width, font * size)).len();
+                size * LINE_HEIGHT * lines as f64 + 0.2
+            }
+        })
+        .sum()
+}
+
+fn max_chars(width: f64, font: f64) -> usize {
+    (width / (font * CHAR_WIDTH)).floor().max(1.0) as usize
+}
+
+/// SVG elements drawing `panel` into a `width` x `height` box at the origin.
+/// Fails when a mandatory value is missing or a value isn't a number.
+pub fn panel_svg(panel: &NutritionElement, width: f64, height: f64) -> Result<String, String> {
+    let amounts = Amounts::new(&panel.values)?;
+    let rows = match panel.standard {
+        NutritionStandard::Eu => eu_rows(panel, &amounts)?,
+        NutritionStandard::Us => us_rows(panel, &amounts)?,
+    };
+    // US panels are boxed, with a little padding inside the box
+    let boxed = panel.standard == NutritionStandard::Us;
+    let padding = if boxed { (width * 0.03).min(1.5) } else { 0.0 };
+    let inner = width - 2.0 * padding;
+
+    // The largest base font size at which every row fits across and the
+    // rows fit down; notes rewrap as the size changes
+    let mut font = f64::INFINITY;
+    for row in &rows {
+        if let Row::Text {
+            left,
+            right,
+            size,
+            indent,
+        } = row
+        {
+            let characters = (chars(left) + chars(right) + 2) as f64 * CHAR_WIDTH * size + indent;
+            font = font.min(inner / characters);
+        }
+    }
+    for _ in 0..4 {
+        let fitting = (height - 2.0 * padding) / rows_height(&rows, font, inner);
+        if fitting >= font {
+            break;
+        }
+        font = fitting;
+    }
+    if !(font.is_finite() && font > 0.0) {
+        return Err("The nutrition panel is too small to lay out".to_string());
+    }
+
+    let mut svg = String::new();
+    let font_family = escape(&panel.font_family);
+    let color = escape(&panel.color);
+    if boxed {
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{0}\" y=\"{0}\" width=\"{1}\" height=\"{2}\" fill=\"none\" stroke=\"{3}\" stroke-width=\"{0}\"/>",
+            font * 0.05,
+            width - font * 0.1,
+            height - font * 0.1,
+            color
+        );
+    }
+    let mut y = padding;
+    for row in &rows {
+        match row {
+            Row::Text {
+                left,
+                right,
+                size,
+                indent,
+            } => {
+                let font_size = font * size;
+                let baseline = y + font_size * ASCENT;
+                for (spans, x, anchor) in [
+                    (left, padding + indent * font, "start"),
+                    (right, width - padding, "end"),
+                ] {
+                    if spans.is_empty() {
+                        continue;
+                    }
+                    let _ = write!(
+                        svg,
+                        "<text x=\"{}\" y=\"{}\" font-family=\"'{}', sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"{}\" xml:space=\"preserve\">",
+                        x, baseline, font_family, font_size, color, anchor
+                    );
+                    for span in spans {
+                        let _ = write!(
+                            svg,
+                            "<tspan font-weight=\"{}\">{}</tspan>",
+                            if span.bold { "bold" } else { "normal" },
+                            escape(&span.text)
+                        );
+                    }
+                    svg.push_str("</text>\n");
+                }
+                y += font_size * LINE_HEIGHT;
+            }
+            Row::Rule { thickness } => {
+                let thickness = thickness * font;
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    padding,
+                    y + font * 0.1,
+                    inner,
+                    thickness,
+                    color
+                );
+                y += thickness + font * 0.2;
+            }
+            Row::Note { text, size } => {
+                let font_size = font * size;
+                y += font * 0.1;
+                for line in render::wrap(text, max_chars(inner, font_size)) {
+                    let _ = writeln!(
+                        svg,
+                        "<text x=\"{}\" y=\"{}\" font-family=\"'{}', sans-serif\" font-size=\"{}\" fill=\"{}\">{}</text>",
+                        padding,
+                        y + font_size * ASCENT,
+                        font_family,
+                        font_size,
+                        color,
+                        escape(&line)
+                    );
+                    y += font_size * LINE_HEIGHT;
+                }
+                y += font * 0.1;
+            }
+        }
+    }
+    Ok(svg)
+}
@@ -0,0 +1,135 @@
+// Text recognition in photos and scans of existing labels with Tesseract, so
+// a legacy label can be rebuilt as an editable design: every line of text
+// found becomes a text element where it was on the label.
+use crate::document::{Document, Element, ElementKind, TextElement};
+use crate::error::LabelgoodError;
+use crate::formats::MM_PER_PT;
+use crate::i18n;
+use crate::printing::{self, RENDER_DPI};
+use crate::tools;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+const TESSERACT_TIMEOUT: Duration = Duration::from_secs(120);
+/// Words Tesseract is less sure of than this, in percent, are dropped
+const MIN_CONFIDENCE: f64 = 40.0;
+/// Height of a line's box, from ascenders to descenders, in ems
+const LINE_BOX_EM: f64 = 0.85;
+
+// A line of recognized text and its box in pixels
+struct Line {
+    words: Vec<String>,
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+// Lines of Tesseract's TSV output: a header, then a row per page, block,
+// paragraph, line and word, e.g.
+//
+//   level page_num block_num par_num line_num word_num left top width height conf text
+//   5     1        2         1       1        1        112  40  210   38     91.4 BEST
+//
+// Words come in reading order within their line.
+fn parse_tsv(tsv: &str) -> Vec<Line> {
+    let mut lines: BTreeMap<(u32, u32, u32, u32), Line> = BTreeMap::new();
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.split('\t').collect();
+        if columns.len() < 12 || columns[0] != "5" {
+            continue;
+        }
+        let number = |i: usize| columns[i].trim().parse::<u32>().unwrap_or(0);
+        let text = columns[11].trim();
+        let confidence = columns[10].trim().parse::<f64>().unwrap_or(0.0);
+        if text.is_empty() || confidence < MIN_CONFIDENCE {
+            continue;
+        }
+        let (left, top) = (number(6), number(7));
+        let (right, bottom) = (left + number(8), top + number(9));
+        let line = lines
+            .entry((number(1), number(2), number(3), number(4)))
+            .or_insert(Line {
+                words: Vec::new(),
+                left,
+                top,
+                right,
+                bottom,
+            });
+        line.words.push(text.to_string());
+        line.left = line.left.min(left);
+        line.top = line.top.min(top);
+        line.right = line.right.max(right);
+        line.bottom = line.bottom.max(bottom);
+    }
+    lines.into_values().collect()
+}
+
+/// A document with the text of the label in the photo or scan at `path`,
+/// `width_mm` wide (the image's width at 300 dpi when not given). `lang` is
+/// one or more Tesseract languages, e.g. "eng" or "nld+eng".
+#[tauri::command]
+pub async fn recognize_label(
+    path: String,
+    width_mm: Option<f64>,
+    lang: Option<String>,
+) -> Result<Document, LabelgoodError> {
+    let lang = lang
+        .as_deref()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .unwrap_or("eng");
+    if !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+') {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!("Not a Tesseract language: \"{}\"", lang),
+        });
+    }
+    let (width_px, height_px) =
+        image::image_dimensions(Path::new(&path)).map_err(|e| LabelgoodError::InvalidInput {
+            message: format!("Failed to read image {}: {}", path, e),
+        })?;
+    let mm_per_px = width_mm
+        .filter(|w| *w > 0.0)
+        .map_or(25.4 / RENDER_DPI, |w| w / width_px as f64);
+    let mut document = Document::new(width_px as f64 * mm_per_px, height_px as f64 * mm_per_px);
+    LabelgoodError::check_dimensions(document.width_mm, document.height_mm)?;
+
+    // Page segmentation mode 11: sparse text in no particular order, as on
+    // most labels
+    let mut command = tools::command("tesseract");
+    command
+        .arg(&path)
+        .arg("stdout")
+        .args(["-l", lang, "--psm", "11", "tsv"]);
+    let missing = |e: std::io::Error| LabelgoodError::DependencyMissing {
+        program: "tesseract".to_string(),
+        message: i18n::t("tesseract-missing", &[("error", e.to_string().into())]),
+    };
+    let output = printing::run_command(command, TESSERACT_TIMEOUT, missing).await?;
+    if !output.status.success() {
+        return Err(format!(
+            "Tesseract failed to read {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    for line in parse_tsv(&String::from_utf8_lossy(&output.stdout)) {
+        let height_mm = (line.bottom - line.top) as f64 * mm_per_px;
+        let text = TextElement {
+            text: line.words.join(" "),
+            font_size_pt: height_mm / LINE_BOX_EM / MM_PER_PT,
+            ..TextElement::default()
+        };
+        document.elements.push(Element::new(
+            line.left as f64 * mm_per_px,
+            line.top as f64 * mm_per_px,
+            (line.right - line.left) as f64 * mm_per_px,
+            height_mm,
+            ElementKind::Text(text),
+        ));
+    }
+    Ok(document)
+}
@@ -0,0 +1,398 @@
+// Jobs held for unreachable printers. A job whose printer is offline, or whose
+// queue isn't accepting jobs, is kept with its PDF in a SQLite database in
+// the app data directory and ends as held rather than printed. The printer
+// monitor tries to print it whenever it sees the printer idle, across
+// restarts, with the template, data and operator it was started with.
+// Printers no backend knows, such as a mistyped queue name, still fail
+// straight away.
+use crate::backends;
+use crate::error::LabelgoodError;
+use crate::events::{self, EventBus, LabelEvent};
+use crate::history::JobDetails;
+use crate::i18n;
+use crate::printing;
+use crate::progress;
+use crate::spool;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS held_jobs (
+        id INTEGER PRIMARY KEY,
+        held_at TEXT NOT NULL,
+        printer TEXT NOT NULL,
+        width_mm REAL NOT NULL,
+        height_mm REAL NOT NULL,
+        labels INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        pdf BLOB NOT NULL
+    );
+";
+
+const JOB_COLUMNS: &str = "id, held_at, printer, width_mm, height_mm, labels, reason";
+
+// Columns added since the table was first created, with their types
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("template", "TEXT"),
+    ("data", "TEXT NOT NULL DEFAULT '[]'"),
+    ("operator", "TEXT"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeldJob {
+    pub id: i64,
+    /// UTC, RFC 3339
+    pub held_at: String,
+    pub printer: String,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub labels: usize,
+    /// Why the printer couldn't be reached
+    pub reason: String,
+}
+
+pub struct OfflineStore {
+    connection: Mutex<Connection>,
+    /// Printers whose held jobs are being printed right now
+    releasing: Mutex<HashSet<String>>,
+}
+
+// Held jobs are taken deep inside `printing::submit_pdf`, which has no access
+// to Tauri state
+static STORE: OnceLock<OfflineStore> = OnceLock::new();
+
+tokio::task_local! {
+    // Set while held jobs are printed, so one that fails again stays where
+    // it is rather than being held a second time
+    static RELEASE: ();
+    // The job being run, so one held deep inside `printing::submit_pdf`
+    // keeps what it printed and its runner learns it was held
+    static JOB: RefCell<RunningJob>;
+}
+
+struct RunningJob {
+    operator: Option<String>,
+    details: JobDetails,
+    held: Option<i64>,
+}
+
+/// Run a job's `work`, returning its output along with the id of the held
+/// job it became, if its printer was unreachable
+pub async fn track<T>(
+    operator: Option<String>,
+    details: JobDetails,
+    work: impl Future<Output = T>,
+) -> (T, Option<i64>) {
+    let job = RunningJob {
+        operator,
+        details,
+        held: None,
+    };
+    JOB.scope(RefCell::new(job), async {
+        let output = work.await;
+        (output, JOB.with(|job| job.borrow().held))
+    })
+    .await
+}
+
+/// Open or create the database at `path`; later calls have no effect.
+/// Without it, as in the CLI, jobs for unreachable printers fail.
+pub fn init(path: &Path) {
+    if STORE.get().is_some() {
+        return;
+    }
+    match open_file(path) {
+        Ok(connection) => {
+            let _ = STORE.set(OfflineStore {
+                connection: Mutex::new(connection),
+                releasing: Mutex::new(HashSet::new()),
+            });
+        }
+        Err(e) => error!("{}; jobs for unreachable printers won't be held", e),
+    }
+}
+
+fn open_file(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let connection = Connection::open(path).map_err(|e| format!("Failed to open held jobs: {}", e))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to set up held jobs: {}", e))?;
+    for (column, kind) in ADDED_COLUMNS {
+        // Preparing a query fails when the column doesn't exist yet
+        if connection.prepare(&format!("SELECT {} FROM held_jobs LIMIT 0", column)).is_err() {
+            connection
+                .execute_batch(&format!("ALTER TABLE held_jobs ADD COLUMN {} {}", column, kind))
+                .map_err(|e| format!("Failed to update held jobs: {}", e))?;
+        }
+    }
+    Ok(connection)
+}
+
+fn store() -> Result<&'static OfflineStore, String> {
+    STORE.get().ok_or_else(|| "Held jobs aren't available".to_string())
+}
+
+impl OfflineStore {
+    #[allow(clippy::too_many_arguments)]
+    fn add(
+        &self,
+        printer: &str,
+        width_mm: f64,
+        height_mm: f64,
+        labels: usize,
+        reason: &str,
+        pdf: &[u8],
+        operator: Option<&str>,
+        details: &JobDetails,
+    ) -> Result<i64, String> {
+        let data = serde_json::to_string(&details.data)
+            .map_err(|e| format!("Failed to serialize job data: {}", e))?;
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO held_jobs (held_at, printer, width_mm, height_mm, labels, reason, pdf, template, data, operator)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    printer,
+                    width_mm,
+                    height_mm,
+                    labels as i64,
+                    reason,
+                    pdf,
+                    details.template,
+                    data,
+                    operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to hold print job: {}", e))?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Held jobs, oldest first; only `printer`'s when given
+    fn list(&self, printer: Option<&str>) -> Result<Vec<HeldJob>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM held_jobs WHERE ?1 IS NULL OR printer = ?1 ORDER BY id",
+                JOB_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to query held jobs: {}", e))?;
+        let jobs = statement
+            .query_map([printer], held_job)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query held jobs: {}", e))?;
+        Ok(jobs)
+    }
+
+    fn pdf(&self, id: i64) -> Result<Vec<u8>, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row("SELECT pdf FROM held_jobs WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read held job: {}", e))?
+            .ok_or_else(|| format!("Held job {} not found", id))
+    }
+
+    // Who started held job `id` and what it printed
+    fn origin(&self, id: i64) -> Result<(Option<String>, JobDetails), String> {
+        let connection = self.connection.lock().unwrap();
+        let (operator, template, data): (Option<String>, Option<String>, String) = connection
+            .query_row(
+                "SELECT operator, template, data FROM held_jobs WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| format!("Failed to read held job: {}", e))?;
+        let details = JobDetails {
+            template,
+            // Written by `add`, so only a hand-edited database fails here
+            data: serde_json::from_str(&data).unwrap_or_default(),
+        };
+        Ok((operator, details))
+    }
+
+    fn remove(&self, id: i64) -> Result<bool, String> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection
+            .execute("DELETE FROM held_jobs WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to remove held job: {}", e))?;
+        Ok(deleted > 0)
+    }
+
+    fn printers(&self) -> Result<Vec<String>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT DISTINCT printer FROM held_jobs")
+            .map_err(|e| format!("Failed to query held jobs: {}", e))?;
+        let printers = statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to query held jobs: {}", e))?;
+        Ok(printers)
+    }
+}
+
+fn held_job(row: &Row) -> rusqlite::Result<HeldJob> {
+    Ok(HeldJob {
+        id: row.get(0)?,
+        held_at: row.get(1)?,
+        printer: row.get(2)?,
+        width_mm: row.get(3)?,
+        height_mm: row.get(4)?,
+        labels: row.get::<_, i64>(5)? as usize,
+        reason: row.get(6)?,
+    })
+}
+
+/// Keep a job `printer` couldn't take because it's unreachable, returning
+/// the `Held` error the job ends with. Returns `error` as it is when the job
+/// can't be held: there's no store, the printer is unknown rather than
+/// offline, or the job is a held job being released.
+pub async fn hold(
+    pdf_path: &Path,
+    width_mm: f64,
+    height_mm: f64,
+    printer: &str,
+    error: LabelgoodError,
+) -> LabelgoodError {
+    let Ok(store) = store() else {
+        return error;
+    };
+    if RELEASE.try_with(|_| ()).is_ok() {
+        return error;
+    }
+    // Backends only know the state of printers that exist
+    if backends::resolve(printer).status(printer).await.state == "unknown" {
+        return error;
+    }
+    let pdf = match fs::read(pdf_path) {
+        Ok(pdf) => pdf,
+        Err(e) => {
+            return LabelgoodError::Io {
+                message: format!("Failed to read PDF: {}", e),
+            }
+        }
+    };
+    let labels = printing::count_pages(&pdf);
+    let (operator, details) = JOB
+        .try_with(|job| {
+            let job = job.borrow();
+            (job.operator.clone(), job.details.clone())
+        })
+        .unwrap_or_default();
+    let id = match store.add(
+        printer,
+        width_mm,
+        height_mm,
+        labels,
+        error.message(),
+        &pdf,
+        operator.as_deref(),
+        &details,
+    ) {
+        Ok(id) => id,
+        Err(e) => return e.into(),
+    };
+    warn!("Holding job for {} until it's back: {}", printer, error);
+    let _ = JOB.try_with(|job| job.borrow_mut().held = Some(id));
+    if let Some(job) = progress::current_job() {
+        events::publish_in_job(LabelEvent::JobHeld {
+            job,
+            held: id,
+            printer: printer.to_string(),
+        });
+    }
+    LabelgoodError::Held {
+        printer: printer.to_string(),
+        held: id,
+        message: i18n::t("job-held", &[("printer", printer.into())]),
+    }
+}
+
+/// Printers with held jobs, for the printer monitor to watch
+pub fn held_printers() -> Vec<String> {
+    store().and_then(|store| store.printers()).unwrap_or_default()
+}
+
+/// Print the jobs held for `printer`, oldest first, stopping at the first
+/// that fails, as the operator who started them with their template and
+/// data. Returns how many were printed; releases for a printer already
+/// being released print nothing.
+pub async fn release(app: AppHandle, printer: String) -> Result<usize, String> {
+    let store = store()?;
+    if !store.releasing.lock().unwrap().insert(printer.clone()) {
+        return Ok(0);
+    }
+    let result = release_jobs(&app, store, &printer).await;
+    store.releasing.lock().unwrap().remove(&printer);
+    result
+}
+
+async fn release_jobs(app: &AppHandle, store: &OfflineStore, printer: &str) -> Result<usize, String> {
+    let bus = app.state::<EventBus>();
+    let mut released = 0;
+    for job in store.list(Some(printer))? {
+        // Kept in the spool directory like freshly generated PDFs
+        let pdf_path = spool::path(&format!("label_held_{}.pdf", job.id));
+        fs::write(&pdf_path, store.pdf(job.id)?).map_err(|e| format!("Failed to write PDF file: {}", e))?;
+        info!("Releasing held job {} to {}", job.id, printer);
+        let (operator, details) = store.origin(job.id)?;
+        let work = RELEASE.scope((), async {
+            printing::submit_pdf(&pdf_path, job.width_mm, job.height_mm, Some(printer))
+                .await
+                .map_err(String::from)
+        });
+        let result = bus
+            .run_job_as("offline", job.labels, Some(printer), operator, details, work)
+            .await;
+        // Written again from the database on the next try
+        if let Err(e) = fs::remove_file(&pdf_path) {
+            warn!("Failed to remove {}: {}", pdf_path.display(), e);
+        }
+        match result {
+            Ok(_) => {
+                store.remove(job.id)?;
+                released += 1;
+            }
+            Err(e) => {
+                warn!("Held job {} for {} failed again, keeping it: {}", job.id, printer, e);
+                break;
+            }
+        }
+    }
+    Ok(released)
+}
+
+#[tauri::command]
+pub fn list_held_jobs() -> Result<Vec<HeldJob>, String> {
+    store()?.list(None)
+}
+
+/// Drop a held job without printing it
+#[tauri::command]
+pub fn discard_held_job(id: i64) -> Result<(), String> {
+    match store()?.remove(id)? {
+        true => Ok(()),
+        false => Err(format!("Held job {} not found", id)),
+    }
+}
+
+/// Print `printer_name`'s held jobs now, without waiting for the printer
+/// monitor. Returns how many were printed.
+#[tauri::command]
+pub async fn release_held_jobs(app: AppHandle, printer_name: String) -> Result<usize, String> {
+    release(app, printer_name).await
+}
@@ -0,0 +1,167 @@
+// Who is printing, for traceability: food-production sites must be able to
+// say which operator printed each label. The operator is picked from the
+// configured list when the app starts, or signs in by scanning their badge;
+// until then the name set in the settings is used. In kiosk mode only
+// configured operators can sign in, so nobody prints under a name they
+// made up, or a quota-free one. The operator is stamped
+// on every history and audit entry and, when enabled, printed in a footer
+// on the labels.
+use crate::formats::escape;
+use crate::i18n;
+use crate::kiosk;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::info;
+
+/// Footer text size, in millimetres
+const FOOTER_SIZE_MM: f64 = 1.8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operator {
+    pub name: String,
+    /// Code on the operator's badge, e.g. an employee number
+    #[serde(default)]
+    pub badge: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OperatorSettings {
+    /// Operators to choose from; any name is accepted when empty, except in
+    /// kiosk mode, where nobody can sign in by name then
+    pub operators: Vec<Operator>,
+    /// Ask who is printing when the app starts
+    pub ask_at_start: bool,
+    /// Print the operator's name and the print time along the bottom edge
+    /// of each label
+    pub footer: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorStatus {
+    pub current: Option<String>,
+    pub operators: Vec<String>,
+    pub ask_at_start: bool,
+}
+
+#[derive(Default)]
+struct Session {
+    settings: OperatorSettings,
+    /// From the settings, used while nobody is signed in
+    default: Option<String>,
+    signed_in: Option<String>,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+impl Session {
+    // Whether `name` can be signed in
+    fn accepts(&self, name: &str) -> bool {
+        let operators = &self.settings.operators;
+        if operators.is_empty() {
+            return !kiosk::locked();
+        }
+        operators.iter().any(|o| o.name == name)
+    }
+
+    // Someone signed in by name before the kiosk was locked only counts
+    // once they're on the list
+    fn current(&self) -> Option<String> {
+        let signed_in = self.signed_in.clone().filter(|name| self.accepts(name));
+        signed_in.or_else(|| self.default.clone())
+    }
+}
+
+fn with_session<T>(f: impl FnOnce(&mut Session) -> T) -> T {
+    f(SESSION.lock().unwrap().get_or_insert_with(Session::default))
+}
+
+/// Use the configured operators, and `default` while nobody is signed in
+pub fn configure(settings: OperatorSettings, default: Option<String>) {
+    with_session(|session| {
+        session.settings = settings;
+        // An operator removed from the list is signed out
+        if session.signed_in.as_deref().is_some_and(|name| !session.accepts(name)) {
+            session.signed_in = None;
+        }
+        session.default = default.filter(|name| !name.trim().is_empty());
+    });
+}
+
+/// The operator printing right now, if known
+pub fn current() -> Option<String> {
+    with_session(|session| session.current())
+}
+
+/// Sign in the operator whose badge reads `code`; their name, or None when
+/// it isn't a badge
+pub fn sign_in_by_badge(code: &str) -> Option<String> {
+    let code = code.trim();
+    let name = with_session(|session| {
+        let name = session
+            .settings
+            .operators
+            .iter()
+            .find(|o| o.badge.as_deref().is_some_and(|badge| badge == code))?
+            .name
+            .clone();
+        session.signed_in = Some(name.clone());
+        Some(name)
+    })?;
+    info!("{} signed in by badge", name);
+    Some(name)
+}
+
+/// SVG of the operator footer over a `width_mm` x `height_mm` label in
+/// millimetre user units; None when it's off or nobody is known
+pub fn footer_svg(width_mm: f64, height_mm: f64) -> Option<String> {
+    if !with_session(|session| session.settings.footer) {
+        return None;
+    }
+    let operator = current()?;
+    let text = format!("{} {}", operator, chrono::Local::now().format("%Y-%m-%d %H:%M"));
+    Some(format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" fill=\"#000000\" text-anchor=\"end\">{}</text>\n",
+        width_mm - FOOTER_SIZE_MM * 0.5,
+        height_mm - FOOTER_SIZE_MM * 0.4,
+        FOOTER_SIZE_MM,
+        escape(&text)
+    ))
+}
+
+/// The current operator and who can be picked
+#[tauri::command]
+pub fn get_operator() -> OperatorStatus {
+    with_session(|session| OperatorStatus {
+        current: session.current(),
+        operators: session.settings.operators.iter().map(|o| o.name.clone()).collect(),
+        ask_at_start: session.settings.ask_at_start,
+    })
+}
+
+/// Sign in as `name`, or out when None. With a list of operators
+/// configured, or in kiosk mode, only names on the list are accepted.
+#[tauri::command]
+pub fn set_operator(name: Option<String>) -> Result<(), String> {
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    with_session(|session| {
+        if let Some(name) = &name {
+            if session.settings.operators.is_empty() && kiosk::locked() {
+                return Err(i18n::t("operator-list-empty", &[]));
+            }
+            if !session.accepts(name) {
+                return Err(i18n::t("operator-unknown", &[("name", name.as_str().into())]));
+            }
+        }
+        session.signed_in = name;
+        Ok(())
+    })?;
+    info!("Operator is now {}", current().as_deref().unwrap_or("unknown"));
+    Ok(())
+}
+
+/// Sign in by a scanned badge code
+#[tauri::command]
+pub fn scan_operator_badge(code: String) -> Result<String, String> {
+    sign_in_by_badge(&code).ok_or_else(|| i18n::t("operator-badge-unknown", &[("code", code.trim().into())]))
+}
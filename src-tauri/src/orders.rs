@@ -0,0 +1,347 @@
+// Open orders from a web shop, printed as shipping labels (one per order) or
+// product labels (one per item), then marked in the shop so the next fetch
+// leaves them out. Each shop is an `OrderSource`:
+//
+//   Shopify        Admin REST API, with an access token from a custom app;
+//                  printed orders get a tag
+//   WooCommerce    REST API v3, with a consumer key and secret; printed
+//                  orders get a meta field, or move to another status
+//
+// Orders are marked once their shipping labels print; product labels can be
+// printed before and leave them open.
+mod shopify;
+mod woocommerce;
+
+use crate::api::{self, PrintResponse};
+use crate::i18n;
+use crate::merge::{CheckDigitPolicy, DataSource};
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tracing::{error, info};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Gallery templates printed when none is configured
+const SHIPPING_TEMPLATE: &str = "shipping";
+const PRODUCT_TEMPLATE: &str = "price";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderSettings {
+    /// Off when absent
+    pub shop: Option<OrderShop>,
+    /// Printed as `sender` on shipping labels
+    pub sender: String,
+    /// Template for shipping labels; the gallery's shipping label when absent
+    pub shipping_template: Option<String>,
+    /// Template for product labels; the gallery's price label when absent
+    pub product_template: Option<String>,
+    /// Mark orders in the shop once their shipping labels printed
+    pub mark_printed: bool,
+}
+
+impl Default for OrderSettings {
+    fn default() -> Self {
+        OrderSettings {
+            shop: None,
+            sender: String::new(),
+            shipping_template: None,
+            product_template: None,
+            mark_printed: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OrderShop {
+    Shopify {
+        /// e.g. "my-shop" or "my-shop.myshopify.com"
+        shop: String,
+        access_token: String,
+        /// Tag added to printed orders; "labelgood-printed" when absent
+        #[serde(default)]
+        printed_tag: Option<String>,
+    },
+    Woocommerce {
+        /// Shop address, e.g. "https://shop.example.com"
+        url: String,
+        consumer_key: String,
+        consumer_secret: String,
+        /// Status printed orders move to. When absent they keep theirs and
+        /// get a "labelgood_printed" meta field instead, since moving to
+        /// "completed" emails the customer.
+        #[serde(default)]
+        printed_status: Option<String>,
+    },
+}
+
+/// Where an order goes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShippingAddress {
+    pub name: String,
+    pub company: String,
+    pub street: String,
+    pub street2: String,
+    pub postcode: String,
+    pub city: String,
+    pub state: String,
+    pub country: String,
+    pub phone: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderItem {
+    pub sku: String,
+    pub product: String,
+    pub quantity: u32,
+    /// Unit price as the shop formats it, e.g. "12.50"
+    pub price: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Order {
+    /// Id in the shop's API
+    pub id: String,
+    /// Number the customer sees, e.g. "#1001"
+    pub number: String,
+    /// RFC 3339
+    pub created_at: String,
+    pub address: ShippingAddress,
+    pub items: Vec<OrderItem>,
+    /// Shopify tags, kept when the printed tag is added
+    pub tags: Vec<String>,
+}
+
+/// Which labels to print for an order
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderLabels {
+    /// One per order
+    Shipping,
+    /// One per item, as many as were ordered
+    Products,
+}
+
+/// A web shop orders are fetched from
+pub trait OrderSource: Send {
+    /// Orders waiting to ship, less those already marked printed
+    fn open_orders(&self) -> Result<Vec<Order>, String>;
+
+    /// Mark `order` as having its labels printed
+    fn mark_printed(&self, order: &Order) -> Result<(), String>;
+}
+
+fn source(shop: &OrderShop) -> Box<dyn OrderSource> {
+    match shop {
+        OrderShop::Shopify {
+            shop,
+            access_token,
+            printed_tag,
+        } => Box::new(shopify::Shopify::new(shop, access_token, printed_tag.as_deref())),
+        OrderShop::Woocommerce {
+            url,
+            consumer_key,
+            consumer_secret,
+            printed_status,
+        } => Box::new(woocommerce::WooCommerce::new(
+            url,
+            consumer_key,
+            consumer_secret,
+            printed_status.as_deref(),
+        )),
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into()
+}
+
+impl ShippingAddress {
+    /// The address as it's written on a parcel
+    pub fn lines(&self) -> String {
+        let postcode_city = join(&[&self.postcode, &self.city, &self.state], " ");
+        [
+            &self.name,
+            &self.company,
+            &self.street,
+            &self.street2,
+            &postcode_city,
+            &self.country,
+        ]
+        .into_iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+// A field of a shop's JSON as text; empty when absent
+fn text(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn join(parts: &[&String], separator: &str) -> String {
+    parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+impl Order {
+    /// Merge fields of the order, named like the address book's so address
+    /// templates fit too
+    pub fn record(&self, sender: &str) -> BTreeMap<String, String> {
+        let address = &self.address;
+        BTreeMap::from([
+            ("order".to_string(), self.number.clone()),
+            ("name".to_string(), address.name.clone()),
+            ("company".to_string(), address.company.clone()),
+            ("street".to_string(), join(&[&address.street, &address.street2], ", ")),
+            ("postcode".to_string(), address.postcode.clone()),
+            ("city".to_string(), address.city.clone()),
+            ("state".to_string(), address.state.clone()),
+            (
+                "postcode_city".to_string(),
+                join(&[&address.postcode, &address.city], " "),
+            ),
+            ("country".to_string(), address.country.clone()),
+            ("phone".to_string(), address.phone.clone()),
+            ("email".to_string(), address.email.clone()),
+            ("recipient".to_string(), address.lines()),
+            ("sender".to_string(), sender.to_string()),
+            // Filled in by the carrier's label, not the shop
+            ("tracking".to_string(), String::new()),
+        ])
+    }
+
+    // One record per ordered unit, with the order's fields too
+    fn item_records(&self, sender: &str) -> Vec<BTreeMap<String, String>> {
+        let mut records = Vec::new();
+        for item in &self.items {
+            let mut record = self.record(sender);
+            record.insert("sku".to_string(), item.sku.clone());
+            record.insert("product".to_string(), item.product.clone());
+            record.insert("price".to_string(), item.price.clone());
+            record.insert("quantity".to_string(), item.quantity.to_string());
+            records.extend((0..item.quantity).map(|_| record.clone()));
+        }
+        records
+    }
+}
+
+fn configured_shop(settings: &OrderSettings) -> Result<OrderShop, String> {
+    settings
+        .shop
+        .clone()
+        .ok_or_else(|| i18n::t("orders-no-shop", &[]))
+}
+
+async fn fetch(shop: OrderShop) -> Result<Vec<Order>, String> {
+    tauri::async_runtime::spawn_blocking(move || source(&shop).open_orders())
+        .await
+        .map_err(|e| format!("Failed to fetch orders: {}", e))?
+}
+
+/// Orders in the configured shop waiting for labels
+#[tauri::command]
+pub async fn list_open_orders(settings: State<'_, SettingsStore>) -> Result<Vec<Order>, String> {
+    fetch(configured_shop(&settings.get().orders)?).await
+}
+
+/// Print shipping or product labels for the open orders with `order_ids`,
+/// then, for shipping labels, mark them in the shop. Orders that can't be
+/// marked are reported but don't fail the job, since their labels did
+/// print. Jobs held for an offline printer fail here, so their orders stay
+/// open.
+#[tauri::command]
+pub async fn print_orders(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    order_ids: Vec<String>,
+    labels: OrderLabels,
+    printer_name: Option<String>,
+) -> Result<PrintResponse, String> {
+    let settings = settings.get().orders;
+    let shop = configured_shop(&settings)?;
+    // Fetched again so a label never shows an address changed since the list
+    let orders: Vec<Order> = fetch(shop.clone())
+        .await?
+        .into_iter()
+        .filter(|order| order_ids.contains(&order.id))
+        .collect();
+    if let Some(missing) = order_ids.iter().find(|id| !orders.iter().any(|order| &order.id == *id)) {
+        return Err(i18n::t("order-not-open", &[("order", missing.as_str().into())]));
+    }
+
+    let (template, records): (&str, Vec<BTreeMap<String, String>>) = match labels {
+        OrderLabels::Shipping => (
+            settings.shipping_template.as_deref().unwrap_or(SHIPPING_TEMPLATE),
+            orders.iter().map(|order| order.record(&settings.sender)).collect(),
+        ),
+        OrderLabels::Products => (
+            settings.product_template.as_deref().unwrap_or(PRODUCT_TEMPLATE),
+            orders
+                .iter()
+                .flat_map(|order| order.item_records(&settings.sender))
+                .collect(),
+        ),
+    };
+    if records.is_empty() {
+        return Err(i18n::t("orders-nothing-to-print", &[]));
+    }
+    let data = DataSource::Records { records };
+    let mut response = api::print_template(
+        &app,
+        "orders",
+        template,
+        data,
+        BTreeMap::new(),
+        printer_name,
+        CheckDigitPolicy::default(),
+    )
+    .await?;
+    if !settings.mark_printed || labels != OrderLabels::Shipping {
+        return Ok(response);
+    }
+
+    let failed = tauri::async_runtime::spawn_blocking(move || {
+        let source = source(&shop);
+        let mut failed = Vec::new();
+        for order in &orders {
+            match source.mark_printed(order) {
+                Ok(()) => info!("Marked order {} as printed", order.number),
+                Err(e) => {
+                    error!("Failed to mark order {} as printed: {}", order.number, e);
+                    failed.push(order.number.clone());
+                }
+            }
+        }
+        failed
+    })
+    .await
+    .map_err(|e| format!("Failed to mark orders: {}", e))?;
+    if !failed.is_empty() {
+        response.message = format!(
+            "{}; failed to mark order(s) {} as printed",
+            response.message,
+            failed.join(", ")
+        );
+    }
+    Ok(response)
+}
@@ -0,0 +1,112 @@
+// Shopify Admin REST API: unfulfilled open orders, marked with a tag
+use super::{agent, text, Order, OrderItem, OrderSource, ShippingAddress};
+use serde_json::{json, Value};
+
+const API_VERSION: &str = "2024-10";
+const DEFAULT_TAG: &str = "labelgood-printed";
+
+pub struct Shopify {
+    base_url: String,
+    access_token: String,
+    printed_tag: String,
+}
+
+impl Shopify {
+    pub fn new(shop: &str, access_token: &str, printed_tag: Option<&str>) -> Self {
+        let shop = shop
+            .trim()
+            .trim_start_matches("https://")
+            .trim_end_matches('/')
+            .trim_end_matches(".myshopify.com");
+        Shopify {
+            base_url: format!("https://{}.myshopify.com/admin/api/{}", shop, API_VERSION),
+            access_token: access_token.to_string(),
+            printed_tag: printed_tag
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .unwrap_or(DEFAULT_TAG)
+                .to_string(),
+        }
+    }
+}
+
+fn parse_order(order: &Value) -> Order {
+    let shipping = order.get("shipping_address").cloned().unwrap_or_default();
+    let address = ShippingAddress {
+        name: text(&shipping, "name"),
+        company: text(&shipping, "company"),
+        street: text(&shipping, "address1"),
+        street2: text(&shipping, "address2"),
+        postcode: text(&shipping, "zip"),
+        city: text(&shipping, "city"),
+        state: text(&shipping, "province_code"),
+        country: text(&shipping, "country"),
+        phone: Some(text(&shipping, "phone"))
+            .filter(|phone| !phone.is_empty())
+            .unwrap_or_else(|| text(order, "phone")),
+        email: text(order, "email"),
+    };
+    let items = order
+        .get("line_items")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|item| OrderItem {
+            sku: text(item, "sku"),
+            product: text(item, "name"),
+            quantity: item.get("quantity").and_then(Value::as_u64).unwrap_or(1) as u32,
+            price: text(item, "price"),
+        })
+        .collect();
+    Order {
+        id: text(order, "id"),
+        number: text(order, "name"),
+        created_at: text(order, "created_at"),
+        address,
+        items,
+        tags: text(order, "tags")
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+impl OrderSource for Shopify {
+    fn open_orders(&self) -> Result<Vec<Order>, String> {
+        let url = format!(
+            "{}/orders.json?status=open&fulfillment_status=unfulfilled&limit=250",
+            self.base_url
+        );
+        let body: Value = agent()
+            .get(&url)
+            .header("X-Shopify-Access-Token", &self.access_token)
+            .call()
+            .map_err(|e| format!("Failed to fetch Shopify orders: {}", e))?
+            .body_mut()
+            .read_json()
+            .map_err(|e| format!("Shopify returned invalid orders: {}", e))?;
+        let orders = body
+            .get("orders")
+            .and_then(Value::as_array)
+            .ok_or("Shopify returned no orders list")?;
+        Ok(orders
+            .iter()
+            .map(parse_order)
+            .filter(|order| !order.tags.contains(&self.printed_tag))
+            .collect())
+    }
+
+    fn mark_printed(&self, order: &Order) -> Result<(), String> {
+        let mut tags = order.tags.clone();
+        tags.push(self.printed_tag.clone());
+        let url = format!("{}/orders/{}.json", self.base_url, order.id);
+        agent()
+            .put(&url)
+            .header("X-Shopify-Access-Token", &self.access_token)
+            .send_json(json!({ "order": { "id": order.id, "tags": tags.join(", ") } }))
+            .map_err(|e| format!("Failed to tag Shopify order {}: {}", order.number, e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+// WooCommerce REST API v3: orders being processed, given a meta field once
+// printed, or moved to another status if one is configured
+use super::{agent, text, Order, OrderItem, OrderSource, ShippingAddress};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+const OPEN_STATUS: &str = "processing";
+const PRINTED_META: &str = "labelgood_printed";
+
+pub struct WooCommerce {
+    base_url: String,
+    authorization: String,
+    /// Marked with `PRINTED_META` when None
+    printed_status: Option<String>,
+}
+
+impl WooCommerce {
+    pub fn new(url: &str, consumer_key: &str, consumer_secret: &str, printed_status: Option<&str>) -> Self {
+        WooCommerce {
+            base_url: format!("{}/wp-json/wc/v3", url.trim().trim_end_matches('/')),
+            // Basic auth, which WooCommerce accepts over HTTPS
+            authorization: format!(
+                "Basic {}",
+                BASE64.encode(format!("{}:{}", consumer_key, consumer_secret))
+            ),
+            printed_status: printed_status
+                .map(str::trim)
+                .filter(|status| !status.is_empty())
+                .map(str::to_string),
+        }
+    }
+}
+
+// Whether `order` has the meta field printed orders get
+fn marked_printed(order: &Value) -> bool {
+    order
+        .get("meta_data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .any(|meta| meta.get("key").and_then(Value::as_str) == Some(PRINTED_META))
+}
+
+fn parse_order(order: &Value) -> Order {
+    let shipping = order.get("shipping").cloned().unwrap_or_default();
+    let billing = order.get("billing").cloned().unwrap_or_default();
+    let name = format!("{} {}", text(&shipping, "first_name"), text(&shipping, "last_name"));
+    let address = ShippingAddress {
+        name: name.trim().to_string(),
+        company: text(&shipping, "company"),
+        street: text(&shipping, "address_1"),
+        street2: text(&shipping, "address_2"),
+        postcode: text(&shipping, "postcode"),
+        city: text(&shipping, "city"),
+        state: text(&shipping, "state"),
+        country: text(&shipping, "country"),
+        phone: Some(text(&shipping, "phone"))
+            .filter(|phone| !phone.is_empty())
+            .unwrap_or_else(|| text(&billing, "phone")),
+        email: text(&billing, "email"),
+    };
+    let items = order
+        .get("line_items")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|item| OrderItem {
+            sku: text(item, "sku"),
+            product: text(item, "name"),
+            quantity: item.get("quantity").and_then(Value::as_u64).unwrap_or(1) as u32,
+            price: text(item, "price"),
+        })
+        .collect();
+    Order {
+        id: text(order, "id"),
+        number: format!("#{}", text(order, "number")),
+        created_at: text(order, "date_created_gmt"),
+        address,
+        items,
+        tags: Vec::new(),
+    }
+}
+
+impl OrderSource for WooCommerce {
+    fn open_orders(&self) -> Result<Vec<Order>, String> {
+        let url = format!("{}/orders?status={}&per_page=100", self.base_url, OPEN_STATUS);
+        let body: Value = agent()
+            .get(&url)
+            .header("Authorization", &self.authorization)
+            .call()
+            .map_err(|e| format!("Failed to fetch WooCommerce orders: {}", e))?
+            .body_mut()
+            .read_json()
+            .map_err(|e| format!("WooCommerce returned invalid orders: {}", e))?;
+        let orders = body.as_array().ok_or("WooCommerce returned no orders list")?;
+        Ok(orders
+            .iter()
+            .filter(|order| !marked_printed(order))
+            .map(parse_order)
+            .collect())
+    }
+
+    fn mark_printed(&self, order: &Order) -> Result<(), String> {
+        let url = format!("{}/orders/{}", self.base_url, order.id);
+        let update = match &self.printed_status {
+            Some(status) => json!({ "status": status }),
+            None => {
+                let printed_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+                json!({ "meta_data": [{ "key": PRINTED_META, "value": printed_at }] })
+            }
+        };
+        agent()
+            .put(&url)
+            .header("Authorization", &self.authorization)
+            .send_json(update)
+            .map_err(|e| format!("Failed to update WooCommerce order {}: {}", order.number, e))?;
+        Ok(())
+    }
+}
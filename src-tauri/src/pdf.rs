@@ -0,0 +1,125 @@
+// Minimal PDF writer for label images, used when no converter can write PDF:
+// ImageMagick isn't installed or its security policy forbids PDF. Each image
+// becomes one page of the label size, stored losslessly with Flate
+// compression; transparent areas are flattened onto white, as they'd print.
+use crate::error::LabelgoodError;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Write `image_paths` as the pages of a `width_points` x `height_points`
+/// PDF at `pdf_path`
+pub fn write_image_pdf(
+    image_paths: &[PathBuf],
+    width_points: f64,
+    height_points: f64,
+    pdf_path: &Path,
+) -> Result<(), LabelgoodError> {
+    let mut pdf = PdfWriter::default();
+    pdf.buffer.extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+    // Catalog and page tree first, then a page, its content and its image
+    // for each image
+    let page_ids: Vec<usize> = (0..image_paths.len()).map(|i| 3 + i * 3).collect();
+    pdf.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        page_ids.len()
+    );
+    pdf.object(2, pages.as_bytes());
+
+    for (path, page_id) in image_paths.iter().zip(page_ids) {
+        let (content_id, image_id) = (page_id + 1, page_id + 2);
+        let page = format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+            width_points, height_points, image_id, content_id
+        );
+        pdf.object(page_id, page.as_bytes());
+
+        let content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", width_points, height_points);
+        pdf.stream(content_id, "", content.as_bytes());
+
+        let (width, height, rgb) = flattened_rgb(path)?;
+        let compressed = deflate(&rgb).map_err(|e| LabelgoodError::Io {
+            message: format!("Failed to compress label image: {}", e),
+        })?;
+        let image = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /FlateDecode",
+            width, height
+        );
+        pdf.stream(image_id, &image, &compressed);
+    }
+
+    let bytes = pdf.finish();
+    fs::write(pdf_path, bytes).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to write PDF file: {}", e),
+    })
+}
+
+// Pixels of the image at `path` as RGB, composited onto white
+fn flattened_rgb(path: &Path) -> Result<(u32, u32, Vec<u8>), LabelgoodError> {
+    let image = image::open(path)
+        .map_err(|e| LabelgoodError::RenderFailed {
+            message: format!("Failed to read {}: {}", path.display(), e),
+        })?
+        .to_rgba8();
+    let mut rgb = Vec::with_capacity(image.width() as usize * image.height() as usize * 3);
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let alpha = a as u32;
+        for channel in [r, g, b] {
+            rgb.push(((channel as u32 * alpha + 255 * (255 - alpha)) / 255) as u8);
+        }
+    }
+    Ok((image.width(), image.height(), rgb))
+}
+
+fn deflate(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+// Objects are numbered by the caller and may be written in any order
+#[derive(Default)]
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<(usize, usize)>,
+}
+
+impl PdfWriter {
+    fn object(&mut self, id: usize, body: &[u8]) {
+        self.offsets.push((id, self.buffer.len()));
+        self.buffer.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+        self.buffer.extend_from_slice(body);
+        self.buffer.extend_from_slice(b"\nendobj\n");
+    }
+
+    // A stream object; `dictionary` holds entries besides /Length
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) {
+        let mut body = format!("<< {} /Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.object(id, &body);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.offsets.sort();
+        let xref = self.buffer.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for (_, offset) in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            xref
+        ));
+        self.buffer.extend_from_slice(table.as_bytes());
+        self.buffer
+    }
+}
@@ -0,0 +1,72 @@
+// Saving generated PDFs where the user wants them. `generate_pdf` writes to
+// the temp directory under a timestamp name, which is fine for printing but
+// not for keeping; these copy the PDF to a path the user picks, asking with
+// a save dialog when the frontend doesn't pass one.
+use crate::error::LabelgoodError;
+use crate::printing;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+use tracing::info;
+
+// e.g. "label-20261015-142301.pdf"
+fn suggested_name() -> String {
+    format!("label-{}.pdf", chrono::Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+// Ask where to save, starting with `suggested`; None when the dialog is
+// cancelled
+async fn choose_destination(app: &AppHandle, suggested: String) -> Result<Option<PathBuf>, LabelgoodError> {
+    let dialog = app.dialog().file().add_filter("PDF", &["pdf"]).set_file_name(suggested);
+    // Blocks until the dialog closes, so off the async runtime
+    let chosen = tauri::async_runtime::spawn_blocking(move || dialog.blocking_save_file())
+        .await
+        .map_err(|e| LabelgoodError::Other { message: e.to_string() })?;
+    chosen
+        .map(|path| {
+            path.into_path().map_err(|e| LabelgoodError::InvalidInput {
+                message: format!("Can't save to the chosen location: {}", e),
+            })
+        })
+        .transpose()
+}
+
+/// Copy `pdf_path` to `path`, or to where the user picks when no path is
+/// given. Returns where it was saved, or None when the dialog was
+/// cancelled.
+pub async fn save_as(app: &AppHandle, pdf_path: &Path, path: Option<String>) -> Result<Option<String>, LabelgoodError> {
+    let target = match path.filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => match choose_destination(app, suggested_name()).await? {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+    // The filter isn't enforced on every platform
+    let target = if target.extension().is_none() {
+        target.with_extension("pdf")
+    } else {
+        target
+    };
+    fs::copy(pdf_path, &target).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to save PDF to {}: {}", target.display(), e),
+    })?;
+    info!("Saved {} as {}", pdf_path.display(), target.display());
+    Ok(Some(target.display().to_string()))
+}
+
+/// Save the PDF of the last preview or print job to `path`, or to where the
+/// user picks when no path is given
+#[tauri::command]
+pub async fn export_last_pdf(app: AppHandle, path: Option<String>) -> Result<Option<String>, LabelgoodError> {
+    let job = printing::last_job().ok_or_else(|| LabelgoodError::InvalidInput {
+        message: "Nothing has been previewed or printed yet".to_string(),
+    })?;
+    if !job.pdf_path.exists() {
+        return Err(LabelgoodError::Io {
+            message: format!("The last job's PDF is gone: {}", job.pdf_path.display()),
+        });
+    }
+    save_as(&app, &job.pdf_path, path).await
+}
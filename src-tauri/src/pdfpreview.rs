@@ -0,0 +1,217 @@
+// In-app proofing of generated PDFs. A job without a printer registers its
+// PDF here instead of opening it in the system viewer, and a
+// `preview_ready` label event tells the frontend to show it. The preview
+// pane reads the PDF, or its pages rendered to PNG, through the
+// labelgood-pdf URI scheme:
+//
+//   labelgood-pdf://localhost/<id>              the PDF
+//   labelgood-pdf://localhost/<id>/<page>.png   a page (0-based) at RENDER_DPI
+//
+// On Windows the same paths are under http://labelgood-pdf.localhost/.
+// Zoom and page navigation are the pane's; pages are rendered once and kept
+// while their preview is one of the most recent.
+use crate::error::LabelgoodError;
+use crate::events::{self, LabelEvent};
+use crate::printing;
+use crate::progress;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Runtime, UriSchemeContext, UriSchemeResponder};
+use tracing::warn;
+
+/// URI scheme the preview pane loads PDFs and pages from
+pub const SCHEME: &str = "labelgood-pdf";
+
+/// Previews kept before the oldest is forgotten
+const MAX_PREVIEWS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfPreview {
+    pub id: u64,
+    /// The PDF through the preview scheme; pages are at "<url>/<page>.png"
+    pub url: String,
+    pub pages: usize,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+struct Entry {
+    preview: PdfPreview,
+    path: PathBuf,
+    /// Rendered pages by index
+    pages: BTreeMap<usize, Arc<Vec<u8>>>,
+}
+
+static EXTERNAL_VIEWER: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static PREVIEWS: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Open previews in the system PDF viewer instead of the preview pane
+pub fn configure(external_viewer: bool) {
+    EXTERNAL_VIEWER.store(external_viewer, Ordering::Relaxed);
+}
+
+pub fn external_viewer() -> bool {
+    EXTERNAL_VIEWER.load(Ordering::Relaxed)
+}
+
+fn base_url() -> &'static str {
+    if cfg!(windows) {
+        "http://labelgood-pdf.localhost"
+    } else {
+        "labelgood-pdf://localhost"
+    }
+}
+
+/// Keep `pdf_path` for the preview pane and tell the frontend it's ready
+pub fn register(pdf_path: &Path, width_mm: f64, height_mm: f64) -> Result<PdfPreview, LabelgoodError> {
+    let pdf = fs::read(pdf_path).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to read PDF {}: {}", pdf_path.display(), e),
+    })?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let preview = PdfPreview {
+        id,
+        url: format!("{}/{}", base_url(), id),
+        pages: printing::count_pages(&pdf),
+        width_mm,
+        height_mm,
+    };
+    let mut previews = PREVIEWS.lock().unwrap();
+    previews.push(Entry {
+        preview: preview.clone(),
+        path: pdf_path.to_path_buf(),
+        pages: BTreeMap::new(),
+    });
+    if previews.len() > MAX_PREVIEWS {
+        previews.remove(0);
+    }
+    drop(previews);
+    events::publish_in_job(LabelEvent::PreviewReady {
+        job: progress::current_job(),
+        preview: preview.clone(),
+    });
+    Ok(preview)
+}
+
+// The preview `id`, or the latest
+fn find(id: Option<u64>) -> Result<(PdfPreview, PathBuf), LabelgoodError> {
+    let previews = PREVIEWS.lock().unwrap();
+    let entry = match id {
+        Some(id) => previews.iter().find(|entry| entry.preview.id == id),
+        None => previews.last(),
+    };
+    entry
+        .map(|entry| (entry.preview.clone(), entry.path.clone()))
+        .ok_or_else(|| LabelgoodError::InvalidInput {
+            message: match id {
+                Some(id) => format!("Preview {} is no longer available", id),
+                None => "Nothing has been previewed yet".to_string(),
+            },
+        })
+}
+
+fn read_pdf(id: u64) -> Result<Vec<u8>, LabelgoodError> {
+    let (_, path) = find(Some(id))?;
+    fs::read(&path).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to read PDF {}: {}", path.display(), e),
+    })
+}
+
+/// Page `page` (0-based) of preview `id` as a PNG at `RENDER_DPI`
+pub async fn page_png(id: u64, page: usize) -> Result<Arc<Vec<u8>>, LabelgoodError> {
+    let (preview, path) = find(Some(id))?;
+    if page >= preview.pages.max(1) {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!("Preview {} has {} page(s)", id, preview.pages),
+        });
+    }
+    let cached = PREVIEWS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|entry| entry.preview.id == id)
+        .and_then(|entry| entry.pages.get(&page).cloned());
+    if let Some(png) = cached {
+        return Ok(png);
+    }
+    let png = Arc::new(printing::rasterize_pdf(&path, Some(page)).await?.swap_remove(0));
+    if let Some(entry) = PREVIEWS.lock().unwrap().iter_mut().find(|entry| entry.preview.id == id) {
+        entry.pages.insert(page, png.clone());
+    }
+    Ok(png)
+}
+
+// "<id>" or "<id>/<page>.png"
+async fn resolve(path: &str) -> Result<(&'static str, Vec<u8>), LabelgoodError> {
+    let invalid = || LabelgoodError::InvalidInput {
+        message: format!("Not a preview address: {}", path),
+    };
+    match path.split_once('/') {
+        None => {
+            let id = path.parse().map_err(|_| invalid())?;
+            Ok(("application/pdf", read_pdf(id)?))
+        }
+        Some((id, page)) => {
+            let id = id.parse().map_err(|_| invalid())?;
+            let page = page
+                .strip_suffix(".png")
+                .and_then(|page| page.parse().ok())
+                .ok_or_else(invalid)?;
+            Ok(("image/png", page_png(id, page).await?.to_vec()))
+        }
+    }
+}
+
+/// Answer a request to the labelgood-pdf scheme
+pub fn protocol<R: Runtime>(
+    _context: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let path = request.uri().path().trim_matches('/').to_string();
+    tauri::async_runtime::spawn(async move {
+        let response = match resolve(&path).await {
+            Ok((mime, body)) => Response::builder()
+                .header(header::CONTENT_TYPE, mime)
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(body),
+            Err(e) => {
+                warn!("Failed to serve preview {}: {}", path, e);
+                let status = match e {
+                    LabelgoodError::InvalidInput { .. } => StatusCode::NOT_FOUND,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                Response::builder()
+                    .status(status)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                    .body(e.to_string().into_bytes())
+            }
+        };
+        responder.respond(response.unwrap_or_default());
+    });
+}
+
+/// Preview `id`, or the latest when no id is given
+#[tauri::command]
+pub fn get_pdf_preview(id: Option<u64>) -> Result<PdfPreview, LabelgoodError> {
+    find(id).map(|(preview, _)| preview)
+}
+
+/// Open preview `id`, or the latest, in the system PDF viewer after all
+#[tauri::command]
+pub fn open_pdf_preview_externally(id: Option<u64>) -> Result<(), LabelgoodError> {
+    let (_, path) = find(id)?;
+    #[cfg(not(mobile))]
+    opener::open(&path).map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to open PDF: {}", e),
+    })?;
+    #[cfg(mobile)]
+    crate::backends::mobile::open(&path)?;
+    Ok(())
+}
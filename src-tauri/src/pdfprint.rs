@@ -0,0 +1,91 @@
+// Printing an existing PDF on labels: its pages are rasterized and placed on
+// the media like images in a folder batch, one label per page.
+use crate::document::Document;
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::history::JobDetails;
+use crate::imagefolder::{self, ImageFit};
+use crate::merge;
+use crate::printing::{self, RENDER_DPI};
+use crate::settings::MediaSize;
+use crate::shipping;
+use image::ImageFormat;
+use serde::Deserialize;
+use std::io::Cursor;
+use std::path::Path;
+use tauri::State;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PdfPrintOptions {
+    pub media: MediaSize,
+    /// 1-based page to print; every page when absent
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub fit: ImageFit,
+    /// Crop the page's white margins before placing it
+    #[serde(default)]
+    pub trim: bool,
+    #[serde(default)]
+    pub printer_name: Option<String>,
+}
+
+// One label per selected page of the PDF at `path`
+async fn pdf_labels(path: &Path, options: &PdfPrintOptions) -> Result<Vec<Document>, String> {
+    let media = &options.media;
+    if media.width_mm <= 0.0 || media.height_mm <= 0.0 {
+        return Err(format!("Invalid media size {}x{}mm", media.width_mm, media.height_mm));
+    }
+    let page = match options.page {
+        Some(0) => return Err("Pages are numbered from 1".to_string()),
+        page => page.map(|page| page - 1),
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    printing::rasterize_pdf(path, page)
+        .await?
+        .iter()
+        .enumerate()
+        .map(|(i, png)| {
+            let mut page = image::load_from_memory_with_format(png, ImageFormat::Png)
+                .map_err(|e| format!("Failed to decode rendered PDF page: {}", e))?;
+            if options.trim {
+                let (left, top, width, height) = shipping::printed_area(&page.to_luma8());
+                page = page.crop_imm(left, top, width, height);
+            }
+            let mut png = Vec::new();
+            page.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .map_err(|e| format!("Failed to encode label PNG: {}", e))?;
+            let to_mm = |px: u32| px as f64 / RENDER_DPI * 25.4;
+            let number = options.page.unwrap_or(i + 1);
+            imagefolder::image_label(
+                &format!("{}-{}.png", stem, number),
+                &png,
+                (to_mm(page.width()), to_mm(page.height())),
+                media.width_mm,
+                media.height_mm,
+                options.fit,
+            )
+        })
+        .collect()
+}
+
+/// Print pages of an existing PDF scaled or cropped onto the label media.
+/// `op_id` lets `cancel_operation` stop it.
+#[tauri::command]
+pub async fn print_pdf_file(
+    fonts: State<'_, FontLibrary>,
+    events: State<'_, EventBus>,
+    path: String,
+    options: PdfPrintOptions,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    let documents = pdf_labels(Path::new(&path), &options).await?;
+    let details = JobDetails {
+        template: Some(path.clone()),
+        data: Vec::new(),
+    };
+    let printer_name = options.printer_name.as_deref();
+    let work = merge::print_documents(&documents, fonts.database(), printer_name);
+    let job = events.run_job("pdf", documents.len(), printer_name, details, work);
+    events.run_operation(op_id, job).await
+}
@@ -0,0 +1,336 @@
+// Placeholder engine shared by merges, print-time fields and the CLI.
+//
+//   {name}                        value of a data field
+//   {price | currency("EUR")}     value passed through filters, left to right
+//   {weight | ean13_measure("2812345", 3)}
+//                                 in-store EAN-13 with the value embedded
+//   {counter:batch}               next value of a serial counter
+//   {date:%d.%m.%Y}               print-time date fields, see `datefields`
+//   {number}                      number of a numbered batch, see `numbering`
+//   {{ and }}                     literal braces
+//
+// A `{` without a matching `}` is kept as literal text.
+use crate::barcodes::variable_measure;
+use crate::datefields;
+use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+
+/// Variable prefix that takes the next value of a serial counter
+pub const COUNTER_PREFIX: &str = "counter:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Literal(String),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    /// Original text between the braces
+    pub source: String,
+    pub variable: String,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Hands out the next formatted value of a named counter
+pub type NextSerial<'a> = &'a mut dyn FnMut(&str) -> Result<String, String>;
+
+/// Where variable values come from while rendering
+pub struct Context<'a> {
+    pub values: &'a BTreeMap<String, String>,
+    /// Time date fields are evaluated at; fixed for a whole print job
+    pub now: DateTime<Local>,
+    /// Counter placeholders are kept as written when `None`, e.g. for previews
+    pub next_serial: Option<NextSerial<'a>>,
+}
+
+/// How a variable will be resolved, for validation and the field list UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableKind {
+    Counter,
+    Date,
+    Data,
+}
+
+pub fn variable_kind(variable: &str) -> VariableKind {
+    if variable.starts_with(COUNTER_PREFIX) {
+        VariableKind::Counter
+    } else if datefields::evaluate(variable, Local::now()).is_some() {
+        VariableKind::Date
+    } else {
+        VariableKind::Data
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if rest[start..].starts_with("{{") || rest[start..].starts_with("}}") {
+            literal.push_str(&rest[start..start + 1]);
+            rest = &after[1..];
+            continue;
+        }
+        if rest[start..].starts_with('}') {
+            literal.push('}');
+            rest = after;
+            continue;
+        }
+        match find_closing(after) {
+            Some(len) if !after[..len].trim().is_empty() => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Expression(parse_expression(&after[..len])?));
+                rest = &after[len + 1..];
+            }
+            _ => {
+                literal.push('{');
+                rest = after;
+            }
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+// Closing brace of an expression, skipping braces inside quoted filter arguments
+fn find_closing(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '}') => return Some(i),
+            (None, '{') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_expression(source: &str) -> Result<Expression, String> {
+    let mut parts = split_outside_quotes(source, '|').into_iter();
+    let variable = parts.next().unwrap_or_default().trim().to_string();
+    if variable.is_empty() {
+        return Err(format!("Placeholder {{{}}} has no field name", source));
+    }
+    let filters = parts
+        .map(|part| parse_filter(part.trim()).map_err(|e| format!("In {{{}}}: {}", source, e)))
+        .collect::<Result<_, _>>()?;
+    Ok(Expression {
+        source: source.to_string(),
+        variable,
+        filters,
+    })
+}
+
+fn parse_filter(text: &str) -> Result<Filter, String> {
+    let (name, args) = match text.find('(') {
+        Some(open) => {
+            let inner = text[open + 1..]
+                .strip_suffix(')')
+                .ok_or_else(|| format!("filter \"{}\" is missing a closing parenthesis", text))?;
+            let args = if inner.trim().is_empty() {
+                Vec::new()
+            } else {
+                split_outside_quotes(inner, ',')
+                    .into_iter()
+                    .map(|arg| unquote(arg.trim()))
+                    .collect()
+            };
+            (text[..open].trim(), args)
+        }
+        None => (text, Vec::new()),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("invalid filter \"{}\"", text));
+    }
+    Ok(Filter {
+        name: name.to_string(),
+        args,
+    })
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c == separator => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn unquote(arg: &str) -> String {
+    for q in ['"', '\''] {
+        if let Some(inner) = arg.strip_prefix(q).and_then(|a| a.strip_suffix(q)) {
+            return inner.to_string();
+        }
+    }
+    arg.to_string()
+}
+
+/// Distinct variable names used in `text`, in order of appearance.
+/// Text that fails to parse contributes no variables.
+pub fn variables(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for segment in parse(text).unwrap_or_default() {
+        if let Segment::Expression(expression) = segment {
+            if !names.contains(&expression.variable) {
+                names.push(expression.variable);
+            }
+        }
+    }
+    names
+}
+
+/// Render `text` with every placeholder resolved. Placeholders whose data
+/// field has no value are kept as written, so missing data stays visible,
+/// unless they have a `default` filter.
+pub fn render(text: &str, context: &mut Context) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    for segment in parse(text)? {
+        match segment {
+            Segment::Literal(literal) => out.push_str(&literal),
+            Segment::Expression(expression) => match resolve(&expression.variable, context)? {
+                Some(value) => out.push_str(&apply_filters(value, &expression.filters)?),
+                // `default` stands in for data that isn't there at all
+                None if expression.filters.iter().any(|f| f.name == "default") => {
+                    out.push_str(&apply_filters(String::new(), &expression.filters)?)
+                }
+                None => {
+                    out.push('{');
+                    out.push_str(&expression.source);
+                    out.push('}');
+                }
+            },
+        }
+    }
+    Ok(out)
+}
+
+fn resolve(variable: &str, context: &mut Context) -> Result<Option<String>, String> {
+    // Data wins over built-in fields, so a "date" column is used as-is
+    if let Some(value) = context.values.get(variable) {
+        return Ok(Some(value.clone()));
+    }
+    if let Some(counter) = variable.strip_prefix(COUNTER_PREFIX) {
+        return match context.next_serial.as_mut() {
+            Some(next_serial) => next_serial(counter.trim()).map(Some),
+            None => Ok(None),
+        };
+    }
+    datefields::evaluate(variable, context.now).transpose()
+}
+
+pub fn apply_filters(mut value: String, filters: &[Filter]) -> Result<String, String> {
+    for filter in filters {
+        value = apply_filter(&value, filter)?;
+    }
+    Ok(value)
+}
+
+/// Filters `apply_filters` understands
+pub const FILTERS: &[&str] = &[
+    "upper", "lower", "trim", "default", "prefix", "suffix", "truncate", "replace", "pad", "number",
+    "currency", "ean13_measure",
+];
+
+fn apply_filter(value: &str, filter: &Filter) -> Result<String, String> {
+    let arg = |i: usize| filter.args.get(i).map(String::as_str);
+    let number_arg = |i: usize, default: usize| -> Result<usize, String> {
+        match arg(i) {
+            Some(a) => a
+                .parse()
+                .map_err(|_| format!("{}: expected a whole number, got \"{}\"", filter.name, a)),
+            None => Ok(default),
+        }
+    };
+
+    Ok(match filter.name.as_str() {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "trim" => value.trim().to_string(),
+        "default" => {
+            if value.trim().is_empty() {
+                arg(0).unwrap_or_default().to_string()
+            } else {
+                value.to_string()
+            }
+        }
+        "prefix" => format!("{}{}", arg(0).unwrap_or_default(), value),
+        "suffix" => format!("{}{}", value, arg(0).unwrap_or_default()),
+        "truncate" => value.chars().take(number_arg(0, usize::MAX)?).collect(),
+        "replace" => value.replace(arg(0).unwrap_or_default(), arg(1).unwrap_or_default()),
+        "pad" => {
+            let width = number_arg(0, 0)?;
+            let fill = arg(1).and_then(|f| f.chars().next()).unwrap_or('0');
+            let len = value.chars().count();
+            let padding: String = std::iter::repeat_n(fill, width.saturating_sub(len)).collect();
+            format!("{}{}", padding, value)
+        }
+        "number" => format!("{:.*}", number_arg(0, 2)?, parse_number(value, &filter.name)?),
+        "currency" => {
+            let code = arg(0).unwrap_or("EUR").to_ascii_uppercase();
+            let (symbol, default_decimals) = currency_symbol(&code);
+            let amount = parse_number(value, &filter.name)?;
+            let decimals = number_arg(1, default_decimals)?;
+            let sign = if amount < 0.0 { "-" } else { "" };
+            format!("{}{}{:.*}", sign, symbol, decimals, amount.abs())
+        }
+        // Prefix and item code, the value's decimals, and "true" for a price
+        // check digit
+        "ean13_measure" => variable_measure::compose(
+            arg(0).unwrap_or_default(),
+            parse_number(value, &filter.name)?,
+            number_arg(1, 2)?,
+            arg(2) == Some("true"),
+        )
+        .map_err(|e| format!("{}: {}", filter.name, e))?,
+        _ => return Err(format!("Unknown filter: {}", filter.name)),
+    })
+}
+
+// Accepts both "12.5" and "12,5"
+fn parse_number(value: &str, filter: &str) -> Result<f64, String> {
+    let trimmed = value.trim();
+    trimmed
+        .parse::<f64>()
+        .or_else(|_| trimmed.replace(',', ".").parse::<f64>())
+        .map_err(|_| format!("{}: \"{}\" is not a number", filter, value))
+}
+
+fn currency_symbol(code: &str) -> (String, usize) {
+    match code {
+        "EUR" => ("€".to_string(), 2),
+        "USD" => ("$".to_string(), 2),
+        "GBP" => ("£".to_string(), 2),
+        "JPY" => ("¥".to_string(), 0),
+        other => (format!("{} ", other), 2),
+    }
+}
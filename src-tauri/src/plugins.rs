@@ -0,0 +1,163 @@
+// Plugin surface for printer drivers, data sources and barcode symbologies,
+// so support for niche hardware and systems can live outside the core crate.
+// Plugins are native libraries in the plugins directory; see `native` for
+// the C ABI they export.
+#[cfg(feature = "mock-printer")]
+pub mod mock;
+pub mod native;
+
+use crate::barcodes::Symbol;
+use crate::merge::DataTable;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// Separates a plugin driver's name from its printer's name in the printer
+/// list, e.g. "escpos:Counter printer"
+pub const DRIVER_SEPARATOR: char = ':';
+
+pub trait PrinterDriver: Send + Sync {
+    fn name(&self) -> &str;
+    fn list_printers(&self) -> Result<Vec<String>, String>;
+    /// Print a PDF whose pages are `width_mm` x `height_mm`
+    fn print_pdf(&self, printer: &str, pdf_path: &Path, width_mm: f64, height_mm: f64) -> Result<String, String>;
+}
+
+pub trait DataSourcePlugin: Send + Sync {
+    fn name(&self) -> &str;
+    /// Rows for a source configured with plugin-specific `config`
+    fn load(&self, config: &Value) -> Result<DataTable, String>;
+}
+
+pub trait SymbologyPlugin: Send + Sync {
+    /// Lowercase symbology name, as used in `BarcodeElement`
+    fn name(&self) -> &str;
+    fn encode(&self, data: &str) -> Result<Symbol, String>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub version: String,
+    pub path: String,
+    pub printer_drivers: Vec<String>,
+    pub data_sources: Vec<String>,
+    pub symbologies: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    pub plugins: Vec<PluginInfo>,
+    /// Plugins that failed to load, with the reason
+    pub errors: Vec<String>,
+    drivers: Vec<Box<dyn PrinterDriver>>,
+    data_sources: Vec<Box<dyn DataSourcePlugin>>,
+    symbologies: Vec<Box<dyn SymbologyPlugin>>,
+}
+
+impl PluginRegistry {
+    /// Load every plugin library in `dir`. A plugin that fails to load is
+    /// recorded in `errors` and skipped.
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut registry = PluginRegistry::with_builtins();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return registry;
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| native::is_library(p))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match native::load(&path) {
+                Ok(plugin) => registry.register(plugin),
+                Err(e) => {
+                    warn!("Skipping plugin {}: {}", path.display(), e);
+                    registry.errors.push(format!("{}: {}", path.display(), e));
+                }
+            }
+        }
+        registry
+    }
+
+    /// Drivers that ship with Labelgood and don't need a plugin library
+    fn with_builtins() -> Self {
+        let mut registry = PluginRegistry::default();
+        registry.drivers.extend(builtin_drivers());
+        registry
+    }
+
+    fn register(&mut self, plugin: native::LoadedPlugin) {
+        info!("Loaded plugin {} {}", plugin.info.name, plugin.info.version);
+        self.drivers.extend(plugin.drivers);
+        self.data_sources.extend(plugin.data_sources);
+        self.symbologies.extend(plugin.symbologies);
+        self.plugins.push(plugin.info);
+    }
+
+    pub fn driver(&self, name: &str) -> Option<&dyn PrinterDriver> {
+        self.drivers.iter().find(|d| d.name() == name).map(|d| d.as_ref())
+    }
+
+    pub fn data_source(&self, name: &str) -> Option<&dyn DataSourcePlugin> {
+        self.data_sources.iter().find(|s| s.name() == name).map(|s| s.as_ref())
+    }
+
+    pub fn symbology(&self, name: &str) -> Option<&dyn SymbologyPlugin> {
+        self.symbologies.iter().find(|s| s.name() == name).map(|s| s.as_ref())
+    }
+
+    /// Printers of every plugin driver, prefixed with the driver name
+    pub fn printers(&self) -> Vec<String> {
+        self.drivers
+            .iter()
+            .flat_map(|driver| match driver.list_printers() {
+                Ok(printers) => printers
+                    .into_iter()
+                    .map(|p| format!("{}{}{}", driver.name(), DRIVER_SEPARATOR, p))
+                    .collect(),
+                Err(e) => {
+                    warn!("Plugin driver {} failed to list printers: {}", driver.name(), e);
+                    Vec::new()
+                }
+            })
+            .collect()
+    }
+
+    /// Plugin driver and printer for a name from `printers`, if it is one
+    pub fn resolve_printer<'a>(&self, printer_name: &'a str) -> Option<(&dyn PrinterDriver, &'a str)> {
+        let (driver, printer) = printer_name.split_once(DRIVER_SEPARATOR)?;
+        Some((self.driver(driver)?, printer))
+    }
+}
+
+// The mock printer of test builds; nothing otherwise
+fn builtin_drivers() -> Vec<Box<dyn PrinterDriver>> {
+    #[cfg(feature = "mock-printer")]
+    if let Some(mock) = mock::MockPrinter::configured() {
+        info!("Capturing jobs for mock printers");
+        return vec![Box::new(mock)];
+    }
+    Vec::new()
+}
+
+// Plugins are loaded once at startup and needed deep inside data source and
+// barcode code that has no access to Tauri state
+static REGISTRY: OnceLock<PluginRegistry> = OnceLock::new();
+
+/// Load the plugins directory; later calls have no effect
+pub fn init(dir: &Path) {
+    REGISTRY.get_or_init(|| PluginRegistry::load_dir(dir));
+}
+
+/// The loaded plugins, or only the built-in drivers before `init`
+pub fn registry() -> &'static PluginRegistry {
+    REGISTRY.get_or_init(PluginRegistry::with_builtins)
+}
+
+#[tauri::command]
+pub fn list_plugins() -> Vec<PluginInfo> {
+    registry().plugins.clone()
+}
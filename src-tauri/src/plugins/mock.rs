@@ -0,0 +1,73 @@
+// Printer driver that captures jobs to files instead of printing, for the
+// integration tests. Built with the `mock-printer` feature only, and enabled
+// by `capture_to` with a directory; "mock:NAME" then captures to NAME/
+// inside it, e.g.
+//
+//   NAME/job-1.pdf   the PDF exactly as it would have been spooled
+//   NAME/job-1.json  {"printer": "NAME", "width_mm": 62.0, "height_mm": 29.0, "bytes": 10742}
+use super::PrinterDriver;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Capture jobs to `dir`. Only the first call counts, and only before the
+/// plugins are loaded.
+pub fn capture_to(dir: PathBuf) {
+    let _ = DIR.set(dir);
+}
+
+/// What the driver was asked to print, written next to the captured PDF
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedJob {
+    pub printer: String,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub bytes: u64,
+}
+
+pub struct MockPrinter {
+    dir: PathBuf,
+    next_job: AtomicU64,
+}
+
+impl MockPrinter {
+    /// The mock driver, once `capture_to` gave it a directory
+    pub fn configured() -> Option<Self> {
+        Some(MockPrinter {
+            dir: DIR.get()?.clone(),
+            next_job: AtomicU64::new(1),
+        })
+    }
+}
+
+impl PrinterDriver for MockPrinter {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn list_printers(&self) -> Result<Vec<String>, String> {
+        Ok(vec!["capture".to_string()])
+    }
+
+    fn print_pdf(&self, printer: &str, pdf_path: &Path, width_mm: f64, height_mm: f64) -> Result<String, String> {
+        let dir = self.dir.join(printer);
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let job = self.next_job.fetch_add(1, Ordering::Relaxed);
+        let pdf_target = dir.join(format!("job-{}.pdf", job));
+        let bytes = fs::copy(pdf_path, &pdf_target).map_err(|e| format!("Failed to capture PDF: {}", e))?;
+
+        let captured = CapturedJob {
+            printer: printer.to_string(),
+            width_mm,
+            height_mm,
+            bytes,
+        };
+        let json = serde_json::to_string_pretty(&captured).map_err(|e| format!("Failed to serialize job: {}", e))?;
+        fs::write(dir.join(format!("job-{}.json", job)), json).map_err(|e| format!("Failed to capture job: {}", e))?;
+        Ok(format!("Captured to {}", pdf_target.display()))
+    }
+}
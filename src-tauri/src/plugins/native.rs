@@ -0,0 +1,218 @@
+// Native (dylib) plugins. A plugin exports one C function,
+//
+//   const LabelgoodPluginV1 *labelgood_plugin_v1(void);
+//
+// returning a table of functions that exchange JSON strings, so plugins can
+// be written in any language and don't depend on Rust's unstable ABI:
+//
+//   manifest()              {"name", "version", "printer_drivers": [..],
+//                            "data_sources": [..], "symbologies": [..]}
+//   call(method, args)      {"ok": result} or {"error": "message"}
+//   free_string(s)          releases strings returned by the two above
+//
+// Methods and their results:
+//
+//   list_printers   {"driver"}                                  ["name", ..]
+//   print_pdf       {"driver", "printer", "path", "width_mm", "height_mm"}  "message"
+//   load_data       {"source", "config"}                        {"columns": [..], "rows": [[..]]}
+//   encode_barcode  {"symbology", "data"}                       {"width", "height", "modules": "0110..", "text"}
+use super::{DataSourcePlugin, PluginInfo, PrinterDriver, SymbologyPlugin};
+use crate::barcodes::Symbol;
+use crate::merge::DataTable;
+use libloading::Library;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const ABI_VERSION: u32 = 1;
+const ENTRY_POINT: &[u8] = b"labelgood_plugin_v1\0";
+
+#[repr(C)]
+pub struct LabelgoodPluginV1 {
+    pub abi_version: u32,
+    pub manifest: unsafe extern "C" fn() -> *mut c_char,
+    pub call: unsafe extern "C" fn(method: *const c_char, args: *const c_char) -> *mut c_char,
+    pub free_string: unsafe extern "C" fn(*mut c_char),
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    printer_drivers: Vec<String>,
+    #[serde(default)]
+    data_sources: Vec<String>,
+    #[serde(default)]
+    symbologies: Vec<String>,
+}
+
+pub struct LoadedPlugin {
+    pub info: PluginInfo,
+    pub drivers: Vec<Box<dyn PrinterDriver>>,
+    pub data_sources: Vec<Box<dyn DataSourcePlugin>>,
+    pub symbologies: Vec<Box<dyn SymbologyPlugin>>,
+}
+
+pub fn is_library(path: &Path) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    ["so", "dylib", "dll"].contains(&extension)
+}
+
+// A loaded library and its function table. The table points into the
+// library, so the two are kept together.
+struct Handle {
+    _library: Library,
+    table: &'static LabelgoodPluginV1,
+}
+
+impl Handle {
+    fn take_string(&self, ptr: *mut c_char) -> Result<String, String> {
+        if ptr.is_null() {
+            return Err("plugin returned no result".to_string());
+        }
+        // SAFETY: the plugin returns a NUL-terminated string it allocated,
+        // which stays valid until handed back to its free_string
+        let text = unsafe { CStr::from_ptr(ptr) }.to_string_lossy().to_string();
+        unsafe { (self.table.free_string)(ptr) };
+        Ok(text)
+    }
+
+    fn call(&self, method: &str, args: Value) -> Result<Value, String> {
+        let method = CString::new(method).map_err(|e| e.to_string())?;
+        let args = CString::new(args.to_string()).map_err(|e| e.to_string())?;
+        // SAFETY: both arguments are valid C strings for the duration of the call
+        let response = self.take_string(unsafe { (self.table.call)(method.as_ptr(), args.as_ptr()) })?;
+        let mut response: Value =
+            serde_json::from_str(&response).map_err(|e| format!("plugin returned invalid JSON: {}", e))?;
+        if let Some(error) = response.get("error").and_then(Value::as_str) {
+            return Err(error.to_string());
+        }
+        Ok(response.get_mut("ok").map(Value::take).unwrap_or(Value::Null))
+    }
+}
+
+// SAFETY: plugins must accept calls from any thread, as stated in the ABI
+unsafe impl Send for Handle {}
+unsafe impl Sync for Handle {}
+
+pub fn load(path: &Path) -> Result<LoadedPlugin, String> {
+    // SAFETY: loading a library runs its initialisers; plugins in the
+    // plugins directory are trusted like any other installed code
+    let library = unsafe { Library::new(path) }.map_err(|e| format!("failed to load: {}", e))?;
+    let table = unsafe {
+        let entry = library
+            .get::<unsafe extern "C" fn() -> *const LabelgoodPluginV1>(ENTRY_POINT)
+            .map_err(|_| "not a Labelgood plugin (no labelgood_plugin_v1)".to_string())?;
+        let table = entry();
+        if table.is_null() {
+            return Err("plugin returned no function table".to_string());
+        }
+        &*table
+    };
+    if table.abi_version != ABI_VERSION {
+        return Err(format!("plugin ABI version {} is not supported (expected {})", table.abi_version, ABI_VERSION));
+    }
+
+    let handle = Arc::new(Handle { _library: library, table });
+    // SAFETY: manifest takes no arguments
+    let manifest = handle.take_string(unsafe { (handle.table.manifest)() })?;
+    let manifest: Manifest = serde_json::from_str(&manifest).map_err(|e| format!("invalid manifest: {}", e))?;
+
+    Ok(LoadedPlugin {
+        info: PluginInfo {
+            name: manifest.name,
+            version: manifest.version,
+            path: path.display().to_string(),
+            printer_drivers: manifest.printer_drivers.clone(),
+            data_sources: manifest.data_sources.clone(),
+            symbologies: manifest.symbologies.clone(),
+        },
+        drivers: manifest
+            .printer_drivers
+            .into_iter()
+            .map(|name| Box::new(Native { name, handle: handle.clone() }) as Box<dyn PrinterDriver>)
+            .collect(),
+        data_sources: manifest
+            .data_sources
+            .into_iter()
+            .map(|name| Box::new(Native { name, handle: handle.clone() }) as Box<dyn DataSourcePlugin>)
+            .collect(),
+        symbologies: manifest
+            .symbologies
+            .into_iter()
+            .map(|name| Box::new(Native { name, handle: handle.clone() }) as Box<dyn SymbologyPlugin>)
+            .collect(),
+    })
+}
+
+/// One named capability of a native plugin
+struct Native {
+    name: String,
+    handle: Arc<Handle>,
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(value: Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|e| format!("plugin returned an unexpected result: {}", e))
+}
+
+impl PrinterDriver for Native {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn list_printers(&self) -> Result<Vec<String>, String> {
+        decode(self.handle.call("list_printers", json!({ "driver": self.name }))?)
+    }
+
+    fn print_pdf(&self, printer: &str, pdf_path: &Path, width_mm: f64, height_mm: f64) -> Result<String, String> {
+        let args = json!({
+            "driver": self.name,
+            "printer": printer,
+            "path": pdf_path.display().to_string(),
+            "width_mm": width_mm,
+            "height_mm": height_mm,
+        });
+        decode(self.handle.call("print_pdf", args)?)
+    }
+}
+
+impl DataSourcePlugin for Native {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn load(&self, config: &Value) -> Result<DataTable, String> {
+        decode(self.handle.call("load_data", json!({ "source": self.name, "config": config }))?)
+    }
+}
+
+#[derive(Deserialize)]
+struct EncodedSymbol {
+    width: u32,
+    height: u32,
+    /// Row-major, '1' for dark modules
+    modules: String,
+    #[serde(default)]
+    text: String,
+}
+
+impl SymbologyPlugin for Native {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn encode(&self, data: &str) -> Result<Symbol, String> {
+        let symbol: EncodedSymbol =
+            decode(self.handle.call("encode_barcode", json!({ "symbology": self.name, "data": data }))?)?;
+        let modules: Vec<bool> = symbol.modules.chars().map(|c| c == '1').collect();
+        if symbol.width == 0 || symbol.height == 0 || modules.len() != (symbol.width * symbol.height) as usize {
+            return Err(format!("{} returned a malformed symbol", self.name));
+        }
+        let text = if symbol.text.is_empty() { data.to_string() } else { symbol.text };
+        Ok(Symbol::from_modules(symbol.width, symbol.height, modules, text))
+    }
+}
@@ -0,0 +1,48 @@
+// Portable mode, for shared kiosk PCs and installs run from a USB stick:
+// with a file named "portable" next to the executable, or LABELGOOD_PORTABLE
+// set, settings, templates, history and everything else Labelgood keeps live
+// in a "data" directory beside the executable instead of the user profile.
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+/// Marker file next to the executable that turns portable mode on
+pub const MARKER_FILE: &str = "portable";
+/// Set to anything but "" or "0" to run portable without a marker file
+pub const PORTABLE_ENV: &str = "LABELGOOD_PORTABLE";
+const DATA_DIR: &str = "data";
+
+/// The data directory beside the executable when running portable
+pub fn data_dir() -> Option<&'static PathBuf> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let exe_dir = match std::env::current_exe() {
+            Ok(exe) => exe.parent()?.to_path_buf(),
+            Err(e) => {
+                warn!("Failed to locate the executable, portable mode is off: {}", e);
+                return None;
+            }
+        };
+        let forced = std::env::var(PORTABLE_ENV).is_ok_and(|v| !v.is_empty() && v != "0");
+        (forced || exe_dir.join(MARKER_FILE).is_file()).then(|| exe_dir.join(DATA_DIR))
+    })
+    .as_ref()
+}
+
+/// Where app data is kept: the portable data directory or Tauri's app data
+/// directory in the user profile
+pub fn app_data_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    match data_dir() {
+        Some(dir) => Ok(dir.clone()),
+        None => app.path().app_data_dir(),
+    }
+}
+
+/// Where settings are kept; beside the rest of the data when portable
+pub fn app_config_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    match data_dir() {
+        Some(dir) => Ok(dir.clone()),
+        None => app.path().app_config_dir(),
+    }
+}
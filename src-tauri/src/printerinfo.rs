@@ -0,0 +1,107 @@
+// Make, model, serial number and firmware of a printer, from whatever its
+// backend can ask: the IEEE 1284 device ID of a USB printer, IPP printer
+// attributes, SNMP for raw network printers, and the PPD of a CUPS queue.
+// The make and command set tell which printer language it speaks.
+pub mod snmp;
+
+use crate::backends;
+use crate::calibration::PrinterLanguage;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PrinterInfo {
+    pub printer: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    pub resolution_dpi: Option<f64>,
+    /// IEEE 1284 device ID as reported, e.g. "MFG:Zebra;MDL:ZD421;CMD:ZPL;"
+    pub device_id: Option<String>,
+    /// Command language, for features that talk to the printer directly
+    pub language: Option<PrinterLanguage>,
+}
+
+// Non-empty trimmed text
+fn text(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches('"').trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+impl PrinterInfo {
+    pub fn new(printer_name: &str) -> Self {
+        PrinterInfo {
+            printer: printer_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Fill in what an IEEE 1284 device ID says: its MFG, MDL, CMD and SN keys
+    /// or their long forms
+    pub fn add_device_id(&mut self, device_id: &str) {
+        let mut command_set = None;
+        for field in device_id.split(';') {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            let value = text(value);
+            match key.trim().to_ascii_uppercase().as_str() {
+                "MFG" | "MANUFACTURER" => self.make = self.make.take().or(value),
+                "MDL" | "MODEL" => self.model = self.model.take().or(value),
+                "SN" | "SERN" | "SERIALNUMBER" => self.serial = self.serial.take().or(value),
+                "CMD" | "COMMAND SET" => command_set = value,
+                _ => {}
+            }
+        }
+        self.device_id = self.device_id.take().or(text(device_id));
+        self.language = self
+            .language
+            .or_else(|| command_set.as_deref().and_then(PrinterLanguage::detect));
+    }
+
+    /// Fill in make and model from a "make and model" string, e.g.
+    /// "Brother QL-820NWB"
+    pub fn add_make_and_model(&mut self, make_and_model: &str) {
+        let Some(make_and_model) = text(make_and_model) else {
+            return;
+        };
+        let (make, model) = make_and_model.split_once(' ').unwrap_or((make_and_model.as_str(), ""));
+        self.make = self.make.take().or(text(make));
+        self.model = self.model.take().or(text(model));
+    }
+
+    /// Fields `other` knows that this doesn't
+    pub fn merge(&mut self, other: PrinterInfo) {
+        self.make = self.make.take().or(other.make);
+        self.model = self.model.take().or(other.model);
+        self.serial = self.serial.take().or(other.serial);
+        self.firmware = self.firmware.take().or(other.firmware);
+        self.resolution_dpi = self.resolution_dpi.or(other.resolution_dpi);
+        self.device_id = self.device_id.take().or(other.device_id);
+        self.language = self.language.or(other.language);
+    }
+}
+
+/// What `printer_name` reports about itself. Fields nothing reported are
+/// left empty; the language falls back to what the make suggests.
+pub async fn printer_info(printer_name: &str) -> PrinterInfo {
+    let backend = backends::resolve(printer_name);
+    let mut info = backend.info(printer_name).await;
+    if info.resolution_dpi.is_none() {
+        info.resolution_dpi = Some(backend.capabilities(printer_name).await.dpi);
+    }
+    if info.language.is_none() {
+        let described = format!(
+            "{} {}",
+            info.make.as_deref().unwrap_or_default(),
+            info.model.as_deref().unwrap_or_default()
+        );
+        info.language = PrinterLanguage::detect(&described);
+    }
+    info
+}
+
+#[tauri::command]
+pub async fn get_printer_info(printer_name: String) -> PrinterInfo {
+    printer_info(&printer_name).await
+}
@@ -0,0 +1,148 @@
+// Just enough SNMP v2c to read a few strings from a network printer: one
+// GetRequest for the OIDs asked for, with the community most printers ship
+// with, and the OCTET STRING values of the response
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const PORT: u16 = 161;
+const COMMUNITY: &[u8] = b"public";
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// SNMP v2c, which reports missing OIDs one by one instead of failing the
+/// whole request like v1
+const VERSION_2C: u8 = 1;
+
+// BER tags
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const GET_REQUEST: u8 = 0xa0;
+const GET_RESPONSE: u8 = 0xa2;
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match value.len() {
+        len if len < 0x80 => out.push(len as u8),
+        len if len < 0x100 => out.extend([0x81, len as u8]),
+        len => out.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+// Encoded arcs of a dotted OID, e.g. "1.3.6.1.2.1.1.1.0"
+fn oid(text: &str) -> Option<Vec<u8>> {
+    let arcs: Vec<u32> = text.split('.').map(|arc| arc.parse().ok()).collect::<Option<_>>()?;
+    let [first, second, rest @ ..] = &arcs[..] else {
+        return None;
+    };
+    let mut out = vec![u8::try_from(first * 40 + second).ok()?];
+    for &arc in rest {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut high = arc >> 7;
+        while high > 0 {
+            bytes.push((high & 0x7f) as u8 | 0x80);
+            high >>= 7;
+        }
+        out.extend(bytes.iter().rev());
+    }
+    Some(out)
+}
+
+fn get_request(request_id: u16, oids: &[&str]) -> Option<Vec<u8>> {
+    let mut bindings = Vec::new();
+    for text in oids {
+        bindings.extend(tlv(
+            SEQUENCE,
+            &[tlv(OBJECT_IDENTIFIER, &oid(text)?), tlv(NULL, &[])].concat(),
+        ));
+    }
+    let pdu = [
+        tlv(INTEGER, &request_id.to_be_bytes()),
+        tlv(INTEGER, &[0]),
+        tlv(INTEGER, &[0]),
+        tlv(SEQUENCE, &bindings),
+    ]
+    .concat();
+    let message = [
+        tlv(INTEGER, &[VERSION_2C]),
+        tlv(OCTET_STRING, COMMUNITY),
+        tlv(GET_REQUEST, &pdu),
+    ]
+    .concat();
+    Some(tlv(SEQUENCE, &message))
+}
+
+// Tag and value of the TLV at the start of `bytes`, and what follows it
+fn read(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *bytes.first()?;
+    let (length, header) = match *bytes.get(1)? {
+        length if length < 0x80 => (length as usize, 2),
+        0x81 => (*bytes.get(2)? as usize, 3),
+        0x82 => (u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]) as usize, 4),
+        _ => return None,
+    };
+    let value = bytes.get(header..header + length)?;
+    Some((tag, value, &bytes[header + length..]))
+}
+
+// String values of the variable bindings of a GetResponse, in order
+fn parse_response(bytes: &[u8]) -> Option<Vec<Option<String>>> {
+    let (SEQUENCE, message, _) = read(bytes)? else {
+        return None;
+    };
+    let (_, _, rest) = read(message)?; // version
+    let (_, _, rest) = read(rest)?; // community
+    let (GET_RESPONSE, pdu, _) = read(rest)? else {
+        return None;
+    };
+    let (_, _, rest) = read(pdu)?; // request id
+    let (_, status, rest) = read(rest)?;
+    if status.iter().any(|b| *b != 0) {
+        return None;
+    }
+    let (_, _, rest) = read(rest)?; // error index
+    let (_, mut bindings, _) = read(rest)?;
+    let mut values = Vec::new();
+    while !bindings.is_empty() {
+        let (_, binding, rest) = read(bindings)?;
+        let (_, _, value) = read(binding)?; // name
+        let (tag, value, _) = read(value)?;
+        values.push(
+            (tag == OCTET_STRING)
+                .then(|| {
+                    String::from_utf8_lossy(value)
+                        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                        .to_string()
+                })
+                .filter(|text| !text.is_empty()),
+        );
+        bindings = rest;
+    }
+    Some(values)
+}
+
+/// String values of `oids` on `host`, in order; None for OIDs the printer
+/// doesn't have or that aren't strings
+pub async fn get_strings(host: &str, oids: &[&str]) -> Result<Vec<Option<String>>, String> {
+    let request_id = std::process::id() as u16;
+    let request = get_request(request_id, oids).ok_or("Invalid OID")?;
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open an SNMP socket: {}", e))?;
+    socket
+        .connect((host, PORT))
+        .await
+        .map_err(|e| format!("Failed to reach {} over SNMP: {}", host, e))?;
+    socket
+        .send(&request)
+        .await
+        .map_err(|e| format!("Failed to query {} over SNMP: {}", host, e))?;
+    let mut buffer = vec![0; 4096];
+    let length = tokio::time::timeout(TIMEOUT, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| format!("{} didn't answer SNMP within {} seconds", host, TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to read the SNMP answer of {}: {}", host, e))?;
+    parse_response(&buffer[..length]).ok_or_else(|| format!("{} sent an SNMP answer that couldn't be read", host))
+}
@@ -0,0 +1,363 @@
+// PDF generation and job submission shared by single labels and merged batches
+use crate::alignment;
+use crate::backends;
+use crate::color;
+use crate::error::LabelgoodError;
+use crate::events;
+use crate::groups;
+use crate::i18n;
+use crate::imagemagick;
+use crate::media;
+use crate::offline;
+use crate::pdf;
+use crate::pdfpreview;
+use crate::progress::{self, RENDER_PROGRESS_EVENT, SPOOL_PROGRESS_EVENT};
+use crate::queue;
+use crate::spool;
+use std::cell::RefCell;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+/// Resolution labels are rasterized at before conversion to PDF
+pub const RENDER_DPI: f64 = 300.0;
+
+/// Points per millimetre (1mm = 2.83465pt)
+pub const POINTS_PER_MM: f64 = 2.83465;
+
+// How long external programs may run before they're killed, so a network
+// printer that stops answering can't hang a job forever
+pub const LPSTAT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const CONVERT_TIMEOUT: Duration = Duration::from_secs(120);
+pub const LPR_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run `command` to completion without blocking the async runtime, killing it
+/// after `timeout` or when the job it runs for is cancelled. `missing`
+/// describes a program that couldn't be started.
+pub async fn run_command(
+    mut command: Command,
+    timeout: Duration,
+    missing: impl FnOnce(io::Error) -> LabelgoodError,
+) -> Result<Output, LabelgoodError> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    command.kill_on_drop(true);
+    tokio::select! {
+        result = tokio::time::timeout(timeout, command.output()) => match result {
+            Ok(result) => result.map_err(missing),
+            Err(_) => Err(LabelgoodError::TimedOut {
+                message: i18n::t(
+                    "command-timed-out",
+                    &[("program", program.as_str().into()), ("seconds", timeout.as_secs().into())],
+                ),
+                program,
+            }),
+        },
+        cancelled = progress::cancelled() => Err(cancelled),
+    }
+}
+
+/// Convert one or more PNG pages (rendered at `RENDER_DPI`) into a PDF in the
+/// spool directory, one page per image, each sized to the label
+pub async fn images_to_pdf(png_paths: &[&Path], width_mm: f64, height_mm: f64) -> Result<PathBuf, LabelgoodError> {
+    LabelgoodError::check_dimensions(width_mm, height_mm)?;
+
+    // Create persistent PDF file in the spool directory
+    let pdf_filename = format!("label_{}.pdf", std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis());
+    let pdf_path = spool::path(&pdf_filename);
+
+    info!("Generated PDF path: {}", pdf_path.display());
+
+    // Preserve the 300 DPI of the rendered PNGs throughout the conversion
+    let width_points = width_mm * POINTS_PER_MM;
+    let height_points = height_mm * POINTS_PER_MM;
+
+    info!("Creating PDF: {}x{} mm ({}x{} points), {} page(s)",
+             width_mm, height_mm,
+             width_points as u32, height_points as u32, png_paths.len());
+
+    progress::report(RENDER_PROGRESS_EVENT, "converting", 0, 1)?;
+    let converter = if cfg!(mobile) {
+        // Phones and tablets have no ImageMagick to look for
+        None
+    } else {
+        match imagemagick::converter().await {
+            Ok(converter) if converter.pdf_blocked_by.is_none() => Some(converter),
+            Ok(converter) => {
+                warn!("{} may not write PDF, writing it without ImageMagick", converter.tool.program());
+                None
+            }
+            Err(e) => {
+                warn!("{}; writing the PDF without ImageMagick", e);
+                None
+            }
+        }
+    };
+    if let Some(converter) = converter {
+        // Convert PNG to PDF preserving dimensions:
+        // The key is to read at 300 DPI and write at 72 DPI (PDF standard)
+        // This causes ImageMagick to scale appropriately
+        let mut command = converter.tool.command();
+        command
+            .args(png_paths)
+            .arg("-density").arg(RENDER_DPI.to_string()) // Input PNG is at 300 DPI
+            .arg("-units").arg("PixelsPerInch")
+            .arg("-density").arg("72") // Output PDF at 72 DPI (standard)
+            .arg("-page").arg(format!("{}x{}", width_points as u32, height_points as u32)) // Set PDF page size in points
+            .arg(&pdf_path);
+        let output = run_command(command, CONVERT_TIMEOUT, LabelgoodError::imagemagick_missing).await?;
+
+        let error = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            info!("PDF generated successfully at: {}", pdf_path.display());
+            progress::report(RENDER_PROGRESS_EVENT, "converting", 1, 1)?;
+            return Ok(pdf_path);
+        } else if !imagemagick::is_policy_error(&error) {
+            error!("ImageMagick convert failed. stderr: {}", error);
+            debug!("ImageMagick stdout: {}", String::from_utf8_lossy(&output.stdout));
+            return Err(LabelgoodError::RenderFailed {
+                message: format!("ImageMagick convert failed: {}. Make sure ImageMagick is installed.", error),
+            });
+        }
+        warn!("{} refused to write PDF, writing it without ImageMagick: {}", converter.tool.program(), error);
+    }
+
+    let paths: Vec<PathBuf> = png_paths.iter().map(|p| p.to_path_buf()).collect();
+    let target = pdf_path.clone();
+    let writing = tauri::async_runtime::spawn_blocking(move || {
+        pdf::write_image_pdf(&paths, width_points, height_points, &target)
+    });
+    tokio::select! {
+        result = writing => result.map_err(|e| LabelgoodError::Other { message: e.to_string() })??,
+        cancelled = progress::cancelled() => return Err(cancelled),
+    }
+    info!("PDF written at: {}", pdf_path.display());
+    progress::report(RENDER_PROGRESS_EVENT, "converting", 1, 1)?;
+    Ok(pdf_path)
+}
+
+/// A PDF that was sent to a printer or opened, kept so it can be printed again
+#[derive(Debug, Clone)]
+pub struct SubmittedJob {
+    pub pdf_path: PathBuf,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub printer_name: Option<String>,
+}
+
+impl SubmittedJob {
+    /// Number of labels, counted as PDF pages
+    pub fn pages(&self) -> usize {
+        fs::read(&self.pdf_path).map_or(0, |pdf| count_pages(&pdf))
+    }
+}
+
+/// Pages of a PDF, counted by their /Type /Page objects
+pub fn count_pages(pdf: &[u8]) -> usize {
+    pdf.windows(12)
+        .filter(|w| w.starts_with(b"/Type /Page") && w[11] != b's')
+        .count()
+}
+
+static LAST_JOB: Mutex<Option<SubmittedJob>> = Mutex::new(None);
+
+tokio::task_local! {
+    // What the current job's work submitted, so a job runner can pick up the
+    // PDF it produced without every caller passing it back
+    static SUBMITTED: RefCell<Option<SubmittedJob>>;
+}
+
+/// The most recent job `submit_pdf` completed, from any source
+pub fn last_job() -> Option<SubmittedJob> {
+    LAST_JOB.lock().unwrap().clone()
+}
+
+/// Run `work`, returning its output along with the last job `submit_pdf`
+/// completed while it ran
+pub async fn capture_submitted<T>(work: impl Future<Output = T>) -> (T, Option<SubmittedJob>) {
+    SUBMITTED
+        .scope(RefCell::new(None), async {
+            let output = work.await;
+            (output, SUBMITTED.with(|submitted| submitted.borrow_mut().take()))
+        })
+        .await
+}
+
+/// Print a label rendered to `png` at its size, as the app prints the label
+/// being edited
+pub async fn print_png(
+    png: &Path,
+    width_mm: f64,
+    height_mm: f64,
+    printer_name: Option<&str>,
+) -> Result<String, LabelgoodError> {
+    let pdf_path = images_to_pdf(&[png], width_mm, height_mm).await?;
+    submit_pdf(&pdf_path, width_mm, height_mm, printer_name).await
+}
+
+/// Send a PDF to `printer_name` through the print queue, or open it with the
+/// default viewer when no printer is given. Returns a status message or the
+/// PDF path respectively.
+pub async fn submit_pdf(
+    pdf_path: &Path,
+    width_mm: f64,
+    height_mm: f64,
+    printer_name: Option<&str>,
+) -> Result<String, LabelgoodError> {
+    let message = match printer_name {
+        // Group printers fail over to each other instead of holding the job
+        Some(group) if groups::is_group(group) => {
+            groups::spool(group, |printer| async move {
+                queue::spool(&printer, || send_pdf(pdf_path, width_mm, height_mm, Some(&printer))).await
+            })
+            .await?
+        }
+        Some(printer) => match queue::spool(printer, || send_pdf(pdf_path, width_mm, height_mm, printer_name)).await {
+            // Kept until the printer is back, and not recorded as submitted
+            Err(e @ LabelgoodError::PrinterUnavailable { .. }) => {
+                return Err(offline::hold(pdf_path, width_mm, height_mm, printer, e).await);
+            }
+            result => result?,
+        },
+        None => send_pdf(pdf_path, width_mm, height_mm, None).await?,
+    };
+    let job = SubmittedJob {
+        pdf_path: pdf_path.to_path_buf(),
+        width_mm,
+        height_mm,
+        printer_name: printer_name.map(str::to_string),
+    };
+    // Outside `capture_submitted` there's no job runner to tell
+    let _ = SUBMITTED.try_with(|submitted| *submitted.borrow_mut() = Some(job.clone()));
+    *LAST_JOB.lock().unwrap() = Some(job);
+    Ok(message)
+}
+
+async fn send_pdf(
+    pdf_path: &Path,
+    width_mm: f64,
+    height_mm: f64,
+    printer_name: Option<&str>,
+) -> Result<String, LabelgoodError> {
+    let pdf_path_str = pdf_path.to_string_lossy().to_string();
+
+    let Some(printer_name) = printer_name else {
+        if !pdfpreview::external_viewer() {
+            pdfpreview::register(pdf_path, width_mm, height_mm)?;
+            return Ok(pdf_path_str);
+        }
+        // Open the PDF with the system default application (cross-platform)
+        #[cfg(not(mobile))]
+        opener::open(&pdf_path_str).map_err(|e| LabelgoodError::Io {
+            message: format!("Failed to open PDF: {}", e),
+        })?;
+        #[cfg(mobile)]
+        backends::mobile::open(pdf_path)?;
+        return Ok(pdf_path_str);
+    };
+
+    // Verify PDF exists
+    if !pdf_path.exists() {
+        return Err(LabelgoodError::Io {
+            message: format!("PDF file does not exist at: {}", pdf_path_str),
+        });
+    }
+
+    // Corrected only now, so held jobs and reprints follow the printer they
+    // end up on
+    let corrected = match alignment::for_printer(printer_name) {
+        Some(correction) => Some(alignment::correct_pdf(pdf_path, &correction, width_mm, height_mm).await?),
+        None => None,
+    };
+    let pdf_path = corrected.as_deref().unwrap_or(pdf_path);
+    let converted = match color::for_printer(printer_name) {
+        Some(printer) => color::convert_pdf(pdf_path, &printer, width_mm, height_mm).await?,
+        None => None,
+    };
+    let pdf_path = converted.as_deref().unwrap_or(pdf_path);
+
+    progress::report(SPOOL_PROGRESS_EVENT, "spooling", 0, 1)?;
+    let labels = fs::read(pdf_path).map_or(0, |pdf| count_pages(&pdf));
+    media::check(printer_name, labels, width_mm, height_mm);
+    let job = backends::PrintJob {
+        printer: printer_name,
+        pdf_path,
+        width_mm,
+        height_mm,
+    };
+    let message = match backends::submit(job).await {
+        Ok(submission) => submission.message,
+        Err(e) => {
+            if let LabelgoodError::AuthRequired { printer, message } = &e {
+                events::request_credentials(printer, message);
+            }
+            return Err(e);
+        }
+    };
+    media::consume(printer_name, labels, width_mm, height_mm);
+    // Spooled is spooled; a cancel arriving now is too late to report
+    let _ = progress::report(SPOOL_PROGRESS_EVENT, "spooled", 1, 1);
+    Ok(message)
+}
+
+/// Pages of a PDF rendered to PNG at `RENDER_DPI` on a white background;
+/// only `page` (0-based) when given
+pub async fn rasterize_pdf(pdf_path: &Path, page: Option<usize>) -> Result<Vec<Vec<u8>>, LabelgoodError> {
+    let out_dir = tempfile::tempdir().map_err(|e| LabelgoodError::Io {
+        message: format!("Failed to create temp directory: {}", e),
+    })?;
+    let input = match page {
+        Some(page) => format!("{}[{}]", pdf_path.display(), page),
+        None => pdf_path.display().to_string(),
+    };
+    let converter = imagemagick::converter().await?;
+    let mut command = converter.tool.command();
+    command
+        .arg("-density").arg(RENDER_DPI.to_string())
+        .arg(&input)
+        .arg("-background").arg("white");
+    if converter.tool.supports_alpha() {
+        command.arg("-alpha").arg("remove").arg("-alpha").arg("off");
+    }
+    command
+        .arg("-units").arg("PixelsPerInch")
+        .arg("+adjoin")
+        .arg(out_dir.path().join("page-%d.png"));
+    let result = run_command(command, CONVERT_TIMEOUT, LabelgoodError::imagemagick_missing).await?;
+    if !result.status.success() {
+        let error = String::from_utf8_lossy(&result.stderr);
+        if imagemagick::is_policy_error(&error) {
+            return Err(converter.policy_error());
+        }
+        return Err(LabelgoodError::RenderFailed {
+            message: format!("ImageMagick convert failed: {}", error),
+        });
+    }
+
+    // page-0.png, page-1.png, ... in page order
+    let mut pages = Vec::new();
+    loop {
+        let png_path = out_dir.path().join(format!("page-{}.png", pages.len()));
+        if !png_path.exists() {
+            break;
+        }
+        let png = fs::read(&png_path).map_err(|e| LabelgoodError::Io {
+            message: format!("Failed to read rendered PDF page: {}", e),
+        })?;
+        pages.push(png);
+    }
+    if pages.is_empty() {
+        return Err(LabelgoodError::RenderFailed {
+            message: format!("{} has no pages", pdf_path.display()),
+        });
+    }
+    Ok(pages)
+}
@@ -0,0 +1,98 @@
+// Progress of the running print job, so the frontend can show a progress bar
+// and a cancel button for large batches and slow printers. Reporting is tied
+// to the job `EventBus::run_job` is running; outside a job (previews, the
+// CLI) reports go nowhere and nothing can be cancelled.
+use crate::error::LabelgoodError;
+use crate::i18n;
+use serde::Serialize;
+use std::future::Future;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+use tracing::error;
+
+/// Event emitted while labels are rendered and converted to PDF
+pub const RENDER_PROGRESS_EVENT: &str = "render-progress";
+/// Event emitted while the PDF is handed to the printer
+pub const SPOOL_PROGRESS_EVENT: &str = "spool-progress";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    /// Job id from the job's `label-event`s
+    pub job: u64,
+    /// "rendering" or "converting" for render progress, "spooling" or
+    /// "spooled" for spool progress
+    pub stage: String,
+    pub done: usize,
+    pub total: usize,
+    pub percent: u8,
+}
+
+/// What work running inside a job reports to
+#[derive(Clone)]
+pub struct JobTracker {
+    app: AppHandle,
+    job: u64,
+    cancelled: watch::Receiver<bool>,
+}
+
+impl JobTracker {
+    pub fn new(app: AppHandle, job: u64, cancelled: watch::Receiver<bool>) -> Self {
+        JobTracker { app, job, cancelled }
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: JobTracker;
+}
+
+/// Run `work` as the job `tracker` reports for
+pub async fn track<T>(tracker: JobTracker, work: impl Future<Output = T>) -> T {
+    CURRENT.scope(tracker, work).await
+}
+
+/// Id of the job the current task runs for
+pub fn current_job() -> Option<u64> {
+    CURRENT.try_with(|tracker| tracker.job).ok()
+}
+
+/// App the current job runs in
+pub fn app() -> Option<AppHandle> {
+    CURRENT.try_with(|tracker| tracker.app.clone()).ok()
+}
+
+/// Report `done` of `total` steps of `stage` under `event`. Fails once the
+/// job has been cancelled, so loops stop at the next step.
+pub fn report(event: &str, stage: &str, done: usize, total: usize) -> Result<(), LabelgoodError> {
+    let Ok(tracker) = CURRENT.try_with(JobTracker::clone) else {
+        return Ok(());
+    };
+    if *tracker.cancelled.borrow() {
+        return Err(LabelgoodError::Cancelled {
+            message: i18n::t("job-cancelled", &[("job", tracker.job.into())]),
+        });
+    }
+    let progress = Progress {
+        job: tracker.job,
+        stage: stage.to_string(),
+        done,
+        total,
+        percent: (done * 100 / total.max(1)).min(100) as u8,
+    };
+    if let Err(e) = tracker.app.emit(event, &progress) {
+        error!("Failed to emit {}: {}", event, e);
+    }
+    Ok(())
+}
+
+/// Resolves when the current job is cancelled; never outside a job
+pub async fn cancelled() -> LabelgoodError {
+    let tracker = CURRENT.try_with(JobTracker::clone).ok();
+    if let Some(mut tracker) = tracker {
+        if tracker.cancelled.wait_for(|cancelled| *cancelled).await.is_ok() {
+            return LabelgoodError::Cancelled {
+                message: i18n::t("job-cancelled", &[("job", tracker.job.into())]),
+            };
+        }
+    }
+    std::future::pending().await
+}
@@ -0,0 +1,229 @@
+// Labelgood project files (.lblg): a zip bundle holding a versioned JSON
+// manifest plus the original bytes of every embedded asset
+use crate::document::{asset_id, Asset, Document};
+use crate::ingest;
+use crate::versions;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Version written by this build. Bump it whenever the saved layout changes
+/// and add a step to `migrate` that upgrades the previous version.
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+pub const PROJECT_EXTENSION: &str = "lblg";
+
+const MANIFEST_ENTRY: &str = "project.json";
+const ASSETS_DIR: &str = "assets/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub format_version: u32,
+    pub document: Document,
+}
+
+impl Project {
+    pub fn new(document: Document) -> Self {
+        Project {
+            format_version: CURRENT_FORMAT_VERSION,
+            document,
+        }
+    }
+}
+
+pub fn save(project: &Project, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create project file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let zip_err = |e: zip::result::ZipError| format!("Failed to write project file: {}", e);
+    let io_err = |e: std::io::Error| format!("Failed to write project file: {}", e);
+
+    // Assets go in as separate entries so they keep their original bytes;
+    // images are already compressed, so store them as-is
+    let mut manifest = project.clone();
+    for (id, asset) in manifest.document.assets.iter_mut() {
+        let bytes = asset.bytes()?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file(format!("{}{}", ASSETS_DIR, id), options).map_err(zip_err)?;
+        zip.write_all(&bytes).map_err(io_err)?;
+        asset.data.clear();
+    }
+
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize project: {}", e))?;
+    zip.start_file(MANIFEST_ENTRY, SimpleFileOptions::default()).map_err(zip_err)?;
+    zip.write_all(json.as_bytes()).map_err(io_err)?;
+    zip.finish().map_err(zip_err)?;
+    Ok(())
+}
+
+/// Document from a .lblg project or any label file `formats` can import
+pub fn load_document(path: &Path) -> Result<Document, String> {
+    let is_project = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(PROJECT_EXTENSION));
+    if is_project {
+        Ok(load(path)?.document)
+    } else {
+        crate::formats::import(path)
+    }
+}
+
+pub fn load(path: &Path) -> Result<Project, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read project file: {}", e))?;
+
+    // Format versions before 2 were a plain JSON file
+    if !bytes.starts_with(b"PK") {
+        let json = String::from_utf8(bytes).map_err(|_| "Project file is not valid UTF-8")?;
+        return parse(&json);
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| format!("Project file is not a valid bundle: {}", e))?;
+    let json = read_entry(&mut archive, MANIFEST_ENTRY)
+        .ok_or("Project bundle has no project.json")
+        .and_then(|bytes| String::from_utf8(bytes).map_err(|_| "Project manifest is not valid UTF-8"))?;
+    let mut project = parse(&json)?;
+
+    for (id, asset) in project.document.assets.iter_mut() {
+        if asset.data.is_empty() {
+            let bytes = read_entry(&mut archive, &format!("{}{}", ASSETS_DIR, id))
+                .ok_or_else(|| format!("Project bundle is missing asset {}", asset.file_name))?;
+            asset.data = BASE64.encode(bytes);
+        }
+    }
+
+    Ok(project)
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+pub fn parse(json: &str) -> Result<Project, String> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| format!("Project file is not valid JSON: {}", e))?;
+    let value = migrate(value)?;
+    serde_json::from_value(value).map_err(|e| format!("Project file is invalid: {}", e))
+}
+
+/// Upgrade a raw project value to `CURRENT_FORMAT_VERSION`, one version at a time
+fn migrate(mut value: Value) -> Result<Value, String> {
+    let mut version = format_version(&value)?;
+
+    if version > CURRENT_FORMAT_VERSION {
+        return Err(format!(
+            "This project was saved by a newer version of Labelgood (format version {}, \
+             this version supports up to {}). Please update Labelgood to open it.",
+            version, CURRENT_FORMAT_VERSION
+        ));
+    }
+
+    while version < CURRENT_FORMAT_VERSION {
+        value = match version {
+            0 => migrate_v0_to_v1(value),
+            1 => migrate_v1_to_v2(value)?,
+            _ => unreachable!("no migration registered for format version {}", version),
+        };
+        version += 1;
+        value["format_version"] = json!(version);
+    }
+
+    Ok(value)
+}
+
+// Files without a version predate versioning
+fn format_version(value: &Value) -> Result<u32, String> {
+    match value.get("format_version") {
+        None => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("Project file has an invalid format_version: {}", v)),
+    }
+}
+
+// v0 files were a bare document object
+fn migrate_v0_to_v1(value: Value) -> Value {
+    json!({ "document": value })
+}
+
+// v1 image elements carried their bytes inline; v2 moves them into the
+// document's asset table and references them by id
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, String> {
+    let document = value
+        .get_mut("document")
+        .and_then(Value::as_object_mut)
+        .ok_or("Project file has no document")?;
+
+    let mut assets = Map::new();
+    if let Some(elements) = document.get_mut("elements").and_then(Value::as_array_mut) {
+        for element in elements.iter_mut().filter_map(Value::as_object_mut) {
+            if element.get("type").and_then(Value::as_str) != Some("image") {
+                continue;
+            }
+            let data = element.remove("data").and_then(|d| d.as_str().map(str::to_string));
+            let mime_type = element
+                .remove("mime_type")
+                .and_then(|m| m.as_str().map(str::to_string))
+                .unwrap_or_else(|| "image/png".to_string());
+            let bytes = BASE64
+                .decode(data.unwrap_or_default())
+                .map_err(|e| format!("Project file has an invalid image: {}", e))?;
+
+            let id = asset_id(&bytes);
+            let asset = Asset {
+                file_name: format!("image-{}", id),
+                mime_type,
+                data: BASE64.encode(&bytes),
+            };
+            assets.insert(id.clone(), json!(asset));
+            element.insert("asset_id".to_string(), json!(id));
+        }
+    }
+
+    document.insert("assets".to_string(), Value::Object(assets));
+    Ok(value)
+}
+
+#[tauri::command]
+pub async fn save_project(document: Document, path: String) -> Result<String, String> {
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension(PROJECT_EXTENSION);
+    }
+    // Saving over a good design shouldn't lose it
+    if let Err(e) = versions::before_save(&path, &document) {
+        warn!("Failed to keep a version of {}: {}", path.display(), e);
+    }
+    save(&Project::new(document), &path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn open_project(path: String) -> Result<Document, String> {
+    load(Path::new(&path)).map(|project| project.document)
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadedAsset {
+    pub id: String,
+    pub asset: Asset,
+}
+
+/// Read an image file, normalized by `ingest`, so the frontend can add it to
+/// a document's assets
+#[tauri::command]
+pub async fn load_image_asset(path: String) -> Result<LoadedAsset, String> {
+    Ok(ingest::load(Path::new(&path), None).await?.into_asset())
+}
@@ -0,0 +1,109 @@
+// Print-time prompts: fields a template asks for in a form each time it's
+// printed, like a batch number or best-before date, instead of reading them
+// from a data source. Answers apply to every label of the job.
+use crate::document::{Document, PromptField, PromptKind};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptError {
+    pub field: String,
+    pub message: String,
+}
+
+// Why `answer` isn't acceptable for `prompt`, if it isn't
+fn check(prompt: &PromptField, answer: &str) -> Option<String> {
+    let label = if prompt.label.is_empty() {
+        &prompt.name
+    } else {
+        &prompt.label
+    };
+    match prompt.kind {
+        PromptKind::Text => {}
+        PromptKind::Number => {
+            if answer.trim().replace(',', ".").parse::<f64>().is_err() {
+                return Some(format!("{} must be a number", label));
+            }
+        }
+        PromptKind::Date => {
+            if chrono::NaiveDate::parse_from_str(answer.trim(), "%Y-%m-%d").is_err() {
+                return Some(format!("{} must be a date (YYYY-MM-DD)", label));
+            }
+        }
+    }
+    let pattern = prompt.pattern.as_deref().filter(|p| !p.is_empty())?;
+    match Regex::new(&format!("^(?:{})$", pattern)) {
+        Ok(regex) if regex.is_match(answer) => None,
+        Ok(_) => Some(format!("{} doesn't have the expected format", label)),
+        Err(e) => Some(format!("{} has an invalid pattern: {}", label, e)),
+    }
+}
+
+/// Problems with the answers for `prompts`; empty answers are left to the
+/// field's default and aren't checked
+pub fn check_answers(prompts: &[PromptField], answers: &BTreeMap<String, String>) -> Vec<PromptError> {
+    prompts
+        .iter()
+        .filter_map(|prompt| {
+            let answer = answers.get(&prompt.name).filter(|a| !a.is_empty())?;
+            let message = check(prompt, answer)?;
+            Some(PromptError {
+                field: prompt.name.clone(),
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Fail with every problem `check_answers` finds
+pub fn validate(prompts: &[PromptField], answers: &BTreeMap<String, String>) -> Result<(), String> {
+    let messages: Vec<String> = check_answers(prompts, answers)
+        .into_iter()
+        .map(|problem| problem.message)
+        .collect();
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(messages.join("\n"))
+    }
+}
+
+/// Fill the prompt fields of `values`: non-empty answers win, otherwise a
+/// value from the data row is kept, otherwise the default is used
+pub fn fill(prompts: &[PromptField], answers: &BTreeMap<String, String>, values: &mut BTreeMap<String, String>) {
+    for prompt in prompts {
+        match answers.get(&prompt.name).filter(|a| !a.is_empty()) {
+            Some(answer) => {
+                values.insert(prompt.name.clone(), answer.clone());
+            }
+            None => {
+                values
+                    .entry(prompt.name.clone())
+                    .or_insert_with(|| prompt.default.clone());
+            }
+        }
+    }
+}
+
+/// The fields to ask for before printing `document`, in form order, each
+/// with a label
+#[tauri::command]
+pub fn get_prompt_fields(document: Document) -> Vec<PromptField> {
+    document
+        .prompts
+        .into_iter()
+        .map(|mut prompt| {
+            if prompt.label.is_empty() {
+                prompt.label = prompt.name.clone();
+            }
+            prompt
+        })
+        .collect()
+}
+
+/// Check a filled-in prompt form before printing
+#[tauri::command]
+pub fn check_prompt_answers(document: Document, answers: BTreeMap<String, String>) -> Vec<PromptError> {
+    check_answers(&document.prompts, &answers)
+}
@@ -0,0 +1,305 @@
+// Print queue: jobs for the same printer are spooled one at a time, highest
+// priority first and otherwise in the order they arrive, with an optional
+// pause between them for thermal printers that drop jobs sent back to back.
+// A rush reprint waiting behind a long batch can be moved ahead of it without
+// cancelling anything. Spooling that fails for a reason that may pass (a
+// timeout, a busy spooler) is retried.
+use crate::error::LabelgoodError;
+use crate::progress::{self, SPOOL_PROGRESS_EVENT};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::warn;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueSettings {
+    /// Pause between jobs on the same printer
+    pub delay_ms: u64,
+    /// Per-printer pause, overriding `delay_ms`
+    pub printer_delay_ms: BTreeMap<String, u64>,
+    /// Further attempts after a transient spooling failure
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        QueueSettings {
+            delay_ms: 0,
+            printer_delay_ms: BTreeMap::new(),
+            retries: 2,
+            retry_delay_ms: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueState {
+    /// Waiting for earlier jobs on the printer
+    Waiting,
+    Spooling,
+    /// Waiting to try again after a failed attempt
+    Retrying,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    /// E.g. a reprint someone is standing at the printer for
+    Rush,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedJob {
+    /// Queue entry id, for reordering
+    pub id: u64,
+    /// Job id from `label-event`s, if the spooling belongs to a job
+    pub job: Option<u64>,
+    pub printer: String,
+    pub state: QueueState,
+    pub priority: Priority,
+    /// 1 for the first attempt
+    pub attempt: u32,
+    /// RFC 3339 UTC
+    pub queued_at: String,
+    /// Order among waiting jobs of the same priority; the entry id unless
+    /// the job was moved
+    #[serde(skip)]
+    position: i64,
+}
+
+#[derive(Default)]
+struct Printer {
+    /// Whether a job is being sent to the printer
+    busy: bool,
+    last_finished: Option<Instant>,
+}
+
+#[derive(Default)]
+struct PrintQueue {
+    settings: Mutex<QueueSettings>,
+    /// Locked before `entries` where both are needed
+    printers: Mutex<HashMap<String, Printer>>,
+    /// Spooling that hasn't finished, in arrival order
+    entries: Mutex<BTreeMap<u64, QueuedJob>>,
+    next_entry: AtomicU64,
+    /// Wakes waiting jobs when a printer frees up or the queue is reordered
+    changed: Notify,
+}
+
+tokio::task_local! {
+    // Priority of the jobs spooled by the current work
+    static PRIORITY: Priority;
+}
+
+static QUEUE: OnceLock<PrintQueue> = OnceLock::new();
+
+fn queue() -> &'static PrintQueue {
+    QUEUE.get_or_init(PrintQueue::default)
+}
+
+pub fn configure(settings: QueueSettings) {
+    *queue().settings.lock().unwrap() = settings;
+}
+
+/// Run `work` with whatever it spools queued at `priority`
+pub async fn with_priority<T>(priority: Priority, work: impl Future<Output = T>) -> T {
+    PRIORITY.scope(priority, work).await
+}
+
+/// Jobs waiting for or being sent to a printer, oldest first
+pub fn snapshot() -> Vec<QueuedJob> {
+    queue().entries.lock().unwrap().values().cloned().collect()
+}
+
+// Where a waiting job stands among those for the same printer; lowest goes
+// first
+fn turn_order(entry: &QueuedJob) -> (Reverse<Priority>, i64) {
+    (Reverse(entry.priority), entry.position)
+}
+
+// Start sending entry `id` to `printer` if the printer is free and no
+// waiting job is ahead of it, returning when the printer last finished a job
+fn take_turn(id: u64, printer: &str) -> Option<Option<Instant>> {
+    let queue = queue();
+    let mut printers = queue.printers.lock().unwrap();
+    let line = printers.entry(printer.to_string()).or_default();
+    if line.busy {
+        return None;
+    }
+    let mut entries = queue.entries.lock().unwrap();
+    let next = entries
+        .values()
+        .filter(|entry| entry.printer == printer && entry.state == QueueState::Waiting)
+        .min_by_key(|entry| turn_order(entry))?;
+    if next.id != id {
+        return None;
+    }
+    // No longer waiting, so it can't be reordered
+    if let Some(entry) = entries.get_mut(&id) {
+        entry.state = QueueState::Spooling;
+    }
+    line.busy = true;
+    Some(line.last_finished)
+}
+
+// Frees the printer however spooling ends, including cancellation
+struct Turn<'a>(&'a str);
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let queue = queue();
+        if let Some(line) = queue.printers.lock().unwrap().get_mut(self.0) {
+            line.busy = false;
+            line.last_finished = Some(Instant::now());
+        }
+        queue.changed.notify_waiters();
+    }
+}
+
+// Removes the entry however spooling ends, including cancellation
+struct Entry(u64);
+
+impl Entry {
+    fn set(&self, state: QueueState, attempt: u32) {
+        if let Some(entry) = queue().entries.lock().unwrap().get_mut(&self.0) {
+            entry.state = state;
+            entry.attempt = attempt;
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        queue().entries.lock().unwrap().remove(&self.0);
+        // A job that gave up waiting may have been next
+        queue().changed.notify_waiters();
+    }
+}
+
+// Failures worth another attempt; a missing printer or program won't appear
+// within seconds
+fn is_transient(error: &LabelgoodError) -> bool {
+    matches!(error, LabelgoodError::TimedOut { .. } | LabelgoodError::SpoolFailed { .. })
+}
+
+async fn sleep_unless_cancelled(duration: Duration) -> Result<(), LabelgoodError> {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => Ok(()),
+        cancelled = progress::cancelled() => Err(cancelled),
+    }
+}
+
+/// Run `attempt` once it's `printer`'s turn, retrying transient failures
+pub async fn spool<F, Fut>(printer: &str, mut attempt: F) -> Result<String, LabelgoodError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<String, LabelgoodError>>,
+{
+    let queue = queue();
+    let settings = queue.settings.lock().unwrap().clone();
+    let delay = Duration::from_millis(settings.printer_delay_ms.get(printer).copied().unwrap_or(settings.delay_ms));
+    let retry_delay = Duration::from_millis(settings.retry_delay_ms);
+
+    let id = queue.next_entry.fetch_add(1, Ordering::Relaxed);
+    queue.entries.lock().unwrap().insert(
+        id,
+        QueuedJob {
+            id,
+            job: progress::current_job(),
+            printer: printer.to_string(),
+            state: QueueState::Waiting,
+            priority: PRIORITY.try_with(|priority| *priority).unwrap_or_default(),
+            attempt: 0,
+            queued_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            position: id as i64,
+        },
+    );
+    let entry = Entry(id);
+
+    progress::report(SPOOL_PROGRESS_EVENT, "queued", 0, 1)?;
+    let last_finished = loop {
+        // Registered before checking, so a change in between isn't missed
+        let changed = queue.changed.notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+        if let Some(last_finished) = take_turn(id, printer) {
+            break last_finished;
+        }
+        tokio::select! {
+            _ = changed => {}
+            cancelled = progress::cancelled() => return Err(cancelled),
+        }
+    };
+    let _turn = Turn(printer);
+    if let Some(finished) = last_finished {
+        sleep_unless_cancelled(delay.saturating_sub(finished.elapsed())).await?;
+    }
+
+    let mut tries = 0;
+    let result = loop {
+        tries += 1;
+        entry.set(QueueState::Spooling, tries);
+        match attempt().await {
+            Err(e) if is_transient(&e) && tries <= settings.retries => {
+                warn!("Printing to {} failed, retrying: {}", printer, e);
+                entry.set(QueueState::Retrying, tries);
+                sleep_unless_cancelled(retry_delay).await?;
+            }
+            result => break result,
+        }
+    };
+    result
+}
+
+// Apply `change` to waiting entry `id` and wake the waiting jobs
+fn reorder(id: u64, change: impl FnOnce(&mut QueuedJob, &BTreeMap<u64, QueuedJob>)) -> Result<(), String> {
+    let queue = queue();
+    let mut entries = queue.entries.lock().unwrap();
+    let others = entries.clone();
+    let entry = entries
+        .get_mut(&id)
+        .ok_or_else(|| format!("Queue entry {} isn't waiting", id))?;
+    if entry.state != QueueState::Waiting {
+        return Err(format!("Queue entry {} is already being sent to {}", id, entry.printer));
+    }
+    change(entry, &others);
+    queue.changed.notify_waiters();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_print_queue() -> Vec<QueuedJob> {
+    snapshot()
+}
+
+#[tauri::command]
+pub fn set_queue_priority(id: u64, priority: Priority) -> Result<(), String> {
+    reorder(id, |entry, _| entry.priority = priority)
+}
+
+/// Send a waiting job next on its printer, e.g. an urgent label queued
+/// behind a long batch, by giving it the highest priority of the jobs
+/// waiting there and putting it first among them
+#[tauri::command]
+pub fn move_to_front_of_queue(id: u64) -> Result<(), String> {
+    reorder(id, |entry, others| {
+        let waiting = others
+            .values()
+            .filter(|other| other.printer == entry.printer && other.state == QueueState::Waiting);
+        let first = waiting.clone().map(|other| other.position).min().unwrap_or(entry.position);
+        entry.priority = waiting.map(|other| other.priority).max().unwrap_or(entry.priority);
+        entry.position = first - 1;
+    })
+}
@@ -0,0 +1,174 @@
+// Daily label quotas for kiosk mode, for sites where label stock is
+// expensive. A quota caps the labels one operator, or all of them together,
+// print per day, of one template or of all of them. Usage is counted from
+// the audit log, which can't be cleared from the app, plus the jobs admitted
+// but not logged yet, and days start at local midnight. Jobs that would go
+// over a quota fail before anything is printed; quotas don't apply while
+// the kiosk is unlocked.
+use crate::audit::AuditLog;
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::kiosk;
+use crate::operators;
+use crate::settings::SettingsStore;
+use chrono::{Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::State;
+
+/// Held jobs released once their printer is back were admitted before
+const EXEMPT_SOURCES: &[&str] = &["offline"];
+
+// Jobs admitted and not in the audit log yet, so jobs running at the same
+// time can't go over a quota together
+static ADMITTED: Mutex<Vec<Admitted>> = Mutex::new(Vec::new());
+static NEXT_ADMISSION: AtomicU64 = AtomicU64::new(1);
+
+struct Admitted {
+    id: u64,
+    operator: Option<String>,
+    template: Option<String>,
+    labels: usize,
+}
+
+/// A job let through by `admit`, counted against quotas until dropped,
+/// which the job does once it's in the audit log
+pub struct Admission(Option<u64>);
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Some(id) = self.0 {
+            ADMITTED.lock().unwrap().retain(|admitted| admitted.id != id);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrintQuota {
+    /// Operator name as set in the settings; every operator, counted
+    /// together, when absent
+    #[serde(default)]
+    pub operator: Option<String>,
+    /// Gallery template id or label file path; labels of any template when
+    /// absent
+    #[serde(default)]
+    pub template: Option<String>,
+    pub labels_per_day: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaUsage {
+    pub template: Option<String>,
+    pub labels_per_day: u32,
+    /// Printed today
+    pub used: usize,
+    pub remaining: usize,
+}
+
+// Start of today, as the audit log writes times
+fn today() -> String {
+    let midnight = Local::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map(|midnight| midnight.with_timezone(&Utc))
+        // A midnight skipped by a DST change; the day is close enough
+        .unwrap_or_else(|| Utc::now() - chrono::Duration::days(1));
+    midnight.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Quotas that `operator` prints `template` under
+fn applicable<'a>(
+    quotas: &'a [PrintQuota],
+    operator: Option<&'a str>,
+    template: Option<&'a str>,
+) -> impl Iterator<Item = &'a PrintQuota> {
+    quotas.iter().filter(move |quota| {
+        quota.operator.as_deref().is_none_or(|o| Some(o) == operator)
+            && quota.template.as_deref().is_none_or(|t| Some(t) == template)
+    })
+}
+
+// Today's usage of `quota`, including jobs admitted but not logged yet
+fn usage(audit: &AuditLog, quota: &PrintQuota, admitted: &[Admitted], since: &str) -> Result<QuotaUsage, String> {
+    let (operator, template) = (quota.operator.as_deref(), quota.template.as_deref());
+    let running: usize = admitted
+        .iter()
+        .filter(|job| operator.is_none_or(|o| job.operator.as_deref() == Some(o)))
+        .filter(|job| template.is_none_or(|t| job.template.as_deref() == Some(t)))
+        .map(|job| job.labels)
+        .sum();
+    let used = audit.labels_printed(operator, template, since)? + running;
+    Ok(QuotaUsage {
+        template: quota.template.clone(),
+        labels_per_day: quota.labels_per_day,
+        used,
+        remaining: (quota.labels_per_day as usize).saturating_sub(used),
+    })
+}
+
+/// Admit a job printing `labels` of `template` from `source` for
+/// `operator`, or fail with `QuotaExceeded` when it would go over one of
+/// the quotas that apply. Checking and admitting are one step, so jobs
+/// started together are counted against each other.
+pub fn admit(
+    audit: &AuditLog,
+    quotas: &[PrintQuota],
+    source: &str,
+    operator: Option<&str>,
+    template: Option<&str>,
+    labels: usize,
+) -> Result<Admission, LabelgoodError> {
+    if !kiosk::locked() || EXEMPT_SOURCES.contains(&source) {
+        return Ok(Admission(None));
+    }
+    let mut admitted = ADMITTED.lock().unwrap();
+    let since = today();
+    for quota in applicable(quotas, operator, template) {
+        let today = usage(audit, quota, &admitted, &since)?;
+        if labels > today.remaining {
+            return Err(LabelgoodError::QuotaExceeded {
+                operator: operator.map(str::to_string),
+                template: quota.template.clone(),
+                limit: quota.labels_per_day,
+                used: today.used,
+                message: i18n::t(
+                    "quota-exceeded",
+                    &[
+                        ("limit", quota.labels_per_day.into()),
+                        ("remaining", today.remaining.into()),
+                        ("labels", labels.into()),
+                    ],
+                ),
+            });
+        }
+    }
+    let id = NEXT_ADMISSION.fetch_add(1, Ordering::Relaxed);
+    admitted.push(Admitted {
+        id,
+        operator: operator.map(str::to_string),
+        template: template.map(str::to_string),
+        labels,
+    });
+    Ok(Admission(Some(id)))
+}
+
+/// Today's usage of the quotas that apply to the current operator
+#[tauri::command]
+pub fn get_quota_usage(
+    settings: State<'_, SettingsStore>,
+    audit: State<'_, AuditLog>,
+) -> Result<Vec<QuotaUsage>, String> {
+    let operator = operators::current();
+    let operator = operator.as_deref();
+    let since = today();
+    let admitted = ADMITTED.lock().unwrap();
+    settings
+        .get()
+        .quotas
+        .iter()
+        .filter(|quota| quota.operator.as_deref().is_none_or(|o| Some(o) == operator))
+        .map(|quota| usage(&audit, quota, &admitted, &since))
+        .collect()
+}
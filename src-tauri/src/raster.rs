@@ -0,0 +1,92 @@
+// What a thermal print head lays down for a page: the rendered label scaled
+// to the printer's resolution and dithered to black and white, the way CUPS
+// raster drivers for label printers do. Fine text, thin lines and greys can
+// look very different from the canvas once every dot is either on or off.
+use crate::printing::RENDER_DPI;
+use crate::tape::{LoadedTape, TapeWarning};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::imageops::{self, FilterType};
+use image::{GrayImage, ImageFormat, Luma};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// A page as the printer would print it
+#[derive(Debug, Clone, Serialize)]
+pub struct PrintRaster {
+    /// 1-bit PNG data URL, or in the ink and tape colours of the loaded
+    /// cassette, or in colour for colour printers
+    pub image: String,
+    pub width_px: u32,
+    pub height_px: u32,
+    /// Dots per inch of the image, to show it at the label's physical size
+    pub dpi: f64,
+    /// Cassette the label is shown on, when the printer reported one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tape: Option<LoadedTape>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<TapeWarning>,
+}
+
+/// `page`, a PNG rendered at `RENDER_DPI`, scaled to `dpi` and dithered to
+/// black and white
+pub fn print_raster(page: &[u8], dpi: f64) -> Result<GrayImage, String> {
+    let page = image::load_from_memory_with_format(page, ImageFormat::Png)
+        .map_err(|e| format!("Failed to decode rendered page: {}", e))?
+        .to_luma8();
+    let scale = dpi / RENDER_DPI;
+    let width = ((page.width() as f64 * scale).round() as u32).max(1);
+    let height = ((page.height() as f64 * scale).round() as u32).max(1);
+    let scaled = if (width, height) == page.dimensions() {
+        page
+    } else {
+        imageops::resize(&page, width, height, FilterType::Triangle)
+    };
+    Ok(dither(&scaled))
+}
+
+// Floyd-Steinberg error diffusion, so greys become dot patterns of the same
+// average darkness instead of solid black or nothing
+fn dither(image: &GrayImage) -> GrayImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut levels: Vec<f32> = image.pixels().map(|Luma([luma])| *luma as f32).collect();
+    let mut output = GrayImage::new(image.width(), image.height());
+    for y in 0..height {
+        for x in 0..width {
+            let old = levels[y * width + x];
+            let new = if old < 128.0 { 0.0 } else { 255.0 };
+            output.put_pixel(x as u32, y as u32, Luma([new as u8]));
+            let error = old - new;
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    levels[(y + dy) * width + nx as usize] += error * weight;
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+    output
+}
+
+pub fn encode_png(image: &GrayImage) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode print raster: {}", e))?;
+    Ok(png)
+}
+
+pub fn to_preview(image: &GrayImage, dpi: f64) -> Result<PrintRaster, String> {
+    Ok(PrintRaster {
+        image: format!("data:image/png;base64,{}", BASE64.encode(encode_png(image)?)),
+        width_px: image.width(),
+        height_px: image.height(),
+        dpi,
+        tape: None,
+        warnings: Vec::new(),
+    })
+}
@@ -0,0 +1,569 @@
+// Backend label renderer: composes a document as SVG and rasterizes it with
+// resvg, using the same font database as the font picker
+use crate::adjustments;
+use crate::barcodes::{self, Symbol};
+use crate::color;
+use crate::document::{
+    BarcodeElement, Document, Element, ElementKind, ShapeKind, TextAlign, TextElement, TextLayout, TextRun,
+};
+use crate::fonts;
+use crate::formats::{escape, MM_PER_PT};
+use crate::nutrition;
+use crate::operators;
+use crate::watermark;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use resvg::{tiny_skia, usvg};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// Approximate ascent and line height as fractions of the font size, matching
+/// the browser's defaults closely enough for label text
+pub const ASCENT: f64 = 0.8;
+pub const LINE_HEIGHT: f64 = 1.2;
+
+/// SVG for a document, in millimetre user units, with barcodes on the dot
+/// grid of a printer at `printer_dpi` when given. Fails if a barcode can't be
+/// encoded, rather than printing a label without it.
+pub fn to_svg(document: &Document, printer_dpi: Option<f64>) -> Result<String, String> {
+    let (w, h) = (document.width_mm, document.height_mm);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#ffffff\"/>\n"
+    );
+    for element in document.stacked() {
+        write_element(&mut svg, document, element, printer_dpi)?;
+    }
+    if let Some(overlays) = overlays(w, h) {
+        svg.push_str(&overlays);
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// What's drawn over every label, in millimetre user units: the watermark
+/// and the operator footer. None when neither is on.
+pub fn overlays(width_mm: f64, height_mm: f64) -> Option<String> {
+    let overlays: String = [
+        watermark::overlay_svg(width_mm, height_mm),
+        operators::footer_svg(width_mm, height_mm),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    (!overlays.is_empty()).then_some(overlays)
+}
+
+/// Draw `overlays` over a label image made elsewhere, e.g. by the frontend,
+/// at the image's own resolution
+pub fn stamp_png(
+    png: &[u8],
+    overlays: &str,
+    fonts: Arc<fontdb::Database>,
+    width_mm: f64,
+    height_mm: f64,
+) -> Result<Vec<u8>, String> {
+    let (width_px, _) = image::ImageReader::new(std::io::Cursor::new(png))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read label image: {}", e))?
+        .into_dimensions()
+        .map_err(|e| format!("Failed to read label image: {}", e))?;
+    let dpi = width_px as f64 / width_mm * 25.4;
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <image width=\"{w}\" height=\"{h}\" preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>\n\
+         {}</svg>\n",
+        BASE64.encode(png),
+        overlays,
+        w = width_mm,
+        h = height_mm
+    );
+    rasterize(&svg, fonts, width_mm, height_mm, dpi)
+}
+
+fn write_element(
+    svg: &mut String,
+    document: &Document,
+    element: &Element,
+    printer_dpi: Option<f64>,
+) -> Result<(), String> {
+    let (w, h) = (element.width_mm, element.height_mm);
+    let _ = writeln!(
+        svg,
+        "<g transform=\"translate({} {}) rotate({})\">",
+        element.x_mm, element.y_mm, element.rotation_deg
+    );
+
+    match &element.kind {
+        ElementKind::Text(text) => write_text(svg, text, element.id, w, h),
+        ElementKind::Barcode(barcode) => {
+            let symbol = barcodes::encode(&barcode.symbology, &barcode.data, &barcode.options)?;
+            svg.push_str(&barcode_svg(&symbol, w, h, barcode, printer_dpi));
+        }
+        ElementKind::Image(image) => {
+            if let Some(asset) = document.assets.get(&image.asset_id) {
+                let asset = color::in_srgb(asset)?;
+                let asset = asset.as_ref();
+                let adjusted = match &image.adjustments {
+                    Some(adjustments) => Some(adjustments::adjusted(asset, adjustments)?),
+                    None => None,
+                };
+                let (mime_type, data) = adjusted
+                    .as_ref()
+                    .map_or((&asset.mime_type, &asset.data), |(mime_type, data)| (mime_type, data));
+                let _ = writeln!(
+                    svg,
+                    "<image width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\" href=\"data:{};base64,{}\"/>",
+                    w, h, mime_type, data
+                );
+            }
+        }
+        ElementKind::Shape(shape) => {
+            let stroke = format!(
+                "stroke=\"{}\" stroke-width=\"{}\" fill=\"{}\"",
+                escape(&shape.stroke_color),
+                shape.stroke_width_mm,
+                escape(shape.fill_color.as_deref().unwrap_or("none"))
+            );
+            let _ = match shape.shape {
+                ShapeKind::Rectangle => writeln!(svg, "<rect width=\"{}\" height=\"{}\" {}/>", w, h, stroke),
+                ShapeKind::Ellipse => writeln!(
+                    svg,
+                    "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" {}/>",
+                    w / 2.0,
+                    h / 2.0,
+                    w / 2.0,
+                    h / 2.0,
+                    stroke
+                ),
+                ShapeKind::Line => writeln!(svg, "<line x2=\"{}\" y2=\"{}\" {}/>", w, h, stroke),
+            };
+        }
+        ElementKind::Nutrition(nutrition) => svg.push_str(&nutrition::panel_svg(nutrition, w, h)?),
+    }
+
+    svg.push_str("</g>\n");
+    Ok(())
+}
+
+// A piece of a text line in one style; `run` is None for the element's own
+// style, which the surrounding <text> already carries
+struct Span<'a> {
+    text: &'a str,
+    run: Option<&'a TextRun>,
+    /// Millimetres
+    size: f64,
+}
+
+fn span<'a>(text: &'a TextElement, run: Option<&'a TextRun>, content: &'a str) -> Span<'a> {
+    Span {
+        text: content,
+        run,
+        size: run.and_then(|r| r.font_size_pt).unwrap_or(text.font_size_pt) * MM_PER_PT,
+    }
+}
+
+// The element's lines, each split into styled spans
+fn styled_lines(text: &TextElement) -> Vec<Vec<Span<'_>>> {
+    if text.runs.is_empty() {
+        return text.text.lines().map(|line| vec![span(text, None, line)]).collect();
+    }
+    let mut lines = vec![Vec::new()];
+    for run in &text.runs {
+        for (i, part) in run.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            let part = part.strip_suffix('\r').unwrap_or(part);
+            if !part.is_empty() {
+                lines.last_mut().unwrap().push(span(text, Some(run), part));
+            }
+        }
+    }
+    lines
+}
+
+fn style_attrs(family: &str, size: f64, bold: bool, italic: bool, color: &str) -> String {
+    format!(
+        "font-family=\"'{}', sans-serif\" font-size=\"{}\" font-weight=\"{}\" font-style=\"{}\" fill=\"{}\"",
+        escape(family),
+        size,
+        if bold { "bold" } else { "normal" },
+        if italic { "italic" } else { "normal" },
+        escape(color)
+    )
+}
+
+// `content` in the span's style; extra attributes like a position go first
+fn write_span(svg: &mut String, text: &TextElement, span: &Span, position: &str, content: &str) {
+    let style = span.run.map(|run| {
+        style_attrs(
+            run.font_family.as_deref().unwrap_or(&text.font_family),
+            span.size,
+            run.bold.unwrap_or(text.bold),
+            run.italic.unwrap_or(text.italic),
+            run.color.as_deref().unwrap_or(&text.color),
+        )
+    });
+    if position.is_empty() && style.is_none() {
+        svg.push_str(&escape(content));
+        return;
+    }
+    let attrs: Vec<String> = [Some(position.to_string()), style]
+        .into_iter()
+        .flatten()
+        .filter(|attrs| !attrs.is_empty())
+        .collect();
+    let _ = write!(svg, "<tspan {}>{}</tspan>", attrs.join(" "), escape(content));
+}
+
+// Height of a line: its largest font, or the element's when it's empty
+fn line_size(text: &TextElement, line: &[Span]) -> f64 {
+    line.iter()
+        .map(|span| span.size)
+        .reduce(f64::max)
+        .unwrap_or(text.font_size_pt * MM_PER_PT)
+}
+
+fn write_text(svg: &mut String, text: &TextElement, id: u32, w: f64, h: f64) {
+    let size = text.font_size_pt * MM_PER_PT;
+    let lines = styled_lines(text);
+    let anchor = match (text.layout, text.align) {
+        (TextLayout::Vertical, _) | (_, TextAlign::Center) => "middle",
+        (_, TextAlign::Left) => "start",
+        (_, TextAlign::Right) => "end",
+    };
+    if let TextLayout::Arc { radius_mm, inside } = text.layout {
+        let r = radius_mm.max(0.1);
+        let line_size = lines.iter().map(|line| line_size(text, line)).reduce(f64::max).unwrap_or(size);
+        // Baseline along the top of the circle, or along the bottom with
+        // room for descenders
+        let (cy, sweep) = if inside {
+            (h - line_size * (LINE_HEIGHT - ASCENT) - r, 0)
+        } else {
+            (line_size * ASCENT + r, 1)
+        };
+        let _ = writeln!(
+            svg,
+            "<defs><path id=\"arc-{}\" d=\"M {} {} A {} {} 0 0 {} {} {}\"/></defs>",
+            id,
+            w / 2.0 - r,
+            cy,
+            r,
+            r,
+            sweep,
+            w / 2.0 + r,
+            cy
+        );
+    }
+    let _ = write!(
+        svg,
+        "<text {} text-anchor=\"{}\">",
+        style_attrs(&text.font_family, size, text.bold, text.italic, &text.color),
+        anchor
+    );
+    match text.layout {
+        TextLayout::Horizontal => {
+            let x = match text.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => w / 2.0,
+                TextAlign::Right => w,
+            };
+            let mut top = 0.0;
+            for line in &lines {
+                let line_size = line_size(text, line);
+                let _ = write!(svg, "<tspan x=\"{}\" y=\"{}\">", x, top + line_size * ASCENT);
+                let rtl = is_rtl(&line.iter().map(|span| span.text).collect::<String>());
+                if rtl {
+                    svg.push('\u{202B}');
+                }
+                for span in line {
+                    write_span(svg, text, span, "", span.text);
+                }
+                if rtl {
+                    svg.push('\u{202C}');
+                }
+                svg.push_str("</tspan>");
+                top += line_size * LINE_HEIGHT;
+            }
+        }
+        TextLayout::Vertical => {
+            let mut right = w;
+            for line in &lines {
+                let column = line_size(text, line) * LINE_HEIGHT;
+                let x = right - column / 2.0;
+                let height: f64 = line
+                    .iter()
+                    .map(|span| span.text.chars().count() as f64 * span.size * LINE_HEIGHT)
+                    .sum();
+                let mut top = match text.align {
+                    TextAlign::Left => 0.0,
+                    TextAlign::Center => (h - height) / 2.0,
+                    TextAlign::Right => h - height,
+                };
+                for span in line {
+                    for c in span.text.chars() {
+                        if !c.is_whitespace() {
+                            let position = format!("x=\"{}\" y=\"{}\"", x, top + span.size * ASCENT);
+                            write_span(svg, text, span, &position, c.encode_utf8(&mut [0; 4]));
+                        }
+                        top += span.size * LINE_HEIGHT;
+                    }
+                }
+                right -= column;
+            }
+        }
+        TextLayout::Arc { .. } => {
+            let offset = match text.align {
+                TextAlign::Left => "0%",
+                TextAlign::Center => "50%",
+                TextAlign::Right => "100%",
+            };
+            let _ = write!(svg, "<textPath href=\"#arc-{}\" startOffset=\"{}\">", id, offset);
+            // One line along the arc
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    svg.push(' ');
+                }
+                for span in line {
+                    write_span(svg, text, span, "", span.text);
+                }
+            }
+            svg.push_str("</textPath>");
+        }
+    }
+    svg.push_str("</text>\n");
+}
+
+/// Whether a line reads right to left: its first letter with a direction is
+/// Hebrew, Arabic or another right-to-left script
+pub fn is_rtl(line: &str) -> bool {
+    line.chars()
+        .map(bidi_class)
+        .find(|class| matches!(class, BidiClass::L | BidiClass::R | BidiClass::AL))
+        .is_some_and(|class| class != BidiClass::L)
+}
+
+/// `line` ready for an SVG text chunk. resvg lays chunks out as left-to-right
+/// paragraphs, which puts the punctuation and embedded Latin or numbers of a
+/// right-to-left line on the wrong side; an embedding (RLE ... PDF) makes
+/// the line a right-to-left paragraph. Alignment stays as designed.
+pub fn bidi_line(line: &str) -> String {
+    if is_rtl(line) {
+        format!("\u{202B}{}\u{202C}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Word-wrapped lines of `text` at most `max_chars` wide
+pub fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// SVG elements drawing `symbol` into a `width` x `height` box at the origin.
+/// Linear codes stretch to the box with their text underneath; 2D codes keep
+/// square modules and are centred. A fixed module size overrides stretching.
+/// With `printer_dpi`, modules are a whole number of that printer's dots.
+pub fn barcode_svg(
+    symbol: &Symbol,
+    width: f64,
+    height: f64,
+    barcode: &BarcodeElement,
+    printer_dpi: Option<f64>,
+) -> String {
+    let color = escape(&barcode.color);
+    let fixed_module = barcode.options.module_size_mm.filter(|m| *m > 0.0);
+    if !symbol.is_linear() {
+        let module = match fixed_module {
+            Some(module) => barcodes::snap_module(module, printer_dpi, false),
+            None => {
+                let fit = (width / symbol.width as f64).min(height / symbol.height as f64);
+                barcodes::snap_module(fit, printer_dpi, true)
+            }
+        };
+        let (w, h) = (module * symbol.width as f64, module * symbol.height as f64);
+        return format!(
+            "<path transform=\"translate({} {})\" fill=\"{}\" d=\"{}\"/>\n",
+            (width - w) / 2.0,
+            (height - h) / 2.0,
+            color,
+            symbol.path_data(w, h)
+        );
+    }
+
+    let text_size = if barcode.show_text { (height * 0.2).min(3.0) } else { 0.0 };
+    let bar_height = height - text_size * LINE_HEIGHT;
+    let module = match fixed_module {
+        Some(module) => barcodes::snap_module(module, printer_dpi, false),
+        None => barcodes::snap_module(width / symbol.width as f64, printer_dpi, true),
+    };
+    let bar_width = module * symbol.width as f64;
+    let mut out = format!(
+        "<path transform=\"translate({} 0)\" fill=\"{}\" d=\"{}\"/>\n",
+        (width - bar_width) / 2.0,
+        color,
+        symbol.path_data(bar_width, bar_height)
+    );
+    if barcode.show_text {
+        let _ = writeln!(
+            out,
+            "<text font-family=\"monospace\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" x=\"{}\" y=\"{}\">{}</text>",
+            text_size,
+            color,
+            width / 2.0,
+            height - text_size * (LINE_HEIGHT - ASCENT),
+            escape(&symbol.text)
+        );
+    }
+    out
+}
+
+/// Rasterize a document to PNG at `dpi`
+pub fn render_png(document: &Document, fonts: Arc<fontdb::Database>, dpi: f64) -> Result<Vec<u8>, String> {
+    render_png_for(document, fonts, dpi, None)
+}
+
+/// Rasterize a document to PNG at `dpi` for a printer at `printer_dpi`, with
+/// barcodes on its dot grid
+pub fn render_png_for(
+    document: &Document,
+    fonts: Arc<fontdb::Database>,
+    dpi: f64,
+    printer_dpi: Option<f64>,
+) -> Result<Vec<u8>, String> {
+    rasterize(&to_svg(document, printer_dpi)?, fonts, document.width_mm, document.height_mm, dpi)
+}
+
+/// Total size of cached PNGs before the oldest are dropped
+const CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+type CacheKey = [u8; 32];
+
+// Rendered PNGs by a hash of everything that goes into them, so previews
+// that are shown again, batch labels with identical content and repeated
+// prints skip rasterizing
+#[derive(Default)]
+struct RenderCache {
+    entries: HashMap<CacheKey, Vec<u8>>,
+    /// Oldest first
+    order: VecDeque<CacheKey>,
+    bytes: usize,
+}
+
+impl RenderCache {
+    fn get(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: CacheKey, png: Vec<u8>) {
+        if png.len() > CACHE_BYTES || self.entries.contains_key(&key) {
+            return;
+        }
+        self.bytes += png.len();
+        self.entries.insert(key, png);
+        self.order.push_back(key);
+        while self.bytes > CACHE_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.len();
+            }
+        }
+    }
+}
+
+static CACHE: OnceLock<Mutex<RenderCache>> = OnceLock::new();
+
+fn cache() -> &'static Mutex<RenderCache> {
+    CACHE.get_or_init(Mutex::default)
+}
+
+// The SVG fixes the content; the face count changes when a font is added,
+// and the emoji setting picks other fonts, either of which can change how
+// the same SVG renders
+fn cache_key(svg: &str, fonts: &fontdb::Database, width_mm: f64, height_mm: f64, dpi: f64) -> CacheKey {
+    let mut hasher = Sha256::new();
+    hasher.update(svg.as_bytes());
+    for value in [width_mm, height_mm, dpi] {
+        hasher.update(value.to_le_bytes());
+    }
+    hasher.update(fonts.len().to_le_bytes());
+    hasher.update([u8::from(fonts::monochrome_emoji())]);
+    hasher.finalize().into()
+}
+
+/// Rasterize SVG with a `width_mm` x `height_mm` viewport to PNG at `dpi`.
+/// Identical input is only rendered once while it stays in the cache.
+pub fn rasterize(
+    svg: &str,
+    fonts: Arc<fontdb::Database>,
+    width_mm: f64,
+    height_mm: f64,
+    dpi: f64,
+) -> Result<Vec<u8>, String> {
+    let key = cache_key(svg, &fonts, width_mm, height_mm, dpi);
+    if let Some(png) = cache().lock().unwrap().get(&key) {
+        return Ok(png);
+    }
+    let png = render_svg(svg, fonts, width_mm, height_mm, dpi)?;
+    cache().lock().unwrap().insert(key, png.clone());
+    Ok(png)
+}
+
+fn render_svg(
+    svg: &str,
+    fonts: Arc<fontdb::Database>,
+    width_mm: f64,
+    height_mm: f64,
+    dpi: f64,
+) -> Result<Vec<u8>, String> {
+    // resvg shapes text with rustybuzz; characters the chosen font lacks
+    // come from a font for their script first, then any font that has them
+    let default_fallback = usvg::FontResolver::default_fallback_selector();
+    let options = usvg::Options {
+        fontdb: fonts,
+        font_resolver: usvg::FontResolver {
+            select_fallback: Box::new(move |c, exclude, db| {
+                fonts::script_fallback(db, c, exclude).or_else(|| default_fallback(c, exclude, db))
+            }),
+            ..usvg::FontResolver::default()
+        },
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| format!("Failed to render label: {}", e))?;
+
+    let width_px = (width_mm / 25.4 * dpi).round().max(1.0) as u32;
+    let height_px = (height_mm / 25.4 * dpi).round().max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width_px, height_px)
+        .ok_or_else(|| format!("Failed to render label: invalid size {}x{}px", width_px, height_px))?;
+
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        width_px as f32 / size.width(),
+        height_px as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    pixmap
+        .encode_png()
+        .map_err(|e| format!("Failed to encode label PNG: {}", e))
+}
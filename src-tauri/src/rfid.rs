@@ -0,0 +1,79 @@
+// RFID encoding for Zebra RFID printers: the EPC written to the inlay of
+// each label in the same job that prints it. The EPC is hex and can hold
+// placeholders, e.g. "3034257BF400B7{counter:epc}", filled in per label like
+// any other field. A tag that fails to write is voided and the next label
+// tried, up to `retries` times, before the printer gives up on it as
+// `on_failure` says.
+use serde::{Deserialize, Serialize};
+
+/// Longest EPC of Gen 2 tags, 496 bits, in hex digits
+const MAX_EPC_DIGITS: usize = 124;
+
+/// Command starting an RFID write of hex data to the EPC bank, with the
+/// PC bits adjusted to its length
+pub const ZPL_WRITE_EPC: &str = "^RFW,H,,,A";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RfidTag {
+    /// Hex digits in whole 16-bit words; placeholders allowed
+    pub epc: String,
+    pub on_failure: RfidFailure,
+    /// Labels tried for each one before giving up, 1 to 10
+    pub retries: u8,
+}
+
+impl Default for RfidTag {
+    fn default() -> Self {
+        RfidTag {
+            epc: String::new(),
+            on_failure: RfidFailure::Void,
+            retries: 3,
+        }
+    }
+}
+
+/// What the printer does once a tag failed on every try
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RfidFailure {
+    /// Leave the voided labels and carry on with the next one
+    #[default]
+    Void,
+    /// Pause until someone resumes the printer
+    Pause,
+    /// Stop with an error
+    Error,
+}
+
+impl RfidTag {
+    /// ^RS setting up Gen 2 encoding with this tag's retries and failure
+    /// handling
+    pub fn zpl_setup(&self) -> String {
+        let handling = match self.on_failure {
+            RfidFailure::Void => 'N',
+            RfidFailure::Pause => 'P',
+            RfidFailure::Error => 'E',
+        };
+        format!("^RS8,,,{},{}", self.retries.clamp(1, 10), handling)
+    }
+}
+
+/// Check that `epc`, placeholders filled in, can be written to a tag
+pub fn check_epc(epc: &str) -> Result<(), String> {
+    if epc.is_empty() {
+        return Err("RFID EPC is empty".to_string());
+    }
+    if !epc.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("RFID EPC \"{}\" isn't hexadecimal", epc));
+    }
+    if epc.len() % 4 != 0 || epc.len() > MAX_EPC_DIGITS {
+        return Err(format!(
+            "RFID EPC \"{}\" has {} digits; use a multiple of 4, up to {}",
+            epc,
+            epc.len(),
+            MAX_EPC_DIGITS
+        ));
+    }
+    Ok(())
+}
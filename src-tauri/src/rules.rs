@@ -0,0 +1,83 @@
+// Conditional content rules: per-element visibility and formatting decided
+// by a merge row's values, so one template serves many product variants
+use crate::document::{Condition, ConditionOp, Document, Element, ElementKind, RuleAction};
+use crate::placeholders;
+use std::collections::BTreeMap;
+
+pub fn holds(condition: &Condition, values: &BTreeMap<String, String>) -> bool {
+    let value = values.get(&condition.field).map(|v| v.trim()).unwrap_or_default();
+    let expected = condition.value.trim();
+    let numbers = || Some((parse_number(value)?, parse_number(expected)?));
+    match condition.op {
+        ConditionOp::Equals => value.eq_ignore_ascii_case(expected),
+        ConditionOp::NotEquals => !value.eq_ignore_ascii_case(expected),
+        ConditionOp::Contains => value.to_lowercase().contains(&expected.to_lowercase()),
+        ConditionOp::Empty => value.is_empty(),
+        ConditionOp::NotEmpty => !value.is_empty(),
+        ConditionOp::GreaterThan => numbers().is_some_and(|(a, b)| a > b),
+        ConditionOp::LessThan => numbers().is_some_and(|(a, b)| a < b),
+    }
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.parse().ok().or_else(|| text.replace(',', ".").parse().ok())
+}
+
+/// Fields read by the rules of `document`, so merges load their columns
+pub fn fields(document: &Document) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for rule in document.elements.iter().flat_map(|e| &e.rules) {
+        if !fields.contains(&rule.when.field) {
+            fields.push(rule.when.field.clone());
+        }
+        if let RuleAction::SetText { text } = &rule.then {
+            for name in placeholders::variables(text) {
+                if !fields.contains(&name) {
+                    fields.push(name);
+                }
+            }
+        }
+    }
+    fields
+}
+
+/// Apply every element's rules for one merge row, dropping hidden elements.
+/// Must run before placeholders are filled in, so replaced text gets its
+/// placeholders resolved like any other.
+pub fn apply(document: &mut Document, values: &BTreeMap<String, String>) {
+    document.elements.retain(|element| visible(element, values));
+    for element in &mut document.elements {
+        let rules = std::mem::take(&mut element.rules);
+        for rule in rules.iter().filter(|r| holds(&r.when, values)) {
+            apply_action(element, &rule.then);
+        }
+    }
+}
+
+fn visible(element: &Element, values: &BTreeMap<String, String>) -> bool {
+    let mut shows = element.rules.iter().filter(|r| matches!(r.then, RuleAction::Show)).peekable();
+    let shown = shows.peek().is_none() || shows.any(|r| holds(&r.when, values));
+    let hidden = element
+        .rules
+        .iter()
+        .any(|r| matches!(r.then, RuleAction::Hide) && holds(&r.when, values));
+    shown && !hidden
+}
+
+fn apply_action(element: &mut Element, action: &RuleAction) {
+    match (action, &mut element.kind) {
+        (RuleAction::SetColor { color }, ElementKind::Text(text)) => text.color = color.clone(),
+        (RuleAction::SetColor { color }, ElementKind::Barcode(barcode)) => barcode.color = color.clone(),
+        (RuleAction::SetColor { color }, ElementKind::Shape(shape)) => shape.stroke_color = color.clone(),
+        (RuleAction::SetColor { color }, ElementKind::Nutrition(nutrition)) => nutrition.color = color.clone(),
+        (RuleAction::SetFill { color }, ElementKind::Shape(shape)) => shape.fill_color = Some(color.clone()),
+        (RuleAction::SetText { text: replacement }, ElementKind::Text(text)) => {
+            text.text = replacement.clone();
+            text.runs.clear();
+        }
+        (RuleAction::SetText { text }, ElementKind::Barcode(barcode)) => barcode.data = text.clone(),
+        (RuleAction::SetBold { bold }, ElementKind::Text(text)) => text.bold = *bold,
+        // Visibility is handled before actions; other combinations don't apply
+        _ => {}
+    }
+}
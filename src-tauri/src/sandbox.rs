@@ -0,0 +1,35 @@
+// Flatpak and Snap packages run in a sandbox without lpr, lpstat or USB
+// printer devices. Printers named by socket:// or ipp:// URIs still work
+// there, since they're reached over the network from Rust; everything else
+// goes through the XDG print portal, see `backends::portal`.
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+}
+
+impl Sandbox {
+    pub fn name(self) -> &'static str {
+        match self {
+            Sandbox::Flatpak => "Flatpak",
+            Sandbox::Snap => "Snap",
+        }
+    }
+}
+
+/// The sandbox the app runs in, if any, detected once
+pub fn detect() -> Option<Sandbox> {
+    static DETECTED: OnceLock<Option<Sandbox>> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        if std::env::var_os("FLATPAK_ID").is_some() || Path::new("/.flatpak-info").exists() {
+            Some(Sandbox::Flatpak)
+        } else if std::env::var_os("SNAP").is_some() {
+            Some(Sandbox::Snap)
+        } else {
+            None
+        }
+    })
+}
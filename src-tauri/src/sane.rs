@@ -0,0 +1,97 @@
+// Flatbed and document scanners through SANE's `scanimage`, so artwork or a
+// signature can be scanned straight onto a label. Scans come back as image
+// assets, like a loaded image file.
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::ingest;
+use crate::printing;
+use crate::project::LoadedAsset;
+use crate::tools;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Finding scanners probes the network too
+const LIST_TIMEOUT: Duration = Duration::from_secs(60);
+/// A slow scanner at a high resolution takes minutes
+const SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_DPI: u32 = 300;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Scanner {
+    /// SANE device name, e.g. "epson2:libusb:001:004"
+    pub device: String,
+    /// Vendor and model
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    Color,
+    #[default]
+    Gray,
+    /// Black and white
+    Lineart,
+}
+
+impl ScanMode {
+    fn sane_name(self) -> &'static str {
+        match self {
+            ScanMode::Color => "Color",
+            ScanMode::Gray => "Gray",
+            ScanMode::Lineart => "Lineart",
+        }
+    }
+}
+
+fn missing(e: std::io::Error) -> LabelgoodError {
+    LabelgoodError::DependencyMissing {
+        program: "scanimage".to_string(),
+        message: i18n::t("scanimage-missing", &[("error", e.to_string().into())]),
+    }
+}
+
+fn failed(output: &std::process::Output) -> LabelgoodError {
+    format!("scanimage failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into()
+}
+
+#[tauri::command]
+pub async fn list_scanners() -> Result<Vec<Scanner>, LabelgoodError> {
+    let mut command = tools::command("scanimage");
+    command.arg("--formatted-device-list=%d\t%v %m%n");
+    let output = printing::run_command(command, LIST_TIMEOUT, missing).await?;
+    if !output.status.success() {
+        return Err(failed(&output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(device, name)| Scanner {
+            device: device.to_string(),
+            name: name.trim().to_string(),
+        })
+        .collect())
+}
+
+/// Scan from `device` (the first scanner SANE finds when not given) at
+/// `dpi`, 300 by default, as a PNG image asset
+#[tauri::command]
+pub async fn scan_image(
+    device: Option<String>,
+    dpi: Option<u32>,
+    mode: Option<ScanMode>,
+) -> Result<LoadedAsset, LabelgoodError> {
+    let mut command = tools::command("scanimage");
+    if let Some(device) = device.as_deref().filter(|d| !d.is_empty()) {
+        command.arg(format!("--device-name={}", device));
+    }
+    command
+        .arg("--format=png")
+        .arg(format!("--resolution={}", dpi.unwrap_or(DEFAULT_DPI)))
+        .arg(format!("--mode={}", mode.unwrap_or_default().sane_name()));
+    let output = printing::run_command(command, SCAN_TIMEOUT, missing).await?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(failed(&output));
+    }
+    Ok(ingest::from_raster("scan", output.stdout, None)?.into_asset())
+}
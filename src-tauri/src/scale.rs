@@ -0,0 +1,202 @@
+// Weighing scale over a serial (or USB serial) port, for deli and warehouse
+// weight/price labels. `{weight}` and `{weight_unit}` merge fields are read
+// from the scale once per print job.
+use crate::merge::DataTable;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+pub const WEIGHT_FIELD: &str = "weight";
+pub const WEIGHT_UNIT_FIELD: &str = "weight_unit";
+
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+const STX: u8 = 0x02;
+const ENQ: u8 = 0x05;
+const ACK: u8 = 0x06;
+const DC1: u8 = 0x11;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScaleSettings {
+    /// e.g. "/dev/ttyUSB0" or "COM3"
+    pub port: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    #[serde(default)]
+    pub protocol: ScaleProtocol,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleProtocol {
+    /// Mettler Toledo 8217: send "W", receive STX + weight + CR
+    #[default]
+    Toledo,
+    /// CAS ENQ/DC1 handshake with "ST,GS,  1.234kg" style replies
+    Cas,
+    /// Scale that streams readings on its own; the first complete line is used
+    Continuous,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reading {
+    pub weight: f64,
+    pub unit: String,
+    /// False while the load is still settling
+    pub stable: bool,
+}
+
+#[derive(Default)]
+pub struct Scale {
+    settings: Mutex<Option<ScaleSettings>>,
+}
+
+impl Scale {
+    pub fn configure(&self, settings: Option<ScaleSettings>) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.settings.lock().unwrap().is_some()
+    }
+
+    pub fn read(&self) -> Result<Reading, String> {
+        let settings = self
+            .settings
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or("No scale is configured")?;
+        let mut port = serialport::new(&settings.port, settings.baud_rate)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|e| format!("Failed to open scale on {}: {}", settings.port, e))?;
+        let io_err = |e: std::io::Error| format!("Scale on {} did not respond: {}", settings.port, e);
+
+        match settings.protocol {
+            ScaleProtocol::Toledo => {
+                port.write_all(b"W\r").map_err(io_err)?;
+                parse_toledo(&read_until(&mut *port, b'\r').map_err(io_err)?)
+            }
+            ScaleProtocol::Cas => {
+                port.write_all(&[ENQ]).map_err(io_err)?;
+                let mut ack = [0u8; 1];
+                port.read_exact(&mut ack).map_err(io_err)?;
+                if ack[0] != ACK {
+                    return Err(format!("Scale on {} did not acknowledge", settings.port));
+                }
+                port.write_all(&[DC1]).map_err(io_err)?;
+                parse_line(&read_until(&mut *port, b'\n').map_err(io_err)?)
+            }
+            ScaleProtocol::Continuous => {
+                // Skip the partial line that was in flight when the port opened
+                read_until(&mut *port, b'\n').map_err(io_err)?;
+                parse_line(&read_until(&mut *port, b'\n').map_err(io_err)?)
+            }
+        }
+    }
+
+    /// Add weight columns to `table` when the document uses them, with one
+    /// reading shared by every label of the job
+    pub fn fill_fields(&self, fields: &[String], table: &mut DataTable) -> Result<(), String> {
+        let wanted: Vec<&str> = [WEIGHT_FIELD, WEIGHT_UNIT_FIELD]
+            .into_iter()
+            .filter(|f| fields.iter().any(|field| field == f) && !table.columns.iter().any(|c| c == f))
+            .collect();
+        if wanted.is_empty() || !self.is_configured() {
+            return Ok(());
+        }
+
+        let reading = self.read()?;
+        if !reading.stable {
+            return Err("The scale is not stable yet; wait for the load to settle".to_string());
+        }
+        for field in wanted {
+            let value = if field == WEIGHT_FIELD {
+                reading.weight.to_string()
+            } else {
+                reading.unit.clone()
+            };
+            table.columns.push(field.to_string());
+            for row in &mut table.rows {
+                row.resize(table.columns.len() - 1, String::new());
+                row.push(value.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_until(port: &mut dyn serialport::SerialPort, end: u8) -> std::io::Result<Vec<u8>> {
+    let deadline = Instant::now() + READ_TIMEOUT;
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while Instant::now() < deadline {
+        port.read_exact(&mut byte)?;
+        if byte[0] == end {
+            return Ok(line);
+        }
+        line.push(byte[0]);
+    }
+    Err(std::io::ErrorKind::TimedOut.into())
+}
+
+// STX, then either the weight ("01.234") or '?' and a status byte whose bits
+// report motion (0x01), over capacity (0x02) and under zero (0x04)
+fn parse_toledo(reply: &[u8]) -> Result<Reading, String> {
+    let body = reply.strip_prefix(&[STX]).unwrap_or(reply);
+    if let Some(status) = body.strip_prefix(b"?") {
+        let status = status.first().copied().unwrap_or_default();
+        if status & 0x02 != 0 {
+            return Err("The scale is over capacity".to_string());
+        }
+        if status & 0x04 != 0 {
+            return Err("The scale is under zero".to_string());
+        }
+        return Ok(Reading { weight: 0.0, unit: "kg".to_string(), stable: status & 0x01 == 0 });
+    }
+    let text = String::from_utf8_lossy(body);
+    let weight = text
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unexpected reply from scale: {:?}", text))?;
+    Ok(Reading { weight, unit: "kg".to_string(), stable: true })
+}
+
+// "ST,GS,+  1.234kg": stability (ST stable, US unstable, OL overload),
+// gross/net, then the signed weight and its unit
+fn parse_line(reply: &[u8]) -> Result<Reading, String> {
+    let text = String::from_utf8_lossy(reply).trim().to_string();
+    if text.starts_with("OL") {
+        return Err("The scale is overloaded".to_string());
+    }
+    let stable = !text.starts_with("US");
+    let reading = text.rsplit(',').next().unwrap_or_default().replace(' ', "");
+    let split = reading
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(reading.len());
+    let (number, unit) = reading.split_at(split);
+    let weight = number
+        .parse()
+        .map_err(|_| format!("Unexpected reply from scale: {:?}", text))?;
+    let unit = if unit.is_empty() { "kg" } else { unit };
+    Ok(Reading { weight, unit: unit.to_string(), stable })
+}
+
+#[tauri::command]
+pub async fn read_weight(scale: State<'_, Scale>) -> Result<Reading, String> {
+    scale.read()
+}
+
+/// Serial ports a scale could be connected to
+#[tauri::command]
+pub fn list_serial_ports() -> Result<Vec<String>, String> {
+    let ports = serialport::available_ports().map_err(|e| format!("Failed to list serial ports: {}", e))?;
+    Ok(ports.into_iter().map(|p| p.port_name).collect())
+}
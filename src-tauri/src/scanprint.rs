@@ -0,0 +1,179 @@
+// Scan-to-print relabeling station: a lookup table is registered once, then
+// each code the frontend forwards from a barcode scanner prints the label of
+// the matching record straight away
+use crate::counters::CounterStore;
+use crate::document::Document;
+use crate::events::EventBus;
+use crate::fonts::FontLibrary;
+use crate::history::JobDetails;
+use crate::merge::{self, CheckDigitPolicy, DataSource, DataTable, MergeRequest};
+use crate::operators;
+use crate::scale::Scale;
+use crate::scripting::ScriptHooks;
+use crate::settings::SettingsStore;
+use crate::templates;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanStationConfig {
+    pub source: DataSource,
+    /// Column holding the scanned codes
+    pub key_column: String,
+    /// Gallery template id, or a path to a .lblg or importable label file
+    pub template: String,
+    #[serde(default)]
+    pub mapping: BTreeMap<String, String>,
+    /// Opens the PDF when absent
+    #[serde(default)]
+    pub printer_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanStationSummary {
+    pub records: usize,
+    /// Codes that appear on more than one row; the first row wins
+    pub duplicate_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanResult {
+    pub code: String,
+    /// 1-based row of the matched record; 0 for an operator badge
+    pub row: usize,
+    pub message: String,
+}
+
+struct Station {
+    config: ScanStationConfig,
+    document: Document,
+    table: DataTable,
+    /// Scanned code -> row index
+    index: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct ScanStation {
+    station: Mutex<Option<Station>>,
+}
+
+// Scanners often add whitespace or a trailing CR, and codes are compared
+// without regard to case
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+#[tauri::command]
+pub async fn register_scan_station(
+    settings: State<'_, SettingsStore>,
+    stations: State<'_, ScanStation>,
+    config: ScanStationConfig,
+) -> Result<ScanStationSummary, String> {
+    let shared_dir = settings.get().shared_templates_dir.map(PathBuf::from);
+    let document = templates::resolve_document(&config.template, shared_dir.as_deref())?;
+    let table = config.source.load()?;
+    let key = table
+        .columns
+        .iter()
+        .position(|c| c == &config.key_column)
+        .ok_or_else(|| format!("Data source has no column named \"{}\"", config.key_column))?;
+
+    let mut index = HashMap::new();
+    let mut duplicate_keys = Vec::new();
+    for (i, row) in table.rows.iter().enumerate() {
+        let code = normalize_code(row.get(key).map(String::as_str).unwrap_or_default());
+        if code.is_empty() {
+            continue;
+        }
+        if index.contains_key(&code) {
+            duplicate_keys.push(code);
+        } else {
+            index.insert(code, i);
+        }
+    }
+
+    let summary = ScanStationSummary {
+        records: index.len(),
+        duplicate_keys,
+    };
+    *stations.station.lock().unwrap() = Some(Station {
+        config,
+        document,
+        table,
+        index,
+    });
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn clear_scan_station(stations: State<'_, ScanStation>) {
+    *stations.station.lock().unwrap() = None;
+}
+
+/// Print the label for a scanned code
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_scan(
+    stations: State<'_, ScanStation>,
+    fonts: State<'_, FontLibrary>,
+    counters: State<'_, CounterStore>,
+    events: State<'_, EventBus>,
+    scripts: State<'_, ScriptHooks>,
+    scale: State<'_, Scale>,
+    code: String,
+) -> Result<ScanResult, String> {
+    // Operators can sign in at the station by scanning their badge
+    if let Some(operator) = operators::sign_in_by_badge(&code) {
+        return Ok(ScanResult {
+            code,
+            row: 0,
+            message: format!("Signed in as {}", operator),
+        });
+    }
+    let (request, table, row, template) = {
+        let station = stations.station.lock().unwrap();
+        let station = station.as_ref().ok_or("No scan station is set up")?;
+        let code = normalize_code(&code);
+        let row = *station
+            .index
+            .get(&code)
+            .ok_or_else(|| format!("No record for scanned code \"{}\"", code))?;
+        let request = MergeRequest {
+            document: station.document.clone(),
+            source: DataSource::Records { records: Vec::new() },
+            mapping: station.config.mapping.clone(),
+            range: None,
+            printer_name: station.config.printer_name.clone(),
+            check_digits: CheckDigitPolicy::Reject,
+            numbering: None,
+            answers: BTreeMap::new(),
+        };
+        let table = DataTable {
+            columns: station.table.columns.clone(),
+            rows: vec![station.table.rows[row].clone()],
+        };
+        (request, table, row, station.config.template.clone())
+    };
+
+    let mut table = table;
+    scale.fill_fields(&merge::document_fields(&request.document), &mut table)?;
+    let table = scripts.merge_rows(table)?;
+    let mut documents = merge::merge_documents(&request, &table, &mut |name| counters.next_serial(name))?;
+    scripts.before_render(&mut documents)?;
+
+    let printer_name = request.printer_name.as_deref();
+    let details = JobDetails {
+        template: Some(template),
+        data: merge::selected_records(&request, &table),
+    };
+    let work = merge::print_documents(&documents, fonts.database(), printer_name);
+    let message = events.run_job("scan", documents.len(), printer_name, details, work).await?;
+    Ok(ScanResult {
+        code,
+        row: row + 1,
+        message,
+    })
+}
@@ -0,0 +1,285 @@
+// Scheduled printing: named jobs that print a template, optionally merged with
+// a data source, at a set time or on a recurrence, e.g. the day's
+// production-date labels every morning at 06:00. Schedules are persisted in
+// the app data directory along with when each runs next, so a run missed
+// while the app was closed happens once when it starts again.
+use crate::api;
+use crate::merge::{CheckDigitPolicy, DataSource};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use tracing::{error, info};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(20);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Gallery template id, or a path to a .lblg or importable label file
+    pub template: String,
+    /// One label per row; a single label with only print-time fields, such
+    /// as today's date, when absent
+    #[serde(default)]
+    pub source: Option<DataSource>,
+    /// Document field name -> data source column, as in a merge
+    #[serde(default)]
+    pub mapping: BTreeMap<String, String>,
+    /// Opens the PDF when absent
+    #[serde(default)]
+    pub printer: Option<String>,
+    pub when: Recurrence,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Local time with offset, RFC 3339; none once a one-off schedule has run
+    #[serde(default)]
+    pub next_run: Option<String>,
+    #[serde(default)]
+    pub last_run: Option<String>,
+    /// Status message or error of the last run
+    #[serde(default)]
+    pub last_result: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// When a schedule runs, in local time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Recurrence {
+    /// Once, e.g. "2026-10-16T06:00"
+    Once { at: String },
+    /// Every day at a time, e.g. "06:00"; only on `weekdays` (e.g. "Mon",
+    /// "Fri") when any are given
+    Daily {
+        time: String,
+        #[serde(default)]
+        weekdays: Vec<String>,
+    },
+    /// Every `minutes` minutes
+    Interval { minutes: u32 },
+}
+
+impl Recurrence {
+    /// First time after `after` the schedule runs; None when it never does
+    /// again
+    fn next_after(&self, after: DateTime<Local>) -> Result<Option<DateTime<Local>>, String> {
+        match self {
+            Recurrence::Once { at } => {
+                let at = NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M")
+                    .map_err(|e| format!("Invalid schedule time {}: {}", at, e))?;
+                Ok(Local.from_local_datetime(&at).earliest().filter(|at| *at > after))
+            }
+            Recurrence::Daily { time, weekdays } => {
+                let time = NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|e| format!("Invalid schedule time {}: {}", time, e))?;
+                let weekdays = weekdays
+                    .iter()
+                    .map(|day| day.parse::<Weekday>().map_err(|_| format!("Invalid weekday: {}", day)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // A week ahead always reaches one of the days; a time skipped
+                // by a DST change moves on to the next day
+                let next = (0..=7)
+                    .filter_map(|days| after.date_naive().checked_add_signed(Duration::days(days)))
+                    .filter(|date| weekdays.is_empty() || weekdays.contains(&date.weekday()))
+                    .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+                    .find(|next| *next > after);
+                Ok(next)
+            }
+            Recurrence::Interval { minutes } => {
+                if *minutes == 0 {
+                    return Err("A schedule's interval must be at least a minute".to_string());
+                }
+                Ok(Some(after + Duration::minutes(i64::from(*minutes))))
+            }
+        }
+    }
+}
+
+fn timestamp(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn is_due(schedule: &Schedule, now: DateTime<Local>) -> bool {
+    schedule.enabled
+        && schedule
+            .next_run
+            .as_deref()
+            .and_then(|next| DateTime::parse_from_rfc3339(next).ok())
+            .is_some_and(|next| next <= now)
+}
+
+pub struct ScheduleStore {
+    path: PathBuf,
+    schedules: Mutex<BTreeMap<String, Schedule>>,
+}
+
+impl ScheduleStore {
+    /// Load schedules from `path`; a missing or unreadable file starts with
+    /// none
+    pub fn load(path: PathBuf) -> Self {
+        let schedules = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                error!("Ignoring invalid schedules file {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        ScheduleStore {
+            path,
+            schedules: Mutex::new(schedules),
+        }
+    }
+
+    pub fn list(&self) -> BTreeMap<String, Schedule> {
+        self.schedules.lock().unwrap().clone()
+    }
+
+    /// Add or replace `name`, working out when it next runs. Run history of
+    /// a schedule being replaced is kept.
+    pub fn set(&self, name: &str, mut schedule: Schedule) -> Result<(), String> {
+        let next_run = schedule.when.next_after(Local::now())?;
+        if next_run.is_none() {
+            return Err(format!("Schedule {} would never run: its time has passed", name));
+        }
+        schedule.next_run = next_run.map(timestamp);
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut updated = schedules.clone();
+        if let Some(previous) = updated.get(name) {
+            schedule.last_run = previous.last_run.clone();
+            schedule.last_result = previous.last_result.clone();
+        }
+        updated.insert(name.to_string(), schedule);
+        self.write(&updated)?;
+        *schedules = updated;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut updated = schedules.clone();
+        updated.remove(name);
+        self.write(&updated)?;
+        *schedules = updated;
+        Ok(())
+    }
+
+    /// The schedules due at `now`, moved on to their next run
+    /// before they print so a crash mid-job doesn't print them again
+    fn take_due(&self, now: DateTime<Local>) -> Result<Vec<(String, Schedule)>, String> {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut updated = schedules.clone();
+        let mut due = Vec::new();
+        for (name, schedule) in updated.iter_mut().filter(|(_, schedule)| is_due(schedule, now)) {
+            due.push((name.clone(), schedule.clone()));
+            schedule.last_run = Some(timestamp(now));
+            schedule.next_run = match schedule.when.next_after(now) {
+                Ok(next) => next.map(timestamp),
+                Err(e) => {
+                    error!("Schedule {} can't run again: {}", name, e);
+                    None
+                }
+            };
+        }
+        if !due.is_empty() {
+            self.write(&updated)?;
+            *schedules = updated;
+        }
+        Ok(due)
+    }
+
+    fn record_result(&self, name: &str, result: String) {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut updated = schedules.clone();
+        let Some(schedule) = updated.get_mut(name) else {
+            return;
+        };
+        schedule.last_result = Some(result);
+        match self.write(&updated) {
+            Ok(()) => *schedules = updated,
+            Err(e) => error!("{}", e),
+        }
+    }
+
+    // Write to a temp file and rename so a crash can't leave half a file behind
+    fn write(&self, schedules: &BTreeMap<String, Schedule>) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let json =
+            serde_json::to_string_pretty(schedules).map_err(|e| format!("Failed to serialize schedules: {}", e))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, json).map_err(|e| format!("Failed to write schedules: {}", e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| format!("Failed to save schedules: {}", e))
+    }
+}
+
+async fn run(app: &AppHandle, name: &str, schedule: Schedule) -> Result<String, String> {
+    info!("Running schedule {}", name);
+    let data = schedule.source.unwrap_or(DataSource::Records {
+        records: vec![BTreeMap::new()],
+    });
+    let response = api::print_template(
+        app,
+        "schedule",
+        &schedule.template,
+        data,
+        schedule.mapping,
+        schedule.printer,
+        CheckDigitPolicy::Reject,
+    )
+    .await?;
+    Ok(response.message)
+}
+
+/// Check for due schedules every few seconds and print them, one at a time
+pub fn start_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let store = app.state::<ScheduleStore>();
+        loop {
+            let due = store.take_due(Local::now()).unwrap_or_else(|e| {
+                error!("{}", e);
+                Vec::new()
+            });
+            for (name, schedule) in due {
+                let result = run(&app, &name, schedule).await.unwrap_or_else(|e| {
+                    error!("Schedule {} failed: {}", name, e);
+                    e
+                });
+                store.record_result(&name, result);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_schedules(store: State<'_, ScheduleStore>) -> BTreeMap<String, Schedule> {
+    store.list()
+}
+
+#[tauri::command]
+pub fn set_schedule(store: State<'_, ScheduleStore>, name: String, schedule: Schedule) -> Result<(), String> {
+    store.set(&name, schedule)
+}
+
+#[tauri::command]
+pub fn delete_schedule(store: State<'_, ScheduleStore>, name: String) -> Result<(), String> {
+    store.delete(&name)
+}
+
+/// Print a schedule now, without changing when it next runs
+#[tauri::command]
+pub async fn run_schedule_now(app: AppHandle, store: State<'_, ScheduleStore>, name: String) -> Result<String, String> {
+    let schedule = store
+        .list()
+        .remove(&name)
+        .ok_or_else(|| format!("Unknown schedule: {}", name))?;
+    let result = run(&app, &name, schedule).await;
+    store.record_result(&name, result.clone().unwrap_or_else(|e| e));
+    result
+}
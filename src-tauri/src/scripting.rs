@@ -0,0 +1,189 @@
+// Rhai scripting hooks for power users. The script configured in settings
+// may define any of:
+//
+//   fn merge_row(row) { row.total = parse_float(row.price) * parse_int(row.qty); row }
+//   fn before_render(document) { document }
+//   fn after_print(job) { print(`printed ${job.labels} labels`); }
+//
+// `next_serial(name)` takes the next value of a serial counter. Hooks run
+// with limits on operations, nesting, string and array sizes and time, so a
+// runaway script fails its job instead of hanging it.
+use crate::counters::CounterStore;
+use crate::document::Document;
+use crate::events::{EventBus, LabelEvent};
+use crate::merge::DataTable;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast;
+use tracing::error;
+
+const MAX_OPERATIONS: u64 = 50_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_STRING_SIZE: usize = 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 10_000;
+
+/// Longest a hook call may run
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Operations between checks of the time limit
+const TIMEOUT_CHECK_INTERVAL: u64 = 4096;
+
+thread_local! {
+    // When the hook running on this thread has to stop
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+pub struct ScriptHooks {
+    engine: Engine,
+    script: RwLock<Option<AST>>,
+}
+
+impl ScriptHooks {
+    pub fn new(app: AppHandle) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.on_progress(|operations| {
+            if operations % TIMEOUT_CHECK_INTERVAL != 0 {
+                return None;
+            }
+            let expired = DEADLINE.with(|deadline| deadline.get().is_some_and(|d| Instant::now() > d));
+            expired.then(|| format!("ran for more than {} seconds", HOOK_TIMEOUT.as_secs()).into())
+        });
+        engine.register_fn("next_serial", move |name: &str| -> Result<String, Box<EvalAltResult>> {
+            app.state::<CounterStore>().next_serial(name).map_err(Into::into)
+        });
+        ScriptHooks {
+            engine,
+            script: RwLock::new(None),
+        }
+    }
+
+    /// Compile the hooks script at `path`, replacing the current one. `None`
+    /// removes all hooks.
+    pub fn load(&self, path: Option<&Path>) -> Result<(), String> {
+        let ast = match path {
+            Some(path) => {
+                let source = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read script {}: {}", path.display(), e))?;
+                Some(
+                    self.engine
+                        .compile(source)
+                        .map_err(|e| format!("Script {} has an error: {}", path.display(), e))?,
+                )
+            }
+            None => None,
+        };
+        *self.script.write().unwrap() = ast;
+        Ok(())
+    }
+
+    // Call hook `name` with one argument, or `None` if the script doesn't
+    // define it
+    fn call(&self, name: &str, arg: Dynamic) -> Option<Result<Dynamic, String>> {
+        let script = self.script.read().unwrap();
+        let ast = script.as_ref()?;
+        if !ast.iter_functions().any(|f| f.name == name && f.params.len() == 1) {
+            return None;
+        }
+        DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + HOOK_TIMEOUT)));
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), ast, name, (arg,))
+            .map_err(|e| format!("Script hook {} failed: {}", name, e));
+        DEADLINE.with(|deadline| deadline.set(None));
+        Some(result)
+    }
+
+    /// Pass every row through the `merge_row` hook. Fields the hook adds
+    /// become new columns.
+    pub fn merge_rows(&self, table: DataTable) -> Result<DataTable, String> {
+        let mut records = Vec::with_capacity(table.rows.len());
+        for i in 0..table.rows.len() {
+            let record = table.record(i);
+            let row: Map = record.iter().map(|(k, v)| (k.into(), v.clone().into())).collect();
+            let Some(result) = self.call("merge_row", row.into()) else {
+                return Ok(table);
+            };
+            let row = result?
+                .try_cast::<Map>()
+                .ok_or_else(|| format!("Script hook merge_row must return the row (row {})", i + 1))?;
+            let record: BTreeMap<String, String> =
+                row.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            records.push(record);
+        }
+
+        // Keep the source's column order, with derived fields after it
+        let mut merged = DataTable::from_records(&records);
+        let mut columns = table.columns.clone();
+        columns.extend(merged.columns.iter().filter(|c| !table.columns.contains(c)).cloned());
+        merged.rows = records
+            .iter()
+            .map(|record| columns.iter().map(|c| record.get(c).cloned().unwrap_or_default()).collect())
+            .collect();
+        merged.columns = columns;
+        Ok(merged)
+    }
+
+    /// Let the `before_render` hook adjust each document before it's drawn
+    pub fn before_render(&self, documents: &mut [Document]) -> Result<(), String> {
+        for document in documents.iter_mut() {
+            let value = rhai::serde::to_dynamic(&*document).map_err(|e| e.to_string())?;
+            let Some(result) = self.call("before_render", value) else {
+                return Ok(());
+            };
+            *document = rhai::serde::from_dynamic(&result?)
+                .map_err(|e| format!("Script hook before_render returned an invalid document: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Call the `after_print` hook for every completed job. Runs on its own
+/// thread so a slow script never holds up printing.
+pub fn start_after_print_hook(app: AppHandle) {
+    let mut receiver = app.state::<EventBus>().subscribe();
+    std::thread::spawn(move || {
+        let mut accepted: HashMap<u64, Map> = HashMap::new();
+        loop {
+            let event = match receiver.blocking_recv() {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            match event {
+                LabelEvent::JobAccepted { job, source, labels, printer } => {
+                    let mut info = Map::new();
+                    info.insert("job".into(), (job as i64).into());
+                    info.insert("source".into(), source.into());
+                    info.insert("labels".into(), (labels as i64).into());
+                    info.insert("printer".into(), printer.map_or(Dynamic::UNIT, Dynamic::from));
+                    accepted.insert(job, info);
+                }
+                LabelEvent::JobCompleted { job, message } => {
+                    let Some(mut info) = accepted.remove(&job) else {
+                        continue;
+                    };
+                    info.insert("message".into(), message.into());
+                    if let Some(Err(e)) = app.state::<ScriptHooks>().call("after_print", info.into()) {
+                        error!("{}", e);
+                    }
+                }
+                LabelEvent::JobFailed { job, .. } => {
+                    accepted.remove(&job);
+                }
+                _ => {}
+            }
+        }
+    });
+}
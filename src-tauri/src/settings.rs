@@ -0,0 +1,395 @@
+// Persistent application settings, stored as JSON in the app config directory
+use crate::alignment::{self, PrinterCorrection};
+use crate::api::{ApiServer, ApiSettings};
+use crate::backends::file;
+use crate::backends::registered::{self, NetworkPrinter};
+use crate::barcodes::verify;
+use crate::color::{self, ColorPrinter};
+use crate::costs::MediaCost;
+use crate::duplicates::DuplicateGuard;
+use crate::fonts;
+use crate::groups::{self, PrinterGroup};
+use crate::grpc::{GrpcServer, GrpcSettings};
+use crate::handling::{self, LabelHandling};
+use crate::history::{self, HistorySettings};
+use crate::hotfolder::{HotFolder, HotFolderConfig};
+use crate::i18n;
+use crate::mqtt::{MqttBridge, MqttSettings};
+use crate::operators::{self, OperatorSettings};
+use crate::orders::{OrderSettings, OrderShop};
+use crate::pdfpreview;
+use crate::queue::{self, QueueSettings};
+use crate::quotas::PrintQuota;
+use crate::scale::{Scale, ScaleSettings};
+use crate::scripting::ScriptHooks;
+use crate::shortcuts::{self, ShortcutBinding};
+use crate::shortener::{self, ShortenerService, ShortenerSettings};
+use crate::spool::{self, SpoolSettings};
+use crate::inventory::{InventorySettings, InventoryTarget};
+use crate::kiosk::{self, KioskSettings};
+use crate::templates::TemplateWatcher;
+use crate::tools;
+use crate::translation::{TranslationService, TranslationSettings};
+use crate::virtual_printer;
+use crate::watermark::{self, Watermark};
+use crate::webhooks::Webhook;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+use tracing::warn;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Whether the editor should restore the values below on startup
+    pub remember_settings: bool,
+    pub last_media: Option<MediaSize>,
+    pub last_printer: Option<String>,
+    pub units: Units,
+    pub preview: PreviewPreferences,
+    /// Directory of team-shared label designs merged into the template gallery
+    pub shared_templates_dir: Option<String>,
+    /// Directory whose dropped files are printed automatically
+    pub hot_folder: Option<HotFolderConfig>,
+    pub api: ApiSettings,
+    pub grpc: GrpcSettings,
+    /// Rhai script defining merge_row, before_render and after_print hooks
+    pub hooks_script: Option<String>,
+    pub scale: Option<ScaleSettings>,
+    /// Dropped images with more pixels than this needs at their physical
+    /// size are downscaled; kept as they are when absent
+    pub dropped_image_max_dpi: Option<f64>,
+    /// Template ids or label file paths offered for quick printing in the
+    /// tray menu
+    pub pinned_templates: Vec<String>,
+    /// Global keyboard shortcuts, active while the app is running
+    pub shortcuts: Vec<ShortcutBinding>,
+    /// Broker connection for printing from MQTT messages
+    pub mqtt: Option<MqttSettings>,
+    /// Who is printing on this workstation, recorded in the audit log
+    /// alongside the system account, until an operator signs in
+    pub operator_name: Option<String>,
+    /// Operators to sign in as, and whether to print who printed a label on
+    /// it
+    pub operators: OperatorSettings,
+    /// URLs called when a print job completes or fails
+    pub webhooks: Vec<Webhook>,
+    /// Pacing and retries of jobs sent to printers
+    pub queue: QueueSettings,
+    /// Where "Virtual Printer (PDF)" saves jobs; the app data directory when
+    /// absent
+    pub virtual_printer_dir: Option<String>,
+    /// Directories file:// printers may save into; file:// printers naming
+    /// any other directory are refused
+    pub file_output_dirs: Vec<String>,
+    /// Language of the interface and backend messages, e.g. "nl"; the
+    /// system language when absent
+    pub language: Option<String>,
+    /// Printers addressed together as "group:NAME"
+    pub printer_groups: Vec<PrinterGroup>,
+    /// Network printers added by address, without a CUPS queue
+    pub network_printers: Vec<NetworkPrinter>,
+    /// Price of each printer's media, by printer name, for job costs
+    pub media_costs: BTreeMap<String, MediaCost>,
+    /// Currency costs are shown in, e.g. "EUR"
+    pub currency: Option<String>,
+    /// Tear-off, peel, cutter or applicator mode, by printer name; printers
+    /// without one keep their own setting
+    pub label_handling: BTreeMap<String, LabelHandling>,
+    /// Position and size corrections from the alignment wizard, by printer
+    /// name
+    pub alignment: BTreeMap<String, PrinterCorrection>,
+    /// Colour printers by name, previewed in colour and converted to their
+    /// output profile
+    pub color_printers: BTreeMap<String, ColorPrinter>,
+    /// Render ZPL previews with the Labelary web service, which sends the ZPL
+    /// to labelary.com, instead of locally
+    pub labelary_preview: bool,
+    /// Decode every barcode at the printer's resolution before printing and
+    /// fail jobs with codes that won't scan
+    pub verify_barcodes: bool,
+    /// Label sizes offered by name when choosing media, e.g. from an
+    /// installed template pack
+    pub media_presets: Vec<MediaPreset>,
+    /// Stock taken off for printed product labels
+    pub inventory: InventorySettings,
+    /// Operator mode; changed only through lock_kiosk and unlock_kiosk
+    pub kiosk: KioskSettings,
+    /// Paths of external programs by name, e.g. "magick", for those that
+    /// aren't found on their own
+    pub tool_paths: BTreeMap<String, String>,
+    /// Look for a newer release on GitHub at startup
+    pub check_for_updates: bool,
+    /// Draw emoji in black and white, for thermal printers
+    pub monochrome_emoji: bool,
+    /// "SAMPLE" or similar stamped on every label, for proofs and test
+    /// prints
+    pub watermark: Watermark,
+    /// Ask before printing a label that was printed recently with the same
+    /// data
+    pub duplicate_guard: DuplicateGuard,
+    /// Daily label limits per operator and template, enforced in kiosk mode
+    pub quotas: Vec<PrintQuota>,
+    /// Where rendered labels are written and how long they're kept
+    pub spool: SpoolSettings,
+    /// How long jobs and their PDFs are kept in the print history
+    pub history: HistorySettings,
+    /// Web shop open orders are printed from
+    pub orders: OrderSettings,
+    /// Service long URLs in QR codes are shortened with
+    pub url_shortener: ShortenerSettings,
+    /// Service label text is machine-translated with
+    pub translation: TranslationSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaSize {
+    pub width_mm: f64,
+    pub height_mm: f64,
+    #[serde(default)]
+    pub continuous_width: bool,
+    #[serde(default)]
+    pub continuous_height: bool,
+}
+
+/// A label size by name, e.g. "Shipping 4x6"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub size: MediaSize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Mm,
+    Inch,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewPreferences {
+    pub view_rotation: ViewRotation,
+    pub print_mode: PrintMode,
+    /// Open previews in the system PDF viewer instead of the preview pane
+    pub external_viewer: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewRotation {
+    #[default]
+    Normal,
+    Rotated,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrintMode {
+    /// Open the generated PDF instead of printing
+    #[default]
+    Preview,
+    Print,
+}
+
+/// Settings loaded at startup and kept in Tauri managed state
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    /// Load settings from `path`, falling back to defaults if the file is
+    /// missing or unreadable so a corrupt file never blocks startup
+    pub fn load(path: PathBuf) -> Self {
+        let settings = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring invalid settings file {}: {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+        SettingsStore {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) -> Result<(), String> {
+        let mut current = self.settings.lock().unwrap();
+        self.write(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    // Write to a temp file and rename so a crash can't leave half a file behind
+    fn write(&self, settings: &Settings) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, json).map_err(|e| format!("Failed to write settings: {}", e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| format!("Failed to save settings: {}", e))
+    }
+}
+
+impl Settings {
+    /// These settings without PIN hash, tokens, passwords, API keys or
+    /// request headers, for operators of a locked kiosk
+    pub fn redacted(mut self) -> Settings {
+        self.kiosk.pin_hash = None;
+        self.api.token = None;
+        self.grpc.token = None;
+        if let Some(mqtt) = &mut self.mqtt {
+            mqtt.username = None;
+            mqtt.password = None;
+        }
+        for webhook in &mut self.webhooks {
+            webhook.headers.clear();
+        }
+        match &mut self.orders.shop {
+            Some(OrderShop::Shopify { access_token, .. }) => access_token.clear(),
+            Some(OrderShop::Woocommerce {
+                consumer_key,
+                consumer_secret,
+                ..
+            }) => {
+                consumer_key.clear();
+                consumer_secret.clear();
+            }
+            None => {}
+        }
+        match &mut self.url_shortener.service {
+            Some(ShortenerService::Shlink { api_key, .. }) => api_key.clear(),
+            Some(ShortenerService::Http { headers, .. }) => headers.clear(),
+            None => {}
+        }
+        match &mut self.translation.service {
+            Some(TranslationService::Deepl { api_key }) => api_key.clear(),
+            Some(TranslationService::LibreTranslate { api_key, .. }) => *api_key = None,
+            None => {}
+        }
+        if let Some(InventoryTarget::Http { headers, .. }) = &mut self.inventory.target {
+            headers.clear();
+        }
+        self
+    }
+}
+
+/// The settings; with secrets left out while the kiosk is locked
+#[tauri::command]
+pub fn get_settings(store: State<'_, SettingsStore>) -> Settings {
+    if kiosk::locked() {
+        store.get().redacted()
+    } else {
+        store.get()
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn set_settings(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    watcher: State<'_, TemplateWatcher>,
+    hot_folder: State<'_, HotFolder>,
+    api: State<'_, ApiServer>,
+    grpc: State<'_, GrpcServer>,
+    mqtt: State<'_, MqttBridge>,
+    scripts: State<'_, ScriptHooks>,
+    scale: State<'_, Scale>,
+    mut settings: Settings,
+) -> Result<(), String> {
+    let previous = store.get();
+    // Saving other settings mustn't unlock the kiosk or drop its PIN
+    settings.kiosk = previous.kiosk.clone();
+    let shared_dir = settings.shared_templates_dir.clone();
+    let hot_folder_config = settings.hot_folder.clone();
+    let api_settings = settings.api.clone();
+    let grpc_settings = settings.grpc.clone();
+    let hooks_script = settings.hooks_script.clone();
+    let scale_settings = settings.scale.clone();
+    let shortcut_bindings = settings.shortcuts.clone();
+    let mqtt_settings = settings.mqtt.clone();
+    let queue_settings = settings.queue.clone();
+    let virtual_printer_dir = settings.virtual_printer_dir.clone();
+    let file_output_dirs = settings.file_output_dirs.clone();
+    let language = settings.language.clone();
+    let printer_groups = settings.printer_groups.clone();
+    let network_printers = settings.network_printers.clone();
+    let label_handling = settings.label_handling.clone();
+    let alignment = settings.alignment.clone();
+    let color_printers = settings.color_printers.clone();
+    let spool_settings = settings.spool.clone();
+    let history_settings = settings.history.clone();
+    let url_shortener = settings.url_shortener.clone();
+    let external_viewer = settings.preview.external_viewer;
+    let verify_barcodes = settings.verify_barcodes;
+    let tool_paths = settings.tool_paths.clone();
+    let monochrome_emoji = settings.monochrome_emoji;
+    let watermark = settings.watermark.clone();
+    let operator_settings = settings.operators.clone();
+    let operator_name = settings.operator_name.clone();
+    let tray_changed = previous.pinned_templates != settings.pinned_templates
+        || previous.last_printer != settings.last_printer
+        || previous.shared_templates_dir != shared_dir
+        || previous.printer_groups != settings.printer_groups
+        || previous.network_printers != settings.network_printers;
+    store.set(settings)?;
+    scale.configure(scale_settings);
+    queue::configure(queue_settings);
+    virtual_printer::configure(&app, virtual_printer_dir.as_deref());
+    file::configure(file_output_dirs);
+    i18n::configure(language.as_deref());
+    groups::configure(printer_groups);
+    registered::configure(network_printers);
+    handling::configure(label_handling);
+    alignment::configure(alignment);
+    color::configure(color_printers);
+    spool::configure(spool_settings);
+    history::configure(history_settings);
+    shortener::configure(url_shortener);
+    pdfpreview::configure(external_viewer);
+    verify::configure(verify_barcodes);
+    tools::configure(tool_paths);
+    fonts::configure_emoji(monochrome_emoji);
+    watermark::configure(watermark);
+    operators::configure(operator_settings, operator_name);
+    if tray_changed {
+        crate::tray::refresh(&app).await?;
+    }
+    if previous.shortcuts != shortcut_bindings {
+        shortcuts::register(&app, &shortcut_bindings)?;
+    }
+    if previous.shared_templates_dir != shared_dir {
+        watcher.watch(&app, shared_dir.as_deref().map(Path::new))?;
+    }
+    if previous.hot_folder != hot_folder_config {
+        hot_folder.start(&app, hot_folder_config)?;
+    }
+    if previous.api != api_settings {
+        api.start(&app, &api_settings).await?;
+    }
+    if previous.grpc != grpc_settings {
+        grpc.start(&app, &grpc_settings)?;
+    }
+    if previous.mqtt != mqtt_settings {
+        mqtt.start(&app, mqtt_settings)?;
+    }
+    if previous.hooks_script != hooks_script {
+        scripts.load(hooks_script.as_deref().map(Path::new))?;
+    }
+    Ok(())
+}
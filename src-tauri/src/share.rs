@@ -0,0 +1,145 @@
+// Sending a label proof to someone for approval. Phones and tablets hand the
+// file to the OS, which offers its share sheet. On desktop a new email is
+// started with the file attached where the system can do that: xdg-email on
+// Linux, Mail on macOS. Elsewhere, mailto: links can't carry attachments, so
+// the email is started without one and the file's folder is opened to drag
+// it in from.
+use crate::error::LabelgoodError;
+#[cfg(not(mobile))]
+use crate::printing::{run_command, LPR_TIMEOUT};
+#[cfg(not(mobile))]
+use crate::tools;
+use serde::{Deserialize, Serialize};
+#[cfg(not(mobile))]
+use std::fmt::Write;
+use std::path::Path;
+#[cfg(not(mobile))]
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareFormat {
+    #[default]
+    Pdf,
+    Png,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareOptions {
+    pub format: ShareFormat,
+    /// Email address to fill in, e.g. the approver's
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareResult {
+    /// The file that was shared
+    pub path: String,
+    /// Whether the file was attached or handed to the share sheet; false
+    /// when the user has to attach it from the opened folder
+    pub attached: bool,
+}
+
+#[cfg(not(mobile))]
+const DEFAULT_SUBJECT: &str = "Label proof";
+
+#[cfg(not(mobile))]
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
+
+#[cfg(not(mobile))]
+fn mailto(options: &ShareOptions, body: &str) -> String {
+    format!(
+        "mailto:{}?subject={}&body={}",
+        options.to.as_deref().unwrap_or_default(),
+        percent_encode(options.subject.as_deref().unwrap_or(DEFAULT_SUBJECT)),
+        percent_encode(body)
+    )
+}
+
+// Start an email with `file` attached through the platform's mail helper;
+// false when there's none to run
+#[cfg(not(mobile))]
+async fn attach(file: &Path, options: &ShareOptions) -> Result<bool, LabelgoodError> {
+    let subject = options.subject.as_deref().unwrap_or(DEFAULT_SUBJECT);
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = tools::command("open");
+        command.arg("-a").arg("Mail").arg(file);
+        command
+    } else if cfg!(target_os = "linux") && tools::resolve("xdg-email").is_some() {
+        let mut command = tools::command("xdg-email");
+        command.arg("--subject").arg(subject).arg("--attach").arg(file);
+        if let Some(body) = &options.body {
+            command.arg("--body").arg(body);
+        }
+        if let Some(to) = options.to.as_deref().filter(|t| !t.is_empty()) {
+            command.arg(to);
+        }
+        command
+    } else {
+        return Ok(false);
+    };
+    let missing = |e: std::io::Error| LabelgoodError::Io { message: e.to_string() };
+    match run_command(command, LPR_TIMEOUT, missing).await {
+        Ok(output) if output.status.success() => Ok(true),
+        Ok(output) => {
+            warn!(
+                "Failed to attach {}: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Ok(false)
+        }
+        Err(LabelgoodError::Io { message }) => {
+            warn!("Failed to attach {}: {}", file.display(), message);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Hand `file` to the OS share sheet
+#[cfg(mobile)]
+pub async fn share(file: &Path, _options: &ShareOptions) -> Result<ShareResult, LabelgoodError> {
+    crate::backends::mobile::open(file)?;
+    Ok(ShareResult {
+        path: file.display().to_string(),
+        attached: true,
+    })
+}
+
+/// Start an email with `file` attached, or without it and with its folder
+/// opened where it can't be attached
+#[cfg(not(mobile))]
+pub async fn share(file: &Path, options: &ShareOptions) -> Result<ShareResult, LabelgoodError> {
+    let path = file.display().to_string();
+    if attach(file, options).await? {
+        info!("Started an email with {} attached", path);
+        return Ok(ShareResult { path, attached: true });
+    }
+    let body = match &options.body {
+        Some(body) => format!("{}\n\nAttachment: {}", body, path),
+        None => format!("Attachment: {}", path),
+    };
+    let open_error = |e: opener::OpenError| LabelgoodError::Io {
+        message: format!("Failed to share {}: {}", path, e),
+    };
+    opener::open(mailto(options, &body)).map_err(open_error)?;
+    if let Some(dir) = file.parent() {
+        opener::open(dir).map_err(open_error)?;
+    }
+    info!("Started an email for {}, to be attached by hand", path);
+    Ok(ShareResult { path, attached: false })
+}
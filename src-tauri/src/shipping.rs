@@ -0,0 +1,147 @@
+// Carrier shipping labels: a PDF from DHL, UPS and the like is rasterized,
+// cropped to its printed area and scaled onto the loaded media, so a 4x6"
+// label generated for a desktop printer prints correctly on a label roll
+use crate::events::EventBus;
+use crate::history::JobDetails;
+use crate::printing::{self, RENDER_DPI};
+use crate::settings::MediaSize;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::imageops::{self, FilterType};
+use image::{GrayImage, ImageFormat, Luma};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use tauri::State;
+
+/// Pixels at least this dark count as printed when cropping
+const INK_THRESHOLD: u8 = 200;
+/// White space kept around the cropped content
+const CROP_MARGIN_MM: f64 = 1.0;
+
+/// Render each page of `path` cropped and fitted to `media`, as PNG at
+/// `RENDER_DPI`
+async fn fit_pages(path: &Path, media: &MediaSize) -> Result<Vec<Vec<u8>>, String> {
+    if media.width_mm <= 0.0 || media.height_mm <= 0.0 {
+        return Err(format!("Invalid media size {}x{}mm", media.width_mm, media.height_mm));
+    }
+    printing::rasterize_pdf(path, None)
+        .await?
+        .iter()
+        .map(|page| {
+            let page = image::load_from_memory_with_format(page, ImageFormat::Png)
+                .map_err(|e| format!("Failed to decode rendered PDF page: {}", e))?
+                .to_luma8();
+            let fitted = fit(&crop(&page), media);
+            let mut png = Vec::new();
+            fitted
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .map_err(|e| format!("Failed to encode label PNG: {}", e))?;
+            Ok(png)
+        })
+        .collect()
+}
+
+// The smallest box holding every dark pixel, plus a small margin. A blank
+// page is returned as it is.
+fn crop(page: &GrayImage) -> GrayImage {
+    let (left, top, width, height) = printed_area(page);
+    imageops::crop_imm(page, left, top, width, height).to_image()
+}
+
+/// Left, top, width and height of the smallest box around every dark pixel
+/// of a page rendered at `RENDER_DPI`, plus a small margin; the whole page
+/// when it's blank
+pub fn printed_area(page: &GrayImage) -> (u32, u32, u32, u32) {
+    let (mut left, mut top, mut right, mut bottom) = (page.width(), page.height(), 0, 0);
+    for (x, y, Luma([luma])) in page.enumerate_pixels() {
+        if *luma < INK_THRESHOLD {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+    if right <= left || bottom <= top {
+        return (0, 0, page.width(), page.height());
+    }
+    let margin = (CROP_MARGIN_MM / 25.4 * RENDER_DPI).round() as u32;
+    let left = left.saturating_sub(margin);
+    let top = top.saturating_sub(margin);
+    let right = (right + margin).min(page.width());
+    let bottom = (bottom + margin).min(page.height());
+    (left, top, right - left, bottom - top)
+}
+
+// Scale `content` to fill as much of the media as possible, turning it a
+// quarter when that fits better (a landscape label on portrait media), and
+// centre it on a white media-sized page
+fn fit(content: &GrayImage, media: &MediaSize) -> GrayImage {
+    let media_w = (media.width_mm / 25.4 * RENDER_DPI).round().max(1.0) as u32;
+    let media_h = (media.height_mm / 25.4 * RENDER_DPI).round().max(1.0) as u32;
+    let scale_for = |w: u32, h: u32| (media_w as f64 / w as f64).min(media_h as f64 / h as f64);
+
+    let (w, h) = (content.width(), content.height());
+    let rotated;
+    let content = if scale_for(h, w) > scale_for(w, h) {
+        rotated = imageops::rotate90(content);
+        &rotated
+    } else {
+        content
+    };
+    let scale = scale_for(content.width(), content.height());
+    let scaled_w = ((content.width() as f64 * scale).round() as u32).clamp(1, media_w);
+    let scaled_h = ((content.height() as f64 * scale).round() as u32).clamp(1, media_h);
+    let scaled = imageops::resize(content, scaled_w, scaled_h, FilterType::Lanczos3);
+
+    let mut page = GrayImage::from_pixel(media_w, media_h, Luma([255]));
+    imageops::overlay(
+        &mut page,
+        &scaled,
+        ((media_w - scaled_w) / 2) as i64,
+        ((media_h - scaled_h) / 2) as i64,
+    );
+    page
+}
+
+/// Preview of a shipping label PDF fitted to `media`, one PNG data URL per page
+#[tauri::command]
+pub async fn preview_shipping_label(path: String, media: MediaSize) -> Result<Vec<String>, String> {
+    let pages = fit_pages(Path::new(&path), &media).await?;
+    Ok(pages
+        .iter()
+        .map(|png| format!("data:image/png;base64,{}", BASE64.encode(png)))
+        .collect())
+}
+
+/// Print a shipping label PDF fitted to `media`, one label per page.
+/// `op_id` lets `cancel_operation` stop it.
+#[tauri::command]
+pub async fn print_shipping_label(
+    events: State<'_, EventBus>,
+    path: String,
+    media: MediaSize,
+    printer_name: Option<String>,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    let pages = fit_pages(Path::new(&path), &media).await?;
+    let dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let mut png_paths = Vec::with_capacity(pages.len());
+    for (i, png) in pages.iter().enumerate() {
+        let png_path = dir.path().join(format!("label_{:04}.png", i));
+        fs::write(&png_path, png).map_err(|e| format!("Failed to write PNG file: {}", e))?;
+        png_paths.push(png_path);
+    }
+
+    let details = JobDetails {
+        template: Some(path.clone()),
+        data: Vec::new(),
+    };
+    let printer_name = printer_name.as_deref();
+    let job = events.run_job("shipping", pages.len(), printer_name, details, async {
+        let png_refs: Vec<&Path> = png_paths.iter().map(|p| p.as_path()).collect();
+        let pdf_path = printing::images_to_pdf(&png_refs, media.width_mm, media.height_mm).await?;
+        Ok(printing::submit_pdf(&pdf_path, media.width_mm, media.height_mm, printer_name).await?)
+    });
+    events.run_operation(op_id, job).await
+}
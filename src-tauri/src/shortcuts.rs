@@ -0,0 +1,85 @@
+// System-wide keyboard shortcuts, so a label can be reprinted while another
+// application has focus
+use crate::events::EventBus;
+use crate::history::{self, HistoryStore};
+use crate::settings::SettingsStore;
+use crate::tray;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+use tracing::{error, info};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShortcutBinding {
+    /// Key combination, e.g. "CommandOrControl+Shift+P"
+    pub keys: String,
+    pub action: ShortcutAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShortcutAction {
+    /// Send the most recent job's PDF to the same printer again
+    ReprintLast,
+    /// Print one label of a template, as from the tray menu
+    PrintTemplate { template: String },
+}
+
+/// Register `bindings`, replacing whatever was registered before. Every
+/// binding is tried; the error lists the ones that failed.
+pub fn register(app: &AppHandle, bindings: &[ShortcutBinding]) -> Result<(), String> {
+    let shortcuts = app.global_shortcut();
+    shortcuts
+        .unregister_all()
+        .map_err(|e| format!("Failed to clear global shortcuts: {}", e))?;
+
+    let mut failures = Vec::new();
+    for binding in bindings {
+        let registered = binding
+            .keys
+            .parse::<Shortcut>()
+            .map_err(|e| e.to_string())
+            .and_then(|shortcut| shortcuts.register(shortcut).map_err(|e| e.to_string()));
+        if let Err(e) = registered {
+            failures.push(format!("{}: {}", binding.keys, e));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to register global shortcuts: {}", failures.join("; ")))
+    }
+}
+
+/// Handler for the global shortcut plugin
+pub fn handle(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let bindings = app.state::<SettingsStore>().get().shortcuts;
+    let Some(binding) = bindings
+        .into_iter()
+        .find(|b| b.keys.parse::<Shortcut>().is_ok_and(|s| s.id() == shortcut.id()))
+    else {
+        return;
+    };
+
+    let app = app.clone();
+    // The handler runs on the event loop, which printing mustn't hold up
+    tauri::async_runtime::spawn(async move {
+        let result = match &binding.action {
+            ShortcutAction::ReprintLast => reprint_last(&app).await,
+            ShortcutAction::PrintTemplate { template } => tray::quick_print(&app, "shortcut", template).await,
+        };
+        if let Err(e) = result {
+            error!("Shortcut {} failed: {}", binding.keys, e);
+        }
+    });
+}
+
+async fn reprint_last(app: &AppHandle) -> Result<(), String> {
+    let message =
+        history::replay_last(&app.state::<HistoryStore>(), &app.state::<EventBus>(), None, "shortcut").await?;
+    info!("Reprinted last job: {}", message);
+    Ok(())
+}
@@ -0,0 +1,272 @@
+// Short links for QR codes: a long URL makes a dense code that small labels
+// can't print sharply enough to scan, so URLs over a configured length are
+// replaced by a short link before printing. Either a self-hosted Shlink or
+// any API that takes a URL as JSON and answers with the short one.
+//
+// Every link made is kept in the history database, so the same URL prints
+// the same code and what a code points to can be looked up later. When the
+// service can't be reached the long URL is printed as it is.
+use crate::document::{Document, ElementKind};
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS short_urls (
+        long_url TEXT PRIMARY KEY,
+        short_url TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortenerSettings {
+    /// Off when absent
+    pub service: Option<ShortenerService>,
+    /// URLs in QR codes longer than this are shortened
+    pub min_length: usize,
+}
+
+impl Default for ShortenerSettings {
+    fn default() -> Self {
+        ShortenerSettings {
+            service: None,
+            min_length: 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShortenerService {
+    /// Shlink's REST API, e.g. `https://s.example.com`
+    Shlink { url: String, api_key: String },
+    /// POST `{"url": "..."}` and read the short link at `short_url_path`, a
+    /// dotted path into the response ("short_url" when absent)
+    Http {
+        url: String,
+        /// Extra request headers, e.g. `Authorization`
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        #[serde(default)]
+        short_url_path: Option<String>,
+    },
+}
+
+/// A long URL and the short link printed in its place
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortUrl {
+    pub long_url: String,
+    pub short_url: String,
+    /// UTC, RFC 3339
+    pub created_at: String,
+}
+
+static SETTINGS: Mutex<Option<ShortenerSettings>> = Mutex::new(None);
+static STORE: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+pub fn configure(settings: ShortenerSettings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// Keep short links in the database at `path`, the print history's; later
+/// calls have no effect. Without it links are made again for every job.
+pub fn init(path: &Path) {
+    if STORE.get().is_some() {
+        return;
+    }
+    let opened = Connection::open(path).and_then(|connection| connection.execute_batch(SCHEMA).map(|_| connection));
+    match opened {
+        Ok(connection) => {
+            let _ = STORE.set(Mutex::new(connection));
+        }
+        Err(e) => error!("Failed to open short link table in {}: {}", path.display(), e),
+    }
+}
+
+fn lookup(long_url: &str) -> Option<String> {
+    let connection = STORE.get()?.lock().unwrap();
+    connection
+        .query_row(
+            "SELECT short_url FROM short_urls WHERE long_url = ?1",
+            params![long_url],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap_or_else(|e| {
+            warn!("Failed to look up short link: {}", e);
+            None
+        })
+}
+
+fn remember(long_url: &str, short_url: &str) {
+    let Some(store) = STORE.get() else {
+        return;
+    };
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    if let Err(e) = store.lock().unwrap().execute(
+        "INSERT OR REPLACE INTO short_urls (long_url, short_url, created_at) VALUES (?1, ?2, ?3)",
+        params![long_url, short_url, now],
+    ) {
+        warn!("Failed to record short link {}: {}", short_url, e);
+    }
+}
+
+// Ask the service for a short link to `long_url`
+fn request(service: &ShortenerService, long_url: &str) -> Result<String, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into();
+    let (response, path) = match service {
+        ShortenerService::Shlink { url, api_key } => {
+            let endpoint = format!("{}/rest/v3/short-urls", url.trim_end_matches('/'));
+            let response = agent
+                .post(&endpoint)
+                .header("X-Api-Key", api_key)
+                .send_json(json!({ "longUrl": long_url, "findIfExists": true }));
+            (response, "shortUrl")
+        }
+        ShortenerService::Http {
+            url,
+            headers,
+            short_url_path,
+        } => {
+            let mut request = agent.post(url);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            let response = request.send_json(json!({ "url": long_url }));
+            (response, short_url_path.as_deref().unwrap_or("short_url"))
+        }
+    };
+    let body: Value = response
+        .map_err(|e| format!("Failed to shorten {}: {}", long_url, e))?
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("URL shortener returned invalid JSON: {}", e))?;
+    let mut short_url = &body;
+    for key in path.split('.').filter(|key| !key.is_empty()) {
+        short_url = short_url
+            .get(key)
+            .ok_or_else(|| format!("URL shortener response has no \"{}\" field", key))?;
+    }
+    short_url
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "URL shortener returned no short link".to_string())
+}
+
+// A QR code holding a URL worth shortening
+fn is_long_url(kind: &ElementKind, min_length: usize) -> bool {
+    let ElementKind::Barcode(barcode) = kind else {
+        return false;
+    };
+    let data = barcode.data.as_str();
+    barcode.symbology == "qr"
+        && (data.starts_with("https://") || data.starts_with("http://"))
+        && data.len() > min_length
+        && !data.contains(char::is_whitespace)
+}
+
+// The short link for `long_url`, recorded or made now
+fn shorten(service: &ShortenerService, long_url: &str) -> Option<String> {
+    if let Some(short_url) = lookup(long_url) {
+        return Some(short_url);
+    }
+    match request(service, long_url) {
+        Ok(short_url) => {
+            info!("Shortened {} to {}", long_url, short_url);
+            remember(long_url, &short_url);
+            Some(short_url)
+        }
+        Err(e) => {
+            warn!("{}; printing the long URL", e);
+            None
+        }
+    }
+}
+
+/// `documents` with long URLs in QR codes replaced by short links; the same
+/// documents when nothing needed shortening
+pub async fn shorten_documents(documents: &[Document]) -> Cow<'_, [Document]> {
+    let Some(settings) = SETTINGS.lock().unwrap().clone() else {
+        return Cow::Borrowed(documents);
+    };
+    let Some(service) = settings.service else {
+        return Cow::Borrowed(documents);
+    };
+    let min_length = settings.min_length;
+    if !documents.iter().any(|document| {
+        document
+            .elements
+            .iter()
+            .any(|element| is_long_url(&element.kind, min_length))
+    }) {
+        return Cow::Borrowed(documents);
+    }
+
+    let mut owned = documents.to_vec();
+    let shortened = tauri::async_runtime::spawn_blocking(move || {
+        // Each URL once per job, however many labels carry it
+        let mut links: BTreeMap<String, Option<String>> = BTreeMap::new();
+        for document in &mut owned {
+            for element in &mut document.elements {
+                if !is_long_url(&element.kind, min_length) {
+                    continue;
+                }
+                let ElementKind::Barcode(barcode) = &mut element.kind else {
+                    continue;
+                };
+                let short_url = links
+                    .entry(barcode.data.clone())
+                    .or_insert_with(|| shorten(&service, &barcode.data));
+                if let Some(short_url) = short_url {
+                    barcode.data = short_url.clone();
+                }
+            }
+        }
+        owned
+    })
+    .await;
+    match shortened {
+        Ok(documents) => Cow::Owned(documents),
+        Err(e) => {
+            error!("Failed to shorten URLs: {}", e);
+            Cow::Borrowed(documents)
+        }
+    }
+}
+
+/// Short links printed so far, newest first
+#[tauri::command]
+pub fn list_short_urls(limit: Option<u32>) -> Result<Vec<ShortUrl>, String> {
+    let Some(store) = STORE.get() else {
+        return Ok(Vec::new());
+    };
+    let connection = store.lock().unwrap();
+    let mut statement = connection
+        .prepare("SELECT long_url, short_url, created_at FROM short_urls ORDER BY created_at DESC LIMIT ?1")
+        .map_err(|e| format!("Failed to read short links: {}", e))?;
+    let rows = statement
+        .query_map(params![limit.unwrap_or(100)], |row| {
+            Ok(ShortUrl {
+                long_url: row.get(0)?,
+                short_url: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read short links: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read short links: {}", e))
+}
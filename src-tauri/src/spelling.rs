@@ -0,0 +1,184 @@
+// Spell checking of label text with Hunspell, whose dictionaries come with
+// most distributions, LibreOffice and browsers. Words the user adds are kept
+// in a personal dictionary in the app data directory, one per line, which
+// Hunspell reads on every check.
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::printing;
+use crate::tools;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Seek, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::State;
+use tracing::error;
+
+const HUNSPELL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Misspelling {
+    pub word: String,
+    /// 0-based line of the text the word is on
+    pub line: usize,
+    /// Character offset of the word in its line
+    pub start: usize,
+    pub suggestions: Vec<String>,
+}
+
+/// Words the user has told the spell checker are correct
+pub struct UserDictionary {
+    path: PathBuf,
+    words: Mutex<BTreeSet<String>>,
+}
+
+impl UserDictionary {
+    pub fn load(path: PathBuf) -> Self {
+        let words = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .lines()
+                .map(str::trim)
+                .filter(|w| !w.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => {
+                error!("Failed to read user dictionary {}: {}", path.display(), e);
+                BTreeSet::new()
+            }
+        };
+        UserDictionary {
+            path,
+            words: Mutex::new(words),
+        }
+    }
+
+    fn save(&self, words: &BTreeSet<String>) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create user dictionary directory: {}", e))?;
+        }
+        let text: String = words.iter().map(|word| format!("{}\n", word)).collect();
+        fs::write(&self.path, text).map_err(|e| format!("Failed to save user dictionary: {}", e))
+    }
+}
+
+/// Misspelled words of `text`, checked against the Hunspell dictionary for
+/// `lang` (e.g. "en_US" or "nl-NL") and the user dictionary
+#[tauri::command]
+pub async fn check_text(
+    dictionary: State<'_, UserDictionary>,
+    lang: String,
+    text: String,
+) -> Result<Vec<Misspelling>, LabelgoodError> {
+    let lang = lang.trim().replace('-', "_");
+    // Hunspell takes paths as dictionary names too
+    if lang.is_empty() || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!("Not a dictionary language: \"{}\"", lang),
+        });
+    }
+
+    // Each line starts with ^ so Hunspell doesn't take it as a command
+    let mut input = tempfile::tempfile().map_err(|e| format!("Failed to create temp file: {}", e))?;
+    for line in text.lines() {
+        writeln!(input, "^{}", line).map_err(|e| format!("Failed to write temp file: {}", e))?;
+    }
+    input
+        .rewind()
+        .map_err(|e| format!("Failed to write temp file: {}", e))?;
+
+    let mut command = tools::command("hunspell");
+    command.args(["-a", "-i", "utf-8", "-d", lang.as_str()]);
+    if dictionary.path.exists() {
+        command.arg("-p").arg(&dictionary.path);
+    }
+    command.stdin(Stdio::from(input));
+    let missing = |e: std::io::Error| LabelgoodError::DependencyMissing {
+        program: "hunspell".to_string(),
+        message: i18n::t("hunspell-missing", &[("error", e.to_string().into())]),
+    };
+    let output = printing::run_command(command, HUNSPELL_TIMEOUT, missing).await?;
+    if !output.status.success() {
+        return Err(LabelgoodError::InvalidInput {
+            message: format!(
+                "Failed to spell check with the {} dictionary: {}",
+                lang,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(parse(&text, &String::from_utf8_lossy(&output.stdout)))
+}
+
+// Misspellings in Hunspell's pipe mode output for `text`: a version banner,
+// then for each line of text a result per word and an empty line. Wrong
+// words read "& word count offset: suggestion, ..." or, without
+// suggestions, "# word offset"; offsets are looked up again here since
+// Hunspell counts them differently between versions.
+fn parse(text: &str, output: &str) -> Vec<Misspelling> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut misspellings = Vec::new();
+    let (mut line, mut cursor) = (0, 0);
+    for result in output.lines().skip_while(|l| l.starts_with('@')) {
+        if result.is_empty() {
+            line += 1;
+            cursor = 0;
+            continue;
+        }
+        let (word, suggestions) = match result.split_once(' ') {
+            Some(("&", rest)) => {
+                let (head, suggestions) = rest.split_once(": ").unwrap_or((rest, ""));
+                let suggestions = suggestions.split(", ").filter(|s| !s.is_empty()).map(String::from);
+                (head.split(' ').next().unwrap_or(""), suggestions.collect())
+            }
+            Some(("#", rest)) => (rest.split(' ').next().unwrap_or(""), Vec::new()),
+            _ => continue,
+        };
+        let Some(text_line) = lines.get(line) else {
+            break;
+        };
+        let Some(found) = text_line.get(cursor..).and_then(|rest| rest.find(word)) else {
+            continue;
+        };
+        let byte = cursor + found;
+        cursor = byte + word.len();
+        misspellings.push(Misspelling {
+            word: word.to_string(),
+            line,
+            start: text_line[..byte].chars().count(),
+            suggestions,
+        });
+    }
+    misspellings
+}
+
+#[tauri::command]
+pub fn list_dictionary_words(dictionary: State<'_, UserDictionary>) -> Vec<String> {
+    dictionary.words.lock().unwrap().iter().cloned().collect()
+}
+
+/// Accept `word` as correctly spelled in every language
+#[tauri::command]
+pub fn add_dictionary_word(dictionary: State<'_, UserDictionary>, word: String) -> Result<(), String> {
+    let word = word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(format!("Not a single word: \"{}\"", word));
+    }
+    let mut words = dictionary.words.lock().unwrap();
+    if words.insert(word.to_string()) {
+        dictionary.save(&words)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_dictionary_word(dictionary: State<'_, UserDictionary>, word: String) -> Result<(), String> {
+    let mut words = dictionary.words.lock().unwrap();
+    if words.remove(word.trim()) {
+        dictionary.save(&words)?;
+    }
+    Ok(())
+}
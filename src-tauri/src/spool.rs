@@ -0,0 +1,114 @@
+// Where rendered labels are written on their way to the printer: the PDFs of
+// new jobs, reprints, released held jobs and proofs. The system temp
+// directory unless set, e.g. to a RAM disk for speed or a network share to
+// archive what was printed. With a retention period, older files are removed
+// at startup and every hour; only files named like the ones written here are
+// touched, so a shared directory can hold other things too.
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Names of the files written here start with one of these
+const PREFIXES: &[&str] = &["label_", "label-proof-"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoolSettings {
+    /// Directory rendered labels are written to; the system temp directory
+    /// when absent
+    pub dir: Option<String>,
+    /// Hours rendered labels are kept; until the system clears its temp
+    /// directory, or forever elsewhere, when absent
+    pub retention_hours: Option<u32>,
+}
+
+static SETTINGS: Mutex<Option<SpoolSettings>> = Mutex::new(None);
+
+pub fn configure(settings: SpoolSettings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// The directory to write rendered labels to, created when missing; the
+/// temp directory when it can't be
+pub fn dir() -> PathBuf {
+    let configured = SETTINGS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|settings| settings.dir.clone())
+        .filter(|dir| !dir.trim().is_empty());
+    let Some(dir) = configured.map(PathBuf::from) else {
+        return std::env::temp_dir();
+    };
+    match fs::create_dir_all(&dir) {
+        Ok(()) => dir,
+        Err(e) => {
+            warn!(
+                "Failed to create spool directory {}, using the temp directory: {}",
+                dir.display(),
+                e
+            );
+            std::env::temp_dir()
+        }
+    }
+}
+
+/// Where to write `file_name` in the spool directory
+pub fn path(file_name: &str) -> PathBuf {
+    dir().join(file_name)
+}
+
+/// Remove rendered labels older than the retention period. Returns how many
+/// were removed.
+pub fn clean() -> Result<usize, String> {
+    let Some(hours) = SETTINGS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|settings| settings.retention_hours)
+    else {
+        return Ok(0);
+    };
+    // At least an hour, so a job being spooled keeps its PDF
+    let cutoff = SystemTime::now() - Duration::from_secs(u64::from(hours.max(1)) * 60 * 60);
+    let dir = dir();
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read spool directory {}: {}", dir.display(), e))?;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if !PREFIXES.iter().any(|prefix| name.to_string_lossy().starts_with(prefix)) {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.modified().is_ok_and(|modified| modified < cutoff));
+        if !expired {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove {}: {}", entry.path().display(), e),
+        }
+    }
+    if removed > 0 {
+        info!("Removed {} rendered label file(s) older than {} hours", removed, hours);
+    }
+    Ok(removed)
+}
+
+/// Clean up now and every hour after
+pub fn start_cleanup() {
+    tauri::async_runtime::spawn(async {
+        loop {
+            if let Ok(Err(e)) = tauri::async_runtime::spawn_blocking(clean).await {
+                warn!("{}", e);
+            }
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,159 @@
+// Usage statistics over the print history for the statistics screen: labels
+// per day, printer and template, failure rates and media used, each as a
+// series of (key, value) points a chart can draw directly.
+use crate::costs;
+use crate::history::HistoryStore;
+use crate::media::{self, RollKind};
+use crate::settings::SettingsStore;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// Key for jobs without a template or printer
+const NONE_KEY: &str = "";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Point {
+    pub key: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    pub jobs: usize,
+    pub failed_jobs: usize,
+    /// Labels of successful jobs
+    pub labels: usize,
+    /// Metres of media used by successful jobs
+    pub media_m: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageStatistics {
+    /// Local dates, oldest first, with days without printing as 0
+    pub labels_per_day: Vec<Point>,
+    /// Most labels first
+    pub labels_per_printer: Vec<Point>,
+    /// Most labels first; "" for jobs without a template
+    pub labels_per_template: Vec<Point>,
+    /// Failed jobs as a fraction of all jobs, 0 to 1
+    pub failure_rate_per_day: Vec<Point>,
+    pub failure_rate_per_printer: Vec<Point>,
+    /// Metres of media per printer, most first
+    pub media_per_printer: Vec<Point>,
+    pub totals: UsageTotals,
+}
+
+#[derive(Default)]
+struct Tally {
+    jobs: usize,
+    failed: usize,
+    labels: usize,
+    media_m: f64,
+}
+
+// Local date of an RFC 3339 UTC time
+fn local_date(printed_at: &str) -> Option<NaiveDate> {
+    let time: DateTime<Utc> = printed_at.parse().ok()?;
+    Some(time.with_timezone(&Local).date_naive())
+}
+
+fn descending(tallies: &BTreeMap<String, Tally>, value: impl Fn(&Tally) -> f64) -> Vec<Point> {
+    let mut points: Vec<Point> = tallies
+        .iter()
+        .map(|(key, tally)| Point {
+            key: key.clone(),
+            value: value(tally),
+        })
+        .collect();
+    points.sort_by(|a, b| b.value.total_cmp(&a.value));
+    points
+}
+
+fn failure_rate(tally: &Tally) -> f64 {
+    if tally.jobs == 0 {
+        0.0
+    } else {
+        tally.failed as f64 / tally.jobs as f64
+    }
+}
+
+/// Statistics for the jobs printed at or after `since` and before `until`
+/// (RFC 3339 UTC or dates like 2026-10-01). Media is the length of tape or
+/// backing the labels take, using the tape width from the printer's media
+/// cost to tell which way they run when it's set.
+#[tauri::command]
+pub async fn get_usage_statistics(
+    settings: State<'_, SettingsStore>,
+    history: State<'_, HistoryStore>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<UsageStatistics, String> {
+    let settings = settings.get();
+    let mut days: BTreeMap<NaiveDate, Tally> = BTreeMap::new();
+    let mut printers: BTreeMap<String, Tally> = BTreeMap::new();
+    let mut templates: BTreeMap<String, Tally> = BTreeMap::new();
+    let mut totals = UsageTotals::default();
+
+    for job in history.summaries(since.as_deref(), until.as_deref())? {
+        let printer = job.printer.clone().unwrap_or_else(|| NONE_KEY.to_string());
+        let media_m = match (job.ok, job.width_mm, job.height_mm) {
+            (true, Some(width_mm), Some(height_mm)) => {
+                let tape_width = costs::media_cost(&settings.media_costs, &printer).and_then(|cost| cost.width_mm);
+                media::usage(RollKind::Continuous, tape_width, job.labels, width_mm, height_mm) / 1000.0
+            }
+            _ => 0.0,
+        };
+        let labels = if job.ok { job.labels } else { 0 };
+        let tallies = [
+            local_date(&job.printed_at).map(|day| days.entry(day).or_default()),
+            job.printer
+                .is_some()
+                .then(|| printers.entry(printer.clone()).or_default()),
+            Some(
+                templates
+                    .entry(job.template.clone().unwrap_or_else(|| NONE_KEY.to_string()))
+                    .or_default(),
+            ),
+        ];
+        for tally in tallies.into_iter().flatten() {
+            tally.jobs += 1;
+            tally.failed += usize::from(!job.ok);
+            tally.labels += labels;
+            tally.media_m += media_m;
+        }
+        totals.jobs += 1;
+        totals.failed_jobs += usize::from(!job.ok);
+        totals.labels += labels;
+        totals.media_m += media_m;
+    }
+
+    // Every day in the range gets a point, so gaps show on a chart
+    let mut labels_per_day = Vec::new();
+    let mut failure_rate_per_day = Vec::new();
+    if let (Some(first), Some(last)) = (days.keys().next().copied(), days.keys().next_back().copied()) {
+        for day in first.iter_days().take_while(|day| *day <= last) {
+            let key = day.format("%Y-%m-%d").to_string();
+            let tally = days.get(&day);
+            labels_per_day.push(Point {
+                key: key.clone(),
+                value: tally.map_or(0.0, |t| t.labels as f64),
+            });
+            failure_rate_per_day.push(Point {
+                key,
+                value: tally.map_or(0.0, failure_rate),
+            });
+        }
+    }
+
+    Ok(UsageStatistics {
+        labels_per_day,
+        labels_per_printer: descending(&printers, |t| t.labels as f64),
+        labels_per_template: descending(&templates, |t| t.labels as f64),
+        failure_rate_per_day,
+        failure_rate_per_printer: descending(&printers, failure_rate),
+        media_per_printer: descending(&printers, |t| t.media_m),
+        totals,
+    })
+}
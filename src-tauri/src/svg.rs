@@ -0,0 +1,86 @@
+// SVG images on labels. The backend measures an SVG at its intrinsic size
+// and renders it at print resolution, so logos stay sharp instead of being
+// rasterized at screen resolution by the webview. Documents keep the SVG
+// itself as the asset; backend renders draw it as vectors.
+use crate::clipboard::SCREEN_DPI;
+use crate::document::{asset_id, Asset};
+use crate::fonts::FontLibrary;
+use crate::printing::RENDER_DPI;
+use crate::project::LoadedAsset;
+use crate::render;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use resvg::usvg;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tauri::State;
+
+pub const MIME_TYPE: &str = "image/svg+xml";
+
+/// Width and height of an SVG in millimeters. usvg resolves physical units
+/// to CSS pixels; a document without width and height is sized by its
+/// viewBox.
+pub fn intrinsic_size_mm(bytes: &[u8]) -> Result<(f64, f64), String> {
+    let tree =
+        usvg::Tree::from_data(bytes, &usvg::Options::default()).map_err(|e| format!("Failed to parse SVG: {}", e))?;
+    let size = tree.size();
+    let to_mm = |px: f32| px as f64 / SCREEN_DPI * 25.4;
+    Ok((to_mm(size.width()), to_mm(size.height())))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SvgImport {
+    #[serde(flatten)]
+    pub asset: LoadedAsset,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+/// Read an SVG file for placing on a label, with its intrinsic size
+#[tauri::command]
+pub async fn import_svg(path: String) -> Result<SvgImport, String> {
+    let path = Path::new(&path);
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read SVG file: {}", e))?;
+    let (width_mm, height_mm) = intrinsic_size_mm(&bytes)?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image.svg".to_string());
+    Ok(SvgImport {
+        asset: LoadedAsset {
+            id: asset_id(&bytes),
+            asset: Asset {
+                file_name,
+                mime_type: MIME_TYPE.to_string(),
+                data: BASE64.encode(&bytes),
+            },
+        },
+        width_mm,
+        height_mm,
+    })
+}
+
+/// PNG data URL of a base64 SVG stretched to `width_mm` x `height_mm`, as
+/// an image element of that size prints, at `dpi` (the print resolution by
+/// default). For composing print images in the frontend.
+#[tauri::command]
+pub async fn render_svg_asset(
+    fonts: State<'_, FontLibrary>,
+    data: String,
+    width_mm: f64,
+    height_mm: f64,
+    dpi: Option<f64>,
+) -> Result<String, String> {
+    if width_mm <= 0.0 || height_mm <= 0.0 {
+        return Err(format!("Invalid image size {}x{}mm", width_mm, height_mm));
+    }
+    let (w, h) = (width_mm, height_mm);
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <image width=\"{w}\" height=\"{h}\" preserveAspectRatio=\"none\" href=\"data:{MIME_TYPE};base64,{data}\"/>\n\
+         </svg>\n"
+    );
+    let png = render::rasterize(&svg, fonts.database(), w, h, dpi.unwrap_or(RENDER_DPI))?;
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+}
@@ -0,0 +1,307 @@
+// The tape and ink colours of the cassette in a Brother P-touch printer, read
+// from its status, so previews show the label the way it comes out: the print
+// head only lays down the cassette's ink, on the cassette's tape, and colours
+// too light to be dotted are left out. Printers that can't be asked, and
+// other makes, preview in black and white as before.
+use crate::backends;
+use crate::calibration::PrinterLanguage;
+use crate::document::{Document, ElementKind};
+use crate::i18n;
+use crate::printerinfo;
+use crate::raster::PrintRaster;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{GrayImage, ImageFormat, Pixel, Rgb, RgbImage};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How long a detected cassette is trusted before the printer is asked again
+const DETECTION_TTL: Duration = Duration::from_secs(30);
+
+const REPLY_WAIT: Duration = Duration::from_secs(2);
+
+/// Colours lighter than this are left out by the print head
+const PRINT_THRESHOLD: u8 = 128;
+
+/// Ink and tape closer than this in brightness are hard to tell apart
+const MIN_CONTRAST: u8 = 64;
+
+// Status bytes: a 32-byte reply starting with the print head mark and size
+const STATUS_SIZE: usize = 32;
+const STATUS_WIDTH: usize = 10;
+const STATUS_TAPE_COLOR: usize = 24;
+const STATUS_TEXT_COLOR: usize = 25;
+
+// Tape colour codes as the status reports them, with a name and how the
+// tape looks
+const TAPE_COLORS: &[(u8, &str, &str)] = &[
+    (0x01, "white", "#ffffff"),
+    (0x03, "clear", "#eef2f3"),
+    (0x04, "red", "#d7282f"),
+    (0x05, "blue", "#1f5fae"),
+    (0x06, "yellow", "#f7d417"),
+    (0x07, "green", "#2e9a48"),
+    (0x08, "black", "#1a1a1a"),
+    (0x09, "clear", "#eef2f3"),
+    (0x20, "matte white", "#f7f7f4"),
+    (0x21, "matte clear", "#e8ecec"),
+    (0x22, "matte silver", "#c4c6c8"),
+    (0x23, "satin gold", "#c9a94a"),
+    (0x24, "satin silver", "#b9bcbf"),
+    (0x30, "blue", "#1f5fae"),
+    (0x31, "red", "#d7282f"),
+    (0x40, "fluorescent orange", "#ff7a1a"),
+    (0x41, "fluorescent yellow", "#ecf542"),
+    (0x50, "berry pink", "#d9478f"),
+    (0x51, "light grey", "#c8c9ca"),
+    (0x52, "lime green", "#9ccc3c"),
+    (0x60, "yellow", "#f7d417"),
+    (0x61, "pink", "#f29ac1"),
+    (0x62, "blue", "#56a0d8"),
+    (0x70, "white", "#ffffff"),
+    (0x90, "white", "#ffffff"),
+    (0x91, "yellow", "#f7d417"),
+];
+
+// Ink colour codes
+const INK_COLORS: &[(u8, &str, &str)] = &[
+    (0x01, "white", "#ffffff"),
+    (0x04, "red", "#d7282f"),
+    (0x05, "blue", "#1f5fae"),
+    (0x08, "black", "#000000"),
+    (0x0a, "gold", "#c9a94a"),
+    (0x62, "blue", "#1f5fae"),
+];
+
+/// The cassette loaded in a printer
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadedTape {
+    pub width_mm: u8,
+    /// e.g. "yellow"
+    pub tape: String,
+    /// How the tape looks, "#rrggbb"
+    pub tape_color: String,
+    /// e.g. "black"
+    pub ink: String,
+    pub ink_color: String,
+}
+
+/// Something on a label that won't show on the loaded tape
+#[derive(Debug, Clone, Serialize)]
+pub struct TapeWarning {
+    /// Id of the element, when it's one element
+    pub element: Option<u32>,
+    pub message: String,
+}
+
+static DETECTED: Mutex<BTreeMap<String, (Instant, Option<LoadedTape>)>> = Mutex::new(BTreeMap::new());
+
+fn color(table: &[(u8, &str, &str)], code: u8) -> Option<(String, String)> {
+    table
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, name, hex)| (name.to_string(), hex.to_string()))
+}
+
+// Clear the print buffer, initialize and ask for the status
+fn status_request() -> Vec<u8> {
+    let mut data = vec![0; 100];
+    data.extend_from_slice(b"\x1b@\x1biS");
+    data
+}
+
+/// The cassette a status reply reports; None for QL printers, which report
+/// no colours, and for cleaning tape or a cassette the printer doesn't know
+pub fn parse_status(reply: &[u8]) -> Option<LoadedTape> {
+    let status = reply
+        .windows(STATUS_SIZE)
+        .find(|status| status[0] == 0x80 && status[1] == STATUS_SIZE as u8 && status[2] == b'B')?;
+    let (tape, tape_color) = color(TAPE_COLORS, status[STATUS_TAPE_COLOR])?;
+    let (ink, ink_color) = color(INK_COLORS, status[STATUS_TEXT_COLOR])?;
+    Some(LoadedTape {
+        width_mm: status[STATUS_WIDTH],
+        tape,
+        tape_color,
+        ink,
+        ink_color,
+    })
+}
+
+/// Ask `printer_name` which cassette is loaded; None when it isn't a Brother
+/// printer or reports no tape colours
+pub async fn detect(printer_name: &str) -> Result<Option<LoadedTape>, String> {
+    let tape = if printerinfo::printer_info(printer_name).await.language == Some(PrinterLanguage::Brother) {
+        let reply = backends::query(printer_name, &status_request(), REPLY_WAIT).await?;
+        parse_status(&reply)
+    } else {
+        None
+    };
+    DETECTED
+        .lock()
+        .unwrap()
+        .insert(printer_name.to_string(), (Instant::now(), tape.clone()));
+    Ok(tape)
+}
+
+/// The cassette in `printer_name` as detected within the last half minute,
+/// or asked now; None when it can't be told
+pub async fn loaded_tape(printer_name: &str) -> Option<LoadedTape> {
+    if let Some((at, tape)) = DETECTED.lock().unwrap().get(printer_name) {
+        if at.elapsed() < DETECTION_TTL {
+            return tape.clone();
+        }
+    }
+    match detect(printer_name).await {
+        Ok(tape) => tape,
+        Err(e) => {
+            debug!("Can't tell which tape {} has loaded: {}", printer_name, e);
+            DETECTED
+                .lock()
+                .unwrap()
+                .insert(printer_name.to_string(), (Instant::now(), None));
+            None
+        }
+    }
+}
+
+// "#rrggbb" or "#rgb" as a pixel
+fn rgb(color: &str) -> Option<Rgb<u8>> {
+    let hex = color.trim().trim_start_matches('#');
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        6 => Some(Rgb([
+            channel(hex.get(0..2)?)?,
+            channel(hex.get(2..4)?)?,
+            channel(hex.get(4..6)?)?,
+        ])),
+        3 => {
+            let short = |i: usize| channel(&hex.get(i..i + 1)?.repeat(2));
+            Some(Rgb([short(0)?, short(1)?, short(2)?]))
+        }
+        _ => None,
+    }
+}
+
+// Brightness of a colour, as the print head's threshold sees it
+fn luma(color: &str) -> Option<u8> {
+    rgb(color).map(|rgb| rgb.to_luma()[0])
+}
+
+// Warn when the ink barely stands out from the tape
+fn contrast_warning(tape: &LoadedTape) -> Option<TapeWarning> {
+    let (ink, background) = (luma(&tape.ink_color)?, luma(&tape.tape_color)?);
+    (ink.abs_diff(background) < MIN_CONTRAST).then(|| TapeWarning {
+        element: None,
+        message: i18n::t(
+            "tape-low-contrast",
+            &[("ink", tape.ink.as_str().into()), ("tape", tape.tape.as_str().into())],
+        ),
+    })
+}
+
+/// Elements of `document` in colours too light to print, and ink that won't
+/// stand out from the tape
+pub fn warnings(document: &Document, tape: &LoadedTape) -> Vec<TapeWarning> {
+    let mut warnings: Vec<TapeWarning> = contrast_warning(tape).into_iter().collect();
+    for element in document.printable().elements {
+        let colors: Vec<&str> = match &element.kind {
+            ElementKind::Text(text) => std::iter::once(text.color.as_str())
+                .chain(text.runs.iter().filter_map(|run| run.color.as_deref()))
+                .collect(),
+            ElementKind::Barcode(barcode) => vec![barcode.color.as_str()],
+            ElementKind::Shape(shape) => std::iter::once(shape.stroke_color.as_str())
+                .filter(|_| shape.stroke_width_mm > 0.0)
+                .chain(shape.fill_color.as_deref())
+                .collect(),
+            ElementKind::Nutrition(nutrition) => vec![nutrition.color.as_str()],
+            // Photos are dithered; there's no one colour to judge
+            ElementKind::Image(_) => Vec::new(),
+        };
+        if let Some(color) = colors
+            .into_iter()
+            .find(|c| luma(c).is_some_and(|l| l >= PRINT_THRESHOLD))
+        {
+            warnings.push(TapeWarning {
+                element: Some(element.id),
+                message: i18n::t(
+                    "tape-element-too-light",
+                    &[
+                        ("id", element.id.into()),
+                        ("color", color.into()),
+                        ("tape", tape.tape.as_str().into()),
+                    ],
+                ),
+            });
+        }
+    }
+    warnings
+}
+
+/// Warnings for a label image made elsewhere, e.g. by the frontend: ink that
+/// won't stand out, and coloured areas too light to print. Greys are left
+/// alone, since every anti-aliased edge has them.
+pub fn image_warnings(png: &[u8], tape: &LoadedTape) -> Result<Vec<TapeWarning>, String> {
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+        .map_err(|e| format!("Failed to decode rendered page: {}", e))?
+        .to_rgb8();
+    let light = image
+        .pixels()
+        .filter(|pixel| {
+            let spread = pixel.0.iter().max().unwrap_or(&0) - pixel.0.iter().min().unwrap_or(&0);
+            spread > 40 && pixel.to_luma()[0] >= PRINT_THRESHOLD
+        })
+        .count();
+    let mut warnings: Vec<TapeWarning> = contrast_warning(tape).into_iter().collect();
+    // More than a speck
+    if light * 200 > image.pixels().len() {
+        warnings.push(TapeWarning {
+            element: None,
+            message: i18n::t("tape-light-areas", &[("tape", tape.tape.as_str().into())]),
+        });
+    }
+    Ok(warnings)
+}
+
+/// A print raster shown as it comes out on `tape`: dots in the ink colour
+/// on the tape colour
+pub fn preview(
+    image: &GrayImage,
+    dpi: f64,
+    tape: LoadedTape,
+    warnings: Vec<TapeWarning>,
+) -> Result<PrintRaster, String> {
+    let black = Rgb([0, 0, 0]);
+    let (ink, background) = (
+        rgb(&tape.ink_color).unwrap_or(black),
+        rgb(&tape.tape_color).unwrap_or(black),
+    );
+    let colored = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        if image.get_pixel(x, y)[0] < PRINT_THRESHOLD {
+            ink
+        } else {
+            background
+        }
+    });
+    let mut png = Vec::new();
+    colored
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode print raster: {}", e))?;
+    Ok(PrintRaster {
+        image: format!("data:image/png;base64,{}", BASE64.encode(png)),
+        width_px: image.width(),
+        height_px: image.height(),
+        dpi,
+        tape: Some(tape),
+        warnings,
+    })
+}
+
+/// The cassette loaded in `printer_name`, asking it now
+#[tauri::command]
+pub async fn get_loaded_tape(printer_name: String) -> Result<Option<LoadedTape>, String> {
+    detect(&printer_name).await
+}
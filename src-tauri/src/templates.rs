@@ -0,0 +1,269 @@
+// Template gallery: starter label designs with {placeholder} fields, plus
+// templates installed from template packs and designs from an optional
+// shared templates directory
+pub mod pack;
+
+use crate::document::Document;
+use crate::settings::SettingsStore;
+use notify::{Config, PollWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{error, warn};
+
+/// Event emitted to the frontend when the shared templates directory changes
+pub const TEMPLATES_CHANGED_EVENT: &str = "templates-changed";
+
+// Network shares don't deliver native change notifications reliably, so the
+// shared directory is polled
+const SHARED_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where templates installed from packs are kept; set once at startup
+static INSTALLED_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Starter templates are compiled into the binary so they ship with every build
+const BUNDLED_TEMPLATES: &[&str] = &[
+    include_str!("../templates/address.json"),
+    include_str!("../templates/shipping.json"),
+    include_str!("../templates/folder.json"),
+    include_str!("../templates/price.json"),
+    include_str!("../templates/cable_flag.json"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub category: String,
+    /// Placeholders used by the document, in the order they should be asked for
+    #[serde(default)]
+    pub fields: Vec<PlaceholderField>,
+    pub document: Document,
+    /// Loaded from the shared templates directory rather than bundled
+    #[serde(skip)]
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaceholderField {
+    pub name: String,
+    pub label: String,
+    #[serde(default)]
+    pub default: String,
+}
+
+/// Gallery entry without the document itself
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateSummary {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub shared: bool,
+}
+
+impl From<&Template> for TemplateSummary {
+    fn from(template: &Template) -> Self {
+        TemplateSummary {
+            id: template.id.clone(),
+            name: template.name.clone(),
+            description: template.description.clone(),
+            category: template.category.clone(),
+            width_mm: template.document.width_mm,
+            height_mm: template.document.height_mm,
+            shared: template.shared,
+        }
+    }
+}
+
+/// A fresh copy of a template's document, ready for its fields to be filled in
+#[derive(Debug, Clone, Serialize)]
+pub struct InstantiatedTemplate {
+    pub document: Document,
+    pub fields: Vec<PlaceholderField>,
+}
+
+pub fn bundled_templates() -> Result<Vec<Template>, String> {
+    BUNDLED_TEMPLATES
+        .iter()
+        .map(|json| {
+            serde_json::from_str(json).map_err(|e| format!("Failed to parse bundled template: {}", e))
+        })
+        .collect()
+}
+
+/// Keep templates installed from packs in `dir`; later calls have no effect
+pub fn init(dir: &Path) {
+    let _ = INSTALLED_DIR.set(dir.to_path_buf());
+}
+
+fn installed_dir() -> Result<&'static Path, String> {
+    INSTALLED_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| "Installing templates isn't available".to_string())
+}
+
+/// Templates from the shared directory. JSON files are full templates; any
+/// other label file Labelgood can import becomes a template named after the
+/// file. Unreadable files are skipped so one bad file can't hide the rest.
+pub fn shared_templates(dir: &Path) -> Vec<Template> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to read shared templates directory {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| match load_shared_template(path) {
+            Ok(template) => template,
+            Err(e) => {
+                warn!("Skipping shared template {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn load_shared_template(path: &Path) -> Result<Option<Template>, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    let mut template = if extension == "json" {
+        let json = fs::read_to_string(path).map_err(|e| format!("Failed to read template: {}", e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse template: {}", e))?
+    } else if crate::formats::is_importable(path) {
+        Template {
+            id: format!("shared:{}", file_name),
+            name: path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+            description: String::new(),
+            category: "Shared".to_string(),
+            fields: Vec::new(),
+            document: crate::formats::import(path)?,
+            shared: true,
+        }
+    } else {
+        return Ok(None);
+    };
+    template.shared = true;
+    Ok(Some(template))
+}
+
+/// Bundled templates followed by installed and shared ones. A later template
+/// with the same id as an earlier one replaces it, so a team can customise
+/// the starters.
+pub fn all_templates(shared_dir: Option<&Path>) -> Result<Vec<Template>, String> {
+    let mut templates = bundled_templates()?;
+    let mut added = Vec::new();
+    if let Some(dir) = installed_dir().ok().filter(|dir| dir.is_dir()) {
+        added.extend(shared_templates(dir).into_iter().map(|template| Template {
+            shared: false,
+            ..template
+        }));
+    }
+    if let Some(dir) = shared_dir {
+        added.extend(shared_templates(dir));
+    }
+    for template in added {
+        match templates.iter_mut().find(|t| t.id == template.id) {
+            Some(existing) => *existing = template,
+            None => templates.push(template),
+        }
+    }
+    Ok(templates)
+}
+
+pub fn find_template(id: &str, shared_dir: Option<&Path>) -> Result<Template, String> {
+    all_templates(shared_dir)?
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| format!("Template not found: {}", id))
+}
+
+/// Document of a gallery template id, or of a .lblg or importable label file
+/// when `template` is a path
+pub fn resolve_document(template: &str, shared_dir: Option<&Path>) -> Result<Document, String> {
+    if Path::new(template).is_file() {
+        crate::project::load_document(Path::new(template))
+    } else {
+        Ok(find_template(template, shared_dir)?.document)
+    }
+}
+
+/// Watches the shared templates directory and notifies the frontend when it changes
+#[derive(Default)]
+pub struct TemplateWatcher {
+    watcher: Mutex<Option<PollWatcher>>,
+}
+
+impl TemplateWatcher {
+    /// Start watching `dir`, replacing any previous watch. `None` stops watching.
+    pub fn watch(&self, app: &AppHandle, dir: Option<&Path>) -> Result<(), String> {
+        let mut current = self.watcher.lock().unwrap();
+        *current = None;
+        let Some(dir) = dir else {
+            return Ok(());
+        };
+
+        let app = app.clone();
+        let config = Config::default().with_poll_interval(SHARED_POLL_INTERVAL);
+        let mut watcher = PollWatcher::new(
+            move |result: notify::Result<notify::Event>| match result {
+                Ok(_) => {
+                    if let Err(e) = app.emit(TEMPLATES_CHANGED_EVENT, ()) {
+                        error!("Failed to emit {}: {}", TEMPLATES_CHANGED_EVENT, e);
+                    }
+                }
+                Err(e) => error!("Shared templates watch error: {}", e),
+            },
+            config,
+        )
+        .map_err(|e| format!("Failed to watch shared templates directory: {}", e))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch shared templates directory: {}", e))?;
+        *current = Some(watcher);
+        Ok(())
+    }
+}
+
+fn shared_dir(settings: &SettingsStore) -> Option<PathBuf> {
+    settings.get().shared_templates_dir.map(PathBuf::from)
+}
+
+#[tauri::command]
+pub async fn list_templates(settings: State<'_, SettingsStore>) -> Result<Vec<TemplateSummary>, String> {
+    let templates = all_templates(shared_dir(&settings).as_deref())?;
+    Ok(templates.iter().map(TemplateSummary::from).collect())
+}
+
+#[tauri::command]
+pub async fn instantiate_template(
+    settings: State<'_, SettingsStore>,
+    id: String,
+) -> Result<InstantiatedTemplate, String> {
+    let template = find_template(&id, shared_dir(&settings).as_deref())?;
+    Ok(InstantiatedTemplate {
+        document: template.document,
+        fields: template.fields,
+    })
+}
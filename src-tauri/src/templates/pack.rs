@@ -0,0 +1,247 @@
+// Template packs (.lblgpack): a set of templates with their images, the
+// fonts they use and named media sizes in one zip file, so a designer can
+// hand a complete label set to every station. Laid out like a project file:
+// a JSON manifest, the original bytes of each asset under assets/ and each
+// font file under fonts/.
+use super::{find_template, installed_dir, Template, TemplateSummary, TEMPLATES_CHANGED_EVENT};
+use crate::document::ElementKind;
+use crate::fonts::FontLibrary;
+use crate::settings::{MediaPreset, SettingsStore};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, State};
+use tracing::error;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const PACK_EXTENSION: &str = "lblgpack";
+/// Version written by this build
+const PACK_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "pack.json";
+const ASSETS_DIR: &str = "assets/";
+const FONTS_DIR: &str = "fonts/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    format_version: u32,
+    templates: Vec<Template>,
+    #[serde(default)]
+    fonts: Vec<PackedFont>,
+    #[serde(default)]
+    media_presets: Vec<MediaPreset>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PackedFont {
+    /// Entry name under fonts/
+    file_name: String,
+    families: Vec<String>,
+}
+
+/// What installing a pack added
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledPack {
+    pub templates: Vec<TemplateSummary>,
+    /// Font families that weren't available yet
+    pub fonts: Vec<String>,
+    pub media_presets: Vec<String>,
+}
+
+fn write(path: &Path, mut manifest: Manifest, font_files: &BTreeMap<String, PathBuf>) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create template pack: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let zip_err = |e: zip::result::ZipError| format!("Failed to write template pack: {}", e);
+    let io_err = |e: std::io::Error| format!("Failed to write template pack: {}", e);
+
+    // Images are already compressed, so they're stored as-is; templates
+    // sharing an image share its entry
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut written = BTreeSet::new();
+    for template in &mut manifest.templates {
+        for (id, asset) in template.document.assets.iter_mut() {
+            if written.insert(id.clone()) {
+                zip.start_file(format!("{}{}", ASSETS_DIR, id), stored)
+                    .map_err(zip_err)?;
+                zip.write_all(&asset.bytes()?).map_err(io_err)?;
+            }
+            asset.data.clear();
+        }
+    }
+    for (file_name, path) in font_files {
+        let data = fs::read(path).map_err(|e| format!("Failed to read font {}: {}", path.display(), e))?;
+        zip.start_file(format!("{}{}", FONTS_DIR, file_name), SimpleFileOptions::default())
+            .map_err(zip_err)?;
+        zip.write_all(&data).map_err(io_err)?;
+    }
+
+    let json =
+        serde_json::to_string_pretty(&manifest).map_err(|e| format!("Failed to serialize template pack: {}", e))?;
+    zip.start_file(MANIFEST_ENTRY, SimpleFileOptions::default())
+        .map_err(zip_err)?;
+    zip.write_all(json.as_bytes()).map_err(io_err)?;
+    zip.finish().map_err(zip_err)?;
+    Ok(())
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+// Installed templates are saved under their id, made safe as a file name
+fn template_file_name(id: &str) -> String {
+    let stem: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.json", stem)
+}
+
+/// Pack the templates `template_ids`, the font files of every family their
+/// text uses, and the media presets from the settings into `path`, .lblgpack
+/// added when it has no extension
+#[tauri::command]
+pub async fn export_template_pack(
+    settings: State<'_, SettingsStore>,
+    fonts: State<'_, FontLibrary>,
+    template_ids: Vec<String>,
+    path: String,
+) -> Result<(), String> {
+    let current = settings.get();
+    let shared_dir = current.shared_templates_dir.as_deref().map(Path::new);
+    let templates = template_ids
+        .iter()
+        .map(|id| find_template(id, shared_dir))
+        .collect::<Result<Vec<Template>, String>>()?;
+    if templates.is_empty() {
+        return Err("A template pack needs at least one template".to_string());
+    }
+
+    let families: BTreeSet<&str> = templates
+        .iter()
+        .flat_map(|template| &template.document.elements)
+        .filter_map(|element| match &element.kind {
+            ElementKind::Text(text) => Some(text.font_family.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut font_files = BTreeMap::new();
+    let mut packed_fonts: BTreeMap<String, PackedFont> = BTreeMap::new();
+    for family in families {
+        for path in fonts.font_files(family) {
+            let Some(file_name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                continue;
+            };
+            packed_fonts
+                .entry(file_name.clone())
+                .or_insert_with(|| PackedFont {
+                    file_name: file_name.clone(),
+                    families: Vec::new(),
+                })
+                .families
+                .push(family.to_string());
+            font_files.entry(file_name).or_insert(path);
+        }
+    }
+
+    let manifest = Manifest {
+        format_version: PACK_FORMAT_VERSION,
+        templates,
+        fonts: packed_fonts.into_values().collect(),
+        media_presets: current.media_presets,
+    };
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension(PACK_EXTENSION);
+    }
+    write(&path, manifest, &font_files)
+}
+
+/// Install the template pack at `path`: its templates join the gallery, its
+/// fonts the font library and its media presets the settings. Templates and
+/// presets replace installed ones with the same id or name.
+#[tauri::command]
+pub async fn import_template_pack(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    fonts: State<'_, FontLibrary>,
+    path: String,
+) -> Result<InstalledPack, String> {
+    let bytes = fs::read(&path).map_err(|e| format!("Failed to read template pack: {}", e))?;
+    let mut archive =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Template pack is not a valid bundle: {}", e))?;
+    let json = read_entry(&mut archive, MANIFEST_ENTRY).ok_or("Template pack has no pack.json")?;
+    let mut manifest: Manifest =
+        serde_json::from_slice(&json).map_err(|e| format!("Template pack is invalid: {}", e))?;
+    if manifest.format_version > PACK_FORMAT_VERSION {
+        return Err(format!(
+            "This template pack was made by a newer version of Labelgood (format version {}, this version \
+             supports up to {}). Please update Labelgood to install it.",
+            manifest.format_version, PACK_FORMAT_VERSION
+        ));
+    }
+
+    // Fonts whose families are all available already aren't installed twice
+    let known: BTreeSet<String> = fonts.families().into_iter().map(|f| f.family.to_lowercase()).collect();
+    let mut added_families = BTreeSet::new();
+    for font in &manifest.fonts {
+        if font
+            .families
+            .iter()
+            .all(|family| known.contains(&family.to_lowercase()))
+        {
+            continue;
+        }
+        let data = read_entry(&mut archive, &format!("{}{}", FONTS_DIR, font.file_name))
+            .ok_or_else(|| format!("Template pack is missing font {}", font.file_name))?;
+        added_families.extend(fonts.add_font_data(&font.file_name, data)?);
+    }
+
+    let dir = installed_dir()?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create templates directory: {}", e))?;
+    for template in &mut manifest.templates {
+        for (id, asset) in template.document.assets.iter_mut() {
+            if asset.data.is_empty() {
+                let bytes = read_entry(&mut archive, &format!("{}{}", ASSETS_DIR, id))
+                    .ok_or_else(|| format!("Template pack is missing asset {}", asset.file_name))?;
+                asset.data = BASE64.encode(bytes);
+            }
+        }
+        let json =
+            serde_json::to_string_pretty(template).map_err(|e| format!("Failed to serialize template: {}", e))?;
+        fs::write(dir.join(template_file_name(&template.id)), json)
+            .map_err(|e| format!("Failed to install template {}: {}", template.name, e))?;
+    }
+
+    if !manifest.media_presets.is_empty() {
+        let mut current = settings.get();
+        for preset in &manifest.media_presets {
+            current.media_presets.retain(|p| p.name != preset.name);
+            current.media_presets.push(preset.clone());
+        }
+        settings.set(current)?;
+    }
+
+    if let Err(e) = app.emit(TEMPLATES_CHANGED_EVENT, ()) {
+        error!("Failed to emit {}: {}", TEMPLATES_CHANGED_EVENT, e);
+    }
+    Ok(InstalledPack {
+        templates: manifest.templates.iter().map(TemplateSummary::from).collect(),
+        fonts: added_families.into_iter().collect(),
+        media_presets: manifest.media_presets.into_iter().map(|p| p.name).collect(),
+    })
+}
@@ -0,0 +1,38 @@
+// Hooks for the integration tests, built with the `mock-printer` feature
+// only: a scratch directory for captured jobs and app data, set without
+// changing the environment of a process whose tests run in parallel, and
+// the app's own render and print steps, which otherwise need a window.
+use crate::document::Document;
+use crate::plugins::mock;
+use crate::printing;
+use crate::render;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Resolution labels are rendered and printed at
+pub const RENDER_DPI: f64 = printing::RENDER_DPI;
+
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Capture "mock:NAME" jobs to `dir`/printed/NAME and keep the CLI's fonts
+/// and counters in `dir`/data. Only the first call counts.
+pub fn use_scratch_dir(dir: &Path) {
+    mock::capture_to(dir.join("printed"));
+    let _ = DATA_DIR.set(dir.join("data"));
+}
+
+/// The data directory `use_scratch_dir` set
+pub fn data_dir() -> Option<&'static PathBuf> {
+    DATA_DIR.get()
+}
+
+/// A blank `width_mm` x `height_mm` label as PNG, rendered as for printing
+pub fn render_blank(width_mm: f64, height_mm: f64) -> Result<Vec<u8>, String> {
+    let document = Document::new(width_mm, height_mm);
+    render::render_png(&document, Arc::new(fontdb::Database::new()), RENDER_DPI)
+}
+
+/// Print the label in `png` as the app's Print button does
+pub async fn print_png(png: &Path, width_mm: f64, height_mm: f64, printer_name: &str) -> Result<String, String> {
+    Ok(printing::print_png(png, width_mm, height_mm, Some(printer_name)).await?)
+}
@@ -0,0 +1,129 @@
+// Small PNG previews of saved projects and gallery templates for the start
+// screen and template picker. Thumbnails are cached in the app data dir,
+// keyed by what they show: a project file's path, size and modification
+// time, or a template's content, so an edited design gets a new one.
+use crate::document::Document;
+use crate::fonts::FontLibrary;
+use crate::project;
+use crate::render;
+use crate::settings::SettingsStore;
+use crate::templates;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::State;
+use tracing::warn;
+
+/// Longest side of a thumbnail in pixels, unless asked otherwise
+const DEFAULT_SIZE_PX: u32 = 256;
+const MAX_SIZE_PX: u32 = 1024;
+
+pub struct Thumbnails {
+    dir: PathBuf,
+}
+
+impl Thumbnails {
+    pub fn new(dir: PathBuf) -> Self {
+        Thumbnails { dir }
+    }
+
+    /// The cached thumbnail for `key`, rendering and storing it first if needed
+    pub fn get(
+        &self,
+        key: &[u8],
+        size: u32,
+        fonts: &FontLibrary,
+        document: impl FnOnce() -> Result<Document, String>,
+    ) -> Result<String, String> {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        hasher.update(size.to_le_bytes());
+        let name: String = hasher
+            .finalize()
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let path = self.dir.join(format!("{}.png", name));
+
+        let png = match fs::read(&path) {
+            Ok(png) => png,
+            Err(_) => {
+                let document = document()?;
+                let longest_mm = document.width_mm.max(document.height_mm);
+                if longest_mm <= 0.0 {
+                    return Err("Can't preview a label without a size".to_string());
+                }
+                let dpi = size as f64 / longest_mm * 25.4;
+                let png = render::render_png(&document, fonts.database(), dpi)?;
+                // A thumbnail that can't be cached is still worth showing
+                if let Err(e) = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, &png)) {
+                    warn!("Failed to cache thumbnail {}: {}", path.display(), e);
+                }
+                png
+            }
+        };
+        Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+    }
+
+    /// Remove every cached thumbnail
+    pub fn clear(&self) -> Result<(), String> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to clear thumbnails: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Thumbnail size asked for, defaulted and clamped
+pub fn thumbnail_size(size: Option<u32>) -> u32 {
+    size.unwrap_or(DEFAULT_SIZE_PX).clamp(16, MAX_SIZE_PX)
+}
+
+/// PNG data URL previewing a .lblg project or importable label file, at
+/// most `size` pixels on its longest side
+#[tauri::command]
+pub async fn get_project_thumbnail(
+    thumbnails: State<'_, Thumbnails>,
+    fonts: State<'_, FontLibrary>,
+    path: String,
+    size: Option<u32>,
+) -> Result<String, String> {
+    let file = Path::new(&path);
+    let metadata = fs::metadata(file).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos());
+    let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    let key = format!("project\0{}\0{}\0{}", canonical.display(), metadata.len(), modified);
+    thumbnails.get(key.as_bytes(), thumbnail_size(size), &fonts, || {
+        project::load_document(file)
+    })
+}
+
+/// PNG data URL previewing gallery template `id`, at most `size` pixels on
+/// its longest side
+#[tauri::command]
+pub async fn get_template_thumbnail(
+    thumbnails: State<'_, Thumbnails>,
+    fonts: State<'_, FontLibrary>,
+    settings: State<'_, SettingsStore>,
+    id: String,
+    size: Option<u32>,
+) -> Result<String, String> {
+    let shared_dir = settings.get().shared_templates_dir.map(PathBuf::from);
+    let template = templates::find_template(&id, shared_dir.as_deref())?;
+    let json = serde_json::to_vec(&template.document).map_err(|e| format!("Failed to serialize template: {}", e))?;
+    let key = [b"template\0".as_slice(), &json].concat();
+    thumbnails.get(&key, thumbnail_size(size), &fonts, || Ok(template.document))
+}
+
+#[tauri::command]
+pub fn clear_thumbnails(thumbnails: State<'_, Thumbnails>) -> Result<(), String> {
+    thumbnails.clear()
+}
@@ -0,0 +1,158 @@
+// Where the external programs Labelgood runs live. A bare name only works
+// when the program is on PATH, which it often isn't: apps started from the
+// macOS Dock don't see Homebrew's PATH, Snap and Flatpak builds have their
+// own, and some installs put tools in /usr/local or /opt. Programs are
+// looked for, in order:
+//
+//   1. the path set for the program in the settings
+//   2. PATH
+//   3. the usual install directories: /usr/bin, /usr/local/bin, Homebrew,
+//      MacPorts, /snap/bin and the Snap's own directories
+//   4. inside Flatpak, on the host through `flatpak-spawn --host`, which
+//      needs the org.freedesktop.Flatpak talk permission; host programs only
+//      see files in directories shared with the host
+use crate::sandbox::{self, Sandbox};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Programs Labelgood can run, for the settings screen
+pub const KNOWN_TOOLS: &[&str] = &[
+    "magick",
+    "convert",
+    "gm",
+    "lpr",
+    "lp",
+    "lpstat",
+    "lpoptions",
+    "cancel",
+    "scanimage",
+    "tesseract",
+    "hunspell",
+    "rembg",
+    "xdg-email",
+];
+
+const SEARCH_DIRS: &[&str] = &[
+    "/usr/bin",
+    "/usr/local/bin",
+    "/opt/homebrew/bin",
+    "/home/linuxbrew/.linuxbrew/bin",
+    "/opt/local/bin",
+    "/snap/bin",
+];
+
+static OVERRIDES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+// Found programs; misses aren't kept, so installing one takes effect without
+// a restart
+static FOUND: Mutex<Option<HashMap<String, PathBuf>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolLocation {
+    pub program: String,
+    /// Where it was found; None when it's missing or only on the Flatpak host
+    pub path: Option<String>,
+    /// The path comes from the settings
+    pub overridden: bool,
+    /// Run on the host, outside the Flatpak sandbox
+    pub host: bool,
+}
+
+/// Use the paths set in the settings, by program name
+pub fn configure(overrides: BTreeMap<String, String>) {
+    *OVERRIDES.lock().unwrap() = overrides;
+    *FOUND.lock().unwrap() = None;
+}
+
+fn executable(dir: &Path, program: &str) -> Option<PathBuf> {
+    let candidate = dir.join(format!("{}{}", program, std::env::consts::EXE_SUFFIX));
+    candidate.is_file().then_some(candidate)
+}
+
+fn overridden(program: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(OVERRIDES.lock().unwrap().get(program).filter(|p| !p.is_empty())?);
+    if path.is_file() {
+        Some(path)
+    } else {
+        warn!(
+            "{} is set to {}, which doesn't exist; looking for it elsewhere",
+            program,
+            path.display()
+        );
+        None
+    }
+}
+
+fn search(program: &str) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(SEARCH_DIRS.iter().map(PathBuf::from));
+    // Tools bundled into the Snap
+    if let Some(snap) = std::env::var_os("SNAP") {
+        dirs.extend(["usr/bin", "bin"].iter().map(|dir| Path::new(&snap).join(dir)));
+    }
+    dirs.iter().find_map(|dir| executable(dir, program))
+}
+
+/// Where `program` is, if it can be found in the sandbox or on this system
+pub fn resolve(program: &str) -> Option<PathBuf> {
+    if let Some(path) = overridden(program) {
+        return Some(path);
+    }
+    if let Some(path) = FOUND.lock().unwrap().as_ref().and_then(|found| found.get(program)) {
+        return Some(path.clone());
+    }
+    let path = search(program)?;
+    info!("Using {} at {}", program, path.display());
+    FOUND
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(program.to_string(), path.clone());
+    Some(path)
+}
+
+// Inside Flatpak, whether `program` can be run on the host instead
+fn on_host(program: &str) -> bool {
+    sandbox::detect() == Some(Sandbox::Flatpak) && resolve("flatpak-spawn").is_some() && program != "flatpak-spawn"
+}
+
+/// A command running `program`, wherever it was found. A program that can't
+/// be found is run by its bare name, so starting it fails with the usual
+/// error.
+pub fn command(program: &str) -> Command {
+    match resolve(program) {
+        Some(path) => Command::new(path),
+        None if on_host(program) => {
+            let mut command = Command::new("flatpak-spawn");
+            command.arg("--host").arg(program);
+            command
+        }
+        None => Command::new(program),
+    }
+}
+
+/// Where each program Labelgood can run was found
+#[tauri::command]
+pub fn locate_tools() -> Vec<ToolLocation> {
+    KNOWN_TOOLS
+        .iter()
+        .map(|program| {
+            let path = resolve(program);
+            ToolLocation {
+                program: program.to_string(),
+                overridden: OVERRIDES
+                    .lock()
+                    .unwrap()
+                    .get(*program)
+                    .is_some_and(|p| path.as_deref() == Some(Path::new(p))),
+                host: path.is_none() && on_host(program),
+                path: path.map(|p| p.display().to_string()),
+            }
+        })
+        .collect()
+}
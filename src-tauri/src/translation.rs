@@ -0,0 +1,137 @@
+// Machine translation of label text, so one source template can be filled in
+// for every market an export product ships to. DeepL or a LibreTranslate
+// instance, called only when asked; labels are never translated behind the
+// user's back.
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::State;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+const DEEPL_URL: &str = "https://api.deepl.com/v2/translate";
+const DEEPL_FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+    /// Off when absent
+    pub service: Option<TranslationService>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TranslationService {
+    /// Keys of free accounts end in ":fx" and go to DeepL's free API
+    Deepl { api_key: String },
+    /// A LibreTranslate instance, e.g. `https://translate.example.com`
+    LibreTranslate {
+        url: String,
+        #[serde(default)]
+        api_key: Option<String>,
+    },
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into()
+}
+
+// POST `body` and read the JSON answer
+fn post(url: &str, authorization: Option<&str>, body: Value) -> Result<Value, String> {
+    let mut request = agent().post(url);
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    request
+        .send_json(body)
+        .map_err(|e| format!("Failed to reach translation service: {}", e))?
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("Translation service returned invalid JSON: {}", e))
+}
+
+/// `text` in `target`, a language code like "de" or "en-GB"; from
+/// `source`, or the language the service detects
+pub fn translate(
+    service: &TranslationService,
+    text: &str,
+    source: Option<&str>,
+    target: &str,
+) -> Result<String, String> {
+    match service {
+        TranslationService::Deepl { api_key } => {
+            let url = if api_key.ends_with(":fx") {
+                DEEPL_FREE_URL
+            } else {
+                DEEPL_URL
+            };
+            let mut body = json!({ "text": [text], "target_lang": target.to_uppercase() });
+            if let Some(source) = source {
+                // DeepL takes source languages without a region
+                let language = source.split(['-', '_']).next().unwrap_or(source);
+                body["source_lang"] = json!(language.to_uppercase());
+            }
+            let response = post(url, Some(&format!("DeepL-Auth-Key {}", api_key)), body)?;
+            response["translations"][0]["text"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| "DeepL returned no translation".to_string())
+        }
+        TranslationService::LibreTranslate { url, api_key } => {
+            let mut body = json!({
+                "q": text,
+                "source": source.unwrap_or("auto").to_lowercase(),
+                "target": target.to_lowercase(),
+                "format": "text",
+            });
+            if let Some(api_key) = api_key.as_deref().filter(|key| !key.is_empty()) {
+                body["api_key"] = json!(api_key);
+            }
+            let response = post(&format!("{}/translate", url.trim_end_matches('/')), None, body)?;
+            if let Some(error) = response["error"].as_str() {
+                return Err(format!("LibreTranslate: {}", error));
+            }
+            response["translatedText"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| "LibreTranslate returned no translation".to_string())
+        }
+    }
+}
+
+/// `text` translated into each of `target_langs`, keyed by language code
+#[tauri::command]
+pub async fn translate_text(
+    settings: State<'_, SettingsStore>,
+    text: String,
+    target_langs: Vec<String>,
+    source_lang: Option<String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let service = settings
+        .get()
+        .translation
+        .service
+        .ok_or("No translation service is configured")?;
+    if text.trim().is_empty() {
+        return Ok(target_langs.into_iter().map(|lang| (lang, String::new())).collect());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut translations = BTreeMap::new();
+        for target in target_langs {
+            if translations.contains_key(&target) {
+                continue;
+            }
+            let translated = translate(&service, &text, source_lang.as_deref(), &target)
+                .map_err(|e| format!("Failed to translate into {}: {}", target, e))?;
+            translations.insert(target, translated);
+        }
+        Ok::<_, String>(translations)
+    })
+    .await
+    .map_err(|e| format!("Failed to translate: {}", e))?
+}
@@ -0,0 +1,168 @@
+// Tray icon for quick printing: pinned templates print with their default
+// field values on the saved printer, without opening the main window
+use crate::api::{self, PrintRequest};
+use crate::merge::CheckDigitPolicy;
+use crate::queue::Priority;
+use crate::settings::SettingsStore;
+use crate::templates;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Wry};
+use tracing::{error, info};
+
+const TRAY_ID: &str = "main";
+
+// Menu item ids carry their argument after the prefix
+const PRINT_PREFIX: &str = "print:";
+const PRINTER_PREFIX: &str = "printer:";
+const SHOW_ID: &str = "show";
+const QUIT_ID: &str = "quit";
+
+/// Create the tray icon. Printers are filled in once CUPS has answered, so a
+/// slow network printer doesn't hold up startup.
+pub fn create(app: &AppHandle) -> Result<(), String> {
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Labelgood")
+        .menu(&build_menu(app, Vec::new())?)
+        .show_menu_on_left_click(true)
+        .on_menu_event(|app, event| handle_menu_event(app, event.id().as_ref()));
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app).map_err(|e| format!("Failed to create tray icon: {}", e))?;
+    spawn_refresh(app);
+    Ok(())
+}
+
+/// Rebuild the menu after pinned templates or the default printer change
+pub async fn refresh(app: &AppHandle) -> Result<(), String> {
+    let printers = crate::list_printers().await.unwrap_or_else(|e| {
+        error!("{}", e);
+        Vec::new()
+    });
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    tray.set_menu(Some(build_menu(app, printers)?))
+        .map_err(|e| format!("Failed to update tray menu: {}", e))
+}
+
+fn spawn_refresh(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = refresh(&app).await {
+            error!("{}", e);
+        }
+    });
+}
+
+fn build_menu(app: &AppHandle, printers: Vec<String>) -> Result<Menu<Wry>, String> {
+    let settings = app.state::<SettingsStore>().get();
+    let shared_dir = settings.shared_templates_dir.as_deref().map(Path::new);
+    // The gallery is only needed for names; a broken share still lists the
+    // pinned entries
+    let gallery = templates::all_templates(shared_dir).unwrap_or_default();
+
+    let menu_error = |e: tauri::Error| format!("Failed to build tray menu: {}", e);
+    let mut items: Vec<Box<dyn IsMenuItem<Wry>>> = Vec::new();
+    for template in &settings.pinned_templates {
+        let name = gallery
+            .iter()
+            .find(|t| &t.id == template)
+            .map(|t| t.name.clone())
+            .unwrap_or_else(|| {
+                let stem = Path::new(template).file_stem().unwrap_or_default();
+                stem.to_string_lossy().to_string()
+            });
+        let id = format!("{}{}", PRINT_PREFIX, template);
+        let item = MenuItem::with_id(app, id, format!("Print {}", name), true, None::<&str>).map_err(menu_error)?;
+        items.push(Box::new(item));
+    }
+    if items.is_empty() {
+        let item = MenuItem::new(app, "No pinned templates", false, None::<&str>).map_err(menu_error)?;
+        items.push(Box::new(item));
+    }
+    items.push(Box::new(PredefinedMenuItem::separator(app).map_err(menu_error)?));
+
+    // Choosing a printer saves it as the default for quick printing
+    let mut printer_items = vec![CheckMenuItem::with_id(
+        app,
+        PRINTER_PREFIX,
+        "Open PDF",
+        true,
+        settings.last_printer.is_none(),
+        None::<&str>,
+    )
+    .map_err(menu_error)?];
+    for printer in printers {
+        let checked = settings.last_printer.as_deref() == Some(printer.as_str());
+        let id = format!("{}{}", PRINTER_PREFIX, printer);
+        printer_items.push(CheckMenuItem::with_id(app, id, &printer, true, checked, None::<&str>).map_err(menu_error)?);
+    }
+    let printer_refs: Vec<&dyn IsMenuItem<Wry>> = printer_items.iter().map(|i| i as &dyn IsMenuItem<Wry>).collect();
+    items.push(Box::new(Submenu::with_items(app, "Printer", true, &printer_refs).map_err(menu_error)?));
+
+    items.push(Box::new(PredefinedMenuItem::separator(app).map_err(menu_error)?));
+    items.push(Box::new(MenuItem::with_id(app, SHOW_ID, "Show Labelgood", true, None::<&str>).map_err(menu_error)?));
+    items.push(Box::new(MenuItem::with_id(app, QUIT_ID, "Quit", true, None::<&str>).map_err(menu_error)?));
+
+    let refs: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|i| i.as_ref()).collect();
+    Menu::with_items(app, &refs).map_err(menu_error)
+}
+
+fn handle_menu_event(app: &AppHandle, id: &str) {
+    if let Some(template) = id.strip_prefix(PRINT_PREFIX) {
+        let app = app.clone();
+        let template = template.to_string();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = quick_print(&app, "tray", &template).await {
+                error!("Quick print of {} failed: {}", template, e);
+            }
+        });
+    } else if let Some(printer) = id.strip_prefix(PRINTER_PREFIX) {
+        if let Err(e) = set_default_printer(app, printer) {
+            error!("{}", e);
+        }
+    } else if id == SHOW_ID {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    } else if id == QUIT_ID {
+        app.exit(0);
+    }
+}
+
+fn set_default_printer(app: &AppHandle, printer: &str) -> Result<(), String> {
+    let store = app.state::<SettingsStore>();
+    let mut settings = store.get();
+    settings.last_printer = (!printer.is_empty()).then(|| printer.to_string());
+    store.set(settings)?;
+    // Rebuilt so the check marks follow the choice
+    spawn_refresh(app);
+    Ok(())
+}
+
+/// Print one label of a template, filling its fields with their defaults,
+/// on the default printer
+pub async fn quick_print(app: &AppHandle, source: &str, template: &str) -> Result<(), String> {
+    let settings = app.state::<SettingsStore>().get();
+    let shared_dir = settings.shared_templates_dir.map(PathBuf::from);
+    let record: BTreeMap<String, String> = templates::find_template(template, shared_dir.as_deref())
+        .map(|t| t.fields.into_iter().map(|f| (f.name, f.default)).collect())
+        .unwrap_or_default();
+
+    let request = PrintRequest {
+        template: template.to_string(),
+        definition: None,
+        records: vec![record],
+        printer: settings.last_printer,
+        check_digits: CheckDigitPolicy::Reject,
+        priority: Priority::Normal,
+    };
+    let response = api::print_records(app, source, request).await?;
+    info!("Quick print of {}: {}", template, response.message);
+    Ok(())
+}
@@ -0,0 +1,135 @@
+// New version checks against the project's GitHub releases, for installs
+// that don't update themselves, like the AppImage. Nothing is downloaded or
+// installed; the frontend shows the changelog and links to the release.
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info};
+
+/// Emitted with an `UpdateInfo` when the startup check finds a newer version
+pub const UPDATE_AVAILABLE_EVENT: &str = "update-available";
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/NotCoffee418/Labelgood/releases/latest";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest_version: String,
+    pub update_available: bool,
+    /// Release notes, as Markdown
+    pub changelog: String,
+    /// Release page
+    pub url: String,
+    /// Download for this platform, e.g. the AppImage, when the release has one
+    pub download_url: Option<String>,
+    pub published_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    html_url: String,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+// File name endings of this platform's downloads, preferred first
+fn asset_suffixes() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &[".msi", "-setup.exe", ".exe"]
+    } else if cfg!(target_os = "macos") {
+        &[".dmg"]
+    } else {
+        &[".AppImage", ".deb", ".rpm"]
+    }
+}
+
+fn download_url(assets: &[Asset]) -> Option<String> {
+    let arch: &[&str] = match std::env::consts::ARCH {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        _ => &[],
+    };
+    asset_suffixes().iter().find_map(|suffix| {
+        assets
+            .iter()
+            .filter(|asset| asset.name.ends_with(suffix))
+            // Builds for one architecture only don't always say which
+            .find(|asset| arch.iter().any(|a| asset.name.contains(a)))
+            .or_else(|| assets.iter().find(|asset| asset.name.ends_with(suffix)))
+            .map(|asset| asset.browser_download_url.clone())
+    })
+}
+
+// "v1.2.0" or "1.2.0" as a version; tags that aren't one never count as
+// newer
+fn parse_version(tag: &str) -> Option<semver::Version> {
+    semver::Version::parse(tag.trim().trim_start_matches('v')).ok()
+}
+
+/// The latest release and whether it's newer than this build
+pub fn check() -> Result<UpdateInfo, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into();
+    let current_version = env!("CARGO_PKG_VERSION").to_string();
+    let release: Release = agent
+        .get(LATEST_RELEASE_URL)
+        .header("Accept", "application/vnd.github+json")
+        // GitHub refuses API requests without one
+        .header("User-Agent", &format!("Labelgood/{}", current_version))
+        .call()
+        .map_err(|e| format!("Failed to check for updates: {}", e))?
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("Failed to read the latest release: {}", e))?;
+
+    let update_available = match (parse_version(&release.tag_name), parse_version(&current_version)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    };
+    Ok(UpdateInfo {
+        latest_version: release.tag_name.trim_start_matches('v').to_string(),
+        current_version,
+        update_available,
+        changelog: release.body.unwrap_or_default(),
+        url: release.html_url,
+        download_url: download_url(&release.assets),
+        published_at: release.published_at,
+    })
+}
+
+/// Check in the background and tell the frontend when there's a newer
+/// version; failures are only logged, e.g. when offline
+pub fn check_at_startup(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || match check() {
+        Ok(update) if update.update_available => {
+            info!("Labelgood {} is available", update.latest_version);
+            if let Err(e) = app.emit(UPDATE_AVAILABLE_EVENT, &update) {
+                error!("Failed to emit {}: {}", UPDATE_AVAILABLE_EVENT, e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => info!("{}", e),
+    });
+}
+
+/// The latest release, its changelog and whether it's newer than this build
+#[tauri::command]
+pub async fn check_for_updates() -> Result<UpdateInfo, String> {
+    tauri::async_runtime::spawn_blocking(check)
+        .await
+        .map_err(|e| e.to_string())?
+}
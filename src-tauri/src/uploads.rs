@@ -0,0 +1,104 @@
+// Chunked uploads of large label images. A banner-length label at 300 DPI is
+// hundreds of megabytes as a base64 string, so the frontend can instead send
+// the PNG as raw binary chunks, which are appended to a temp file and never
+// held in memory as a whole:
+//
+//   const id = await invoke("begin_upload");
+//   for (const chunk of chunks) {
+//     await invoke("append_upload", chunk, { headers: { "upload-id": id } });
+//   }
+//   await invoke("generate_pdf", { options: { upload_id: id, ... } });
+use crate::error::LabelgoodError;
+use crate::i18n;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::ipc::{InvokeBody, Request};
+use tauri::State;
+use tempfile::{Builder, NamedTempFile};
+
+/// Header naming the upload a chunk belongs to
+const UPLOAD_ID_HEADER: &str = "upload-id";
+/// Largest upload accepted, well above a 2m label at 300 DPI
+const MAX_UPLOAD_BYTES: u64 = 1024 * 1024 * 1024;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+struct Upload {
+    file: NamedTempFile,
+    bytes: u64,
+}
+
+/// Uploads in progress, until they're taken by the command they were for
+#[derive(Default)]
+pub struct Uploads {
+    uploads: Mutex<HashMap<String, Upload>>,
+    next_id: AtomicU64,
+}
+
+impl Uploads {
+    /// The finished upload `id` as a PNG temp file, removed from the uploads
+    pub fn take_png(&self, id: &str) -> Result<NamedTempFile, LabelgoodError> {
+        let upload = self.uploads.lock().unwrap().remove(id).ok_or_else(|| LabelgoodError::InvalidInput {
+            message: i18n::t("upload-unknown", &[("id", id.into())]),
+        })?;
+        let mut signature = [0u8; 8];
+        let is_png = File::open(upload.file.path())
+            .and_then(|mut file| file.read_exact(&mut signature))
+            .is_ok_and(|_| signature == PNG_SIGNATURE);
+        if !is_png {
+            return Err(LabelgoodError::InvalidInput {
+                message: i18n::t("upload-not-png", &[("id", id.into()), ("bytes", upload.bytes.into())]),
+            });
+        }
+        Ok(upload.file)
+    }
+}
+
+#[tauri::command]
+pub fn begin_upload(uploads: State<'_, Uploads>) -> Result<String, String> {
+    let file = Builder::new()
+        .prefix("label_upload_")
+        .suffix(".png")
+        .tempfile()
+        .map_err(|e| format!("Failed to create upload file: {}", e))?;
+    let id = format!("upload-{}", uploads.next_id.fetch_add(1, Ordering::Relaxed));
+    uploads.uploads.lock().unwrap().insert(id.clone(), Upload { file, bytes: 0 });
+    Ok(id)
+}
+
+/// Append the raw request body to an upload. Returns the bytes received so far.
+#[tauri::command]
+pub fn append_upload(uploads: State<'_, Uploads>, request: Request<'_>) -> Result<u64, String> {
+    let id = request
+        .headers()
+        .get(UPLOAD_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or("Missing upload-id header")?;
+    let InvokeBody::Raw(chunk) = request.body() else {
+        return Err(i18n::t("upload-not-binary", &[]));
+    };
+
+    let mut uploads = uploads.uploads.lock().unwrap();
+    let upload = uploads
+        .get_mut(id)
+        .ok_or_else(|| i18n::t("upload-unknown", &[("id", id.into())]))?;
+    if upload.bytes + chunk.len() as u64 > MAX_UPLOAD_BYTES {
+        uploads.remove(id);
+        return Err(i18n::t("upload-too-large", &[("id", id.into()), ("max", MAX_UPLOAD_BYTES.into())]));
+    }
+    upload
+        .file
+        .write_all(chunk)
+        .map_err(|e| format!("Failed to write upload: {}", e))?;
+    upload.bytes += chunk.len() as u64;
+    Ok(upload.bytes)
+}
+
+/// Drop an upload that won't be used, deleting its file
+#[tauri::command]
+pub fn discard_upload(uploads: State<'_, Uploads>, id: String) {
+    uploads.uploads.lock().unwrap().remove(&id);
+}
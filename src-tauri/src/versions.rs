@@ -0,0 +1,451 @@
+// Version snapshots of saved projects, kept in a folder next to the project
+// ("Label.lblg.versions"), so a good design overwritten right before a big
+// print run can be brought back. Saving over a project snapshots what was
+// there first; snapshots can also be taken by hand with a label such as
+// "v2 - new logo". Each snapshot is the project manifest as JSON, and assets
+// are stored once in assets/ by their content hash, since most snapshots
+// share them. Asset ids come from the project file, so they're never used
+// as file names.
+use crate::document::{self, Asset, Document};
+use crate::fonts::FontLibrary;
+use crate::project::{self, Project};
+use crate::render;
+use crate::thumbnails::{self, Thumbnails};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{SecondsFormat, Utc};
+use image::{ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing::{info, warn};
+
+const ASSETS_DIR: &str = "assets";
+
+/// Snapshots taken on save that are kept; labelled ones are never dropped
+const MAX_AUTOMATIC: usize = 30;
+
+// Channel difference below which pixels count as unchanged, so anti-aliasing
+// noise doesn't show
+const DIFF_TOLERANCE: u8 = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    created_at: String,
+    label: Option<String>,
+    automatic: bool,
+    /// Project manifest, asset data left out
+    project: Value,
+    /// Content hash in assets/ of each asset, by asset id
+    #[serde(default)]
+    blobs: BTreeMap<String, String>,
+}
+
+/// A snapshot as listed
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentVersion {
+    pub id: String,
+    /// UTC, RFC 3339
+    pub created_at: String,
+    pub label: Option<String>,
+    /// Taken on save rather than by hand
+    pub automatic: bool,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    pub elements: usize,
+}
+
+/// Two versions side by side, with what changed highlighted
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionComparison {
+    /// PNG data URLs
+    pub before: String,
+    pub after: String,
+    /// `after` faded with changed pixels in red; None when the label size
+    /// changed
+    pub diff: Option<String>,
+    /// Share of the label that changed, 0 to 100
+    pub changed_percent: f64,
+    /// Element ids only in `after`, only in `before`, and in both but changed
+    pub added: Vec<u32>,
+    pub removed: Vec<u32>,
+    pub changed: Vec<u32>,
+}
+
+// e.g. "Label.lblg.versions" next to "Label.lblg"
+fn versions_dir(project_path: &Path) -> PathBuf {
+    let name = project_path
+        .file_name()
+        .map_or_else(|| "project".to_string(), |name| name.to_string_lossy().to_string());
+    project_path.with_file_name(format!("{}.versions", name))
+}
+
+// Version ids are timestamps; anything else could reach outside the folder
+fn snapshot_path(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid version id: {}", id));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+// A stored asset; blob names are content hashes, anything else could reach
+// outside the folder
+fn blob_path(dir: &Path, blob: &str) -> Result<PathBuf, String> {
+    if blob.len() != 24 || !blob.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid asset in version: {}", blob));
+    }
+    Ok(dir.join(ASSETS_DIR).join(blob))
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read version {}: {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Version {} is invalid: {}", path.display(), e))
+}
+
+// Snapshots in `dir` by id, oldest first
+fn snapshots(dir: &Path) -> Result<BTreeMap<String, Snapshot>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("Failed to read versions of project: {}", e)),
+    };
+    let mut snapshots = BTreeMap::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+            continue;
+        };
+        match read_snapshot(&path) {
+            Ok(snapshot) => {
+                snapshots.insert(id, snapshot);
+            }
+            Err(e) => warn!("Skipping version: {}", e),
+        }
+    }
+    Ok(snapshots)
+}
+
+// The document of a snapshot, assets read back from the folder
+fn document_of(dir: &Path, snapshot: &Snapshot) -> Result<Document, String> {
+    let mut project = project::parse(&snapshot.project.to_string())?;
+    for (id, asset) in project.document.assets.iter_mut() {
+        let blob = snapshot
+            .blobs
+            .get(id)
+            .ok_or_else(|| format!("Version is missing asset {}", asset.file_name))?;
+        let bytes = fs::read(blob_path(dir, blob)?)
+            .map_err(|e| format!("Version is missing asset {}: {}", asset.file_name, e))?;
+        asset.data = BASE64.encode(bytes);
+    }
+    Ok(project.document)
+}
+
+fn load_version(project_path: &Path, id: &str) -> Result<Document, String> {
+    let dir = versions_dir(project_path);
+    document_of(&dir, &read_snapshot(&snapshot_path(&dir, id)?)?)
+}
+
+// The document as it's compared: assets by id only
+fn comparable(document: &Document) -> Result<Value, String> {
+    let mut document = document.clone();
+    document
+        .assets
+        .values_mut()
+        .for_each(|asset: &mut Asset| asset.data.clear());
+    serde_json::to_value(&document).map_err(|e| format!("Failed to serialize document: {}", e))
+}
+
+/// Snapshot `document` in the versions folder of `project_path`
+pub fn snapshot(
+    project_path: &Path,
+    document: &Document,
+    label: Option<String>,
+    automatic: bool,
+) -> Result<DocumentVersion, String> {
+    let dir = versions_dir(project_path);
+    let assets_dir = dir.join(ASSETS_DIR);
+    fs::create_dir_all(&assets_dir).map_err(|e| format!("Failed to create {}: {}", assets_dir.display(), e))?;
+    let mut blobs = BTreeMap::new();
+    for (id, asset) in &document.assets {
+        let bytes = asset.bytes()?;
+        let blob = document::asset_id(&bytes);
+        let path = blob_path(&dir, &blob)?;
+        if !path.exists() {
+            fs::write(&path, bytes).map_err(|e| format!("Failed to write asset {}: {}", asset.file_name, e))?;
+        }
+        blobs.insert(id.clone(), blob);
+    }
+
+    let now = Utc::now();
+    let mut id = now.format("%Y%m%dT%H%M%S%3fZ").to_string();
+    // Two snapshots within a millisecond
+    while dir.join(format!("{}.json", id)).exists() {
+        id.push('-');
+    }
+    let mut manifest = Project::new(document.clone());
+    manifest
+        .document
+        .assets
+        .values_mut()
+        .for_each(|asset| asset.data.clear());
+    let snapshot = Snapshot {
+        created_at: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+        label: label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty()),
+        automatic,
+        project: serde_json::to_value(&manifest).map_err(|e| format!("Failed to serialize project: {}", e))?,
+        blobs,
+    };
+    let json = serde_json::to_string_pretty(&snapshot).map_err(|e| format!("Failed to serialize version: {}", e))?;
+    fs::write(dir.join(format!("{}.json", id)), json).map_err(|e| format!("Failed to write version: {}", e))?;
+    info!("Saved version {} of {}", id, project_path.display());
+    if automatic {
+        prune(&dir)?;
+    }
+    Ok(listed(id, &snapshot, document))
+}
+
+fn listed(id: String, snapshot: &Snapshot, document: &Document) -> DocumentVersion {
+    DocumentVersion {
+        id,
+        created_at: snapshot.created_at.clone(),
+        label: snapshot.label.clone(),
+        automatic: snapshot.automatic,
+        width_mm: document.width_mm,
+        height_mm: document.height_mm,
+        elements: document.elements.len(),
+    }
+}
+
+/// Snapshot the project saved at `project_path` before it's overwritten by
+/// `document`, unless there's nothing there or nothing changed
+pub fn before_save(project_path: &Path, document: &Document) -> Result<(), String> {
+    if !project_path.exists() {
+        return Ok(());
+    }
+    let saved = project::load(project_path)?.document;
+    if comparable(&saved)? == comparable(document)? {
+        return Ok(());
+    }
+    snapshot(project_path, &saved, None, true).map(|_| ())
+}
+
+// Drop the oldest automatic snapshots beyond `MAX_AUTOMATIC`, then assets no
+// snapshot uses
+fn prune(dir: &Path) -> Result<(), String> {
+    let snapshots = snapshots(dir)?;
+    let automatic: Vec<&String> = snapshots
+        .iter()
+        .filter(|(_, snapshot)| snapshot.automatic)
+        .map(|(id, _)| id)
+        .collect();
+    let excess = automatic.len().saturating_sub(MAX_AUTOMATIC);
+    for id in &automatic[..excess] {
+        if let Err(e) = fs::remove_file(dir.join(format!("{}.json", id))) {
+            warn!("Failed to remove old version {}: {}", id, e);
+        }
+    }
+    let dropped: BTreeSet<&String> = automatic[..excess].iter().copied().collect();
+    let used: BTreeSet<String> = snapshots
+        .iter()
+        .filter(|(id, _)| !dropped.contains(id))
+        .flat_map(|(_, snapshot)| snapshot.blobs.values().cloned())
+        .collect();
+    remove_unused_assets(dir, &used);
+    Ok(())
+}
+
+fn remove_unused_assets(dir: &Path, used: &BTreeSet<String>) {
+    let Ok(entries) = fs::read_dir(dir.join(ASSETS_DIR)) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !used.contains(&name) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+// `png` decoded for comparing
+fn decode(png: &[u8]) -> Result<RgbaImage, String> {
+    Ok(image::load_from_memory(png)
+        .map_err(|e| format!("Failed to decode thumbnail: {}", e))?
+        .to_rgba8())
+}
+
+fn data_url(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", BASE64.encode(png))
+}
+
+// `after` faded with the pixels that differ from `before` in red, and the
+// share that differ
+fn diff_image(before: &RgbaImage, after: &RgbaImage) -> Result<(Vec<u8>, f64), String> {
+    let mut diff = RgbaImage::new(after.width(), after.height());
+    let mut changed = 0usize;
+    for ((a, b), out) in after.pixels().zip(before.pixels()).zip(diff.pixels_mut()) {
+        if a.0.iter().zip(b.0).any(|(x, y)| x.abs_diff(y) > DIFF_TOLERANCE) {
+            changed += 1;
+            *out = Rgba([229, 57, 53, 255]);
+        } else {
+            let fade = |c: u8| 255 - (255 - c) / 4;
+            *out = Rgba([fade(a[0]), fade(a[1]), fade(a[2]), 255]);
+        }
+    }
+    let mut png = Vec::new();
+    diff.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode diff: {}", e))?;
+    let total = (after.width() as usize * after.height() as usize).max(1);
+    Ok((png, changed as f64 * 100.0 / total as f64))
+}
+
+// Ids of elements added, removed and changed from `before` to `after`
+fn element_changes(before: &Document, after: &Document) -> (Vec<u32>, Vec<u32>, Vec<u32>) {
+    let index = |document: &Document| -> BTreeMap<u32, Value> {
+        document
+            .elements
+            .iter()
+            .map(|element| (element.id, serde_json::to_value(element).unwrap_or_default()))
+            .collect()
+    };
+    let (before, after) = (index(before), index(after));
+    let added = after.keys().filter(|id| !before.contains_key(id)).copied().collect();
+    let removed = before.keys().filter(|id| !after.contains_key(id)).copied().collect();
+    let changed = after
+        .iter()
+        .filter(|(id, element)| before.get(id).is_some_and(|old| old != *element))
+        .map(|(id, _)| *id)
+        .collect();
+    (added, removed, changed)
+}
+
+/// Snapshot `document`, or the project as saved when none is given, with an
+/// optional `label`
+#[tauri::command]
+pub async fn save_version(
+    project_path: String,
+    document: Option<Document>,
+    label: Option<String>,
+) -> Result<DocumentVersion, String> {
+    let path = Path::new(&project_path);
+    let document = match document {
+        Some(document) => document,
+        None => project::load(path)?.document,
+    };
+    snapshot(path, &document, label, false)
+}
+
+/// Snapshots of the project at `project_path`, newest first
+#[tauri::command]
+pub async fn list_versions(project_path: String) -> Result<Vec<DocumentVersion>, String> {
+    let dir = versions_dir(Path::new(&project_path));
+    let mut versions = Vec::new();
+    for (id, snapshot) in snapshots(&dir)?.into_iter().rev() {
+        let document: Document = match project::parse(&snapshot.project.to_string()) {
+            Ok(project) => project.document,
+            Err(e) => {
+                warn!("Skipping version {}: {}", id, e);
+                continue;
+            }
+        };
+        versions.push(listed(id, &snapshot, &document));
+    }
+    Ok(versions)
+}
+
+/// PNG data URL previewing version `version`, at most `size` pixels on its
+/// longest side
+#[tauri::command]
+pub async fn get_version_thumbnail(
+    thumbnails: State<'_, Thumbnails>,
+    fonts: State<'_, FontLibrary>,
+    project_path: String,
+    version: String,
+    size: Option<u32>,
+) -> Result<String, String> {
+    let path = Path::new(&project_path);
+    let dir = versions_dir(path);
+    // Snapshots never change, so where one is says what it shows
+    let key = format!("version\0{}\0{}", snapshot_path(&dir, &version)?.display(), version);
+    thumbnails.get(key.as_bytes(), thumbnails::thumbnail_size(size), &fonts, || {
+        load_version(path, &version)
+    })
+}
+
+/// Version `before` beside version `after`, or the project as saved when no
+/// `after` is given, rendered at most `size` pixels on the longest side with
+/// what changed
+#[tauri::command]
+pub async fn compare_versions(
+    fonts: State<'_, FontLibrary>,
+    project_path: String,
+    before: String,
+    after: Option<String>,
+    size: Option<u32>,
+) -> Result<VersionComparison, String> {
+    let path = Path::new(&project_path);
+    let old = load_version(path, &before)?;
+    let new = match after {
+        Some(after) => load_version(path, &after)?,
+        None => project::load(path)?.document,
+    };
+    let longest_mm = old.width_mm.max(old.height_mm).max(new.width_mm).max(new.height_mm);
+    if longest_mm <= 0.0 {
+        return Err("Can't compare labels without a size".to_string());
+    }
+    // The same scale for both, so pixels line up
+    let dpi = thumbnails::thumbnail_size(size) as f64 / longest_mm * 25.4;
+    let old_png = render::render_png(&old, fonts.database(), dpi)?;
+    let new_png = render::render_png(&new, fonts.database(), dpi)?;
+    let (old_image, new_image) = (decode(&old_png)?, decode(&new_png)?);
+    let (diff, changed_percent) = if old_image.dimensions() == new_image.dimensions() {
+        let (png, percent) = diff_image(&old_image, &new_image)?;
+        (Some(data_url(&png)), percent)
+    } else {
+        (None, 100.0)
+    };
+    let (added, removed, changed) = element_changes(&old, &new);
+    Ok(VersionComparison {
+        before: data_url(&old_png),
+        after: data_url(&new_png),
+        diff,
+        changed_percent,
+        added,
+        removed,
+        changed,
+    })
+}
+
+/// Save version `version` as the project at `project_path`, snapshotting what
+/// was saved first, and return its document
+#[tauri::command]
+pub async fn restore_version(project_path: String, version: String) -> Result<Document, String> {
+    let path = Path::new(&project_path);
+    let document = load_version(path, &version)?;
+    before_save(path, &document)?;
+    project::save(&Project::new(document.clone()), path)?;
+    info!("Restored {} to version {}", path.display(), version);
+    Ok(document)
+}
+
+/// Delete version `version` of the project at `project_path`
+#[tauri::command]
+pub async fn delete_version(project_path: String, version: String) -> Result<(), String> {
+    let dir = versions_dir(Path::new(&project_path));
+    let path = snapshot_path(&dir, &version)?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete version {}: {}", version, e))?;
+    let used: BTreeSet<String> = snapshots(&dir)?
+        .values()
+        .flat_map(|snapshot| snapshot.blobs.values().cloned())
+        .collect();
+    remove_unused_assets(&dir, &used);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+// "Virtual Printer (PDF)": a destination that goes through the same queue,
+// progress and history as a real printer but saves what would have been
+// spooled instead of using up label stock. Each job leaves the PDF and its
+// pages as a 300 DPI print head would lay them down, scaled and dithered
+// like `preview_print_output` does, so size and fine detail can be checked
+// before a real run:
+//
+//   20261015-142301-120.pdf
+//   20261015-142301-120-page-1.png
+//   20261015-142301-120-page-2.png
+use crate::error::LabelgoodError;
+use crate::i18n;
+use crate::portable;
+use crate::printing::{self, RENDER_DPI};
+use crate::raster;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tracing::info;
+
+/// Printer name of the virtual printer, listed with the system printers
+pub const VIRTUAL_PRINTER: &str = "Virtual Printer (PDF)";
+
+static OUTPUT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Save virtual printer jobs to `dir`, or to "virtual-printer" in the app
+/// data directory when none is set
+pub fn configure(app: &AppHandle, dir: Option<&str>) {
+    let dir = match dir {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => portable::app_data_dir(app).ok().map(|d| d.join("virtual-printer")),
+    };
+    *OUTPUT_DIR.lock().unwrap() = dir;
+}
+
+// The configured directory; the CLI, which doesn't load settings, uses the
+// temp directory
+fn output_dir() -> PathBuf {
+    OUTPUT_DIR
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("labelgood-virtual-printer"))
+}
+
+/// "Print" a PDF by saving it and its rasterized pages. Returns a status
+/// message naming the saved PDF.
+pub async fn print(pdf_path: &Path, width_mm: f64, height_mm: f64) -> Result<String, LabelgoodError> {
+    let io_err = |e: std::io::Error| LabelgoodError::Io {
+        message: format!("Failed to save virtual print: {}", e),
+    };
+    let dir = output_dir();
+    fs::create_dir_all(&dir).map_err(io_err)?;
+    let stem = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+
+    let pages = printing::rasterize_pdf(pdf_path, None).await?;
+    for (index, page) in pages.iter().enumerate() {
+        let png = raster::encode_png(&raster::print_raster(page, RENDER_DPI)?)?;
+        fs::write(dir.join(format!("{}-page-{}.png", stem, index + 1)), png).map_err(io_err)?;
+    }
+    let target = dir.join(format!("{}.pdf", stem));
+    fs::copy(pdf_path, &target).map_err(io_err)?;
+
+    info!(
+        "Virtual printer saved {} page(s) of {}x{}mm to {}",
+        pages.len(),
+        width_mm,
+        height_mm,
+        target.display()
+    );
+    Ok(i18n::t("virtual-print-saved", &[("path", target.display().to_string().into())]))
+}
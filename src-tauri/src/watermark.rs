@@ -0,0 +1,110 @@
+// Proof and test print stamps: a word like "SAMPLE" or "VOID" across every
+// label and, optionally, an image such as a logo at a fixed spot. Applied
+// last, on top of the design, to labels rendered by the backend and to the
+// label images the frontend sends, so nothing printed while it's on can be
+// mistaken for a real label.
+use crate::formats::{self, escape};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    pub enabled: bool,
+    /// Written diagonally across the label; nothing when empty
+    pub text: String,
+    pub color: String,
+    /// 0 is invisible, 1 fully covers what's underneath
+    pub opacity: f64,
+    pub image: Option<StampImage>,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            enabled: false,
+            text: "SAMPLE".to_string(),
+            color: "#ff0000".to_string(),
+            opacity: 0.35,
+            image: None,
+        }
+    }
+}
+
+/// A PNG, JPEG or SVG file placed at the same spot on every label
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StampImage {
+    pub path: String,
+    pub x_mm: f64,
+    pub y_mm: f64,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+// The settings with the image read into a data URL, so rendering never
+// touches the disk
+struct Stamp {
+    watermark: Watermark,
+    image_url: Option<String>,
+}
+
+static STAMP: Mutex<Option<Stamp>> = Mutex::new(None);
+
+/// Stamp labels with `watermark` when it's enabled. An image that can't be
+/// read is left out, so the text still marks the labels.
+pub fn configure(watermark: Watermark) {
+    if !watermark.enabled {
+        *STAMP.lock().unwrap() = None;
+        return;
+    }
+    let image_url = watermark.image.as_ref().and_then(|image| match fs::read(&image.path) {
+        Ok(bytes) => Some(format!(
+            "data:{};base64,{}",
+            formats::mime_type_for(&image.path),
+            BASE64.encode(bytes)
+        )),
+        Err(e) => {
+            warn!("Failed to read watermark image {}: {}", image.path, e);
+            None
+        }
+    });
+    *STAMP.lock().unwrap() = Some(Stamp { watermark, image_url });
+}
+
+/// SVG drawing the stamp over a `width_mm` x `height_mm` label in
+/// millimetre user units; None when stamping is off
+pub fn overlay_svg(width_mm: f64, height_mm: f64) -> Option<String> {
+    let stamp = STAMP.lock().unwrap();
+    let Stamp { watermark, image_url } = stamp.as_ref()?;
+    let mut svg = format!("<g opacity=\"{}\">\n", watermark.opacity.clamp(0.0, 1.0));
+    if let (Some(image), Some(url)) = (&watermark.image, image_url) {
+        svg.push_str(&format!(
+            "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"{}\"/>\n",
+            image.x_mm, image.y_mm, image.width_mm, image.height_mm, url
+        ));
+    }
+    let text = watermark.text.trim();
+    if !text.is_empty() {
+        // Along the diagonal from bottom left to top right, as large as fits
+        let diagonal = width_mm.hypot(height_mm);
+        let angle = -(height_mm / width_mm).atan().to_degrees();
+        let size = (diagonal * 0.8 / (text.chars().count() as f64 * 0.6)).min(height_mm.min(width_mm) * 0.5);
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" transform=\"rotate({} {} {})\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+            width_mm / 2.0,
+            height_mm / 2.0,
+            angle,
+            width_mm / 2.0,
+            height_mm / 2.0,
+            size,
+            escape(&watermark.color),
+            escape(text)
+        ));
+    }
+    svg.push_str("</g>\n");
+    Some(svg)
+}
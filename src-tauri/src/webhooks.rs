@@ -0,0 +1,115 @@
+// Webhooks called when a print job completes or fails, so a warehouse
+// management system can mark orders as labelled without polling
+use crate::history::JobDetails;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{error, warn};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Which outcomes to send; both when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    JobCompleted,
+    JobFailed,
+}
+
+/// JSON body POSTed to each webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct JobNotification {
+    pub event: WebhookEvent,
+    pub job: u64,
+    /// UTC, RFC 3339
+    pub finished_at: String,
+    pub source: String,
+    pub template: Option<String>,
+    pub printer: Option<String>,
+    pub labels: usize,
+    /// Status message on success, the error otherwise
+    pub message: String,
+    /// One record per label, e.g. to find the order a label was for
+    pub data: Vec<BTreeMap<String, String>>,
+}
+
+impl JobNotification {
+    pub fn new(
+        job: u64,
+        source: &str,
+        labels: usize,
+        printer: Option<&str>,
+        details: &JobDetails,
+        result: &Result<String, String>,
+    ) -> Self {
+        let (event, message) = match result {
+            Ok(message) => (WebhookEvent::JobCompleted, message.clone()),
+            Err(error) => (WebhookEvent::JobFailed, error.clone()),
+        };
+        JobNotification {
+            event,
+            job,
+            finished_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            source: source.to_string(),
+            template: details.template.clone(),
+            printer: printer.map(str::to_string),
+            labels,
+            message,
+            data: details.data.clone(),
+        }
+    }
+}
+
+/// POST `notification` to every webhook subscribed to its event, in the
+/// background. Each is retried a few times, since a WMS being briefly
+/// unreachable shouldn't lose the update.
+pub fn notify(webhooks: Vec<Webhook>, notification: JobNotification) {
+    let webhooks: Vec<Webhook> = webhooks
+        .into_iter()
+        .filter(|w| w.events.is_empty() || w.events.contains(&notification.event))
+        .collect();
+    if webhooks.is_empty() {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+        for webhook in &webhooks {
+            for attempt in 1..=ATTEMPTS {
+                match send(&agent, webhook, &notification) {
+                    Ok(()) => break,
+                    Err(e) if attempt < ATTEMPTS => {
+                        warn!("Webhook {} failed (attempt {}): {}", webhook.url, attempt, e);
+                        std::thread::sleep(RETRY_DELAY * attempt);
+                    }
+                    Err(e) => error!("Webhook {} failed, giving up: {}", webhook.url, e),
+                }
+            }
+        }
+    });
+}
+
+fn send(agent: &ureq::Agent, webhook: &Webhook, notification: &JobNotification) -> Result<(), String> {
+    let mut request = agent.post(&webhook.url);
+    for (name, value) in &webhook.headers {
+        request = request.header(name, value);
+    }
+    request.send_json(notification).map_err(|e| e.to_string())?;
+    Ok(())
+}
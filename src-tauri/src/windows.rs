@@ -0,0 +1,287 @@
+// Label documents open in windows of their own. Each window tells the
+// backend which file it has open and whether it has unsaved changes; the
+// backend saves a recovery copy of unsaved documents every minute, and keeps
+// a window that's closed with unsaved changes open until the frontend has
+// asked what to do with them. Recovery copies left by a crash are offered
+// again on the next start.
+use crate::document::Document;
+use crate::project::{self, Project, PROJECT_EXTENSION};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder, Window, WindowEvent};
+use tracing::{error, warn};
+
+/// Labels of windows opened by `open_document_window` start with this
+pub const DOCUMENT_WINDOW_PREFIX: &str = "document-";
+/// Emitted to a window closed with unsaved changes; it stays open until the
+/// frontend calls `close_window`
+pub const CLOSE_REQUESTED_EVENT: &str = "close-requested";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DocumentState {
+    /// File the document was opened from or last saved to
+    pub path: Option<String>,
+    /// Whether it has changes that aren't saved to `path`
+    pub dirty: bool,
+    /// Latest content of a dirty document, for recovery copies
+    #[serde(skip)]
+    document: Option<Document>,
+    /// Whether `document` changed since its last recovery copy
+    #[serde(skip)]
+    unsaved_copy: bool,
+}
+
+pub struct WindowManager {
+    windows: Mutex<BTreeMap<String, DocumentState>>,
+    next_window: AtomicU32,
+    autosave_dir: PathBuf,
+    /// Start time of this run, prefixed to its recovery copies so they can be
+    /// told from those of earlier runs
+    session: String,
+}
+
+impl WindowManager {
+    pub fn new(autosave_dir: PathBuf) -> Self {
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default()
+            .to_string();
+        WindowManager {
+            windows: Mutex::new(BTreeMap::new()),
+            next_window: AtomicU32::new(1),
+            autosave_dir,
+            session,
+        }
+    }
+
+    fn autosave_path(&self, label: &str) -> PathBuf {
+        self.autosave_dir
+            .join(format!("{}-{}.{}", self.session, label, PROJECT_EXTENSION))
+    }
+
+    fn is_dirty(&self, label: &str) -> bool {
+        self.windows.lock().unwrap().get(label).is_some_and(|state| state.dirty)
+    }
+
+    // Forget a closed window along with its recovery copy
+    fn remove(&self, label: &str) {
+        self.windows.lock().unwrap().remove(label);
+        remove_file(&self.autosave_path(label));
+    }
+
+    // Save a recovery copy of every dirty document changed since its last one
+    fn autosave(&self) {
+        let pending: Vec<(String, Document)> = self
+            .windows
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|(_, state)| state.dirty && state.unsaved_copy)
+            .filter_map(|(label, state)| {
+                state.unsaved_copy = false;
+                Some((label.clone(), state.document.clone()?))
+            })
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        if let Err(e) = fs::create_dir_all(&self.autosave_dir) {
+            error!("Failed to create autosave directory: {}", e);
+            return;
+        }
+        for (label, document) in pending {
+            if let Err(e) = project::save(&Project::new(document), &self.autosave_path(&label)) {
+                error!("Failed to autosave {}: {}", label, e);
+            }
+        }
+    }
+}
+
+fn remove_file(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Save recovery copies of unsaved documents every `AUTOSAVE_INTERVAL`
+pub fn start_autosave(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(AUTOSAVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let manager = app.state::<WindowManager>();
+            manager.autosave();
+        }
+    });
+}
+
+/// Hold back closing a window with unsaved changes, and forget closed ones
+pub fn handle_event(window: &Window, event: &WindowEvent) {
+    let manager = window.state::<WindowManager>();
+    match event {
+        WindowEvent::CloseRequested { api, .. } if manager.is_dirty(window.label()) => {
+            api.prevent_close();
+            if let Err(e) = window.emit(CLOSE_REQUESTED_EVENT, ()) {
+                error!("Failed to emit {}: {}", CLOSE_REQUESTED_EVENT, e);
+            }
+        }
+        WindowEvent::Destroyed => manager.remove(window.label()),
+        _ => {}
+    }
+}
+
+/// Open a new document window, empty or for the label file at `path`; a
+/// file already open is brought to the front instead. Returns the window
+/// label. The window finds its file with `get_document_state`.
+#[tauri::command]
+pub async fn open_document_window(
+    app: AppHandle,
+    manager: State<'_, WindowManager>,
+    path: Option<String>,
+) -> Result<String, String> {
+    if let Some(path) = &path {
+        let open = manager
+            .windows
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, state)| state.path.as_ref() == Some(path))
+            .map(|(label, _)| label.clone());
+        if let Some(window) = open.and_then(|label| app.get_webview_window(&label)) {
+            window
+                .set_focus()
+                .map_err(|e| format!("Failed to focus window: {}", e))?;
+            return Ok(window.label().to_string());
+        }
+    }
+
+    let label = format!(
+        "{}{}",
+        DOCUMENT_WINDOW_PREFIX,
+        manager.next_window.fetch_add(1, Ordering::Relaxed)
+    );
+    manager.windows.lock().unwrap().insert(
+        label.clone(),
+        DocumentState {
+            path: path.clone(),
+            ..DocumentState::default()
+        },
+    );
+    let built = WebviewWindowBuilder::new(&app, &label, WebviewUrl::App("index.html".into()))
+        .title(window_title(path.as_deref(), false))
+        .inner_size(800.0, 600.0)
+        .build();
+    if let Err(e) = built {
+        manager.windows.lock().unwrap().remove(&label);
+        return Err(format!("Failed to open window: {}", e));
+    }
+    Ok(label)
+}
+
+fn window_title(path: Option<&str>, dirty: bool) -> String {
+    let name = path
+        .and_then(|p| Path::new(p).file_name())
+        .map_or("Untitled".to_string(), |n| n.to_string_lossy().to_string());
+    format!("{}{} - Labelgood", if dirty { "• " } else { "" }, name)
+}
+
+/// The file and unsaved state the backend has for the calling window
+#[tauri::command]
+pub fn get_document_state(window: Window, manager: State<'_, WindowManager>) -> DocumentState {
+    manager
+        .windows
+        .lock()
+        .unwrap()
+        .get(window.label())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Record the calling window's file and whether it has unsaved changes,
+/// with the current `document` while it has, for recovery copies
+#[tauri::command]
+pub fn set_document_state(
+    window: Window,
+    manager: State<'_, WindowManager>,
+    path: Option<String>,
+    dirty: bool,
+    document: Option<Document>,
+) -> Result<(), String> {
+    window
+        .set_title(&window_title(path.as_deref(), dirty))
+        .map_err(|e| format!("Failed to set window title: {}", e))?;
+    let mut windows = manager.windows.lock().unwrap();
+    let state = windows.entry(window.label().to_string()).or_default();
+    state.path = path;
+    state.dirty = dirty;
+    state.unsaved_copy = dirty && document.is_some();
+    state.document = if dirty { document } else { None };
+    drop(windows);
+    if !dirty {
+        remove_file(&manager.autosave_path(window.label()));
+    }
+    Ok(())
+}
+
+/// Every open window's document, by window label
+#[tauri::command]
+pub fn list_document_windows(manager: State<'_, WindowManager>) -> BTreeMap<String, DocumentState> {
+    manager.windows.lock().unwrap().clone()
+}
+
+/// Close the calling window, discarding unsaved changes; called once the
+/// user has answered the prompt for `CLOSE_REQUESTED_EVENT`
+#[tauri::command]
+pub fn close_window(window: Window, manager: State<'_, WindowManager>) -> Result<(), String> {
+    manager.remove(window.label());
+    window.close().map_err(|e| format!("Failed to close window: {}", e))
+}
+
+/// Recovery copies of documents left unsaved by an earlier run that ended
+/// without closing them, newest first; open them with `open_project`
+#[tauri::command]
+pub fn list_autosaves(manager: State<'_, WindowManager>) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(&manager.autosave_dir) else {
+        return Vec::new();
+    };
+    let current = format!("{}-", manager.session);
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            !name.starts_with(&current) && name.ends_with(&format!(".{}", PROJECT_EXTENSION))
+        })
+        .map(|path| {
+            (
+                fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH),
+                path,
+            )
+        })
+        .collect();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    files
+        .into_iter()
+        .map(|(_, path)| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Delete a recovery copy from `list_autosaves` once it's been restored or
+/// turned down
+#[tauri::command]
+pub fn discard_autosave(manager: State<'_, WindowManager>, path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if path.parent() != Some(manager.autosave_dir.as_path()) {
+        return Err(format!("Not a recovery copy: {}", path.display()));
+    }
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
@@ -0,0 +1,70 @@
+// Previews of ZPL before it goes to a Zebra. By default the ZPL is
+// interpreted locally (see `formats::zpl`) and rasterized like a print job.
+// With the Labelary setting on, the Labelary web service renders it instead:
+// Zebra's own fonts and every command, at the cost of sending the ZPL off
+// this computer.
+use crate::fonts::FontLibrary;
+use crate::formats::zpl;
+use crate::printing::RENDER_DPI;
+use crate::raster::{self, PrintRaster};
+use crate::render;
+use crate::settings::SettingsStore;
+use image::{GrayImage, ImageFormat};
+use std::time::Duration;
+use tauri::State;
+
+const LABELARY_URL: &str = "https://api.labelary.com/v1/printers";
+const LABELARY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Largest label side Labelary renders, in inches
+const LABELARY_MAX_INCHES: f64 = 15.0;
+
+fn labelary(zpl: &str, dpmm: f64, width_mm: f64, height_mm: f64) -> Result<GrayImage, String> {
+    let inches = |mm: f64| (mm / 25.4).min(LABELARY_MAX_INCHES);
+    let url = format!(
+        "{}/{}dpmm/labels/{:.2}x{:.2}/0/",
+        LABELARY_URL,
+        dpmm,
+        inches(width_mm),
+        inches(height_mm)
+    );
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(LABELARY_TIMEOUT))
+        .build()
+        .into();
+    let mut response = agent
+        .post(&url)
+        .header("Accept", "image/png")
+        .send(zpl)
+        .map_err(|e| format!("Failed to render ZPL with Labelary: {}", e))?;
+    let png = response
+        .body_mut()
+        .read_to_vec()
+        .map_err(|e| format!("Failed to read the Labelary preview: {}", e))?;
+    Ok(image::load_from_memory_with_format(&png, ImageFormat::Png)
+        .map_err(|e| format!("Failed to decode the Labelary preview: {}", e))?
+        .to_luma8())
+}
+
+/// The first label of `zpl` as a printer with `dpmm` dots per millimetre
+/// would print it; 8 (203 dpi) when not given
+#[tauri::command]
+pub async fn preview_zpl(
+    settings: State<'_, SettingsStore>,
+    fonts: State<'_, FontLibrary>,
+    zpl: String,
+    dpmm: Option<f64>,
+) -> Result<PrintRaster, String> {
+    let dpmm = dpmm.unwrap_or(zpl::DEFAULT_DPMM);
+    let document = zpl::parse(&zpl, dpmm)?;
+    let dpi = dpmm * 25.4;
+    let image = if settings.get().labelary_preview {
+        let (width_mm, height_mm) = (document.width_mm, document.height_mm);
+        tauri::async_runtime::spawn_blocking(move || labelary(&zpl, dpmm, width_mm, height_mm))
+            .await
+            .map_err(|e| format!("Failed to render ZPL with Labelary: {}", e))??
+    } else {
+        let png = render::render_png(&document, fonts.database(), RENDER_DPI)?;
+        raster::print_raster(&png, dpi)?
+    };
+    raster::to_preview(&image, dpi)
+}
@@ -0,0 +1,203 @@
+// End-to-end checks of the render and print pipeline, through the CLI and
+// the steps the app's Print button takes, with jobs captured by the mock
+// printer driver instead of reaching a printer. Guards the mm -> points ->
+// pixels conversions every label depends on. Without ImageMagick the PDFs
+// come from the built-in writer instead. Needs the `mock-printer` feature:
+//
+//   cargo test --features mock-printer
+use labelgood_lib::testing::{self, RENDER_DPI};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+// One scratch directory for the whole run, since the mock driver and the
+// CLI's data directory are set once per process
+fn scratch() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("labelgood-tests-{}", std::process::id()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        testing::use_scratch_dir(&dir);
+        dir
+    })
+}
+
+fn write_project(name: &str, width_mm: f64, height_mm: f64) -> PathBuf {
+    let path = scratch().join(format!("{}.lblg", name));
+    let project = serde_json::json!({
+        "format_version": 2,
+        "document": { "width_mm": width_mm, "height_mm": height_mm },
+    });
+    fs::write(&path, project.to_string()).unwrap();
+    path
+}
+
+fn cli(args: &[&str]) -> i32 {
+    let mut argv = vec!["labelgood".to_string()];
+    argv.extend(args.iter().map(|a| a.to_string()));
+    labelgood_lib::cli::run(&argv)
+}
+
+// Page size in points from the first /MediaBox of a PDF
+fn media_box(pdf: &[u8]) -> (f64, f64) {
+    let text = String::from_utf8_lossy(pdf);
+    let start = text.find("/MediaBox").expect("PDF has no MediaBox");
+    let open = start + text[start..].find('[').unwrap() + 1;
+    let close = open + text[open..].find(']').unwrap();
+    let numbers: Vec<f64> = text[open..close].split_whitespace().map(|n| n.parse().unwrap()).collect();
+    (numbers[2] - numbers[0], numbers[3] - numbers[1])
+}
+
+// Pixel size from a PNG's IHDR chunk
+fn png_size(png: &[u8]) -> (u32, u32) {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n", "not a PNG");
+    let be = |at: usize| u32::from_be_bytes(png[at..at + 4].try_into().unwrap());
+    (be(16), be(20))
+}
+
+fn page_count(pdf: &[u8]) -> usize {
+    pdf.windows(12)
+        .filter(|w| w.starts_with(b"/Type /Page") && w[11] != b's')
+        .count()
+}
+
+fn assert_page_size(pdf: &[u8], width_mm: f64, height_mm: f64) {
+    let (width_pt, height_pt) = media_box(pdf);
+    // ImageMagick rounds the page size to whole points
+    assert!((width_pt - width_mm * POINTS_PER_MM).abs() <= 1.0, "width {}pt for {}mm", width_pt, width_mm);
+    assert!((height_pt - height_mm * POINTS_PER_MM).abs() <= 1.0, "height {}pt for {}mm", height_pt, height_mm);
+}
+
+fn captured(printer: &str, job: &str) -> (Value, Vec<u8>) {
+    let dir = scratch().join("printed").join(printer);
+    let json = fs::read_to_string(dir.join(format!("{}.json", job))).expect("job was not captured");
+    (serde_json::from_str(&json).unwrap(), fs::read(dir.join(format!("{}.pdf", job))).unwrap())
+}
+
+// The job the mock printer captured for `printer`, the only one printed there
+fn only_job(printer: &str) -> (Value, Vec<u8>) {
+    let dir = scratch().join("printed").join(printer);
+    let jobs: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|e| e.ok()?.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+        .collect();
+    assert_eq!(jobs.len(), 1, "expected one job for {}", printer);
+    captured(printer, &jobs[0])
+}
+
+#[test]
+fn prints_label_at_its_size() {
+    let project = write_project("address", 62.0, 29.0);
+    assert_eq!(cli(&["print", "--template", project.to_str().unwrap(), "--printer", "mock:address"]), 0);
+
+    let (job, pdf) = only_job("address");
+    assert_eq!(job["printer"], "address");
+    assert_eq!(job["width_mm"], 62.0);
+    assert_eq!(job["height_mm"], 29.0);
+    assert_eq!(job["bytes"].as_u64(), Some(pdf.len() as u64));
+    assert_eq!(page_count(&pdf), 1);
+    assert_page_size(&pdf, 62.0, 29.0);
+}
+
+#[test]
+fn renders_labels_at_print_resolution() {
+    let px = |mm: f64| (mm / 25.4 * RENDER_DPI).round() as u32;
+    assert_eq!(png_size(&testing::render_blank(62.0, 29.0).unwrap()), (px(62.0), px(29.0)));
+    // 4x6" at 300 DPI
+    assert_eq!(png_size(&testing::render_blank(101.6, 152.4).unwrap()), (1200, 1800));
+}
+
+#[test]
+fn app_prints_label_at_its_size() {
+    let png = scratch().join("app.png");
+    fs::write(&png, testing::render_blank(62.0, 29.0).unwrap()).unwrap();
+    tauri::async_runtime::block_on(testing::print_png(&png, 62.0, 29.0, "mock:app")).unwrap();
+
+    let (job, pdf) = only_job("app");
+    assert_eq!(job["printer"], "app");
+    assert_eq!(job["width_mm"], 62.0);
+    assert_eq!(job["height_mm"], 29.0);
+    assert_eq!(page_count(&pdf), 1);
+    assert_page_size(&pdf, 62.0, 29.0);
+}
+
+#[test]
+fn merge_prints_one_page_per_row() {
+    let project = write_project("shelf", 50.0, 30.0);
+    let data = scratch().join("shelf.csv");
+    fs::write(&data, "name,price\nTea,2.50\nCoffee,4.00\nCocoa,3.25\n").unwrap();
+    let args = [
+        "print",
+        "--template",
+        project.to_str().unwrap(),
+        "--data",
+        data.to_str().unwrap(),
+        "--printer",
+        "mock:shelf",
+    ];
+    assert_eq!(cli(&args), 0);
+
+    let (_, pdf) = only_job("shelf");
+    assert_eq!(page_count(&pdf), 3);
+    assert_page_size(&pdf, 50.0, 30.0);
+}
+
+#[test]
+fn output_pdf_matches_shipping_label_size() {
+    // 4x6", the common shipping label
+    let project = write_project("shipping", 101.6, 152.4);
+    let output = scratch().join("shipping.pdf");
+    let args = ["print", "--template", project.to_str().unwrap(), "--output", output.to_str().unwrap()];
+    assert_eq!(cli(&args), 0);
+
+    let pdf = fs::read(&output).unwrap();
+    assert_page_size(&pdf, 101.6, 152.4);
+    let (width_pt, height_pt) = media_box(&pdf);
+    assert_eq!((width_pt.round(), height_pt.round()), (288.0, 432.0));
+}
+
+#[test]
+fn rejects_impossible_label_sizes() {
+    let zero = write_project("zero", 0.0, 29.0);
+    let output = scratch().join("zero.pdf");
+    assert_eq!(cli(&["print", "--template", zero.to_str().unwrap(), "--output", output.to_str().unwrap()]), 1);
+    assert!(!output.exists());
+}
+
+#[test]
+fn prints_label_definition() {
+    let definition = scratch().join("definition.toml");
+    fs::write(
+        &definition,
+        r#"width = 62
+height = 29
+
+[[element]]
+type = "text"
+x = 2
+y = 2
+width = 58
+height = 8
+text = "Tea"
+font_size = 14
+
+[[element]]
+type = "rectangle"
+x = 1
+y = 1
+width = 60
+height = 27
+"#,
+    )
+    .unwrap();
+    let output = scratch().join("definition.pdf");
+    let args = ["print", "--template", definition.to_str().unwrap(), "--output", output.to_str().unwrap()];
+    assert_eq!(cli(&args), 0);
+
+    let pdf = fs::read(&output).unwrap();
+    assert_eq!(page_count(&pdf), 1);
+    assert_page_size(&pdf, 62.0, 29.0);
+}